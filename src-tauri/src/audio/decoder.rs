@@ -9,10 +9,51 @@ use symphonia::core::io::MediaSourceStream;
 use symphonia::core::meta::MetadataOptions;
 use symphonia::core::probe::Hint;
 use symphonia::core::sample::Sample;
-use symphonia::core::units::Time;
+use symphonia::core::units::{Time, TimeBase};
 use std::fs::File;
 use std::path::Path;
 
+use crate::error::{AppError, ErrorContext};
+use crate::metadata::cue::{split_cue_track_path, CueRange};
+
+/// Default number of consecutive decode errors (with no good packet in between)
+/// before a file is treated as undecodable
+pub const DEFAULT_MAX_CONSECUTIVE_DECODE_ERRORS: u32 = 100;
+
+/// Tracks consecutive decode errors so sparse corruption is skipped
+/// but a hopelessly corrupt file fails instead of spinning forever
+#[derive(Debug, Clone)]
+struct DecodeErrorCounter {
+    consecutive: u32,
+    max_consecutive: u32,
+}
+
+impl DecodeErrorCounter {
+    fn new(max_consecutive: u32) -> Self {
+        Self {
+            consecutive: 0,
+            max_consecutive,
+        }
+    }
+    
+    /// Record a decode error; returns Err once the threshold is reached
+    fn record_error(&mut self, error: &str) -> Result<(), AppError> {
+        self.consecutive += 1;
+        if self.consecutive >= self.max_consecutive {
+            return Err(AppError::Decode(format!(
+                "Too many consecutive decode errors ({}), last: {}",
+                self.consecutive, error
+            )));
+        }
+        Ok(())
+    }
+    
+    /// Any successfully decoded packet resets the count
+    fn record_success(&mut self) {
+        self.consecutive = 0;
+    }
+}
+
 pub struct AudioDecoder {
     format: Box<dyn FormatReader>,
     decoder: Box<dyn Decoder>,
@@ -20,13 +61,22 @@ pub struct AudioDecoder {
     sample_rate: u32,
     channels: usize,
     duration_ms: Option<i64>,
+    decode_errors: DecodeErrorCounter,
+    /// Part of the file a CUE track plays; positions are reported relative to its start
+    range: Option<CueRange>,
+    time_base: Option<TimeBase>,
 }
 
 impl AudioDecoder {
-    /// Open an audio file and prepare for decoding
-    pub fn open(path: &Path) -> Result<Self, String> {
-        let file = File::open(path)
-            .map_err(|e| format!("Failed to open file: {}", e))?;
+    /// Open an audio file and prepare for decoding. A CUE track path ("file#start-end")
+    /// opens the file and plays only that track.
+    pub fn open(path: &Path) -> Result<Self, AppError> {
+        let (path, range) = match path.to_str().map(split_cue_track_path) {
+            Some((file, range)) => (Path::new(file), range),
+            None => (path, None),
+        };
+        let file = File::open(crate::path_utils::to_extended_length(path))
+            .context("Failed to open file")?;
         
         let mss = MediaSourceStream::new(Box::new(file), Default::default());
         
@@ -39,7 +89,7 @@ impl AudioDecoder {
         // Probe the media source
         let probed = symphonia::default::get_probe()
             .format(&hint, mss, &FormatOptions::default(), &MetadataOptions::default())
-            .map_err(|e| format!("Failed to probe file format: {}", e))?;
+            .map_err(|e| AppError::Decode(format!("Failed to probe file format: {}", e)))?;
         
         let format = probed.format;
         
@@ -47,30 +97,48 @@ impl AudioDecoder {
         let track = format.tracks()
             .iter()
             .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
-            .ok_or_else(|| "No audio track found".to_string())?;
+            .ok_or_else(|| AppError::Decode("No audio track found".to_string()))?;
         
         let track_id = track.id;
         let sample_rate = track.codec_params.sample_rate.unwrap_or(44100);
         let channels = track.codec_params.channels.map(|c| c.count()).unwrap_or(2);
+        let time_base = track.codec_params.time_base;
         
         // Calculate duration in milliseconds
-        let duration_ms = track.codec_params.n_frames.map(|frames| {
+        let file_duration_ms = track.codec_params.n_frames.map(|frames| {
             (frames as f64 / sample_rate as f64 * 1000.0) as i64
         });
+        let duration_ms = match range {
+            Some(CueRange { start_ms, end_ms: Some(end_ms) }) => Some(end_ms - start_ms),
+            Some(CueRange { start_ms, end_ms: None }) => file_duration_ms.map(|d| (d - start_ms).max(0)),
+            None => file_duration_ms,
+        };
         
         // Create decoder for the track
         let decoder = symphonia::default::get_codecs()
             .make(&track.codec_params, &DecoderOptions::default())
-            .map_err(|e| format!("Failed to create decoder: {}", e))?;
+            .map_err(|e| AppError::Decode(format!("Failed to create decoder: {}", e)))?;
         
-        Ok(Self {
+        let mut decoder = Self {
             format,
             decoder,
             track_id,
             sample_rate,
             channels,
             duration_ms,
-        })
+            decode_errors: DecodeErrorCounter::new(DEFAULT_MAX_CONSECUTIVE_DECODE_ERRORS),
+            range,
+            time_base,
+        };
+        if range.is_some_and(|r| r.start_ms > 0) {
+            decoder.seek(0)?;
+        }
+        Ok(decoder)
+    }
+    
+    /// Set how many consecutive decode errors are tolerated before decode_next fails
+    pub fn set_max_consecutive_errors(&mut self, max_errors: u32) {
+        self.decode_errors = DecodeErrorCounter::new(max_errors.max(1));
     }
     
     /// Get the sample rate of the audio
@@ -90,7 +158,7 @@ impl AudioDecoder {
     
     /// Decode next packet, returns interleaved f32 samples
     /// Returns None when end of stream is reached
-    pub fn decode_next(&mut self) -> Result<Option<Vec<f32>>, String> {
+    pub fn decode_next(&mut self) -> Result<Option<Vec<f32>>, AppError> {
         loop {
             let packet = match self.format.next_packet() {
                 Ok(p) => p,
@@ -103,7 +171,7 @@ impl AudioDecoder {
                     self.decoder.reset();
                     continue;
                 }
-                Err(e) => return Err(format!("Failed to read packet: {}", e)),
+                Err(e) => return Err(AppError::from(e).context("Failed to read packet")),
             };
             
             // Skip packets from other tracks
@@ -114,21 +182,36 @@ impl AudioDecoder {
             // Decode the packet
             match self.decoder.decode(&packet) {
                 Ok(decoded) => {
-                    return Ok(Some(Self::audio_buf_to_f32(&decoded)));
+                    self.decode_errors.record_success();
+                    let samples = Self::audio_buf_to_f32(&decoded);
+                    if self.range.is_none() {
+                        return Ok(Some(samples));
+                    }
+                    match self.clip_to_range(packet.ts(), samples) {
+                        None => return Ok(None),
+                        Some(samples) if samples.is_empty() => continue,
+                        Some(samples) => return Ok(Some(samples)),
+                    }
                 }
                 Err(SymphoniaError::DecodeError(e)) => {
-                    // Log decode errors but continue
+                    // Log decode errors and continue, unless the file is corrupt beyond repair
                     eprintln!("Decode error (skipping): {}", e);
+                    self.decode_errors.record_error(e)?;
                     continue;
                 }
-                Err(e) => return Err(format!("Decode failed: {}", e)),
+                Err(e) => return Err(AppError::from(e).context("Decode failed")),
             }
         }
     }
     
-    /// Seek to position in milliseconds
-    pub fn seek(&mut self, position_ms: i64) -> Result<u64, String> {
-        let seconds = position_ms as f64 / 1000.0;
+    /// Seek to position in milliseconds.
+    /// Goes through the container's seek index (FLAC SEEKTABLE, MP4 sample tables, ...),
+    /// so the cost doesn't grow with the position: landing at minute 50 of a long FLAC takes
+    /// about as long as landing at minute 1. Coarse mode may land slightly before the target;
+    /// the returned position is where decoding actually resumes.
+    pub fn seek(&mut self, position_ms: i64) -> Result<u64, AppError> {
+        let start_ms = self.range.map_or(0, |r| r.start_ms);
+        let seconds = (start_ms + position_ms) as f64 / 1000.0;
         let time = Time::new(seconds as u64, seconds.fract());
         
         let seeked_to = self.format.seek(
@@ -137,14 +220,50 @@ impl AudioDecoder {
                 time,
                 track_id: Some(self.track_id),
             }
-        ).map_err(|e| format!("Seek failed: {}", e))?;
+        ).context("Seek failed")?;
         
         // Reset decoder state after seek
         self.decoder.reset();
         
         // Return the actual position we seeked to (in ms)
         let actual_ms = (seeked_to.actual_ts as f64 / self.sample_rate as f64 * 1000.0) as u64;
-        Ok(actual_ms)
+        Ok(actual_ms.saturating_sub(start_ms as u64))
+    }
+    
+    /// Frame index of a packet timestamp
+    fn frame_at(&self, ts: u64) -> u64 {
+        match self.time_base {
+            Some(time_base) => {
+                let time = time_base.calc_time(ts);
+                time.seconds * self.sample_rate as u64 + (time.frac * self.sample_rate as f64).round() as u64
+            }
+            None => ts,
+        }
+    }
+    
+    /// Cut a decoded packet starting at `ts` down to the CUE range. Empty if the packet
+    /// ends before the range starts (a seek landed early), None once it starts past the end.
+    fn clip_to_range(&self, ts: u64, mut samples: Vec<f32>) -> Option<Vec<f32>> {
+        let Some(range) = self.range else {
+            return Some(samples);
+        };
+        let to_frame = |ms: i64| (ms.max(0) as u64 * self.sample_rate as u64) / 1000;
+        let channels = self.channels.max(1);
+        let first = self.frame_at(ts);
+        let frames = (samples.len() / channels) as u64;
+        
+        if let Some(end) = range.end_ms.map(to_frame) {
+            if first >= end {
+                return None;
+            }
+            samples.truncate(((end - first).min(frames) as usize) * channels);
+        }
+        let start = to_frame(range.start_ms);
+        if first < start {
+            let skip = ((start - first).min(frames) as usize) * channels;
+            samples.drain(..skip.min(samples.len()));
+        }
+        Some(samples)
     }
     
     /// Convert any AudioBufferRef to interleaved f32 samples
@@ -225,3 +344,170 @@ impl AudioDecoder {
         interleaved
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::write_wav;
+
+    /// Write an MPEG-1 Layer III mono stream (128 kbps, 44.1 kHz) with one frame per entry:
+    /// `true` is a silent frame, `false` a frame whose side info claims more than the 288
+    /// big_values a granule can hold, which the decoder rejects
+    fn write_mp3(path: &Path, frames: &[bool]) {
+        const FRAME_LEN: usize = 417;
+        let mut bytes = Vec::new();
+        for &good in frames {
+            let mut frame = vec![0u8; FRAME_LEN];
+            frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0xC0]);
+            if !good {
+                // Side info starts after the header; big_values is bits 30..39
+                frame[7..9].copy_from_slice(&[0xFF, 0xFF]);
+            }
+            bytes.extend_from_slice(&frame);
+        }
+        std::fs::write(path, bytes).unwrap();
+    }
+
+    /// Decode a file to the end, returning how many packets came out
+    fn decode_all(path: &Path, max_errors: u32) -> Result<usize, AppError> {
+        let mut decoder = AudioDecoder::open(path)?;
+        decoder.set_max_consecutive_errors(max_errors);
+        let mut decoded = 0;
+        while decoder.decode_next()?.is_some() {
+            decoded += 1;
+        }
+        Ok(decoded)
+    }
+
+    #[test]
+    fn test_bails_out_after_consecutive_decode_errors() {
+        let path = std::env::temp_dir().join(format!("musicsloth_corrupt_{}.mp3", std::process::id()));
+        let mut frames = vec![true];
+        frames.extend(vec![false; 20]);
+        write_mp3(&path, &frames);
+
+        let result = decode_all(&path, 10);
+        let _ = std::fs::remove_file(&path);
+        assert!(result.unwrap_err().to_string().contains("Too many consecutive decode errors (10)"));
+    }
+
+    #[test]
+    fn test_tolerates_sparse_decode_errors() {
+        // 9 corrupt frames, a good one, 9 more corrupt ones: never 10 in a row
+        let path = std::env::temp_dir().join(format!("musicsloth_sparse_{}.mp3", std::process::id()));
+        let mut frames = vec![true];
+        frames.extend(vec![false; 9]);
+        frames.push(true);
+        frames.extend(vec![false; 9]);
+        frames.push(true);
+        write_mp3(&path, &frames);
+
+        let result = decode_all(&path, 10);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(result.unwrap(), 3);
+    }
+
+    #[test]
+    fn test_truncated_file_ends_cleanly() {
+        // The header promises ten seconds but the data stops after one
+        let path = std::env::temp_dir().join(format!("musicsloth_truncated_{}.wav", std::process::id()));
+        write_wav(&path, 1000, 10_000, |_| 0);
+        let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(44 + 2 * 1000).unwrap();
+        drop(file);
+
+        let mut decoder = AudioDecoder::open(&path).unwrap();
+        let mut samples = 0;
+        while let Some(packet) = decoder.decode_next().unwrap() {
+            samples += packet.len();
+        }
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(samples, 1000);
+    }
+
+    /// Write a 16-bit mono WAV whose samples count up the elapsed seconds,
+    /// so a decoded sample tells where in the file it came from
+    fn write_ramp_wav(path: &Path, sample_rate: u32, seconds: u32) {
+        write_wav(path, sample_rate, sample_rate * seconds, |frame| (frame / sample_rate) as i16);
+    }
+
+    #[test]
+    fn test_seek_resumes_at_target_in_long_file() {
+        let path = std::env::temp_dir().join(format!("musicsloth_seek_{}.wav", std::process::id()));
+        write_ramp_wav(&path, 1000, 3600);
+
+        let mut decoder = AudioDecoder::open(&path).unwrap();
+        let started = std::time::Instant::now();
+        let landed_ms = decoder.seek(50 * 60 * 1000).unwrap();
+        let samples = decoder.decode_next().unwrap().unwrap();
+        let elapsed = started.elapsed();
+        let _ = std::fs::remove_file(&path);
+
+        assert!((2_999_000..=3_000_000).contains(&landed_ms), "landed at {} ms", landed_ms);
+        // Decoding picks up at minute 50 instead of replaying the file from the start
+        let second = (samples[0] * 32768.0).round() as i64;
+        assert!((2999..=3000).contains(&second), "resumed at second {}", second);
+        assert!(elapsed < std::time::Duration::from_secs(1), "seek took {:?}", elapsed);
+    }
+
+    #[test]
+    fn test_decodes_aiff() {
+        // One second of 16-bit mono 8 kHz silence
+        let frames: u32 = 8000;
+        let mut comm = Vec::new();
+        comm.extend_from_slice(&1u16.to_be_bytes());
+        comm.extend_from_slice(&frames.to_be_bytes());
+        comm.extend_from_slice(&16u16.to_be_bytes());
+        // 8000.0 as an 80-bit extended float
+        comm.extend_from_slice(&[0x40, 0x0B, 0xFA, 0, 0, 0, 0, 0, 0, 0]);
+        let mut ssnd = vec![0u8; 8];
+        ssnd.resize(8 + frames as usize * 2, 0);
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"FORM");
+        bytes.extend_from_slice(&(4 + 8 + comm.len() as u32 + 8 + ssnd.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(b"AIFF");
+        bytes.extend_from_slice(b"COMM");
+        bytes.extend_from_slice(&(comm.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&comm);
+        bytes.extend_from_slice(b"SSND");
+        bytes.extend_from_slice(&(ssnd.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&ssnd);
+
+        let path = std::env::temp_dir().join(format!("musicsloth_decode_{}.aiff", std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        let decoder = AudioDecoder::open(&path);
+        let _ = std::fs::remove_file(&path);
+
+        let decoder = decoder.unwrap();
+        assert_eq!(decoder.sample_rate(), 8000);
+        assert_eq!(decoder.duration_ms(), Some(1000));
+    }
+
+    #[test]
+    fn test_cue_track_plays_only_its_range() {
+        let path = std::env::temp_dir().join(format!("musicsloth_cue_range_{}.wav", std::process::id()));
+        write_ramp_wav(&path, 1000, 10);
+        let file = path.to_string_lossy().to_string();
+        let second_of = |sample: f32| (sample * 32768.0).round() as i64;
+
+        let mut decoder = AudioDecoder::open(Path::new(&format!("{}#3000-5000", file))).unwrap();
+        assert_eq!(decoder.duration_ms(), Some(2000));
+        let mut samples = Vec::new();
+        while let Some(packet) = decoder.decode_next().unwrap() {
+            samples.extend(packet);
+        }
+        assert_eq!(samples.len(), 2000);
+        assert_eq!((second_of(samples[0]), second_of(samples[1999])), (3, 4));
+
+        // Seeking is relative to the track start (coarse, so it may land a little early)
+        let landed_ms = decoder.seek(1500).unwrap() as i64;
+        assert!((1..=1500).contains(&landed_ms), "landed at {} ms", landed_ms);
+        assert_eq!(second_of(decoder.decode_next().unwrap().unwrap()[0]), (3000 + landed_ms) / 1000);
+
+        // The last track runs to the end of the file
+        let last = AudioDecoder::open(Path::new(&format!("{}#8000-", file))).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(last.duration_ms(), Some(2000));
+    }
+}
@@ -0,0 +1,232 @@
+// Graphic equalizer
+// One peaking biquad (RBJ audio EQ cookbook) per ISO octave band, run in series on every
+// channel with separate filter state, so channels never bleed into each other.
+use crate::settings::{PlaybackSettings, EQ_BAND_COUNT, MAX_EQ_GAIN_DB};
+
+/// Centre frequencies of the bands in Hz
+pub const EQ_FREQUENCIES_HZ: [f32; EQ_BAND_COUNT] = [
+    31.0, 62.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0,
+];
+
+/// Band width of one octave
+const EQ_Q: f32 = 1.41;
+
+/// Bands this close to Nyquist can't be realized and are skipped
+const MAX_BAND_FRACTION_OF_RATE: f32 = 0.45;
+
+/// What the playback thread should apply
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct EqualizerParams {
+    pub enabled: bool,
+    /// Gain per band in dB, lowest frequency first
+    pub gains: [f32; EQ_BAND_COUNT],
+}
+
+impl EqualizerParams {
+    /// Params from settings values. Missing bands are flat, gains are clamped to ±MAX_EQ_GAIN_DB.
+    pub fn new(enabled: bool, gains: &[f32]) -> Self {
+        let mut params = Self { enabled, gains: [0.0; EQ_BAND_COUNT] };
+        for (band, gain) in params.gains.iter_mut().zip(gains) {
+            if gain.is_finite() {
+                *band = gain.clamp(-MAX_EQ_GAIN_DB, MAX_EQ_GAIN_DB);
+            }
+        }
+        params
+    }
+
+    pub fn from_settings(settings: &PlaybackSettings) -> Self {
+        Self::new(settings.equalizer_enabled, &settings.equalizer_gains)
+    }
+
+    /// Whether the equalizer changes the sound at all
+    fn is_active(&self) -> bool {
+        self.enabled && self.gains.iter().any(|gain| gain.abs() > 0.01)
+    }
+}
+
+/// Normalized biquad coefficients
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl Biquad {
+    /// Peaking filter, None if the band doesn't do anything or can't be realized at this rate
+    fn peaking(sample_rate: u32, frequency_hz: f32, gain_db: f32) -> Option<Self> {
+        if gain_db.abs() <= 0.01 || frequency_hz >= sample_rate as f32 * MAX_BAND_FRACTION_OF_RATE {
+            return None;
+        }
+        let a = 10.0_f32.powf(gain_db / 40.0);
+        let omega = 2.0 * std::f32::consts::PI * frequency_hz / sample_rate as f32;
+        let alpha = omega.sin() / (2.0 * EQ_Q);
+        let cos_omega = omega.cos();
+
+        let a0 = 1.0 + alpha / a;
+        Some(Self {
+            b0: (1.0 + alpha * a) / a0,
+            b1: (-2.0 * cos_omega) / a0,
+            b2: (1.0 - alpha * a) / a0,
+            a1: (-2.0 * cos_omega) / a0,
+            a2: (1.0 - alpha / a) / a0,
+        })
+    }
+}
+
+/// Equalizer filter state for interleaved samples
+pub struct Equalizer {
+    sample_rate: u32,
+    params: EqualizerParams,
+    /// (band index, coefficients) of the bands in use
+    filters: Vec<(usize, Biquad)>,
+    /// Transposed direct form II state per channel and band
+    state: Vec<[[f32; 2]; EQ_BAND_COUNT]>,
+    /// Attenuation that keeps the largest boost from clipping
+    preamp: f32,
+}
+
+impl Equalizer {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            params: EqualizerParams::default(),
+            filters: Vec::new(),
+            state: Vec::new(),
+            preamp: 1.0,
+        }
+    }
+
+    /// Change the band gains. Filter state is kept so a change while playing doesn't click.
+    pub fn set_params(&mut self, params: EqualizerParams) {
+        if params == self.params {
+            return;
+        }
+        self.params = params;
+        self.filters = EQ_FREQUENCIES_HZ
+            .iter()
+            .zip(params.gains)
+            .enumerate()
+            .filter_map(|(band, (&frequency_hz, gain_db))| {
+                Biquad::peaking(self.sample_rate, frequency_hz, gain_db).map(|filter| (band, filter))
+            })
+            .collect();
+        let max_boost_db = params.gains.iter().copied().fold(0.0_f32, f32::max);
+        self.preamp = 10.0_f32.powf(-max_boost_db / 20.0);
+    }
+
+    /// Clear the filter history (on seek or track change) so old audio doesn't ring on
+    pub fn reset(&mut self) {
+        self.state.iter_mut().for_each(|channel| *channel = [[0.0; 2]; EQ_BAND_COUNT]);
+    }
+
+    /// Apply the equalizer in place
+    pub fn process(&mut self, samples: &mut [f32], channels: usize) {
+        if !self.params.is_active() || channels == 0 {
+            return;
+        }
+        if self.state.len() != channels {
+            self.state = vec![[[0.0; 2]; EQ_BAND_COUNT]; channels];
+        }
+
+        for frame in samples.chunks_exact_mut(channels) {
+            for (sample, state) in frame.iter_mut().zip(self.state.iter_mut()) {
+                let mut x = *sample * self.preamp;
+                for &(band, f) in &self.filters {
+                    let [z1, z2] = state[band];
+                    let y = f.b0 * x + z1;
+                    state[band] = [f.b1 * x - f.a1 * y + z2, f.b2 * x - f.a2 * y];
+                    x = y;
+                }
+                *sample = x;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 48000;
+
+    fn tone(frequency_hz: f32, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .map(|i| 0.5 * (2.0 * std::f32::consts::PI * frequency_hz * i as f32 / RATE as f32).sin())
+            .collect()
+    }
+
+    /// Peak of the second half, after the filters settled
+    fn settled_peak(samples: &[f32]) -> f32 {
+        samples[samples.len() / 2..].iter().fold(0.0, |peak, s| peak.max(s.abs()))
+    }
+
+    /// Level change of a tone in dB
+    fn gain_db(params: EqualizerParams, frequency_hz: f32) -> f32 {
+        let mut eq = Equalizer::new(RATE);
+        eq.set_params(params);
+        let original = tone(frequency_hz, RATE as usize / 2);
+        let mut samples = original.clone();
+        eq.process(&mut samples, 1);
+        20.0 * (settled_peak(&samples) / settled_peak(&original)).log10()
+    }
+
+    #[test]
+    fn test_band_boost_only_affects_its_band() {
+        let mut gains = [0.0; EQ_BAND_COUNT];
+        gains[5] = 6.0; // 1 kHz
+        let params = EqualizerParams::new(true, &gains);
+
+        // The preamp takes the boost back off, so the band ends up at unity and the rest 6 dB down
+        let boosted = gain_db(params, 1000.0);
+        let untouched = gain_db(params, 100.0);
+        assert!(boosted.abs() < 0.2, "1 kHz changed by {:.2} dB", boosted);
+        assert!((untouched + 6.0).abs() < 0.3, "100 Hz changed by {:.2} dB", untouched);
+    }
+
+    fn preset_gains(name: &str) -> Vec<f32> {
+        PlaybackSettings::default().apply_equalizer_preset(name).unwrap()
+    }
+
+    #[test]
+    fn test_flat_or_disabled_passes_through() {
+        let original = tone(440.0, 4800);
+        for params in [
+            EqualizerParams::new(true, &[0.0; EQ_BAND_COUNT]),
+            EqualizerParams::new(false, &preset_gains("rock")),
+        ] {
+            let mut eq = Equalizer::new(RATE);
+            eq.set_params(params);
+            let mut samples = original.clone();
+            eq.process(&mut samples, 1);
+            assert_eq!(samples, original);
+        }
+    }
+
+    #[test]
+    fn test_channels_are_filtered_independently() {
+        let mut eq = Equalizer::new(RATE);
+        eq.set_params(EqualizerParams::new(true, &preset_gains("bass_boost")));
+
+        // Silent left channel, tone on the right
+        let mut samples: Vec<f32> = tone(62.0, 4800).into_iter().flat_map(|s| [0.0, s]).collect();
+        eq.process(&mut samples, 2);
+        assert!(samples.iter().step_by(2).all(|&left| left == 0.0));
+        assert!(samples.iter().skip(1).step_by(2).any(|&right| right != 0.0));
+    }
+
+    #[test]
+    fn test_reset_clears_ringing() {
+        let mut eq = Equalizer::new(RATE);
+        eq.set_params(EqualizerParams::new(true, &preset_gains("bass_boost")));
+        let mut samples = tone(31.0, 4800);
+        eq.process(&mut samples, 1);
+
+        eq.reset();
+        let mut silence = vec![0.0; 480];
+        eq.process(&mut silence, 1);
+        assert!(silence.iter().all(|&s| s == 0.0));
+    }
+}
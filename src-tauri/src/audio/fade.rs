@@ -0,0 +1,338 @@
+// Fade curves
+// Gain shapes used when fading tracks in/out and crossfading between them
+use crate::settings::FadeSettings;
+use std::f32::consts::FRAC_PI_2;
+
+/// Dynamic range of the logarithmic curve in dB (gain at the quiet end)
+const LOG_FADE_RANGE_DB: f32 = -60.0;
+
+/// Shape of a fade's gain over time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FadeCurve {
+    /// Gain changes linearly - dips in perceived loudness mid-crossfade
+    Linear,
+    /// sin/cos gains whose squares sum to 1, so power stays constant across a crossfade
+    #[default]
+    EqualPower,
+    /// Gain changes linearly in dB, which sounds even to the ear
+    Logarithmic,
+}
+
+impl FadeCurve {
+    /// Parse a settings value ("linear", "equal_power", "log").
+    /// Unknown values fall back to equal-power.
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "linear" => FadeCurve::Linear,
+            "log" | "logarithmic" => FadeCurve::Logarithmic,
+            _ => FadeCurve::EqualPower,
+        }
+    }
+
+    /// Gain for a fade-in at `progress` (0.0 = start, 1.0 = end), going 0 -> 1
+    pub fn fade_in_gain(&self, progress: f32) -> f32 {
+        let t = progress.clamp(0.0, 1.0);
+        match self {
+            FadeCurve::Linear => t,
+            FadeCurve::EqualPower => (t * FRAC_PI_2).sin(),
+            FadeCurve::Logarithmic => {
+                if t <= 0.0 {
+                    0.0
+                } else {
+                    10f32.powf(LOG_FADE_RANGE_DB * (1.0 - t) / 20.0)
+                }
+            }
+        }
+    }
+
+    /// Gain for a fade-out at `progress` (0.0 = start, 1.0 = end), going 1 -> 0
+    pub fn fade_out_gain(&self, progress: f32) -> f32 {
+        self.fade_in_gain(1.0 - progress.clamp(0.0, 1.0))
+    }
+}
+
+/// Fade lengths and shape the player uses, taken from FadeSettings
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FadeParams {
+    pub fade_in_ms: f64,
+    pub fade_out_ms: f64,
+    pub curve: FadeCurve,
+}
+
+impl FadeParams {
+    /// Disabled fades have zero length
+    pub fn from_settings(settings: &FadeSettings) -> Self {
+        if !settings.enabled {
+            return Self::default();
+        }
+        Self {
+            fade_in_ms: settings.fade_in_ms.max(0) as f64,
+            fade_out_ms: settings.fade_out_ms.max(0) as f64,
+            curve: FadeCurve::from_name(&settings.curve),
+        }
+    }
+}
+
+/// Gain envelope of the playing track, indexed by track position in milliseconds.
+/// Combines the fade-in, the fade-out before the track's end and a fade-out started by stopping.
+#[derive(Debug, Clone, Default)]
+pub struct FadeEnvelope {
+    params: FadeParams,
+    /// Position the fade-in ramps up from, None when there's no fade-in running
+    fade_in_from: Option<f64>,
+    /// Track length, for fading out before the end. None when unknown or when the
+    /// next track follows gaplessly
+    track_end: Option<f64>,
+    /// (start, end) positions of a fade-out requested by stopping
+    stop_window: Option<(f64, f64)>,
+}
+
+impl FadeEnvelope {
+    pub fn new(params: FadeParams) -> Self {
+        Self {
+            params,
+            fade_in_from: Some(0.0),
+            ..Self::default()
+        }
+    }
+
+    pub fn set_params(&mut self, params: FadeParams) {
+        self.params = params;
+    }
+
+    /// A new track starts at position 0, with or without a fade-in
+    pub fn restart_track(&mut self, fade_in: bool) {
+        self.fade_in_from = fade_in.then_some(0.0);
+        self.stop_window = None;
+    }
+
+    /// Seeking inside the fade-in window restarts the fade-in from there; seeking past it
+    /// plays at full level
+    pub fn seeked(&mut self, position_ms: f64) {
+        self.fade_in_from = (position_ms < self.params.fade_in_ms).then_some(position_ms);
+    }
+
+    pub fn set_track_end(&mut self, track_end_ms: Option<f64>) {
+        self.track_end = track_end_ms;
+    }
+
+    /// Start fading out at `position_ms` because playback is stopping. The fade never runs
+    /// past the end of the track. Returns the position playback should stop at.
+    pub fn fade_out_now(&mut self, position_ms: f64) -> f64 {
+        let remaining = self.track_end.map(|end| (end - position_ms).max(0.0));
+        let length = match remaining {
+            Some(remaining) => self.params.fade_out_ms.min(remaining),
+            None => self.params.fade_out_ms,
+        };
+        let end = position_ms + length;
+        if length > 0.0 {
+            self.stop_window = Some((position_ms, end));
+        }
+        end
+    }
+
+    /// Gain at a track position
+    pub fn gain_at(&self, position_ms: f64) -> f32 {
+        let curve = self.params.curve;
+        let mut gain = 1.0;
+
+        if let Some(from) = self.fade_in_from {
+            if self.params.fade_in_ms > 0.0 && position_ms < from + self.params.fade_in_ms {
+                gain *= curve.fade_in_gain(((position_ms - from) / self.params.fade_in_ms) as f32);
+            }
+        }
+
+        if let Some(end) = self.track_end {
+            // Shorter than the track for very short tracks
+            let length = self.params.fade_out_ms.min(end);
+            if length > 0.0 && position_ms > end - length {
+                gain *= curve.fade_out_gain(((position_ms - (end - length)) / length) as f32);
+            }
+        }
+
+        if let Some((start, end)) = self.stop_window {
+            if position_ms >= start {
+                gain *= curve.fade_out_gain(((position_ms - start) / (end - start)) as f32);
+            }
+        }
+
+        gain
+    }
+
+    /// Apply the envelope to interleaved samples covering track positions `start_ms..end_ms`
+    pub fn apply(&self, samples: &mut [f32], channels: usize, start_ms: f64, end_ms: f64) {
+        let frames = samples.len() / channels.max(1);
+        if frames == 0 || (self.gain_at(start_ms) == 1.0 && self.gain_at(end_ms) == 1.0) {
+            return;
+        }
+
+        let step = (end_ms - start_ms) / frames as f64;
+        for (index, frame) in samples.chunks_mut(channels.max(1)).enumerate() {
+            let gain = self.gain_at(start_ms + step * index as f64);
+            for sample in frame {
+                *sample *= gain;
+            }
+        }
+    }
+}
+
+/// Progress of a crossfade, counted in output frames. Mixes the outgoing track into the
+/// incoming one with equal-power gains, so the overlap doesn't dip or swell in loudness.
+#[derive(Debug, Clone, Copy)]
+pub struct Crossfade {
+    length_frames: u64,
+    mixed_frames: u64,
+}
+
+impl Crossfade {
+    pub fn new(length_frames: u64) -> Self {
+        Self { length_frames: length_frames.max(1), mixed_frames: 0 }
+    }
+
+    /// Whether the outgoing track has faded out completely
+    pub fn is_finished(&self) -> bool {
+        self.mixed_frames >= self.length_frames
+    }
+
+    /// Fade `incoming` in and mix `outgoing` (same length, scaled by `outgoing_gain`) into it
+    pub fn mix(&mut self, incoming: &mut [f32], outgoing: &[f32], channels: usize, outgoing_gain: f32) {
+        let curve = FadeCurve::EqualPower;
+        let channels = channels.max(1);
+        for (frame_in, frame_out) in incoming.chunks_mut(channels).zip(outgoing.chunks(channels)) {
+            let progress = self.mixed_frames as f32 / self.length_frames as f32;
+            let gain_in = curve.fade_in_gain(progress);
+            let gain_out = curve.fade_out_gain(progress) * outgoing_gain;
+            for (sample, &tail) in frame_in.iter_mut().zip(frame_out) {
+                *sample = *sample * gain_in + tail * gain_out;
+            }
+            self.mixed_frames = (self.mixed_frames + 1).min(self.length_frames);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CURVES: [FadeCurve; 3] = [FadeCurve::Linear, FadeCurve::EqualPower, FadeCurve::Logarithmic];
+
+    #[test]
+    fn test_curve_endpoints() {
+        for curve in CURVES {
+            assert!(curve.fade_in_gain(0.0).abs() < 1e-6, "{:?} fade-in start", curve);
+            assert!((curve.fade_in_gain(1.0) - 1.0).abs() < 1e-6, "{:?} fade-in end", curve);
+            assert!((curve.fade_out_gain(0.0) - 1.0).abs() < 1e-6, "{:?} fade-out start", curve);
+            assert!(curve.fade_out_gain(1.0).abs() < 1e-6, "{:?} fade-out end", curve);
+        }
+    }
+
+    #[test]
+    fn test_equal_power_midpoint() {
+        let curve = FadeCurve::EqualPower;
+        for progress in [0.25, 0.5, 0.75] {
+            let fade_in = curve.fade_in_gain(progress);
+            let fade_out = curve.fade_out_gain(progress);
+            assert!((fade_in * fade_in + fade_out * fade_out - 1.0).abs() < 1e-5);
+        }
+        // A linear crossfade dips to half power in the middle
+        let linear = FadeCurve::Linear;
+        let power = linear.fade_in_gain(0.5).powi(2) + linear.fade_out_gain(0.5).powi(2);
+        assert!((power - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(FadeCurve::from_name("linear"), FadeCurve::Linear);
+        assert_eq!(FadeCurve::from_name("equal_power"), FadeCurve::EqualPower);
+        assert_eq!(FadeCurve::from_name("log"), FadeCurve::Logarithmic);
+        assert_eq!(FadeCurve::from_name("bogus"), FadeCurve::EqualPower);
+    }
+
+    fn params(fade_in_ms: f64, fade_out_ms: f64) -> FadeParams {
+        FadeParams { fade_in_ms, fade_out_ms, curve: FadeCurve::Linear }
+    }
+
+    #[test]
+    fn test_fade_in_and_out_at_track_end() {
+        let mut envelope = FadeEnvelope::new(params(1000.0, 2000.0));
+        envelope.set_track_end(Some(10_000.0));
+
+        assert_eq!(envelope.gain_at(0.0), 0.0);
+        assert!((envelope.gain_at(500.0) - 0.5).abs() < 1e-6);
+        assert_eq!(envelope.gain_at(5_000.0), 1.0);
+        assert!((envelope.gain_at(9_000.0) - 0.5).abs() < 1e-6);
+        assert_eq!(envelope.gain_at(10_000.0), 0.0);
+
+        // A track shorter than the fade-out fades over its whole length
+        envelope.set_track_end(Some(1_000.0));
+        envelope.restart_track(false);
+        assert!((envelope.gain_at(500.0) - 0.5).abs() < 1e-6);
+
+        // Gapless continuation: no fade at the end
+        envelope.set_track_end(None);
+        assert_eq!(envelope.gain_at(9_999.0), 1.0);
+
+        // Disabled settings don't change the signal
+        let disabled = FadeParams::from_settings(&FadeSettings { enabled: false, fade_in_ms: 500, ..FadeSettings::default() });
+        let mut samples = vec![0.5; 8];
+        FadeEnvelope::new(disabled).apply(&mut samples, 2, 0.0, 10.0);
+        assert_eq!(samples, vec![0.5; 8]);
+    }
+
+    #[test]
+    fn test_seek_restarts_fade_in_only_inside_window() {
+        let mut envelope = FadeEnvelope::new(params(1000.0, 0.0));
+
+        envelope.seeked(400.0);
+        assert_eq!(envelope.gain_at(400.0), 0.0);
+        assert!((envelope.gain_at(900.0) - 0.5).abs() < 1e-6);
+
+        envelope.seeked(3_000.0);
+        assert_eq!(envelope.gain_at(3_000.0), 1.0);
+    }
+
+    #[test]
+    fn test_stop_fade_clamped_to_remaining_time() {
+        let mut envelope = FadeEnvelope::new(params(0.0, 2000.0));
+        envelope.set_track_end(Some(60_000.0));
+
+        assert_eq!(envelope.fade_out_now(10_000.0), 12_000.0);
+        assert!((envelope.gain_at(11_000.0) - 0.5).abs() < 1e-6);
+
+        // Only half a second left: the fade can't outlast the track
+        let mut envelope = FadeEnvelope::new(params(0.0, 2000.0));
+        envelope.set_track_end(Some(60_000.0));
+        assert_eq!(envelope.fade_out_now(59_500.0), 60_000.0);
+
+        let mut samples = vec![1.0; 4];
+        envelope.apply(&mut samples, 2, 59_500.0, 60_000.0);
+        assert!(samples[0] > samples[2]);
+    }
+
+    #[test]
+    fn test_crossfade_keeps_power_and_finishes() {
+        let mut crossfade = Crossfade::new(4);
+        let mut incoming = [1.0; 8];
+        let outgoing = [1.0; 8];
+        crossfade.mix(&mut incoming[..4], &outgoing[..4], 2, 1.0);
+        // Starts on the outgoing track alone
+        assert_eq!(incoming[0], 1.0);
+        assert!(!crossfade.is_finished());
+
+        crossfade.mix(&mut incoming[4..], &outgoing[4..], 2, 1.0);
+        assert!(crossfade.is_finished());
+
+        // Uncorrelated tracks keep their power across the overlap
+        let mut crossfade = Crossfade::new(100);
+        for _ in 0..100 {
+            let mut probe = [1.0, 0.0];
+            crossfade.mix(&mut probe, &[0.0, 1.0], 2, 1.0);
+            assert!((probe[0].powi(2) + probe[1].powi(2) - 1.0).abs() < 1e-5);
+        }
+
+        // Past the end only the incoming track is left
+        let mut samples = vec![0.5; 4];
+        crossfade.mix(&mut samples, &[1.0; 4], 2, 1.0);
+        assert!(samples.iter().all(|&s| (s - 0.5).abs() < 1e-6));
+    }
+}
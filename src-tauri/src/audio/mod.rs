@@ -1,8 +1,10 @@
 // Audio playback module
 // Uses Symphonia for decoding and cpal for output
 
+pub mod crossfeed;
 pub mod decoder;
+pub mod equalizer;
+pub mod fade;
 pub mod output;
 pub mod player;
-
-pub use player::{Player, PlayerState};
+pub mod resample;
@@ -5,7 +5,8 @@ use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::{Stream, StreamConfig};
 use parking_lot::Mutex;
 use ringbuf::{HeapRb, traits::{Consumer, Observer, Producer, Split}};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::ops::Range;
 use std::sync::Arc;
 
 const RING_BUFFER_SIZE: usize = 48000 * 2 / 4; // ~250ms of stereo audio at 48kHz
@@ -13,22 +14,255 @@ const RING_BUFFER_SIZE: usize = 48000 * 2 / 4; // ~250ms of stereo audio at 48kH
 type RingProducer = ringbuf::HeapProd<f32>;
 type RingConsumer = ringbuf::HeapCons<f32>;
 
+/// Device name that opens a null output in tests, for driving playback without sound hardware
+#[cfg(test)]
+pub const NULL_OUTPUT_DEVICE: &str = "Null output";
+
+/// Format the output stream runs at, for the diagnostics panel
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct OutputInfo {
+    pub device_name: String,
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// Length of the ring buffer between the decoder and the device
+    pub buffer_ms: f32,
+}
+
+impl OutputInfo {
+    fn new(device_name: String, sample_rate: u32, channels: u16) -> Self {
+        let buffer_ms = if sample_rate > 0 && channels > 0 {
+            RING_BUFFER_SIZE as f32 / channels as f32 / sample_rate as f32 * 1000.0
+        } else {
+            0.0
+        };
+        Self {
+            device_name,
+            sample_rate,
+            channels,
+            buffer_ms,
+        }
+    }
+}
+
+/// -3 dB, the usual weight for a channel shared between two speakers
+const FOLD_GAIN: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+/// Speaker a channel is meant for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Speaker {
+    Mono,
+    FrontLeft,
+    FrontRight,
+    Center,
+    Lfe,
+    BackLeft,
+    BackRight,
+    BackCenter,
+    SideLeft,
+    SideRight,
+}
+
+/// Speakers of each channel for a channel count, in the order decoders hand them out
+/// (WAV/FLAC/Vorbis order). None for channels past the known layouts, which are dropped.
+fn speaker_layout(channels: usize) -> Vec<Option<Speaker>> {
+    use Speaker::*;
+    let layout: &[Speaker] = match channels {
+        1 => &[Mono],
+        3 => &[FrontLeft, FrontRight, Center],
+        4 => &[FrontLeft, FrontRight, BackLeft, BackRight],
+        5 => &[FrontLeft, FrontRight, Center, BackLeft, BackRight],
+        6 => &[FrontLeft, FrontRight, Center, Lfe, BackLeft, BackRight],
+        7 => &[FrontLeft, FrontRight, Center, Lfe, BackCenter, SideLeft, SideRight],
+        8 => &[FrontLeft, FrontRight, Center, Lfe, BackLeft, BackRight, SideLeft, SideRight],
+        // Stereo, and the front pair of layouts we don't know
+        _ => &[FrontLeft, FrontRight],
+    };
+    (0..channels).map(|i| layout.get(i).copied()).collect()
+}
+
+/// Output speakers (with weights) a source speaker ends up on when the output doesn't have it
+fn fold_speaker(speaker: Speaker, output: &[Option<Speaker>]) -> Vec<(Speaker, f32)> {
+    use Speaker::*;
+    let has = |s: Speaker| output.contains(&Some(s));
+    if has(speaker) {
+        return vec![(speaker, 1.0)];
+    }
+    // Mono output: fold to stereo first, then sum the two sides
+    if has(Mono) {
+        return fold_speaker(speaker, &[Some(FrontLeft), Some(FrontRight)])
+            .into_iter()
+            .map(|(_, weight)| (Mono, weight))
+            .collect();
+    }
+    let pair = |left: Speaker, right: Speaker, weight: f32| vec![(left, weight), (right, weight)];
+    match speaker {
+        // A mono source plays on both sides at full level
+        Mono => pair(FrontLeft, FrontRight, 1.0),
+        Center => pair(FrontLeft, FrontRight, FOLD_GAIN),
+        // Bass management is the device's job, the usual downmix leaves LFE out
+        Lfe => Vec::new(),
+        SideLeft if has(BackLeft) => vec![(BackLeft, 1.0)],
+        SideRight if has(BackRight) => vec![(BackRight, 1.0)],
+        BackLeft if has(SideLeft) => vec![(SideLeft, 1.0)],
+        BackRight if has(SideRight) => vec![(SideRight, 1.0)],
+        SideLeft | BackLeft => vec![(FrontLeft, FOLD_GAIN)],
+        SideRight | BackRight => vec![(FrontRight, FOLD_GAIN)],
+        BackCenter if has(BackLeft) => pair(BackLeft, BackRight, FOLD_GAIN),
+        BackCenter if has(SideLeft) => pair(SideLeft, SideRight, FOLD_GAIN),
+        BackCenter => pair(FrontLeft, FrontRight, FOLD_GAIN * FOLD_GAIN),
+        FrontLeft | FrontRight => Vec::new(),
+    }
+}
+
+/// Mixing matrix from a track's channels to the device's: copies channels the device has,
+/// duplicates mono to both sides and downmixes surround with the usual -3 dB weights (LFE
+/// left out). Output channels that would sum past full scale are scaled down so the
+/// downmix can't clip. Built per track, since the source channel count can change.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelMap {
+    source_channels: usize,
+    /// One row of source channel weights per output channel
+    matrix: Vec<Vec<f32>>,
+}
+
+impl ChannelMap {
+    pub fn new(source_channels: usize, output_channels: usize) -> Self {
+        let source_channels = source_channels.max(1);
+        let output_channels = output_channels.max(1);
+        let mut matrix = vec![vec![0.0; source_channels]; output_channels];
+
+        if source_channels == output_channels {
+            for (channel, row) in matrix.iter_mut().enumerate() {
+                row[channel] = 1.0;
+            }
+        } else {
+            let output = speaker_layout(output_channels);
+            for (source, speaker) in speaker_layout(source_channels).into_iter().enumerate() {
+                let Some(speaker) = speaker else { continue };
+                for (target, weight) in fold_speaker(speaker, &output) {
+                    if let Some(out) = output.iter().position(|s| *s == Some(target)) {
+                        matrix[out][source] += weight;
+                    }
+                }
+            }
+            for row in &mut matrix {
+                let sum: f32 = row.iter().sum();
+                if sum > 1.0 {
+                    row.iter_mut().for_each(|weight| *weight /= sum);
+                }
+            }
+        }
+
+        Self { source_channels, matrix }
+    }
+
+    pub fn output_channels(&self) -> usize {
+        self.matrix.len()
+    }
+
+    /// Whether samples pass through unchanged
+    pub fn is_identity(&self) -> bool {
+        self.source_channels == self.output_channels()
+    }
+
+    /// Map interleaved source samples to interleaved output samples
+    pub fn map(&self, samples: &[f32]) -> Vec<f32> {
+        if self.is_identity() {
+            return samples.to_vec();
+        }
+        let mut output = Vec::with_capacity(samples.len() / self.source_channels * self.output_channels());
+        for frame in samples.chunks_exact(self.source_channels) {
+            for row in &self.matrix {
+                output.push(row.iter().zip(frame).map(|(weight, sample)| weight * sample).sum());
+            }
+        }
+        output
+    }
+
+    /// Map a range of frames of planar source channels (resampler output) to interleaved
+    /// output samples
+    pub fn map_planar(&self, planar: &[Vec<f32>], frames: Range<usize>) -> Vec<f32> {
+        let mut output = Vec::with_capacity(frames.len() * self.output_channels());
+        for frame in frames {
+            for row in &self.matrix {
+                output.push(row.iter().zip(planar).map(|(weight, channel)| weight * channel[frame]).sum());
+            }
+        }
+        output
+    }
+}
+
 pub struct AudioOutput {
-    _stream: Stream,
+    // None for the test null output
+    _stream: Option<Stream>,
     producer: Arc<Mutex<RingProducer>>,
     sample_rate: u32,
     channels: u16,
     volume: Arc<Mutex<f32>>,
     clear_flag: Arc<AtomicBool>,
+    // Set by the stream's error callback (e.g. device unplugged or default device changed)
+    stream_error: Arc<AtomicBool>,
+    device_name: String,
+    /// Samples pushed into the ring buffer since the stream was opened
+    samples_written: AtomicU64,
+    /// Samples the device callback took out of the ring buffer (played or dropped by clear)
+    samples_consumed: Arc<AtomicU64>,
 }
 
 impl AudioOutput {
     /// Create a new audio output with default device
     pub fn new() -> Result<Self, String> {
-        let host = cpal::default_host();
-        
-        let device = host.default_output_device()
+        let device = cpal::default_host().default_output_device()
             .ok_or("No output device available")?;
+        Self::open(device)
+    }
+    
+    /// Create an output on the named device. If it isn't there any more (unplugged, renamed)
+    /// the default device is used instead; `device_name()` tells which one was opened.
+    pub fn new_with_device(name: &str) -> Result<Self, String> {
+        #[cfg(test)]
+        if name == NULL_OUTPUT_DEVICE {
+            return Ok(Self::null(48_000, 2));
+        }
+        match Self::find_device(name) {
+            Some(device) => Self::open(device),
+            None => {
+                eprintln!("[Audio] Output device '{}' not found, using the default device", name);
+                Self::new()
+            }
+        }
+    }
+    
+    /// Names of the output devices currently available
+    pub fn list_devices() -> Vec<String> {
+        match cpal::default_host().output_devices() {
+            Ok(devices) => devices.filter_map(|d| d.name().ok()).collect(),
+            Err(e) => {
+                eprintln!("[Audio] Failed to enumerate output devices: {}", e);
+                Vec::new()
+            }
+        }
+    }
+    
+    /// Name of the device `new_with_device(name)` (or `new()` for None) would open
+    pub fn resolve_device_name(name: Option<&str>) -> Option<String> {
+        if let Some(name) = name {
+            if Self::find_device(name).is_some() {
+                return Some(name.to_string());
+            }
+        }
+        cpal::default_host().default_output_device().and_then(|d| d.name().ok())
+    }
+    
+    fn find_device(name: &str) -> Option<cpal::Device> {
+        cpal::default_host()
+            .output_devices()
+            .ok()?
+            .find(|d| d.name().map(|n| n == name).unwrap_or(false))
+    }
+    
+    fn open(device: cpal::Device) -> Result<Self, String> {
+        let device_name = device.name().unwrap_or_else(|_| "Unknown device".to_string());
         
         let config = device.default_output_config()
             .map_err(|e| format!("Failed to get default output config: {}", e))?;
@@ -48,16 +282,22 @@ impl AudioOutput {
         let clear_flag = Arc::new(AtomicBool::new(false));
         let clear_flag_clone = clear_flag.clone();
         
+        let stream_error = Arc::new(AtomicBool::new(false));
+        let stream_error_clone = stream_error.clone();
+        
+        let samples_consumed = Arc::new(AtomicU64::new(0));
+        let samples_consumed_clone = samples_consumed.clone();
+        
         // Build the output stream based on sample format
         let stream = match config.sample_format() {
             cpal::SampleFormat::F32 => {
-                Self::build_stream::<f32>(&device, &config.into(), consumer, volume_clone, clear_flag_clone)?
+                Self::build_stream::<f32>(&device, &config.into(), consumer, volume_clone, clear_flag_clone, stream_error_clone, samples_consumed_clone)?
             }
             cpal::SampleFormat::I16 => {
-                Self::build_stream::<i16>(&device, &config.into(), consumer, volume_clone, clear_flag_clone)?
+                Self::build_stream::<i16>(&device, &config.into(), consumer, volume_clone, clear_flag_clone, stream_error_clone, samples_consumed_clone)?
             }
             cpal::SampleFormat::U16 => {
-                Self::build_stream::<u16>(&device, &config.into(), consumer, volume_clone, clear_flag_clone)?
+                Self::build_stream::<u16>(&device, &config.into(), consumer, volume_clone, clear_flag_clone, stream_error_clone, samples_consumed_clone)?
             }
             format => return Err(format!("Unsupported sample format: {:?}", format)),
         };
@@ -65,41 +305,113 @@ impl AudioOutput {
         stream.play().map_err(|e| format!("Failed to start stream: {}", e))?;
         
         Ok(Self {
-            _stream: stream,
+            _stream: Some(stream),
             producer,
             sample_rate,
             channels,
             volume,
             clear_flag,
+            stream_error,
+            device_name,
+            samples_written: AtomicU64::new(0),
+            samples_consumed,
         })
     }
     
+    /// An output without a device: a thread takes samples out of the buffer in real time,
+    /// the way a device callback would, until the output is dropped
+    #[cfg(test)]
+    fn null(sample_rate: u32, channels: u16) -> Self {
+        let (producer, consumer) = HeapRb::<f32>::new(RING_BUFFER_SIZE).split();
+        let producer = Arc::new(Mutex::new(producer));
+        let clear_flag = Arc::new(AtomicBool::new(false));
+        let samples_consumed = Arc::new(AtomicU64::new(0));
+        
+        let alive = Arc::downgrade(&producer);
+        let (clear, consumed) = (clear_flag.clone(), samples_consumed.clone());
+        std::thread::spawn(move || {
+            let mut consumer = consumer;
+            let per_tick = (sample_rate as usize * channels as usize) / 200; // 5 ms
+            while alive.upgrade().is_some() {
+                let limit = if clear.swap(false, Ordering::SeqCst) { usize::MAX } else { per_tick };
+                let mut taken = 0u64;
+                while (taken as usize) < limit && consumer.try_pop().is_some() {
+                    taken += 1;
+                }
+                consumed.fetch_add(taken, Ordering::SeqCst);
+                std::thread::sleep(std::time::Duration::from_millis(5));
+            }
+        });
+        
+        Self {
+            _stream: None,
+            producer,
+            sample_rate,
+            channels,
+            volume: Arc::new(Mutex::new(1.0)),
+            clear_flag,
+            stream_error: Arc::new(AtomicBool::new(false)),
+            device_name: NULL_OUTPUT_DEVICE.to_string(),
+            samples_written: AtomicU64::new(0),
+            samples_consumed,
+        }
+    }
+    
+    /// Format an output on the named device (default device for None, or if it's gone)
+    /// would use, without opening a stream
+    pub fn probe(name: Option<&str>) -> Result<OutputInfo, String> {
+        let device = name
+            .and_then(Self::find_device)
+            .or_else(|| cpal::default_host().default_output_device())
+            .ok_or("No output device available")?;
+        let device_name = device.name().unwrap_or_else(|_| "Unknown device".to_string());
+        let config = device.default_output_config()
+            .map_err(|e| format!("Failed to get default output config: {}", e))?;
+        
+        Ok(OutputInfo::new(device_name, config.sample_rate().0, config.channels()))
+    }
+    
     fn build_stream<T: cpal::SizedSample + cpal::FromSample<f32>>(
         device: &cpal::Device,
         config: &StreamConfig,
         consumer: Arc<Mutex<RingConsumer>>,
         volume: Arc<Mutex<f32>>,
         clear_flag: Arc<AtomicBool>,
+        stream_error: Arc<AtomicBool>,
+        samples_consumed: Arc<AtomicU64>,
     ) -> Result<Stream, String> {
         let stream = device.build_output_stream(
             config,
             move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
                 let mut consumer = consumer.lock();
                 let vol = *volume.lock();
+                let mut consumed = 0u64;
                 
                 // If clear flag is set, drain the buffer and output silence
                 if clear_flag.swap(false, Ordering::SeqCst) {
                     // Drain all samples from the buffer
-                    while consumer.try_pop().is_some() {}
+                    while consumer.try_pop().is_some() {
+                        consumed += 1;
+                    }
                 }
                 
                 for sample in data.iter_mut() {
-                    let value = consumer.try_pop().unwrap_or(0.0) * vol;
+                    // Underruns play silence, which doesn't count as consumed
+                    let value = match consumer.try_pop() {
+                        Some(value) => {
+                            consumed += 1;
+                            value * vol
+                        }
+                        None => 0.0,
+                    };
                     *sample = T::from_sample(value);
                 }
+                samples_consumed.fetch_add(consumed, Ordering::SeqCst);
             },
             move |err| {
                 eprintln!("Audio output error: {}", err);
+                // Let the playback loop know the stream is gone so it can rebuild it
+                stream_error.store(true, Ordering::SeqCst);
             },
             None,
         ).map_err(|e| format!("Failed to build output stream: {}", e))?;
@@ -122,6 +434,7 @@ impl AudioOutput {
             }
         }
         
+        self.samples_written.fetch_add(written as u64, Ordering::SeqCst);
         written
     }
     
@@ -133,6 +446,9 @@ impl AudioOutput {
             let written = self.write(remaining);
             if written > 0 {
                 remaining = &remaining[written..];
+            } else if self.has_stream_error() {
+                // Stream is dead, nothing will drain the buffer
+                return;
             } else {
                 // Buffer full, wait a bit
                 std::thread::sleep(std::time::Duration::from_millis(1));
@@ -140,7 +456,19 @@ impl AudioOutput {
         }
     }
     
+    /// Frames written to the buffer since the stream was opened
+    pub fn frames_written(&self) -> u64 {
+        self.samples_written.load(Ordering::SeqCst) / self.channels.max(1) as u64
+    }
+    
+    /// Frames the device has taken from the buffer since the stream was opened.
+    /// Frames dropped by `clear` count too, so written - consumed is what's still buffered.
+    pub fn frames_consumed(&self) -> u64 {
+        self.samples_consumed.load(Ordering::SeqCst) / self.channels.max(1) as u64
+    }
+    
     /// Get available space in the buffer
+    #[allow(dead_code)]
     pub fn available_space(&self) -> usize {
         let producer = self.producer.lock();
         producer.vacant_len()
@@ -152,6 +480,21 @@ impl AudioOutput {
         self.clear_flag.store(true, Ordering::SeqCst);
     }
     
+    /// Check whether the output stream reported an error and needs rebuilding
+    pub fn has_stream_error(&self) -> bool {
+        self.stream_error.load(Ordering::SeqCst)
+    }
+    
+    /// Get the name of the output device
+    pub fn device_name(&self) -> &str {
+        &self.device_name
+    }
+    
+    /// Device, format and buffer length of this output
+    pub fn info(&self) -> OutputInfo {
+        OutputInfo::new(self.device_name.clone(), self.sample_rate, self.channels)
+    }
+    
     /// Get the output sample rate
     pub fn sample_rate(&self) -> u32 {
         self.sample_rate
@@ -162,13 +505,72 @@ impl AudioOutput {
         self.channels
     }
     
+    /// Channel mapping from a track with `source_channels` channels to this device
+    pub fn channel_map(&self, source_channels: usize) -> ChannelMap {
+        ChannelMap::new(source_channels, self.channels as usize)
+    }
+    
     /// Set the output volume (0.0 to 1.0)
     pub fn set_volume(&self, vol: f32) {
         *self.volume.lock() = vol.clamp(0.0, 1.0);
     }
     
     /// Get the current volume
+    #[allow(dead_code)]
     pub fn volume(&self) -> f32 {
         *self.volume.lock()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_info_buffer_length() {
+        // The ring buffer holds 250ms of stereo audio at 48kHz
+        let info = OutputInfo::new("Speakers".to_string(), 48000, 2);
+        assert!((info.buffer_ms - 250.0).abs() < 0.01);
+
+        let info = OutputInfo::new("Speakers".to_string(), 44100, 2);
+        assert!((info.buffer_ms - 272.1).abs() < 0.1);
+
+        assert_eq!(OutputInfo::new("None".to_string(), 0, 0).buffer_ms, 0.0);
+    }
+
+    #[test]
+    fn test_mono_is_duplicated_to_stereo() {
+        let map = ChannelMap::new(1, 2);
+        assert_eq!(map.map(&[0.5, -0.25]), vec![0.5, 0.5, -0.25, -0.25]);
+        assert_eq!(map.map_planar(&[vec![0.5, -0.25]], 0..2), vec![0.5, 0.5, -0.25, -0.25]);
+
+        // Stereo to a mono device averages the sides
+        assert_eq!(ChannelMap::new(2, 1).map(&[0.5, 0.25]), vec![0.375]);
+        assert!(ChannelMap::new(2, 2).is_identity());
+    }
+
+    #[test]
+    fn test_surround_downmix_coefficients() {
+        // FL FR C LFE BL BR
+        let map = ChannelMap::new(6, 2);
+        let norm = 1.0 + 2.0 * FOLD_GAIN;
+        let expected_left = [1.0 / norm, 0.0, FOLD_GAIN / norm, 0.0, FOLD_GAIN / norm, 0.0];
+        let expected_right = [0.0, 1.0 / norm, FOLD_GAIN / norm, 0.0, 0.0, FOLD_GAIN / norm];
+        for (row, expected) in map.matrix.iter().zip([expected_left, expected_right]) {
+            for (weight, expected) in row.iter().zip(expected) {
+                assert!((weight - expected).abs() < 1e-6, "{:?}", map.matrix);
+            }
+        }
+
+        // Full scale on every channel stays within full scale
+        let mixed = map.map(&[1.0; 6]);
+        assert_eq!(mixed.len(), 2);
+        assert!(mixed.iter().all(|&s| s <= 1.0 + 1e-6));
+
+        // LFE alone is left out
+        assert_eq!(map.map(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0]), vec![0.0, 0.0]);
+
+        // A stereo track on a 5.1 device plays on the front speakers only
+        assert_eq!(ChannelMap::new(2, 6).map(&[0.5, 0.25]), vec![0.5, 0.25, 0.0, 0.0, 0.0, 0.0]);
+    }
+}
@@ -1,15 +1,30 @@
 // Audio player using Symphonia for decoding and cpal for output
 
+use super::crossfeed::{Crossfeed, CrossfeedLevel};
+use super::equalizer::{Equalizer, EqualizerParams};
 use super::decoder::AudioDecoder;
-use super::output::AudioOutput;
+use super::fade::{Crossfade, FadeEnvelope, FadeParams};
+use super::output::{AudioOutput, ChannelMap, OutputInfo};
+use super::resample::PacketResampler;
+use crate::error::AppError;
+use crate::settings::FadeSettings;
 use parking_lot::{Mutex, RwLock};
-use rubato::{Resampler, SincFixedIn, SincInterpolationType, SincInterpolationParameters, WindowFunction};
 use std::path::PathBuf;
-use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU8, Ordering};
 use std::sync::Arc;
 use std::thread::{self, JoinHandle};
 use std::time::Duration;
 
+/// Number of times to try rebuilding the output stream after a device error
+const OUTPUT_RECOVERY_ATTEMPTS: u32 = 5;
+/// Delay between output rebuild attempts (gives the OS time to settle the new default device)
+const OUTPUT_RECOVERY_DELAY_MS: u64 = 300;
+
+/// Callback invoked with the new device name after the output stream was rebuilt
+type DeviceChangedCallback = Arc<Mutex<Option<Box<dyn Fn(String) + Send + Sync + 'static>>>>;
+/// Callback invoked with the file path when a track played to its end
+type TrackEndedCallback = Arc<Mutex<Option<Box<dyn Fn(String) + Send + Sync + 'static>>>>;
+
 /// Player state that can be serialized and sent to frontend
 #[derive(Clone, Debug, serde::Serialize)]
 pub struct PlayerState {
@@ -35,6 +50,88 @@ struct PreloadedDecoder {
 // which are all Send. We transfer ownership across threads.
 unsafe impl Send for PreloadedDecoder {}
 
+/// Next track to crossfade into, picked up by the playback loop
+struct CrossfadeRequest {
+    next: PreloadedDecoder,
+    duration_ms: i64,
+}
+
+/// The previous track while it fades out under the next one. Decodes on demand, as many
+/// output samples as the incoming track produced.
+struct FadingOut {
+    decoder: AudioDecoder,
+    resampler: Option<PacketResampler>,
+    channel_map: ChannelMap,
+    /// Normalization gain of the track as linear multiplier
+    track_gain: f32,
+    /// Output samples decoded but not mixed yet
+    pending: Vec<f32>,
+    finished: bool,
+}
+
+impl FadingOut {
+    /// The next `len` output samples, padded with silence past the end of the track
+    fn take(&mut self, len: usize) -> Vec<f32> {
+        while self.pending.len() < len && !self.finished {
+            match self.decoder.decode_next() {
+                Ok(Some(samples)) => {
+                    let converted = match self.resampler.as_mut() {
+                        Some(resampler) => resampler.process(&samples, &self.channel_map),
+                        None => self.channel_map.map(&samples),
+                    };
+                    self.pending.extend(converted);
+                }
+                Ok(None) => self.finished = true,
+                Err(e) => {
+                    eprintln!("Decode error in fading out track: {}", e);
+                    self.finished = true;
+                }
+            }
+        }
+        let mut taken: Vec<f32> = self.pending.drain(..len.min(self.pending.len())).collect();
+        taken.resize(len, 0.0);
+        taken
+    }
+}
+
+/// Gain that brings audio meant for `volume` to the right level on an output set to `output_volume`
+fn relative_gain(volume: f32, output_volume: f32) -> f32 {
+    if output_volume > 0.0 {
+        volume / output_volume
+    } else {
+        1.0
+    }
+}
+
+/// Maps frames the output device has consumed to a track position. Decoding runs ahead of
+/// the device by the ring buffer, so this is the position that's actually audible.
+#[derive(Debug, Clone, Copy)]
+struct PlaybackClock {
+    /// Track position of the frame written right after `anchor_frame`
+    anchor_ms: i64,
+    /// `AudioOutput::frames_written` when the anchor was set
+    anchor_frame: u64,
+    sample_rate: u32,
+}
+
+impl PlaybackClock {
+    fn new(anchor_ms: i64, anchor_frame: u64, sample_rate: u32) -> Self {
+        Self { anchor_ms, anchor_frame, sample_rate }
+    }
+
+    /// Start counting from whatever is written to `output` next
+    fn at(anchor_ms: i64, output: &AudioOutput) -> Self {
+        Self::new(anchor_ms, output.frames_written(), output.sample_rate())
+    }
+
+    /// Position once the device has consumed `frames_consumed` frames. Frames before the
+    /// anchor (the tail of the previous track, audio dropped by a seek) don't move it.
+    fn position_ms(&self, frames_consumed: u64) -> i64 {
+        let played = frames_consumed.saturating_sub(self.anchor_frame);
+        self.anchor_ms + (played as f64 * 1000.0 / self.sample_rate.max(1) as f64) as i64
+    }
+}
+
 /// Audio player with Symphonia decoding and cpal output
 pub struct Player {
     // Playback state flags
@@ -69,11 +166,44 @@ pub struct Player {
     
     // Track ended callback trigger
     track_ended: Arc<AtomicBool>,
+    // Notified once when a track plays to its end (not on stop)
+    track_ended_callback: TrackEndedCallback,
     
     // Gapless playback: pre-opened decoder for the next track
     next_decoder: Arc<Mutex<Option<PreloadedDecoder>>>,
     // Signals that a gapless transition just occurred
     gapless_transition: Arc<AtomicBool>,
+    // Whether next tracks may be preloaded (PlaybackSettings.gapless)
+    gapless: Arc<AtomicBool>,
+    
+    // Crossfade length between queue tracks in ms, 0 = off (PlaybackSettings.crossfade_ms)
+    crossfade_ms: Arc<AtomicI64>,
+    // Next track to crossfade into, handed to the playback thread
+    crossfade_request: Arc<Mutex<Option<CrossfadeRequest>>>,
+    
+    // Notified when playback moves to a new output device
+    device_changed_callback: DeviceChangedCallback,
+    // Output device picked by the user, None = system default
+    output_device: Arc<RwLock<Option<String>>>,
+    // Asks the playback thread to reopen the output on `output_device`
+    device_switch_request: Arc<AtomicBool>,
+    
+    // Format of the open output stream, None when nothing is playing
+    output_info: Arc<RwLock<Option<OutputInfo>>>,
+    
+    // Headphone crossfeed level (CrossfeedLevel as u8)
+    crossfeed_level: Arc<AtomicU8>,
+    
+    // Graphic equalizer switch and band gains
+    equalizer: Arc<RwLock<EqualizerParams>>,
+    
+    // Fade-in/out lengths and curve
+    fade: Arc<RwLock<FadeParams>>,
+    // Asks the playback thread to fade out and then stop
+    fade_stop_request: Arc<AtomicBool>,
+    // Set by stop() until the next track starts. Unlike the two requests above the
+    // playback thread never clears it, so its exit can tell a stopped track from an ended one
+    stopping: Arc<AtomicBool>,
 }
 
 impl Player {
@@ -94,13 +224,26 @@ impl Player {
             seek_request: Arc::new(AtomicI64::new(-1)),
             playback_thread: Mutex::new(None),
             track_ended: Arc::new(AtomicBool::new(false)),
+            track_ended_callback: Arc::new(Mutex::new(None)),
             next_decoder: Arc::new(Mutex::new(None)),
             gapless_transition: Arc::new(AtomicBool::new(false)),
+            gapless: Arc::new(AtomicBool::new(false)),
+            crossfade_ms: Arc::new(AtomicI64::new(0)),
+            crossfade_request: Arc::new(Mutex::new(None)),
+            device_changed_callback: Arc::new(Mutex::new(None)),
+            output_device: Arc::new(RwLock::new(None)),
+            device_switch_request: Arc::new(AtomicBool::new(false)),
+            output_info: Arc::new(RwLock::new(None)),
+            crossfeed_level: Arc::new(AtomicU8::new(CrossfeedLevel::Off.to_u8())),
+            equalizer: Arc::new(RwLock::new(EqualizerParams::default())),
+            fade: Arc::new(RwLock::new(FadeParams::default())),
+            fade_stop_request: Arc::new(AtomicBool::new(false)),
+            stopping: Arc::new(AtomicBool::new(false)),
         }
     }
     
     /// Start playing a file with optional track-specific normalization gain
-    pub fn play_with_gain(&self, file_path: PathBuf, track_gain_db: Option<f32>) -> Result<(), String> {
+    pub fn play_with_gain(&self, file_path: PathBuf, track_gain_db: Option<f32>) -> Result<(), AppError> {
         // Set track gain before starting playback
         let gain_db = track_gain_db.unwrap_or(0.0);
         *self.track_gain_db.write() = gain_db;
@@ -117,9 +260,9 @@ impl Player {
     }
     
     /// Start playing a file
-    pub fn play(&self, file_path: PathBuf) -> Result<(), String> {
-        // Stop any current playback first
-        self.stop();
+    pub fn play(&self, file_path: PathBuf) -> Result<(), AppError> {
+        // Stop any current playback first, cutting short a fade-out still running
+        self.halt();
         
         // Reset track ended flag
         self.track_ended.store(false, Ordering::SeqCst);
@@ -127,6 +270,7 @@ impl Player {
         // Clear any preloaded next track
         *self.next_decoder.lock() = None;
         self.gapless_transition.store(false, Ordering::SeqCst);
+        *self.crossfade_request.lock() = None;
         
         // Update current file
         *self.current_file.write() = Some(file_path.clone());
@@ -135,8 +279,11 @@ impl Player {
         self.is_playing.store(true, Ordering::SeqCst);
         self.is_paused.store(false, Ordering::SeqCst);
         self.should_stop.store(false, Ordering::SeqCst);
+        self.fade_stop_request.store(false, Ordering::SeqCst);
+        self.stopping.store(false, Ordering::SeqCst);
         self.position_ms.store(0, Ordering::SeqCst);
         self.seek_request.store(-1, Ordering::SeqCst);
+        self.device_switch_request.store(false, Ordering::SeqCst);
         
         // Clone Arcs for the playback thread
         let is_playing = self.is_playing.clone();
@@ -150,9 +297,20 @@ impl Player {
         let normalization_enabled = self.normalization_enabled.clone();
         let seek_request = self.seek_request.clone();
         let track_ended = self.track_ended.clone();
+        let track_ended_callback = self.track_ended_callback.clone();
+        let stopping = self.stopping.clone();
         let next_decoder = self.next_decoder.clone();
         let gapless_transition = self.gapless_transition.clone();
+        let crossfade_request = self.crossfade_request.clone();
         let current_file = self.current_file.clone();
+        let device_changed_callback = self.device_changed_callback.clone();
+        let output_device = self.output_device.clone();
+        let device_switch_request = self.device_switch_request.clone();
+        let output_info = self.output_info.clone();
+        let crossfeed_level = self.crossfeed_level.clone();
+        let equalizer = self.equalizer.clone();
+        let fade = self.fade.clone();
+        let fade_stop_request = self.fade_stop_request.clone();
         
         // Spawn playback thread
         let handle = thread::spawn(move || {
@@ -161,8 +319,8 @@ impl Player {
                 is_playing.clone(),
                 is_paused,
                 should_stop,
-                position_ms,
-                duration_ms,
+                position_ms.clone(),
+                duration_ms.clone(),
                 volume,
                 track_gain,
                 track_gain_db_arc,
@@ -171,15 +329,38 @@ impl Player {
                 track_ended.clone(),
                 next_decoder,
                 gapless_transition,
-                current_file,
+                crossfade_request,
+                current_file.clone(),
+                device_changed_callback,
+                output_device,
+                device_switch_request,
+                output_info.clone(),
+                crossfeed_level,
+                equalizer,
+                fade,
+                fade_stop_request,
             ) {
                 eprintln!("Playback error: {}", e);
             }
+            *output_info.write() = None;
             
-            // Mark track as ended BEFORE marking as not playing
-            // This prevents race condition where frontend sees is_playing=false
-            // but track_ended hasn't been set yet
-            track_ended.store(true, Ordering::SeqCst);
+            if stopping.load(Ordering::SeqCst) {
+                // A faded stop() returns before this thread is done, so clear up here
+                *current_file.write() = None;
+                position_ms.store(0, Ordering::SeqCst);
+                duration_ms.store(0, Ordering::SeqCst);
+            } else {
+                // A track that wasn't stopped ended (or failed to play)
+                // Mark track as ended BEFORE marking as not playing
+                // This prevents race condition where frontend sees is_playing=false
+                // but track_ended hasn't been set yet
+                track_ended.store(true, Ordering::SeqCst);
+                if let Some(file) = current_file.read().clone() {
+                    if let Some(ref callback) = *track_ended_callback.lock() {
+                        callback(file.to_string_lossy().to_string());
+                    }
+                }
+            }
             is_playing.store(false, Ordering::SeqCst);
         });
         
@@ -189,6 +370,7 @@ impl Player {
     }
     
     /// The main playback loop running in a separate thread
+    #[allow(clippy::too_many_arguments)]
     fn playback_loop(
         file_path: PathBuf,
         is_playing: Arc<AtomicBool>,
@@ -204,8 +386,17 @@ impl Player {
         _track_ended: Arc<AtomicBool>,
         next_decoder: Arc<Mutex<Option<PreloadedDecoder>>>,
         gapless_transition: Arc<AtomicBool>,
+        crossfade_request: Arc<Mutex<Option<CrossfadeRequest>>>,
         current_file: Arc<RwLock<Option<PathBuf>>>,
-    ) -> Result<(), String> {
+        device_changed_callback: DeviceChangedCallback,
+        output_device: Arc<RwLock<Option<String>>>,
+        device_switch_request: Arc<AtomicBool>,
+        output_info: Arc<RwLock<Option<OutputInfo>>>,
+        crossfeed_level: Arc<AtomicU8>,
+        equalizer_params: Arc<RwLock<EqualizerParams>>,
+        fade: Arc<RwLock<FadeParams>>,
+        fade_stop_request: Arc<AtomicBool>,
+    ) -> Result<(), AppError> {
         // Open the audio file
         let mut decoder = AudioDecoder::open(&file_path)?;
         
@@ -215,13 +406,16 @@ impl Player {
         }
         
         // Initialize audio output
-        let output = AudioOutput::new()?;
+        let mut output = Self::open_output(&output_device)?;
+        *output_info.write() = Some(output.info());
         
         // Get rates and channels
         let mut input_sample_rate = decoder.sample_rate();
         let mut input_channels = decoder.channels();
-        let output_sample_rate = output.sample_rate();
-        let output_channels = output.channels() as usize;
+        let mut output_sample_rate = output.sample_rate();
+        let mut output_channels = output.channels() as usize;
+        // Up/downmix from the track's channels to the device's, rebuilt when either changes
+        let mut channel_map = output.channel_map(input_channels);
         
         eprintln!(
             "Audio: input {}Hz {}ch -> output {}Hz {}ch",
@@ -229,21 +423,31 @@ impl Player {
         );
         
         // Create resampler if sample rates don't match
-        let mut needs_resample = input_sample_rate != output_sample_rate;
-        let chunk_size = 1024;
-        let mut resampler: Option<SincFixedIn<f32>> = if needs_resample {
-            Some(Self::create_resampler(input_sample_rate, output_sample_rate, input_channels, chunk_size)?)
-        } else {
-            None
-        };
+        let mut resampler = Self::create_resampler(input_sample_rate, output_sample_rate, input_channels)?;
+        
+        // Crossfeed runs on the final output samples, so it follows the output rate
+        let mut crossfeed = Crossfeed::new(output_sample_rate);
+        // So does the equalizer, which runs just before it
+        let mut equalizer = Equalizer::new(output_sample_rate);
+        
+        // Fade-in from the start, fade-out before the end or when stopping
+        let mut envelope = FadeEnvelope::new(*fade.read());
+        // Position a fade-out requested by stop() ends at
+        let mut stop_at_ms: Option<f64> = None;
+        
+        // The previous track while it fades out under this one, and how far the crossfade got
+        let mut fading_out: Option<(FadingOut, Crossfade)> = None;
+        // Normalization gain the output volume stays at after a crossfade. The ring buffer still
+        // holds audio meant for it, so the new track's gain goes into its samples instead.
+        let mut held_track_gain: Option<f32> = None;
         
         // Calculate samples per millisecond for position tracking (at input rate)
         let mut samples_per_ms = (input_sample_rate as f64 * input_channels as f64) / 1000.0;
         
         let mut samples_decoded: i64 = 0;
-        
-        // Buffer for accumulating samples for the resampler (planar format)
-        let mut input_buffer: Vec<Vec<f32>> = vec![Vec::new(); input_channels];
+        // Reported position follows the device, never ahead of what's been decoded
+        let mut clock = PlaybackClock::at(0, &output);
+
         
         // Main decode/playback loop
         while !should_stop.load(Ordering::SeqCst) {
@@ -253,6 +457,65 @@ impl Player {
                 continue;
             }
             
+            let decoded_ms = (samples_decoded as f64 / samples_per_ms) as i64;
+            position_ms.store(clock.position_ms(output.frames_consumed()).min(decoded_ms), Ordering::SeqCst);
+            
+            // Recover from a dead output stream (e.g. headphones plugged in / default device changed),
+            // or move to the device the user picked
+            let switch_requested = device_switch_request.swap(false, Ordering::SeqCst);
+            if output.has_stream_error() || switch_requested {
+                let rebuilt = if switch_requested {
+                    Self::open_output(&output_device)
+                } else {
+                    Self::rebuild_output(&should_stop, &output_device)
+                };
+                match rebuilt {
+                    Ok(new_output) => {
+                        output = new_output;
+                        *output_info.write() = Some(output.info());
+                        output_sample_rate = output.sample_rate();
+                        output_channels = output.channels() as usize;
+                        channel_map = output.channel_map(input_channels);
+                        
+                        eprintln!(
+                            "[Player] Output rebuilt on '{}': {}Hz {}ch",
+                            output.device_name(), output_sample_rate, output_channels
+                        );
+                        
+                        // The new device may run at a different rate
+                        resampler = Self::create_resampler(input_sample_rate, output_sample_rate, input_channels)?;
+                        crossfeed = Crossfeed::new(output_sample_rate);
+                        equalizer = Equalizer::new(output_sample_rate);
+                        // Nothing is buffered on the new device, so a crossfade just ends
+                        fading_out = None;
+                        held_track_gain = None;
+                        
+                        // Samples queued on the old device are lost, so resume from the current position
+                        let resume_pos = position_ms.load(Ordering::SeqCst);
+                        match decoder.seek(resume_pos) {
+                            Ok(actual_pos) => {
+                                position_ms.store(actual_pos as i64, Ordering::SeqCst);
+                                samples_decoded = (actual_pos as f64 * samples_per_ms) as i64;
+                                clock = PlaybackClock::at(actual_pos as i64, &output);
+                            }
+                            Err(e) => {
+                                eprintln!("[Player] Failed to resume after device change: {}", e);
+                                clock = PlaybackClock::at((samples_decoded as f64 / samples_per_ms) as i64, &output);
+                            }
+                        }
+                        
+                        if let Some(ref cb) = *device_changed_callback.lock() {
+                            cb(output.device_name().to_string());
+                        }
+                    }
+                    Err(e) => {
+                        // Fallback: give up and stop cleanly rather than spinning on a dead stream
+                        eprintln!("[Player] {}", e);
+                        break;
+                    }
+                }
+            }
+            
             // Handle seek request
             let seek_pos = seek_request.swap(-1, Ordering::SeqCst);
             if seek_pos >= 0 {
@@ -262,13 +525,16 @@ impl Player {
                         position_ms.store(actual_pos as i64, Ordering::SeqCst);
                         samples_decoded = (actual_pos as f64 * samples_per_ms) as i64;
                         // Clear buffers
-                        for buf in &mut input_buffer {
-                            buf.clear();
-                        }
                         if let Some(ref mut rs) = resampler {
                             rs.reset();
                         }
+                        crossfeed.reset();
+                        equalizer.reset();
+                        envelope.seeked(actual_pos as f64);
+                        fading_out = None;
+                        held_track_gain = None;
                         output.clear();
+                        clock = PlaybackClock::at(actual_pos as i64, &output);
                     }
                     Err(e) => {
                         eprintln!("Seek failed: {}", e);
@@ -276,106 +542,146 @@ impl Player {
                 }
             }
             
+            // Start a crossfade: the next track becomes the current one right away while the
+            // previous one fades out underneath it. A crossfade still running is cut short.
+            let request = crossfade_request.lock().take();
+            if let Some(request) = request {
+                let next = request.next;
+                let outgoing_gain = *track_gain.read();
+                held_track_gain.get_or_insert(outgoing_gain);
+                let outgoing = FadingOut {
+                    decoder: std::mem::replace(&mut decoder, next.decoder),
+                    resampler: resampler.take(),
+                    channel_map: channel_map.clone(),
+                    track_gain: outgoing_gain,
+                    pending: Vec::new(),
+                    finished: false,
+                };
+                
+                eprintln!("Crossfade to: {:?}", next.file_path);
+                
+                *track_gain_db_arc.write() = next.gain_db;
+                let gain_linear = if next.gain_db.abs() < 0.001 {
+                    1.0
+                } else {
+                    10.0_f32.powf(next.gain_db / 20.0)
+                };
+                *track_gain.write() = gain_linear;
+                *current_file.write() = Some(next.file_path);
+                if let Some(dur) = decoder.duration_ms() {
+                    duration_ms.store(dur, Ordering::SeqCst);
+                }
+                
+                // Position counts from where the new track starts in the buffer
+                samples_decoded = 0;
+                position_ms.store(0, Ordering::SeqCst);
+                clock = PlaybackClock::at(0, &output);
+                
+                input_sample_rate = decoder.sample_rate();
+                input_channels = decoder.channels();
+                samples_per_ms = (input_sample_rate as f64 * input_channels as f64) / 1000.0;
+                resampler = Self::create_resampler(input_sample_rate, output_sample_rate, input_channels)
+                    .unwrap_or_else(|e| {
+                        eprintln!("Failed to create resampler for crossfade: {}", e);
+                        None
+                    });
+                channel_map = output.channel_map(input_channels);
+                // The crossfade is the fade-in
+                envelope.restart_track(false);
+                
+                let length_frames = request.duration_ms.max(0) as u64 * output_sample_rate as u64 / 1000;
+                fading_out = Some((outgoing, Crossfade::new(length_frames)));
+            }
+            
             // Apply combined volume: user volume * track normalization gain
             // If normalization is disabled, track_gain is treated as 1.0
             let user_vol = *volume.read();
-            let norm_gain = if normalization_enabled.load(Ordering::SeqCst) {
-                *track_gain.read()
-            } else {
-                1.0
-            };
+            let normalization = normalization_enabled.load(Ordering::SeqCst);
             // Clamp the combined gain to prevent clipping (max 1.0)
-            let combined_vol = (user_vol * norm_gain).min(1.0);
-            output.set_volume(combined_vol);
+            let volume_for = |gain: f32| (user_vol * if normalization { gain } else { 1.0 }).min(1.0);
+            let combined_vol = volume_for(*track_gain.read());
+            let output_vol = held_track_gain.map_or(combined_vol, volume_for);
+            output.set_volume(output_vol);
+            let stream_gain = relative_gain(combined_vol, output_vol);
+            
+            let level = CrossfeedLevel::from_u8(crossfeed_level.load(Ordering::SeqCst));
+            if level != crossfeed.level() {
+                crossfeed.set_level(level);
+            }
+            equalizer.set_params(*equalizer_params.read());
+            
+            envelope.set_params(*fade.read());
+            // No fade-out at the end when the next track follows gaplessly
+            let track_end = duration_ms.load(Ordering::SeqCst);
+            if track_end > 0 && next_decoder.lock().is_none() {
+                envelope.set_track_end(Some(track_end as f64));
+            } else {
+                envelope.set_track_end(None);
+            }
+            
+            if fade_stop_request.swap(false, Ordering::SeqCst) {
+                let stop_at = envelope.fade_out_now(samples_decoded as f64 / samples_per_ms);
+                stop_at_ms = Some(stop_at);
+            }
             
             // Decode next packet
             match decoder.decode_next() {
                 Ok(Some(interleaved_samples)) => {
-                    // Update position based on input samples
-                    samples_decoded += interleaved_samples.len() as i64;
-                    let pos = (samples_decoded as f64 / samples_per_ms) as i64;
-                    position_ms.store(pos, Ordering::SeqCst);
+                    let packet_start_ms = samples_decoded as f64 / samples_per_ms;
                     
-                    // Convert interleaved to planar for resampling
-                    let frame_count = interleaved_samples.len() / input_channels;
+                    samples_decoded += interleaved_samples.len() as i64;
                     
                     // Prepare output samples
-                    let output_samples = if needs_resample {
-                        // De-interleave and accumulate into planar buffers
-                        for frame in 0..frame_count {
-                            for ch in 0..input_channels {
-                                input_buffer[ch].push(interleaved_samples[frame * input_channels + ch]);
-                            }
-                        }
-                        
-                        // Process in chunks when we have enough samples
-                        let mut all_resampled: Vec<f32> = Vec::new();
-                        
-                        while input_buffer[0].len() >= chunk_size {
-                            // Extract exactly chunk_size frames
-                            let mut chunk: Vec<Vec<f32>> = vec![Vec::with_capacity(chunk_size); input_channels];
-                            for ch in 0..input_channels {
-                                chunk[ch] = input_buffer[ch].drain(..chunk_size).collect();
-                            }
-                            
-                            // Resample the chunk
-                            if let Some(ref mut rs) = resampler {
-                                match rs.process(&chunk, None) {
-                                    Ok(resampled) => {
-                                        let interleaved = Self::interleave_and_convert_channels(&resampled, output_channels);
-                                        all_resampled.extend(interleaved);
-                                    }
-                                    Err(e) => {
-                                        eprintln!("Resample error: {}", e);
-                                    }
-                                }
-                            }
-                        }
-                        
-                        all_resampled
-                    } else {
+                    let mut output_samples = match resampler.as_mut() {
+                        Some(rs) => rs.process(&interleaved_samples, &channel_map),
                         // No resampling needed, but might need channel conversion
-                        Self::convert_channels(&interleaved_samples, input_channels, output_channels)
+                        None => channel_map.map(&interleaved_samples),
                     };
                     
                     // Write samples to output (blocking to prevent buffer overrun)
                     if !output_samples.is_empty() {
+                        envelope.apply(&mut output_samples, output_channels, packet_start_ms, samples_decoded as f64 / samples_per_ms);
+                        Self::apply_gain(&mut output_samples, stream_gain);
+                        if let Some((outgoing, crossfade)) = fading_out.as_mut() {
+                            let tail = outgoing.take(output_samples.len());
+                            let outgoing_gain = relative_gain(volume_for(outgoing.track_gain), output_vol);
+                            crossfade.mix(&mut output_samples, &tail, output_channels, outgoing_gain);
+                            if crossfade.is_finished() {
+                                fading_out = None;
+                            }
+                        }
+                        equalizer.process(&mut output_samples, output_channels);
+                        crossfeed.process(&mut output_samples, output_channels);
                         output.write_blocking(&output_samples);
                     }
+                    
+                    // Faded out after a stop request
+                    if stop_at_ms.is_some_and(|stop_at| samples_decoded as f64 / samples_per_ms >= stop_at) {
+                        // Let the buffered end of the fade play out
+                        thread::sleep(Duration::from_millis(output.info().buffer_ms as u64));
+                        break;
+                    }
                 }
                 Ok(None) => {
                     // End of file - flush remaining samples in resampler buffer
-                    if needs_resample && !input_buffer[0].is_empty() {
-                        // Pad remaining samples to chunk size
-                        let remaining = input_buffer[0].len();
-                        for ch in 0..input_channels {
-                            input_buffer[ch].resize(chunk_size, 0.0);
-                        }
+                    if let Some(ref mut rs) = resampler {
+                        let mut final_samples = rs.flush(&channel_map);
                         
-                        if let Some(ref mut rs) = resampler {
-                            if let Ok(resampled) = rs.process(&input_buffer, None) {
-                                // Only output the valid portion
-                                let valid_ratio = remaining as f64 / chunk_size as f64;
-                                let valid_frames = (resampled[0].len() as f64 * valid_ratio) as usize;
-                                
-                                let mut final_samples: Vec<f32> = Vec::with_capacity(valid_frames * output_channels);
-                                for frame in 0..valid_frames {
-                                    for out_ch in 0..output_channels {
-                                        if out_ch < resampled.len() {
-                                            final_samples.push(resampled[out_ch][frame]);
-                                        } else if !resampled.is_empty() {
-                                            final_samples.push(resampled[0][frame]);
-                                        }
-                                    }
-                                }
-                                
-                                if !final_samples.is_empty() {
-                                    output.write_blocking(&final_samples);
-                                }
-                            }
+                        if !final_samples.is_empty() {
+                            let end_ms = samples_decoded as f64 / samples_per_ms;
+                            envelope.apply(&mut final_samples, output_channels, end_ms, end_ms);
+                            Self::apply_gain(&mut final_samples, stream_gain);
+                            equalizer.process(&mut final_samples, output_channels);
+                            crossfeed.process(&mut final_samples, output_channels);
+                            output.write_blocking(&final_samples);
                         }
                     }
                     
+                    // A crossfade requested right at the end starts on the next iteration
+                    if crossfade_request.lock().is_some() {
+                        continue;
+                    }
+                    
                     // Check for gapless next track (pre-opened decoder)
                     let preloaded = next_decoder.lock().take();
                     if let Some(preloaded) = preloaded {
@@ -404,9 +710,10 @@ impl Player {
                                     duration_ms.store(dur, Ordering::SeqCst);
                                 }
                                 
-                                // Reset position
+                                // Reset position, counting from where the new track starts in the buffer
                                 samples_decoded = 0;
                                 position_ms.store(0, Ordering::SeqCst);
+                                clock = PlaybackClock::at(0, &output);
                                 
                                 // Check if resampler needs to be recreated
                                 let new_input_sr = new_decoder.sample_rate();
@@ -417,24 +724,21 @@ impl Player {
                                     input_channels = new_input_ch;
                                     samples_per_ms = (input_sample_rate as f64 * input_channels as f64) / 1000.0;
                                     
-                                    needs_resample = input_sample_rate != output_sample_rate;
-                                    resampler = if needs_resample {
-                                        match Self::create_resampler(input_sample_rate, output_sample_rate, input_channels, chunk_size) {
-                                            Ok(rs) => Some(rs),
-                                            Err(e) => {
-                                                eprintln!("Failed to create resampler for gapless: {}", e);
-                                                None
-                                            }
-                                        }
-                                    } else {
-                                        None
-                                    };
+                                    resampler = Self::create_resampler(input_sample_rate, output_sample_rate, input_channels)
+                                        .unwrap_or_else(|e| {
+                                            eprintln!("Failed to create resampler for gapless: {}", e);
+                                            None
+                                        });
                                 } else if let Some(ref mut rs) = resampler {
                                     rs.reset();
                                 }
                                 
-                                // Reset input buffer for new channel count
-                                input_buffer = vec![Vec::new(); input_channels];
+                                // Reset channel mapping for the new channel count
+                                channel_map = output.channel_map(input_channels);
+                                crossfeed.reset();
+                                equalizer.reset();
+                                // The tracks run into each other, so no fade-in
+                                envelope.restart_track(false);
                                 
                                 // Replace decoder and continue the loop
                                 decoder = new_decoder;
@@ -451,7 +755,9 @@ impl Player {
                     
                     // Wait for buffer to drain before exiting
                     thread::sleep(Duration::from_millis(100));
-                    break;
+                    if crossfade_request.lock().is_none() {
+                        break;
+                    }
                 }
                 Err(e) => {
                     eprintln!("Decode error: {}", e);
@@ -464,83 +770,55 @@ impl Player {
         Ok(())
     }
     
-    /// Create a resampler with the given parameters
-    fn create_resampler(
-        input_sample_rate: u32,
-        output_sample_rate: u32,
-        input_channels: usize,
-        chunk_size: usize,
-    ) -> Result<SincFixedIn<f32>, String> {
-        let params = SincInterpolationParameters {
-            sinc_len: 256,
-            f_cutoff: 0.95,
-            interpolation: SincInterpolationType::Linear,
-            oversampling_factor: 256,
-            window: WindowFunction::BlackmanHarris2,
-        };
+    /// Try to open a new output stream on the current default device.
+    /// Retries a bounded number of times; returns an error if every attempt fails.
+    fn rebuild_output(should_stop: &AtomicBool, output_device: &RwLock<Option<String>>) -> Result<AudioOutput, String> {
+        let mut last_error = String::new();
         
-        let resample_ratio = output_sample_rate as f64 / input_sample_rate as f64;
-        
-        SincFixedIn::new(
-            resample_ratio,
-            2.0,
-            params,
-            chunk_size,
-            input_channels,
-        ).map_err(|e| format!("Failed to create resampler: {}", e))
-    }
-    
-    /// Interleave planar audio and convert channels if needed
-    fn interleave_and_convert_channels(planar: &[Vec<f32>], output_channels: usize) -> Vec<f32> {
-        if planar.is_empty() || planar[0].is_empty() {
-            return vec![];
-        }
-        
-        let input_channels = planar.len();
-        let frame_count = planar[0].len();
-        let mut output = Vec::with_capacity(frame_count * output_channels);
-        
-        for frame in 0..frame_count {
-            for out_ch in 0..output_channels {
-                if out_ch < input_channels {
-                    output.push(planar[out_ch][frame]);
-                } else if input_channels == 1 {
-                    // Mono to stereo: duplicate
-                    output.push(planar[0][frame]);
-                } else {
-                    // More output channels than input: use first channel
-                    output.push(planar[0][frame]);
+        for attempt in 1..=OUTPUT_RECOVERY_ATTEMPTS {
+            if should_stop.load(Ordering::SeqCst) {
+                return Err("Playback stopped during output recovery".to_string());
+            }
+            
+            thread::sleep(Duration::from_millis(OUTPUT_RECOVERY_DELAY_MS));
+            
+            match Self::open_output(output_device) {
+                Ok(output) => return Ok(output),
+                Err(e) => {
+                    eprintln!("[Player] Output rebuild attempt {}/{} failed: {}", attempt, OUTPUT_RECOVERY_ATTEMPTS, e);
+                    last_error = e;
                 }
             }
         }
         
-        output
+        Err(format!("Giving up on output device recovery: {}", last_error))
     }
     
-    /// Convert interleaved audio between channel counts
-    fn convert_channels(samples: &[f32], input_channels: usize, output_channels: usize) -> Vec<f32> {
-        if input_channels == output_channels {
-            return samples.to_vec();
+    /// Open the output on the user's device, or the default one if none is picked or it's gone
+    fn open_output(output_device: &RwLock<Option<String>>) -> Result<AudioOutput, String> {
+        match output_device.read().as_deref() {
+            Some(name) => AudioOutput::new_with_device(name),
+            None => AudioOutput::new(),
         }
-        
-        let frame_count = samples.len() / input_channels;
-        let mut output = Vec::with_capacity(frame_count * output_channels);
-        
-        for frame in 0..frame_count {
-            for out_ch in 0..output_channels {
-                if out_ch < input_channels {
-                    output.push(samples[frame * input_channels + out_ch]);
-                } else if input_channels == 1 {
-                    // Mono to stereo: duplicate
-                    output.push(samples[frame * input_channels]);
-                } else {
-                    // More output channels than input: use first channel
-                    output.push(samples[frame * input_channels]);
-                }
-            }
+    }
+    
+    /// Create a resampler if the track's sample rate doesn't match the output's
+    fn create_resampler(
+        input_sample_rate: u32,
+        output_sample_rate: u32,
+        input_channels: usize,
+    ) -> Result<Option<PacketResampler>, String> {
+        if input_sample_rate == output_sample_rate {
+            return Ok(None);
+        }
+        PacketResampler::new(input_sample_rate, output_sample_rate, input_channels).map(Some)
+    }
+    
+    /// Scale samples by a linear gain
+    fn apply_gain(samples: &mut [f32], gain: f32) {
+        if gain != 1.0 {
+            samples.iter_mut().for_each(|sample| *sample *= gain);
         }
-        
-        output
     }
     
     /// Pause playback
@@ -553,15 +831,35 @@ impl Player {
         self.is_paused.store(false, Ordering::SeqCst);
     }
     
-    /// Stop playback completely
+    /// Stop playback completely. With a fade-out configured, a playing track fades out
+    /// first (never past its end). That happens on the playback thread and this returns
+    /// right away; is_playing stays set until the fade is over.
     pub fn stop(&self) {
+        let fade_out = self.fade.read().fade_out_ms > 0.0
+            && self.is_playing.load(Ordering::SeqCst)
+            && !self.is_paused.load(Ordering::SeqCst);
+        if !fade_out {
+            self.halt();
+            return;
+        }
+        
+        // Already fading out
+        if self.stopping.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        // The playback thread fades out and exits on its own; the next play() or stop() joins it
+        self.fade_stop_request.store(true, Ordering::SeqCst);
+        self.track_ended.store(false, Ordering::SeqCst);
+        self.clear_next_track();
+    }
+    
+    /// Stop playback right away and wait for the playback thread to exit
+    fn halt(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
         // Signal the playback thread to stop
         self.should_stop.store(true, Ordering::SeqCst);
         self.is_paused.store(false, Ordering::SeqCst); // Unpause so thread can exit
-        
-        // Clear preloaded next track
-        *self.next_decoder.lock() = None;
-        self.gapless_transition.store(false, Ordering::SeqCst);
+        self.clear_next_track();
         
         // Wait for playback thread to finish
         if let Some(handle) = self.playback_thread.lock().take() {
@@ -572,16 +870,41 @@ impl Player {
         self.is_playing.store(false, Ordering::SeqCst);
         self.is_paused.store(false, Ordering::SeqCst);
         self.should_stop.store(false, Ordering::SeqCst);
+        self.fade_stop_request.store(false, Ordering::SeqCst);
+        // A track that ended before the stop isn't followed by the next one
+        self.track_ended.store(false, Ordering::SeqCst);
         *self.current_file.write() = None;
         self.position_ms.store(0, Ordering::SeqCst);
         self.duration_ms.store(0, Ordering::SeqCst);
     }
     
-    /// Seek to a position in milliseconds
+    /// Drop a preloaded or crossfading next track
+    fn clear_next_track(&self) {
+        *self.next_decoder.lock() = None;
+        self.gapless_transition.store(false, Ordering::SeqCst);
+        *self.crossfade_request.lock() = None;
+    }
+    
+    /// Seek to a position in milliseconds. The playback thread seeks the decoder through
+    /// the container's index and drops whatever was already buffered for the output.
     pub fn seek(&self, position_ms: i64) {
         self.seek_request.store(position_ms.max(0), Ordering::SeqCst);
     }
     
+    /// Seek to a percentage (0-100) of the current track's duration.
+    /// Returns the target position in milliseconds.
+    pub fn seek_to_percent(&self, percent: f32) -> Result<i64, AppError> {
+        let duration_ms = self.duration_ms.load(Ordering::SeqCst);
+        if duration_ms <= 0 {
+            return Err(AppError::InvalidInput("No track duration available".to_string()));
+        }
+        
+        let fraction = (percent as f64 / 100.0).clamp(0.0, 1.0);
+        let target_ms = ((duration_ms as f64 * fraction).round() as i64).clamp(0, duration_ms);
+        self.seek(target_ms);
+        Ok(target_ms)
+    }
+    
     /// Set volume in dB (-60 to +15)
     /// 0 dB = unity gain (no boost/cut)
     /// +15 dB = max boost (~5.6x gain)
@@ -613,6 +936,7 @@ impl Player {
     }
     
     /// Get current volume in dB
+    #[allow(dead_code)]
     pub fn volume_db(&self) -> f32 {
         *self.volume_db.read()
     }
@@ -627,6 +951,22 @@ impl Player {
         self.normalization_enabled.load(Ordering::SeqCst)
     }
     
+    /// Set the headphone crossfeed level, takes effect on the next decoded packet.
+    /// Only applied while the output is stereo.
+    pub fn set_crossfeed(&self, level: CrossfeedLevel) {
+        self.crossfeed_level.store(level.to_u8(), Ordering::SeqCst);
+    }
+    
+    /// Set the equalizer switch and band gains, takes effect on the next decoded packet
+    pub fn set_equalizer(&self, params: EqualizerParams) {
+        *self.equalizer.write() = params;
+    }
+    
+    /// Set fade-in/fade-out lengths and curve, applies to the playing track right away
+    pub fn set_fade(&self, settings: &FadeSettings) {
+        *self.fade.write() = FadeParams::from_settings(settings);
+    }
+    
     /// Set the track-specific normalization gain in dB
     pub fn set_track_gain(&self, gain_db: f32) {
         *self.track_gain_db.write() = gain_db;
@@ -640,10 +980,37 @@ impl Player {
     }
     
     /// Get the current track's normalization gain in dB
+    #[allow(dead_code)]
     pub fn track_gain_db(&self) -> f32 {
         *self.track_gain_db.read()
     }
     
+    /// Device and format audio is going out at. While playing this is the open stream;
+    /// otherwise it's what the default device would be opened with.
+    pub fn output_info(&self) -> Result<OutputInfo, AppError> {
+        if let Some(info) = self.output_info.read().clone() {
+            return Ok(info);
+        }
+        Ok(AudioOutput::probe(self.output_device.read().as_deref())?)
+    }
+    
+    /// Output device picked by the user, None = system default
+    pub fn output_device(&self) -> Option<String> {
+        self.output_device.read().clone()
+    }
+    
+    /// Pick the output device (None = system default). A playing track moves to it right
+    /// away and resumes from its position. Returns the device that will actually be used,
+    /// which is the default one if the named device isn't available.
+    pub fn set_output_device(&self, name: Option<String>) -> Option<String> {
+        let resolved = AudioOutput::resolve_device_name(name.as_deref());
+        *self.output_device.write() = name;
+        if self.is_playing.load(Ordering::SeqCst) {
+            self.device_switch_request.store(true, Ordering::SeqCst);
+        }
+        resolved
+    }
+    
     /// Get current player state
     pub fn get_state(&self) -> PlayerState {
         PlayerState {
@@ -659,33 +1026,89 @@ impl Player {
         }
     }
     
+    /// Position of the audio the device is playing right now (not how far decoding got)
+    pub fn current_position(&self) -> Duration {
+        Duration::from_millis(self.position_ms.load(Ordering::SeqCst).max(0) as u64)
+    }
+    
     /// Check if the current track has ended
     pub fn has_track_ended(&self) -> bool {
         self.track_ended.swap(false, Ordering::SeqCst)
     }
     
-    /// Preload the next track for gapless playback by opening the decoder in the background
-    pub fn preload_next_track(&self, file_path: PathBuf, gain_db: Option<f32>) {
-        let next_decoder = self.next_decoder.clone();
-        let gain = gain_db.unwrap_or(0.0);
-        let path = file_path.clone();
+    /// Simulate the end of the current track
+    #[cfg(test)]
+    pub(crate) fn mark_track_ended(&self) {
+        self.track_ended.store(true, Ordering::SeqCst);
+    }
+    
+    /// Enable or disable gapless playback. Disabling drops a preloaded next track,
+    /// so the current track ends normally.
+    pub fn set_gapless(&self, enabled: bool) {
+        self.gapless.store(enabled, Ordering::SeqCst);
+        if !enabled {
+            self.clear_preloaded_track();
+        }
+    }
+    
+    /// Get whether gapless playback is enabled
+    pub fn is_gapless(&self) -> bool {
+        self.gapless.load(Ordering::SeqCst)
+    }
+    
+    /// Set the crossfade length between queue tracks in ms, 0 = off
+    pub fn set_crossfade_ms(&self, crossfade_ms: i64) {
+        self.crossfade_ms.store(crossfade_ms.max(0), Ordering::SeqCst);
+    }
+    
+    /// Crossfade length to use between queue tracks. Always 0 while gapless playback is on,
+    /// since that asks for tracks to run into each other untouched.
+    pub fn crossfade_ms(&self) -> i64 {
+        if self.is_gapless() {
+            return 0;
+        }
+        self.crossfade_ms.load(Ordering::SeqCst)
+    }
+    
+    /// Fade the playing track out while `file_path` fades in over `duration_ms`, each at its
+    /// own normalization gain, with equal-power curves. The next track becomes the current one
+    /// (file, position, duration) as soon as the playback thread picks it up. Without a playing
+    /// track or a crossfade length this just plays the file.
+    pub fn crossfade_to(&self, file_path: PathBuf, gain_db: Option<f32>, duration_ms: i64) -> Result<(), AppError> {
+        let playing = self.is_playing.load(Ordering::SeqCst) && !self.is_paused.load(Ordering::SeqCst);
+        if duration_ms <= 0 || !playing {
+            return self.play_with_gain(file_path, gain_db);
+        }
         
-        // Open the decoder in a background thread so it's ready instantly at EOF
-        thread::spawn(move || {
-            match AudioDecoder::open(&path) {
-                Ok(decoder) => {
-                    eprintln!("[Gapless] Pre-opened decoder for: {:?}", path);
-                    *next_decoder.lock() = Some(PreloadedDecoder {
-                        decoder,
-                        file_path: path,
-                        gain_db: gain,
-                    });
-                }
-                Err(e) => {
-                    eprintln!("[Gapless] Failed to pre-open decoder: {}", e);
-                }
-            }
+        let decoder = AudioDecoder::open(&file_path)?;
+        // A preloaded track was lined up after the one fading out
+        *self.next_decoder.lock() = None;
+        self.track_ended.store(false, Ordering::SeqCst);
+        *self.crossfade_request.lock() = Some(CrossfadeRequest {
+            next: PreloadedDecoder {
+                decoder,
+                file_path,
+                gain_db: gain_db.unwrap_or(0.0),
+            },
+            duration_ms,
         });
+        Ok(())
+    }
+    
+    /// Open the next track's decoder now and hand it to the running playback loop, which
+    /// continues into it on the same output stream when the current track ends. Position and
+    /// duration switch over at that moment, not here.
+    pub fn enqueue_next(&self, file_path: PathBuf, gain_db: Option<f32>) -> Result<(), AppError> {
+        if !self.is_gapless() {
+            return Err(AppError::InvalidInput("Gapless playback is disabled".to_string()));
+        }
+        let decoder = AudioDecoder::open(&file_path)?;
+        *self.next_decoder.lock() = Some(PreloadedDecoder {
+            decoder,
+            file_path,
+            gain_db: gain_db.unwrap_or(0.0),
+        });
+        Ok(())
     }
     
     /// Clear any preloaded next track
@@ -698,6 +1121,24 @@ impl Player {
         self.gapless_transition.swap(false, Ordering::SeqCst)
     }
     
+    /// Set callback invoked when playback moves to a new output device
+    pub fn set_device_changed_callback<F>(&self, callback: F)
+    where
+        F: Fn(String) + Send + Sync + 'static,
+    {
+        *self.device_changed_callback.lock() = Some(Box::new(callback));
+    }
+    
+    /// Set callback invoked with the file path when a track plays to its end. It runs once
+    /// per track on the playback thread, never for stop() or seeks. Tracks that hand over
+    /// gaplessly to a preloaded one report through has_gapless_transition instead.
+    pub fn set_track_ended_callback<F>(&self, callback: F)
+    where
+        F: Fn(String) + Send + Sync + 'static,
+    {
+        *self.track_ended_callback.lock() = Some(Box::new(callback));
+    }
+    
     // Legacy compatibility methods
     
     pub fn set_current_file(&self, file_path: PathBuf) {
@@ -712,3 +1153,197 @@ impl Player {
         *self.current_file.write() = None;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::output::NULL_OUTPUT_DEVICE;
+    use crate::test_support::{temp_dir, write_silent_wav, write_test_wav};
+
+    #[test]
+    fn test_seek_to_percent() {
+        let player = Player::new();
+        assert!(player.seek_to_percent(50.0).is_err());
+
+        player.duration_ms.store(200_000, Ordering::SeqCst);
+        assert_eq!(player.seek_to_percent(50.0).unwrap(), 100_000);
+        assert_eq!(player.seek_request.load(Ordering::SeqCst), 100_000);
+
+        // Out-of-range percentages are clamped to the track
+        assert_eq!(player.seek_to_percent(150.0).unwrap(), 200_000);
+        assert_eq!(player.seek_to_percent(-5.0).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_enqueue_next_follows_gapless_setting() {
+        let file = std::env::temp_dir().join(format!("musicsloth_gapless_{}.wav", std::process::id()));
+        write_test_wav(&file);
+        let player = Player::new();
+
+        // Off by default, like the setting
+        assert!(player.enqueue_next(file.clone(), None).is_err());
+        assert!(player.next_decoder.lock().is_none());
+
+        player.set_gapless(true);
+        player.enqueue_next(file.clone(), Some(-3.0)).unwrap();
+        assert!(player.next_decoder.lock().is_some());
+        // Nothing switches over until the transition happens
+        assert_eq!(player.duration_ms.load(Ordering::SeqCst), 0);
+        assert!(player.current_file().is_none());
+
+        assert!(player.enqueue_next(file.with_extension("missing"), None).is_err());
+
+        player.set_gapless(false);
+        assert!(player.next_decoder.lock().is_none());
+
+        let _ = std::fs::remove_file(&file);
+    }
+
+    #[test]
+    fn test_track_end_is_reported_once() {
+        let player = Player::new();
+        let ended = Arc::new(Mutex::new(Vec::new()));
+        let reported = ended.clone();
+        player.set_track_ended_callback(move |file| reported.lock().push(file));
+
+        // A file that can't be played ends right away, so the queue can move past it
+        let file = std::env::temp_dir().join(format!("musicsloth_track_end_{}.missing", std::process::id()));
+        player.play(file.clone()).unwrap();
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while player.is_playing.load(Ordering::SeqCst) && std::time::Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(*ended.lock(), vec![file.to_string_lossy().to_string()]);
+        assert!(player.has_track_ended());
+
+        // Stopping afterwards doesn't report the track again
+        player.stop();
+        assert_eq!(ended.lock().len(), 1);
+        assert!(!player.has_track_ended());
+    }
+
+    #[test]
+    fn test_faded_stop_is_not_reported_as_track_end() {
+        let dir = temp_dir("faded_stop");
+        let file = dir.join("long.wav");
+        write_silent_wav(&file, 10_000);
+
+        let player = Player::new();
+        let ended = Arc::new(Mutex::new(Vec::new()));
+        let reported = ended.clone();
+        player.set_track_ended_callback(move |file| reported.lock().push(file));
+        player.set_output_device(Some(NULL_OUTPUT_DEVICE.to_string()));
+        player.set_fade(&FadeSettings {
+            enabled: true,
+            fade_in_ms: 0,
+            fade_out_ms: 200,
+            curve: "linear".to_string(),
+        });
+
+        player.play(file.clone()).unwrap();
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while player.output_info.read().is_none() && std::time::Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        thread::sleep(Duration::from_millis(100));
+        assert!(player.is_playing.load(Ordering::SeqCst));
+
+        // The fade-out runs through the playback loop, then the thread exits. stop()
+        // doesn't wait for it, so callers holding the player lock aren't held up.
+        player.stop();
+        assert!(player.is_playing.load(Ordering::SeqCst));
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while player.is_playing.load(Ordering::SeqCst) && std::time::Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(!player.is_playing.load(Ordering::SeqCst));
+        assert!(ended.lock().is_empty());
+        assert!(!player.has_track_ended());
+        assert!(player.current_file().is_none());
+
+        // Playing again while a fade-out runs cuts it short
+        player.play(file.clone()).unwrap();
+        thread::sleep(Duration::from_millis(100));
+        player.stop();
+        player.play(file).unwrap();
+        assert!(player.is_playing.load(Ordering::SeqCst));
+        player.set_fade(&FadeSettings { enabled: false, fade_in_ms: 0, fade_out_ms: 0, curve: "linear".to_string() });
+        player.stop();
+        assert!(!player.is_playing.load(Ordering::SeqCst));
+        assert!(ended.lock().is_empty());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_crossfade_switches_track_in_the_playback_loop() {
+        let dir = temp_dir("crossfade_loop");
+        let outgoing = dir.join("outgoing.wav");
+        let incoming = dir.join("incoming.wav");
+        write_silent_wav(&outgoing, 10_000);
+        write_silent_wav(&incoming, 600);
+
+        let player = Player::new();
+        let ended = Arc::new(Mutex::new(Vec::new()));
+        let reported = ended.clone();
+        player.set_track_ended_callback(move |file| reported.lock().push(file));
+        player.set_output_device(Some(NULL_OUTPUT_DEVICE.to_string()));
+        player.set_crossfade_ms(200);
+
+        player.play(outgoing).unwrap();
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while player.output_info.read().is_none() && std::time::Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        thread::sleep(Duration::from_millis(100));
+
+        // The incoming track takes over on the same stream while the outgoing one fades
+        player.crossfade_to(incoming.clone(), None, player.crossfade_ms()).unwrap();
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while player.current_file() != Some(incoming.clone()) && std::time::Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(player.current_file(), Some(incoming.clone()));
+        assert_eq!(player.duration_ms.load(Ordering::SeqCst), 600);
+        assert!(player.is_playing.load(Ordering::SeqCst));
+        assert!(ended.lock().is_empty());
+
+        // Only the incoming track reports its end
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while player.is_playing.load(Ordering::SeqCst) && std::time::Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(*ended.lock(), vec![incoming.to_string_lossy().to_string()]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_playback_clock_follows_consumed_frames() {
+        // Seeked to 5 s when 48000 frames had been written
+        let clock = PlaybackClock::new(5_000, 48_000, 48_000);
+        // Still playing what was buffered before the seek
+        assert_eq!(clock.position_ms(24_000), 5_000);
+        assert_eq!(clock.position_ms(48_000), 5_000);
+        // Half a second of the new audio played
+        assert_eq!(clock.position_ms(72_000), 5_500);
+        assert_eq!(clock.position_ms(48_000 + 480_000), 15_000);
+    }
+
+    #[test]
+    fn test_crossfade_is_off_while_gapless() {
+        let player = Player::new();
+        assert_eq!(player.crossfade_ms(), 0);
+
+        player.set_crossfade_ms(3000);
+        assert_eq!(player.crossfade_ms(), 3000);
+
+        player.set_gapless(true);
+        assert_eq!(player.crossfade_ms(), 0);
+        player.set_gapless(false);
+        assert_eq!(player.crossfade_ms(), 3000);
+
+        player.set_crossfade_ms(-5);
+        assert_eq!(player.crossfade_ms(), 0);
+    }
+}
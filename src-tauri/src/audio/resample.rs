@@ -0,0 +1,151 @@
+// Sample rate conversion
+// Converts decoded packets from a track's sample rate to the output device's. The sinc
+// resampler works on fixed chunks, so packet frames are collected until a chunk is full.
+// Input and output buffers are allocated once per track, not per packet.
+
+use rubato::{Resampler, SincFixedIn, SincInterpolationParameters, SincInterpolationType, WindowFunction};
+
+use super::output::ChannelMap;
+
+/// Frames per resampler chunk. About 23 ms at 44.1 kHz, small enough to keep latency low.
+pub const CHUNK_FRAMES: usize = 1024;
+
+pub struct PacketResampler {
+    resampler: SincFixedIn<f32>,
+    ratio: f64,
+    /// Planar input, filled up to `filled` frames
+    input: Vec<Vec<f32>>,
+    filled: usize,
+    /// Planar output of the last processed chunk
+    output: Vec<Vec<f32>>,
+    /// Output frames of filter delay still to skip, so the track starts on time
+    delay: usize,
+    frames_in: u64,
+    frames_out: u64,
+}
+
+impl PacketResampler {
+    pub fn new(input_sample_rate: u32, output_sample_rate: u32, channels: usize) -> Result<Self, String> {
+        let params = SincInterpolationParameters {
+            sinc_len: 256,
+            f_cutoff: 0.95,
+            interpolation: SincInterpolationType::Linear,
+            oversampling_factor: 256,
+            window: WindowFunction::BlackmanHarris2,
+        };
+
+        let ratio = output_sample_rate as f64 / input_sample_rate as f64;
+
+        let resampler = SincFixedIn::new(ratio, 2.0, params, CHUNK_FRAMES, channels)
+            .map_err(|e| format!("Failed to create resampler: {}", e))?;
+        let output = resampler.output_buffer_allocate(true);
+        let delay = resampler.output_delay();
+
+        Ok(Self {
+            resampler,
+            ratio,
+            input: vec![vec![0.0; CHUNK_FRAMES]; channels],
+            filled: 0,
+            output,
+            delay,
+            frames_in: 0,
+            frames_out: 0,
+        })
+    }
+
+    /// Add a packet of interleaved samples and resample every full chunk, returning
+    /// interleaved output samples mapped to the device's channels. Leftover frames wait
+    /// for the next packet.
+    pub fn process(&mut self, interleaved_samples: &[f32], channel_map: &ChannelMap) -> Vec<f32> {
+        let channels = self.input.len();
+        let mut resampled = Vec::new();
+
+        for frame in interleaved_samples.chunks_exact(channels) {
+            for (channel, &sample) in self.input.iter_mut().zip(frame) {
+                channel[self.filled] = sample;
+            }
+            self.filled += 1;
+            self.frames_in += 1;
+
+            if self.filled == CHUNK_FRAMES {
+                self.filled = 0;
+                if let Some(frames) = self.process_chunk() {
+                    let start = self.delay.min(frames);
+                    self.delay -= start;
+                    self.frames_out += (frames - start) as u64;
+                    resampled.extend(channel_map.map_planar(&self.output, start..frames));
+                }
+            }
+        }
+
+        resampled
+    }
+
+    /// Resample the frames still waiting at the end of a track, along with what's left in
+    /// the filter. Input is padded with silence and the output ends where the track does.
+    pub fn flush(&mut self, channel_map: &ChannelMap) -> Vec<f32> {
+        let total_out = (self.frames_in as f64 * self.ratio).round() as u64;
+        let mut resampled = Vec::new();
+
+        while self.frames_out < total_out {
+            for channel in &mut self.input {
+                channel[self.filled..].fill(0.0);
+            }
+            self.filled = 0;
+
+            let Some(frames) = self.process_chunk() else { break };
+            let start = self.delay.min(frames);
+            self.delay -= start;
+            let end = frames.min(start + (total_out - self.frames_out) as usize);
+            self.frames_out += (end - start) as u64;
+            resampled.extend(channel_map.map_planar(&self.output, start..end));
+        }
+
+        resampled
+    }
+
+    /// Drop buffered input and filter state (after a seek)
+    pub fn reset(&mut self) {
+        self.resampler.reset();
+        self.filled = 0;
+        self.delay = self.resampler.output_delay();
+        self.frames_in = 0;
+        self.frames_out = 0;
+    }
+
+    /// Resample the full input chunk into `output`, returning the frames written
+    fn process_chunk(&mut self) -> Option<usize> {
+        match self.resampler.process_into_buffer(&self.input, &mut self.output, None) {
+            Ok((_, frames)) => Some(frames),
+            Err(e) => {
+                eprintln!("Resample error: {}", e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upsample_output_length() {
+        let mut resampler = PacketResampler::new(22_050, 48_000, 1).unwrap();
+        let map = ChannelMap::new(1, 1);
+
+        // One second of a 440 Hz tone, in decoder-sized packets
+        let tone: Vec<f32> = (0..22_050)
+            .map(|i| (i as f32 * 440.0 * std::f32::consts::TAU / 22_050.0).sin() * 0.5)
+            .collect();
+        let mut output = Vec::new();
+        for packet in tone.chunks(1152) {
+            output.extend(resampler.process(packet, &map));
+        }
+        output.extend(resampler.flush(&map));
+
+        // One second at the output rate, filter delay skipped at the start and drained at the end
+        assert_eq!(output.len(), 48_000);
+        assert!(output.iter().all(|sample| sample.is_finite() && sample.abs() <= 1.0));
+    }
+}
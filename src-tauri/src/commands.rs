@@ -2,12 +2,21 @@
 use tauri::{State, AppHandle, Emitter, Manager};
 use std::path::PathBuf;
 
-use crate::state::AppState;
+use crate::error::{AppError, ErrorContext};
+use crate::state::{AppState, TrackEndOutcome};
 use crate::library::scanner::DirectoryScanner;
-use crate::library::indexer::{LibraryIndexer, IndexingResult, IndexingProgress, LoudnessAnalysisProgress};
-use crate::metadata::loudness::analyze_loudness;
+use crate::library::watcher::LibraryWatcher;
+use crate::library::indexer::{LibraryIndexer, IndexingResult, IndexingProgress};
+use crate::metadata::cue::{audio_file_path, split_cue_track_path};
+use crate::metadata::loudness::{analyze_album, analyze_loudness_with_timeout, measure_track_loudness, AlbumLoudness, FULL_ANALYSIS_TIMEOUT_SECS};
+use crate::metadata::writer::{write_tags, TrackEdits};
+use crate::db::connection::DatabaseConnection;
 use crate::db::operations::DbOperations;
-use crate::db::models::{Track, Album, Artist, Genre, Queue, ScanPath, Playlist};
+use crate::db::models::{Track, Album, Artist, Genre, Queue, ScanPath, Playlist, QueuePlaybackModes, RepeatMode, FolderNode, DirectoryListing, AnnotatedQueueTrack, QueueDuration, LibraryStatus, LibraryStats, SmartPlaylist, AlbumStats, SessionState, TrackLoudness, TrackPage, TrackSort, AlbumSort, ArtistSort};
+use crate::playlist::manager::{PlaylistManager, FolderPlaylistResult, ImportPlaylistResult};
+use crate::playlist::import_export::export_m3u8;
+use crate::queue::manager::{QueueManager, QueueStartResult};
+use crate::scrobble::lastfm::{LastfmAuthRequest, LastfmClient};
 use lofty::file::TaggedFileExt;
 
 // Backend now only tracks current file - playback is in frontend
@@ -15,15 +24,16 @@ use lofty::file::TaggedFileExt;
 pub fn set_current_track(
     file_path: String,
     state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     let path = PathBuf::from(file_path);
     let player = state.player.lock().unwrap();
     player.set_current_file(path);
+    state.track_change.track_changed();
     Ok(())
 }
 
 #[tauri::command]
-pub fn clear_current_track(state: State<'_, AppState>) -> Result<(), String> {
+pub fn clear_current_track(state: State<'_, AppState>) -> Result<(), AppError> {
     let player = state.player.lock().unwrap();
     player.clear_current_file();
     Ok(())
@@ -31,22 +41,31 @@ pub fn clear_current_track(state: State<'_, AppState>) -> Result<(), String> {
 
 // ===== Library Management Commands =====
 
+/// Scan all configured paths and index new/changed files.
+/// Cleanup of removed files only looks at scan paths whose files changed since the last scan,
+/// and only removes tracks inside them, unless `force_cleanup` is set (used by the explicit
+/// "remove missing" action).
 #[tauri::command]
 pub async fn scan_library(
+    force_cleanup: Option<bool>,
     state: State<'_, AppState>,
     app: AppHandle,
-) -> Result<IndexingResult, String> {
+) -> Result<IndexingResult, AppError> {
+    let force_cleanup = force_cleanup.unwrap_or(false);
     // Clone what we need for the async task
     let db = state.db.clone();
+    let settings = state.settings()?;
+    let cancel = state.scan_cancel.clone();
+    cancel.store(false, std::sync::atomic::Ordering::SeqCst);
     
     // Spawn blocking task to avoid blocking the event loop
     let result = tokio::task::spawn_blocking(move || {
         // Get all configured scan paths
         let scan_paths = DbOperations::get_all_scan_paths(&db)
-            .map_err(|e| format!("Failed to get scan paths: {}", e))?;
+            .context("Failed to get scan paths")?;
         
         if scan_paths.is_empty() {
-            return Err("No scan paths configured. Please add at least one directory to scan.".to_string());
+            return Err(AppError::InvalidInput("No scan paths configured. Please add at least one directory to scan.".to_string()));
         }
         
         // Accumulate results from all scan paths
@@ -54,62 +73,190 @@ pub async fn scan_library(
         let mut successful = 0;
         let mut failed = 0;
         let mut skipped = 0;
+        let mut unchanged = 0;
         let mut updated = 0;
         let mut all_errors = Vec::new();
         
-        // Scan each path individually with its last_scanned timestamp
+        let library_settings = &settings.library;
+        
+        // Every audio file seen on disk during this scan, used to detect removed tracks
+        let mut found_paths: std::collections::HashSet<String> = std::collections::HashSet::new();
+        // Scan paths whose files changed since the last scan, and the file count of each path
+        let mut changed_roots = Vec::new();
+        let mut file_counts = Vec::new();
+        
+        // Scan each path individually, recording when it was last scanned
+        let mut aborted = false;
         for scan_path in &scan_paths {
+            if cancel.load(std::sync::atomic::Ordering::SeqCst) {
+                aborted = true;
+                break;
+            }
+            
             // Scan this directory for audio files
-            let audio_files = DirectoryScanner::scan(&scan_path.path)
-                .map_err(|e| format!("Failed to scan directory {}: {}", scan_path.path, e))?;
+            let audio_files = DirectoryScanner::scan(&scan_path.path, library_settings.follow_symlinks)
+                .with_context(|| format!("Failed to scan directory {}", scan_path.path))?;
+            
+            found_paths.extend(audio_files.iter().map(|p| p.to_string_lossy().to_string()));
             
-            // Index files with last_scanned check
+            // Index files, skipping those whose size and modification time are unchanged
             let result = LibraryIndexer::index_files_with_progress(
                 &audio_files, 
                 &db, 
-                scan_path.last_scanned,
+                library_settings.prefer_sidecar,
+                library_settings.dedup_by_hash,
+                &cancel,
                 |progress| {
                     // Emit progress event to frontend
                     let _ = app.emit("scan-progress", progress);
                 }
             )
-            .map_err(|e| format!("Failed to index files from {}: {}", scan_path.path, e))?;
+            .with_context(|| format!("Failed to index files from {}", scan_path.path))?;
             
             // Accumulate results
             total_files += result.total_files;
             successful += result.successful;
             failed += result.failed;
             skipped += result.skipped;
+            unchanged += result.unchanged;
             updated += result.updated;
             all_errors.extend(result.errors);
             
+            if result.aborted {
+                aborted = true;
+                break;
+            }
+            
+            // Nothing new, modified or unreadable, and as many files as last time: nothing
+            // was removed from this path either
+            let path_unchanged = result.updated == 0
+                && result.failed == 0
+                && scan_path.file_count == Some(audio_files.len() as i64);
+            if !path_unchanged {
+                changed_roots.push(scan_path.path.clone());
+            }
+            file_counts.push((scan_path.id, audio_files.len()));
+            
             // Update last_scanned timestamp for this path
             DbOperations::update_scan_path_last_scanned(&db, scan_path.id)
-                .map_err(|e| format!("Failed to update last_scanned for {}: {}", scan_path.path, e))?;
+                .with_context(|| format!("Failed to update last_scanned for {}", scan_path.path))?;
         }
         
-        // Final cleanup: remove tracks outside all scan paths and missing files
-        let removed = DbOperations::remove_tracks_outside_scan_paths(&db, |current, total| {
+        // A cancelled scan only saw part of the library, so nothing may be treated as removed
+        if aborted {
             let _ = app.emit("scan-progress", IndexingProgress {
-                current: total_files + current,
-                total: total_files + total,
-                current_file: format!("Removing orphaned tracks: {} / {}", current, total),
+                current: successful + failed,
+                total: total_files,
+                current_file: "Scan cancelled".to_string(),
             });
-        })
-        .unwrap_or(0);
+            return Ok(IndexingResult {
+                total_files,
+                successful,
+                failed,
+                skipped,
+                unchanged,
+                updated,
+                removed: 0,
+                errors: all_errors,
+                aborted: true,
+            });
+        }
         
-        let removed_missing = DbOperations::remove_missing_files(&db, |current, total| {
+        if force_cleanup || !changed_roots.is_empty() {
+            // Tracks whose file is gone but that have a copy elsewhere move to the copy
+            let moved = DbOperations::promote_track_locations(&db, &found_paths)
+                .context("Failed to update track locations")?;
+            if moved > 0 {
+                eprintln!("[Scan] Moved {} tracks to an alternate location", moved);
+            }
+        }
+        
+        let (removed, removed_missing) = if force_cleanup {
+            // Full passes: remove tracks outside all scan paths and missing files
+            let removed = DbOperations::remove_tracks_outside_scan_paths(&db, &cancel, |current, total| {
+                let _ = app.emit("scan-progress", IndexingProgress {
+                    current: total_files + current,
+                    total: total_files + total,
+                    current_file: format!("Removing orphaned tracks: {} / {}", current, total),
+                });
+            })
+            .unwrap_or(0);
+            
+            let removed_missing = DbOperations::remove_missing_files(&db, &cancel, |current, total| {
+                let _ = app.emit("scan-progress", IndexingProgress {
+                    current: total_files + current,
+                    total: total_files + total,
+                    current_file: format!("Checking file existence: {} / {}", current, total),
+                });
+            })
+            .unwrap_or(0);
+            
+            (removed, removed_missing)
+        } else if changed_roots.is_empty() {
+            eprintln!("[Scan] Library unchanged on disk, skipping cleanup");
+            (0, 0)
+        } else {
+            // A track inside a changed scan path that this scan didn't see is no longer on
+            // disk. Diffing against the scan results avoids stat-ing every track.
+            let stale_track_ids = DbOperations::find_tracks_not_in_paths(&db, &changed_roots, &found_paths)
+                .context("Failed to check for removed tracks")?;
+            if stale_track_ids.is_empty() {
+                (0, 0)
+            } else {
+                let _ = app.emit("scan-progress", IndexingProgress {
+                    current: total_files,
+                    total: total_files,
+                    current_file: format!("Removing {} missing tracks", stale_track_ids.len()),
+                });
+                
+                let removed = match DbOperations::remove_tracks_by_ids(&db, &stale_track_ids) {
+                    Ok(removed) => removed,
+                    Err(e) => {
+                        eprintln!("[Scan] Failed to remove missing tracks: {}", e);
+                        // Leave the paths marked as changed so the next scan retries
+                        file_counts.clear();
+                        0
+                    }
+                };
+                (removed, 0)
+            }
+        };
+        
+        // Drop album rows whose tracks were removed or retagged
+        if let Err(e) = DbOperations::sync_albums(&db) {
+            eprintln!("[Scan] Failed to sync albums: {}", e);
+        }
+        
+        // Cancelled during the cleanup passes: keep what was removed, skip loudness analysis
+        if cancel.load(std::sync::atomic::Ordering::SeqCst) {
             let _ = app.emit("scan-progress", IndexingProgress {
-                current: total_files + current,
-                total: total_files + total,
-                current_file: format!("Checking file existence: {} / {}", current, total),
+                current: total_files,
+                total: total_files,
+                current_file: "Scan cancelled".to_string(),
             });
-        })
-        .unwrap_or(0);
+            return Ok(IndexingResult {
+                total_files,
+                successful,
+                failed,
+                skipped,
+                unchanged,
+                updated,
+                removed: removed + removed_missing,
+                errors: all_errors,
+                aborted: true,
+            });
+        }
+        
+        // The cleanup finished, so the next scan can skip paths that still hold these files
+        for (path_id, file_count) in file_counts {
+            DbOperations::set_scan_path_file_count(&db, path_id, file_count)
+                .context("Failed to record scanned file count")?;
+        }
         
         // Analyze loudness for tracks that don't have normalization data yet
         // This is CPU-intensive but essential for ReplayGain-style volume normalization
-        let (loudness_analyzed, loudness_failed) = LibraryIndexer::analyze_loudness_with_progress(&db, |progress| {
+        let replay_gain = &settings.playback.replay_gain;
+        let (loudness_analyzed, loudness_failed) = LibraryIndexer::analyze_loudness_with_progress(&db, replay_gain.segments_per_minute, replay_gain.prefer_tags, |progress| {
             let _ = app.emit("loudness-analysis-progress", progress);
         })
         .unwrap_or((0, 0));
@@ -118,49 +265,127 @@ pub async fn scan_library(
             eprintln!("[Scan] Loudness analysis: {} analyzed, {} failed", loudness_analyzed, loudness_failed);
         }
         
-        Ok::<IndexingResult, String>(IndexingResult {
+        Ok::<IndexingResult, AppError>(IndexingResult {
             total_files,
             successful,
             failed,
             skipped,
+            unchanged,
             updated,
             removed: removed + removed_missing,
             errors: all_errors,
+            aborted: false,
         })
     })
     .await
-    .map_err(|e| format!("Task join error: {}", e))??;
+    .context("Task join error")??;
     
     Ok(result)
 }
 
+/// (Re)start watching the configured scan paths, so files added to or removed from them
+/// show up in the library without a manual scan. Emits `library-changed` after each batch.
+pub(crate) fn restart_library_watcher(app: &AppHandle) {
+    let state = app.state::<AppState>();
+    let folders: Vec<PathBuf> = match DbOperations::get_all_scan_paths(&state.db) {
+        Ok(paths) => paths.into_iter().map(|p| PathBuf::from(p.path)).collect(),
+        Err(e) => {
+            eprintln!("[Watcher] Failed to load scan paths: {}", e);
+            return;
+        }
+    };
+    
+    let app_handle = app.clone();
+    let watcher = LibraryWatcher::start(&folders, move |paths| {
+        let state = app_handle.state::<AppState>();
+        let library_settings = match state.settings() {
+            Ok(settings) => settings.library,
+            Err(e) => {
+                eprintln!("[Watcher] {}", e);
+                return;
+            }
+        };
+        match LibraryWatcher::apply_changes(&state.db, &paths, &library_settings) {
+            Ok(change) if !change.is_empty() => {
+                let _ = app_handle.emit("library-changed", change);
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("[Watcher] Failed to apply changes: {}", e),
+        }
+    });
+    
+    match watcher {
+        Ok(watcher) => {
+            println!("[Watcher] Watching {} of {} scan paths", watcher.watched_folders().len(), folders.len());
+            *state.library_watcher.lock().unwrap() = Some(watcher);
+        }
+        Err(e) => eprintln!("[Watcher] Failed to start: {}", e),
+    }
+}
+
 #[tauri::command]
-pub fn add_scan_path(path: String, state: State<'_, AppState>) -> Result<i64, String> {
+pub fn add_scan_path(path: String, app: AppHandle, state: State<'_, AppState>) -> Result<i64, AppError> {
+    // Refuse typo'd, unmounted or unreadable folders rather than storing a path that scans to nothing
+    DirectoryScanner::validate_scan_path(&path)?;
+    
     // Check if path is a subdirectory of existing paths
     if DbOperations::is_subdirectory_of_existing_path(&state.db, &path)
-        .map_err(|e| format!("Failed to check subdirectory: {}", e))? 
+        .context("Failed to check subdirectory")? 
     {
-        return Err("This directory is already covered by an existing scan path.".to_string());
+        return Err(AppError::InvalidInput("This directory is already covered by an existing scan path.".to_string()));
     }
     
-    DbOperations::add_scan_path(&state.db, &path)
-        .map_err(|e| format!("Failed to add scan path: {}", e))
+    let id = DbOperations::add_scan_path(&state.db, &path)
+        .context("Failed to add scan path")?;
+    restart_library_watcher(&app);
+    Ok(id)
+}
+
+/// Check a folder before adding it as a scan path, returns the number of audio files
+/// directly inside it
+#[tauri::command]
+pub fn validate_scan_path(path: String) -> Result<usize, AppError> {
+    DirectoryScanner::validate_scan_path(&path).map_err(AppError::from)
+}
+
+/// Every path a track's file is known at, its own path first (see `dedup_by_hash`)
+#[tauri::command]
+pub fn get_track_locations(track_id: i64, state: State<'_, AppState>) -> Result<Vec<String>, AppError> {
+    DbOperations::get_track_locations(&state.db, track_id)
+        .context("Failed to get track locations")
 }
 
 #[tauri::command]
-pub fn get_all_scan_paths(state: State<'_, AppState>) -> Result<Vec<ScanPath>, String> {
+pub fn get_all_scan_paths(state: State<'_, AppState>) -> Result<Vec<ScanPath>, AppError> {
     DbOperations::get_all_scan_paths(&state.db)
-        .map_err(|e| format!("Failed to get scan paths: {}", e))
+        .context("Failed to get scan paths")
+}
+
+/// Startup check: whether scan paths are configured, how many tracks the library has
+/// and when it was last scanned
+#[tauri::command]
+pub fn get_library_status(state: State<'_, AppState>) -> Result<LibraryStatus, AppError> {
+    DbOperations::get_library_status(&state.db)
+        .context("Failed to get library status")
+}
+
+/// Totals for the library overview: tracks, artists, albums, playtime and size on disk
+#[tauri::command]
+pub fn get_library_stats(state: State<'_, AppState>) -> Result<LibraryStats, AppError> {
+    DbOperations::get_library_stats(&state.db)
+        .context("Failed to get library stats")
 }
 
 #[tauri::command]
-pub fn remove_scan_path(path_id: i64, state: State<'_, AppState>) -> Result<(), String> {
+pub fn remove_scan_path(path_id: i64, app: AppHandle, state: State<'_, AppState>) -> Result<(), AppError> {
     DbOperations::remove_scan_path(&state.db, path_id)
-        .map_err(|e| format!("Failed to remove scan path: {}", e))
+        .context("Failed to remove scan path")?;
+    restart_library_watcher(&app);
+    Ok(())
 }
 
 #[tauri::command]
-pub async fn pick_folder(app: AppHandle) -> Result<Option<String>, String> {
+pub async fn pick_folder(app: AppHandle) -> Result<Option<String>, AppError> {
     use tauri_plugin_dialog::DialogExt;
     
     let folder = app.dialog()
@@ -171,7 +396,7 @@ pub async fn pick_folder(app: AppHandle) -> Result<Option<String>, String> {
         Some(file_path) => {
             match file_path.into_path() {
                 Ok(path) => Ok(Some(path.to_string_lossy().to_string())),
-                Err(e) => Err(format!("Failed to get path: {}", e)),
+                Err(e) => Err(AppError::Io(format!("Failed to get path: {}", e))),
             }
         }
         None => Ok(None),
@@ -179,180 +404,429 @@ pub async fn pick_folder(app: AppHandle) -> Result<Option<String>, String> {
 }
 
 #[tauri::command]
-pub fn get_all_tracks(state: State<'_, AppState>) -> Result<Vec<Track>, String> {
+pub fn get_all_tracks(state: State<'_, AppState>) -> Result<Vec<Track>, AppError> {
     DbOperations::get_all_tracks(&state.db)
-        .map_err(|e| format!("Failed to get tracks: {}", e))
+        .context("Failed to get tracks")
 }
 
+/// One page of the library for virtualized lists. `sort` is a TrackSort name such as
+/// "date_added_desc" or "title_asc".
 #[tauri::command]
-pub fn get_all_albums(state: State<'_, AppState>) -> Result<Vec<Album>, String> {
-    DbOperations::get_all_albums(&state.db)
-        .map_err(|e| format!("Failed to get albums: {}", e))
+pub fn get_tracks_page(
+    limit: i64,
+    offset: i64,
+    sort: String,
+    state: State<'_, AppState>,
+) -> Result<TrackPage, AppError> {
+    let sort = TrackSort::from_name(&sort)
+        .ok_or_else(|| AppError::InvalidInput(format!("Invalid sort order: {}", sort)))?;
+    DbOperations::get_tracks_page(&state.db, limit, offset, sort)
+        .context("Failed to get tracks")
 }
 
+/// A track by id with all its columns, None if it's no longer in the library
 #[tauri::command]
-pub fn get_all_artists(state: State<'_, AppState>) -> Result<Vec<Artist>, String> {
-    DbOperations::get_all_artists(&state.db)
-        .map_err(|e| format!("Failed to get artists: {}", e))
+pub fn get_track(track_id: i64, state: State<'_, AppState>) -> Result<Option<Track>, AppError> {
+    DbOperations::get_track_by_id(&state.db, track_id)
+        .context("Failed to get track")
 }
 
+/// `sort` is an AlbumSort name ("name_asc", "year_desc", "song_count_desc", "artist_asc"),
+/// by name when omitted
 #[tauri::command]
-pub fn get_all_genres(state: State<'_, AppState>) -> Result<Vec<Genre>, String> {
+pub fn get_all_albums(sort: Option<String>, state: State<'_, AppState>) -> Result<Vec<Album>, AppError> {
+    let sort = match sort {
+        Some(sort) => AlbumSort::from_name(&sort)
+            .ok_or_else(|| AppError::InvalidInput(format!("Invalid sort order: {}", sort)))?,
+        None => AlbumSort::default(),
+    };
+    DbOperations::get_all_albums(&state.db, sort)
+        .context("Failed to get albums")
+}
+
+/// Albums marked as compilations (soundtracks, samplers)
+#[tauri::command]
+pub fn get_compilation_albums(state: State<'_, AppState>) -> Result<Vec<Album>, AppError> {
+    DbOperations::get_compilation_albums(&state.db)
+        .context("Failed to get compilation albums")
+}
+
+/// `sort` is an ArtistSort name ("name_asc", "song_count_desc"), by name when omitted
+#[tauri::command]
+pub fn get_all_artists(sort: Option<String>, state: State<'_, AppState>) -> Result<Vec<Artist>, AppError> {
+    let sort = match sort {
+        Some(sort) => ArtistSort::from_name(&sort)
+            .ok_or_else(|| AppError::InvalidInput(format!("Invalid sort order: {}", sort)))?,
+        None => ArtistSort::default(),
+    };
+    DbOperations::get_all_artists(&state.db, sort)
+        .context("Failed to get artists")
+}
+
+#[tauri::command]
+pub fn get_all_genres(state: State<'_, AppState>) -> Result<Vec<Genre>, AppError> {
     DbOperations::get_all_genres(&state.db)
-        .map_err(|e| format!("Failed to get genres: {}", e))
+        .context("Failed to get genres")
 }
 
+/// Release years as (year, track count), newest first; year 0 holds tracks without one
 #[tauri::command]
-pub fn clear_library(state: State<'_, AppState>) -> Result<(), String> {
+pub fn get_all_years(state: State<'_, AppState>) -> Result<Vec<(i32, i32)>, AppError> {
+    DbOperations::get_all_years(&state.db)
+        .context("Failed to get years")
+}
+
+#[tauri::command]
+pub fn get_folder_tree(state: State<'_, AppState>) -> Result<Vec<FolderNode>, AppError> {
+    DbOperations::get_folder_tree(&state.db)
+        .context("Failed to get folder tree")
+}
+
+/// Subfolders and tracks of a folder, for browsing the library by directory
+#[tauri::command]
+pub fn browse_directory(path: String, state: State<'_, AppState>) -> Result<DirectoryListing, AppError> {
+    DbOperations::browse_directory(&state.db, &path)
+        .context("Failed to browse directory")
+}
+
+#[tauri::command]
+pub fn clear_library(state: State<'_, AppState>) -> Result<(), AppError> {
     DbOperations::clear_library(&state.db)
-        .map_err(|e| format!("Failed to clear library: {}", e))
+        .context("Failed to clear library")
+}
+
+/// Groups of tracks with identical content at different paths
+#[tauri::command]
+pub fn find_duplicate_tracks(state: State<'_, AppState>) -> Result<Vec<Vec<Track>>, AppError> {
+    DbOperations::find_duplicate_tracks(&state.db)
+        .context("Failed to find duplicate tracks")
+}
+
+/// Groups of tracks that look like the same recording (title, artist and duration)
+#[tauri::command]
+pub fn find_duplicate_metadata(state: State<'_, AppState>) -> Result<Vec<Vec<Track>>, AppError> {
+    DbOperations::find_duplicate_metadata(&state.db)
+        .context("Failed to find duplicate tracks")
+}
+
+/// Remove one track from the library (and from every queue and playlist). With
+/// `delete_file` its audio file is deleted from disk first; if that fails the track stays.
+#[tauri::command]
+pub fn delete_track(track_id: i64, delete_file: bool, state: State<'_, AppState>) -> Result<(), AppError> {
+    let track = DbOperations::get_track_by_id(&state.db, track_id)
+        .context("Failed to get track")?
+        .ok_or_else(|| AppError::NotFound(format!("Track not found: {}", track_id)))?;
+    
+    if delete_file {
+        // The file holds every track of its CUE sheet
+        if split_cue_track_path(&track.file_path).1.is_some() {
+            return Err(AppError::InvalidInput("Can't delete the file of a single CUE sheet track".to_string()));
+        }
+        match std::fs::remove_file(crate::path_utils::to_extended_length(std::path::Path::new(&track.file_path))) {
+            Ok(()) => println!("[Backend] Deleted file {}", track.file_path),
+            // Already gone, only the library entry is left to remove
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(AppError::from(e).context("Failed to delete file")),
+        }
+    }
+    
+    DbOperations::delete_track(&state.db, track_id)
+        .context("Failed to delete track")?;
+    Ok(())
 }
 
+/// Reset listening statistics but keep the music. Scoped to one track or one album when
+/// `track_id` or `album_id` is given, otherwise applies to the whole library.
+/// Returns the number of tracks reset.
 #[tauri::command]
-pub fn get_tracks_by_artist(state: State<'_, AppState>, artist_id: i64) -> Result<Vec<Track>, String> {
+pub fn reset_play_statistics(
+    track_id: Option<i64>,
+    album_id: Option<i64>,
+    state: State<'_, AppState>,
+) -> Result<usize, AppError> {
+    let result = match (track_id, album_id) {
+        (Some(track_id), _) => DbOperations::reset_track_statistics(&state.db, track_id),
+        (None, Some(album_id)) => DbOperations::reset_album_statistics(&state.db, album_id),
+        (None, None) => DbOperations::reset_statistics(&state.db),
+    };
+    result.context("Failed to reset play statistics")
+}
+
+#[tauri::command]
+pub fn get_tracks_by_artist(state: State<'_, AppState>, artist_id: i64) -> Result<Vec<Track>, AppError> {
     DbOperations::get_tracks_by_artist(&state.db, artist_id)
-        .map_err(|e| format!("Failed to get tracks by artist: {}", e))
+        .context("Failed to get tracks by artist")
+}
+
+#[tauri::command]
+pub fn get_tracks_by_album_artist(state: State<'_, AppState>, artist_id: i64) -> Result<Vec<Track>, AppError> {
+    DbOperations::get_tracks_by_album_artist(&state.db, artist_id)
+        .context("Failed to get tracks by album artist")
+}
+
+#[tauri::command]
+pub fn get_tracks_by_composer(state: State<'_, AppState>, composer: String) -> Result<Vec<Track>, AppError> {
+    DbOperations::get_tracks_by_composer(&state.db, &composer)
+        .context("Failed to get tracks by composer")
 }
 
 #[tauri::command]
-pub fn get_tracks_by_genre(state: State<'_, AppState>, genre_id: i64) -> Result<Vec<Track>, String> {
+pub fn get_tracks_by_genre(state: State<'_, AppState>, genre_id: i64) -> Result<Vec<Track>, AppError> {
     DbOperations::get_tracks_by_genre(&state.db, genre_id)
-        .map_err(|e| format!("Failed to get tracks by genre: {}", e))
+        .context("Failed to get tracks by genre")
 }
 
 #[tauri::command]
-pub fn get_tracks_by_album(state: State<'_, AppState>, album_name: String) -> Result<Vec<Track>, String> {
+pub fn get_tracks_by_year(state: State<'_, AppState>, year: i32) -> Result<Vec<Track>, AppError> {
+    DbOperations::get_tracks_by_year(&state.db, year)
+        .context("Failed to get tracks by year")
+}
+
+#[tauri::command]
+pub fn get_tracks_by_decade(state: State<'_, AppState>, decade_start: i32) -> Result<Vec<Track>, AppError> {
+    DbOperations::get_tracks_by_decade(&state.db, decade_start)
+        .context("Failed to get tracks by decade")
+}
+
+#[tauri::command]
+pub fn get_tracks_by_album(state: State<'_, AppState>, album_name: String) -> Result<Vec<Track>, AppError> {
     DbOperations::get_tracks_by_album(&state.db, &album_name)
-        .map_err(|e| format!("Failed to get tracks by album: {}", e))
+        .context("Failed to get tracks by album")
+}
+
+/// Tracks of an album by its id from get_all_albums
+#[tauri::command]
+pub fn get_tracks_by_album_id(state: State<'_, AppState>, album_id: i64) -> Result<Vec<Track>, AppError> {
+    DbOperations::get_tracks_by_album_id(&state.db, album_id)
+        .context("Failed to get tracks by album")
+}
+
+/// Tracks of an album entry as listed by get_all_albums (name plus artist)
+#[tauri::command]
+pub fn get_tracks_by_album_and_artist(state: State<'_, AppState>, album_name: String, artist: Option<String>) -> Result<Vec<Track>, AppError> {
+    DbOperations::get_tracks_by_album_and_artist(&state.db, &album_name, artist.as_deref())
+        .context("Failed to get tracks by album")
 }
 
+/// Summed play count, last played time and duration of an album's tracks
 #[tauri::command]
-pub fn get_current_track(state: State<'_, AppState>) -> Result<Option<Track>, String> {
+pub fn get_album_stats(state: State<'_, AppState>, album_id: i64) -> Result<AlbumStats, AppError> {
+    DbOperations::get_album_stats(&state.db, album_id)
+        .context("Failed to get album stats")
+}
+
+/// Return the Track for a file, indexing it first if it isn't in the library yet.
+/// Used for drag-and-drop playback of files outside the scan paths.
+#[tauri::command]
+pub async fn ensure_track(file_path: String, state: State<'_, AppState>) -> Result<Track, AppError> {
+    let db = state.db.clone();
+    
+    tokio::task::spawn_blocking(move || {
+        LibraryIndexer::ensure_track(std::path::Path::new(&file_path), &db)
+            .context("Failed to ensure track")
+    })
+    .await
+    .context("Task join error")?
+}
+
+#[tauri::command]
+pub fn get_current_track(state: State<'_, AppState>) -> Result<Option<Track>, AppError> {
     let player = state.player.lock().unwrap();
     
     if let Some(file_path) = player.current_file() {
         let file_path_str = file_path.to_string_lossy().to_string();
         DbOperations::get_track_by_file_path(&state.db, &file_path_str)
-            .map_err(|e| format!("Failed to get track: {}", e))
+            .context("Failed to get track")
     } else {
         Ok(None)
     }
 }
 
+/// Write tag edits into a track's file, then re-index it (rehashing the file) and return
+/// the updated Track
 #[tauri::command]
-pub async fn get_album_art(file_path: String) -> Result<Option<Vec<u8>>, String> {
-    use lofty::probe::Probe;
-    use lofty::picture::PictureType;
-    use std::path::Path;
+pub async fn update_track_tags(track_id: i64, edits: TrackEdits, state: State<'_, AppState>) -> Result<Track, AppError> {
+    let db = state.db.clone();
+    let prefer_sidecar = state.settings()?.library.prefer_sidecar;
     
-    // Run file I/O in a blocking task to avoid blocking the async runtime
     tokio::task::spawn_blocking(move || {
-        let path = Path::new(&file_path);
+        let track = DbOperations::get_track_by_id(&db, track_id)
+            .context("Failed to get track")?
+            .ok_or_else(|| AppError::NotFound(format!("Track not found: {}", track_id)))?;
         
-        // Try lofty first
-        let lofty_result = Probe::open(&file_path)
-            .and_then(|p| p.read());
-        
-        if let Ok(tagged_file) = lofty_result {
-            // Priority order for picture types (matching foobar2000 behavior)
-            let picture_priority = [
-                PictureType::CoverFront,      // Front Cover (most common)
-                PictureType::Media,            // Media (e.g., label side of CD)
-                PictureType::CoverBack,        // Back Cover
-                PictureType::Leaflet,          // Leaflet page
-                PictureType::Other,            // Other/Undefined
-                PictureType::Icon,             // Icon
-                PictureType::OtherIcon,        // Other Icon
-                PictureType::Artist,           // Artist/Performer
-                PictureType::Band,             // Band/Orchestra
-                PictureType::Composer,         // Composer
-                PictureType::Lyricist,         // Lyricist/Text writer
-                PictureType::RecordingLocation, // Recording Location
-                PictureType::DuringRecording,  // During Recording
-                PictureType::DuringPerformance, // During Performance
-                PictureType::ScreenCapture,    // Screen Capture
-                PictureType::BrightFish,       // Bright Colored Fish
-                PictureType::Illustration,     // Illustration
-                PictureType::BandLogo,         // Band/Artist Logotype
-                PictureType::PublisherLogo,    // Publisher/Studio Logotype
-            ];
-            
-            // Try to get the primary tag first
-            if let Some(tag) = tagged_file.primary_tag() {
-                // Try each picture type in priority order
-                for pic_type in &picture_priority {
-                    for picture in tag.pictures() {
-                        if picture.pic_type() == *pic_type {
-                            return Ok(Some(picture.data().to_vec()));
-                        }
-                    }
-                }
-            }
-            
-            // Try all tags if primary tag didn't have cover art
-            for tag in tagged_file.tags() {
-                // Try each picture type in priority order
-                for pic_type in &picture_priority {
-                    for picture in tag.pictures() {
-                        if picture.pic_type() == *pic_type {
-                            return Ok(Some(picture.data().to_vec()));
-                        }
-                    }
-                }
-            }
-            
-            return Ok(None);
+        // CUE tracks share one file whose tags describe the whole album
+        if split_cue_track_path(&track.file_path).1.is_some() {
+            return Err(AppError::InvalidInput("Tags of CUE sheet tracks come from the sheet and can't be edited".to_string()));
         }
         
-        // Fallback: try id3 crate for MP3 files if lofty failed
-        let extension = path.extension()
-            .and_then(|s| s.to_str())
-            .map(|s| s.to_lowercase());
+        write_tags(std::path::Path::new(&track.file_path), &edits)
+            .context("Failed to write tags")?;
         
-        if extension.as_deref() == Some("mp3") {
-            if let Ok(tag) = id3::Tag::read_from_path(&file_path) {
-                // id3 crate picture type priority (similar to lofty)
-                use id3::frame::PictureType as Id3PictureType;
-                let id3_priority = [
-                    Id3PictureType::CoverFront,
-                    Id3PictureType::Media,
-                    Id3PictureType::CoverBack,
-                    Id3PictureType::Leaflet,
-                    Id3PictureType::Other,
-                    Id3PictureType::Icon,
-                    Id3PictureType::OtherIcon,
-                    Id3PictureType::Artist,
-                    Id3PictureType::Band,
-                    Id3PictureType::Composer,
-                    Id3PictureType::Lyricist,
-                    Id3PictureType::RecordingLocation,
-                    Id3PictureType::DuringRecording,
-                    Id3PictureType::DuringPerformance,
-                    Id3PictureType::ScreenCapture,
-                    Id3PictureType::BrightFish,
-                    Id3PictureType::Illustration,
-                    Id3PictureType::BandLogo,
-                    Id3PictureType::PublisherLogo,
-                ];
-                
-                for pic_type in &id3_priority {
-                    for picture in tag.pictures() {
-                        if picture.picture_type == *pic_type {
-                            return Ok(Some(picture.data.clone()));
-                        }
-                    }
-                }
-            }
+        LibraryIndexer::refresh_track(track_id, &db, prefer_sidecar)
+            .context("Failed to refresh track")
+    })
+    .await
+    .context("Task join error")?
+}
+
+/// Re-read a track's tags after they were edited in another program and return the
+/// updated Track, without rescanning the library
+#[tauri::command]
+pub async fn refresh_track(track_id: i64, state: State<'_, AppState>) -> Result<Track, AppError> {
+    let db = state.db.clone();
+    let prefer_sidecar = state.settings()?.library.prefer_sidecar;
+    
+    tokio::task::spawn_blocking(move || {
+        LibraryIndexer::refresh_track(track_id, &db, prefer_sidecar)
+            .context("Failed to refresh track")
+    })
+    .await
+    .context("Task join error")?
+}
+
+/// Cover of a track: its embedded art (extracted into the artwork cache on first use, and
+/// recorded as its album's artwork), else the cover the user picked for the album, else
+/// the album's artwork (folder art, or another track's embedded art)
+fn cached_album_art(
+    db: &DatabaseConnection,
+    cache_dir: &std::path::Path,
+    track: &Track,
+) -> Result<Option<PathBuf>, AppError> {
+    let path = crate::metadata::artwork::cached_album_art(cache_dir, track)
+        .context("Failed to cache album art")?;
+    if let Some(ref path) = path {
+        DbOperations::set_album_artwork_path(db, track.id, &path.to_string_lossy())
+            .context("Failed to save album artwork path")?;
+        return Ok(Some(path.clone()));
+    }
+    
+    let (custom, album) = DbOperations::get_track_album_artwork(db, track.id)
+        .context("Failed to get album artwork")?;
+    Ok([custom, album].into_iter().flatten().map(PathBuf::from).find(|p| p.is_file()))
+}
+
+#[tauri::command]
+pub async fn get_album_art(file_path: String, state: State<'_, AppState>) -> Result<Option<Vec<u8>>, AppError> {
+    let db = state.db.clone();
+    let cache_dir = state.app_dir.join(crate::metadata::artwork::ARTWORK_CACHE_DIR);
+    
+    // Run file I/O in a blocking task to avoid blocking the async runtime
+    tokio::task::spawn_blocking(move || {
+        let track = DbOperations::get_track_by_file_path(&db, &file_path)
+            .context("Failed to get track")?;
+        match track {
+            Some(track) => match cached_album_art(&db, &cache_dir, &track)? {
+                Some(path) => std::fs::read(&path)
+                    .map(Some)
+                    .context("Failed to read cached album art"),
+                None => Ok(None),
+            },
+            // Not in the library, nothing to key a cache entry on
+            None => Ok(crate::metadata::artwork::read_album_art(std::path::Path::new(audio_file_path(&file_path)))),
         }
+    })
+    .await
+    .context("Task join error")?
+}
+
+/// Path of a track's cover (see get_album_art), for loading it in the webview with
+/// `convertFileSrc` instead of sending the bytes over IPC. None if it has no art.
+#[tauri::command]
+pub async fn get_album_art_path(track_id: i64, state: State<'_, AppState>) -> Result<Option<String>, AppError> {
+    let db = state.db.clone();
+    let cache_dir = state.app_dir.join(crate::metadata::artwork::ARTWORK_CACHE_DIR);
+    
+    tokio::task::spawn_blocking(move || {
+        let track = DbOperations::get_track_by_id(&db, track_id)
+            .context("Failed to get track")?
+            .ok_or_else(|| AppError::NotFound(format!("Track not found: {}", track_id)))?;
+        let path = cached_album_art(&db, &cache_dir, &track)?;
+        Ok(path.map(|p| p.to_string_lossy().to_string()))
+    })
+    .await
+    .context("Task join error")?
+}
+
+/// Embed cover art into an audio file, from an image file on disk or raw bytes.
+/// `pic_type` defaults to the front cover; an existing picture of that type is replaced.
+#[tauri::command]
+pub async fn set_album_art(
+    file_path: String,
+    image_path: Option<String>,
+    image_bytes: Option<Vec<u8>>,
+    pic_type: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), AppError> {
+    use crate::metadata::artwork;
+    use lofty::picture::PictureType;
+    
+    let pic_type = match pic_type {
+        Some(name) => artwork::picture_type_from_name(&name)
+            .ok_or_else(|| AppError::InvalidInput(format!("Unknown picture type: {}", name)))?,
+        None => PictureType::CoverFront,
+    };
+    let db = state.db.clone();
+    
+    tokio::task::spawn_blocking(move || {
+        let image_data = match (image_bytes, image_path) {
+            (Some(bytes), _) => bytes,
+            (None, Some(image_path)) => std::fs::read(&image_path)
+                .with_context(|| format!("Failed to read image {}", image_path))?,
+            (None, None) => return Err(AppError::InvalidInput("No image provided".to_string())),
+        };
         
-        Ok(None)
+        let path = std::path::Path::new(&file_path);
+        let file_hash = artwork::set_album_art(path, image_data, pic_type)
+            .context("Failed to set album art")?;
+        
+        let file_size = std::fs::metadata(path).ok().map(|m| m.len() as i64);
+        DbOperations::update_track_file_hash(&db, &file_path, &file_hash, file_size)
+            .context("Failed to update track hash")?;
+        
+        println!("[Backend] Embedded {:?} art into {}", pic_type, file_path);
+        Ok(())
+    })
+    .await
+    .context("Task join error")?
+}
+
+/// Pick a JPEG or PNG image as an album's cover, shown for its tracks without embedded
+/// art. None goes back to the folder art.
+#[tauri::command]
+pub fn set_album_artwork(album_id: i64, image_path: Option<String>, state: State<'_, AppState>) -> Result<(), AppError> {
+    if let Some(ref image_path) = image_path {
+        let data = std::fs::read(image_path)
+            .context("Failed to read image file")?;
+        crate::metadata::artwork::validate_image(&data).map_err(AppError::from)?;
+    }
+    
+    let found = DbOperations::set_album_custom_artwork(&state.db, album_id, image_path.as_deref())
+        .context("Failed to set album artwork")?;
+    if !found {
+        return Err(AppError::NotFound(format!("Album not found: {}", album_id)));
+    }
+    Ok(())
+}
+
+/// Delete cached artwork for tracks/albums that are no longer in the library.
+/// Returns the number of bytes reclaimed.
+#[tauri::command]
+pub async fn prune_artwork_cache(state: State<'_, AppState>) -> Result<u64, AppError> {
+    use crate::metadata::artwork;
+    
+    let db = state.db.clone();
+    let cache_dir = state.app_dir.join(artwork::ARTWORK_CACHE_DIR);
+    
+    tokio::task::spawn_blocking(move || {
+        artwork::prune_cache(&db, &cache_dir)
+            .context("Failed to prune artwork cache")
     })
     .await
-    .map_err(|e| format!("Task join error: {}", e))?
+    .context("Task join error")?
 }
 
 #[tauri::command]
-pub async fn get_lyrics(file_path: String) -> Result<Option<String>, String> {
+pub async fn get_lyrics(file_path: String) -> Result<Option<String>, AppError> {
     use lofty::probe::Probe;
     use lofty::tag::ItemKey;
     use std::path::Path;
@@ -360,7 +834,8 @@ pub async fn get_lyrics(file_path: String) -> Result<Option<String>, String> {
     
     // Run file I/O in a blocking task to avoid blocking the async runtime
     tokio::task::spawn_blocking(move || {
-        let path = Path::new(&file_path);
+        let file_path = audio_file_path(&file_path);
+        let path = Path::new(file_path);
         
         // First, try to read .lrc file with the same name
         if let Some(parent) = path.parent() {
@@ -382,10 +857,10 @@ pub async fn get_lyrics(file_path: String) -> Result<Option<String>, String> {
         }
         
         // If no .lrc file, try to read lyrics from audio file tags
-        let tagged_file = Probe::open(&file_path)
-            .map_err(|e| format!("Failed to open file: {}", e))?
+        let tagged_file = Probe::open(file_path)
+            .context("Failed to open file")?
             .read()
-            .map_err(|e| format!("Failed to read file: {}", e))?;
+            .context("Failed to read file")?;
         
         // Try to get lyrics from primary tag first
         if let Some(tag) = tagged_file.primary_tag() {
@@ -432,7 +907,7 @@ pub async fn get_lyrics(file_path: String) -> Result<Option<String>, String> {
         Ok(None)
     })
     .await
-    .map_err(|e| format!("Task join error: {}", e))?
+    .context("Task join error")?
 }
 
 // ===== Queue Management Commands =====
@@ -443,7 +918,7 @@ pub fn create_queue_from_tracks(
     track_ids: Vec<i64>,
     clicked_index: usize,
     state: State<'_, AppState>,
-) -> Result<i64, String> {
+) -> Result<i64, AppError> {
     println!("[Queue] Starting queue creation with {} tracks", track_ids.len());
     
     // Reorder tracks: clicked track first, then remaining after, then before clicked
@@ -451,27 +926,23 @@ pub fn create_queue_from_tracks(
     reordered_tracks.push(track_ids[clicked_index]);
     
     // Add tracks after clicked track
-    for i in (clicked_index + 1)..track_ids.len() {
-        reordered_tracks.push(track_ids[i]);
-    }
+    reordered_tracks.extend_from_slice(&track_ids[clicked_index + 1..]);
     
     // Add tracks before clicked track
-    for i in 0..clicked_index {
-        reordered_tracks.push(track_ids[i]);
-    }
+    reordered_tracks.extend_from_slice(&track_ids[..clicked_index]);
     
     // Check if queue with same name (source) already exists
     println!("[Queue] Checking for existing queue with name: {}", name);
     if let Some(existing_queue_id) = DbOperations::find_queue_by_name(&state.db, &name)
-        .map_err(|e| format!("Failed to check for existing queue: {}", e))? 
+        .context("Failed to check for existing queue")? 
     {
         println!("[Queue] Found existing queue ID: {}, replacing tracks", existing_queue_id);
         // Replace tracks in existing queue
         DbOperations::replace_queue_tracks(&state.db, existing_queue_id, &reordered_tracks)
-            .map_err(|e| format!("Failed to replace queue tracks: {}", e))?;
+            .context("Failed to replace queue tracks")?;
         // Set as active
         DbOperations::set_active_queue(&state.db, existing_queue_id)
-            .map_err(|e| format!("Failed to set active queue: {}", e))?;
+            .context("Failed to set active queue")?;
         println!("[Queue] Replaced tracks in existing queue");
         return Ok(existing_queue_id);
     }
@@ -480,221 +951,585 @@ pub fn create_queue_from_tracks(
     
     // Create new queue (name is unique, so this should succeed)
     let queue_id = DbOperations::create_queue(&state.db, &name)
-        .map_err(|e| format!("Failed to create queue: {}", e))?;
+        .context("Failed to create queue")?;
     println!("[Queue] Created queue '{}' with ID: {}", name, queue_id);
     
     println!("[Queue] Adding {} tracks to queue...", reordered_tracks.len());
     DbOperations::add_tracks_to_queue(&state.db, queue_id, &reordered_tracks)
-        .map_err(|e| format!("Failed to add tracks to queue: {}", e))?;
+        .context("Failed to add tracks to queue")?;
+    
+    // Apply the saved-queue retention policy
+    let max_saved_queues = state.settings()?.playback.max_saved_queues;
+    if max_saved_queues > 0 {
+        if let Err(e) = DbOperations::prune_old_queues(&state.db, max_saved_queues as usize) {
+            eprintln!("[Queue] Failed to prune old queues: {}", e);
+        }
+    }
     
     println!("[Queue] Queue creation complete, ID: {}", queue_id);
     Ok(queue_id)
 }
 
+/// Play everything by an artist (album/disc/track order), optionally shuffled
+#[tauri::command]
+pub fn create_queue_from_artist(
+    artist_id: i64,
+    shuffle: bool,
+    state: State<'_, AppState>,
+) -> Result<QueueStartResult, AppError> {
+    QueueManager::create_queue_from_artist(&state.db, artist_id, shuffle)
+        .context("Failed to create artist queue")
+}
+
+/// Play everything in a genre (grouped by artist, then album), optionally shuffled
+#[tauri::command]
+pub fn create_queue_from_genre(
+    genre_id: i64,
+    shuffle: bool,
+    state: State<'_, AppState>,
+) -> Result<QueueStartResult, AppError> {
+    QueueManager::create_queue_from_genre(&state.db, genre_id, shuffle)
+        .context("Failed to create genre queue")
+}
+
+/// Play a single track right away using the ad-hoc "Now Playing" queue.
+/// Returns the queue id so the frontend can follow along.
 #[tauri::command]
-pub fn get_all_queues(state: State<'_, AppState>) -> Result<Vec<Queue>, String> {
+pub fn play_track_now(track_id: i64, state: State<'_, AppState>) -> Result<i64, AppError> {
+    let track = DbOperations::get_track_by_id(&state.db, track_id)
+        .context("Failed to get track")?
+        .ok_or_else(|| AppError::NotFound("Track not found".to_string()))?;
+    
+    let queue_id = DbOperations::create_single_track_queue(&state.db, track_id)
+        .context("Failed to create queue")?;
+    println!("[Queue] Playing track {} now in queue {}", track_id, queue_id);
+    
+    let gain_db = peak_limited_gain(&state, &track.file_path, track.normalization_gain_db);
+    let player = state.player.lock().context("Lock error")?;
+    state.track_change.track_changed();
+    player.play_with_gain(PathBuf::from(&track.file_path), gain_db)?;
+    
+    Ok(queue_id)
+}
+
+#[tauri::command]
+pub fn get_all_queues(state: State<'_, AppState>) -> Result<Vec<Queue>, AppError> {
     DbOperations::get_all_queues(&state.db)
-        .map_err(|e| format!("Failed to get queues: {}", e))
+        .context("Failed to get queues")
 }
 
 #[tauri::command]
-pub fn get_queue_tracks(queue_id: i64, state: State<'_, AppState>) -> Result<Vec<Track>, String> {
+pub fn get_queue_tracks(queue_id: i64, state: State<'_, AppState>) -> Result<Vec<Track>, AppError> {
     DbOperations::get_queue_tracks(&state.db, queue_id)
-        .map_err(|e| format!("Failed to get queue tracks: {}", e))
+        .context("Failed to get queue tracks")
 }
 
+/// Queue tracks in sequential order, flagged as current/already played (shuffle-aware)
 #[tauri::command]
-pub fn set_active_queue(queue_id: i64, state: State<'_, AppState>) -> Result<(), String> {
+pub fn get_queue_tracks_annotated(queue_id: i64, state: State<'_, AppState>) -> Result<Vec<AnnotatedQueueTrack>, AppError> {
+    DbOperations::get_queue_tracks_annotated(&state.db, queue_id)
+        .context("Failed to get annotated queue tracks")
+}
+
+#[tauri::command]
+pub fn set_active_queue(queue_id: i64, state: State<'_, AppState>) -> Result<(), AppError> {
     DbOperations::set_active_queue(&state.db, queue_id)
-        .map_err(|e| format!("Failed to set active queue: {}", e))
+        .context("Failed to set active queue")
 }
 
 #[tauri::command]
-pub fn get_active_queue(state: State<'_, AppState>) -> Result<Option<Queue>, String> {
+pub fn get_active_queue(state: State<'_, AppState>) -> Result<Option<Queue>, AppError> {
     DbOperations::get_active_queue(&state.db)
-        .map_err(|e| format!("Failed to get active queue: {}", e))
+        .context("Failed to get active queue")
 }
 
 #[tauri::command]
-pub fn delete_queue(queue_id: i64, state: State<'_, AppState>) -> Result<(), String> {
+pub fn delete_queue(queue_id: i64, state: State<'_, AppState>) -> Result<(), AppError> {
     DbOperations::delete_queue(&state.db, queue_id)
-        .map_err(|e| format!("Failed to delete queue: {}", e))
+        .context("Failed to delete queue")
 }
 
+/// Empty a queue but keep it (and its shuffle/repeat settings) around
 #[tauri::command]
-pub fn update_queue_current_index(queue_id: i64, track_index: i32, state: State<'_, AppState>) -> Result<(), String> {
+pub fn clear_queue(queue_id: i64, state: State<'_, AppState>) -> Result<(), AppError> {
+    DbOperations::clear_queue(&state.db, queue_id)
+        .context("Failed to clear queue")
+}
+
+/// Delete the oldest inactive queues beyond `keep`; returns how many were removed
+#[tauri::command]
+pub fn prune_queues(keep: usize, state: State<'_, AppState>) -> Result<usize, AppError> {
+    DbOperations::prune_old_queues(&state.db, keep)
+        .context("Failed to prune queues")
+}
+
+#[tauri::command]
+pub fn update_queue_current_index(queue_id: i64, track_index: i32, state: State<'_, AppState>) -> Result<(), AppError> {
     DbOperations::update_queue_current_index(&state.db, queue_id, track_index)
-        .map_err(|e| format!("Failed to update queue current index: {}", e))
+        .context("Failed to update queue current index")
 }
 
 #[tauri::command]
-pub fn get_queue_current_index(queue_id: i64, state: State<'_, AppState>) -> Result<i32, String> {
+pub fn get_queue_current_index(queue_id: i64, state: State<'_, AppState>) -> Result<i32, AppError> {
     DbOperations::get_queue_current_index(&state.db, queue_id)
-        .map_err(|e| format!("Failed to get queue current index: {}", e))
+        .context("Failed to get queue current index")
 }
 
 #[tauri::command]
-pub fn get_next_queue(excluded_queue_id: i64, state: State<'_, AppState>) -> Result<Option<Queue>, String> {
+pub fn get_next_queue(excluded_queue_id: i64, state: State<'_, AppState>) -> Result<Option<Queue>, AppError> {
     DbOperations::get_next_queue(&state.db, excluded_queue_id)
-        .map_err(|e| format!("Failed to get next queue: {}", e))
+        .context("Failed to get next queue")
 }
 
 #[tauri::command]
-pub fn get_queue_track_at_position(queue_id: i64, position: i32, state: State<'_, AppState>) -> Result<Option<Track>, String> {
+pub fn get_queue_track_at_position(queue_id: i64, position: i32, state: State<'_, AppState>) -> Result<Option<Track>, AppError> {
     DbOperations::get_queue_track_at_position(&state.db, queue_id, position)
-        .map_err(|e| format!("Failed to get queue track at position: {}", e))
+        .context("Failed to get queue track at position")
+}
+
+#[tauri::command]
+pub fn find_track_position_in_queue(queue_id: i64, track_id: i64, occurrence: Option<u32>, state: State<'_, AppState>) -> Result<Option<i32>, AppError> {
+    DbOperations::find_track_position_in_queue(&state.db, queue_id, track_id, occurrence.unwrap_or(0))
+        .context("Failed to find track in queue")
 }
 
 #[tauri::command]
-pub fn get_queue_track_at_shuffled_position(queue_id: i64, shuffled_position: i32, shuffle_seed: i64, anchor_position: i32, state: State<'_, AppState>) -> Result<Option<Track>, String> {
+pub fn queue_jump_to(queue_id: i64, position: i32, state: State<'_, AppState>) -> Result<Track, AppError> {
+    DbOperations::queue_jump_to(&state.db, queue_id, position)
+        .context("Failed to jump to queue position")
+}
+
+#[tauri::command]
+pub fn get_queue_track_at_shuffled_position(queue_id: i64, shuffled_position: i32, shuffle_seed: i64, anchor_position: i32, state: State<'_, AppState>) -> Result<Option<Track>, AppError> {
     DbOperations::get_queue_track_at_shuffled_position(&state.db, queue_id, shuffled_position, shuffle_seed, anchor_position)
-        .map_err(|e| format!("Failed to get queue track at shuffled position: {}", e))
+        .context("Failed to get queue track at shuffled position")
 }
 
 #[tauri::command]
-pub fn get_queue_length(queue_id: i64, state: State<'_, AppState>) -> Result<i32, String> {
+pub fn get_queue_length(queue_id: i64, state: State<'_, AppState>) -> Result<i32, AppError> {
     DbOperations::get_queue_length(&state.db, queue_id)
-        .map_err(|e| format!("Failed to get queue length: {}", e))
+        .context("Failed to get queue length")
+}
+
+/// Total and remaining play time of a queue, for the queue header
+#[tauri::command]
+pub fn get_queue_duration(queue_id: i64, state: State<'_, AppState>) -> Result<QueueDuration, AppError> {
+    DbOperations::get_queue_duration(&state.db, queue_id)
+        .context("Failed to get queue duration")
 }
 
 #[tauri::command]
-pub fn toggle_queue_shuffle(queue_id: i64, current_track_id: Option<i64>, state: State<'_, AppState>) -> Result<(i64, i32), String> {
+pub fn get_queue_total_duration_ms(queue_id: i64, state: State<'_, AppState>) -> Result<i64, AppError> {
+    DbOperations::get_queue_total_duration_ms(&state.db, queue_id)
+        .context("Failed to get queue duration")
+}
+
+/// Play time of the queue from `from_position` (in play order) to its end
+#[tauri::command]
+pub fn get_queue_remaining_duration_ms(queue_id: i64, from_position: i32, state: State<'_, AppState>) -> Result<i64, AppError> {
+    DbOperations::get_queue_remaining_duration_ms(&state.db, queue_id, from_position)
+        .context("Failed to get queue remaining time")
+}
+
+#[tauri::command]
+pub fn toggle_queue_shuffle(queue_id: i64, current_track_id: Option<i64>, state: State<'_, AppState>) -> Result<(i64, i32), AppError> {
     DbOperations::toggle_queue_shuffle(&state.db, queue_id, current_track_id)
-        .map_err(|e| format!("Failed to toggle queue shuffle: {}", e))
+        .context("Failed to toggle queue shuffle")
 }
 
 #[tauri::command]
-pub fn set_queue_shuffle_seed(queue_id: i64, shuffle_seed: i64, state: State<'_, AppState>) -> Result<(), String> {
+pub fn set_queue_shuffle_seed(queue_id: i64, shuffle_seed: i64, state: State<'_, AppState>) -> Result<(), AppError> {
     DbOperations::set_queue_shuffle_seed(&state.db, queue_id, shuffle_seed)
-        .map_err(|e| format!("Failed to set queue shuffle seed: {}", e))
+        .context("Failed to set queue shuffle seed")
 }
 
 #[tauri::command]
-pub fn get_queue_shuffle_seed(queue_id: i64, state: State<'_, AppState>) -> Result<i64, String> {
+pub fn get_queue_shuffle_seed(queue_id: i64, state: State<'_, AppState>) -> Result<i64, AppError> {
     DbOperations::get_queue_shuffle_seed(&state.db, queue_id)
-        .map_err(|e| format!("Failed to get queue shuffle seed: {}", e))
+        .context("Failed to get queue shuffle seed")
 }
 
 #[tauri::command]
-pub fn set_queue_shuffle_anchor(queue_id: i64, shuffle_anchor: i64, state: State<'_, AppState>) -> Result<(), String> {
+pub fn set_queue_shuffle_anchor(queue_id: i64, shuffle_anchor: i64, state: State<'_, AppState>) -> Result<(), AppError> {
     DbOperations::set_queue_shuffle_anchor(&state.db, queue_id, shuffle_anchor)
-        .map_err(|e| format!("Failed to set queue shuffle anchor: {}", e))
+        .context("Failed to set queue shuffle anchor")
 }
 
 #[tauri::command]
-pub fn get_queue_shuffle_anchor(queue_id: i64, state: State<'_, AppState>) -> Result<i64, String> {
+pub fn get_queue_shuffle_anchor(queue_id: i64, state: State<'_, AppState>) -> Result<i64, AppError> {
     DbOperations::get_queue_shuffle_anchor(&state.db, queue_id)
-        .map_err(|e| format!("Failed to get queue shuffle anchor: {}", e))
+        .context("Failed to get queue shuffle anchor")
+}
+
+#[tauri::command]
+pub fn get_queue_playback_modes(queue_id: i64, state: State<'_, AppState>) -> Result<QueuePlaybackModes, AppError> {
+    DbOperations::get_queue_playback_modes(&state.db, queue_id)
+        .context("Failed to get queue playback modes")
+}
+
+#[tauri::command]
+pub fn set_queue_playback_modes(queue_id: i64, shuffle_on: bool, repeat_mode: RepeatMode, state: State<'_, AppState>) -> Result<QueuePlaybackModes, AppError> {
+    DbOperations::set_queue_playback_modes(&state.db, queue_id, shuffle_on, repeat_mode)
+        .context("Failed to set queue playback modes")
+}
+
+#[tauri::command]
+pub fn set_queue_repeat_mode(queue_id: i64, repeat_mode: RepeatMode, state: State<'_, AppState>) -> Result<(), AppError> {
+    DbOperations::set_queue_repeat_mode(&state.db, queue_id, repeat_mode)
+        .context("Failed to set queue repeat mode")
+}
+
+#[tauri::command]
+pub fn get_queue_repeat_mode(queue_id: i64, state: State<'_, AppState>) -> Result<RepeatMode, AppError> {
+    DbOperations::get_queue_repeat_mode(&state.db, queue_id)
+        .context("Failed to get queue repeat mode")
+}
+
+/// Queue position to play after `current_index`, or None at the end of a non-repeating queue
+#[tauri::command]
+pub fn get_next_queue_position(queue_id: i64, current_index: i32, state: State<'_, AppState>) -> Result<Option<i32>, AppError> {
+    DbOperations::next_position(&state.db, queue_id, current_index)
+        .context("Failed to get next queue position")
+}
+
+#[tauri::command]
+pub fn find_shuffled_position(original_index: i32, seed: i64, queue_length: i32, anchor_position: i32) -> Result<i32, AppError> {
+    DbOperations::find_shuffled_position(original_index, seed, queue_length, anchor_position)
+        .map_err(|e| AppError::Other(format!("Failed to find shuffled position: {}", e)))
+}
+
+// ===== System Playlists Commands =====
+
+#[tauri::command]
+pub fn get_recent_tracks(state: State<'_, AppState>) -> Result<Vec<Track>, AppError> {
+    DbOperations::get_recent_tracks(&state.db)
+        .context("Failed to get recent tracks")
+}
+
+#[tauri::command]
+pub fn get_recently_played_albums(limit: usize, state: State<'_, AppState>) -> Result<Vec<Album>, AppError> {
+    DbOperations::get_recently_played_albums(&state.db, limit)
+        .context("Failed to get recently played albums")
+}
+
+/// Tracks marked as favorite, highest rated first
+#[tauri::command]
+pub fn get_favorite_tracks(state: State<'_, AppState>) -> Result<Vec<Track>, AppError> {
+    DbOperations::get_favorite_tracks(&state.db)
+        .context("Failed to get favorite tracks")
+}
+
+/// Tracks played on this month/day in earlier years
+#[tauri::command]
+pub fn get_tracks_played_on_day(month: u32, day: u32, state: State<'_, AppState>) -> Result<Vec<Track>, AppError> {
+    DbOperations::get_tracks_played_on_day(&state.db, month, day)
+        .context("Failed to get tracks played on day")
+}
+
+/// Set a track's star rating (0 = unrated, up to 5)
+#[tauri::command]
+pub fn set_track_rating(track_id: i64, rating: i32, state: State<'_, AppState>) -> Result<(), AppError> {
+    DbOperations::set_track_rating(&state.db, track_id, rating)
+        .context("Failed to set track rating")
+}
+
+/// Flip a track's favorite flag, returns the new state
+#[tauri::command]
+pub fn toggle_favorite(track_id: i64, state: State<'_, AppState>) -> Result<bool, AppError> {
+    DbOperations::toggle_favorite(&state.db, track_id)
+        .context("Failed to toggle favorite")
+}
+
+/// Wait for a pending track change to settle. False, with a log line, if the track was
+/// replaced before it settled (rapid skipping).
+async fn wait_for_settled_track(state: &AppState, track_id: i64) -> Result<bool, AppError> {
+    let track_change = state.track_change.clone();
+    let generation = track_change.generation();
+    let settled = tokio::task::spawn_blocking(move || track_change.wait_until_settled(generation))
+        .await
+        .context("Task join error")?;
+    if !settled {
+        println!("[Backend] Not recording play for track {}: track changed before it settled", track_id);
+    }
+    Ok(settled)
+}
+
+/// Wait for a pending track change to settle, then claim the listen of the current track
+/// for counting a play. False, with a log line saying why, if the track was replaced before it
+/// settled or this listen was already counted.
+async fn claim_settled_listen(state: &AppState, track_id: i64) -> Result<bool, AppError> {
+    if !wait_for_settled_track(state, track_id).await? {
+        return Ok(false);
+    }
+    if !state.track_change.claim_listen() {
+        println!("[Backend] Not recording play for track {}: this listen was already counted", track_id);
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Count a play of a track once it passed the play threshold (half of its duration).
+/// Unlike `record_track_play` this doesn't add to the track's play time.
+#[tauri::command]
+pub async fn record_play(track_id: i64, state: State<'_, AppState>) -> Result<(), AppError> {
+    if !claim_settled_listen(&state, track_id).await? {
+        return Ok(());
+    }
+    let found = DbOperations::increment_play_count(&state.db, track_id)
+        .context("Failed to record play")?;
+    if !found {
+        return Err(AppError::NotFound(format!("Track not found: {}", track_id)));
+    }
+    Ok(())
+}
+
+/// Record a finished track: adds its play time, and counts the play unless `record_play`
+/// already counted this listen at the play threshold
+#[tauri::command]
+pub async fn record_track_play(track_id: i64, duration_seconds: i64, state: State<'_, AppState>) -> Result<(), AppError> {
+    // A track that was only current for a moment (rapid skipping) doesn't count as played
+    if !wait_for_settled_track(&state, track_id).await? {
+        return Ok(());
+    }
+    if state.track_change.claim_listen() {
+        DbOperations::record_track_play(&state.db, track_id, duration_seconds)
+            .context("Failed to record track play")
+    } else {
+        DbOperations::add_play_time(&state.db, track_id, duration_seconds)
+            .context("Failed to record track play time")
+    }
+}
+
+/// Count a skip of a track, called instead of `record_play` when the track is left before
+/// the play threshold. Each listen counts as a play or a skip, whichever comes first.
+#[tauri::command]
+pub fn record_skip(track_id: i64, state: State<'_, AppState>) -> Result<(), AppError> {
+    // Unlike a play there is nothing to wait for: the track is being left, and if its own
+    // track change hasn't settled yet the user is skipping through tracks rapidly
+    if !state.track_change.is_settled() {
+        println!("[Backend] Not recording skip for track {}: track change hasn't settled", track_id);
+        return Ok(());
+    }
+    if !state.track_change.claim_listen() {
+        println!("[Backend] Not recording skip for track {}: this listen was already counted", track_id);
+        return Ok(());
+    }
+    let found = DbOperations::increment_skip_count(&state.db, track_id)
+        .context("Failed to record skip")?;
+    if !found {
+        return Err(AppError::NotFound(format!("Track not found: {}", track_id)));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_most_played_tracks(state: State<'_, AppState>) -> Result<Vec<Track>, AppError> {
+    DbOperations::get_most_played_tracks(&state.db)
+        .context("Failed to get most played tracks")
+}
+
+/// Tracks that are skipped more often than they're played
+#[tauri::command]
+pub fn get_never_finished_tracks(state: State<'_, AppState>) -> Result<Vec<Track>, AppError> {
+    DbOperations::get_never_finished_tracks(&state.db)
+        .context("Failed to get never finished tracks")
+}
+
+#[tauri::command]
+pub fn get_unplayed_tracks(state: State<'_, AppState>) -> Result<Vec<Track>, AppError> {
+    DbOperations::get_unplayed_tracks(&state.db)
+        .context("Failed to get unplayed tracks")
+}
+
+// ===== User Playlists Commands =====
+
+#[tauri::command]
+pub fn get_all_playlists(state: State<'_, AppState>) -> Result<Vec<Playlist>, AppError> {
+    DbOperations::get_all_playlists(&state.db)
+        .context("Failed to get playlists")
+}
+
+#[tauri::command]
+pub fn create_playlist(state: State<'_, AppState>, name: String, description: Option<String>) -> Result<i64, AppError> {
+    DbOperations::create_playlist(&state.db, &name, description.as_deref())
+        .context("Failed to create playlist")
+}
+
+#[tauri::command]
+pub fn rename_playlist(state: State<'_, AppState>, playlist_id: i64, new_name: String) -> Result<(), AppError> {
+    DbOperations::rename_playlist(&state.db, playlist_id, &new_name)
+        .context("Failed to rename playlist")
+}
+
+#[tauri::command]
+pub fn add_track_to_playlist(state: State<'_, AppState>, playlist_id: i64, track_id: i64) -> Result<(), AppError> {
+    DbOperations::add_track_to_playlist(&state.db, playlist_id, track_id)
+        .context("Failed to add track to playlist")
 }
 
 #[tauri::command]
-pub fn find_shuffled_position(original_index: i32, seed: i64, queue_length: i32, anchor_position: i32) -> Result<i32, String> {
-    DbOperations::find_shuffled_position(original_index, seed, queue_length, anchor_position)
-        .map_err(|e| format!("Failed to find shuffled position: {}", e))
+pub fn get_playlist_tracks(state: State<'_, AppState>, playlist_id: i64) -> Result<Vec<Track>, AppError> {
+    DbOperations::get_playlist_tracks(&state.db, playlist_id)
+        .context("Failed to get playlist tracks")
 }
 
-// ===== System Playlists Commands =====
-
+/// Write a playlist's tracks, in order, to an M3U8 file
 #[tauri::command]
-pub fn get_recent_tracks(state: State<'_, AppState>) -> Result<Vec<Track>, String> {
-    DbOperations::get_recent_tracks(&state.db)
-        .map_err(|e| format!("Failed to get recent tracks: {}", e))
+pub fn export_playlist_m3u8(state: State<'_, AppState>, playlist_id: i64, out_path: String) -> Result<(), AppError> {
+    let tracks = DbOperations::get_playlist_tracks(&state.db, playlist_id)
+        .context("Failed to get playlist tracks")?;
+    export_m3u8(&tracks, std::path::Path::new(&out_path))
+        .context("Failed to export playlist")
 }
 
 #[tauri::command]
-pub fn record_track_play(track_id: i64, duration_seconds: i64, state: State<'_, AppState>) -> Result<(), String> {
-    DbOperations::record_track_play(&state.db, track_id, duration_seconds)
-        .map_err(|e| format!("Failed to record track play: {}", e))
+pub fn remove_track_from_playlist(state: State<'_, AppState>, playlist_id: i64, track_id: i64) -> Result<(), AppError> {
+    DbOperations::remove_track_from_playlist(&state.db, playlist_id, track_id)
+        .context("Failed to remove track from playlist")
 }
 
 #[tauri::command]
-pub fn get_most_played_tracks(state: State<'_, AppState>) -> Result<Vec<Track>, String> {
-    DbOperations::get_most_played_tracks(&state.db)
-        .map_err(|e| format!("Failed to get most played tracks: {}", e))
+pub fn remove_playlist_track_at_position(state: State<'_, AppState>, playlist_id: i64, position: i32) -> Result<(), AppError> {
+    DbOperations::remove_playlist_track_at_position(&state.db, playlist_id, position)
+        .context("Failed to remove track from playlist")
 }
 
 #[tauri::command]
-pub fn get_unplayed_tracks(state: State<'_, AppState>) -> Result<Vec<Track>, String> {
-    DbOperations::get_unplayed_tracks(&state.db)
-        .map_err(|e| format!("Failed to get unplayed tracks: {}", e))
+pub fn delete_playlist(state: State<'_, AppState>, playlist_id: i64) -> Result<(), AppError> {
+    DbOperations::delete_playlist(&state.db, playlist_id)
+        .context("Failed to delete playlist")
 }
 
-// ===== User Playlists Commands =====
-
+/// Create a smart playlist from SmartPlaylistRules JSON, returns its id
 #[tauri::command]
-pub fn get_all_playlists(state: State<'_, AppState>) -> Result<Vec<Playlist>, String> {
-    DbOperations::get_all_playlists(&state.db)
-        .map_err(|e| format!("Failed to get playlists: {}", e))
+pub fn create_smart_playlist(state: State<'_, AppState>, name: String, rules_json: String) -> Result<i64, AppError> {
+    DbOperations::create_smart_playlist(&state.db, &name, &rules_json)
+        .context("Failed to create smart playlist")
 }
 
 #[tauri::command]
-pub fn create_playlist(state: State<'_, AppState>, name: String, description: Option<String>) -> Result<i64, String> {
-    DbOperations::create_playlist(&state.db, &name, description.as_deref())
-        .map_err(|e| format!("Failed to create playlist: {}", e))
+pub fn get_all_smart_playlists(state: State<'_, AppState>) -> Result<Vec<SmartPlaylist>, AppError> {
+    DbOperations::get_all_smart_playlists(&state.db)
+        .context("Failed to get smart playlists")
 }
 
+/// Tracks currently matching a smart playlist's rules
 #[tauri::command]
-pub fn rename_playlist(state: State<'_, AppState>, playlist_id: i64, new_name: String) -> Result<(), String> {
-    DbOperations::rename_playlist(&state.db, playlist_id, &new_name)
-        .map_err(|e| format!("Failed to rename playlist: {}", e))
+pub fn get_smart_playlist_tracks(state: State<'_, AppState>, smart_playlist_id: i64) -> Result<Vec<Track>, AppError> {
+    DbOperations::evaluate_smart_playlist(&state.db, smart_playlist_id)
+        .context("Failed to get smart playlist tracks")
 }
 
 #[tauri::command]
-pub fn add_track_to_playlist(state: State<'_, AppState>, playlist_id: i64, track_id: i64) -> Result<(), String> {
-    DbOperations::add_track_to_playlist(&state.db, playlist_id, track_id)
-        .map_err(|e| format!("Failed to add track to playlist: {}", e))
+pub fn delete_smart_playlist(state: State<'_, AppState>, smart_playlist_id: i64) -> Result<(), AppError> {
+    DbOperations::delete_smart_playlist(&state.db, smart_playlist_id)
+        .context("Failed to delete smart playlist")
 }
 
 #[tauri::command]
-pub fn get_playlist_tracks(state: State<'_, AppState>, playlist_id: i64) -> Result<Vec<Track>, String> {
-    DbOperations::get_playlist_tracks(&state.db, playlist_id)
-        .map_err(|e| format!("Failed to get playlist tracks: {}", e))
+pub fn reorder_playlist_track(state: State<'_, AppState>, playlist_id: i64, from_position: i32, to_position: i32) -> Result<(), AppError> {
+    DbOperations::reorder_playlist_track(&state.db, playlist_id, from_position, to_position)
+        .context("Failed to reorder playlist track")
 }
 
+/// Create a playlist from the audio files in a folder, indexing any that aren't in the library
 #[tauri::command]
-pub fn remove_track_from_playlist(state: State<'_, AppState>, playlist_id: i64, track_id: i64) -> Result<(), String> {
-    DbOperations::remove_track_from_playlist(&state.db, playlist_id, track_id)
-        .map_err(|e| format!("Failed to remove track from playlist: {}", e))
+pub async fn create_playlist_from_folder(
+    path: String,
+    name: String,
+    recursive: bool,
+    sort_by_track_number: bool,
+    state: State<'_, AppState>,
+) -> Result<FolderPlaylistResult, AppError> {
+    let db = state.db.clone();
+    
+    tokio::task::spawn_blocking(move || {
+        PlaylistManager::create_playlist_from_folder(
+            &db,
+            std::path::Path::new(&path),
+            &name,
+            recursive,
+            sort_by_track_number,
+        )
+        .context("Failed to create playlist from folder")
+    })
+    .await
+    .context("Task join error")?
 }
 
+/// Create a playlist from an M3U/M3U8 file, returning the referenced files not in the library
 #[tauri::command]
-pub fn delete_playlist(state: State<'_, AppState>, playlist_id: i64) -> Result<(), String> {
-    DbOperations::delete_playlist(&state.db, playlist_id)
-        .map_err(|e| format!("Failed to delete playlist: {}", e))
+pub async fn import_playlist_m3u8(
+    path: String,
+    playlist_name: String,
+    state: State<'_, AppState>,
+) -> Result<ImportPlaylistResult, AppError> {
+    let db = state.db.clone();
+    
+    tokio::task::spawn_blocking(move || {
+        PlaylistManager::import_m3u8_playlist(&db, std::path::Path::new(&path), &playlist_name)
+            .context("Failed to import playlist")
+    })
+    .await
+    .context("Task join error")?
 }
 
 #[tauri::command]
-pub fn reorder_playlist_track(state: State<'_, AppState>, playlist_id: i64, from_position: i32, to_position: i32) -> Result<(), String> {
-    DbOperations::reorder_playlist_track(&state.db, playlist_id, from_position, to_position)
-        .map_err(|e| format!("Failed to reorder playlist track: {}", e))
+pub fn reorder_queue_track(state: State<'_, AppState>, queue_id: i64, from_position: i32, to_position: i32) -> Result<i32, AppError> {
+    DbOperations::reorder_queue_track(&state.db, queue_id, from_position, to_position)
+        .context("Failed to reorder queue track")
 }
 
+/// Sort a queue by "title", "artist", "album" or "duration", returns the new current_track_index
 #[tauri::command]
-pub fn reorder_queue_track(state: State<'_, AppState>, queue_id: i64, from_position: i32, to_position: i32) -> Result<i32, String> {
-    DbOperations::reorder_queue_track(&state.db, queue_id, from_position, to_position)
-        .map_err(|e| format!("Failed to reorder queue track: {}", e))
+pub fn sort_queue(state: State<'_, AppState>, queue_id: i64, sort_key: String, ascending: bool) -> Result<i32, AppError> {
+    DbOperations::sort_queue(&state.db, queue_id, &sort_key, ascending)
+        .context("Failed to sort queue")
 }
 
+/// Append tracks to the end of a queue. With `group_by_album`, the tracks are sorted by
+/// album/disc/track first so albums stay contiguous; by default the given order is kept.
 #[tauri::command]
-pub fn append_tracks_to_queue(state: State<'_, AppState>, queue_id: i64, track_ids: Vec<i64>) -> Result<(), String> {
+pub fn append_tracks_to_queue(state: State<'_, AppState>, queue_id: i64, track_ids: Vec<i64>, group_by_album: Option<bool>) -> Result<(), AppError> {
+    let track_ids = QueueManager::order_for_insert(&state.db, &track_ids, group_by_album.unwrap_or(false))
+        .context("Failed to order tracks")?;
     DbOperations::append_tracks_to_queue(&state.db, queue_id, &track_ids)
-        .map_err(|e| format!("Failed to append tracks to queue: {}", e))
+        .context("Failed to append tracks to queue")
 }
 
+/// Insert tracks after a queue position ("play next"), optionally grouped by album
 #[tauri::command]
-pub fn insert_tracks_after_position(state: State<'_, AppState>, queue_id: i64, track_ids: Vec<i64>, after_position: i32) -> Result<(), String> {
+pub fn insert_tracks_after_position(state: State<'_, AppState>, queue_id: i64, track_ids: Vec<i64>, after_position: i32, group_by_album: Option<bool>) -> Result<(), AppError> {
+    let track_ids = QueueManager::order_for_insert(&state.db, &track_ids, group_by_album.unwrap_or(false))
+        .context("Failed to order tracks")?;
     DbOperations::insert_tracks_after_position(&state.db, queue_id, &track_ids, after_position)
-        .map_err(|e| format!("Failed to insert tracks after position: {}", e))
+        .context("Failed to insert tracks after position")
+}
+
+/// "Play next": insert tracks right after the playing track of the active queue (starting a
+/// queue with the playing track if there is none), returns the new queue length
+#[tauri::command]
+pub fn play_next(track_ids: Vec<i64>, state: State<'_, AppState>) -> Result<i32, AppError> {
+    let current_file = state.player.lock().context("Lock error")?.current_file();
+    let current_track_id = match current_file {
+        Some(file_path) => DbOperations::get_track_by_file_path(&state.db, &file_path.to_string_lossy())
+            .context("Failed to get track")?
+            .map(|track| track.id),
+        None => None,
+    };
+    
+    QueueManager::play_next(&state.db, current_track_id, &track_ids)
+        .context("Failed to play next")
 }
 
 #[tauri::command]
-pub fn remove_track_at_position(state: State<'_, AppState>, queue_id: i64, position: i32) -> Result<i32, String> {
+pub fn remove_track_at_position(state: State<'_, AppState>, queue_id: i64, position: i32) -> Result<i32, AppError> {
     DbOperations::remove_track_at_position(&state.db, queue_id, position)
-        .map_err(|e| format!("Failed to remove track at position: {}", e))
+        .context("Failed to remove track at position")
 }
 
 #[tauri::command]
-pub async fn save_album_art(app: AppHandle, file_path: String, default_name: String) -> Result<bool, String> {
+pub async fn save_album_art(app: AppHandle, file_path: String, default_name: String) -> Result<bool, AppError> {
     use tauri_plugin_dialog::DialogExt;
     use lofty::probe::Probe;
     use lofty::picture::PictureType;
@@ -729,10 +1564,10 @@ pub async fn save_album_art(app: AppHandle, file_path: String, default_name: Str
             }
         }
         None
-    }).await.map_err(|e| format!("Failed to get album art: {}", e))?;
+    }).await.context("Failed to get album art")?;
     
     let Some(data) = art_data else {
-        return Err("No album art found".to_string());
+        return Err(AppError::NotFound("No album art found".to_string()));
     };
     
     // Show save dialog using callback-based approach
@@ -741,14 +1576,14 @@ pub async fn save_album_art(app: AppHandle, file_path: String, default_name: Str
     
     app.dialog()
         .file()
-        .set_file_name(&format!("{}.jpg", default_name))
+        .set_file_name(format!("{}.jpg", default_name))
         .add_filter("JPEG Image", &["jpg", "jpeg"])
         .add_filter("PNG Image", &["png"])
         .save_file(move |file_path_opt| {
             let result = if let Some(path) = file_path_opt {
                 match fs::write(path.as_path().unwrap(), &data_clone) {
                     Ok(_) => Ok(true),
-                    Err(e) => Err(format!("Failed to write file: {}", e)),
+                    Err(e) => Err(AppError::from(e).context("Failed to write file")),
                 }
             } else {
                 Ok(false) // User cancelled
@@ -756,39 +1591,42 @@ pub async fn save_album_art(app: AppHandle, file_path: String, default_name: Str
             let _ = tx.send(result);
         });
     
-    rx.recv().map_err(|e| format!("Dialog error: {}", e))?
+    rx.recv().map_err(|e| AppError::Other(format!("Dialog error: {}", e)))?
 }
 
 // ===== Audio Player Commands =====
 
 use crate::audio::player::PlayerState;
+use crate::audio::output::{AudioOutput, OutputInfo};
+use crate::audio::crossfeed::CrossfeedLevel;
 
 #[tauri::command]
 pub fn player_play(
     file_path: String,
     state: State<'_, AppState>,
-) -> Result<(), String> {
-    let player = state.player.lock().map_err(|e| format!("Lock error: {}", e))?;
+) -> Result<(), AppError> {
+    let player = state.player.lock().context("Lock error")?;
+    state.track_change.track_changed();
     player.play(PathBuf::from(file_path))
 }
 
 #[tauri::command]
-pub fn player_pause(state: State<'_, AppState>) -> Result<(), String> {
-    let player = state.player.lock().map_err(|e| format!("Lock error: {}", e))?;
+pub fn player_pause(state: State<'_, AppState>) -> Result<(), AppError> {
+    let player = state.player.lock().context("Lock error")?;
     player.pause();
     Ok(())
 }
 
 #[tauri::command]
-pub fn player_resume(state: State<'_, AppState>) -> Result<(), String> {
-    let player = state.player.lock().map_err(|e| format!("Lock error: {}", e))?;
+pub fn player_resume(state: State<'_, AppState>) -> Result<(), AppError> {
+    let player = state.player.lock().context("Lock error")?;
     player.resume();
     Ok(())
 }
 
 #[tauri::command]
-pub fn player_stop(state: State<'_, AppState>) -> Result<(), String> {
-    let player = state.player.lock().map_err(|e| format!("Lock error: {}", e))?;
+pub fn player_stop(state: State<'_, AppState>) -> Result<(), AppError> {
+    let player = state.player.lock().context("Lock error")?;
     player.stop();
     Ok(())
 }
@@ -797,18 +1635,28 @@ pub fn player_stop(state: State<'_, AppState>) -> Result<(), String> {
 pub fn player_seek(
     position_ms: i64,
     state: State<'_, AppState>,
-) -> Result<(), String> {
-    let player = state.player.lock().map_err(|e| format!("Lock error: {}", e))?;
+) -> Result<(), AppError> {
+    let player = state.player.lock().context("Lock error")?;
     player.seek(position_ms);
     Ok(())
 }
 
+/// Seek to a percentage (0-100) of the current track, returns the target position in ms
+#[tauri::command]
+pub fn seek_to_percent(
+    percent: f32,
+    state: State<'_, AppState>,
+) -> Result<i64, AppError> {
+    let player = state.player.lock().context("Lock error")?;
+    player.seek_to_percent(percent)
+}
+
 #[tauri::command]
 pub fn player_set_volume(
     volume: f32,
     state: State<'_, AppState>,
-) -> Result<(), String> {
-    let player = state.player.lock().map_err(|e| format!("Lock error: {}", e))?;
+) -> Result<(), AppError> {
+    let player = state.player.lock().context("Lock error")?;
     player.set_volume(volume);
     Ok(())
 }
@@ -817,45 +1665,150 @@ pub fn player_set_volume(
 pub fn player_set_volume_db(
     db: f32,
     state: State<'_, AppState>,
-) -> Result<(), String> {
-    let player = state.player.lock().map_err(|e| format!("Lock error: {}", e))?;
+) -> Result<(), AppError> {
+    let player = state.player.lock().context("Lock error")?;
     player.set_volume_db(db);
     Ok(())
 }
 
+/// Output device, sample rate, channel count and buffer length, for diagnostics
+#[tauri::command]
+pub fn get_output_info(state: State<'_, AppState>) -> Result<OutputInfo, AppError> {
+    let player = state.player.lock().context("Lock error")?;
+    player.output_info()
+}
+
+/// Names of the available audio output devices
+#[tauri::command]
+pub fn list_audio_devices() -> Vec<String> {
+    AudioOutput::list_devices()
+}
+
+/// Play through the named output device (None = system default) and remember the choice.
+/// Returns the device actually used, the default one if the named device isn't available.
+#[tauri::command]
+pub fn set_audio_device(name: Option<String>, state: State<'_, AppState>) -> Result<Option<String>, AppError> {
+    let selected = {
+        let player = state.player.lock().context("Lock error")?;
+        player.set_output_device(name.clone())
+    };
+    state.update_settings(|settings| {
+        settings.playback.output_device = name;
+        Ok(())
+    })?;
+    Ok(selected)
+}
+
+/// Everything needed on launch to continue the last session: active queue, current
+/// track index and position, shuffle/repeat state and volume
+#[tauri::command]
+pub fn restore_session(state: State<'_, AppState>) -> Result<SessionState, AppError> {
+    let volume_db = state.settings()?.playback.volume_db;
+    DbOperations::get_session_state(&state.db, volume_db)
+        .context("Failed to restore session")
+}
+
+/// Remember the playback position and volume so the next launch can resume from them.
+/// Called periodically and on pause/close by the frontend.
+#[tauri::command]
+pub fn save_session(
+    queue_id: i64,
+    position_ms: i64,
+    volume_db: f32,
+    state: State<'_, AppState>,
+) -> Result<(), AppError> {
+    DbOperations::update_queue_position(&state.db, queue_id, position_ms)
+        .context("Failed to save queue position")?;
+    
+    if state.settings()?.playback.volume_db != volume_db {
+        state.update_settings(|settings| {
+            settings.playback.volume_db = volume_db;
+            Ok(())
+        })?;
+    }
+    Ok(())
+}
+
 #[tauri::command]
-pub fn player_get_state(state: State<'_, AppState>) -> Result<PlayerState, String> {
-    let player = state.player.lock().map_err(|e| format!("Lock error: {}", e))?;
+pub fn player_get_state(state: State<'_, AppState>) -> Result<PlayerState, AppError> {
+    let player = state.player.lock().context("Lock error")?;
     Ok(player.get_state())
 }
 
+/// Current playback position in milliseconds, following the audio actually played
+#[tauri::command]
+pub fn get_playback_position_ms(state: State<'_, AppState>) -> Result<i64, AppError> {
+    let player = state.player.lock().context("Lock error")?;
+    Ok(player.current_position().as_millis() as i64)
+}
+
+#[tauri::command]
+pub fn player_has_track_ended(app: AppHandle, state: State<'_, AppState>) -> Result<bool, AppError> {
+    match state.check_track_end()? {
+        TrackEndOutcome::Advance => Ok(true),
+        TrackEndOutcome::StoppedAfterTrack => {
+            println!("[Player] Stopped after current track");
+            let _ = app.emit("stopped-after-track", ());
+            Ok(false)
+        }
+        TrackEndOutcome::Playing => Ok(false),
+    }
+}
+
+/// Stop playback when the current track ends instead of advancing
+#[tauri::command]
+pub fn set_stop_after_current(enabled: bool, state: State<'_, AppState>) -> Result<(), AppError> {
+    state.stop_after_current.store(enabled, std::sync::atomic::Ordering::SeqCst);
+    if enabled {
+        // A preloaded track would roll over gaplessly without reporting a track end
+        let player = state.player.lock().context("Lock error")?;
+        player.clear_preloaded_track();
+    }
+    Ok(())
+}
+
 #[tauri::command]
-pub fn player_has_track_ended(state: State<'_, AppState>) -> Result<bool, String> {
-    let player = state.player.lock().map_err(|e| format!("Lock error: {}", e))?;
-    Ok(player.has_track_ended())
+pub fn get_stop_after_current(state: State<'_, AppState>) -> Result<bool, AppError> {
+    Ok(state.stop_after_current.load(std::sync::atomic::Ordering::SeqCst))
 }
 
+/// Line up the next track for a gapless transition. Does nothing while gapless playback
+/// is disabled.
 #[tauri::command]
-pub fn player_preload_next_track(
+pub async fn player_preload_next_track(
     file_path: String,
     normalization_gain_db: Option<f32>,
     state: State<'_, AppState>,
-) -> Result<(), String> {
-    let player = state.player.lock().map_err(|e| format!("Lock error: {}", e))?;
-    player.preload_next_track(PathBuf::from(file_path), normalization_gain_db);
-    Ok(())
+) -> Result<(), AppError> {
+    // Playback stops after this track, so there's nothing to roll over into
+    if state.stop_after_current.load(std::sync::atomic::Ordering::SeqCst) {
+        return Ok(());
+    }
+    let gain_db = peak_limited_gain(&state, &file_path, normalization_gain_db);
+    let player = state.player.clone();
+    
+    // Opening the decoder reads the file, keep it off the async runtime
+    tokio::task::spawn_blocking(move || {
+        let player = player.lock().context("Lock error")?;
+        if !player.is_gapless() {
+            return Ok(());
+        }
+        player.enqueue_next(PathBuf::from(file_path), gain_db)
+    })
+    .await
+    .context("Task join error")?
 }
 
 #[tauri::command]
-pub fn player_clear_preloaded_track(state: State<'_, AppState>) -> Result<(), String> {
-    let player = state.player.lock().map_err(|e| format!("Lock error: {}", e))?;
+pub fn player_clear_preloaded_track(state: State<'_, AppState>) -> Result<(), AppError> {
+    let player = state.player.lock().context("Lock error")?;
     player.clear_preloaded_track();
     Ok(())
 }
 
 #[tauri::command]
-pub fn player_has_gapless_transition(state: State<'_, AppState>) -> Result<bool, String> {
-    let player = state.player.lock().map_err(|e| format!("Lock error: {}", e))?;
+pub fn player_has_gapless_transition(state: State<'_, AppState>) -> Result<bool, AppError> {
+    let player = state.player.lock().context("Lock error")?;
     Ok(player.has_gapless_transition())
 }
 
@@ -864,17 +1817,65 @@ pub fn player_play_with_normalization(
     file_path: String,
     normalization_gain_db: Option<f32>,
     state: State<'_, AppState>,
-) -> Result<(), String> {
-    let player = state.player.lock().map_err(|e| format!("Lock error: {}", e))?;
-    player.play_with_gain(PathBuf::from(file_path), normalization_gain_db)
+) -> Result<(), AppError> {
+    let gain_db = peak_limited_gain(&state, &file_path, normalization_gain_db);
+    let player = state.player.lock().context("Lock error")?;
+    state.track_change.track_changed();
+    player.play_with_gain(PathBuf::from(file_path), gain_db)
+}
+
+/// Move to the next queue track by crossfading into it, over the crossfade length from the
+/// settings. Plays it right away when crossfading is off or gapless playback is on.
+/// The frontend calls this the crossfade length before the playing track ends. While "stop
+/// after current" is set it does nothing and returns false, so the track plays out and
+/// playback stops.
+#[tauri::command]
+pub fn player_crossfade_to(
+    file_path: String,
+    normalization_gain_db: Option<f32>,
+    state: State<'_, AppState>,
+) -> Result<bool, AppError> {
+    if state.stop_after_current.load(std::sync::atomic::Ordering::SeqCst) {
+        return Ok(false);
+    }
+    let gain_db = peak_limited_gain(&state, &file_path, normalization_gain_db);
+    let player = state.player.lock().context("Lock error")?;
+    state.track_change.track_changed();
+    let duration_ms = player.crossfade_ms();
+    player.crossfade_to(PathBuf::from(file_path), gain_db, duration_ms)?;
+    Ok(true)
+}
+
+/// Limit a normalization gain so it can't push the track's analyzed peak past 0 dBFS.
+/// While the track's album plays in order its album gain (limited by the album peak) is
+/// used instead, so the album keeps its internal dynamics.
+/// Tracks without stored loudness data keep the gain as given.
+fn peak_limited_gain(state: &AppState, file_path: &str, gain_db: Option<f32>) -> Option<f32> {
+    let gain_db = gain_db?;
+    let db = &state.db;
+    let Some(track) = DbOperations::get_track_by_file_path(db, file_path).ok().flatten() else {
+        return Some(gain_db);
+    };
+    let Some(loudness) = DbOperations::get_loudness(db, track.id).ok().flatten() else {
+        return Some(gain_db);
+    };
+    
+    let album_gain_enabled = state.settings().map(|s| s.playback.replay_gain.album_gain).unwrap_or(false);
+    if let (Some(album_gain_db), Some(album_peak_db)) = (loudness.album_gain_db, loudness.album_peak_db) {
+        if album_gain_enabled && DbOperations::is_album_playing_in_order(db, track.id).unwrap_or(false) {
+            return Some(album_gain_db.min((-album_peak_db) as f32));
+        }
+    }
+    
+    Some(gain_db.min((-loudness.true_peak_db) as f32))
 }
 
 #[tauri::command]
 pub fn player_set_track_gain(
     gain_db: f32,
     state: State<'_, AppState>,
-) -> Result<(), String> {
-    let player = state.player.lock().map_err(|e| format!("Lock error: {}", e))?;
+) -> Result<(), AppError> {
+    let player = state.player.lock().context("Lock error")?;
     player.set_track_gain(gain_db);
     Ok(())
 }
@@ -883,39 +1884,134 @@ pub fn player_set_track_gain(
 pub fn player_set_normalization_enabled(
     enabled: bool,
     state: State<'_, AppState>,
-) -> Result<(), String> {
-    let player = state.player.lock().map_err(|e| format!("Lock error: {}", e))?;
+) -> Result<(), AppError> {
+    let player = state.player.lock().context("Lock error")?;
     player.set_normalization_enabled(enabled);
     Ok(())
 }
 
 #[tauri::command]
-pub fn player_get_normalization_enabled(state: State<'_, AppState>) -> Result<bool, String> {
-    let player = state.player.lock().map_err(|e| format!("Lock error: {}", e))?;
+pub fn player_get_normalization_enabled(state: State<'_, AppState>) -> Result<bool, AppError> {
+    let player = state.player.lock().context("Lock error")?;
     Ok(player.is_normalization_enabled())
 }
 
+/// Set the headphone crossfeed level ("off", "low", "medium" or "high") and remember it
+#[tauri::command]
+pub fn set_crossfeed(level: String, state: State<'_, AppState>) -> Result<(), AppError> {
+    let crossfeed = CrossfeedLevel::from_name(&level)
+        .ok_or_else(|| AppError::InvalidInput(format!("Unknown crossfeed level: {}", level)))?;
+    
+    {
+        let player = state.player.lock().context("Lock error")?;
+        player.set_crossfeed(crossfeed);
+    }
+    
+    state.update_settings(|settings| {
+        settings.playback.crossfeed_enabled = crossfeed != CrossfeedLevel::Off;
+        if crossfeed != CrossfeedLevel::Off {
+            // Keep the last strength around so re-enabling restores it
+            settings.playback.crossfeed_level = level;
+        }
+        Ok(())
+    })
+}
+
 /// Analyze loudness for all tracks that don't have normalization data yet
 /// This is CPU-intensive and runs as a background task after the main scan
 #[tauri::command]
 pub async fn analyze_library_loudness(
     state: State<'_, AppState>,
     app: AppHandle,
-) -> Result<(usize, usize), String> {
+) -> Result<(usize, usize), AppError> {
     let db = state.db.clone();
+    let replay_gain = state.settings()?.playback.replay_gain;
     
     let result = tokio::task::spawn_blocking(move || {
-        LibraryIndexer::analyze_loudness_with_progress(&db, |progress| {
+        LibraryIndexer::analyze_loudness_with_progress(&db, replay_gain.segments_per_minute, replay_gain.prefer_tags, |progress| {
             let _ = app.emit("loudness-analysis-progress", progress);
         })
-        .map_err(|e| format!("Loudness analysis failed: {}", e))
+        .context("Loudness analysis failed")
     })
     .await
-    .map_err(|e| format!("Task join error: {}", e))??;
+    .context("Task join error")??;
     
     Ok(result)
 }
 
+/// Fill in missing track durations by decoding the files, emitting progress events
+#[tauri::command]
+pub async fn backfill_durations(
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<(usize, usize), AppError> {
+    let db = state.db.clone();
+    
+    tokio::task::spawn_blocking(move || {
+        LibraryIndexer::backfill_durations(&db, |progress| {
+            let _ = app.emit("duration-backfill-progress", progress);
+        })
+        .context("Duration backfill failed")
+    })
+    .await
+    .context("Task join error")?
+}
+
+/// Start analyzing unanalyzed tracks in the background (ReplayGain "calculate unanalyzed" setting).
+/// Returns false if a backfill is already running.
+#[tauri::command]
+pub fn start_loudness_backfill(
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<bool, AppError> {
+    let settings = state.settings()?;
+    if !settings.playback.replay_gain.calculate_unanalyzed {
+        return Err(AppError::InvalidInput("Calculating unanalyzed tracks is disabled in settings".to_string()));
+    }
+    
+    if state.loudness_backfill_running.swap(true, std::sync::atomic::Ordering::SeqCst) {
+        return Ok(false);
+    }
+    state.loudness_backfill_cancel.store(false, std::sync::atomic::Ordering::SeqCst);
+    
+    let db = state.db.clone();
+    let segments_per_minute = settings.playback.replay_gain.segments_per_minute;
+    let prefer_tags = settings.playback.replay_gain.prefer_tags;
+    let running = state.loudness_backfill_running.clone();
+    let cancel = state.loudness_backfill_cancel.clone();
+    
+    std::thread::spawn(move || {
+        let result = LibraryIndexer::backfill_loudness(&db, segments_per_minute, prefer_tags, &cancel, |progress| {
+            let _ = app.emit("loudness-backfill-progress", progress);
+        });
+        
+        match result {
+            Ok((analyzed, failed)) => {
+                eprintln!("[Loudness] Backfill finished: {} analyzed, {} failed", analyzed, failed);
+            }
+            Err(e) => eprintln!("[Loudness] Backfill error: {}", e),
+        }
+        running.store(false, std::sync::atomic::Ordering::SeqCst);
+    });
+    
+    Ok(true)
+}
+
+/// Ask a running library scan to stop after the current file. Cleanup of removed tracks is
+/// skipped and the scan returns what it indexed so far.
+#[tauri::command]
+pub fn cancel_scan(state: State<'_, AppState>) -> Result<(), AppError> {
+    state.scan_cancel.store(true, std::sync::atomic::Ordering::SeqCst);
+    Ok(())
+}
+
+/// Ask the background loudness backfill to stop after the current track
+#[tauri::command]
+pub fn cancel_loudness_backfill(state: State<'_, AppState>) -> Result<(), AppError> {
+    state.loudness_backfill_cancel.store(true, std::sync::atomic::Ordering::SeqCst);
+    Ok(())
+}
+
 /// Recalculate ReplayGain for a specific track using FULL analysis (not sampled)
 /// This is slower but more accurate than the sampled version used during scanning.
 /// Use this when a user wants to recalculate the gain for a specific track.
@@ -923,32 +2019,102 @@ pub async fn analyze_library_loudness(
 pub async fn recalculate_track_replaygain(
     track_id: i64,
     state: State<'_, AppState>,
-) -> Result<f32, String> {
+) -> Result<f32, AppError> {
     let db = state.db.clone();
     
     // Get the track's file path
     let track = DbOperations::get_track_by_id(&db, track_id)
-        .map_err(|e| format!("Failed to get track: {}", e))?
-        .ok_or_else(|| "Track not found".to_string())?;
+        .context("Failed to get track")?
+        .ok_or_else(|| AppError::NotFound("Track not found".to_string()))?;
     
     let file_path = track.file_path.clone();
     
     // Run full analysis in blocking task
     let result = tokio::task::spawn_blocking(move || {
         let path = std::path::Path::new(&file_path);
-        analyze_loudness(path)
-            .map_err(|e| format!("Loudness analysis failed: {}", e))
+        analyze_loudness_with_timeout(path, FULL_ANALYSIS_TIMEOUT_SECS)
+            .ok_or_else(|| AppError::Decode("Loudness analysis failed or timed out".to_string()))
     })
     .await
-    .map_err(|e| format!("Task join error: {}", e))??;
+    .context("Task join error")??;
     
     // Update the track with the new normalization gain
-    DbOperations::update_track_normalization_gain(&db, track_id, result.normalization_gain_db)
-        .map_err(|e| format!("Failed to update normalization gain: {}", e))?;
+    DbOperations::upsert_loudness(&db, &result.to_track_loudness(track_id))
+        .context("Failed to update normalization gain")?;
     
     Ok(result.normalization_gain_db)
 }
 
+/// Analyze a single track's loudness (sampled, like library scans do, or from its
+/// ReplayGain tags when those are preferred) and store the result
+#[tauri::command]
+pub async fn analyze_track_loudness(
+    track_id: i64,
+    state: State<'_, AppState>,
+) -> Result<TrackLoudness, AppError> {
+    let db = state.db.clone();
+    let replay_gain = state.settings()?.playback.replay_gain;
+    
+    tokio::task::spawn_blocking(move || {
+        let track = DbOperations::get_track_by_id(&db, track_id)
+            .context("Failed to get track")?
+            .ok_or_else(|| AppError::NotFound("Track not found".to_string()))?;
+        
+        let (loudness, source) = measure_track_loudness(
+            std::path::Path::new(&track.file_path),
+            track_id,
+            replay_gain.segments_per_minute,
+            replay_gain.prefer_tags,
+        )
+        .context("Loudness analysis failed")?;
+        
+        DbOperations::upsert_loudness_from(&db, &loudness, source.as_str())
+            .context("Failed to save loudness")?;
+        
+        Ok(loudness)
+    })
+    .await
+    .context("Task join error")?
+}
+
+/// Analyze an album as a whole (full analysis of every track) and store the album gain
+/// with each track's loudness, emitting "album-loudness-progress" events
+#[tauri::command]
+pub async fn analyze_album_loudness(
+    album_id: i64,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<AlbumLoudness, AppError> {
+    let db = state.db.clone();
+    
+    tokio::task::spawn_blocking(move || {
+        let tracks = DbOperations::get_tracks_by_album_id(&db, album_id)
+            .context("Failed to get album tracks")?;
+        if tracks.is_empty() {
+            return Err(AppError::NotFound("Album not found or has no tracks".to_string()));
+        }
+        
+        let paths: Vec<PathBuf> = tracks.iter().map(|t| PathBuf::from(&t.file_path)).collect();
+        let album = analyze_album(&paths, |progress| {
+            let _ = app.emit("album-loudness-progress", progress);
+        })
+        .context("Album loudness analysis failed")?;
+        
+        for (track, result) in tracks.iter().zip(&album.tracks) {
+            let Some(result) = result else { continue };
+            let mut loudness = result.to_track_loudness(track.id);
+            loudness.album_gain_db = Some(album.gain_db);
+            loudness.album_peak_db = Some(album.peak_db);
+            DbOperations::upsert_loudness(&db, &loudness)
+                .context("Failed to save loudness")?;
+        }
+        
+        Ok(album)
+    })
+    .await
+    .context("Task join error")?
+}
+
 // ============================================================================
 // SMTC (System Media Transport Controls) Commands
 // ============================================================================
@@ -960,18 +2126,30 @@ pub fn smtc_update_metadata(
     album: Option<String>,
     artwork_path: Option<String>,
     state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     eprintln!("[SMTC] update_metadata called - title: {}, artwork_path: {:?}", title, artwork_path);
-    let smtc_guard = state.smtc.lock().map_err(|e| format!("Lock error: {}", e))?;
-    if let Some(ref smtc) = *smtc_guard {
-        let artwork = artwork_path.as_ref().map(std::path::Path::new);
-        smtc.update_metadata(
-            &title,
-            artist.as_deref(),
-            album.as_deref(),
-            artwork,
-        )?;
-    }
+    // Only redraw the media overlay once the user stops skipping
+    let smtc = state.smtc.clone();
+    state.track_change.run_when_settled(move || {
+        let smtc_guard = match smtc.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                eprintln!("[SMTC] Lock error: {}", e);
+                return;
+            }
+        };
+        if let Some(ref smtc) = *smtc_guard {
+            let artwork = artwork_path.as_ref().map(std::path::Path::new);
+            if let Err(e) = smtc.update_metadata(
+                &title,
+                artist.as_deref(),
+                album.as_deref(),
+                artwork,
+            ) {
+                eprintln!("[SMTC] Failed to update metadata: {}", e);
+            }
+        }
+    });
     Ok(())
 }
 
@@ -979,8 +2157,8 @@ pub fn smtc_update_metadata(
 pub fn smtc_set_playback_status(
     is_playing: bool,
     state: State<'_, AppState>,
-) -> Result<(), String> {
-    let smtc_guard = state.smtc.lock().map_err(|e| format!("Lock error: {}", e))?;
+) -> Result<(), AppError> {
+    let smtc_guard = state.smtc.lock().context("Lock error")?;
     if let Some(ref smtc) = *smtc_guard {
         smtc.set_playback_status(is_playing)?;
     }
@@ -992,8 +2170,8 @@ pub fn smtc_set_timeline(
     position_ms: i64,
     duration_ms: i64,
     state: State<'_, AppState>,
-) -> Result<(), String> {
-    let smtc_guard = state.smtc.lock().map_err(|e| format!("Lock error: {}", e))?;
+) -> Result<(), AppError> {
+    let smtc_guard = state.smtc.lock().context("Lock error")?;
     if let Some(ref smtc) = *smtc_guard {
         smtc.set_timeline(position_ms, duration_ms)?;
     }
@@ -1001,12 +2179,24 @@ pub fn smtc_set_timeline(
 }
 
 #[tauri::command]
-pub async fn get_artwork_temp_path(app: AppHandle, file_path: String) -> Result<Option<String>, String> {
+pub async fn get_artwork_temp_path(
+    app: AppHandle,
+    file_path: String,
+    state: State<'_, AppState>,
+) -> Result<Option<String>, AppError> {
     use lofty::probe::Probe;
     use lofty::picture::PictureType;
     
+    let track_change = state.track_change.clone();
+    let generation = track_change.generation();
+    
     // Get album art data
     let art_data = tokio::task::spawn_blocking(move || {
+        // Skip extraction for tracks the user has already skipped past
+        if !track_change.wait_until_settled(generation) {
+            return None;
+        }
+        
         let lofty_result = Probe::open(&file_path)
             .and_then(|p| p.read());
         
@@ -1037,21 +2227,21 @@ pub async fn get_artwork_temp_path(app: AppHandle, file_path: String) -> Result<
             }
         }
         None
-    }).await.map_err(|e| format!("Task join error: {}", e))?;
+    }).await.context("Task join error")?;
     
     if let Some(data) = art_data {
         eprintln!("[SMTC] Found artwork data: {} bytes", data.len());
         // Save to temp directory
         let cache_dir = app.path().app_cache_dir()
-            .map_err(|e| format!("Failed to get cache dir: {}", e))?;
+            .context("Failed to get cache dir")?;
         
         std::fs::create_dir_all(&cache_dir)
-            .map_err(|e| format!("Failed to create cache dir: {}", e))?;
+            .context("Failed to create cache dir")?;
         
         let temp_path = cache_dir.join("smtc_artwork.jpg");
         
         std::fs::write(&temp_path, &data)
-            .map_err(|e| format!("Failed to write artwork: {}", e))?;
+            .context("Failed to write artwork")?;
         
         eprintln!("[SMTC] Saved artwork to: {:?}", temp_path);
         Ok(Some(temp_path.to_string_lossy().to_string()))
@@ -1065,14 +2255,186 @@ pub async fn get_artwork_temp_path(app: AppHandle, file_path: String) -> Result<
 // Settings Commands
 // ============================================================================
 
-use crate::settings::AppSettings;
+use crate::settings::{AppSettings, EqPreset, FadeSettings, InterfaceSettings, PlaybackSettings, TabConfig, EQ_BAND_COUNT, MAX_CROSSFADE_MS};
+use crate::audio::equalizer::EqualizerParams;
+
+#[tauri::command]
+pub fn get_settings(state: State<'_, AppState>) -> Result<AppSettings, AppError> {
+    state.settings()
+}
+
+#[tauri::command]
+pub fn save_settings(settings: AppSettings, state: State<'_, AppState>) -> Result<(), AppError> {
+    {
+        let player = state.player.lock().context("Lock error")?;
+        player.set_gapless(settings.playback.gapless);
+        player.set_fade(&settings.playback.fade);
+        player.set_crossfade_ms(settings.playback.crossfade_ms as i64);
+        player.set_equalizer(EqualizerParams::from_settings(&settings.playback));
+        player.set_normalization_enabled(settings.playback.replay_gain.enabled);
+        if player.output_device() != settings.playback.output_device {
+            player.set_output_device(settings.playback.output_device.clone());
+        }
+    }
+    state.update_settings(|current| {
+        *current = settings;
+        Ok(())
+    })
+}
+
+/// Change fade-in/fade-out settings, applied to the playing track immediately
+#[tauri::command]
+pub fn set_fade_settings(fade: FadeSettings, state: State<'_, AppState>) -> Result<(), AppError> {
+    if !(0..=2000).contains(&fade.fade_in_ms) || !(0..=2000).contains(&fade.fade_out_ms) {
+        return Err(AppError::InvalidInput("Fade lengths must be between 0 and 2000 ms".to_string()));
+    }
+    
+    {
+        let player = state.player.lock().context("Lock error")?;
+        player.set_fade(&fade);
+    }
+    
+    state.update_settings(|settings| {
+        settings.playback.fade = fade;
+        Ok(())
+    })
+}
+
+/// Change the crossfade length between queue tracks (0 = off), used from the next track change
+#[tauri::command]
+pub fn set_crossfade_ms(crossfade_ms: i32, state: State<'_, AppState>) -> Result<(), AppError> {
+    if !(0..=MAX_CROSSFADE_MS).contains(&crossfade_ms) {
+        return Err(AppError::InvalidInput(format!("Crossfade length must be between 0 and {} ms", MAX_CROSSFADE_MS)));
+    }
+    
+    {
+        let player = state.player.lock().context("Lock error")?;
+        player.set_crossfade_ms(crossfade_ms as i64);
+    }
+    
+    state.update_settings(|settings| {
+        settings.playback.crossfade_ms = crossfade_ms;
+        Ok(())
+    })
+}
+
+#[tauri::command]
+pub fn get_tab_config(state: State<'_, AppState>) -> Result<Vec<TabConfig>, AppError> {
+    Ok(state.settings()?.interface.tabs)
+}
+
+/// Validate, persist and return a new tab order/visibility configuration
+#[tauri::command]
+pub fn set_tab_config(tabs: Vec<TabConfig>, state: State<'_, AppState>) -> Result<Vec<TabConfig>, AppError> {
+    state.update_settings(|settings| {
+        settings.interface.set_tabs(tabs).map_err(AppError::InvalidInput)?;
+        Ok(settings.interface.tabs.clone())
+    })
+}
+
+/// Custom equalizer presets saved by the user
+#[tauri::command]
+pub fn list_equalizer_presets(state: State<'_, AppState>) -> Result<Vec<EqPreset>, AppError> {
+    Ok(state.settings()?.playback.equalizer_presets)
+}
+
+/// Save (or overwrite) a custom equalizer preset. Gains are clamped to ±12 dB.
+#[tauri::command]
+pub fn save_equalizer_preset(name: String, gains: Vec<f32>, state: State<'_, AppState>) -> Result<Vec<EqPreset>, AppError> {
+    state.update_settings(|settings| {
+        settings.playback.save_equalizer_preset(&name, &gains).map_err(AppError::InvalidInput)?;
+        Ok(settings.playback.equalizer_presets.clone())
+    })
+}
+
+#[tauri::command]
+pub fn delete_equalizer_preset(name: String, state: State<'_, AppState>) -> Result<bool, AppError> {
+    let mut settings = state.settings()?;
+    if !settings.playback.delete_equalizer_preset(&name) {
+        return Ok(false);
+    }
+    state.update_settings(|current| {
+        *current = settings;
+        Ok(true)
+    })
+}
+
+/// Change the equalizer settings and hand the result to the player
+fn update_equalizer<T>(
+    state: &AppState,
+    change: impl FnOnce(&mut PlaybackSettings) -> Result<T, AppError>,
+) -> Result<T, AppError> {
+    let (result, params) = state.update_settings(|settings| {
+        let result = change(&mut settings.playback)?;
+        Ok((result, EqualizerParams::from_settings(&settings.playback)))
+    })?;
+    let player = state.player.lock().context("Lock error")?;
+    player.set_equalizer(params);
+    Ok(result)
+}
+
+/// Select a preset: "flat", "bass_boost", "treble", "vocal", "rock", a saved preset or "custom",
+/// and load its band gains. Returns the gains now in use.
+#[tauri::command]
+pub fn apply_equalizer_preset(name: String, state: State<'_, AppState>) -> Result<Vec<f32>, AppError> {
+    update_equalizer(&state, |playback| playback.apply_equalizer_preset(&name).map_err(AppError::InvalidInput))
+}
+
+#[tauri::command]
+pub fn set_equalizer_enabled(enabled: bool, state: State<'_, AppState>) -> Result<(), AppError> {
+    update_equalizer(&state, |playback| {
+        playback.equalizer_enabled = enabled;
+        Ok(())
+    })
+}
+
+/// Set all band gains (lowest frequency first), which switches to the "custom" preset.
+/// Returns the gains after clamping to ±12 dB.
+#[tauri::command]
+pub fn set_equalizer_bands(gains: [f32; EQ_BAND_COUNT], state: State<'_, AppState>) -> Result<Vec<f32>, AppError> {
+    update_equalizer(&state, |playback| playback.set_equalizer_bands(&gains).map_err(AppError::InvalidInput))
+}
+
+/// Restore the default tabs and return them
+#[tauri::command]
+pub fn reset_tabs(state: State<'_, AppState>) -> Result<Vec<TabConfig>, AppError> {
+    state.update_settings(|settings| {
+        settings.interface.tabs = InterfaceSettings::default_tabs();
+        Ok(settings.interface.tabs.clone())
+    })
+}
 
+/// Start Last.fm authorization with the API key and secret from the scrobble settings.
+/// The user opens the returned URL, grants access, then the token goes to set_lastfm_session.
 #[tauri::command]
-pub fn get_settings(state: State<'_, AppState>) -> Result<AppSettings, String> {
-    AppSettings::load(&state.app_dir)
+pub async fn lastfm_get_auth_url(state: State<'_, AppState>) -> Result<LastfmAuthRequest, AppError> {
+    let scrobble = state.settings()?.scrobble;
+    
+    tokio::task::spawn_blocking(move || {
+        LastfmClient::new(&scrobble.api_key, &scrobble.api_secret, None)
+            .and_then(|client| client.auth_request())
+            .context("Failed to start Last.fm authorization")
+    })
+    .await
+    .context("Task join error")?
 }
 
+/// Exchange an authorized token for a Last.fm session and remember it, returns the user name
 #[tauri::command]
-pub fn save_settings(settings: AppSettings, state: State<'_, AppState>) -> Result<(), String> {
-    settings.save(&state.app_dir)
+pub async fn set_lastfm_session(token: String, state: State<'_, AppState>) -> Result<String, AppError> {
+    let scrobble = state.settings()?.scrobble;
+    
+    let (session_key, username) = tokio::task::spawn_blocking(move || {
+        LastfmClient::new(&scrobble.api_key, &scrobble.api_secret, None)
+            .and_then(|client| client.get_session(&token))
+            .context("Failed to get Last.fm session")
+    })
+    .await
+    .context("Task join error")??;
+    
+    state.update_settings(|settings| {
+        settings.scrobble.session_key = Some(session_key);
+        settings.scrobble.username = Some(username.clone());
+        Ok(username)
+    })
 }
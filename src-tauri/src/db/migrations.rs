@@ -1,6 +1,9 @@
 // Database migrations
 use rusqlite::Connection;
 use anyhow::Result;
+use std::collections::HashMap;
+
+use super::queries::{normalize_name, sync_albums_sql, MARK_COMPILATIONS_SQL};
 
 pub fn run_migrations(conn: &Connection) -> Result<()> {
     // Enable foreign keys
@@ -149,6 +152,47 @@ pub fn run_migrations(conn: &Connection) -> Result<()> {
         }
     }
 
+    // Migration: Add repeat_mode column to queues table (0 = off, 1 = repeat all, 2 = repeat one)
+    let repeat_mode_exists: Result<i64, _> = conn.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info('queues') WHERE name='repeat_mode'",
+        [],
+        |row| row.get(0)
+    );
+    
+    if let Ok(count) = repeat_mode_exists {
+        if count == 0 {
+            conn.execute("ALTER TABLE queues ADD COLUMN repeat_mode INTEGER DEFAULT 0", [])?;
+        }
+    }
+
+    // Migration: Add current_position_ms column to queues table
+    // Playback position within the current track, for resuming the last session
+    let current_position_exists: Result<i64, _> = conn.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info('queues') WHERE name='current_position_ms'",
+        [],
+        |row| row.get(0)
+    );
+    
+    if let Ok(count) = current_position_exists {
+        if count == 0 {
+            conn.execute("ALTER TABLE queues ADD COLUMN current_position_ms INTEGER NOT NULL DEFAULT 0", [])?;
+        }
+    }
+
+    // Migration: Add is_ad_hoc column to queues table
+    // Marks the reusable queue play_track_now puts single tracks in, whatever its name
+    let is_ad_hoc_exists: Result<i64, _> = conn.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info('queues') WHERE name='is_ad_hoc'",
+        [],
+        |row| row.get(0)
+    );
+    
+    if let Ok(count) = is_ad_hoc_exists {
+        if count == 0 {
+            conn.execute("ALTER TABLE queues ADD COLUMN is_ad_hoc BOOLEAN NOT NULL DEFAULT 0", [])?;
+        }
+    }
+
     // Create queue_tracks junction table
     conn.execute(
         "CREATE TABLE IF NOT EXISTS queue_tracks (
@@ -188,6 +232,20 @@ pub fn run_migrations(conn: &Connection) -> Result<()> {
         [],
     )?;
 
+    // Create track_album_artists junction table, linking a track to each artist in its
+    // (possibly collaborative) album artist field
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS track_album_artists (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            track_id INTEGER NOT NULL,
+            artist_id INTEGER NOT NULL,
+            FOREIGN KEY (track_id) REFERENCES tracks(id) ON DELETE CASCADE,
+            FOREIGN KEY (artist_id) REFERENCES artists(id) ON DELETE CASCADE,
+            UNIQUE(track_id, artist_id)
+        )",
+        [],
+    )?;
+
     // Create track_genres junction table for many-to-many relationship
     conn.execute(
         "CREATE TABLE IF NOT EXISTS track_genres (
@@ -238,6 +296,21 @@ pub fn run_migrations(conn: &Connection) -> Result<()> {
         }
     }
 
+    // Migration: Add normalization_source column to tracks table
+    // Records where normalization_gain_db came from: "analysis", "replaygain" (REPLAYGAIN_* tags)
+    // or "soundcheck" (iTunNORM tag). "failed" marks a failed analysis, with a NULL gain
+    let normalization_source_exists: Result<i64, _> = conn.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info('tracks') WHERE name='normalization_source'",
+        [],
+        |row| row.get(0)
+    );
+    
+    if let Ok(count) = normalization_source_exists {
+        if count == 0 {
+            conn.execute("ALTER TABLE tracks ADD COLUMN normalization_source TEXT", [])?;
+        }
+    }
+
     // Migration: Add last_scanned column to scan_paths table
     let last_scanned_exists: Result<i64, _> = conn.query_row(
         "SELECT COUNT(*) FROM pragma_table_info('scan_paths') WHERE name='last_scanned'",
@@ -265,7 +338,218 @@ pub fn run_migrations(conn: &Connection) -> Result<()> {
         }
     }
 
-    // Create indexes for better query performance
+    // Migration: Add has_audio column to tracks table
+    // Tag-only or corrupt files are indexed with has_audio = 0 so they can be told apart
+    let has_audio_exists: Result<i64, _> = conn.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info('tracks') WHERE name='has_audio'",
+        [],
+        |row| row.get(0)
+    );
+    
+    if let Ok(count) = has_audio_exists {
+        if count == 0 {
+            conn.execute("ALTER TABLE tracks ADD COLUMN has_audio INTEGER NOT NULL DEFAULT 1", [])?;
+        }
+    }
+
+    // Migration: Add track_total and disc_total columns to tracks table (the "of 12" in "3 of 12")
+    for column in ["track_total", "disc_total"] {
+        let column_exists: Result<i64, _> = conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('tracks') WHERE name = ?1",
+            [column],
+            |row| row.get(0)
+        );
+        
+        if let Ok(0) = column_exists {
+            conn.execute(&format!("ALTER TABLE tracks ADD COLUMN {} INTEGER", column), [])?;
+        }
+    }
+
+    // Migration: Add mtime column to tracks table (file modification time in unix milliseconds,
+    // recorded with the hash so unchanged files can be skipped without reading them)
+    let mtime_exists: Result<i64, _> = conn.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info('tracks') WHERE name='mtime'",
+        [],
+        |row| row.get(0)
+    );
+    
+    if let Ok(count) = mtime_exists {
+        if count == 0 {
+            conn.execute("ALTER TABLE tracks ADD COLUMN mtime INTEGER", [])?;
+        }
+    }
+
+    // Migration: Add rating (0-5 stars), is_favorite and skip_count columns to tracks table.
+    // All are user data, so rescans never overwrite them.
+    for column in ["rating", "is_favorite", "skip_count"] {
+        let column_exists: Result<i64, _> = conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('tracks') WHERE name = ?1",
+            [column],
+            |row| row.get(0)
+        );
+        
+        if let Ok(0) = column_exists {
+            conn.execute(&format!("ALTER TABLE tracks ADD COLUMN {} INTEGER NOT NULL DEFAULT 0", column), [])?;
+        }
+    }
+
+    // Migration: Add compilation column to tracks table (TCMP/cpil/COMPILATION tag) and
+    // is_compilation to albums. Albums credited to various artists are flagged once album_ids
+    // exist below; tagged compilations are picked up when their files are indexed again.
+    let compilation_exists: Result<i64, _> = conn.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info('tracks') WHERE name='compilation'",
+        [],
+        |row| row.get(0)
+    );
+    
+    if let Ok(0) = compilation_exists {
+        conn.execute("ALTER TABLE tracks ADD COLUMN compilation INTEGER NOT NULL DEFAULT 0", [])?;
+    }
+    
+    let is_compilation_exists: Result<i64, _> = conn.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info('albums') WHERE name='is_compilation'",
+        [],
+        |row| row.get(0)
+    );
+    let mark_compilations = matches!(is_compilation_exists, Ok(0));
+    
+    if mark_compilations {
+        conn.execute("ALTER TABLE albums ADD COLUMN is_compilation INTEGER NOT NULL DEFAULT 0", [])?;
+    }
+
+    // Migration: Add album_id column to tracks table, pointing at the albums row for the
+    // track's album name + album artist. Album rows written before were keyed by the first
+    // track artist instead, so rebuild them from the tracks.
+    let album_id_exists: Result<i64, _> = conn.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info('tracks') WHERE name='album_id'",
+        [],
+        |row| row.get(0)
+    );
+    
+    if let Ok(count) = album_id_exists {
+        if count == 0 {
+            conn.execute("ALTER TABLE tracks ADD COLUMN album_id INTEGER REFERENCES albums(id)", [])?;
+            conn.execute_batch(&sync_albums_sql())?;
+        }
+    }
+    
+    if mark_compilations {
+        conn.execute(MARK_COMPILATIONS_SQL, [])?;
+    }
+
+    // Migration: Smart playlists, whose tracks are computed from rules (SmartPlaylistRules JSON)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS smart_playlists (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT UNIQUE NOT NULL,
+            rules TEXT NOT NULL,
+            date_created INTEGER NOT NULL,
+            date_modified INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    // Migration: Plays waiting to be sent to Last.fm (offline, or the request failed)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS pending_scrobbles (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            artist TEXT NOT NULL,
+            title TEXT NOT NULL,
+            album TEXT,
+            album_artist TEXT,
+            duration_ms INTEGER,
+            timestamp INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    // Migration: Other paths holding an identical copy of a track's file, recorded instead of
+    // separate tracks when deduplicating by hash. mtime and file_size are the copy's stamp
+    // when it was hashed, so rescans can skip it like an unchanged track.
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS track_locations (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            track_id INTEGER NOT NULL,
+            file_path TEXT UNIQUE NOT NULL,
+            mtime INTEGER,
+            file_size INTEGER,
+            FOREIGN KEY (track_id) REFERENCES tracks(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    // Migration: Loudness analysis results per track (the gain is also kept in
+    // tracks.normalization_gain_db, which is what playback reads)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS track_loudness (
+            track_id INTEGER PRIMARY KEY,
+            integrated_lufs REAL NOT NULL,
+            true_peak_db REAL NOT NULL,
+            gain_db REAL NOT NULL,
+            FOREIGN KEY (track_id) REFERENCES tracks(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    // Migration: Add album_gain_db and album_peak_db columns to track_loudness (album
+    // ReplayGain, used instead of the track gain when an album plays in order)
+    for column in ["album_gain_db", "album_peak_db"] {
+        let column_exists: Result<i64, _> = conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('track_loudness') WHERE name = ?1",
+            [column],
+            |row| row.get(0)
+        );
+        
+        if let Ok(0) = column_exists {
+            conn.execute(&format!("ALTER TABLE track_loudness ADD COLUMN {} REAL", column), [])?;
+        }
+    }
+
+    // Migration: Add composer, comment and bpm columns to tracks table (NULL when untagged)
+    for (column, column_type) in [("composer", "TEXT"), ("comment", "TEXT"), ("bpm", "INTEGER")] {
+        let column_exists: Result<i64, _> = conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('tracks') WHERE name = ?1",
+            [column],
+            |row| row.get(0)
+        );
+        
+        if let Ok(0) = column_exists {
+            conn.execute(&format!("ALTER TABLE tracks ADD COLUMN {} {}", column, column_type), [])?;
+        }
+    }
+
+    // Migration: Add custom_artwork_path column to albums table (cover picked by the user,
+    // shown instead of folder art for tracks without embedded art)
+    let custom_artwork_exists: Result<i64, _> = conn.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info('albums') WHERE name='custom_artwork_path'",
+        [],
+        |row| row.get(0)
+    );
+    
+    if let Ok(0) = custom_artwork_exists {
+        conn.execute("ALTER TABLE albums ADD COLUMN custom_artwork_path TEXT", [])?;
+    }
+
+    // Migration: Add name_normalized column to artists and genres (see normalize_name), so
+    // "Beatles", "beatles" and "Beatles " are one artist. Rows that were separate until now
+    // are merged into the oldest one, which keeps its display name.
+    add_normalized_names(conn, "artists", &[("track_artists", "artist_id"), ("track_album_artists", "artist_id")])?;
+    add_normalized_names(conn, "genres", &[("track_genres", "genre_id")])?;
+
+    // Migration: Add file_count column to scan_paths table, so a scan can tell that nothing
+    // was removed from a path and skip the cleanup
+    let file_count_exists: Result<i64, _> = conn.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info('scan_paths') WHERE name='file_count'",
+        [],
+        |row| row.get(0)
+    );
+    
+    if let Ok(0) = file_count_exists {
+        conn.execute("ALTER TABLE scan_paths ADD COLUMN file_count INTEGER", [])?;
+    }
+
+    // Create indexes for better query performance. All are IF NOT EXISTS, so existing
+    // databases pick up newly added ones on the next launch.
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_tracks_artist ON tracks(artist)",
         [],
@@ -274,14 +558,34 @@ pub fn run_migrations(conn: &Connection) -> Result<()> {
         "CREATE INDEX IF NOT EXISTS idx_tracks_album ON tracks(album)",
         [],
     )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_tracks_album_id ON tracks(album_id)",
+        [],
+    )?;
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_tracks_genre ON tracks(genre)",
         [],
     )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_tracks_composer ON tracks(composer)",
+        [],
+    )?;
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_tracks_file_hash ON tracks(file_hash)",
         [],
     )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_tracks_play_count ON tracks(play_count)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_tracks_date_added ON tracks(date_added)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_track_locations_track ON track_locations(track_id)",
+        [],
+    )?;
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_playlist_tracks_playlist ON playlist_tracks(playlist_id)",
         [],
@@ -290,6 +594,10 @@ pub fn run_migrations(conn: &Connection) -> Result<()> {
         "CREATE INDEX IF NOT EXISTS idx_queue_tracks_queue ON queue_tracks(queue_id)",
         [],
     )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_queue_tracks_track ON queue_tracks(track_id)",
+        [],
+    )?;
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_track_artists_track ON track_artists(track_id)",
         [],
@@ -298,6 +606,14 @@ pub fn run_migrations(conn: &Connection) -> Result<()> {
         "CREATE INDEX IF NOT EXISTS idx_track_artists_artist ON track_artists(artist_id)",
         [],
     )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_track_album_artists_track ON track_album_artists(track_id)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_track_album_artists_artist ON track_album_artists(artist_id)",
+        [],
+    )?;
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_track_genres_track ON track_genres(track_id)",
         [],
@@ -309,3 +625,60 @@ pub fn run_migrations(conn: &Connection) -> Result<()> {
 
     Ok(())
 }
+
+/// Add and fill `table`.name_normalized if it's missing, merging rows whose names normalize
+/// the same and repointing their links, then add the unique index on it
+fn add_normalized_names(conn: &Connection, table: &str, links: &[(&str, &str)]) -> Result<()> {
+    let column_exists: Result<i64, _> = conn.query_row(
+        &format!("SELECT COUNT(*) FROM pragma_table_info('{}') WHERE name='name_normalized'", table),
+        [],
+        |row| row.get(0)
+    );
+    
+    if let Ok(0) = column_exists {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN name_normalized TEXT", table), [])?;
+        
+        let rows = conn
+            .prepare(&format!("SELECT id, name FROM {} ORDER BY id", table))?
+            .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        
+        let mut kept: HashMap<String, i64> = HashMap::new();
+        for (id, name) in rows {
+            let normalized = normalize_name(&name);
+            match kept.get(&normalized) {
+                Some(&keep_id) => {
+                    for (link_table, column) in links {
+                        conn.execute(
+                            &format!(
+                                "INSERT OR IGNORE INTO {0} (track_id, {1})
+                                 SELECT track_id, ?1 FROM {0} WHERE {1} = ?2",
+                                link_table, column
+                            ),
+                            [keep_id, id],
+                        )?;
+                        conn.execute(&format!("DELETE FROM {} WHERE {} = ?1", link_table, column), [id])?;
+                    }
+                    conn.execute(&format!("DELETE FROM {} WHERE id = ?1", table), [id])?;
+                }
+                None => {
+                    conn.execute(
+                        &format!("UPDATE {} SET name_normalized = ?1 WHERE id = ?2", table),
+                        rusqlite::params![normalized, id],
+                    )?;
+                    kept.insert(normalized, id);
+                }
+            }
+        }
+    }
+    
+    conn.execute(
+        &format!(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_{0}_name_normalized ON {0}(name_normalized)",
+            table
+        ),
+        [],
+    )?;
+    
+    Ok(())
+}
@@ -34,6 +34,38 @@ pub struct Track {
     /// Used for "Most Played" sorting instead of a simple play count.
     #[serde(default)]
     pub play_time_seconds: i64,
+    /// False for tag-only or corrupt files where no decodable audio track was found
+    #[serde(default = "default_has_audio")]
+    pub has_audio: bool,
+    /// Number of tracks on the disc, from "3/12" style track numbers or a separate total tag
+    #[serde(default)]
+    pub track_total: Option<i32>,
+    /// Number of discs in the release, from "2/3" style disc numbers or a separate total tag
+    #[serde(default)]
+    pub disc_total: Option<i32>,
+    /// Star rating, 0 (unrated) to 5
+    #[serde(default)]
+    pub rating: i32,
+    /// "Loved" flag
+    #[serde(default)]
+    pub is_favorite: bool,
+    #[serde(default)]
+    pub composer: Option<String>,
+    #[serde(default)]
+    pub comment: Option<String>,
+    /// Beats per minute, rounded to a whole number
+    #[serde(default)]
+    pub bpm: Option<i32>,
+    /// Tagged as part of a compilation (TCMP, cpil or COMPILATION)
+    #[serde(default)]
+    pub compilation: bool,
+    /// Times the track was skipped before it counted as played
+    #[serde(default)]
+    pub skip_count: i32,
+}
+
+fn default_has_audio() -> bool {
+    true
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,6 +74,376 @@ pub struct ScanPath {
     pub path: String,
     pub date_added: i64,
     pub last_scanned: Option<i64>,
+    /// Audio files found by the last scan that finished its cleanup
+    pub file_count: Option<i64>,
+}
+
+/// Play statistics summed over an album's tracks
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlbumStats {
+    pub play_count_sum: i64,
+    /// Most recent play of any track on the album, None if never played
+    pub last_played_max: Option<i64>,
+    pub track_count: i64,
+    pub total_duration_ms: i64,
+}
+
+/// Stored loudness analysis of a track
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackLoudness {
+    pub track_id: i64,
+    /// EBU R128 integrated loudness
+    pub integrated_lufs: f64,
+    /// Peak level in dBFS
+    pub true_peak_db: f64,
+    /// Gain that brings the track to the target loudness
+    pub gain_db: f32,
+    /// Gain that brings the track's album to the target loudness, None until the album
+    /// was analyzed as a whole
+    #[serde(default)]
+    pub album_gain_db: Option<f32>,
+    /// Highest peak of the album in dBFS, set together with album_gain_db
+    #[serde(default)]
+    pub album_peak_db: Option<f64>,
+}
+
+/// Everything needed to pick up playback where the last session left off
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionState {
+    /// The active queue, None on first launch
+    pub queue: Option<Queue>,
+    pub current_track_index: i32,
+    /// Position within the current track
+    pub current_position_ms: i64,
+    pub shuffle_seed: i64,
+    pub shuffle_anchor: i64,
+    pub repeat_mode: RepeatMode,
+    /// Last player volume in dB, from the playback settings
+    pub volume_db: f32,
+}
+
+/// Sort order for paging through the library. Every order ends with the track ID so pages
+/// never overlap or skip rows when sort keys tie.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrackSort {
+    #[default]
+    DateAddedDesc,
+    DateAddedAsc,
+    TitleAsc,
+    TitleDesc,
+    /// Artist, then album in disc/track order
+    ArtistAsc,
+    /// Album in disc/track order
+    AlbumAsc,
+    PlayCountDesc,
+    LastPlayedDesc,
+}
+
+impl TrackSort {
+    /// Parse a name from the frontend ("date_added_desc", "title_asc", ...)
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "date_added_desc" => Some(TrackSort::DateAddedDesc),
+            "date_added_asc" => Some(TrackSort::DateAddedAsc),
+            "title_asc" => Some(TrackSort::TitleAsc),
+            "title_desc" => Some(TrackSort::TitleDesc),
+            "artist_asc" => Some(TrackSort::ArtistAsc),
+            "album_asc" => Some(TrackSort::AlbumAsc),
+            "play_count_desc" => Some(TrackSort::PlayCountDesc),
+            "last_played_desc" => Some(TrackSort::LastPlayedDesc),
+            _ => None,
+        }
+    }
+
+    /// ORDER BY clause for the tracks table. Only fixed strings, never user input.
+    pub fn order_by(self) -> &'static str {
+        match self {
+            TrackSort::DateAddedDesc => "date_added DESC, id DESC",
+            TrackSort::DateAddedAsc => "date_added ASC, id ASC",
+            TrackSort::TitleAsc => "title COLLATE NOCASE ASC, id ASC",
+            TrackSort::TitleDesc => "title COLLATE NOCASE DESC, id DESC",
+            TrackSort::ArtistAsc => {
+                "artist IS NULL, artist COLLATE NOCASE ASC, album COLLATE NOCASE ASC,
+                 disc_number ASC, track_number ASC, id ASC"
+            }
+            TrackSort::AlbumAsc => {
+                "album IS NULL, album COLLATE NOCASE ASC, disc_number ASC, track_number ASC, id ASC"
+            }
+            TrackSort::PlayCountDesc => "play_count DESC, id ASC",
+            TrackSort::LastPlayedDesc => "last_played IS NULL, last_played DESC, id DESC",
+        }
+    }
+}
+
+/// Sort order for the album list. Ties fall back to name, artist and ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AlbumSort {
+    #[default]
+    NameAsc,
+    /// Newest first, albums without a year last
+    YearDesc,
+    SongCountDesc,
+    ArtistAsc,
+}
+
+impl AlbumSort {
+    /// Parse a name from the frontend ("name_asc", "year_desc", ...)
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "name_asc" => Some(AlbumSort::NameAsc),
+            "year_desc" => Some(AlbumSort::YearDesc),
+            "song_count_desc" => Some(AlbumSort::SongCountDesc),
+            "artist_asc" => Some(AlbumSort::ArtistAsc),
+            _ => None,
+        }
+    }
+
+    /// ORDER BY clause for the album query (`a` is albums, `t` its tracks, `song_count` the
+    /// track count). Only fixed strings, never user input.
+    pub fn order_by(self) -> &'static str {
+        match self {
+            AlbumSort::NameAsc => "a.name, a.artist, a.id",
+            AlbumSort::YearDesc => "MIN(t.year) IS NULL, MIN(t.year) DESC, a.name, a.artist, a.id",
+            AlbumSort::SongCountDesc => "song_count DESC, a.name, a.artist, a.id",
+            AlbumSort::ArtistAsc => "a.artist IS NULL, a.artist COLLATE NOCASE, a.name, a.id",
+        }
+    }
+}
+
+/// Sort order for the artist list. Ties fall back to name and ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArtistSort {
+    #[default]
+    NameAsc,
+    SongCountDesc,
+}
+
+impl ArtistSort {
+    /// Parse a name from the frontend ("name_asc", "song_count_desc")
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "name_asc" => Some(ArtistSort::NameAsc),
+            "song_count_desc" => Some(ArtistSort::SongCountDesc),
+            _ => None,
+        }
+    }
+
+    /// ORDER BY clause for the artist query (`ar` is artists, `song_count` the aggregate).
+    /// Only fixed strings, never user input.
+    pub fn order_by(self) -> &'static str {
+        match self {
+            ArtistSort::NameAsc => "ar.name, ar.id",
+            ArtistSort::SongCountDesc => "song_count DESC, ar.name, ar.id",
+        }
+    }
+}
+
+/// A playlist whose tracks are whatever currently matches its rules
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SmartPlaylist {
+    pub id: i64,
+    pub name: String,
+    /// SmartPlaylistRules as JSON
+    pub rules: String,
+}
+
+/// Rules of a smart playlist, e.g.
+/// `{"match": "all", "rules": [{"field": "play_count", "operator": ">", "value": 5}], "limit": 50, "sort": "play_count_desc"}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SmartPlaylistRules {
+    #[serde(flatten)]
+    pub group: SmartRuleGroup,
+    /// Maximum number of tracks, all matches if None
+    #[serde(default)]
+    pub limit: Option<u32>,
+    /// TrackSort name, date_added_desc if None
+    #[serde(default)]
+    pub sort: Option<String>,
+}
+
+/// Conditions (or nested groups) that must all ("all") or any ("any") match
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SmartRuleGroup {
+    #[serde(rename = "match", default = "default_match")]
+    pub match_mode: String,
+    pub rules: Vec<SmartRule>,
+}
+
+fn default_match() -> String {
+    "all".to_string()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SmartRule {
+    Condition {
+        field: String,
+        operator: String,
+        #[serde(default)]
+        value: serde_json::Value,
+    },
+    Group(SmartRuleGroup),
+}
+
+/// Numeric track columns rules may compare. Only these names ever reach the SQL.
+const SMART_NUMBER_FIELDS: [&str; 5] = ["play_count", "year", "rating", "date_added", "last_played"];
+/// Columns holding unix timestamps, which also support "in_last_days"
+const SMART_DATE_FIELDS: [&str; 2] = ["date_added", "last_played"];
+/// Text track columns rules may match
+const SMART_TEXT_FIELDS: [&str; 1] = ["genre"];
+
+impl SmartPlaylistRules {
+    /// Parse rules from JSON and check they compile
+    pub fn from_json(json: &str) -> Result<Self, anyhow::Error> {
+        let rules: Self = serde_json::from_str(json)
+            .map_err(|e| anyhow::anyhow!("Invalid smart playlist rules: {}", e))?;
+        rules.where_clause(0)?;
+        rules.track_sort()?;
+        Ok(rules)
+    }
+
+    pub fn track_sort(&self) -> Result<TrackSort, anyhow::Error> {
+        match self.sort.as_deref() {
+            None => Ok(TrackSort::default()),
+            Some(name) => TrackSort::from_name(name)
+                .ok_or_else(|| anyhow::anyhow!("Unknown sort: {}", name)),
+        }
+    }
+
+    /// WHERE clause for the tracks table plus its parameters. Field names and operators come
+    /// from fixed lists; every value is bound as a parameter. `now` is the current unix time,
+    /// for "in the last N days" rules.
+    pub fn where_clause(&self, now: i64) -> Result<(String, Vec<rusqlite::types::Value>), anyhow::Error> {
+        let mut params = Vec::new();
+        let clause = Self::group_clause(&self.group, now, &mut params)?;
+        Ok((clause, params))
+    }
+
+    fn group_clause(
+        group: &SmartRuleGroup,
+        now: i64,
+        params: &mut Vec<rusqlite::types::Value>,
+    ) -> Result<String, anyhow::Error> {
+        let joiner = match group.match_mode.as_str() {
+            "all" => " AND ",
+            "any" => " OR ",
+            other => return Err(anyhow::anyhow!("Unknown match mode: {}", other)),
+        };
+        // An empty group matches everything
+        if group.rules.is_empty() {
+            return Ok("1".to_string());
+        }
+
+        let mut parts = Vec::with_capacity(group.rules.len());
+        for rule in &group.rules {
+            let part = match rule {
+                SmartRule::Group(inner) => Self::group_clause(inner, now, params)?,
+                SmartRule::Condition { field, operator, value } => {
+                    Self::condition_clause(field, operator, value, now, params)?
+                }
+            };
+            parts.push(format!("({})", part));
+        }
+        Ok(parts.join(joiner))
+    }
+
+    fn condition_clause(
+        field: &str,
+        operator: &str,
+        value: &serde_json::Value,
+        now: i64,
+        params: &mut Vec<rusqlite::types::Value>,
+    ) -> Result<String, anyhow::Error> {
+        use rusqlite::types::Value;
+
+        let invalid = || anyhow::anyhow!("Unsupported rule: {} {} {}", field, operator, value);
+
+        // Map the field to its whitelisted column name
+        let column = SMART_NUMBER_FIELDS.iter()
+            .chain(SMART_TEXT_FIELDS.iter())
+            .find(|c| **c == field)
+            .ok_or_else(|| anyhow::anyhow!("Unknown field: {}", field))?;
+
+        match operator {
+            "is_empty" => return Ok(format!("{} IS NULL", column)),
+            "is_not_empty" => return Ok(format!("{} IS NOT NULL", column)),
+            _ => {}
+        }
+
+        if SMART_NUMBER_FIELDS.contains(column) {
+            let number = value.as_i64().ok_or_else(invalid)?;
+            if operator == "in_last_days" && SMART_DATE_FIELDS.contains(column) {
+                params.push(Value::Integer(now - number * 86_400));
+                return Ok(format!("{} >= ?", column));
+            }
+            let sql_operator = match operator {
+                "=" | "!=" | ">" | ">=" | "<" | "<=" => operator,
+                _ => return Err(invalid()),
+            };
+            params.push(Value::Integer(number));
+            // Unset counts/ratings compare as 0
+            return Ok(format!("COALESCE({}, 0) {} ?", column, sql_operator));
+        }
+
+        let text = value.as_str().ok_or_else(invalid)?;
+        // LIKE with the pattern characters escaped, case-insensitive like the rest of the UI
+        let mut like = |pattern: String| {
+            params.push(Value::Text(pattern));
+            format!("COALESCE({}, '') LIKE ? ESCAPE '\\'", column)
+        };
+        let escaped = text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+        let clause = match operator {
+            "is" => like(escaped),
+            "is_not" => format!("NOT {}", like(escaped)),
+            "contains" => like(format!("%{}%", escaped)),
+            "not_contains" => format!("NOT {}", like(format!("%{}%", escaped))),
+            "starts_with" => like(format!("{}%", escaped)),
+            _ => return Err(invalid()),
+        };
+        Ok(clause)
+    }
+}
+
+/// A play to report to Last.fm. Queued in pending_scrobbles until it was accepted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scrobble {
+    /// Row in pending_scrobbles, 0 if not queued
+    pub id: i64,
+    pub artist: String,
+    pub title: String,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub duration_ms: Option<i64>,
+    /// When playback started (unix seconds)
+    pub timestamp: i64,
+}
+
+/// One page of the library plus the size of the whole library
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackPage {
+    pub tracks: Vec<Track>,
+    pub total_count: i64,
+}
+
+/// What the UI shows on launch: an empty state, a "configure paths" prompt, or the library
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LibraryStatus {
+    pub has_scan_paths: bool,
+    pub track_count: i64,
+    /// Most recent scan of any scan path (unix seconds), None if never scanned
+    pub last_scan_at: Option<i64>,
+}
+
+/// Size of the whole collection, for the library overview
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LibraryStats {
+    pub track_count: i64,
+    /// Artists credited on at least one track
+    pub artist_count: i64,
+    /// Albums as listed by get_all_albums (name + album artist)
+    pub album_count: i64,
+    pub total_duration_ms: i64,
+    pub total_size_bytes: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -51,6 +453,8 @@ pub struct Album {
     pub artist: Option<String>,
     pub year: Option<i32>,
     pub song_count: i32,
+    /// Various-artists album (soundtrack, sampler), see DbOperations::sync_albums
+    pub is_compilation: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -74,7 +478,7 @@ pub struct Playlist {
     pub description: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Queue {
     pub id: i64,
     pub name: String,
@@ -87,6 +491,101 @@ pub struct Queue {
     pub original_order: Option<String>,
 }
 
+/// A folder in the library's directory hierarchy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderNode {
+    pub name: String,
+    /// Full folder path, using '/' as separator on all platforms
+    pub path: String,
+    /// Number of tracks in this folder and all its subfolders
+    pub track_count: i32,
+    pub children: Vec<FolderNode>,
+}
+
+/// One folder's contents, for browsing the library by directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectoryListing {
+    /// The folder as requested
+    pub path: String,
+    /// Full paths of the folders directly inside that hold tracks, sorted by name
+    pub subdirectories: Vec<String>,
+    /// Tracks directly in the folder, sorted by file path
+    pub tracks: Vec<Track>,
+}
+
+/// Progress of filling in missing track durations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DurationBackfillProgress {
+    pub current: usize,
+    pub total: usize,
+    pub current_file: String,
+    pub updated: usize,
+    pub failed: usize,
+}
+
+/// Total and remaining play time of a queue
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueueDuration {
+    pub total_ms: i64,
+    /// From the current track (inclusive) to the end of the queue
+    pub remaining_ms: i64,
+    /// Some tracks have no known duration and were left out of the sums
+    pub is_approximate: bool,
+}
+
+/// A queue entry with its play state, for greying out already-played tracks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnnotatedQueueTrack {
+    #[serde(flatten)]
+    pub track: Track,
+    pub queue_position: i32,
+    pub is_current: bool,
+    /// Already played in the current play order (respects shuffle)
+    pub is_played: bool,
+}
+
+/// Combined shuffle/repeat state of a queue, read by the player in one call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueuePlaybackModes {
+    pub shuffle_on: bool,
+    pub shuffle_seed: i64,
+    pub shuffle_anchor: i64,
+    pub repeat_mode: RepeatMode,
+}
+
+/// Repeat mode of a queue. The frontend sees "off", "all" or "one"; queues.repeat_mode
+/// stores 0, 1 or 2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RepeatMode {
+    #[default]
+    Off,
+    /// Start the queue over after the last track
+    All,
+    /// Keep playing the current track
+    One,
+}
+
+impl RepeatMode {
+    /// Value stored in queues.repeat_mode
+    pub fn to_db(self) -> i64 {
+        match self {
+            RepeatMode::Off => 0,
+            RepeatMode::All => 1,
+            RepeatMode::One => 2,
+        }
+    }
+
+    /// Read queues.repeat_mode, unknown values counting as off
+    pub fn from_db(value: i64) -> Self {
+        match value {
+            1 => RepeatMode::All,
+            2 => RepeatMode::One,
+            _ => RepeatMode::Off,
+        }
+    }
+}
+
 fn default_shuffle_seed() -> i64 {
     1
 }
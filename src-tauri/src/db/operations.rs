@@ -1,22 +1,100 @@
 use rusqlite::{params, OptionalExtension};
-use crate::db::models::{Track, Album, Artist, Playlist};
+use crate::db::models::{Track, Album, Artist, Playlist, QueuePlaybackModes, RepeatMode, FolderNode, DirectoryListing, AnnotatedQueueTrack, QueueDuration, LibraryStatus, LibraryStats, Scrobble, SmartPlaylist, SmartPlaylistRules, AlbumStats, SessionState, TrackLoudness, TrackPage, TrackSort, AlbumSort, ArtistSort};
 use crate::db::connection::DatabaseConnection;
+use crate::db::queries::{
+    album_artist_key, normalize_name, sync_albums_sql, MARK_COMPILATIONS_SQL, TRACKS_FOR_AUDIO_FILE_SQL,
+};
+use crate::error::AppError;
+use crate::metadata::cue::{audio_file_path, split_cue_track_path, CueRange};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Track columns in the order row_to_track reads them, for queries over `tracks t`
+const TRACK_COLUMNS: &str =
+    "t.id, t.file_path, t.title, t.artist, t.album, t.album_artist, t.year,
+     t.track_number, t.disc_number, t.duration_ms, t.genre,
+     t.file_size, t.file_format, t.bitrate, t.sample_rate,
+     t.play_count, t.last_played, t.date_added, t.date_modified, t.file_hash,
+     t.normalization_gain_db, t.play_time_seconds, t.has_audio, t.track_total, t.disc_total, t.rating, t.is_favorite,
+     t.composer, t.comment, t.bpm, t.compilation, t.skip_count";
+
+/// Build a Track from a row selected with TRACK_COLUMNS
+fn row_to_track(row: &rusqlite::Row) -> rusqlite::Result<Track> {
+    Ok(Track {
+        id: row.get(0)?,
+        file_path: row.get(1)?,
+        title: row.get(2)?,
+        artist: row.get(3)?,
+        album: row.get(4)?,
+        album_artist: row.get(5)?,
+        year: row.get::<_, Option<i32>>(6)?.map(|y| y as u32),
+        track_number: row.get(7)?,
+        disc_number: row.get(8)?,
+        duration_ms: row.get(9)?,
+        genre: row.get(10)?,
+        file_size: row.get(11)?,
+        file_format: row.get(12)?,
+        bitrate: row.get(13)?,
+        sample_rate: row.get(14)?,
+        play_count: row.get(15)?,
+        last_played: row.get(16)?,
+        date_added: row.get(17)?,
+        date_modified: row.get(18)?,
+        file_hash: row.get(19)?,
+        normalization_gain_db: row.get(20)?,
+        play_time_seconds: row.get(21)?,
+        has_audio: row.get(22)?,
+        track_total: row.get(23)?,
+        disc_total: row.get(24)?,
+        rating: row.get(25)?,
+        is_favorite: row.get(26)?,
+        composer: row.get(27)?,
+        comment: row.get(28)?,
+        bpm: row.get(29)?,
+        compilation: row.get(30)?,
+        skip_count: row.get(31)?,
+    })
+}
+
+/// Tracks whose durations differ by at most this much can be the same recording
+/// (different encoders pad the start and end differently)
+const DUPLICATE_DURATION_TOLERANCE_MS: i64 = 2000;
+
+/// Skips before a track can show up as never finished, so one early skip doesn't list it
+const NEVER_FINISHED_MIN_SKIPS: i64 = 2;
+
+/// Lowercase title or artist without punctuation and extra spaces, so names typed
+/// slightly differently ("Don't Stop!" and "dont  stop") match
+fn normalize_for_matching(value: &str) -> String {
+    value.to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Name given to the reusable queue for playing a single track immediately. The queue is
+/// found by its is_ad_hoc flag, so a user queue with this name is left alone.
+pub const AD_HOC_QUEUE_NAME: &str = "Now Playing";
 
 /// Database operations for library management
 pub struct DbOperations;
 
 impl DbOperations {
-    /// Insert or get artist ID
+    /// Insert or get artist ID. Names are matched by normalize_name; the row keeps the
+    /// name as first seen (trimmed).
     pub fn insert_or_get_artist(
         db: &DatabaseConnection,
         name: &str,
     ) -> Result<i64, anyhow::Error> {
         let conn = db.get_connection();
         let conn = conn.lock().unwrap();
+        let normalized = normalize_name(name);
         
         // Check if artist exists
-        let mut stmt = conn.prepare("SELECT id FROM artists WHERE name = ?1")?;
-        let mut rows = stmt.query(params![name])?;
+        let mut stmt = conn.prepare("SELECT id FROM artists WHERE name_normalized = ?1")?;
+        let mut rows = stmt.query(params![normalized])?;
         
         if let Some(row) = rows.next()? {
             return Ok(row.get(0)?);
@@ -24,54 +102,25 @@ impl DbOperations {
         
         // Insert new artist
         conn.execute(
-            "INSERT INTO artists (name) VALUES (?1)",
-            params![name],
-        )?;
-        
-        Ok(conn.last_insert_rowid())
-    }
-    
-    /// Insert or get album ID
-    pub fn insert_or_get_album(
-        db: &DatabaseConnection,
-        title: &str,
-        artist_name: Option<&str>,
-        year: Option<u32>,
-    ) -> Result<i64, anyhow::Error> {
-        let conn = db.get_connection();
-        let conn = conn.lock().unwrap();
-        
-        // Check if album exists
-        let mut stmt = conn.prepare(
-            "SELECT id FROM albums WHERE name = ?1 AND artist IS ?2"
-        )?;
-        let mut rows = stmt.query(params![title, artist_name])?;
-        
-        if let Some(row) = rows.next()? {
-            return Ok(row.get(0)?);
-        }
-        
-        // Insert new album
-        let year_i32 = year.map(|y| y as i32);
-        conn.execute(
-            "INSERT INTO albums (name, artist, year) VALUES (?1, ?2, ?3)",
-            params![title, artist_name, year_i32],
+            "INSERT INTO artists (name, name_normalized) VALUES (?1, ?2)",
+            params![name.trim(), normalized],
         )?;
         
         Ok(conn.last_insert_rowid())
     }
     
-    /// Insert or get genre ID
+    /// Insert or get genre ID, matched like insert_or_get_artist
     pub fn insert_or_get_genre(
         db: &DatabaseConnection,
         name: &str,
     ) -> Result<i64, anyhow::Error> {
         let conn = db.get_connection();
         let conn = conn.lock().unwrap();
+        let normalized = normalize_name(name);
         
         // Check if genre exists
-        let mut stmt = conn.prepare("SELECT id FROM genres WHERE name = ?1")?;
-        let mut rows = stmt.query(params![name])?;
+        let mut stmt = conn.prepare("SELECT id FROM genres WHERE name_normalized = ?1")?;
+        let mut rows = stmt.query(params![normalized])?;
         
         if let Some(row) = rows.next()? {
             return Ok(row.get(0)?);
@@ -79,8 +128,8 @@ impl DbOperations {
         
         // Insert new genre
         conn.execute(
-            "INSERT INTO genres (name) VALUES (?1)",
-            params![name],
+            "INSERT INTO genres (name, name_normalized) VALUES (?1, ?2)",
+            params![name.trim(), normalized],
         )?;
         
         Ok(conn.last_insert_rowid())
@@ -112,6 +161,26 @@ impl DbOperations {
         Ok(())
     }
     
+    /// Link a track with the individual artists of its album artist field
+    pub fn link_track_album_artists(
+        db: &DatabaseConnection,
+        track_id: i64,
+        artist_names: &[String],
+    ) -> Result<(), anyhow::Error> {
+        for artist_name in artist_names {
+            let artist_id = Self::insert_or_get_artist(db, artist_name)?;
+            
+            let conn = db.get_connection();
+            let conn = conn.lock().unwrap();
+            conn.execute(
+                "INSERT OR IGNORE INTO track_album_artists (track_id, artist_id) VALUES (?1, ?2)",
+                params![track_id, artist_id],
+            )?;
+        }
+        
+        Ok(())
+    }
+    
     /// Link a track with multiple genres
     pub fn link_track_genres(
         db: &DatabaseConnection,
@@ -145,48 +214,140 @@ impl DbOperations {
         let conn = db.get_connection();
         let conn = conn.lock().unwrap();
         
-        let mut stmt = conn.prepare(
-            "SELECT id, file_path, title, artist, album, album_artist, year,
-                    track_number, disc_number, duration_ms, genre,
-                    file_size, file_format, bitrate, sample_rate,
-                    play_count, last_played, date_added, date_modified, file_hash,
-                    normalization_gain_db, play_time_seconds
-             FROM tracks
-             ORDER BY date_added DESC"
-        )?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM tracks t
+             ORDER BY date_added DESC",
+            TRACK_COLUMNS
+        ))?;
         
-        let tracks = stmt.query_map([], |row| {
-            Ok(Track {
-                id: row.get(0)?,
-                file_path: row.get(1)?,
-                title: row.get(2)?,
-                artist: row.get(3)?,
-                album: row.get(4)?,
-                album_artist: row.get(5)?,
-                year: row.get::<_, Option<i32>>(6)?.map(|y| y as u32),
-                track_number: row.get(7)?,
-                disc_number: row.get(8)?,
-                duration_ms: row.get(9)?,
-                genre: row.get(10)?,
-                file_size: row.get(11)?,
-                file_format: row.get(12)?,
-                bitrate: row.get(13)?,
-                sample_rate: row.get(14)?,
-                play_count: row.get(15)?,
-                last_played: row.get(16)?,
-                date_added: row.get(17)?,
-                date_modified: row.get(18)?,
-                file_hash: row.get(19)?,
-                normalization_gain_db: row.get(20)?,
-                play_time_seconds: row.get(21)?,
-            })
-        })?
+        let tracks = stmt.query_map([], row_to_track)?
         .collect::<Result<Vec<_>, _>>()?;
         
         Ok(tracks)
     }
     
-    /// Get tracks by artist (using junction table)
+    /// Groups of tracks with identical content (same file hash) at different paths.
+    /// Tracks without a hash are left out. The tracks of one CUE sheet share a hash, so a
+    /// CUE track only matches the same range of another copy of its file.
+    pub fn find_duplicate_tracks(
+        db: &DatabaseConnection,
+    ) -> Result<Vec<Vec<Track>>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM tracks t
+             WHERE file_hash IN (
+                 SELECT file_hash FROM tracks
+                 WHERE file_hash IS NOT NULL
+                 GROUP BY file_hash
+                 HAVING COUNT(*) > 1
+             )
+             ORDER BY file_hash, file_path",
+            TRACK_COLUMNS
+        ))?;
+        
+        let tracks = stmt.query_map([], row_to_track)?
+        .collect::<Result<Vec<_>, _>>()?;
+        
+        let mut groups: Vec<Vec<Track>> = Vec::new();
+        let mut keys: std::collections::HashMap<(String, Option<CueRange>), usize> = std::collections::HashMap::new();
+        for track in tracks {
+            let key = (track.file_hash.clone().unwrap_or_default(), split_cue_track_path(&track.file_path).1);
+            match keys.get(&key) {
+                Some(&group) => groups[group].push(track),
+                None => {
+                    keys.insert(key, groups.len());
+                    groups.push(vec![track]);
+                }
+            }
+        }
+        groups.retain(|group| group.len() > 1);
+        
+        Ok(groups)
+    }
+    
+    /// Groups of tracks that are probably the same recording in different files or
+    /// encodings: same title and artist (ignoring case, punctuation and spacing) and
+    /// durations within DUPLICATE_DURATION_TOLERANCE_MS of each other. Tracks without a
+    /// duration are left out.
+    pub fn find_duplicate_metadata(
+        db: &DatabaseConnection,
+    ) -> Result<Vec<Vec<Track>>, anyhow::Error> {
+        let mut by_name: std::collections::BTreeMap<(String, String), Vec<Track>> = std::collections::BTreeMap::new();
+        for track in Self::get_all_tracks(db)? {
+            if track.duration_ms.is_none() {
+                continue;
+            }
+            let key = (
+                normalize_for_matching(&track.title),
+                normalize_for_matching(track.artist.as_deref().unwrap_or_default()),
+            );
+            by_name.entry(key).or_default().push(track);
+        }
+        
+        let mut groups = Vec::new();
+        for (_, mut tracks) in by_name {
+            if tracks.len() < 2 {
+                continue;
+            }
+            // Chain tracks whose durations are close into one group
+            tracks.sort_by_key(|t| (t.duration_ms, t.id));
+            let mut group: Vec<Track> = Vec::new();
+            for track in tracks {
+                let close = group.last()
+                    .and_then(|last| last.duration_ms.zip(track.duration_ms))
+                    .is_some_and(|(last, current)| current - last <= DUPLICATE_DURATION_TOLERANCE_MS);
+                if !close {
+                    if group.len() > 1 {
+                        groups.push(std::mem::take(&mut group));
+                    }
+                    group.clear();
+                }
+                group.push(track);
+            }
+            if group.len() > 1 {
+                groups.push(group);
+            }
+        }
+        
+        Ok(groups)
+    }
+    
+    /// Get one page of the library in the given order, with the total track count
+    pub fn get_tracks_page(
+        db: &DatabaseConnection,
+        limit: i64,
+        offset: i64,
+        sort: TrackSort,
+    ) -> Result<TrackPage, anyhow::Error> {
+        if limit < 0 || offset < 0 {
+            return Err(AppError::InvalidInput(format!("Invalid page: limit {}, offset {}", limit, offset)).into());
+        }
+
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+
+        let total_count: i64 = conn.query_row("SELECT COUNT(*) FROM tracks", [], |row| row.get(0))?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM tracks t
+             ORDER BY {}
+             LIMIT ?1 OFFSET ?2",
+            TRACK_COLUMNS,
+            sort.order_by()
+        ))?;
+
+        let tracks = stmt.query_map(params![limit, offset], row_to_track)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(TrackPage { tracks, total_count })
+    }
+    
+    /// Get tracks by artist, as track artist or album artist (using junction tables)
     pub fn get_tracks_by_artist(
         db: &DatabaseConnection,
         artist_id: i64,
@@ -194,44 +355,64 @@ impl DbOperations {
         let conn = db.get_connection();
         let conn = conn.lock().unwrap();
         
-        let mut stmt = conn.prepare(
-            "SELECT t.id, t.file_path, t.title, t.artist, t.album, t.album_artist, t.year,
-                    t.track_number, t.disc_number, t.duration_ms, t.genre,
-                    t.file_size, t.file_format, t.bitrate, t.sample_rate,
-                    t.play_count, t.last_played, t.date_added, t.date_modified, t.file_hash,
-                    t.normalization_gain_db, t.play_time_seconds
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
              FROM tracks t
-             INNER JOIN track_artists ta ON ta.track_id = t.id
-             WHERE ta.artist_id = ?1
-             ORDER BY t.album, t.track_number"
-        )?;
+             WHERE t.id IN (
+                 SELECT track_id FROM track_artists WHERE artist_id = ?1
+                 UNION
+                 SELECT track_id FROM track_album_artists WHERE artist_id = ?1
+             )
+             ORDER BY t.album, t.track_number",
+            TRACK_COLUMNS
+        ))?;
+        
+        let tracks = stmt.query_map([artist_id], row_to_track)?
+        .collect::<Result<Vec<_>, _>>()?;
         
-        let tracks = stmt.query_map([artist_id], |row| {
-            Ok(Track {
-                id: row.get(0)?,
-                file_path: row.get(1)?,
-                title: row.get(2)?,
-                artist: row.get(3)?,
-                album: row.get(4)?,
-                album_artist: row.get(5)?,
-                year: row.get::<_, Option<i32>>(6)?.map(|y| y as u32),
-                track_number: row.get(7)?,
-                disc_number: row.get(8)?,
-                duration_ms: row.get(9)?,
-                genre: row.get(10)?,
-                file_size: row.get(11)?,
-                file_format: row.get(12)?,
-                bitrate: row.get(13)?,
-                sample_rate: row.get(14)?,
-                play_count: row.get(15)?,
-                last_played: row.get(16)?,
-                date_added: row.get(17)?,
-                date_modified: row.get(18)?,
-                file_hash: row.get(19)?,
-                normalization_gain_db: row.get(20)?,
-                play_time_seconds: row.get(21)?,
-            })
-        })?
+        Ok(tracks)
+    }
+    
+    /// Get tracks whose album artist field credits the artist (using junction table)
+    pub fn get_tracks_by_album_artist(
+        db: &DatabaseConnection,
+        artist_id: i64,
+    ) -> Result<Vec<Track>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM tracks t
+             INNER JOIN track_album_artists taa ON taa.track_id = t.id
+             WHERE taa.artist_id = ?1
+             ORDER BY t.album, t.track_number",
+            TRACK_COLUMNS
+        ))?;
+        
+        let tracks = stmt.query_map([artist_id], row_to_track)?
+        .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(tracks)
+    }
+    
+    /// Get tracks by composer
+    pub fn get_tracks_by_composer(
+        db: &DatabaseConnection,
+        composer: &str,
+    ) -> Result<Vec<Track>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM tracks t
+             WHERE t.composer = ?1
+             ORDER BY t.album, t.track_number",
+            TRACK_COLUMNS
+        ))?;
+        
+        let tracks = stmt.query_map([composer], row_to_track)?
         .collect::<Result<Vec<_>, _>>()?;
         
         Ok(tracks)
@@ -245,44 +426,57 @@ impl DbOperations {
         let conn = db.get_connection();
         let conn = conn.lock().unwrap();
         
-        let mut stmt = conn.prepare(
-            "SELECT t.id, t.file_path, t.title, t.artist, t.album, t.album_artist, t.year,
-                    t.track_number, t.disc_number, t.duration_ms, t.genre,
-                    t.file_size, t.file_format, t.bitrate, t.sample_rate,
-                    t.play_count, t.last_played, t.date_added, t.date_modified, t.file_hash,
-                    t.normalization_gain_db, t.play_time_seconds
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
              FROM tracks t
              INNER JOIN track_genres tg ON tg.track_id = t.id
              WHERE tg.genre_id = ?1
-             ORDER BY t.artist, t.album, t.track_number"
-        )?;
+             ORDER BY t.artist, t.album, t.track_number",
+            TRACK_COLUMNS
+        ))?;
         
-        let tracks = stmt.query_map([genre_id], |row| {
-            Ok(Track {
-                id: row.get(0)?,
-                file_path: row.get(1)?,
-                title: row.get(2)?,
-                artist: row.get(3)?,
-                album: row.get(4)?,
-                album_artist: row.get(5)?,
-                year: row.get::<_, Option<i32>>(6)?.map(|y| y as u32),
-                track_number: row.get(7)?,
-                disc_number: row.get(8)?,
-                duration_ms: row.get(9)?,
-                genre: row.get(10)?,
-                file_size: row.get(11)?,
-                file_format: row.get(12)?,
-                bitrate: row.get(13)?,
-                sample_rate: row.get(14)?,
-                play_count: row.get(15)?,
-                last_played: row.get(16)?,
-                date_added: row.get(17)?,
-                date_modified: row.get(18)?,
-                file_hash: row.get(19)?,
-                normalization_gain_db: row.get(20)?,
-                play_time_seconds: row.get(21)?,
-            })
-        })?
+        let tracks = stmt.query_map([genre_id], row_to_track)?
+        .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(tracks)
+    }
+    
+    /// Tracks released in `year`. Year 0 is the "Unknown" bucket of get_all_years:
+    /// tracks without a year.
+    pub fn get_tracks_by_year(
+        db: &DatabaseConnection,
+        year: i32,
+    ) -> Result<Vec<Track>, anyhow::Error> {
+        Self::get_tracks_in_years(db, year, year)
+    }
+    
+    /// Tracks released in the decade starting at `decade_start` (1990 for 1990-1999)
+    pub fn get_tracks_by_decade(
+        db: &DatabaseConnection,
+        decade_start: i32,
+    ) -> Result<Vec<Track>, anyhow::Error> {
+        Self::get_tracks_in_years(db, decade_start, decade_start + 9)
+    }
+    
+    /// Tracks with a year from `from` to `to` (inclusive), newest first. Tracks without
+    /// a year count as year 0.
+    fn get_tracks_in_years(
+        db: &DatabaseConnection,
+        from: i32,
+        to: i32,
+    ) -> Result<Vec<Track>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM tracks t
+             WHERE COALESCE(t.year, 0) BETWEEN ?1 AND ?2
+             ORDER BY t.year DESC, t.artist, t.album, t.track_number",
+            TRACK_COLUMNS
+        ))?;
+        
+        let tracks = stmt.query_map([from, to], row_to_track)?
         .collect::<Result<Vec<_>, _>>()?;
         
         Ok(tracks)
@@ -296,48 +490,92 @@ impl DbOperations {
         let conn = db.get_connection();
         let conn = conn.lock().unwrap();
         
-        let mut stmt = conn.prepare(
-            "SELECT id, file_path, title, artist, album, album_artist, year,
-                    track_number, disc_number, duration_ms, genre,
-                    file_size, file_format, bitrate, sample_rate,
-                    play_count, last_played, date_added, date_modified, file_hash,
-                    normalization_gain_db, play_time_seconds
-             FROM tracks
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM tracks t
              WHERE album = ?1
-             ORDER BY disc_number, track_number"
-        )?;
+             ORDER BY disc_number, track_number",
+            TRACK_COLUMNS
+        ))?;
         
-        let tracks = stmt.query_map([album_name], |row| {
-            Ok(Track {
-                id: row.get(0)?,
-                file_path: row.get(1)?,
-                title: row.get(2)?,
-                artist: row.get(3)?,
-                album: row.get(4)?,
-                album_artist: row.get(5)?,
-                year: row.get::<_, Option<i32>>(6)?.map(|y| y as u32),
-                track_number: row.get(7)?,
-                disc_number: row.get(8)?,
-                duration_ms: row.get(9)?,
-                genre: row.get(10)?,
-                file_size: row.get(11)?,
-                file_format: row.get(12)?,
-                bitrate: row.get(13)?,
-                sample_rate: row.get(14)?,
-                play_count: row.get(15)?,
-                last_played: row.get(16)?,
-                date_added: row.get(17)?,
-                date_modified: row.get(18)?,
-                file_hash: row.get(19)?,
-                normalization_gain_db: row.get(20)?,
-                play_time_seconds: row.get(21)?,
-            })
-        })?
+        let tracks = stmt.query_map([album_name], row_to_track)?
+        .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(tracks)
+    }
+
+    /// Get the tracks of one album entry from get_all_albums. `artist` is the album's
+    /// artist as listed there (album artist, falling back to track artist), None for albums
+    /// without one, so same-named albums by different artists stay apart.
+    pub fn get_tracks_by_album_and_artist(
+        db: &DatabaseConnection,
+        album_name: &str,
+        artist: Option<&str>,
+    ) -> Result<Vec<Track>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM tracks t
+             WHERE album = ?1 AND {} IS ?2
+             ORDER BY disc_number, track_number",
+            TRACK_COLUMNS,
+            album_artist_key("t")
+        ))?;
+        
+        let tracks = stmt.query_map(params![album_name, artist], row_to_track)?
+        .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(tracks)
+    }
+
+    /// Get the tracks of an album by its id from get_all_albums
+    pub fn get_tracks_by_album_id(
+        db: &DatabaseConnection,
+        album_id: i64,
+    ) -> Result<Vec<Track>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM tracks t
+             WHERE album_id = ?1
+             ORDER BY disc_number, track_number",
+            TRACK_COLUMNS
+        ))?;
+        
+        let tracks = stmt.query_map([album_id], row_to_track)?
         .collect::<Result<Vec<_>, _>>()?;
         
         Ok(tracks)
     }
 
+    /// Play count, last played time, track count and total duration of an album
+    pub fn get_album_stats(
+        db: &DatabaseConnection,
+        album_id: i64,
+    ) -> Result<AlbumStats, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let stats = conn.query_row(
+            "SELECT COALESCE(SUM(play_count), 0), MAX(last_played), COUNT(*), COALESCE(SUM(duration_ms), 0)
+             FROM tracks
+             WHERE album_id = ?1",
+            [album_id],
+            |row| Ok(AlbumStats {
+                play_count_sum: row.get(0)?,
+                last_played_max: row.get(1)?,
+                track_count: row.get(2)?,
+                total_duration_ms: row.get(3)?,
+            })
+        )?;
+        
+        Ok(stats)
+    }
+
     /// Get track by file path
     pub fn get_track_by_file_path(
         db: &DatabaseConnection,
@@ -346,43 +584,17 @@ impl DbOperations {
         let conn = db.get_connection();
         let conn = conn.lock().unwrap();
         
-        let mut stmt = conn.prepare(
-            "SELECT id, file_path, title, artist, album, album_artist, year,
-                    track_number, disc_number, duration_ms, genre,
-                    file_size, file_format, bitrate, sample_rate,
-                    play_count, last_played, date_added, date_modified, file_hash,
-                    normalization_gain_db, play_time_seconds
-             FROM tracks
-             WHERE file_path = ?1"
-        )?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM tracks t
+             WHERE file_path = ?1",
+            TRACK_COLUMNS
+        ))?;
         
         let mut rows = stmt.query([file_path])?;
         
         if let Some(row) = rows.next()? {
-            Ok(Some(Track {
-                id: row.get(0)?,
-                file_path: row.get(1)?,
-                title: row.get(2)?,
-                artist: row.get(3)?,
-                album: row.get(4)?,
-                album_artist: row.get(5)?,
-                year: row.get::<_, Option<i32>>(6)?.map(|y| y as u32),
-                track_number: row.get(7)?,
-                disc_number: row.get(8)?,
-                duration_ms: row.get(9)?,
-                genre: row.get(10)?,
-                file_size: row.get(11)?,
-                file_format: row.get(12)?,
-                bitrate: row.get(13)?,
-                sample_rate: row.get(14)?,
-                play_count: row.get(15)?,
-                last_played: row.get(16)?,
-                date_added: row.get(17)?,
-                date_modified: row.get(18)?,
-                file_hash: row.get(19)?,
-                normalization_gain_db: row.get(20)?,
-                play_time_seconds: row.get(21)?,
-            }))
+            Ok(Some(row_to_track(row)?))
         } else {
             Ok(None)
         }
@@ -396,156 +608,381 @@ impl DbOperations {
         let conn = db.get_connection();
         let conn = conn.lock().unwrap();
         
-        let mut stmt = conn.prepare(
-            "SELECT id, file_path, title, artist, album, album_artist, year,
-                    track_number, disc_number, duration_ms, genre,
-                    file_size, file_format, bitrate, sample_rate,
-                    play_count, last_played, date_added, date_modified, file_hash,
-                    normalization_gain_db, play_time_seconds
-             FROM tracks
-             WHERE id = ?1"
-        )?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM tracks t
+             WHERE id = ?1",
+            TRACK_COLUMNS
+        ))?;
         
         let mut rows = stmt.query([track_id])?;
         
         if let Some(row) = rows.next()? {
-            Ok(Some(Track {
-                id: row.get(0)?,
-                file_path: row.get(1)?,
-                title: row.get(2)?,
-                artist: row.get(3)?,
-                album: row.get(4)?,
-                album_artist: row.get(5)?,
-                year: row.get::<_, Option<i32>>(6)?.map(|y| y as u32),
-                track_number: row.get(7)?,
-                disc_number: row.get(8)?,
-                duration_ms: row.get(9)?,
-                genre: row.get(10)?,
-                file_size: row.get(11)?,
-                file_format: row.get(12)?,
-                bitrate: row.get(13)?,
-                sample_rate: row.get(14)?,
-                play_count: row.get(15)?,
-                last_played: row.get(16)?,
-                date_added: row.get(17)?,
-                date_modified: row.get(18)?,
-                file_hash: row.get(19)?,
-                normalization_gain_db: row.get(20)?,
-                play_time_seconds: row.get(21)?,
-            }))
+            Ok(Some(row_to_track(row)?))
         } else {
             Ok(None)
         }
     }
     
-    /// Get all albums with song counts
+    /// Get all albums with song counts. An album is a name plus its artist (album artist,
+    /// falling back to track artist), so same-named albums by different artists are separate.
+    /// Compilations without an album artist are credited to "Various Artists".
+    /// IDs are the albums table's, stable across calls (see sync_albums).
     pub fn get_all_albums(
         db: &DatabaseConnection,
+        sort: AlbumSort,
     ) -> Result<Vec<Album>, anyhow::Error> {
         let conn = db.get_connection();
         let conn = conn.lock().unwrap();
         
-        let mut stmt = conn.prepare(
-            "SELECT t.album, 
-                    COALESCE(t.album_artist, t.artist) as artist,
+        let mut stmt = conn.prepare(&format!(
+            "SELECT a.id, a.name, a.artist,
                     MIN(t.year) as year,
-                    COUNT(DISTINCT t.id) as song_count
-             FROM tracks t
-             WHERE t.album IS NOT NULL
-             GROUP BY t.album
-             ORDER BY t.album"
-        )?;
-        
-        let mut albums = Vec::new();
-        let mut id = 1;
-        
-        let rows = stmt.query_map([], |row| {
-            Ok((
-                row.get::<_, String>(0)?,
-                row.get::<_, Option<String>>(1)?,
-                row.get::<_, Option<i32>>(2)?,
-                row.get::<_, i32>(3)?,
-            ))
-        })?;
-        
-        for row in rows {
-            let (name, artist, year, song_count) = row?;
-            albums.push(Album {
-                id,
-                name,
-                artist,
-                year,
-                song_count,
-            });
-            id += 1;
-        }
+                    COUNT(t.id) as song_count,
+                    a.is_compilation
+             FROM albums a
+             JOIN tracks t ON t.album_id = a.id
+             GROUP BY a.id
+             ORDER BY {}",
+            sort.order_by()
+        ))?;
+        
+        let albums = stmt.query_map([], |row| {
+            Ok(Album {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                artist: row.get(2)?,
+                year: row.get(3)?,
+                song_count: row.get(4)?,
+                is_compilation: row.get(5)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
         
         Ok(albums)
     }
     
-    /// Get all artists with song counts
-    pub fn get_all_artists(
+    /// Get the compilation albums (soundtracks, samplers) with song counts, see
+    /// MARK_COMPILATIONS_SQL. IDs match the ones returned by get_all_albums.
+    pub fn get_compilation_albums(
         db: &DatabaseConnection,
-    ) -> Result<Vec<Artist>, anyhow::Error> {
+    ) -> Result<Vec<Album>, anyhow::Error> {
         let conn = db.get_connection();
         let conn = conn.lock().unwrap();
         
         let mut stmt = conn.prepare(
-            "SELECT ar.id, ar.name, COUNT(DISTINCT ta.track_id) as song_count
-             FROM artists ar
-             LEFT JOIN track_artists ta ON ta.artist_id = ar.id
-             GROUP BY ar.id, ar.name
-             ORDER BY ar.name"
+            "SELECT a.id, a.name, a.artist,
+                    MIN(t.year) as year,
+                    COUNT(t.id) as song_count,
+                    a.is_compilation
+             FROM albums a
+             JOIN tracks t ON t.album_id = a.id
+             WHERE a.is_compilation
+             GROUP BY a.id
+             ORDER BY a.name, a.artist"
         )?;
         
-        let artists = stmt.query_map([], |row| {
-            Ok(Artist {
+        let albums = stmt.query_map([], |row| {
+            Ok(Album {
                 id: row.get(0)?,
                 name: row.get(1)?,
-                song_count: row.get(2)?,
+                artist: row.get(2)?,
+                year: row.get(3)?,
+                song_count: row.get(4)?,
+                is_compilation: row.get(5)?,
             })
         })?
         .collect::<Result<Vec<_>, _>>()?;
         
-        Ok(artists)
+        Ok(albums)
     }
     
-    /// Get all genres with song counts
-    pub fn get_all_genres(
-        db: &DatabaseConnection,
-    ) -> Result<Vec<crate::db::models::Genre>, anyhow::Error> {
+    /// Bring the albums table in line with the tracks: create a row for every album name +
+    /// artist (album artist, falling back to track artist), point each track's album_id at
+    /// its row and delete rows no track belongs to any more. Then flag the compilations.
+    pub fn sync_albums(db: &DatabaseConnection) -> Result<(), anyhow::Error> {
         let conn = db.get_connection();
         let conn = conn.lock().unwrap();
         
-        let mut stmt = conn.prepare(
-            "SELECT g.id, g.name, COUNT(DISTINCT tg.track_id) as song_count
-             FROM genres g
-             LEFT JOIN track_genres tg ON tg.genre_id = g.id
-             GROUP BY g.id, g.name
-             ORDER BY g.name"
-        )?;
-        
-        let genres = stmt.query_map([], |row| {
-            Ok(crate::db::models::Genre {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                song_count: row.get(2)?,
-            })
-        })?
-        .collect::<Result<Vec<_>, _>>()?;
+        conn.execute_batch(&sync_albums_sql())?;
+        conn.execute(MARK_COMPILATIONS_SQL, [])?;
         
-        Ok(genres)
+        Ok(())
     }
     
-    /// Delete all tracks (for testing/reset)
-    pub fn clear_library(db: &DatabaseConnection) -> Result<(), anyhow::Error> {
+    /// Point a track at the albums row for its album name and artist, creating the row if
+    /// needed. Returns the album id, None for tracks without an album.
+    pub fn assign_track_album(
+        db: &DatabaseConnection,
+        track_id: i64,
+    ) -> Result<Option<i64>, anyhow::Error> {
         let conn = db.get_connection();
         let conn = conn.lock().unwrap();
         
-        // Delete junction tables first (foreign key constraints)
-        conn.execute("DELETE FROM track_artists", [])?;
-        conn.execute("DELETE FROM track_genres", [])?;
-        
+        conn.execute(
+            &format!(
+                "INSERT INTO albums (name, artist, year)
+                 SELECT t.album, {0}, t.year FROM tracks t
+                 WHERE t.id = ?1 AND t.album IS NOT NULL
+                   AND NOT EXISTS (SELECT 1 FROM albums a WHERE a.name = t.album AND a.artist IS {0})",
+                album_artist_key("t")
+            ),
+            [track_id],
+        )?;
+        conn.execute(
+            &format!(
+                "UPDATE tracks SET album_id = (
+                    SELECT a.id FROM albums a WHERE a.name = tracks.album AND a.artist IS {}
+                 )
+                 WHERE id = ?1",
+                album_artist_key("tracks")
+            ),
+            [track_id],
+        )?;
+        let album_id = conn.query_row(
+            "SELECT album_id FROM tracks WHERE id = ?1",
+            [track_id],
+            |row| row.get(0),
+        ).optional()?.flatten();
+        if let Some(album_id) = album_id {
+            conn.execute(&format!("{} WHERE id = ?1", MARK_COMPILATIONS_SQL), [album_id])?;
+        }
+        
+        Ok(album_id)
+    }
+    
+    /// Get all artists with song counts. A track counts once for each artist credited
+    /// on it, as track artist or album artist.
+    pub fn get_all_artists(
+        db: &DatabaseConnection,
+        sort: ArtistSort,
+    ) -> Result<Vec<Artist>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT ar.id, ar.name, COUNT(DISTINCT ta.track_id) as song_count
+             FROM artists ar
+             LEFT JOIN (
+                 SELECT track_id, artist_id FROM track_artists
+                 UNION
+                 SELECT track_id, artist_id FROM track_album_artists
+             ) ta ON ta.artist_id = ar.id
+             GROUP BY ar.id, ar.name
+             ORDER BY {}",
+            sort.order_by()
+        ))?;
+        
+        let artists = stmt.query_map([], |row| {
+            Ok(Artist {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                song_count: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(artists)
+    }
+    
+    /// Get an artist's name by ID
+    pub fn get_artist_name(
+        db: &DatabaseConnection,
+        artist_id: i64,
+    ) -> Result<Option<String>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let name = conn.query_row(
+            "SELECT name FROM artists WHERE id = ?1",
+            [artist_id],
+            |row| row.get(0)
+        ).optional()?;
+        
+        Ok(name)
+    }
+    
+    /// Get a genre's name by ID
+    pub fn get_genre_name(
+        db: &DatabaseConnection,
+        genre_id: i64,
+    ) -> Result<Option<String>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let name = conn.query_row(
+            "SELECT name FROM genres WHERE id = ?1",
+            [genre_id],
+            |row| row.get(0)
+        ).optional()?;
+        
+        Ok(name)
+    }
+    
+    /// Get all genres with song counts
+    pub fn get_all_genres(
+        db: &DatabaseConnection,
+    ) -> Result<Vec<crate::db::models::Genre>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let mut stmt = conn.prepare(
+            "SELECT g.id, g.name, COUNT(DISTINCT tg.track_id) as song_count
+             FROM genres g
+             LEFT JOIN track_genres tg ON tg.genre_id = g.id
+             GROUP BY g.id, g.name
+             ORDER BY g.name"
+        )?;
+        
+        let genres = stmt.query_map([], |row| {
+            Ok(crate::db::models::Genre {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                song_count: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(genres)
+    }
+    
+    /// Release years with their track counts, newest first. Tracks without a year are
+    /// counted under year 0 ("Unknown"), which sorts last.
+    pub fn get_all_years(
+        db: &DatabaseConnection,
+    ) -> Result<Vec<(i32, i32)>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let mut stmt = conn.prepare(
+            "SELECT COALESCE(year, 0) AS release_year, COUNT(*)
+             FROM tracks
+             GROUP BY release_year
+             ORDER BY release_year DESC"
+        )?;
+        
+        let years = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(years)
+    }
+    
+    /// Build a folder hierarchy from track file paths, rooted at the scan paths.
+    /// Tracks outside every scan path are rooted at their own parent folder.
+    pub fn get_folder_tree(db: &DatabaseConnection) -> Result<Vec<FolderNode>, anyhow::Error> {
+        let scan_paths: Vec<String> = Self::get_all_scan_paths(db)?
+            .into_iter()
+            .map(|sp| sp.path)
+            .collect();
+        
+        let file_paths: Vec<String> = {
+            let conn = db.get_connection();
+            let conn = conn.lock().unwrap();
+            let mut stmt = conn.prepare("SELECT file_path FROM tracks")?;
+            let paths = stmt.query_map([], |row| row.get(0))?
+                .collect::<Result<Vec<String>, _>>()?;
+            paths
+        };
+        
+        Ok(build_folder_tree(&scan_paths, &file_paths))
+    }
+    
+    /// Get the tracks inside a directory, sorted by file path. With `recursive`, tracks in
+    /// its subfolders are included too. See relative_to_directory for how paths match.
+    pub fn get_tracks_in_directory(
+        db: &DatabaseConnection,
+        dir_prefix: &str,
+        recursive: bool,
+    ) -> Result<Vec<Track>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM tracks t
+             WHERE file_path LIKE ?1 ESCAPE '\\'
+             ORDER BY file_path",
+            TRACK_COLUMNS
+        ))?;
+        
+        let tracks = stmt.query_map([directory_like_pattern(dir_prefix)], row_to_track)?
+        .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(tracks.into_iter()
+            .filter(|track| {
+                relative_to_directory(&track.file_path, dir_prefix)
+                    .is_some_and(|rest| rest.len() == 1 || (recursive && !rest.is_empty()))
+            })
+            .collect())
+    }
+    
+    /// Get the full paths of the folders directly inside a directory that hold tracks (at any
+    /// depth), sorted by name. Paths are the directory as given plus the folder name as stored.
+    pub fn get_subdirectories(
+        db: &DatabaseConnection,
+        dir_prefix: &str,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        let tracks = {
+            let conn = db.get_connection();
+            let conn = conn.lock().unwrap();
+            Self::track_paths_in_directory(&conn, dir_prefix)?
+        };
+        
+        let windows = is_windows_path(dir_prefix);
+        let separator = if windows { '\\' } else { '/' };
+        let parent = dir_prefix.trim_end_matches(['/', '\\']);
+        
+        // Keyed case-insensitively for Windows paths, keeping the first spelling seen
+        let mut children: std::collections::BTreeMap<String, String> = std::collections::BTreeMap::new();
+        for (_, file_path) in &tracks {
+            if let Some(rest) = relative_to_directory(file_path, dir_prefix).filter(|rest| rest.len() > 1) {
+                let key = if windows { rest[0].to_lowercase() } else { rest[0].to_string() };
+                children.entry(key).or_insert_with(|| format!("{}{}{}", parent, separator, rest[0]));
+            }
+        }
+        
+        Ok(children.into_values().collect())
+    }
+    
+    /// (id, file_path) of the tracks inside a directory at any depth, sorted by file path.
+    /// See relative_to_directory for how paths match.
+    fn track_paths_in_directory(
+        conn: &rusqlite::Connection,
+        directory: &str,
+    ) -> Result<Vec<(i64, String)>, anyhow::Error> {
+        let mut stmt = conn.prepare(
+            "SELECT id, file_path FROM tracks WHERE file_path LIKE ?1 ESCAPE '\\' ORDER BY file_path"
+        )?;
+        let rows = stmt.query_map([directory_like_pattern(directory)], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<(i64, String)>, _>>()?;
+        
+        Ok(rows.into_iter()
+            .filter(|(_, file_path)| relative_to_directory(file_path, directory).is_some_and(|rest| !rest.is_empty()))
+            .collect())
+    }
+    
+    /// A directory's subfolders and the tracks directly in it
+    pub fn browse_directory(
+        db: &DatabaseConnection,
+        path: &str,
+    ) -> Result<DirectoryListing, anyhow::Error> {
+        Ok(DirectoryListing {
+            path: path.to_string(),
+            subdirectories: Self::get_subdirectories(db, path)?,
+            tracks: Self::get_tracks_in_directory(db, path, false)?,
+        })
+    }
+    
+    /// Delete all tracks (for testing/reset)
+    pub fn clear_library(db: &DatabaseConnection) -> Result<(), anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        // Delete junction tables first (foreign key constraints)
+        conn.execute("DELETE FROM track_artists", [])?;
+        conn.execute("DELETE FROM track_album_artists", [])?;
+        conn.execute("DELETE FROM track_genres", [])?;
+        
         // Delete main tables
         conn.execute("DELETE FROM tracks", [])?;
         conn.execute("DELETE FROM albums", [])?;
@@ -553,11 +990,52 @@ impl DbOperations {
         conn.execute("DELETE FROM genres", [])?;
         
         // Reset last_scanned on all scan paths so files will be re-indexed
-        conn.execute("UPDATE scan_paths SET last_scanned = NULL", [])?;
+        conn.execute("UPDATE scan_paths SET last_scanned = NULL, file_count = NULL", [])?;
         
         Ok(())
     }
 
+    /// Reset listening statistics (play and skip counts, last played, accumulated play time) of every
+    /// track, keeping the tracks themselves. Returns the number of tracks reset.
+    pub fn reset_statistics(db: &DatabaseConnection) -> Result<usize, anyhow::Error> {
+        Self::reset_statistics_where(db, "1", params![])
+    }
+
+    /// Reset listening statistics of a single track
+    pub fn reset_track_statistics(
+        db: &DatabaseConnection,
+        track_id: i64,
+    ) -> Result<usize, anyhow::Error> {
+        Self::reset_statistics_where(db, "id = ?1", params![track_id])
+    }
+
+    /// Reset listening statistics of every track on an album
+    pub fn reset_album_statistics(
+        db: &DatabaseConnection,
+        album_id: i64,
+    ) -> Result<usize, anyhow::Error> {
+        Self::reset_statistics_where(db, "album_id = ?1", params![album_id])
+    }
+
+    fn reset_statistics_where(
+        db: &DatabaseConnection,
+        condition: &str,
+        params: &[&dyn rusqlite::ToSql],
+    ) -> Result<usize, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let reset = conn.execute(
+            &format!(
+                "UPDATE tracks SET play_count = 0, skip_count = 0, last_played = NULL, play_time_seconds = 0 WHERE {}",
+                condition
+            ),
+            params,
+        )?;
+        
+        Ok(reset)
+    }
+
     /// Create a new queue
     pub fn create_queue(
         db: &DatabaseConnection,
@@ -597,9 +1075,11 @@ impl DbOperations {
         
         // Insert in batches to avoid stack overflow with huge track lists
         const BATCH_SIZE: usize = 500;
-        for chunk in track_ids.chunks(BATCH_SIZE) {
-            for (_index_in_chunk, track_id) in chunk.iter().enumerate() {
-                let global_index = track_ids.iter().position(|&id| id == *track_id).unwrap_or(0);
+        for (chunk_index, chunk) in track_ids.chunks(BATCH_SIZE).enumerate() {
+            for (index_in_chunk, track_id) in chunk.iter().enumerate() {
+                // The same track can be queued more than once, so positions come from the
+                // slice index rather than from looking the id up
+                let global_index = chunk_index * BATCH_SIZE + index_in_chunk;
                 tx.execute(
                     "INSERT INTO queue_tracks (queue_id, track_id, position) VALUES (?1, ?2, ?3)",
                     params![queue_id, track_id, global_index as i32],
@@ -644,44 +1124,16 @@ impl DbOperations {
         let conn = db.get_connection();
         let conn = conn.lock().unwrap();
         
-        let mut stmt = conn.prepare(
-            "SELECT t.id, t.file_path, t.title, t.artist, t.album, t.album_artist,
-                    t.year, t.track_number, t.disc_number, t.duration_ms, t.genre,
-                    t.file_size, t.file_format, t.bitrate, t.sample_rate,
-                    t.date_added, t.date_modified, t.play_count, t.last_played, t.file_hash,
-                    t.normalization_gain_db, t.play_time_seconds
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
              FROM tracks t
              INNER JOIN queue_tracks qt ON qt.track_id = t.id
              WHERE qt.queue_id = ?1
-             ORDER BY qt.position"
-        )?;
+             ORDER BY qt.position",
+            TRACK_COLUMNS
+        ))?;
         
-        let tracks = stmt.query_map([queue_id], |row| {
-            Ok(Track {
-                id: row.get(0)?,
-                file_path: row.get(1)?,
-                title: row.get(2)?,
-                artist: row.get(3)?,
-                album: row.get(4)?,
-                album_artist: row.get(5)?,
-                year: row.get(6)?,
-                track_number: row.get(7)?,
-                disc_number: row.get(8)?,
-                duration_ms: row.get(9)?,
-                genre: row.get(10)?,
-                file_size: row.get(11)?,
-                file_format: row.get(12)?,
-                bitrate: row.get(13)?,
-                sample_rate: row.get(14)?,
-                date_added: row.get(15)?,
-                date_modified: row.get(16)?,
-                play_count: row.get(17)?,
-                last_played: row.get(18)?,
-                file_hash: row.get(19)?,
-                normalization_gain_db: row.get(20)?,
-                play_time_seconds: row.get(21)?,
-            })
-        })?
+        let tracks = stmt.query_map([queue_id], row_to_track)?
         .collect::<Result<Vec<_>, _>>()?;
         
         Ok(tracks)
@@ -705,6 +1157,57 @@ impl DbOperations {
         Ok(None)
     }
 
+    /// Find the ad-hoc "play now" queue, if one has been created
+    pub fn find_ad_hoc_queue(
+        db: &DatabaseConnection,
+    ) -> Result<Option<i64>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let queue_id = conn.query_row(
+            "SELECT id FROM queues WHERE is_ad_hoc = 1 ORDER BY id LIMIT 1",
+            [],
+            |row| row.get(0),
+        ).optional()?;
+        
+        Ok(queue_id)
+    }
+
+    /// Put a single track into the ad-hoc "play now" queue and make it active.
+    /// The same queue is reused every time so one-off plays don't accumulate queues.
+    pub fn create_single_track_queue(
+        db: &DatabaseConnection,
+        track_id: i64,
+    ) -> Result<i64, anyhow::Error> {
+        let queue_id = match Self::find_ad_hoc_queue(db)? {
+            Some(queue_id) => {
+                Self::replace_queue_tracks(db, queue_id, &[track_id])?;
+                Self::set_active_queue(db, queue_id)?;
+                queue_id
+            }
+            None => {
+                // Queue names are unique; number the name if a user queue already has it
+                let mut name = AD_HOC_QUEUE_NAME.to_string();
+                let mut suffix = 2;
+                while Self::find_queue_by_name(db, &name)?.is_some() {
+                    name = format!("{} ({})", AD_HOC_QUEUE_NAME, suffix);
+                    suffix += 1;
+                }
+                // create_queue makes the new queue active at index 0
+                let queue_id = Self::create_queue(db, &name)?;
+                {
+                    let conn = db.get_connection();
+                    let conn = conn.lock().unwrap();
+                    conn.execute("UPDATE queues SET is_ad_hoc = 1 WHERE id = ?1", [queue_id])?;
+                }
+                Self::add_tracks_to_queue(db, queue_id, &[track_id])?;
+                queue_id
+            }
+        };
+        
+        Ok(queue_id)
+    }
+
     /// Replace all tracks in a queue with new tracks
     pub fn replace_queue_tracks(
         db: &DatabaseConnection,
@@ -799,6 +1302,76 @@ impl DbOperations {
         Ok(())
     }
 
+    /// Remove every track from a queue, keeping the queue itself (name, active flag, shuffle
+    /// and repeat settings). Playback position goes back to the start.
+    pub fn clear_queue(
+        db: &DatabaseConnection,
+        queue_id: i64,
+    ) -> Result<(), anyhow::Error> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs() as i64;
+        
+        let conn = db.get_connection();
+        let mut conn = conn.lock().unwrap();
+        
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM queue_tracks WHERE queue_id = ?1", params![queue_id])?;
+        // A shuffled queue keeps an (empty) original order so tracks added later are tracked
+        let updated = tx.execute(
+            "UPDATE queues SET
+                current_track_index = 0,
+                current_position_ms = 0,
+                original_order = CASE WHEN original_order IS NULL THEN NULL ELSE '[]' END,
+                date_modified = ?1
+             WHERE id = ?2",
+            params![now, queue_id],
+        )?;
+        if updated == 0 {
+            return Err(AppError::NotFound(format!("Queue not found: {}", queue_id)).into());
+        }
+        tx.commit()?;
+        
+        Ok(())
+    }
+
+    /// Delete the oldest inactive queues, keeping the `keep` most recently modified ones.
+    /// The active queue is never deleted. Returns the number of queues removed.
+    pub fn prune_old_queues(
+        db: &DatabaseConnection,
+        keep: usize,
+    ) -> Result<usize, anyhow::Error> {
+        let conn = db.get_connection();
+        let mut conn = conn.lock().unwrap();
+        
+        let tx = conn.transaction()?;
+        
+        let stale_ids: Vec<i64> = {
+            let mut stmt = tx.prepare(
+                "SELECT id FROM queues
+                 WHERE is_active = 0
+                 ORDER BY date_modified DESC, id DESC
+                 LIMIT -1 OFFSET ?1"
+            )?;
+            let ids = stmt.query_map([keep as i64], |row| row.get(0))?
+                .collect::<Result<Vec<_>, _>>()?;
+            ids
+        };
+        
+        for queue_id in &stale_ids {
+            tx.execute("DELETE FROM queue_tracks WHERE queue_id = ?1", params![queue_id])?;
+            tx.execute("DELETE FROM queues WHERE id = ?1", params![queue_id])?;
+        }
+        
+        tx.commit()?;
+        
+        if !stale_ids.is_empty() {
+            println!("[Backend] Pruned {} old queues", stale_ids.len());
+        }
+        
+        Ok(stale_ids.len())
+    }
+
     /// Update current track index in queue
     pub fn update_queue_current_index(
         db: &DatabaseConnection,
@@ -808,14 +1381,75 @@ impl DbOperations {
         let conn = db.get_connection();
         let conn = conn.lock().unwrap();
         
+        // The saved position belongs to the previous track
         conn.execute(
-            "UPDATE queues SET current_track_index = ?1 WHERE id = ?2",
+            "UPDATE queues SET
+                current_position_ms = CASE WHEN current_track_index = ?1 THEN current_position_ms ELSE 0 END,
+                current_track_index = ?1
+             WHERE id = ?2",
             params![track_index, queue_id],
         )?;
         
         Ok(())
     }
 
+    /// Save the playback position within the queue's current track
+    pub fn update_queue_position(
+        db: &DatabaseConnection,
+        queue_id: i64,
+        position_ms: i64,
+    ) -> Result<(), anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        conn.execute(
+            "UPDATE queues SET current_position_ms = ?1 WHERE id = ?2",
+            params![position_ms.max(0), queue_id],
+        )?;
+        
+        Ok(())
+    }
+
+    /// Active queue with its current track, position and shuffle/repeat state, for
+    /// restoring the last session. `volume_db` comes from the playback settings.
+    pub fn get_session_state(
+        db: &DatabaseConnection,
+        volume_db: f32,
+    ) -> Result<SessionState, anyhow::Error> {
+        let Some(queue) = Self::get_active_queue(db)? else {
+            return Ok(SessionState {
+                queue: None,
+                current_track_index: 0,
+                current_position_ms: 0,
+                shuffle_seed: 1,
+                shuffle_anchor: 0,
+                repeat_mode: RepeatMode::Off,
+                volume_db,
+            });
+        };
+        
+        let modes = Self::get_queue_playback_modes(db, queue.id)?;
+        let (current_track_index, current_position_ms): (i32, i64) = {
+            let conn = db.get_connection();
+            let conn = conn.lock().unwrap();
+            conn.query_row(
+                "SELECT current_track_index, current_position_ms FROM queues WHERE id = ?1",
+                params![queue.id],
+                |row| Ok((row.get(0)?, row.get(1)?))
+            )?
+        };
+        
+        Ok(SessionState {
+            queue: Some(queue),
+            current_track_index,
+            current_position_ms,
+            shuffle_seed: modes.shuffle_seed,
+            shuffle_anchor: modes.shuffle_anchor,
+            repeat_mode: modes.repeat_mode,
+            volume_db,
+        })
+    }
+
     /// Get current track index from queue
     pub fn get_queue_current_index(
         db: &DatabaseConnection,
@@ -833,20 +1467,57 @@ impl DbOperations {
         Ok(index)
     }
 
-    /// Get the next available queue (by ID order) excluding the given queue
-    pub fn get_next_queue(
+    /// Find the position of a track in a queue.
+    /// `occurrence` selects which copy to return when the track appears more than once (0 = first).
+    pub fn find_track_position_in_queue(
         db: &DatabaseConnection,
-        excluded_queue_id: i64,
-    ) -> Result<Option<crate::db::models::Queue>, anyhow::Error> {
+        queue_id: i64,
+        track_id: i64,
+        occurrence: u32,
+    ) -> Result<Option<i32>, anyhow::Error> {
         let conn = db.get_connection();
         let conn = conn.lock().unwrap();
         
-        let mut stmt = conn.prepare(
-            "SELECT id, name, is_active, shuffle_seed, original_order
-             FROM queues
-             WHERE id != ?1
-             ORDER BY id DESC
-             LIMIT 1"
+        let position: Option<i32> = conn.query_row(
+            "SELECT position FROM queue_tracks
+             WHERE queue_id = ?1 AND track_id = ?2
+             ORDER BY position
+             LIMIT 1 OFFSET ?3",
+            params![queue_id, track_id, occurrence],
+            |row| row.get(0)
+        ).optional()?;
+        
+        Ok(position)
+    }
+
+    /// Jump to a position in a queue: makes it the current track and returns the track to play
+    pub fn queue_jump_to(
+        db: &DatabaseConnection,
+        queue_id: i64,
+        position: i32,
+    ) -> Result<Track, anyhow::Error> {
+        let track = Self::get_queue_track_at_position(db, queue_id, position)?
+            .ok_or_else(|| AppError::NotFound(format!("No track at position {} in queue {}", position, queue_id)))?;
+        
+        Self::update_queue_current_index(db, queue_id, position)?;
+        
+        Ok(track)
+    }
+
+    /// Get the next available queue (by ID order) excluding the given queue
+    pub fn get_next_queue(
+        db: &DatabaseConnection,
+        excluded_queue_id: i64,
+    ) -> Result<Option<crate::db::models::Queue>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let mut stmt = conn.prepare(
+            "SELECT id, name, is_active, shuffle_seed, original_order
+             FROM queues
+             WHERE id != ?1
+             ORDER BY id DESC
+             LIMIT 1"
         )?;
         
         let queue = stmt.query_row([excluded_queue_id], |row| {
@@ -871,162 +1542,71 @@ impl DbOperations {
         let conn = db.get_connection();
         let conn = conn.lock().unwrap();
         
-        let mut stmt = conn.prepare(
-            "SELECT t.id, t.file_path, t.title, t.artist, t.album, t.album_artist,
-                    t.year, t.track_number, t.disc_number, t.duration_ms, t.genre,
-                    t.file_size, t.file_format, t.bitrate, t.sample_rate,
-                    t.date_added, t.date_modified, t.play_count, t.last_played, t.file_hash,
-                    t.normalization_gain_db, t.play_time_seconds
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
              FROM tracks t
              INNER JOIN queue_tracks qt ON qt.track_id = t.id
-             WHERE qt.queue_id = ?1 AND qt.position = ?2"
-        )?;
+             WHERE qt.queue_id = ?1 AND qt.position = ?2",
+            TRACK_COLUMNS
+        ))?;
         
-        let track = stmt.query_row(params![queue_id, position], |row| {
-            Ok(Track {
-                id: row.get(0)?,
-                file_path: row.get(1)?,
-                title: row.get(2)?,
-                artist: row.get(3)?,
-                album: row.get(4)?,
-                album_artist: row.get(5)?,
-                year: row.get(6)?,
-                track_number: row.get(7)?,
-                disc_number: row.get(8)?,
-                duration_ms: row.get(9)?,
-                genre: row.get(10)?,
-                file_size: row.get(11)?,
-                file_format: row.get(12)?,
-                bitrate: row.get(13)?,
-                sample_rate: row.get(14)?,
-                date_added: row.get(15)?,
-                date_modified: row.get(16)?,
-                play_count: row.get(17)?,
-                last_played: row.get(18)?,
-                file_hash: row.get(19)?,
-                normalization_gain_db: row.get(20)?,
-                play_time_seconds: row.get(21)?,
-            })
-        }).optional()?;
+        let track = stmt.query_row(params![queue_id, position], row_to_track).optional()?;
         
         Ok(track)
     }
 
-    /// Get track at a shuffled position in the queue
-    /// This applies the shuffle algorithm to map the shuffled position to the original position
-    /// The shuffle pattern is anchored at the anchor_position (typically the currently playing track)
+    /// Get the track at a position in the shuffled play order. toggle_queue_shuffle
+    /// reorders queue_tracks itself, so the play order is the queue order whether or not
+    /// the queue is shuffled, and the current track is at position 0 right after shuffling.
+    /// The seed and anchor are accepted for existing callers but aren't needed.
     pub fn get_queue_track_at_shuffled_position(
         db: &DatabaseConnection,
         queue_id: i64,
         shuffled_position: i32,
-        shuffle_seed: i64,
-        anchor_position: i32,
+        _shuffle_seed: i64,
+        _anchor_position: i32,
     ) -> Result<Option<Track>, anyhow::Error> {
-        println!("[Backend] get_queue_track_at_shuffled_position called with queue_id={}, shuffled_position={}, shuffle_seed={}, anchor_position={}", 
-            queue_id, shuffled_position, shuffle_seed, anchor_position);
-        
-        // If seed is 1 (sequential), just use the regular position
-        if shuffle_seed == 1 {
-            println!("[Backend] Seed is 1, using sequential order");
-            return Self::get_queue_track_at_position(db, queue_id, shuffled_position);
-        }
-
-        // Get the queue length
-        let queue_length = Self::get_queue_length(db, queue_id)?;
-        println!("[Backend] Queue length: {}", queue_length);
-        
-        if queue_length == 0 {
-            return Ok(None);
-        }
+        Self::get_queue_track_at_position(db, queue_id, shuffled_position)
+    }
 
-        // If this is the anchor position, return the track at anchor position
-        if shuffled_position == anchor_position {
-            println!("[Backend] Shuffled position equals anchor, returning track at position {}", anchor_position);
-            return Self::get_queue_track_at_position(db, queue_id, anchor_position);
-        }
+    // ===== System Playlists =====
 
-        // Generate the shuffled order starting from anchor position
-        let step = (shuffle_seed.abs() % queue_length as i64) as i32;
-        let step = if step == 0 { 1 } else { step };
-        println!("[Backend] step={}, seed.abs()={}, queue_length={}", step, shuffle_seed.abs(), queue_length);
+    /// Get tracks sorted by recently added
+    pub fn get_recent_tracks(
+        db: &DatabaseConnection,
+    ) -> Result<Vec<Track>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
         
-        let mut current_pos = anchor_position; // Start from anchor
-        let mut used = std::collections::HashSet::new();
-        used.insert(anchor_position); // Anchor is always at its own position
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM tracks t
+             ORDER BY date_added DESC",
+            TRACK_COLUMNS
+        ))?;
         
-        // Generate shuffled order until we reach the desired shuffled position
-        for i in 0..queue_length {
-            // If we're at the anchor position in the shuffled order, skip it
-            if i == anchor_position {
-                continue;
-            }
-            
-            // Find next unused position using step pattern
-            current_pos = (current_pos + step) % queue_length;
-            while used.contains(&current_pos) {
-                current_pos = (current_pos + 1) % queue_length;
-            }
-            
-            // Check if this is the shuffled position we're looking for
-            if i == shuffled_position {
-                // current_pos is the original position for this shuffled position
-                println!("[Backend] Found: shuffled position {} maps to original position {}", shuffled_position, current_pos);
-                return Self::get_queue_track_at_position(db, queue_id, current_pos);
-            }
-            
-            used.insert(current_pos);
-        }
+        let tracks = stmt.query_map([], row_to_track)?
+        .collect::<Result<Vec<_>, _>>()?;
         
-        // Shouldn't reach here, but return None as fallback
-        println!("[Backend] WARNING: Did not find mapping for shuffled position {}", shuffled_position);
-        Ok(None)
+        Ok(tracks)
     }
 
-    // ===== System Playlists =====
-
-    /// Get tracks sorted by recently added
-    pub fn get_recent_tracks(
+    /// Get favorite ("loved") tracks, highest rated first
+    pub fn get_favorite_tracks(
         db: &DatabaseConnection,
     ) -> Result<Vec<Track>, anyhow::Error> {
         let conn = db.get_connection();
         let conn = conn.lock().unwrap();
         
-        let mut stmt = conn.prepare(
-            "SELECT id, file_path, title, artist, album, album_artist,
-                    year, track_number, disc_number, duration_ms, genre,
-                    file_size, file_format, bitrate, sample_rate,
-                    date_added, date_modified, play_count, last_played, file_hash,
-                    normalization_gain_db, play_time_seconds
-             FROM tracks
-             ORDER BY date_added DESC"
-        )?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM tracks t
+             WHERE is_favorite = 1
+             ORDER BY rating DESC, date_added DESC",
+            TRACK_COLUMNS
+        ))?;
         
-        let tracks = stmt.query_map([], |row| {
-            Ok(Track {
-                id: row.get(0)?,
-                file_path: row.get(1)?,
-                title: row.get(2)?,
-                artist: row.get(3)?,
-                album: row.get(4)?,
-                album_artist: row.get(5)?,
-                year: row.get(6)?,
-                track_number: row.get(7)?,
-                disc_number: row.get(8)?,
-                duration_ms: row.get(9)?,
-                genre: row.get(10)?,
-                file_size: row.get(11)?,
-                file_format: row.get(12)?,
-                bitrate: row.get(13)?,
-                sample_rate: row.get(14)?,
-                date_added: row.get(15)?,
-                date_modified: row.get(16)?,
-                play_count: row.get(17)?,
-                last_played: row.get(18)?,
-                file_hash: row.get(19)?,
-                normalization_gain_db: row.get(20)?,
-                play_time_seconds: row.get(21)?,
-            })
-        })?
+        let tracks = stmt.query_map([], row_to_track)?
         .collect::<Result<Vec<_>, _>>()?;
         
         Ok(tracks)
@@ -1039,45 +1619,107 @@ impl DbOperations {
         let conn = db.get_connection();
         let conn = conn.lock().unwrap();
         
-        let mut stmt = conn.prepare(
-            "SELECT id, file_path, title, artist, album, album_artist,
-                    year, track_number, disc_number, duration_ms, genre,
-                    file_size, file_format, bitrate, sample_rate,
-                    date_added, date_modified, play_count, last_played, file_hash,
-                    normalization_gain_db, play_time_seconds
-             FROM tracks
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM tracks t
              WHERE play_time_seconds > 0
-             ORDER BY play_time_seconds DESC, last_played DESC"
+             ORDER BY play_time_seconds DESC, last_played DESC",
+            TRACK_COLUMNS
+        ))?;
+        
+        let tracks = stmt.query_map([], row_to_track)?
+        .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(tracks)
+    }
+
+    /// Tracks that are mostly skipped: at least `NEVER_FINISHED_MIN_SKIPS` skips and more
+    /// skips than plays, the most lopsided first
+    pub fn get_never_finished_tracks(
+        db: &DatabaseConnection,
+    ) -> Result<Vec<Track>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM tracks t
+             WHERE skip_count >= ?1 AND skip_count > play_count
+             ORDER BY skip_count - play_count DESC, skip_count DESC, id ASC",
+            TRACK_COLUMNS
+        ))?;
+        
+        let tracks = stmt.query_map([NEVER_FINISHED_MIN_SKIPS], row_to_track)?
+        .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(tracks)
+    }
+
+    /// Get albums containing the most recently played tracks, most recent first.
+    /// Album IDs match the ones returned by get_all_albums.
+    pub fn get_recently_played_albums(
+        db: &DatabaseConnection,
+        limit: usize,
+    ) -> Result<Vec<Album>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let mut stmt = conn.prepare(
+            "SELECT a.id, a.name, a.artist,
+                    MIN(t.year) as year,
+                    COUNT(t.id) as song_count,
+                    a.is_compilation
+             FROM albums a
+             JOIN tracks t ON t.album_id = a.id
+             GROUP BY a.id
+             HAVING MAX(t.last_played) IS NOT NULL
+             ORDER BY MAX(t.last_played) DESC
+             LIMIT ?1"
         )?;
         
-        let tracks = stmt.query_map([], |row| {
-            Ok(Track {
+        let albums = stmt.query_map([limit as i64], |row| {
+            Ok(Album {
                 id: row.get(0)?,
-                file_path: row.get(1)?,
-                title: row.get(2)?,
-                artist: row.get(3)?,
-                album: row.get(4)?,
-                album_artist: row.get(5)?,
-                year: row.get(6)?,
-                track_number: row.get(7)?,
-                disc_number: row.get(8)?,
-                duration_ms: row.get(9)?,
-                genre: row.get(10)?,
-                file_size: row.get(11)?,
-                file_format: row.get(12)?,
-                bitrate: row.get(13)?,
-                sample_rate: row.get(14)?,
-                date_added: row.get(15)?,
-                date_modified: row.get(16)?,
-                play_count: row.get(17)?,
-                last_played: row.get(18)?,
-                file_hash: row.get(19)?,
-                normalization_gain_db: row.get(20)?,
-                play_time_seconds: row.get(21)?,
+                name: row.get(1)?,
+                artist: row.get(2)?,
+                year: row.get(3)?,
+                song_count: row.get(4)?,
+                is_compilation: row.get(5)?,
             })
         })?
         .collect::<Result<Vec<_>, _>>()?;
         
+        Ok(albums)
+    }
+
+    /// Get tracks last played on this month/day in previous years ("on this day").
+    /// Dates are compared in local time, matching what the user saw when playing.
+    pub fn get_tracks_played_on_day(
+        db: &DatabaseConnection,
+        month: u32,
+        day: u32,
+    ) -> Result<Vec<Track>, anyhow::Error> {
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return Err(AppError::InvalidInput(format!("Invalid date: {}/{}", month, day)).into());
+        }
+        
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM tracks t
+             WHERE last_played IS NOT NULL
+               AND CAST(strftime('%m', last_played, 'unixepoch', 'localtime') AS INTEGER) = ?1
+               AND CAST(strftime('%d', last_played, 'unixepoch', 'localtime') AS INTEGER) = ?2
+               AND strftime('%Y', last_played, 'unixepoch', 'localtime') < strftime('%Y', 'now', 'localtime')
+             ORDER BY last_played DESC",
+            TRACK_COLUMNS
+        ))?;
+        
+        let tracks = stmt.query_map(params![month, day], row_to_track)?
+        .collect::<Result<Vec<_>, _>>()?;
+        
         Ok(tracks)
     }
 
@@ -1088,43 +1730,15 @@ impl DbOperations {
         let conn = db.get_connection();
         let conn = conn.lock().unwrap();
         
-        let mut stmt = conn.prepare(
-            "SELECT id, file_path, title, artist, album, album_artist,
-                    year, track_number, disc_number, duration_ms, genre,
-                    file_size, file_format, bitrate, sample_rate,
-                    date_added, date_modified, play_count, last_played, file_hash,
-                    normalization_gain_db, play_time_seconds
-             FROM tracks
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM tracks t
              WHERE play_time_seconds = 0
-             ORDER BY date_added DESC"
-        )?;
+             ORDER BY date_added DESC",
+            TRACK_COLUMNS
+        ))?;
         
-        let tracks = stmt.query_map([], |row| {
-            Ok(Track {
-                id: row.get(0)?,
-                file_path: row.get(1)?,
-                title: row.get(2)?,
-                artist: row.get(3)?,
-                album: row.get(4)?,
-                album_artist: row.get(5)?,
-                year: row.get(6)?,
-                track_number: row.get(7)?,
-                disc_number: row.get(8)?,
-                duration_ms: row.get(9)?,
-                genre: row.get(10)?,
-                file_size: row.get(11)?,
-                file_format: row.get(12)?,
-                bitrate: row.get(13)?,
-                sample_rate: row.get(14)?,
-                date_added: row.get(15)?,
-                date_modified: row.get(16)?,
-                play_count: row.get(17)?,
-                last_played: row.get(18)?,
-                file_hash: row.get(19)?,
-                normalization_gain_db: row.get(20)?,
-                play_time_seconds: row.get(21)?,
-            })
-        })?
+        let tracks = stmt.query_map([], row_to_track)?
         .collect::<Result<Vec<_>, _>>()?;
         
         Ok(tracks)
@@ -1152,6 +1766,164 @@ impl DbOperations {
         Ok(())
     }
 
+    /// Add to a track's play time without counting another play, for a listen that
+    /// increment_play_count already counted
+    pub fn add_play_time(
+        db: &DatabaseConnection,
+        track_id: i64,
+        duration_seconds: i64,
+    ) -> Result<(), anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        conn.execute(
+            "UPDATE tracks SET play_time_seconds = COALESCE(play_time_seconds, 0) + ?1 WHERE id = ?2",
+            rusqlite::params![duration_seconds, track_id],
+        )?;
+        
+        Ok(())
+    }
+
+    /// Count a play of a track without adding play time: bumps play_count and sets
+    /// last_played to now. A single UPDATE, so concurrent calls can't lose counts.
+    /// Returns false if the track doesn't exist.
+    pub fn increment_play_count(
+        db: &DatabaseConnection,
+        track_id: i64,
+    ) -> Result<bool, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs() as i64;
+        
+        let updated = conn.execute(
+            "UPDATE tracks SET play_count = play_count + 1, last_played = ?1 WHERE id = ?2",
+            rusqlite::params![now, track_id],
+        )?;
+        
+        Ok(updated > 0)
+    }
+
+    /// Count a skip of a track, i.e. it was left before it counted as played.
+    /// Returns false if the track doesn't exist.
+    pub fn increment_skip_count(
+        db: &DatabaseConnection,
+        track_id: i64,
+    ) -> Result<bool, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let updated = conn.execute(
+            "UPDATE tracks SET skip_count = skip_count + 1 WHERE id = ?1",
+            rusqlite::params![track_id],
+        )?;
+        
+        Ok(updated > 0)
+    }
+
+    /// Set a track's star rating, 0 (unrated) to 5
+    pub fn set_track_rating(
+        db: &DatabaseConnection,
+        track_id: i64,
+        rating: i32,
+    ) -> Result<(), anyhow::Error> {
+        if !(0..=5).contains(&rating) {
+            return Err(AppError::InvalidInput(format!("Invalid rating: {} (must be 0-5)", rating)).into());
+        }
+        
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let updated = conn.execute(
+            "UPDATE tracks SET rating = ?1 WHERE id = ?2",
+            rusqlite::params![rating, track_id],
+        )?;
+        if updated == 0 {
+            return Err(anyhow::anyhow!("Track not found: {}", track_id));
+        }
+        
+        Ok(())
+    }
+
+    /// Flip a track's favorite flag, returns the new state
+    pub fn toggle_favorite(
+        db: &DatabaseConnection,
+        track_id: i64,
+    ) -> Result<bool, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let is_favorite: Option<bool> = conn.query_row(
+            "UPDATE tracks SET is_favorite = 1 - is_favorite WHERE id = ?1 RETURNING is_favorite",
+            [track_id],
+            |row| row.get(0),
+        ).optional()?;
+        
+        is_favorite.ok_or_else(|| anyhow::anyhow!("Track not found: {}", track_id))
+    }
+
+    /// Get the total and remaining duration of a queue in one aggregate query.
+    /// Queue positions are the play order (shuffling reorders them), so the remaining time
+    /// covers the current track and everything after it.
+    pub fn get_queue_duration(
+        db: &DatabaseConnection,
+        queue_id: i64,
+    ) -> Result<QueueDuration, anyhow::Error> {
+        Self::queue_duration_from(db, queue_id, None)
+    }
+
+    /// get_queue_duration with the remaining time counted from `from_position` instead of
+    /// the current track
+    fn queue_duration_from(
+        db: &DatabaseConnection,
+        queue_id: i64,
+        from_position: Option<i32>,
+    ) -> Result<QueueDuration, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let (total_ms, remaining_ms, unknown_count): (i64, i64, i64) = conn.query_row(
+            "SELECT COALESCE(SUM(t.duration_ms), 0),
+                    COALESCE(SUM(CASE WHEN qt.position >= COALESCE(?2, q.current_track_index) THEN t.duration_ms END), 0),
+                    COUNT(qt.track_id) - COUNT(t.duration_ms)
+             FROM queues q
+             LEFT JOIN queue_tracks qt ON qt.queue_id = q.id
+             LEFT JOIN tracks t ON t.id = qt.track_id
+             WHERE q.id = ?1",
+            params![queue_id, from_position],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        )?;
+        
+        Ok(QueueDuration {
+            total_ms,
+            remaining_ms,
+            is_approximate: unknown_count > 0,
+        })
+    }
+
+    /// Get the summed duration of every track in a queue. Tracks without a known
+    /// duration count as 0.
+    pub fn get_queue_total_duration_ms(
+        db: &DatabaseConnection,
+        queue_id: i64,
+    ) -> Result<i64, anyhow::Error> {
+        Ok(Self::get_queue_duration(db, queue_id)?.total_ms)
+    }
+
+    /// Get the summed duration of the queue tracks at `from_position` and after, NULL
+    /// durations counting as 0. Positions are the play order, which toggle_queue_shuffle
+    /// rewrites, so this is the time left while shuffled too. With repeat on the queue
+    /// doesn't end, so it's only the time until the current pass is through.
+    pub fn get_queue_remaining_duration_ms(
+        db: &DatabaseConnection,
+        queue_id: i64,
+        from_position: i32,
+    ) -> Result<i64, anyhow::Error> {
+        Ok(Self::queue_duration_from(db, queue_id, Some(from_position))?.remaining_ms)
+    }
+
     /// Get the number of tracks in a queue
     pub fn get_queue_length(
         db: &DatabaseConnection,
@@ -1171,21 +1943,23 @@ impl DbOperations {
 
     /// Toggle shuffle for a queue using the original_order approach
     /// When enabling shuffle:
-    ///   - Save current track order to original_order
-    ///   - Shuffle the queue_tracks positions in place
-    ///   - Set shuffle_seed to a non-1 value to indicate shuffled state
+    /// - Save current track order to original_order
+    /// - Shuffle the queue_tracks positions in place, in the shuffled_permutation order for
+    ///   the new seed anchored at the current track (the first track if there is none)
+    /// - Set shuffle_seed to a non-1 value to indicate shuffled state, and shuffle_anchor
+    ///   to the anchor, so find_shuffled_position agrees with the stored order
+    ///
     /// When disabling shuffle:
-    ///   - Restore track positions from original_order
-    ///   - Clear original_order
-    ///   - Set shuffle_seed to 1
+    /// - Restore track positions from original_order
+    /// - Clear original_order
+    /// - Set shuffle_seed to 1
+    ///
     /// Returns (new_seed, new_current_track_index)
     pub fn toggle_queue_shuffle(
         db: &DatabaseConnection,
         queue_id: i64,
         current_track_id: Option<i64>,
     ) -> Result<(i64, i32), anyhow::Error> {
-        use rand::seq::SliceRandom;
-        use rand::SeedableRng;
         use std::time::{SystemTime, UNIX_EPOCH};
         
         let conn = db.get_connection();
@@ -1206,53 +1980,48 @@ impl DbOperations {
         if !is_currently_shuffled {
             // Enable shuffle: save original order, then shuffle
             
-            // Get current track IDs in order
-            let mut stmt = tx.prepare(
-                "SELECT track_id FROM queue_tracks WHERE queue_id = ?1 ORDER BY position"
-            )?;
-            let track_ids: Vec<i64> = stmt.query_map([queue_id], |row| row.get(0))?
-                .collect::<Result<Vec<_>, _>>()?;
-            drop(stmt);
+            // Get current queue rows in order
+            let rows = queue_rows_in_order(&tx, queue_id)?;
             
-            if track_ids.is_empty() {
+            if rows.is_empty() {
                 return Ok((1, 0));
             }
             
             // Save original order as JSON
+            let track_ids: Vec<i64> = rows.iter().map(|&(_, track_id)| track_id).collect();
             let original_order_json = serde_json::to_string(&track_ids)?;
             
-            // Create shuffled order, keeping current track at its position
-            let mut shuffled_ids = track_ids.clone();
+            let current_index: i32 = tx.query_row(
+                "SELECT current_track_index FROM queues WHERE id = ?1",
+                [queue_id],
+                |row| row.get(0)
+            )?;
+            let current_row = current_queue_row(&rows, current_index, current_track_id);
+            
+            // Generate a new seed (just needs to be != 1 to indicate shuffled)
             let seed_value = SystemTime::now()
                 .duration_since(UNIX_EPOCH)
                 .unwrap()
                 .as_nanos() as u64;
-            let mut rng = rand::rngs::StdRng::seed_from_u64(seed_value);
+            let new_seed = (seed_value % 1000000) as i64 + 2; // Ensure it's never 1
             
-            // Remove the current track, shuffle the rest, then put current track at position 0
-            if let Some(track_id) = current_track_id {
-                shuffled_ids.retain(|&id| id != track_id);
-                shuffled_ids.shuffle(&mut rng);
-                shuffled_ids.insert(0, track_id); // Current track goes to front
-            } else {
-                shuffled_ids.shuffle(&mut rng);
-            }
+            // Shuffle the queue_tracks rows, so each copy of a repeated track keeps its own
+            // position. The anchor (current track) goes to position 0.
+            let anchor = current_row.unwrap_or(0) as i32;
+            let permutation = shuffled_permutation(new_seed, rows.len() as i32, anchor);
             
             // Update positions in database
-            for (new_pos, track_id) in shuffled_ids.iter().enumerate() {
+            for (new_pos, &original_pos) in permutation.iter().enumerate() {
                 tx.execute(
-                    "UPDATE queue_tracks SET position = ?1 WHERE queue_id = ?2 AND track_id = ?3",
-                    rusqlite::params![new_pos as i32, queue_id, track_id]
+                    "UPDATE queue_tracks SET position = ?1 WHERE id = ?2",
+                    rusqlite::params![new_pos as i32, rows[original_pos as usize].0]
                 )?;
             }
             
-            // Generate a new seed (just needs to be != 1 to indicate shuffled)
-            let new_seed = (seed_value % 1000000) as i64 + 2; // Ensure it's never 1
-            
-            // Update queue with new seed, original_order, and current_track_index = 0 (since current track is now at front)
+            // Update queue with new seed, anchor, original_order, and current_track_index = 0 (since current track is now at front)
             tx.execute(
-                "UPDATE queues SET shuffle_seed = ?1, original_order = ?2, current_track_index = 0 WHERE id = ?3",
-                rusqlite::params![new_seed, original_order_json, queue_id]
+                "UPDATE queues SET shuffle_seed = ?1, shuffle_anchor = ?2, original_order = ?3, current_track_index = 0 WHERE id = ?4",
+                rusqlite::params![new_seed, anchor, original_order_json, queue_id]
             )?;
             
             tx.commit()?;
@@ -1264,24 +2033,61 @@ impl DbOperations {
             if let Some(original_order_json) = original_order_json {
                 let original_ids: Vec<i64> = serde_json::from_str(&original_order_json)?;
                 
-                // Find where the current track will be in the restored order
-                let new_current_index = if let Some(track_id) = current_track_id {
-                    original_ids.iter().position(|&id| id == track_id).unwrap_or(0) as i32
-                } else {
-                    0
-                };
+                let rows = queue_rows_in_order(&tx, queue_id)?;
+                let current_index: i32 = tx.query_row(
+                    "SELECT current_track_index FROM queues WHERE id = ?1",
+                    [queue_id],
+                    |row| row.get(0)
+                )?;
+                let current_row = current_queue_row(&rows, current_index, current_track_id)
+                    .map(|index| rows[index].0);
+                
+                // original_order holds track IDs, so copies of a repeated track are handed
+                // its occurrences in turn, the current row taking the first one
+                let mut rows_by_track: std::collections::HashMap<i64, std::collections::VecDeque<i64>> =
+                    std::collections::HashMap::new();
+                for &(row_id, track_id) in &rows {
+                    let copies = rows_by_track.entry(track_id).or_default();
+                    if Some(row_id) == current_row {
+                        copies.push_front(row_id);
+                    } else {
+                        copies.push_back(row_id);
+                    }
+                }
                 
                 // Restore positions from original order
-                for (pos, track_id) in original_ids.iter().enumerate() {
+                let mut new_current_index = 0;
+                let mut restored = std::collections::HashSet::new();
+                for track_id in &original_ids {
+                    let Some(row_id) = rows_by_track.get_mut(track_id).and_then(|copies| copies.pop_front()) else {
+                        continue;
+                    };
+                    let pos = restored.len() as i32;
                     tx.execute(
-                        "UPDATE queue_tracks SET position = ?1 WHERE queue_id = ?2 AND track_id = ?3",
-                        rusqlite::params![pos as i32, queue_id, track_id]
+                        "UPDATE queue_tracks SET position = ?1 WHERE id = ?2",
+                        rusqlite::params![pos, row_id]
                     )?;
+                    if Some(row_id) == current_row {
+                        new_current_index = pos;
+                    }
+                    restored.insert(row_id);
+                }
+                
+                // Rows missing from original_order go after the restored ones
+                let mut pos = restored.len() as i32;
+                for &(row_id, _) in &rows {
+                    if !restored.contains(&row_id) {
+                        tx.execute(
+                            "UPDATE queue_tracks SET position = ?1 WHERE id = ?2",
+                            rusqlite::params![pos, row_id]
+                        )?;
+                        pos += 1;
+                    }
                 }
                 
                 // Clear shuffle state
                 tx.execute(
-                    "UPDATE queues SET shuffle_seed = 1, original_order = NULL, current_track_index = ?1 WHERE id = ?2",
+                    "UPDATE queues SET shuffle_seed = 1, shuffle_anchor = 0, original_order = NULL, current_track_index = ?1 WHERE id = ?2",
                     rusqlite::params![new_current_index, queue_id]
                 )?;
                 
@@ -1307,6 +2113,22 @@ impl DbOperations {
         }
     }
 
+    /// Drop any shuffle state from a queue (seed back to 1, no saved original order)
+    pub fn reset_queue_shuffle(
+        db: &DatabaseConnection,
+        queue_id: i64,
+    ) -> Result<(), anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        conn.execute(
+            "UPDATE queues SET shuffle_seed = 1, shuffle_anchor = 0, original_order = NULL WHERE id = ?1",
+            [queue_id]
+        )?;
+        
+        Ok(())
+    }
+
     /// Set shuffle seed for a queue directly
     /// Used when creating a new queue that should inherit shuffle state from previous queue
     pub fn set_queue_shuffle_seed(
@@ -1377,15 +2199,137 @@ impl DbOperations {
         Ok(anchor.unwrap_or(0))
     }
 
-    /// Find what position an original track index ends up at after shuffling
-    /// This is needed when toggling shuffle to maintain the current track position
-    /// The shuffle pattern is anchored at the anchor_position (typically the currently playing track)
-    pub fn find_shuffled_position(
-        original_index: i32,
-        seed: i64,
-        queue_length: i32,
-        anchor_position: i32,
-    ) -> Result<i32, Box<dyn std::error::Error>> {
+    /// Get the shuffle and repeat state of a queue in one read
+    pub fn get_queue_playback_modes(
+        db: &DatabaseConnection,
+        queue_id: i64,
+    ) -> Result<QueuePlaybackModes, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let (seed, anchor, repeat_mode): (Option<i64>, Option<i64>, Option<i64>) = conn.query_row(
+            "SELECT shuffle_seed, shuffle_anchor, repeat_mode FROM queues WHERE id = ?1",
+            [queue_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        )?;
+        
+        let shuffle_seed = seed.unwrap_or(1);
+        
+        Ok(QueuePlaybackModes {
+            shuffle_on: shuffle_seed != 1,
+            shuffle_seed,
+            shuffle_anchor: anchor.unwrap_or(0),
+            repeat_mode: repeat_mode.map(RepeatMode::from_db).unwrap_or_default(),
+        })
+    }
+    
+    /// Set the shuffle and repeat state of a queue.
+    /// Turning shuffle on moves the current track to the front of the reshuffled queue
+    /// and records its original position as the shuffle anchor (see toggle_queue_shuffle).
+    pub fn set_queue_playback_modes(
+        db: &DatabaseConnection,
+        queue_id: i64,
+        shuffle_on: bool,
+        repeat_mode: RepeatMode,
+    ) -> Result<QueuePlaybackModes, anyhow::Error> {
+        let current = Self::get_queue_playback_modes(db, queue_id)?;
+        
+        if current.shuffle_on != shuffle_on {
+            let current_index = Self::get_queue_current_index(db, queue_id)?;
+            let current_track_id = Self::get_queue_track_at_position(db, queue_id, current_index)?
+                .map(|t| t.id);
+            
+            Self::toggle_queue_shuffle(db, queue_id, current_track_id)?;
+        }
+        
+        Self::set_queue_repeat_mode(db, queue_id, repeat_mode)?;
+        
+        Self::get_queue_playback_modes(db, queue_id)
+    }
+
+    /// Set the repeat mode of a queue
+    pub fn set_queue_repeat_mode(
+        db: &DatabaseConnection,
+        queue_id: i64,
+        repeat_mode: RepeatMode,
+    ) -> Result<(), anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        conn.execute(
+            "UPDATE queues SET repeat_mode = ?1 WHERE id = ?2",
+            rusqlite::params![repeat_mode.to_db(), queue_id]
+        )?;
+        
+        Ok(())
+    }
+
+    /// Get the repeat mode of a queue
+    pub fn get_queue_repeat_mode(
+        db: &DatabaseConnection,
+        queue_id: i64,
+    ) -> Result<RepeatMode, anyhow::Error> {
+        Ok(Self::get_queue_playback_modes(db, queue_id)?.repeat_mode)
+    }
+
+    /// Queue position to play after `current_index` under the queue's repeat mode. A shuffled
+    /// queue is stored in its shuffled order, so this is always the next row. None when
+    /// playback should stop at the end of the queue.
+    pub fn next_position(
+        db: &DatabaseConnection,
+        queue_id: i64,
+        current_index: i32,
+    ) -> Result<Option<i32>, anyhow::Error> {
+        let repeat_mode = Self::get_queue_repeat_mode(db, queue_id)?;
+        let queue_length = Self::get_queue_length(db, queue_id)?;
+        if queue_length <= 0 {
+            return Ok(None);
+        }
+        if repeat_mode == RepeatMode::One {
+            return Ok(Some(current_index.clamp(0, queue_length - 1)));
+        }
+        
+        if current_index + 1 < queue_length {
+            Ok(Some(current_index + 1))
+        } else if repeat_mode == RepeatMode::All {
+            Ok(Some(0))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Get the tracks of a queue in play order, annotated with whether each one is the
+    /// current track or has already been played. A shuffled queue is stored in its shuffled
+    /// order (see toggle_queue_shuffle), so everything before the current track was played.
+    pub fn get_queue_tracks_annotated(
+        db: &DatabaseConnection,
+        queue_id: i64,
+    ) -> Result<Vec<AnnotatedQueueTrack>, anyhow::Error> {
+        let tracks = Self::get_queue_tracks(db, queue_id)?;
+        let current_index = Self::get_queue_current_index(db, queue_id)?;
+        
+        Ok(tracks
+            .into_iter()
+            .enumerate()
+            .map(|(position, track)| AnnotatedQueueTrack {
+                track,
+                queue_position: position as i32,
+                is_current: position as i32 == current_index,
+                is_played: (position as i32) < current_index,
+            })
+            .collect())
+    }
+
+    /// Find what position an original track index ends up at after shuffling
+    /// This is needed when toggling shuffle to maintain the current track position
+    /// The shuffle pattern is anchored at the anchor_position (typically the currently playing track),
+    /// which toggle_queue_shuffle moves to position 0
+    pub fn find_shuffled_position(
+        original_index: i32,
+        seed: i64,
+        queue_length: i32,
+        anchor_position: i32,
+    ) -> Result<i32, Box<dyn std::error::Error>> {
         if queue_length <= 1 {
             return Ok(0);
         }
@@ -1395,42 +2339,11 @@ impl DbOperations {
             return Ok(original_index);
         }
         
-        // If this is the anchor position, it stays in place
-        if original_index == anchor_position {
-            return Ok(anchor_position);
-        }
-        
-        // Generate the same shuffled order starting from anchor position
-        let step = (seed.abs() % queue_length as i64) as i32;
-        let step = if step == 0 { 1 } else { step };
-        
-        let mut current_pos = anchor_position; // Start from anchor
-        let mut used = std::collections::HashSet::new();
-        used.insert(anchor_position); // Anchor is always at its own position
-        
-        // Generate shuffled order until we find the original index
-        for i in 0..queue_length {
-            // Skip the anchor position in the iteration
-            if i == anchor_position {
-                continue;
-            }
-            
-            // Find next unused position using step pattern
-            current_pos = (current_pos + step) % queue_length;
-            while used.contains(&current_pos) {
-                current_pos = (current_pos + 1) % queue_length;
-            }
-            
-            // Check if this is the position we're looking for
-            if current_pos == original_index {
-                return Ok(i);
-            }
-            
-            used.insert(current_pos);
-        }
-        
-        // Shouldn't reach here, but return original index as fallback
-        Ok(original_index)
+        // Out-of-range indexes have no shuffled position, keep them as they are
+        Ok(shuffled_position_map(seed, queue_length, anchor_position)
+            .get(original_index as usize)
+            .copied()
+            .unwrap_or(original_index))
     }
 
     // ===== Scan Path Management =====
@@ -1465,7 +2378,7 @@ impl DbOperations {
         let conn = conn.lock().unwrap();
         
         let mut stmt = conn.prepare(
-            "SELECT id, path, date_added, last_scanned FROM scan_paths ORDER BY path"
+            "SELECT id, path, date_added, last_scanned, file_count FROM scan_paths ORDER BY path"
         )?;
         
         let paths = stmt.query_map([], |row| {
@@ -1474,6 +2387,7 @@ impl DbOperations {
                 path: row.get(1)?,
                 date_added: row.get(2)?,
                 last_scanned: row.get(3)?,
+                file_count: row.get(4)?,
             })
         })?
         .collect::<Result<Vec<_>, _>>()?;
@@ -1517,6 +2431,67 @@ impl DbOperations {
         Ok(false)
     }
     
+    /// Scan path and track counts for first-run detection, in a single query
+    pub fn get_library_status(
+        db: &DatabaseConnection,
+    ) -> Result<LibraryStatus, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let status = conn.query_row(
+            "SELECT
+                (SELECT COUNT(*) FROM scan_paths),
+                (SELECT COUNT(*) FROM tracks),
+                (SELECT MAX(last_scanned) FROM scan_paths)",
+            [],
+            |row| Ok(LibraryStatus {
+                has_scan_paths: row.get::<_, i64>(0)? > 0,
+                track_count: row.get(1)?,
+                last_scan_at: row.get(2)?,
+            })
+        )?;
+        
+        Ok(status)
+    }
+    
+    /// Track, artist and album counts plus total playtime and size on disk
+    pub fn get_library_stats(
+        db: &DatabaseConnection,
+    ) -> Result<LibraryStats, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        // TOTAL() is 0.0 on an empty table and skips NULLs, unlike SUM()
+        let stats = conn.query_row(
+            &format!("SELECT
+                (SELECT COUNT(*) FROM tracks),
+                (SELECT COUNT(*) FROM (
+                    SELECT artist_id FROM track_artists
+                    UNION
+                    SELECT artist_id FROM track_album_artists
+                )),
+                (SELECT COUNT(*) FROM (
+                    SELECT 1 FROM tracks
+                    WHERE album IS NOT NULL
+                    GROUP BY album, {}
+                )),
+                (SELECT CAST(TOTAL(duration_ms) AS INTEGER) FROM tracks),
+                (SELECT CAST(TOTAL(file_size) AS INTEGER) FROM tracks)",
+                album_artist_key("tracks")
+            ),
+            [],
+            |row| Ok(LibraryStats {
+                track_count: row.get(0)?,
+                artist_count: row.get(1)?,
+                album_count: row.get(2)?,
+                total_duration_ms: row.get(3)?,
+                total_size_bytes: row.get(4)?,
+            })
+        )?;
+        
+        Ok(stats)
+    }
+    
     /// Update the last_scanned timestamp for a scan path
     pub fn update_scan_path_last_scanned(
         db: &DatabaseConnection,
@@ -1538,10 +2513,28 @@ impl DbOperations {
         
         Ok(())
     }
+    
+    /// Record how many audio files a scan found in a scan path, once its cleanup is done
+    pub fn set_scan_path_file_count(
+        db: &DatabaseConnection,
+        path_id: i64,
+        file_count: usize,
+    ) -> Result<(), anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        conn.execute(
+            "UPDATE scan_paths SET file_count = ?1 WHERE id = ?2",
+            params![file_count as i64, path_id],
+        )?;
+        
+        Ok(())
+    }
 
-    /// Remove tracks whose files no longer exist on disk
+    /// Remove tracks whose files no longer exist on disk. Setting `cancel` stops the pass early.
     pub fn remove_missing_files<F>(
         db: &DatabaseConnection,
+        cancel: &AtomicBool,
         mut progress_callback: F,
     ) -> Result<usize, anyhow::Error>
     where
@@ -1554,9 +2547,12 @@ impl DbOperations {
         let mut removed_count = 0;
         
         for (index, track) in all_tracks.iter().enumerate() {
+            if cancel.load(Ordering::SeqCst) {
+                break;
+            }
             progress_callback(index + 1, total);
             
-            let track_path = Path::new(&track.file_path);
+            let track_path = Path::new(audio_file_path(&track.file_path));
             
             // Check if file exists
             if !track_path.exists() {
@@ -1571,9 +2567,10 @@ impl DbOperations {
         Ok(removed_count)
     }
 
-    /// Remove tracks that are not within any scan path
+    /// Remove tracks that are not within any scan path. Setting `cancel` stops the pass early.
     pub fn remove_tracks_outside_scan_paths<F>(
         db: &DatabaseConnection,
+        cancel: &AtomicBool,
         mut progress_callback: F,
     ) -> Result<usize, anyhow::Error>
     where
@@ -1597,10 +2594,13 @@ impl DbOperations {
         let mut removed_count = 0;
         
         for (index, track) in all_tracks.iter().enumerate() {
+            if cancel.load(Ordering::SeqCst) {
+                break;
+            }
             // Report progress
             progress_callback(index + 1, total);
             
-            let track_path = Path::new(&track.file_path);
+            let track_path = Path::new(audio_file_path(&track.file_path));
             let track_canonical = track_path.canonicalize()
                 .unwrap_or_else(|_| track_path.to_path_buf());
             
@@ -1621,6 +2621,138 @@ impl DbOperations {
         Ok(removed_count)
     }
     
+    /// Find tracks inside one of the scanned `roots` whose file path is not in the given set
+    /// of paths found on disk. Tracks outside the roots (added by opening a file, say) are
+    /// left alone. This is a pure database diff - no filesystem access - so it's cheap to run
+    /// after a scan to decide whether any cleanup is needed.
+    pub fn find_tracks_not_in_paths(
+        db: &DatabaseConnection,
+        roots: &[String],
+        found_paths: &std::collections::HashSet<String>,
+    ) -> Result<Vec<i64>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let mut stale_ids = Vec::new();
+        for root in roots {
+            for (id, file_path) in Self::track_paths_in_directory(&conn, root)? {
+                // CUE tracks are found through their audio file
+                if !found_paths.contains(audio_file_path(&file_path)) {
+                    stale_ids.push(id);
+                }
+            }
+        }
+        // Scan paths can be nested
+        stale_ids.sort();
+        stale_ids.dedup();
+        
+        Ok(stale_ids)
+    }
+    
+    /// Tracks read from an audio file: the file itself, or the CUE tracks in it,
+    /// as (id, file_path)
+    pub fn get_tracks_for_audio_file(
+        db: &DatabaseConnection,
+        audio_path: &str,
+    ) -> Result<Vec<(i64, String)>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let mut stmt = conn.prepare(TRACKS_FOR_AUDIO_FILE_SQL)?;
+        let tracks = stmt.query_map([audio_path], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<(i64, String)>, _>>()?;
+        
+        // Other files can share the "<audio_path>#" prefix, so check the rest is a CUE range
+        Ok(tracks.into_iter().filter(|(_, path)| audio_file_path(path) == audio_path).collect())
+    }
+    
+    /// Find tracks inside a folder (at any depth), e.g. one that was deleted or moved away
+    pub fn find_tracks_in_folder(
+        db: &DatabaseConnection,
+        folder: &str,
+    ) -> Result<Vec<i64>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let tracks = Self::track_paths_in_directory(&conn, folder)?;
+        Ok(tracks.into_iter().map(|(id, _)| id).collect())
+    }
+    
+    /// Remove the given tracks in a single transaction
+    pub fn remove_tracks_by_ids(
+        db: &DatabaseConnection,
+        track_ids: &[i64],
+    ) -> Result<usize, anyhow::Error> {
+        let conn = db.get_connection();
+        let mut conn = conn.lock().unwrap();
+        
+        let tx = conn.transaction()?;
+        let mut removed = 0;
+        for track_id in track_ids {
+            removed += tx.execute("DELETE FROM tracks WHERE id = ?1", params![track_id])?;
+        }
+        tx.commit()?;
+        
+        Ok(removed)
+    }
+    
+    /// Remove one track from the library in a single transaction. Its queue and playlist
+    /// entries are removed with later entries shifted down, so positions stay contiguous.
+    /// Returns false if there was no such track.
+    pub fn delete_track(
+        db: &DatabaseConnection,
+        track_id: i64,
+    ) -> Result<bool, anyhow::Error> {
+        let conn = db.get_connection();
+        let mut conn = conn.lock().unwrap();
+        
+        let tx = conn.transaction()?;
+        
+        // Last position first, so the positions still to remove don't shift
+        let queue_entries: Vec<(i64, i32)> = tx
+            .prepare("SELECT queue_id, position FROM queue_tracks WHERE track_id = ?1 ORDER BY queue_id, position DESC")?
+            .query_map(params![track_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+        for (queue_id, position) in queue_entries {
+            Self::remove_queue_entry(&tx, queue_id, position)?;
+        }
+        
+        let playlist_entries: Vec<(i64, i32)> = tx
+            .prepare("SELECT playlist_id, position FROM playlist_tracks WHERE track_id = ?1 ORDER BY playlist_id, position DESC")?
+            .query_map(params![track_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+        for (playlist_id, position) in playlist_entries {
+            tx.execute(
+                "DELETE FROM playlist_tracks WHERE playlist_id = ?1 AND position = ?2",
+                params![playlist_id, position],
+            )?;
+            tx.execute(
+                "UPDATE playlist_tracks SET position = position - 1 WHERE playlist_id = ?1 AND position > ?2",
+                params![playlist_id, position],
+            )?;
+        }
+        
+        let album_id: Option<i64> = tx.query_row(
+            "SELECT album_id FROM tracks WHERE id = ?1",
+            params![track_id],
+            |row| row.get(0),
+        ).optional()?.flatten();
+        // Artist, genre, lyrics, loudness and location rows cascade with the track
+        let removed = tx.execute("DELETE FROM tracks WHERE id = ?1", params![track_id])?;
+        if let Some(album_id) = album_id {
+            // Drop the album row if this was its last track, otherwise its compilation
+            // flag may have changed with one artist fewer
+            tx.execute(
+                "DELETE FROM albums WHERE id = ?1 AND NOT EXISTS (SELECT 1 FROM tracks WHERE album_id = ?1)",
+                params![album_id],
+            )?;
+            tx.execute(&format!("{} WHERE id = ?1", MARK_COMPILATIONS_SQL), params![album_id])?;
+        }
+        tx.commit()?;
+        
+        Ok(removed > 0)
+    }
+    
     /// Update or insert track with hash comparison
     pub fn upsert_track_with_hash(
         db: &DatabaseConnection,
@@ -1644,37 +2776,52 @@ impl DbOperations {
                 return Ok((track_id, false));
             }
             
-            // Hash changed, update track (reset normalization_gain_db to NULL so it gets recalculated)
+            // Hash changed, update track (reset normalization_gain_db to NULL so it gets recalculated,
+            // unless the file carries SoundCheck data)
+            let normalization_source = track.normalization_gain_db.map(|_| "soundcheck");
             conn.execute(
                 "UPDATE tracks SET 
                     title = ?1, artist = ?2, album = ?3, album_artist = ?4,
                     year = ?5, track_number = ?6, disc_number = ?7, duration_ms = ?8,
                     genre = ?9, file_size = ?10, file_format = ?11, bitrate = ?12,
-                    sample_rate = ?13, date_modified = ?14, file_hash = ?15, normalization_gain_db = NULL
-                WHERE id = ?16",
+                    sample_rate = ?13, date_modified = ?14, file_hash = ?15,
+                    normalization_gain_db = ?16, normalization_source = ?17, has_audio = ?18,
+                    track_total = ?19, disc_total = ?20, composer = ?21, comment = ?22, bpm = ?23,
+                    compilation = ?24
+                WHERE id = ?25",
                 params![
                     track.title, track.artist, track.album, track.album_artist,
                     track.year, track.track_number, track.disc_number, track.duration_ms,
                     track.genre, track.file_size, track.file_format, track.bitrate,
-                    track.sample_rate, track.date_modified, file_hash, track_id
+                    track.sample_rate, track.date_modified, file_hash,
+                    track.normalization_gain_db, normalization_source, track.has_audio,
+                    track.track_total, track.disc_total, track.composer, track.comment, track.bpm,
+                    track.compilation, track_id
                 ],
             )?;
             
             Ok((track_id, true))
         } else {
-            // New track, insert (normalization_gain_db will be NULL, calculated separately)
+            // New track, insert (normalization_gain_db is NULL unless the file carries
+            // SoundCheck data, otherwise calculated separately)
+            let normalization_source = track.normalization_gain_db.map(|_| "soundcheck");
             conn.execute(
                 "INSERT INTO tracks (
                     file_path, title, artist, album, album_artist,
                     year, track_number, disc_number, duration_ms,
                     genre, file_size, file_format, bitrate, sample_rate,
-                    date_added, date_modified, play_count, file_hash
-                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)",
+                    date_added, date_modified, play_count, file_hash,
+                    normalization_gain_db, normalization_source, has_audio,
+                    track_total, disc_total, composer, comment, bpm, compilation
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27)",
                 params![
                     track.file_path, track.title, track.artist, track.album, track.album_artist,
                     track.year, track.track_number, track.disc_number, track.duration_ms,
                     track.genre, track.file_size, track.file_format, track.bitrate, track.sample_rate,
-                    track.date_added, track.date_modified, track.play_count, file_hash
+                    track.date_added, track.date_modified, track.play_count, file_hash,
+                    track.normalization_gain_db, normalization_source, track.has_audio,
+                    track.track_total, track.disc_total, track.composer, track.comment, track.bpm,
+                    track.compilation
                 ],
             )?;
             
@@ -1682,621 +2829,3137 @@ impl DbOperations {
         }
     }
 
-    /// Update the normalization gain for a track (in dB)
-    pub fn update_track_normalization_gain(
+    /// Stored modification time and size of the track or alternate location at `file_path`,
+    /// None if it isn't indexed or was indexed before modification times were recorded
+    pub fn get_track_file_stamp(
+        db: &DatabaseConnection,
+        file_path: &str,
+    ) -> Result<Option<(i64, i64)>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let stamp = conn.query_row(
+            "SELECT mtime, file_size FROM (
+                SELECT mtime, file_size, 0 AS alternate FROM tracks WHERE file_path = ?1
+                UNION ALL
+                SELECT mtime, file_size, 1 FROM track_locations WHERE file_path = ?1
+             )
+             WHERE mtime IS NOT NULL AND file_size IS NOT NULL
+             ORDER BY alternate LIMIT 1",
+            [file_path],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).optional()?;
+        
+        Ok(stamp)
+    }
+
+    /// Record the file modification time (unix milliseconds) a track was last indexed at
+    pub fn set_track_mtime(
         db: &DatabaseConnection,
         track_id: i64,
-        normalization_gain_db: f32,
+        mtime: i64,
     ) -> Result<(), anyhow::Error> {
         let conn = db.get_connection();
         let conn = conn.lock().unwrap();
         
         conn.execute(
-            "UPDATE tracks SET normalization_gain_db = ?1 WHERE id = ?2",
-            params![normalization_gain_db, track_id],
+            "UPDATE tracks SET mtime = ?1 WHERE id = ?2",
+            params![mtime, track_id],
         )?;
         
         Ok(())
     }
 
-    /// Get tracks that need loudness analysis (normalization_gain_db is NULL)
-    pub fn get_tracks_needing_loudness_analysis(
+    /// If `file_path` isn't a track yet but another track has the same content hash, record
+    /// the path as an alternate location of that track instead of indexing it again, with the
+    /// file's (mtime, size) `stamp` so the next scan can skip it without hashing.
+    /// Returns the existing track's id, or None if the file should be indexed normally.
+    pub fn record_duplicate_location(
         db: &DatabaseConnection,
-    ) -> Result<Vec<crate::db::models::Track>, anyhow::Error> {
+        file_path: &str,
+        file_hash: &str,
+        stamp: Option<(i64, i64)>,
+    ) -> Result<Option<i64>, anyhow::Error> {
         let conn = db.get_connection();
         let conn = conn.lock().unwrap();
         
-        let mut stmt = conn.prepare(
-            "SELECT id, file_path, title, artist, album, album_artist, year,
-                    track_number, disc_number, duration_ms, genre,
-                    file_size, file_format, bitrate, sample_rate,
-                    play_count, last_played, date_added, date_modified, file_hash,
-                    normalization_gain_db, play_time_seconds
-             FROM tracks
-             WHERE normalization_gain_db IS NULL
-             ORDER BY id"
+        let is_track: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM tracks WHERE file_path = ?1)",
+            params![file_path],
+            |row| row.get(0),
         )?;
+        if is_track {
+            return Ok(None);
+        }
         
-        let tracks = stmt.query_map([], |row| {
-            Ok(crate::db::models::Track {
-                id: row.get(0)?,
-                file_path: row.get(1)?,
-                title: row.get(2)?,
-                artist: row.get(3)?,
-                album: row.get(4)?,
-                album_artist: row.get(5)?,
-                year: row.get::<_, Option<i32>>(6)?.map(|y| y as u32),
-                track_number: row.get(7)?,
-                disc_number: row.get(8)?,
-                duration_ms: row.get(9)?,
-                genre: row.get(10)?,
-                file_size: row.get(11)?,
-                file_format: row.get(12)?,
-                bitrate: row.get(13)?,
-                sample_rate: row.get(14)?,
-                play_count: row.get(15)?,
-                last_played: row.get(16)?,
-                date_added: row.get(17)?,
-                date_modified: row.get(18)?,
-                file_hash: row.get(19)?,
-                normalization_gain_db: row.get(20)?,
-                play_time_seconds: row.get(21)?,
-            })
-        })?
-        .collect::<Result<Vec<_>, _>>()?;
+        let original: Option<i64> = conn.query_row(
+            "SELECT id FROM tracks WHERE file_hash = ?1 ORDER BY id LIMIT 1",
+            params![file_hash],
+            |row| row.get(0),
+        ).optional()?;
         
-        Ok(tracks)
+        if let Some(track_id) = original {
+            // A location that used to hold other content is re-pointed at this track
+            conn.execute(
+                "INSERT INTO track_locations (track_id, file_path, mtime, file_size) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(file_path) DO UPDATE SET
+                    track_id = excluded.track_id, mtime = excluded.mtime, file_size = excluded.file_size",
+                params![track_id, file_path, stamp.map(|s| s.0), stamp.map(|s| s.1)],
+            )?;
+        }
+        
+        Ok(original)
     }
 
-    // ===== User Playlists =====
-
-    /// Get all user-created playlists
-    pub fn get_all_playlists(
+    /// Every known path of a track: its own file path first, then alternate locations
+    pub fn get_track_locations(
         db: &DatabaseConnection,
-    ) -> Result<Vec<Playlist>, anyhow::Error> {
+        track_id: i64,
+    ) -> Result<Vec<String>, anyhow::Error> {
         let conn = db.get_connection();
         let conn = conn.lock().unwrap();
         
         let mut stmt = conn.prepare(
-            "SELECT id, name, description FROM playlists ORDER BY name"
+            "SELECT file_path FROM (
+                SELECT file_path, 0 AS alternate, 0 AS id FROM tracks WHERE id = ?1
+                UNION ALL
+                SELECT file_path, 1, id FROM track_locations WHERE track_id = ?1
+             ) ORDER BY alternate, id"
         )?;
+        let paths = stmt.query_map(params![track_id], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
         
-        let playlists = stmt.query_map([], |row| {
-            Ok(Playlist {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                description: row.get(2)?,
-            })
-        })?
-        .collect::<Result<Vec<_>, _>>()?;
-        
-        Ok(playlists)
+        Ok(paths)
     }
 
-    /// Create a new playlist
-    pub fn create_playlist(
+    /// Reconcile alternate locations with the files found by a scan: locations that are gone
+    /// are dropped, and a track whose own file is gone moves to a surviving copy so it isn't
+    /// removed from the library. Returns the number of tracks moved.
+    pub fn promote_track_locations(
         db: &DatabaseConnection,
-        name: &str,
-        description: Option<&str>,
-    ) -> Result<i64, anyhow::Error> {
-        use std::time::{SystemTime, UNIX_EPOCH};
-        
+        found_paths: &std::collections::HashSet<String>,
+    ) -> Result<usize, anyhow::Error> {
+        let moved = Self::promote_locations(db, None, |path| found_paths.contains(path))?;
+        Ok(moved.len())
+    }
+
+    /// promote_track_locations for only some tracks, with `exists` telling which paths are
+    /// still there. Returns the tracks that moved to a surviving copy.
+    pub fn promote_locations_of_tracks(
+        db: &DatabaseConnection,
+        track_ids: &[i64],
+        exists: impl Fn(&str) -> bool,
+    ) -> Result<Vec<i64>, anyhow::Error> {
+        Self::promote_locations(db, Some(track_ids), exists)
+    }
+
+    fn promote_locations(
+        db: &DatabaseConnection,
+        track_ids: Option<&[i64]>,
+        exists: impl Fn(&str) -> bool,
+    ) -> Result<Vec<i64>, anyhow::Error> {
         let conn = db.get_connection();
-        let conn = conn.lock().unwrap();
+        let mut conn = conn.lock().unwrap();
         
-        // Check if playlist with same name exists
-        let exists: bool = conn.query_row(
-            "SELECT COUNT(*) > 0 FROM playlists WHERE name = ?1",
-            [name],
-            |row| row.get(0)
-        )?;
+        let tx = conn.transaction()?;
         
-        if exists {
-            return Err(anyhow::anyhow!("Playlist with this name already exists"));
-        }
+        let locations: Vec<(i64, i64, String, String)> = {
+            let mut stmt = tx.prepare(
+                "SELECT l.id, l.track_id, l.file_path, t.file_path
+                 FROM track_locations l JOIN tracks t ON l.track_id = t.id
+                 ORDER BY l.id"
+            )?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+                .collect::<Result<Vec<_>, _>>()?;
+            rows
+        };
         
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
+        let mut moved_tracks = std::collections::BTreeSet::new();
+        for (location_id, track_id, location_path, track_path) in locations {
+            if track_ids.is_some_and(|ids| !ids.contains(&track_id)) {
+                continue;
+            }
+            let location_exists = exists(&location_path);
+            let promote = location_exists
+                && !exists(&track_path)
+                && !moved_tracks.contains(&track_id);
+            if promote {
+                tx.execute(
+                    "UPDATE tracks SET file_path = ?1 WHERE id = ?2",
+                    params![location_path, track_id],
+                )?;
+                moved_tracks.insert(track_id);
+            }
+            if promote || !location_exists {
+                tx.execute("DELETE FROM track_locations WHERE id = ?1", params![location_id])?;
+            }
+        }
         
-        conn.execute(
-            "INSERT INTO playlists (name, description, date_created, date_modified) VALUES (?1, ?2, ?3, ?4)",
-            params![name, description, now, now],
+        tx.commit()?;
+        Ok(moved_tracks.into_iter().collect())
+    }
+
+    /// Store a new file hash and size for a track after the app itself rewrote the file,
+    /// so the next scan doesn't re-index it. Returns false if the file isn't in the library.
+    pub fn update_track_file_hash(
+        db: &DatabaseConnection,
+        file_path: &str,
+        file_hash: &str,
+        file_size: Option<i64>,
+    ) -> Result<bool, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let updated = conn.execute(
+            "UPDATE tracks SET file_hash = ?1, file_size = COALESCE(?2, file_size) WHERE file_path = ?3",
+            params![file_hash, file_size, file_path],
         )?;
         
-        Ok(conn.last_insert_rowid())
+        Ok(updated > 0)
     }
 
-    /// Rename a playlist
-    pub fn rename_playlist(
+    /// (file_hash, file_path) of every hashed track, for matching artwork cache entries
+    pub fn get_track_hashes(
         db: &DatabaseConnection,
-        playlist_id: i64,
-        new_name: &str,
-    ) -> Result<(), anyhow::Error> {
-        use std::time::{SystemTime, UNIX_EPOCH};
-        
+    ) -> Result<Vec<(String, String)>, anyhow::Error> {
         let conn = db.get_connection();
         let conn = conn.lock().unwrap();
         
-        // Check if another playlist with same name exists
-        let exists: bool = conn.query_row(
-            "SELECT COUNT(*) > 0 FROM playlists WHERE name = ?1 AND id != ?2",
-            params![new_name, playlist_id],
-            |row| row.get(0)
+        let mut stmt = conn.prepare(
+            "SELECT file_hash, file_path FROM tracks WHERE file_hash IS NOT NULL"
         )?;
+        let hashes = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
         
-        if exists {
-            return Err(anyhow::anyhow!("Playlist with this name already exists"));
-        }
-        
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
+        Ok(hashes)
+    }
+
+    /// Artwork paths referenced by albums and playlists
+    pub fn get_referenced_artwork_paths(
+        db: &DatabaseConnection,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
         
-        let rows_affected = conn.execute(
-            "UPDATE playlists SET name = ?1, date_modified = ?2 WHERE id = ?3",
-            params![new_name, now, playlist_id],
+        let mut stmt = conn.prepare(
+            "SELECT artwork_path FROM albums WHERE artwork_path IS NOT NULL
+             UNION
+             SELECT artwork_path FROM playlists WHERE artwork_path IS NOT NULL"
         )?;
+        let paths = stmt.query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<_>, _>>()?;
         
-        if rows_affected == 0 {
-            return Err(anyhow::anyhow!("Playlist not found"));
-        }
-        
-        Ok(())
+        Ok(paths)
     }
 
-    /// Add a track to a playlist
-    pub fn add_track_to_playlist(
+    /// Remember a cached cover as the artwork of the album a track belongs to.
+    /// Returns false if the album already had it (nothing is written then).
+    pub fn set_album_artwork_path(
         db: &DatabaseConnection,
-        playlist_id: i64,
         track_id: i64,
-    ) -> Result<(), anyhow::Error> {
-        use std::time::{SystemTime, UNIX_EPOCH};
+        artwork_path: &str,
+    ) -> Result<bool, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let changed = conn.execute(
+            "UPDATE albums SET artwork_path = ?1
+             WHERE id = (SELECT album_id FROM tracks WHERE id = ?2)
+               AND artwork_path IS NOT ?1",
+            params![artwork_path, track_id],
+        )?;
         
+        Ok(changed > 0)
+    }
+
+    /// Record sidecar art (cover.jpg, folder.jpg, ...) as an album's artwork, unless it
+    /// already has some
+    pub fn set_album_folder_artwork(
+        db: &DatabaseConnection,
+        album_id: i64,
+        artwork_path: &str,
+    ) -> Result<(), anyhow::Error> {
         let conn = db.get_connection();
         let conn = conn.lock().unwrap();
         
-        // Get the next position
-        let position: i32 = conn.query_row(
-            "SELECT COALESCE(MAX(position), -1) + 1 FROM playlist_tracks WHERE playlist_id = ?1",
-            [playlist_id],
-            |row| row.get(0)
+        conn.execute(
+            "UPDATE albums SET artwork_path = ?1 WHERE id = ?2 AND artwork_path IS NULL",
+            params![artwork_path, album_id],
         )?;
         
-        // Check if track already exists in playlist
-        let exists: bool = conn.query_row(
-            "SELECT COUNT(*) > 0 FROM playlist_tracks WHERE playlist_id = ?1 AND track_id = ?2",
-            params![playlist_id, track_id],
-            |row| row.get(0)
+        Ok(())
+    }
+
+    /// Set (or with None, clear) the cover the user picked for an album.
+    /// Returns false if there is no such album.
+    pub fn set_album_custom_artwork(
+        db: &DatabaseConnection,
+        album_id: i64,
+        artwork_path: Option<&str>,
+    ) -> Result<bool, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let updated = conn.execute(
+            "UPDATE albums SET custom_artwork_path = ?1 WHERE id = ?2",
+            params![artwork_path, album_id],
         )?;
         
-        if exists {
-            return Err(anyhow::anyhow!("Track already exists in playlist"));
-        }
+        Ok(updated > 0)
+    }
+
+    /// (user-picked artwork, artwork path) of the album a track belongs to
+    pub fn get_track_album_artwork(
+        db: &DatabaseConnection,
+        track_id: i64,
+    ) -> Result<(Option<String>, Option<String>), anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
         
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
+        let paths = conn.query_row(
+            "SELECT a.custom_artwork_path, a.artwork_path FROM albums a
+             JOIN tracks t ON t.album_id = a.id
+             WHERE t.id = ?1",
+            [track_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).optional()?;
+        
+        Ok(paths.unwrap_or((None, None)))
+    }
+
+    /// Queue a play for Last.fm, returns its id
+    pub fn add_pending_scrobble(
+        db: &DatabaseConnection,
+        scrobble: &Scrobble,
+    ) -> Result<i64, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
         
         conn.execute(
-            "INSERT INTO playlist_tracks (playlist_id, track_id, position, date_added) VALUES (?1, ?2, ?3, ?4)",
-            params![playlist_id, track_id, position, now],
+            "INSERT INTO pending_scrobbles (artist, title, album, album_artist, duration_ms, timestamp)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![scrobble.artist, scrobble.title, scrobble.album, scrobble.album_artist,
+                    scrobble.duration_ms, scrobble.timestamp],
         )?;
         
-        Ok(())
+        Ok(conn.last_insert_rowid())
     }
-
-    /// Get all tracks in a playlist
-    pub fn get_playlist_tracks(
+    
+    /// Oldest queued plays first, at most `limit`
+    pub fn get_pending_scrobbles(
         db: &DatabaseConnection,
-        playlist_id: i64,
-    ) -> Result<Vec<Track>, anyhow::Error> {
+        limit: usize,
+    ) -> Result<Vec<Scrobble>, anyhow::Error> {
         let conn = db.get_connection();
         let conn = conn.lock().unwrap();
         
         let mut stmt = conn.prepare(
-            "SELECT t.id, t.file_path, t.title, t.artist, t.album, t.album_artist,
-                    t.year, t.track_number, t.disc_number, t.duration_ms, t.genre,
-                    t.file_size, t.file_format, t.bitrate, t.sample_rate,
-                    t.date_added, t.date_modified, t.play_count, t.last_played, t.file_hash,
-                    t.normalization_gain_db, t.play_time_seconds
-             FROM tracks t
-             INNER JOIN playlist_tracks pt ON t.id = pt.track_id
-             WHERE pt.playlist_id = ?1
-             ORDER BY pt.position"
+            "SELECT id, artist, title, album, album_artist, duration_ms, timestamp
+             FROM pending_scrobbles
+             ORDER BY timestamp, id
+             LIMIT ?1"
         )?;
-        
-        let tracks = stmt.query_map([playlist_id], |row| {
-            Ok(Track {
+        let scrobbles = stmt.query_map([limit as i64], |row| {
+            Ok(Scrobble {
                 id: row.get(0)?,
-                file_path: row.get(1)?,
+                artist: row.get(1)?,
                 title: row.get(2)?,
-                artist: row.get(3)?,
-                album: row.get(4)?,
-                album_artist: row.get(5)?,
-                year: row.get(6)?,
-                track_number: row.get(7)?,
-                disc_number: row.get(8)?,
-                duration_ms: row.get(9)?,
-                genre: row.get(10)?,
-                file_size: row.get(11)?,
-                file_format: row.get(12)?,
-                bitrate: row.get(13)?,
-                sample_rate: row.get(14)?,
-                date_added: row.get(15)?,
-                date_modified: row.get(16)?,
-                play_count: row.get(17)?,
-                last_played: row.get(18)?,
-                file_hash: row.get(19)?,
-                normalization_gain_db: row.get(20)?,
-                play_time_seconds: row.get(21)?,
+                album: row.get(3)?,
+                album_artist: row.get(4)?,
+                duration_ms: row.get(5)?,
+                timestamp: row.get(6)?,
             })
         })?
         .collect::<Result<Vec<_>, _>>()?;
         
-        Ok(tracks)
+        Ok(scrobbles)
     }
-
-    /// Remove a track from a playlist by track ID
-    pub fn remove_track_from_playlist(
+    
+    /// Drop queued plays once Last.fm took them
+    pub fn delete_pending_scrobbles(
         db: &DatabaseConnection,
-        playlist_id: i64,
-        track_id: i64,
-    ) -> Result<(), anyhow::Error> {
+        ids: &[i64],
+    ) -> Result<usize, anyhow::Error> {
         let conn = db.get_connection();
         let mut conn = conn.lock().unwrap();
         
-        // Get the position of the track to be removed
-        let position: Option<i32> = conn
-            .query_row(
-                "SELECT position FROM playlist_tracks WHERE playlist_id = ?1 AND track_id = ?2",
-                params![playlist_id, track_id],
-                |row| row.get(0),
-            )
-            .optional()?;
-        
-        let position = match position {
-            Some(p) => p,
-            None => return Err(anyhow::anyhow!("Track not found in playlist")),
-        };
-        
         let tx = conn.transaction()?;
-        
-        // Remove the track
-        tx.execute(
-            "DELETE FROM playlist_tracks WHERE playlist_id = ?1 AND track_id = ?2",
-            params![playlist_id, track_id],
-        )?;
-        
-        // Shift positions of tracks after the removed one
-        tx.execute(
-            "UPDATE playlist_tracks SET position = position - 1 WHERE playlist_id = ?1 AND position > ?2",
-            params![playlist_id, position],
-        )?;
-        
+        let mut deleted = 0;
+        for id in ids {
+            deleted += tx.execute("DELETE FROM pending_scrobbles WHERE id = ?1", [id])?;
+        }
         tx.commit()?;
-        Ok(())
+        
+        Ok(deleted)
     }
 
-    /// Delete a playlist and all its tracks
-    pub fn delete_playlist(
+    /// Record that loudness analysis failed for a track, so it isn't retried on every run.
+    /// The gain stays NULL (no normalization) and normalization_source is "failed"; indexing
+    /// a changed file resets the source, which makes the track eligible again.
+    pub fn mark_loudness_failed(
         db: &DatabaseConnection,
-        playlist_id: i64,
+        track_id: i64,
     ) -> Result<(), anyhow::Error> {
         let conn = db.get_connection();
         let conn = conn.lock().unwrap();
         
-        // Delete all tracks in the playlist first
         conn.execute(
-            "DELETE FROM playlist_tracks WHERE playlist_id = ?1",
-            params![playlist_id],
-        )?;
-        
-        // Delete the playlist itself
-        let rows_affected = conn.execute(
-            "DELETE FROM playlists WHERE id = ?1",
-            params![playlist_id],
+            "UPDATE tracks SET normalization_gain_db = NULL, normalization_source = 'failed' WHERE id = ?1",
+            params![track_id],
         )?;
         
-        if rows_affected == 0 {
-            return Err(anyhow::anyhow!("Playlist not found"));
-        }
-        
         Ok(())
     }
 
-    /// Move a track from one position to another in a playlist
-    pub fn reorder_playlist_track(
+    /// Store a track's loudness analysis and use its gain for normalization.
+    /// Album values are kept when the new analysis doesn't include them.
+    pub fn upsert_loudness(
         db: &DatabaseConnection,
-        playlist_id: i64,
-        from_position: i32,
-        to_position: i32,
+        loudness: &TrackLoudness,
     ) -> Result<(), anyhow::Error> {
-        if from_position == to_position {
-            return Ok(());
-        }
+        Self::upsert_loudness_from(db, loudness, "analysis")
+    }
 
+    /// `upsert_loudness`, recording `source` ("analysis", "replaygain") as where the
+    /// normalization gain came from
+    pub fn upsert_loudness_from(
+        db: &DatabaseConnection,
+        loudness: &TrackLoudness,
+        source: &str,
+    ) -> Result<(), anyhow::Error> {
         let conn = db.get_connection();
         let mut conn = conn.lock().unwrap();
-
+        
         let tx = conn.transaction()?;
-
-        // Get track_id at from_position
-        let track_id: i64 = tx.query_row(
-            "SELECT track_id FROM playlist_tracks WHERE playlist_id = ?1 AND position = ?2",
-            params![playlist_id, from_position],
-            |row| row.get(0),
+        tx.execute(
+            "INSERT INTO track_loudness (track_id, integrated_lufs, true_peak_db, gain_db, album_gain_db, album_peak_db)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(track_id) DO UPDATE SET
+                integrated_lufs = excluded.integrated_lufs,
+                true_peak_db = excluded.true_peak_db,
+                gain_db = excluded.gain_db,
+                album_gain_db = COALESCE(excluded.album_gain_db, album_gain_db),
+                album_peak_db = COALESCE(excluded.album_peak_db, album_peak_db)",
+            params![
+                loudness.track_id,
+                loudness.integrated_lufs,
+                loudness.true_peak_db,
+                loudness.gain_db,
+                loudness.album_gain_db,
+                loudness.album_peak_db,
+            ],
         )?;
-
-        if from_position < to_position {
-            // Moving down: shift tracks between from+1 and to up by 1
-            tx.execute(
-                "UPDATE playlist_tracks SET position = position - 1 
-                 WHERE playlist_id = ?1 AND position > ?2 AND position <= ?3",
-                params![playlist_id, from_position, to_position],
-            )?;
-        } else {
-            // Moving up: shift tracks between to and from-1 down by 1
-            tx.execute(
-                "UPDATE playlist_tracks SET position = position + 1 
-                 WHERE playlist_id = ?1 AND position >= ?2 AND position < ?3",
-                params![playlist_id, to_position, from_position],
-            )?;
-        }
-
-        // Move the track to its new position
         tx.execute(
-            "UPDATE playlist_tracks SET position = ?1 WHERE playlist_id = ?2 AND track_id = ?3",
-            params![to_position, playlist_id, track_id],
+            "UPDATE tracks SET normalization_gain_db = ?1, normalization_source = ?2 WHERE id = ?3",
+            params![loudness.gain_db, source, loudness.track_id],
         )?;
-
         tx.commit()?;
+        
         Ok(())
     }
 
-    /// Move a track from one position to another in a queue
-    /// If the queue is shuffled, updates the original_order to reflect the new order
-    /// Returns the new current_track_index after adjustment
-    pub fn reorder_queue_track(
+    /// Get a track's stored loudness analysis, None if it hasn't been analyzed
+    pub fn get_loudness(
         db: &DatabaseConnection,
-        queue_id: i64,
-        from_position: i32,
-        to_position: i32,
-    ) -> Result<i32, anyhow::Error> {
-        if from_position == to_position {
-            let current_index = Self::get_queue_current_index(db, queue_id)?;
-            return Ok(current_index);
-        }
-
+        track_id: i64,
+    ) -> Result<Option<TrackLoudness>, anyhow::Error> {
         let conn = db.get_connection();
-        let mut conn = conn.lock().unwrap();
-
-        let tx = conn.transaction()?;
-
-        // Get current track index
-        let current_index: i32 = tx.query_row(
-            "SELECT current_track_index FROM queues WHERE id = ?1",
-            params![queue_id],
-            |row| row.get(0),
-        )?;
+        let conn = conn.lock().unwrap();
+        
+        let loudness = conn.query_row(
+            "SELECT track_id, integrated_lufs, true_peak_db, gain_db, album_gain_db, album_peak_db
+             FROM track_loudness WHERE track_id = ?1",
+            params![track_id],
+            |row| Ok(TrackLoudness {
+                track_id: row.get(0)?,
+                integrated_lufs: row.get(1)?,
+                true_peak_db: row.get(2)?,
+                gain_db: row.get(3)?,
+                album_gain_db: row.get(4)?,
+                album_peak_db: row.get(5)?,
+            }),
+        ).optional()?;
+        
+        Ok(loudness)
+    }
 
-        // Get track_id at from_position
-        let track_id: i64 = tx.query_row(
-            "SELECT track_id FROM queue_tracks WHERE queue_id = ?1 AND position = ?2",
-            params![queue_id, from_position],
+    /// Whether the active queue plays the track as part of its album in order: the queue
+    /// isn't shuffled and a neighbouring track belongs to the same album
+    pub fn is_album_playing_in_order(
+        db: &DatabaseConnection,
+        track_id: i64,
+    ) -> Result<bool, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*)
+             FROM queues q
+             INNER JOIN queue_tracks qt ON qt.queue_id = q.id
+             INNER JOIN tracks t ON t.id = qt.track_id
+             INNER JOIN queue_tracks nb ON nb.queue_id = q.id
+                AND nb.position IN (qt.position - 1, qt.position + 1)
+             INNER JOIN tracks nt ON nt.id = nb.track_id
+             WHERE q.is_active = 1
+               AND q.original_order IS NULL
+               AND t.id = ?1
+               AND t.album_id IS NOT NULL
+               AND nt.album_id = t.album_id",
+            params![track_id],
             |row| row.get(0),
         )?;
+        
+        Ok(count > 0)
+    }
 
-        if from_position < to_position {
-            // Moving down: shift tracks between from+1 and to up by 1
-            tx.execute(
-                "UPDATE queue_tracks SET position = position - 1 
-                 WHERE queue_id = ?1 AND position > ?2 AND position <= ?3",
-                params![queue_id, from_position, to_position],
-            )?;
-        } else {
-            // Moving up: shift tracks between to and from-1 down by 1
+    /// (id, file_path) of the tracks whose tags didn't provide a duration
+    pub fn get_tracks_missing_duration(
+        db: &DatabaseConnection,
+    ) -> Result<Vec<(i64, String)>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let mut stmt = conn.prepare(
+            "SELECT id, file_path FROM tracks WHERE duration_ms IS NULL OR duration_ms <= 0"
+        )?;
+        let tracks = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(tracks)
+    }
+    
+    /// Set a track's duration
+    pub fn update_track_duration(
+        db: &DatabaseConnection,
+        track_id: i64,
+        duration_ms: i64,
+    ) -> Result<(), anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        conn.execute(
+            "UPDATE tracks SET duration_ms = ?1 WHERE id = ?2",
+            params![duration_ms, track_id],
+        )?;
+        
+        Ok(())
+    }
+
+    /// Get tracks that need loudness analysis (normalization_gain_db is NULL and no earlier
+    /// analysis of the file failed)
+    pub fn get_tracks_needing_loudness_analysis(
+        db: &DatabaseConnection,
+    ) -> Result<Vec<crate::db::models::Track>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM tracks t
+             WHERE normalization_gain_db IS NULL
+               AND normalization_source IS NOT 'failed'
+             ORDER BY id",
+            TRACK_COLUMNS
+        ))?;
+        
+        let tracks = stmt.query_map([], row_to_track)?
+        .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(tracks)
+    }
+
+    // ===== User Playlists =====
+
+    /// Get all user-created playlists
+    pub fn get_all_playlists(
+        db: &DatabaseConnection,
+    ) -> Result<Vec<Playlist>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let mut stmt = conn.prepare(
+            "SELECT id, name, description FROM playlists ORDER BY name"
+        )?;
+        
+        let playlists = stmt.query_map([], |row| {
+            Ok(Playlist {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                description: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(playlists)
+    }
+
+    /// Create a new playlist
+    pub fn create_playlist(
+        db: &DatabaseConnection,
+        name: &str,
+        description: Option<&str>,
+    ) -> Result<i64, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        Self::insert_playlist(&conn, name, description)
+    }
+
+    /// Create a playlist holding the given tracks in order, all or nothing.
+    /// A track listed more than once is only added the first time.
+    /// Returns the playlist ID and the number of tracks added.
+    pub fn create_playlist_with_tracks(
+        db: &DatabaseConnection,
+        name: &str,
+        track_ids: &[i64],
+    ) -> Result<(i64, usize), anyhow::Error> {
+        use std::time::{SystemTime, UNIX_EPOCH};
+        
+        let conn = db.get_connection();
+        let mut conn = conn.lock().unwrap();
+        
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        
+        let tx = conn.transaction()?;
+        let playlist_id = Self::insert_playlist(&tx, name, None)?;
+        
+        let mut seen = std::collections::HashSet::new();
+        let mut position = 0;
+        for &track_id in track_ids {
+            if !seen.insert(track_id) {
+                continue;
+            }
             tx.execute(
-                "UPDATE queue_tracks SET position = position + 1 
-                 WHERE queue_id = ?1 AND position >= ?2 AND position < ?3",
-                params![queue_id, to_position, from_position],
+                "INSERT INTO playlist_tracks (playlist_id, track_id, position, date_added) VALUES (?1, ?2, ?3, ?4)",
+                params![playlist_id, track_id, position, now],
             )?;
+            position += 1;
+        }
+        
+        tx.commit()?;
+        Ok((playlist_id, position as usize))
+    }
+
+    /// Insert a playlist row, refusing a name that is already taken
+    fn insert_playlist(
+        conn: &rusqlite::Connection,
+        name: &str,
+        description: Option<&str>,
+    ) -> Result<i64, anyhow::Error> {
+        use std::time::{SystemTime, UNIX_EPOCH};
+        
+        // Check if playlist with same name exists
+        let exists: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM playlists WHERE name = ?1",
+            [name],
+            |row| row.get(0)
+        )?;
+        
+        if exists {
+            return Err(anyhow::anyhow!("Playlist with this name already exists"));
+        }
+        
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        
+        conn.execute(
+            "INSERT INTO playlists (name, description, date_created, date_modified) VALUES (?1, ?2, ?3, ?4)",
+            params![name, description, now, now],
+        )?;
+        
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Rename a playlist
+    pub fn rename_playlist(
+        db: &DatabaseConnection,
+        playlist_id: i64,
+        new_name: &str,
+    ) -> Result<(), anyhow::Error> {
+        use std::time::{SystemTime, UNIX_EPOCH};
+        
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        // Check if another playlist with same name exists
+        let exists: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM playlists WHERE name = ?1 AND id != ?2",
+            params![new_name, playlist_id],
+            |row| row.get(0)
+        )?;
+        
+        if exists {
+            return Err(anyhow::anyhow!("Playlist with this name already exists"));
+        }
+        
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        
+        let rows_affected = conn.execute(
+            "UPDATE playlists SET name = ?1, date_modified = ?2 WHERE id = ?3",
+            params![new_name, now, playlist_id],
+        )?;
+        
+        if rows_affected == 0 {
+            return Err(anyhow::anyhow!("Playlist not found"));
+        }
+        
+        Ok(())
+    }
+
+    /// Add a track to a playlist
+    pub fn add_track_to_playlist(
+        db: &DatabaseConnection,
+        playlist_id: i64,
+        track_id: i64,
+    ) -> Result<(), anyhow::Error> {
+        use std::time::{SystemTime, UNIX_EPOCH};
+        
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        // Get the next position
+        let position: i32 = conn.query_row(
+            "SELECT COALESCE(MAX(position), -1) + 1 FROM playlist_tracks WHERE playlist_id = ?1",
+            [playlist_id],
+            |row| row.get(0)
+        )?;
+        
+        // Check if track already exists in playlist
+        let exists: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM playlist_tracks WHERE playlist_id = ?1 AND track_id = ?2",
+            params![playlist_id, track_id],
+            |row| row.get(0)
+        )?;
+        
+        if exists {
+            return Err(anyhow::anyhow!("Track already exists in playlist"));
+        }
+        
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        
+        conn.execute(
+            "INSERT INTO playlist_tracks (playlist_id, track_id, position, date_added) VALUES (?1, ?2, ?3, ?4)",
+            params![playlist_id, track_id, position, now],
+        )?;
+        
+        Ok(())
+    }
+
+    /// Get all tracks in a playlist
+    pub fn get_playlist_tracks(
+        db: &DatabaseConnection,
+        playlist_id: i64,
+    ) -> Result<Vec<Track>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM tracks t
+             INNER JOIN playlist_tracks pt ON t.id = pt.track_id
+             WHERE pt.playlist_id = ?1
+             ORDER BY pt.position",
+            TRACK_COLUMNS
+        ))?;
+        
+        let tracks = stmt.query_map([playlist_id], row_to_track)?
+        .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(tracks)
+    }
+
+    /// Remove a track from a playlist by track ID
+    pub fn remove_track_from_playlist(
+        db: &DatabaseConnection,
+        playlist_id: i64,
+        track_id: i64,
+    ) -> Result<(), anyhow::Error> {
+        let conn = db.get_connection();
+        let mut conn = conn.lock().unwrap();
+        
+        // Get the position of the track to be removed
+        let position: Option<i32> = conn
+            .query_row(
+                "SELECT position FROM playlist_tracks WHERE playlist_id = ?1 AND track_id = ?2",
+                params![playlist_id, track_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        
+        let position = match position {
+            Some(p) => p,
+            None => return Err(anyhow::anyhow!("Track not found in playlist")),
+        };
+        
+        let tx = conn.transaction()?;
+        
+        // Remove the track
+        tx.execute(
+            "DELETE FROM playlist_tracks WHERE playlist_id = ?1 AND track_id = ?2",
+            params![playlist_id, track_id],
+        )?;
+        
+        // Shift positions of tracks after the removed one
+        tx.execute(
+            "UPDATE playlist_tracks SET position = position - 1 WHERE playlist_id = ?1 AND position > ?2",
+            params![playlist_id, position],
+        )?;
+        
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Remove the track at a position from a playlist, shifting later tracks down so
+    /// positions stay contiguous
+    pub fn remove_playlist_track_at_position(
+        db: &DatabaseConnection,
+        playlist_id: i64,
+        position: i32,
+    ) -> Result<(), anyhow::Error> {
+        let conn = db.get_connection();
+        let mut conn = conn.lock().unwrap();
+        
+        let tx = conn.transaction()?;
+        
+        let removed = tx.execute(
+            "DELETE FROM playlist_tracks WHERE playlist_id = ?1 AND position = ?2",
+            params![playlist_id, position],
+        )?;
+        if removed == 0 {
+            return Err(AppError::NotFound(format!("No track at position {} in playlist", position)).into());
+        }
+        
+        // Shift positions of tracks after the removed one
+        tx.execute(
+            "UPDATE playlist_tracks SET position = position - 1 WHERE playlist_id = ?1 AND position > ?2",
+            params![playlist_id, position],
+        )?;
+        
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Delete a playlist and all its tracks
+    pub fn delete_playlist(
+        db: &DatabaseConnection,
+        playlist_id: i64,
+    ) -> Result<(), anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        // Delete all tracks in the playlist first
+        conn.execute(
+            "DELETE FROM playlist_tracks WHERE playlist_id = ?1",
+            params![playlist_id],
+        )?;
+        
+        // Delete the playlist itself
+        let rows_affected = conn.execute(
+            "DELETE FROM playlists WHERE id = ?1",
+            params![playlist_id],
+        )?;
+        
+        if rows_affected == 0 {
+            return Err(anyhow::anyhow!("Playlist not found"));
+        }
+        
+        Ok(())
+    }
+
+    /// Create a smart playlist from its rules (SmartPlaylistRules JSON), checked up front
+    pub fn create_smart_playlist(
+        db: &DatabaseConnection,
+        name: &str,
+        rules_json: &str,
+    ) -> Result<i64, anyhow::Error> {
+        SmartPlaylistRules::from_json(rules_json)?;
+        
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs() as i64;
+        
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let exists: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM smart_playlists WHERE name = ?1",
+            [name],
+            |row| row.get(0)
+        )?;
+        if exists {
+            return Err(anyhow::anyhow!("Smart playlist with this name already exists"));
+        }
+        
+        conn.execute(
+            "INSERT INTO smart_playlists (name, rules, date_created, date_modified) VALUES (?1, ?2, ?3, ?3)",
+            params![name, rules_json, now],
+        )?;
+        
+        Ok(conn.last_insert_rowid())
+    }
+    
+    /// Get all smart playlists by name
+    pub fn get_all_smart_playlists(
+        db: &DatabaseConnection,
+    ) -> Result<Vec<SmartPlaylist>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let mut stmt = conn.prepare("SELECT id, name, rules FROM smart_playlists ORDER BY name")?;
+        let playlists = stmt.query_map([], |row| {
+            Ok(SmartPlaylist {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                rules: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(playlists)
+    }
+    
+    /// Delete a smart playlist
+    pub fn delete_smart_playlist(
+        db: &DatabaseConnection,
+        smart_playlist_id: i64,
+    ) -> Result<(), anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let rows_affected = conn.execute(
+            "DELETE FROM smart_playlists WHERE id = ?1",
+            params![smart_playlist_id],
+        )?;
+        if rows_affected == 0 {
+            return Err(anyhow::anyhow!("Smart playlist not found"));
+        }
+        
+        Ok(())
+    }
+    
+    /// Tracks currently matching a smart playlist's rules, in its sort order and up to its limit
+    pub fn evaluate_smart_playlist(
+        db: &DatabaseConnection,
+        smart_playlist_id: i64,
+    ) -> Result<Vec<Track>, anyhow::Error> {
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        
+        let rules_json: String = conn.query_row(
+            "SELECT rules FROM smart_playlists WHERE id = ?1",
+            [smart_playlist_id],
+            |row| row.get(0)
+        ).optional()?
+        .ok_or_else(|| anyhow::anyhow!("Smart playlist not found"))?;
+        
+        let rules = SmartPlaylistRules::from_json(&rules_json)?;
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs() as i64;
+        let (where_clause, mut params) = rules.where_clause(now)?;
+        // -1 is no limit in SQLite
+        params.push(rusqlite::types::Value::Integer(rules.limit.map(i64::from).unwrap_or(-1)));
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM tracks t
+             WHERE {}
+             ORDER BY {}
+             LIMIT ?",
+            TRACK_COLUMNS,
+            where_clause,
+            rules.track_sort()?.order_by()
+        ))?;
+        
+        let tracks = stmt.query_map(rusqlite::params_from_iter(params), row_to_track)?
+        .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(tracks)
+    }
+
+    /// Move a track from one position to another in a playlist
+    pub fn reorder_playlist_track(
+        db: &DatabaseConnection,
+        playlist_id: i64,
+        from_position: i32,
+        to_position: i32,
+    ) -> Result<(), anyhow::Error> {
+        if from_position == to_position {
+            return Ok(());
+        }
+
+        let conn = db.get_connection();
+        let mut conn = conn.lock().unwrap();
+
+        let tx = conn.transaction()?;
+
+        // Get track_id at from_position
+        let track_id: i64 = tx.query_row(
+            "SELECT track_id FROM playlist_tracks WHERE playlist_id = ?1 AND position = ?2",
+            params![playlist_id, from_position],
+            |row| row.get(0),
+        )?;
+
+        if from_position < to_position {
+            // Moving down: shift tracks between from+1 and to up by 1
+            tx.execute(
+                "UPDATE playlist_tracks SET position = position - 1 
+                 WHERE playlist_id = ?1 AND position > ?2 AND position <= ?3",
+                params![playlist_id, from_position, to_position],
+            )?;
+        } else {
+            // Moving up: shift tracks between to and from-1 down by 1
+            tx.execute(
+                "UPDATE playlist_tracks SET position = position + 1 
+                 WHERE playlist_id = ?1 AND position >= ?2 AND position < ?3",
+                params![playlist_id, to_position, from_position],
+            )?;
+        }
+
+        // Move the track to its new position
+        tx.execute(
+            "UPDATE playlist_tracks SET position = ?1 WHERE playlist_id = ?2 AND track_id = ?3",
+            params![to_position, playlist_id, track_id],
+        )?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Move a track from one position to another in a queue
+    /// If the queue is shuffled, updates the original_order to reflect the new order
+    /// Returns the new current_track_index after adjustment; both positions must be inside the queue
+    pub fn reorder_queue_track(
+        db: &DatabaseConnection,
+        queue_id: i64,
+        from_position: i32,
+        to_position: i32,
+    ) -> Result<i32, anyhow::Error> {
+        if from_position == to_position {
+            let current_index = Self::get_queue_current_index(db, queue_id)?;
+            return Ok(current_index);
+        }
+
+        let conn = db.get_connection();
+        let mut conn = conn.lock().unwrap();
+
+        let tx = conn.transaction()?;
+
+        let queue_length: i32 = tx.query_row(
+            "SELECT COUNT(*) FROM queue_tracks WHERE queue_id = ?1",
+            params![queue_id],
+            |row| row.get(0),
+        )?;
+        if !(0..queue_length).contains(&from_position) || !(0..queue_length).contains(&to_position) {
+            return Err(anyhow::anyhow!(
+                "Cannot move queue track from {} to {}: queue has {} tracks",
+                from_position, to_position, queue_length
+            ));
+        }
+
+        // Get current track index
+        let current_index: i32 = tx.query_row(
+            "SELECT current_track_index FROM queues WHERE id = ?1",
+            params![queue_id],
+            |row| row.get(0),
+        )?;
+
+        // Get track_id at from_position
+        let track_id: i64 = tx.query_row(
+            "SELECT track_id FROM queue_tracks WHERE queue_id = ?1 AND position = ?2",
+            params![queue_id, from_position],
+            |row| row.get(0),
+        )?;
+
+        if from_position < to_position {
+            // Moving down: shift tracks between from+1 and to up by 1
+            tx.execute(
+                "UPDATE queue_tracks SET position = position - 1 
+                 WHERE queue_id = ?1 AND position > ?2 AND position <= ?3",
+                params![queue_id, from_position, to_position],
+            )?;
+        } else {
+            // Moving up: shift tracks between to and from-1 down by 1
+            tx.execute(
+                "UPDATE queue_tracks SET position = position + 1 
+                 WHERE queue_id = ?1 AND position >= ?2 AND position < ?3",
+                params![queue_id, to_position, from_position],
+            )?;
+        }
+
+        // Move the track to its new position
+        tx.execute(
+            "UPDATE queue_tracks SET position = ?1 WHERE queue_id = ?2 AND track_id = ?3 AND position = ?4",
+            params![to_position, queue_id, track_id, from_position],
+        )?;
+
+        // Note: We do NOT update original_order when reordering a shuffled queue.
+        // The original_order preserves the pre-shuffle state for restoration when unshuffle is pressed.
+        // User's drag-and-drop only affects the current (shuffled) order.
+
+        // Calculate new current_track_index
+        let new_index = if from_position == current_index {
+            // The currently playing track was moved
+            to_position
+        } else if from_position < current_index && to_position >= current_index {
+            // Track moved from before current to after/at current: current shifts up
+            current_index - 1
+        } else if from_position > current_index && to_position <= current_index {
+            // Track moved from after current to before/at current: current shifts down
+            current_index + 1
+        } else {
+            // No effect on current index
+            current_index
+        };
+
+        // Update current_track_index
+        tx.execute(
+            "UPDATE queues SET current_track_index = ?1 WHERE id = ?2",
+            params![new_index, queue_id],
+        )?;
+
+        tx.commit()?;
+        Ok(new_index)
+    }
+
+    /// Sort a whole queue by "title", "artist", "album" or "duration".
+    /// Text is compared case-insensitively and tracks without a value go last in either
+    /// direction; ties keep their current relative order. Like drag-and-drop reordering this
+    /// only changes the current order, not a shuffled queue's original_order.
+    /// The playing track keeps playing, returns its new current_track_index.
+    pub fn sort_queue(
+        db: &DatabaseConnection,
+        queue_id: i64,
+        sort_key: &str,
+        ascending: bool,
+    ) -> Result<i32, anyhow::Error> {
+        let column = match sort_key {
+            "title" => "LOWER(t.title)",
+            "artist" => "LOWER(t.artist)",
+            "album" => "LOWER(t.album)",
+            "duration" => "t.duration_ms",
+            _ => return Err(anyhow::anyhow!("Invalid sort key: {}", sort_key)),
+        };
+
+        let conn = db.get_connection();
+        let mut conn = conn.lock().unwrap();
+
+        let tx = conn.transaction()?;
+
+        let current_index: i32 = tx.query_row(
+            "SELECT current_track_index FROM queues WHERE id = ?1",
+            params![queue_id],
+            |row| row.get(0),
+        )?;
+
+        // (queue_tracks.id, position) in the new order
+        let sorted: Vec<(i64, i32)> = {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT qt.id, qt.position FROM queue_tracks qt
+                 JOIN tracks t ON qt.track_id = t.id
+                 WHERE qt.queue_id = ?1
+                 ORDER BY {column} IS NULL, {column} {direction}, qt.position",
+                column = column,
+                direction = if ascending { "ASC" } else { "DESC" },
+            ))?;
+            let rows = stmt.query_map(params![queue_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<Result<Vec<_>, _>>()?;
+            rows
+        };
+
+        let mut new_index = current_index;
+        for (index, (entry_id, old_position)) in sorted.iter().enumerate() {
+            tx.execute(
+                "UPDATE queue_tracks SET position = ?1 WHERE id = ?2",
+                params![index as i32, entry_id],
+            )?;
+            if *old_position == current_index {
+                new_index = index as i32;
+            }
+        }
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        tx.execute(
+            "UPDATE queues SET current_track_index = ?1, date_modified = ?2 WHERE id = ?3",
+            params![new_index, now, queue_id],
+        )?;
+
+        tx.commit()?;
+        Ok(new_index)
+    }
+
+    /// Append tracks to the end of a queue
+    /// If the queue is shuffled, also appends to the end of original_order
+    pub fn append_tracks_to_queue(
+        db: &DatabaseConnection,
+        queue_id: i64,
+        track_ids: &[i64],
+    ) -> Result<(), anyhow::Error> {
+        let conn = db.get_connection();
+        let mut conn = conn.lock().unwrap();
+
+        // Get current max position and original_order
+        let (max_position, original_order_json): (i32, Option<String>) = conn
+            .query_row(
+                "SELECT COALESCE((SELECT MAX(position) FROM queue_tracks WHERE queue_id = ?1), -1), 
+                        (SELECT original_order FROM queues WHERE id = ?1)",
+                [queue_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
+
+        let tx = conn.transaction()?;
+
+        for (index, track_id) in track_ids.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO queue_tracks (queue_id, track_id, position) VALUES (?1, ?2, ?3)",
+                params![queue_id, track_id, max_position + 1 + index as i32],
+            )?;
+        }
+
+        // If shuffled, also append to original_order
+        if let Some(original_order_str) = original_order_json {
+            let mut original_ids: Vec<i64> = serde_json::from_str(&original_order_str)?;
+            original_ids.extend(track_ids.iter().copied());
+            let updated_original_order = serde_json::to_string(&original_ids)?;
+            tx.execute(
+                "UPDATE queues SET original_order = ?1 WHERE id = ?2",
+                params![updated_original_order, queue_id],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Insert tracks after a specific position, shifting existing tracks
+    /// If the queue is shuffled, they also go after that track in original_order
+    pub fn insert_tracks_after_position(
+        db: &DatabaseConnection,
+        queue_id: i64,
+        track_ids: &[i64],
+        after_position: i32,
+    ) -> Result<(), anyhow::Error> {
+        let conn = db.get_connection();
+        let mut conn = conn.lock().unwrap();
+
+        // Track at the insertion point (before shifting) and original_order
+        let (after_track_id, original_order_json): (Option<i64>, Option<String>) = conn
+            .query_row(
+                "SELECT (SELECT track_id FROM queue_tracks WHERE queue_id = ?1 AND position = ?2),
+                        (SELECT original_order FROM queues WHERE id = ?1)",
+                params![queue_id, after_position],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
+
+        let tx = conn.transaction()?;
+
+        // Shift existing tracks after the insertion point
+        let shift_amount = track_ids.len() as i32;
+        tx.execute(
+            "UPDATE queue_tracks SET position = position + ?1 WHERE queue_id = ?2 AND position > ?3",
+            params![shift_amount, queue_id, after_position],
+        )?;
+
+        // Insert new tracks
+        for (index, track_id) in track_ids.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO queue_tracks (queue_id, track_id, position) VALUES (?1, ?2, ?3)",
+                params![queue_id, track_id, after_position + 1 + index as i32],
+            )?;
+        }
+
+        // If shuffled, insert into original_order after the same track (at the start when
+        // inserting before everything, at the end if it isn't there)
+        if let Some(original_order_str) = original_order_json {
+            let mut original_ids: Vec<i64> = serde_json::from_str(&original_order_str)?;
+            let insert_at = match after_track_id {
+                Some(after_id) => original_ids.iter()
+                    .position(|&id| id == after_id)
+                    .map(|index| index + 1)
+                    .unwrap_or(original_ids.len()),
+                None if after_position < 0 => 0,
+                None => original_ids.len(),
+            };
+            original_ids.splice(insert_at..insert_at, track_ids.iter().copied());
+            let updated_original_order = serde_json::to_string(&original_ids)?;
+            tx.execute(
+                "UPDATE queues SET original_order = ?1 WHERE id = ?2",
+                params![updated_original_order, queue_id],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Remove a track at a specific position from a queue
+    /// If the queue is shuffled, also removes the first occurrence from original_order
+    /// Returns the new current_track_index after adjustment (-1 if queue becomes empty)
+    pub fn remove_track_at_position(
+        db: &DatabaseConnection,
+        queue_id: i64,
+        position: i32,
+    ) -> Result<i32, anyhow::Error> {
+        let conn = db.get_connection();
+        let mut conn = conn.lock().unwrap();
+        
+        let tx = conn.transaction()?;
+
+        let new_index = Self::remove_queue_entry(&tx, queue_id, position)?;
+        tx.commit()?;
+        Ok(new_index)
+    }
+
+    /// Remove the queue entry at `position`, shifting later entries down and keeping
+    /// original_order and current_track_index in step. Returns the new current_track_index.
+    fn remove_queue_entry(
+        conn: &rusqlite::Connection,
+        queue_id: i64,
+        position: i32,
+    ) -> Result<i32, anyhow::Error> {
+        // Get current track index and original_order before removal
+        let (current_index, original_order_json): (i32, Option<String>) = conn.query_row(
+            "SELECT current_track_index, original_order FROM queues WHERE id = ?1",
+            params![queue_id],
+            |row| Ok((row.get(0)?, row.get(1)?))
+        )?;
+
+        // Get total track count before removal
+        let track_count: i32 = conn.query_row(
+            "SELECT COUNT(*) FROM queue_tracks WHERE queue_id = ?1",
+            params![queue_id],
+            |row| row.get(0)
+        )?;
+
+        // Get the track_id being removed (needed to update original_order)
+        let removed_track_id: i64 = conn.query_row(
+            "SELECT track_id FROM queue_tracks WHERE queue_id = ?1 AND position = ?2",
+            params![queue_id, position],
+            |row| row.get(0)
+        )?;
+
+        // Delete the track at the specified position
+        conn.execute(
+            "DELETE FROM queue_tracks WHERE queue_id = ?1 AND position = ?2",
+            params![queue_id, position],
+        )?;
+
+        // Shift all positions after the removed track down by 1
+        conn.execute(
+            "UPDATE queue_tracks SET position = position - 1 WHERE queue_id = ?1 AND position > ?2",
+            params![queue_id, position],
+        )?;
+
+        // If shuffled, remove the first occurrence from original_order
+        if let Some(original_order_str) = original_order_json {
+            let mut original_ids: Vec<i64> = serde_json::from_str(&original_order_str)?;
+            if let Some(pos) = original_ids.iter().position(|&id| id == removed_track_id) {
+                original_ids.remove(pos);
+                let updated_original_order = serde_json::to_string(&original_ids)?;
+                conn.execute(
+                    "UPDATE queues SET original_order = ?1 WHERE id = ?2",
+                    params![updated_original_order, queue_id],
+                )?;
+            }
+        }
+
+        // Calculate new current index
+        let new_index = if track_count <= 1 {
+            // Queue becomes empty
+            0
+        } else if position < current_index {
+            // Removed before current: shift current index down
+            current_index - 1
+        } else if position == current_index {
+            // Removed current track: stay at same position (next track slides into this position)
+            // But clamp to valid range (in case we removed the last track)
+            std::cmp::min(current_index, track_count - 2)
+        } else {
+            // Removed after current: no change needed
+            current_index
+        };
+
+        // Update current_track_index in database
+        conn.execute(
+            "UPDATE queues SET current_track_index = ?1 WHERE id = ?2",
+            params![new_index, queue_id],
+        )?;
+
+        Ok(new_index)
+    }
+}
+
+/// (queue_tracks.id, track_id) of every row of a queue, in position order
+fn queue_rows_in_order(
+    conn: &rusqlite::Connection,
+    queue_id: i64,
+) -> Result<Vec<(i64, i64)>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT id, track_id FROM queue_tracks WHERE queue_id = ?1 ORDER BY position"
+    )?;
+    let rows = stmt.query_map([queue_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Index in `rows` of the playing copy of `current_track_id`: the row at the queue's
+/// current index when it holds that track, otherwise the track's first row
+fn current_queue_row(rows: &[(i64, i64)], current_index: i32, current_track_id: Option<i64>) -> Option<usize> {
+    let track_id = current_track_id?;
+    match rows.get(current_index.max(0) as usize) {
+        Some(&(_, id)) if current_index >= 0 && id == track_id => Some(current_index as usize),
+        _ => rows.iter().position(|&(_, id)| id == track_id),
+    }
+}
+
+/// Seeded Fisher-Yates shuffle of the queue positions, with the anchor first. This is the
+/// order toggle_queue_shuffle stores. Index = shuffled position, value = original position.
+/// The same seed, length and anchor always give the same permutation.
+fn shuffled_permutation(seed: i64, queue_length: i32, anchor_position: i32) -> Vec<i32> {
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
+    
+    let anchor = (0..queue_length).contains(&anchor_position).then_some(anchor_position);
+    let mut order: Vec<i32> = (0..queue_length.max(0))
+        .filter(|&position| Some(position) != anchor)
+        .collect();
+    
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
+    order.shuffle(&mut rng);
+    
+    if let Some(anchor) = anchor {
+        order.insert(0, anchor);
+    }
+    order
+}
+
+/// Map every original queue position to its position in the seeded shuffle order
+/// (the inverse of shuffled_permutation). Index = original position.
+fn shuffled_position_map(seed: i64, queue_length: i32, anchor_position: i32) -> Vec<i32> {
+    let permutation = shuffled_permutation(seed, queue_length, anchor_position);
+    let mut map = vec![0; permutation.len()];
+    for (shuffled, &original) in permutation.iter().enumerate() {
+        map[original as usize] = shuffled as i32;
+    }
+    map
+}
+
+/// A folder path as shown in the folder tree: '/' separators, no trailing separator
+fn display_folder_path(path: &str) -> String {
+    let normalized = path.replace('\\', "/");
+    let trimmed = normalized.trim_end_matches('/');
+    if trimmed.is_empty() {
+        "/".to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// Paths with a drive letter or backslashes, whose folder names are case-insensitive
+fn is_windows_path(path: &str) -> bool {
+    path.contains('\\') || path.as_bytes().get(1) == Some(&b':')
+}
+
+/// The path components of `file_path` below `directory` (the file name last), or None if
+/// the file isn't inside it. Either separator works and trailing ones are ignored, and
+/// Windows paths compare case-insensitively, so `C:\Music` and `C:\music\` are the same.
+fn relative_to_directory<'a>(file_path: &'a str, directory: &str) -> Option<Vec<&'a str>> {
+    let windows = is_windows_path(directory);
+    let mut components = file_path.split(['/', '\\']).filter(|c| !c.is_empty());
+    
+    for expected in directory.split(['/', '\\']).filter(|c| !c.is_empty()) {
+        let component = components.next()?;
+        let same = if windows {
+            component.to_lowercase() == expected.to_lowercase()
+        } else {
+            component == expected
+        };
+        if !same {
+            return None;
+        }
+    }
+    
+    Some(components.collect())
+}
+
+/// LIKE pattern (with `ESCAPE '\'`) matching at least every path relative_to_directory puts
+/// inside `directory`, to narrow a query before that exact check. Separators and non-ASCII
+/// characters match any character, and LIKE already ignores ASCII case.
+fn directory_like_pattern(directory: &str) -> String {
+    // Leading separators: "/music", but also UNC paths like \\server\share
+    let mut pattern = String::from(if directory.starts_with(['/', '\\']) { "%" } else { "" });
+    for component in directory.split(['/', '\\']).filter(|c| !c.is_empty()) {
+        for c in component.chars() {
+            match c {
+                '%' | '_' => {
+                    pattern.push('\\');
+                    pattern.push(c);
+                }
+                c if !c.is_ascii() => pattern.push('_'),
+                c => pattern.push(c),
+            }
+        }
+        pattern.push('_');
+    }
+    pattern.push('%');
+    pattern
+}
+
+/// Intermediate tree node used while grouping paths
+#[derive(Default)]
+struct FolderBuilder {
+    track_count: i32,
+    children: std::collections::BTreeMap<String, FolderBuilder>,
+}
+
+impl FolderBuilder {
+    fn into_node(self, name: String, path: String) -> FolderNode {
+        let children = self.children
+            .into_iter()
+            .map(|(child_name, child)| {
+                let child_path = format!("{}/{}", path.trim_end_matches('/'), child_name);
+                child.into_node(child_name, child_path)
+            })
+            .collect();
+        
+        FolderNode {
+            name,
+            path,
+            track_count: self.track_count,
+            children,
+        }
+    }
+}
+
+/// Group file paths into a folder tree rooted at the given scan paths
+fn build_folder_tree(scan_paths: &[String], file_paths: &[String]) -> Vec<FolderNode> {
+    let mut trees: std::collections::BTreeMap<String, FolderBuilder> = std::collections::BTreeMap::new();
+    
+    for file_path in file_paths {
+        // Longest matching scan path wins; files outside all of them are grouped by folder
+        let in_scan_path = scan_paths.iter()
+            .filter_map(|root| {
+                let rest = relative_to_directory(file_path, root).filter(|rest| !rest.is_empty())?;
+                Some((display_folder_path(root), rest))
+            })
+            .max_by_key(|(root, _)| root.len());
+        let (root, folders) = match in_scan_path {
+            Some((root, rest)) => (root, rest[..rest.len() - 1].to_vec()),
+            None => {
+                let Some((parent, _file_name)) = file_path.rsplit_once(['/', '\\']) else {
+                    continue;
+                };
+                (display_folder_path(parent), Vec::new())
+            }
+        };
+        
+        let mut node = trees.entry(root).or_default();
+        node.track_count += 1;
+        
+        for component in folders {
+            node = node.children.entry(component.to_string()).or_default();
+            node.track_count += 1;
+        }
+    }
+    
+    trees.into_iter()
+        .map(|(root, builder)| {
+            let name = root.rsplit('/').find(|c| !c.is_empty()).unwrap_or(&root).to_string();
+            builder.into_node(name, root)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_test_track, test_db};
+
+    fn create_test_queue(db: &DatabaseConnection, track_count: usize) -> (i64, Vec<i64>) {
+        let track_ids: Vec<i64> = (0..track_count)
+            .map(|i| insert_test_track(db, &format!("/music/track{}.mp3", i)))
+            .collect();
+        let queue_id = DbOperations::create_queue(db, "Test Queue").unwrap();
+        DbOperations::add_tracks_to_queue(db, queue_id, &track_ids).unwrap();
+        (queue_id, track_ids)
+    }
+
+    #[test]
+    fn test_queue_playback_modes_round_trip() {
+        let db = test_db();
+        let (queue_id, track_ids) = create_test_queue(&db, 10);
+
+        let modes = DbOperations::get_queue_playback_modes(&db, queue_id).unwrap();
+        assert!(!modes.shuffle_on);
+        assert_eq!(modes.repeat_mode, RepeatMode::Off);
+
+        DbOperations::update_queue_current_index(&db, queue_id, 3).unwrap();
+        let modes = DbOperations::set_queue_playback_modes(&db, queue_id, true, RepeatMode::All).unwrap();
+        assert!(modes.shuffle_on);
+        assert_eq!(modes.shuffle_anchor, 3);
+        assert_eq!(modes.repeat_mode, RepeatMode::All);
+        assert_eq!(DbOperations::get_queue_playback_modes(&db, queue_id).unwrap(), modes);
+
+        // Current track moved to the front of the shuffled queue
+        let current = DbOperations::get_queue_track_at_position(&db, queue_id, 0).unwrap().unwrap();
+        assert_eq!(current.id, track_ids[3]);
+
+        let modes = DbOperations::set_queue_playback_modes(&db, queue_id, false, RepeatMode::One).unwrap();
+        assert!(!modes.shuffle_on);
+        assert_eq!(modes.repeat_mode, RepeatMode::One);
+        assert_eq!(DbOperations::get_queue_current_index(&db, queue_id).unwrap(), 3);
+
+        // Stored as 0 = off, 1 = repeat all, 2 = repeat one
+        let stored: i64 = db.get_connection().lock().unwrap()
+            .query_row("SELECT repeat_mode FROM queues WHERE id = ?1", [queue_id], |row| row.get(0))
+            .unwrap();
+        assert_eq!(stored, 2);
+    }
+
+    #[test]
+    fn test_find_track_position_and_jump_with_duplicates() {
+        let db = test_db();
+        let a = insert_test_track(&db, "/music/a.mp3");
+        let b = insert_test_track(&db, "/music/b.mp3");
+        let queue_id = DbOperations::create_queue(&db, "Dupes").unwrap();
+        DbOperations::add_tracks_to_queue(&db, queue_id, &[a, b]).unwrap();
+        DbOperations::append_tracks_to_queue(&db, queue_id, &[a]).unwrap();
+
+        assert_eq!(DbOperations::find_track_position_in_queue(&db, queue_id, a, 0).unwrap(), Some(0));
+        assert_eq!(DbOperations::find_track_position_in_queue(&db, queue_id, a, 1).unwrap(), Some(2));
+        assert_eq!(DbOperations::find_track_position_in_queue(&db, queue_id, a, 2).unwrap(), None);
+        assert_eq!(DbOperations::find_track_position_in_queue(&db, queue_id, b, 0).unwrap(), Some(1));
+
+        let track = DbOperations::queue_jump_to(&db, queue_id, 2).unwrap();
+        assert_eq!(track.id, a);
+        assert_eq!(DbOperations::get_queue_current_index(&db, queue_id).unwrap(), 2);
+
+        assert!(DbOperations::queue_jump_to(&db, queue_id, 5).is_err());
+        assert_eq!(DbOperations::get_queue_current_index(&db, queue_id).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_build_folder_tree() {
+        let scan_paths = vec!["C:\\Music\\".to_string(), "/home/me/music".to_string()];
+        let files: Vec<String> = vec![
+            "C:\\Music\\Rock\\Album A\\01.mp3",
+            "C:\\Music\\Rock\\Album A\\02.mp3",
+            "C:\\Music\\Rock\\Album B\\01.flac",
+            "C:\\Music\\Jazz\\01.mp3",
+            "C:\\Music\\loose.mp3",
+            "/home/me/music/Pop/hit.ogg",
+            "/tmp/dropped/song.mp3",
+        ].into_iter().map(String::from).collect();
+
+        let tree = build_folder_tree(&scan_paths, &files);
+        let paths: Vec<&str> = tree.iter().map(|n| n.path.as_str()).collect();
+        assert_eq!(paths, vec!["/home/me/music", "/tmp/dropped", "C:/Music"]);
+
+        let music = &tree[2];
+        assert_eq!(music.name, "Music");
+        assert_eq!(music.track_count, 5);
+        let children: Vec<(&str, i32)> = music.children.iter().map(|c| (c.name.as_str(), c.track_count)).collect();
+        assert_eq!(children, vec![("Jazz", 1), ("Rock", 3)]);
+
+        let rock = &music.children[1];
+        assert_eq!(rock.path, "C:/Music/Rock");
+        assert_eq!(rock.children[0].path, "C:/Music/Rock/Album A");
+        assert_eq!(rock.children[0].track_count, 2);
+        assert_eq!(rock.children[1].track_count, 1);
+
+        assert_eq!(tree[0].children[0].name, "Pop");
+        assert_eq!(tree[1].track_count, 1);
+        assert!(tree[1].children.is_empty());
+    }
+
+    #[test]
+    fn test_single_track_queue_is_reused() {
+        let db = test_db();
+        let (other_queue, track_ids) = create_test_queue(&db, 3);
+
+        let queue_id = DbOperations::create_single_track_queue(&db, track_ids[1]).unwrap();
+        assert_ne!(queue_id, other_queue);
+        assert_eq!(DbOperations::get_queue_length(&db, queue_id).unwrap(), 1);
+        assert_eq!(DbOperations::get_active_queue(&db).unwrap().unwrap().id, queue_id);
+        assert_eq!(DbOperations::get_queue_current_index(&db, queue_id).unwrap(), 0);
+
+        // Playing another track reuses the same queue
+        DbOperations::set_active_queue(&db, other_queue).unwrap();
+        let again = DbOperations::create_single_track_queue(&db, track_ids[2]).unwrap();
+        assert_eq!(again, queue_id);
+        assert_eq!(DbOperations::get_queue_length(&db, queue_id).unwrap(), 1);
+        assert_eq!(DbOperations::get_queue_track_at_position(&db, queue_id, 0).unwrap().unwrap().id, track_ids[2]);
+        assert_eq!(DbOperations::get_active_queue(&db).unwrap().unwrap().id, queue_id);
+        assert_eq!(DbOperations::get_all_queues(&db).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_single_track_queue_leaves_same_named_user_queue_alone() {
+        let db = test_db();
+        let track_ids: Vec<i64> = (0..3).map(|i| insert_test_track(&db, &format!("/music/{}.mp3", i))).collect();
+        let user_queue = DbOperations::create_queue(&db, AD_HOC_QUEUE_NAME).unwrap();
+        DbOperations::add_tracks_to_queue(&db, user_queue, &track_ids).unwrap();
+
+        let queue_id = DbOperations::create_single_track_queue(&db, track_ids[0]).unwrap();
+        assert_ne!(queue_id, user_queue);
+        assert_eq!(DbOperations::get_queue_length(&db, user_queue).unwrap(), 3);
+        assert_eq!(DbOperations::find_queue_by_name(&db, "Now Playing (2)").unwrap(), Some(queue_id));
+
+        assert_eq!(DbOperations::create_single_track_queue(&db, track_ids[1]).unwrap(), queue_id);
+        assert_eq!(DbOperations::get_queue_length(&db, user_queue).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_prune_old_queues_keeps_newest_and_active() {
+        let db = test_db();
+        let track_id = insert_test_track(&db, "/music/a.mp3");
+        let keep = 3;
+        let queue_ids: Vec<i64> = (0..keep + 2)
+            .map(|i| {
+                let id = DbOperations::create_queue(&db, &format!("Queue {}", i)).unwrap();
+                DbOperations::add_tracks_to_queue(&db, id, &[track_id]).unwrap();
+                id
+            })
+            .collect();
+        // Make the oldest queue the active one - it must survive
+        DbOperations::set_active_queue(&db, queue_ids[0]).unwrap();
+
+        let removed = DbOperations::prune_old_queues(&db, keep).unwrap();
+        assert_eq!(removed, 1);
+
+        let mut remaining: Vec<i64> = DbOperations::get_all_queues(&db).unwrap().iter().map(|q| q.id).collect();
+        remaining.sort();
+        let mut expected = vec![queue_ids[0]];
+        expected.extend(&queue_ids[2..]);
+        assert_eq!(remaining, expected);
+
+        // Tracks of the pruned queue are gone too
+        let conn = db.get_connection();
+        let orphaned: i64 = conn.lock().unwrap().query_row(
+            "SELECT COUNT(*) FROM queue_tracks WHERE queue_id = ?1", [queue_ids[1]], |row| row.get(0)
+        ).unwrap();
+        assert_eq!(orphaned, 0);
+    }
+
+    #[test]
+    fn test_annotated_queue_sequential() {
+        let db = test_db();
+        let (queue_id, _) = create_test_queue(&db, 5);
+        DbOperations::update_queue_current_index(&db, queue_id, 2).unwrap();
+
+        let annotated = DbOperations::get_queue_tracks_annotated(&db, queue_id).unwrap();
+        let positions: Vec<i32> = annotated.iter().map(|t| t.queue_position).collect();
+        assert_eq!(positions, vec![0, 1, 2, 3, 4]);
+        let current: Vec<bool> = annotated.iter().map(|t| t.is_current).collect();
+        assert_eq!(current, vec![false, false, true, false, false]);
+        let played: Vec<bool> = annotated.iter().map(|t| t.is_played).collect();
+        assert_eq!(played, vec![true, true, false, false, false]);
+    }
+
+    #[test]
+    fn test_annotated_queue_shuffled() {
+        let db = test_db();
+        let (queue_id, track_ids) = create_test_queue(&db, 7);
+        DbOperations::update_queue_current_index(&db, queue_id, 4).unwrap();
+        let (_, current_index) = DbOperations::toggle_queue_shuffle(&db, queue_id, Some(track_ids[4])).unwrap();
+
+        // Nothing is played yet in the new order, which starts at the current track
+        let annotated = DbOperations::get_queue_tracks_annotated(&db, queue_id).unwrap();
+        assert_eq!(current_index, 0);
+        assert_eq!(annotated[0].track.id, track_ids[4]);
+        assert!(annotated[0].is_current);
+        assert!(annotated.iter().all(|t| !t.is_played));
+
+        // Two tracks further on, the first two entries of the shuffled order are played
+        DbOperations::update_queue_current_index(&db, queue_id, 2).unwrap();
+        let annotated = DbOperations::get_queue_tracks_annotated(&db, queue_id).unwrap();
+        let played: Vec<bool> = annotated.iter().map(|t| t.is_played).collect();
+        assert_eq!(played, vec![true, true, false, false, false, false, false]);
+        assert_eq!(annotated.iter().position(|t| t.is_current), Some(2));
+    }
+
+    fn set_played(db: &DatabaseConnection, track_id: i64, album: &str, last_played: i64) {
+        {
+            let conn = db.get_connection();
+            let conn = conn.lock().unwrap();
+            conn.execute(
+                "UPDATE tracks SET album = ?1, last_played = ?2, play_count = 1 WHERE id = ?3",
+                params![album, last_played, track_id],
+            ).unwrap();
+        }
+        DbOperations::sync_albums(db).unwrap();
+    }
+
+    #[test]
+    fn test_recently_played_albums() {
+        let db = test_db();
+        let ids: Vec<i64> = (0..5).map(|i| insert_test_track(&db, &format!("/music/{}.mp3", i))).collect();
+        set_played(&db, ids[0], "Alpha", 1_000);
+        set_played(&db, ids[1], "Alpha", 5_000);
+        set_played(&db, ids[2], "Beta", 3_000);
+        set_played(&db, ids[3], "Gamma", 4_000);
+        // Never played
+        {
+            let conn = db.get_connection();
+            conn.lock().unwrap().execute("UPDATE tracks SET album = 'Delta' WHERE id = ?1", [ids[4]]).unwrap();
+        }
+        DbOperations::sync_albums(&db).unwrap();
+
+        let albums = DbOperations::get_recently_played_albums(&db, 10).unwrap();
+        let names: Vec<&str> = albums.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, vec!["Alpha", "Gamma", "Beta"]);
+        assert_eq!(albums[0].song_count, 2);
+
+        // IDs line up with the album list
+        let all_albums = DbOperations::get_all_albums(&db, AlbumSort::NameAsc).unwrap();
+        for album in &albums {
+            let listed = all_albums.iter().find(|a| a.name == album.name).unwrap();
+            assert_eq!(listed.id, album.id);
+        }
+
+        assert_eq!(DbOperations::get_recently_played_albums(&db, 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_tracks_played_on_day() {
+        use chrono::{Datelike, Local, TimeZone};
+
+        let db = test_db();
+        let ids: Vec<i64> = (0..4).map(|i| insert_test_track(&db, &format!("/music/{}.mp3", i))).collect();
+        let local = |y, m, d, h| Local.with_ymd_and_hms(y, m, d, h, 0, 0).unwrap().timestamp();
+        let this_year = Local::now().year();
+        // Late evening local time must still count as the 15th
+        set_played(&db, ids[0], "A", local(2019, 3, 15, 23));
+        set_played(&db, ids[1], "A", local(2021, 3, 15, 0));
+        set_played(&db, ids[2], "A", local(2021, 3, 16, 12));
+        // Played on the same day this year - not a memory yet
+        set_played(&db, ids[3], "A", local(this_year, 3, 15, 12));
+
+        let tracks = DbOperations::get_tracks_played_on_day(&db, 3, 15).unwrap();
+        let found: Vec<i64> = tracks.iter().map(|t| t.id).collect();
+        assert_eq!(found, vec![ids[1], ids[0]]);
+
+        assert!(DbOperations::get_tracks_played_on_day(&db, 13, 1).is_err());
+    }
+
+    #[test]
+    fn test_queue_duration_totals() {
+        let db = test_db();
+        // Every test track is 3 minutes long
+        let (queue_id, track_ids) = create_test_queue(&db, 5);
+        DbOperations::update_queue_current_index(&db, queue_id, 3).unwrap();
+
+        let duration = DbOperations::get_queue_duration(&db, queue_id).unwrap();
+        assert_eq!(duration, QueueDuration { total_ms: 900_000, remaining_ms: 360_000, is_approximate: false });
+
+        // Unknown durations are left out and flagged
+        {
+            let conn = db.get_connection();
+            conn.lock().unwrap().execute("UPDATE tracks SET duration_ms = NULL WHERE id = ?1", [track_ids[4]]).unwrap();
+        }
+        let duration = DbOperations::get_queue_duration(&db, queue_id).unwrap();
+        assert_eq!(duration, QueueDuration { total_ms: 720_000, remaining_ms: 180_000, is_approximate: true });
+
+        let empty_queue = DbOperations::create_queue(&db, "Empty").unwrap();
+        let duration = DbOperations::get_queue_duration(&db, empty_queue).unwrap();
+        assert_eq!(duration, QueueDuration { total_ms: 0, remaining_ms: 0, is_approximate: false });
+    }
+
+    #[test]
+    fn test_queue_remaining_duration_follows_play_order() {
+        let db = test_db();
+        let (queue_id, track_ids) = create_test_queue(&db, 6);
+        {
+            let conn = db.get_connection();
+            let conn = conn.lock().unwrap();
+            for (i, id) in track_ids.iter().enumerate() {
+                conn.execute("UPDATE tracks SET duration_ms = ?1 WHERE id = ?2", params![(i as i64 + 1) * 1000, id]).unwrap();
+            }
+            conn.execute("UPDATE tracks SET duration_ms = NULL WHERE id = ?1", [track_ids[5]]).unwrap();
+        }
+
+        assert_eq!(DbOperations::get_queue_total_duration_ms(&db, queue_id).unwrap(), 15_000);
+        assert_eq!(DbOperations::get_queue_remaining_duration_ms(&db, queue_id, 3).unwrap(), 9_000);
+
+        // Shuffled, the remaining time is that of the tracks now at or after the position
+        DbOperations::toggle_queue_shuffle(&db, queue_id, Some(track_ids[0])).unwrap();
+        let order: Vec<i64> = DbOperations::get_queue_tracks(&db, queue_id).unwrap().iter().map(|t| t.id).collect();
+        let expected: i64 = order[3..].iter()
+            .map(|id| track_ids.iter().position(|t| t == id).unwrap() as i64)
+            .filter(|&i| i != 5)
+            .map(|i| (i + 1) * 1000)
+            .sum();
+        assert_eq!(DbOperations::get_queue_remaining_duration_ms(&db, queue_id, 3).unwrap(), expected);
+        assert_eq!(DbOperations::get_queue_total_duration_ms(&db, queue_id).unwrap(), 15_000);
+
+        let empty_queue = DbOperations::create_queue(&db, "Empty").unwrap();
+        assert_eq!(DbOperations::get_queue_total_duration_ms(&db, empty_queue).unwrap(), 0);
+        assert_eq!(DbOperations::get_queue_remaining_duration_ms(&db, empty_queue, 0).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_library_status() {
+        let db = test_db();
+        let status = DbOperations::get_library_status(&db).unwrap();
+        assert_eq!(status, LibraryStatus { has_scan_paths: false, track_count: 0, last_scan_at: None });
+
+        // Paths configured but nothing scanned yet
+        let path_id = DbOperations::add_scan_path(&db, "/music").unwrap();
+        let status = DbOperations::get_library_status(&db).unwrap();
+        assert_eq!(status, LibraryStatus { has_scan_paths: true, track_count: 0, last_scan_at: None });
+
+        insert_test_track(&db, "/music/a.mp3");
+        insert_test_track(&db, "/music/b.mp3");
+        DbOperations::update_scan_path_last_scanned(&db, path_id).unwrap();
+        let status = DbOperations::get_library_status(&db).unwrap();
+        assert!(status.has_scan_paths);
+        assert_eq!(status.track_count, 2);
+        assert!(status.last_scan_at.is_some());
+    }
+
+    #[test]
+    fn test_album_stats() {
+        let db = test_db();
+        let first = insert_test_track(&db, "/music/album/1.mp3");
+        let second = insert_test_track(&db, "/music/album/2.mp3");
+        let other = insert_test_track(&db, "/music/other.mp3");
+        // Same album name, different artists: two separate albums
+        {
+            let conn = db.get_connection();
+            let conn = conn.lock().unwrap();
+            conn.execute(
+                "UPDATE tracks SET album = 'Greatest Hits', artist = 'First' WHERE id IN (?1, ?2)",
+                [first, second],
+            ).unwrap();
+            conn.execute("UPDATE tracks SET album = 'Greatest Hits', artist = 'Second' WHERE id = ?1", [other]).unwrap();
+        }
+        let album_id = DbOperations::assign_track_album(&db, first).unwrap().unwrap();
+        assert_eq!(DbOperations::assign_track_album(&db, second).unwrap(), Some(album_id));
+        let other_album_id = DbOperations::assign_track_album(&db, other).unwrap().unwrap();
+        assert_ne!(album_id, other_album_id);
+
+        let stats = DbOperations::get_album_stats(&db, album_id).unwrap();
+        assert_eq!(stats, AlbumStats { play_count_sum: 0, last_played_max: None, track_count: 2, total_duration_ms: 360_000 });
+
+        for _ in 0..3 {
+            DbOperations::record_track_play(&db, first, 180).unwrap();
+        }
+        DbOperations::record_track_play(&db, second, 180).unwrap();
+        let stats = DbOperations::get_album_stats(&db, album_id).unwrap();
+        assert_eq!(stats.play_count_sum, 4);
+        assert!(stats.last_played_max.is_some());
+        assert_eq!(DbOperations::get_album_stats(&db, other_album_id).unwrap().track_count, 1);
+
+        let missing = DbOperations::get_album_stats(&db, -1).unwrap();
+        assert_eq!(missing, AlbumStats { play_count_sum: 0, last_played_max: None, track_count: 0, total_duration_ms: 0 });
+    }
+
+    #[test]
+    fn test_session_state_round_trip() {
+        let db = test_db();
+        let first_launch = DbOperations::get_session_state(&db, 0.0).unwrap();
+        assert!(first_launch.queue.is_none());
+
+        let (queue_id, _) = create_test_queue(&db, 10);
+        let modes = DbOperations::set_queue_playback_modes(&db, queue_id, true, RepeatMode::All).unwrap();
+        DbOperations::update_queue_current_index(&db, queue_id, 4).unwrap();
+        DbOperations::update_queue_position(&db, queue_id, 83_500).unwrap();
+
+        let session = DbOperations::get_session_state(&db, -12.5).unwrap();
+        assert_eq!(session.queue.as_ref().map(|q| q.id), Some(queue_id));
+        assert_eq!(session.current_track_index, 4);
+        assert_eq!(session.current_position_ms, 83_500);
+        assert_eq!(session.shuffle_seed, modes.shuffle_seed);
+        assert_eq!(session.shuffle_anchor, modes.shuffle_anchor);
+        assert_eq!(session.repeat_mode, RepeatMode::All);
+        assert_eq!(session.volume_db, -12.5);
+
+        // Moving to another track drops the old track's position
+        DbOperations::update_queue_current_index(&db, queue_id, 5).unwrap();
+        assert_eq!(DbOperations::get_session_state(&db, 0.0).unwrap().current_position_ms, 0);
+    }
+
+    #[test]
+    fn test_reset_statistics_keeps_tracks() {
+        let db = test_db();
+        let first = insert_test_track(&db, "/music/album/1.mp3");
+        let second = insert_test_track(&db, "/music/album/2.mp3");
+        let other = insert_test_track(&db, "/music/other.mp3");
+        {
+            let conn = db.get_connection();
+            conn.lock().unwrap().execute(
+                "UPDATE tracks SET album = 'Album' WHERE id IN (?1, ?2)",
+                [first, second],
+            ).unwrap();
+        }
+        let album_id = DbOperations::assign_track_album(&db, first).unwrap().unwrap();
+        DbOperations::assign_track_album(&db, second).unwrap();
+        for track_id in [first, second, other] {
+            DbOperations::record_track_play(&db, track_id, 120).unwrap();
+        }
+
+        // Scoped to a single track, then an album
+        assert_eq!(DbOperations::reset_track_statistics(&db, other).unwrap(), 1);
+        let track = DbOperations::get_track_by_id(&db, other).unwrap().unwrap();
+        assert_eq!((track.play_count, track.last_played, track.play_time_seconds), (0, None, 0));
+        assert_eq!(DbOperations::get_track_by_id(&db, first).unwrap().unwrap().play_count, 1);
+
+        assert_eq!(DbOperations::reset_album_statistics(&db, album_id).unwrap(), 2);
+        assert_eq!(DbOperations::get_album_stats(&db, album_id).unwrap().play_count_sum, 0);
+
+        DbOperations::record_track_play(&db, other, 120).unwrap();
+        assert_eq!(DbOperations::reset_statistics(&db).unwrap(), 3);
+        let tracks = DbOperations::get_all_tracks(&db).unwrap();
+        assert_eq!(tracks.len(), 3);
+        assert!(tracks.iter().all(|t| t.play_count == 0 && t.last_played.is_none() && t.play_time_seconds == 0));
+    }
+
+    #[test]
+    fn test_sort_queue() {
+        let db = test_db();
+        let (queue_id, track_ids) = create_test_queue(&db, 4);
+        {
+            let conn = db.get_connection();
+            let conn = conn.lock().unwrap();
+            for (track_id, title, duration) in [
+                (track_ids[0], "delta", Some(200_000)),
+                (track_ids[1], "Bravo", None),
+                (track_ids[2], "charlie", Some(300_000)),
+                (track_ids[3], "alpha", Some(100_000)),
+            ] {
+                conn.execute(
+                    "UPDATE tracks SET title = ?1, duration_ms = ?2 WHERE id = ?3",
+                    params![title, duration, track_id],
+                ).unwrap();
+            }
+        }
+        // "charlie" is playing
+        DbOperations::update_queue_current_index(&db, queue_id, 2).unwrap();
+
+        let queue_order = |db: &DatabaseConnection| -> Vec<i64> {
+            DbOperations::get_queue_tracks(db, queue_id).unwrap().iter().map(|t| t.id).collect()
+        };
+
+        let current = DbOperations::sort_queue(&db, queue_id, "title", true).unwrap();
+        assert_eq!(queue_order(&db), vec![track_ids[3], track_ids[1], track_ids[2], track_ids[0]]);
+        assert_eq!(current, 2);
+        assert_eq!(DbOperations::get_queue_current_index(&db, queue_id).unwrap(), 2);
+
+        // Longest first, the track without a duration last
+        let current = DbOperations::sort_queue(&db, queue_id, "duration", false).unwrap();
+        assert_eq!(queue_order(&db), vec![track_ids[2], track_ids[0], track_ids[3], track_ids[1]]);
+        assert_eq!(current, 0);
+        assert_eq!(DbOperations::get_queue_current_index(&db, queue_id).unwrap(), 0);
+
+        assert!(DbOperations::sort_queue(&db, queue_id, "rating", true).is_err());
+    }
+
+    #[test]
+    fn test_increment_play_count() {
+        let db = test_db();
+        let track_id = insert_test_track(&db, "/music/played.mp3");
+
+        assert!(DbOperations::increment_play_count(&db, track_id).unwrap());
+        assert!(DbOperations::increment_play_count(&db, track_id).unwrap());
+        assert!(!DbOperations::increment_play_count(&db, track_id + 1).unwrap());
+
+        let track = DbOperations::get_track_by_id(&db, track_id).unwrap().unwrap();
+        assert_eq!(track.play_count, 2);
+        assert!(track.last_played.is_some());
+        // Only counted, no play time added
+        assert_eq!(track.play_time_seconds, 0);
+
+        // The time of that listen is added once it ends, without counting it again
+        DbOperations::add_play_time(&db, track_id, 180).unwrap();
+        let track = DbOperations::get_track_by_id(&db, track_id).unwrap().unwrap();
+        assert_eq!((track.play_count, track.play_time_seconds), (2, 180));
+    }
+
+    #[test]
+    fn test_skip_counts_and_never_finished_tracks() {
+        let db = test_db();
+        let skipped = insert_test_track(&db, "/music/skipped.mp3");
+        let once = insert_test_track(&db, "/music/once.mp3");
+        let liked = insert_test_track(&db, "/music/liked.mp3");
+
+        for _ in 0..3 {
+            assert!(DbOperations::increment_skip_count(&db, skipped).unwrap());
+        }
+        DbOperations::increment_skip_count(&db, once).unwrap();
+        for _ in 0..2 {
+            DbOperations::increment_skip_count(&db, liked).unwrap();
+            DbOperations::increment_play_count(&db, liked).unwrap();
+        }
+        assert!(!DbOperations::increment_skip_count(&db, liked + 1).unwrap());
+
+        let track = DbOperations::get_track_by_id(&db, skipped).unwrap().unwrap();
+        assert_eq!(track.skip_count, 3);
+        assert_eq!(track.play_count, 0);
+
+        // A single skip is noise, and a track played as often as skipped does get finished
+        let never_finished: Vec<i64> = DbOperations::get_never_finished_tracks(&db).unwrap()
+            .into_iter().map(|t| t.id).collect();
+        assert_eq!(never_finished, vec![skipped]);
+
+        DbOperations::reset_track_statistics(&db, skipped).unwrap();
+        assert_eq!(DbOperations::get_track_by_id(&db, skipped).unwrap().unwrap().skip_count, 0);
+    }
+
+    #[test]
+    fn test_add_tracks_to_queue_keeps_repeated_tracks_in_order() {
+        let db = test_db();
+        let a = insert_test_track(&db, "/music/a.mp3");
+        let b = insert_test_track(&db, "/music/b.mp3");
+        let c = insert_test_track(&db, "/music/c.mp3");
+
+        // Long enough to span several insert batches
+        let mut submitted = Vec::new();
+        for i in 0..600 {
+            submitted.push([a, b, a, c][i % 4]);
+        }
+        let queue_id = DbOperations::create_queue(&db, "Repeats").unwrap();
+        DbOperations::add_tracks_to_queue(&db, queue_id, &submitted).unwrap();
+
+        let queued: Vec<i64> = DbOperations::get_queue_tracks(&db, queue_id).unwrap()
+            .iter()
+            .map(|t| t.id)
+            .collect();
+        assert_eq!(queued, submitted);
+    }
+
+    #[test]
+    fn test_shuffle_round_trip_keeps_repeated_tracks() {
+        let db = test_db();
+        let a = insert_test_track(&db, "/music/a.mp3");
+        let b = insert_test_track(&db, "/music/b.mp3");
+        let queue_id = DbOperations::create_queue(&db, "Repeats").unwrap();
+        DbOperations::add_tracks_to_queue(&db, queue_id, &[a, b, a]).unwrap();
+        DbOperations::update_queue_current_index(&db, queue_id, 2).unwrap();
+
+        let positions = |db: &DatabaseConnection| -> Vec<i32> {
+            let conn = db.get_connection();
+            let conn = conn.lock().unwrap();
+            let mut stmt = conn.prepare(
+                "SELECT position FROM queue_tracks WHERE queue_id = ?1 ORDER BY position"
+            ).unwrap();
+            let positions = stmt.query_map([queue_id], |row| row.get(0)).unwrap()
+                .collect::<Result<Vec<_>, _>>().unwrap();
+            positions
+        };
+
+        let (_, current_index) = DbOperations::toggle_queue_shuffle(&db, queue_id, Some(a)).unwrap();
+        assert_eq!(current_index, 0);
+        assert_eq!(positions(&db), vec![0, 1, 2]);
+        let mut shuffled: Vec<i64> = DbOperations::get_queue_tracks(&db, queue_id).unwrap()
+            .iter()
+            .map(|t| t.id)
+            .collect();
+        assert_eq!(shuffled[0], a);
+        shuffled.sort();
+        assert_eq!(shuffled, {
+            let mut ids = vec![a, b, a];
+            ids.sort();
+            ids
+        });
+        assert_eq!(DbOperations::get_queue_length(&db, queue_id).unwrap(), 3);
+
+        let (_, current_index) = DbOperations::toggle_queue_shuffle(&db, queue_id, Some(a)).unwrap();
+        assert_eq!(current_index, 0);
+        assert_eq!(positions(&db), vec![0, 1, 2]);
+        let restored: Vec<i64> = DbOperations::get_queue_tracks(&db, queue_id).unwrap()
+            .iter()
+            .map(|t| t.id)
+            .collect();
+        assert_eq!(restored, vec![a, b, a]);
+    }
+
+    #[test]
+    fn test_same_named_albums_by_different_artists() {
+        let db = test_db();
+        let ids: Vec<i64> = (0..5).map(|i| insert_test_track(&db, &format!("/music/{}.mp3", i))).collect();
+        {
+            let conn = db.get_connection();
+            let conn = conn.lock().unwrap();
+            for (id, album, artist, album_artist) in [
+                (ids[0], "Greatest Hits", Some("Queen"), None),
+                (ids[1], "Greatest Hits", Some("Freddie Mercury"), Some("Queen")),
+                (ids[2], "Greatest Hits", Some("ABBA"), None),
+                (ids[3], "Greatest Hits", None, None),
+                (ids[4], "Arrival", Some("ABBA"), None),
+            ] {
+                conn.execute(
+                    "UPDATE tracks SET album = ?1, artist = ?2, album_artist = ?3 WHERE id = ?4",
+                    params![album, artist, album_artist, id],
+                ).unwrap();
+            }
+        }
+        DbOperations::sync_albums(&db).unwrap();
+
+        let albums = DbOperations::get_all_albums(&db, AlbumSort::NameAsc).unwrap();
+        let listed: Vec<(&str, Option<&str>, i32)> = albums.iter()
+            .map(|a| (a.name.as_str(), a.artist.as_deref(), a.song_count))
+            .collect();
+        assert_eq!(listed, vec![
+            ("Arrival", Some("ABBA"), 1),
+            ("Greatest Hits", None, 1),
+            ("Greatest Hits", Some("ABBA"), 1),
+            ("Greatest Hits", Some("Queen"), 2),
+        ]);
+
+        let queen = DbOperations::get_tracks_by_album_and_artist(&db, "Greatest Hits", Some("Queen")).unwrap();
+        let mut queen_ids: Vec<i64> = queen.iter().map(|t| t.id).collect();
+        queen_ids.sort();
+        assert_eq!(queen_ids, vec![ids[0], ids[1]]);
+        let unknown = DbOperations::get_tracks_by_album_and_artist(&db, "Greatest Hits", None).unwrap();
+        assert_eq!(unknown.len(), 1);
+        assert_eq!(unknown[0].id, ids[3]);
+
+        // Album ids are stable and fetch the same tracks
+        let by_id = DbOperations::get_tracks_by_album_id(&db, albums[3].id).unwrap();
+        assert_eq!(by_id.iter().map(|t| t.id).collect::<Vec<_>>(), queen.iter().map(|t| t.id).collect::<Vec<_>>());
+        assert_eq!(DbOperations::get_all_albums(&db, AlbumSort::NameAsc).unwrap()[3].id, albums[3].id);
+
+        // Removing an album's tracks drops it without renumbering the others
+        DbOperations::remove_tracks_by_ids(&db, &[ids[4]]).unwrap();
+        DbOperations::sync_albums(&db).unwrap();
+        let remaining = DbOperations::get_all_albums(&db, AlbumSort::NameAsc).unwrap();
+        assert_eq!(remaining.len(), 3);
+        assert_eq!(remaining.iter().map(|a| a.id).collect::<Vec<_>>(), albums[1..].iter().map(|a| a.id).collect::<Vec<_>>());
+
+        // Recently played albums keep the same ids
+        set_played(&db, ids[2], "Greatest Hits", 1_000);
+        let recent = DbOperations::get_recently_played_albums(&db, 10).unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].artist.as_deref(), Some("ABBA"));
+        assert_eq!(recent[0].id, albums[2].id);
+    }
+
+    #[test]
+    fn test_upsert_and_get_loudness() {
+        let db = test_db();
+        let track_id = insert_test_track(&db, "/music/loud.mp3");
+        assert_eq!(DbOperations::get_loudness(&db, track_id).unwrap(), None);
+
+        let mut loudness = TrackLoudness {
+            track_id,
+            integrated_lufs: -9.0,
+            true_peak_db: -0.1,
+            gain_db: -5.0,
+            album_gain_db: None,
+            album_peak_db: None,
+        };
+        DbOperations::upsert_loudness(&db, &loudness).unwrap();
+        loudness.integrated_lufs = -20.0;
+        loudness.true_peak_db = -3.0;
+        loudness.gain_db = 3.0;
+        DbOperations::upsert_loudness(&db, &loudness).unwrap();
+
+        assert_eq!(DbOperations::get_loudness(&db, track_id).unwrap(), Some(loudness));
+        // Playback reads the gain from the track
+        let track = DbOperations::get_track_by_id(&db, track_id).unwrap().unwrap();
+        assert_eq!(track.normalization_gain_db, Some(3.0));
+    }
+
+    #[test]
+    fn test_album_gain_survives_track_reanalysis_and_applies_in_order() {
+        let db = test_db();
+        let ids: Vec<i64> = (0..3).map(|i| insert_test_track(&db, &format!("/music/{}.mp3", i))).collect();
+        for &id in &ids[..2] {
+            set_played(&db, id, "Album", 1_000);
+        }
+        set_played(&db, ids[2], "Other", 1_000);
+
+        let mut loudness = TrackLoudness {
+            track_id: ids[0],
+            integrated_lufs: -9.0,
+            true_peak_db: -0.1,
+            gain_db: -5.0,
+            album_gain_db: Some(-4.0),
+            album_peak_db: Some(-0.1),
+        };
+        DbOperations::upsert_loudness(&db, &loudness).unwrap();
+        loudness.gain_db = -6.0;
+        loudness.album_gain_db = None;
+        loudness.album_peak_db = None;
+        DbOperations::upsert_loudness(&db, &loudness).unwrap();
+        let stored = DbOperations::get_loudness(&db, ids[0]).unwrap().unwrap();
+        assert_eq!(stored.gain_db, -6.0);
+        assert_eq!(stored.album_gain_db, Some(-4.0));
+
+        // Not queued at all
+        assert!(!DbOperations::is_album_playing_in_order(&db, ids[0]).unwrap());
+
+        let queue_id = DbOperations::create_queue(&db, "Album").unwrap();
+        DbOperations::add_tracks_to_queue(&db, queue_id, &ids).unwrap();
+        assert!(DbOperations::is_album_playing_in_order(&db, ids[0]).unwrap());
+        assert!(DbOperations::is_album_playing_in_order(&db, ids[1]).unwrap());
+        assert!(!DbOperations::is_album_playing_in_order(&db, ids[2]).unwrap());
+
+        DbOperations::toggle_queue_shuffle(&db, queue_id, Some(ids[0])).unwrap();
+        assert!(!DbOperations::is_album_playing_in_order(&db, ids[0]).unwrap());
+    }
+
+    #[test]
+    fn test_get_tracks_page_returns_disjoint_ordered_slices() {
+        let db = test_db();
+        for name in ["c.mp3", "a.mp3", "e.mp3", "b.mp3", "d.mp3"] {
+            insert_test_track(&db, name);
+        }
+
+        let titles = |page: &TrackPage| page.tracks.iter().map(|t| t.title.clone()).collect::<Vec<_>>();
+        let first = DbOperations::get_tracks_page(&db, 2, 0, TrackSort::TitleAsc).unwrap();
+        let second = DbOperations::get_tracks_page(&db, 2, 2, TrackSort::TitleAsc).unwrap();
+        let last = DbOperations::get_tracks_page(&db, 2, 4, TrackSort::TitleAsc).unwrap();
+        assert_eq!(first.total_count, 5);
+        assert_eq!(titles(&first), vec!["a.mp3", "b.mp3"]);
+        assert_eq!(titles(&second), vec!["c.mp3", "d.mp3"]);
+        assert_eq!(titles(&last), vec!["e.mp3"]);
+
+        // Tracks added in the same second still page without overlap
+        let mut ids: Vec<i64> = (0..3)
+            .flat_map(|page| DbOperations::get_tracks_page(&db, 2, page * 2, TrackSort::DateAddedDesc).unwrap().tracks)
+            .map(|t| t.id)
+            .collect();
+        assert_eq!(ids.len(), 5);
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 5);
+
+        assert!(DbOperations::get_tracks_page(&db, -1, 0, TrackSort::TitleAsc).is_err());
+    }
+
+    #[test]
+    fn test_next_position_wraps_by_repeat_mode() {
+        let db = test_db();
+        let (queue_id, _) = create_test_queue(&db, 4);
+
+        assert_eq!(DbOperations::get_queue_repeat_mode(&db, queue_id).unwrap(), RepeatMode::Off);
+        assert_eq!(DbOperations::next_position(&db, queue_id, 1).unwrap(), Some(2));
+        assert_eq!(DbOperations::next_position(&db, queue_id, 3).unwrap(), None);
+
+        DbOperations::set_queue_repeat_mode(&db, queue_id, RepeatMode::All).unwrap();
+        assert_eq!(DbOperations::get_queue_repeat_mode(&db, queue_id).unwrap(), RepeatMode::All);
+        assert_eq!(DbOperations::next_position(&db, queue_id, 3).unwrap(), Some(0));
+
+        DbOperations::set_queue_repeat_mode(&db, queue_id, RepeatMode::One).unwrap();
+        assert_eq!(DbOperations::next_position(&db, queue_id, 3).unwrap(), Some(3));
+        assert_eq!(DbOperations::next_position(&db, queue_id, 1).unwrap(), Some(1));
+
+        assert_eq!(serde_json::to_string(&RepeatMode::All).unwrap(), "\"all\"");
+        assert!(serde_json::from_str::<RepeatMode>("\"sometimes\"").is_err());
+    }
+
+    #[test]
+    fn test_next_position_follows_shuffle_order_and_wraps() {
+        let db = test_db();
+        let (queue_id, track_ids) = create_test_queue(&db, 5);
+        DbOperations::update_queue_current_index(&db, queue_id, 3).unwrap();
+        DbOperations::set_queue_playback_modes(&db, queue_id, true, RepeatMode::All).unwrap();
+        let shuffled: Vec<i64> = DbOperations::get_queue_tracks(&db, queue_id).unwrap().iter().map(|t| t.id).collect();
+        assert_eq!(shuffled[0], track_ids[3]);
+
+        // Repeat-all plays the shuffled queue from the current track and wraps to the start
+        let start = DbOperations::get_queue_current_index(&db, queue_id).unwrap();
+        assert_eq!(start, 0);
+        let mut played = vec![shuffled[start as usize]];
+        let mut position = start;
+        for _ in 0..5 {
+            position = DbOperations::next_position(&db, queue_id, position).unwrap().unwrap();
+            played.push(DbOperations::get_queue_track_at_position(&db, queue_id, position).unwrap().unwrap().id);
+        }
+        let mut expected = shuffled.clone();
+        expected.push(shuffled[0]);
+        assert_eq!(played, expected);
+
+        // Without repeat the walk ends after the last entry
+        DbOperations::set_queue_repeat_mode(&db, queue_id, RepeatMode::Off).unwrap();
+        assert_eq!(DbOperations::next_position(&db, queue_id, 3).unwrap(), Some(4));
+        assert_eq!(DbOperations::next_position(&db, queue_id, 4).unwrap(), None);
+    }
+
+    #[test]
+    fn test_reorder_queue_track_shifts_range_and_keeps_current_track() {
+        let db = test_db();
+        let (queue_id, track_ids) = create_test_queue(&db, 5);
+        DbOperations::update_queue_current_index(&db, queue_id, 2).unwrap();
+
+        // Moving an earlier track past the current one shifts the current track up
+        let current = DbOperations::reorder_queue_track(&db, queue_id, 0, 3).unwrap();
+        let order: Vec<i64> = DbOperations::get_queue_tracks(&db, queue_id).unwrap().iter().map(|t| t.id).collect();
+        assert_eq!(order, vec![track_ids[1], track_ids[2], track_ids[3], track_ids[0], track_ids[4]]);
+        assert_eq!(current, 1);
+        assert_eq!(order[current as usize], track_ids[2]);
+
+        // Moving the current track itself follows it
+        let current = DbOperations::reorder_queue_track(&db, queue_id, 1, 4).unwrap();
+        assert_eq!(current, 4);
+        assert_eq!(DbOperations::get_queue_current_index(&db, queue_id).unwrap(), 4);
+        let order: Vec<i64> = DbOperations::get_queue_tracks(&db, queue_id).unwrap().iter().map(|t| t.id).collect();
+        assert_eq!(order[4], track_ids[2]);
+
+        assert!(DbOperations::reorder_queue_track(&db, queue_id, 0, 5).is_err());
+        assert!(DbOperations::reorder_queue_track(&db, queue_id, -1, 2).is_err());
+        assert_eq!(DbOperations::get_queue_length(&db, queue_id).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_shuffled_permutation_is_anchored_bijection() {
+        for queue_length in 0..40 {
+            for seed in [2, 7, 12, 1_000_003, -42] {
+                for anchor in [0, queue_length / 2, queue_length - 1] {
+                    let permutation = shuffled_permutation(seed, queue_length, anchor);
+                    let mut sorted = permutation.clone();
+                    sorted.sort();
+                    assert_eq!(sorted, (0..queue_length).collect::<Vec<_>>(), "len {} seed {}", queue_length, seed);
+                    if queue_length > 0 {
+                        assert_eq!(permutation[0], anchor);
+                    }
+
+                    // Deterministic, and the map is its inverse
+                    assert_eq!(permutation, shuffled_permutation(seed, queue_length, anchor));
+                    let map = shuffled_position_map(seed, queue_length, anchor);
+                    for (shuffled, &original) in permutation.iter().enumerate() {
+                        assert_eq!(map[original as usize], shuffled as i32);
+                        assert_eq!(
+                            DbOperations::find_shuffled_position(original, seed, queue_length, anchor).unwrap(),
+                            if queue_length <= 1 { 0 } else { shuffled as i32 }
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_toggle_shuffle_stores_the_seeded_permutation() {
+        let db = test_db();
+        let (queue_id, track_ids) = create_test_queue(&db, 9);
+
+        DbOperations::update_queue_current_index(&db, queue_id, 3).unwrap();
+        let (seed, current_index) = DbOperations::toggle_queue_shuffle(&db, queue_id, Some(track_ids[3])).unwrap();
+        assert_eq!(current_index, 0);
+        assert_eq!(DbOperations::get_queue_shuffle_anchor(&db, queue_id).unwrap(), 3);
+
+        let order: Vec<i64> = DbOperations::get_queue_tracks(&db, queue_id).unwrap().iter().map(|t| t.id).collect();
+        for (original, track_id) in track_ids.iter().enumerate() {
+            let shuffled = DbOperations::find_shuffled_position(original as i32, seed, 9, 3).unwrap();
+            assert_eq!(order[shuffled as usize], *track_id);
+        }
+    }
+
+    #[test]
+    fn test_track_at_shuffled_position_visits_every_track() {
+        let db = test_db();
+        let (queue_id, track_ids) = create_test_queue(&db, 6);
+
+        DbOperations::update_queue_current_index(&db, queue_id, 2).unwrap();
+        let (seed, _) = DbOperations::toggle_queue_shuffle(&db, queue_id, Some(track_ids[2])).unwrap();
+
+        // The shuffled order is the reordered queue, starting at the current track
+        let mut visited: Vec<i64> = (0..6)
+            .map(|position| {
+                DbOperations::get_queue_track_at_shuffled_position(&db, queue_id, position, seed, 2)
+                    .unwrap()
+                    .unwrap()
+                    .id
+            })
+            .collect();
+        let queue_order: Vec<i64> = DbOperations::get_queue_tracks(&db, queue_id).unwrap().iter().map(|t| t.id).collect();
+        assert_eq!(visited, queue_order);
+        assert_eq!(visited[0], track_ids[2]);
+        assert!(DbOperations::get_queue_track_at_shuffled_position(&db, queue_id, 6, seed, 2).unwrap().is_none());
+        visited.sort();
+        let mut expected = track_ids.clone();
+        expected.sort();
+        assert_eq!(visited, expected);
+    }
+
+    #[test]
+    fn test_playlist_removal_keeps_positions_contiguous() {
+        let db = test_db();
+        let track_ids: Vec<i64> = (0..5)
+            .map(|i| insert_test_track(&db, &format!("/music/playlist_{}.mp3", i)))
+            .collect();
+        let playlist_id = DbOperations::create_playlist(&db, "Mix", None).unwrap();
+        for &track_id in &track_ids {
+            DbOperations::add_track_to_playlist(&db, playlist_id, track_id).unwrap();
+        }
+
+        let positions = |db: &DatabaseConnection| -> Vec<(i32, i64)> {
+            let conn = db.get_connection();
+            let conn = conn.lock().unwrap();
+            let mut stmt = conn
+                .prepare("SELECT position, track_id FROM playlist_tracks WHERE playlist_id = ?1 ORDER BY position")
+                .unwrap();
+            stmt.query_map([playlist_id], |row| Ok((row.get(0)?, row.get(1)?)))
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap()
+        };
+
+        DbOperations::remove_playlist_track_at_position(&db, playlist_id, 1).unwrap();
+        DbOperations::remove_track_from_playlist(&db, playlist_id, track_ids[3]).unwrap();
+        assert_eq!(positions(&db), vec![(0, track_ids[0]), (1, track_ids[2]), (2, track_ids[4])]);
+        assert!(DbOperations::remove_playlist_track_at_position(&db, playlist_id, 3).is_err());
+
+        DbOperations::reorder_playlist_track(&db, playlist_id, 2, 0).unwrap();
+        assert_eq!(positions(&db), vec![(0, track_ids[4]), (1, track_ids[0]), (2, track_ids[2])]);
+
+        DbOperations::delete_playlist(&db, playlist_id).unwrap();
+        assert!(positions(&db).is_empty());
+        assert!(DbOperations::get_all_playlists(&db).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rating_and_favorite() {
+        let db = test_db();
+        let first = insert_test_track(&db, "/music/loved.mp3");
+        let second = insert_test_track(&db, "/music/also_loved.mp3");
+        insert_test_track(&db, "/music/meh.mp3");
+
+        DbOperations::set_track_rating(&db, first, 3).unwrap();
+        DbOperations::set_track_rating(&db, second, 5).unwrap();
+        assert!(DbOperations::set_track_rating(&db, first, 6).is_err());
+        assert!(DbOperations::set_track_rating(&db, first, -1).is_err());
+        assert!(DbOperations::set_track_rating(&db, 9999, 1).is_err());
+
+        assert!(DbOperations::toggle_favorite(&db, first).unwrap());
+        assert!(DbOperations::toggle_favorite(&db, second).unwrap());
+        assert!(DbOperations::toggle_favorite(&db, 9999).is_err());
+
+        let favorites = DbOperations::get_favorite_tracks(&db).unwrap();
+        assert_eq!(favorites.iter().map(|t| t.id).collect::<Vec<_>>(), vec![second, first]);
+        assert_eq!((favorites[1].rating, favorites[1].is_favorite), (3, true));
+
+        // Rescanning a changed file keeps the user's rating and flag
+        let mut track = DbOperations::get_track_by_id(&db, first).unwrap().unwrap();
+        track.title = "Retagged".to_string();
+        DbOperations::upsert_track_with_hash(&db, &track, "new-hash").unwrap();
+        let track = DbOperations::get_all_tracks(&db).unwrap().into_iter().find(|t| t.id == first).unwrap();
+        assert_eq!((track.title.as_str(), track.rating, track.is_favorite), ("Retagged", 3, true));
+
+        assert!(!DbOperations::toggle_favorite(&db, first).unwrap());
+        assert_eq!(DbOperations::get_favorite_tracks(&db).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_library_stats() {
+        let db = test_db();
+        assert_eq!(DbOperations::get_library_stats(&db).unwrap(), LibraryStats::default());
+
+        let a = insert_test_track(&db, "/music/a.mp3");
+        let b = insert_test_track(&db, "/music/b.mp3");
+        insert_test_track(&db, "/music/c.mp3");
+        {
+            let conn = db.get_connection();
+            let conn = conn.lock().unwrap();
+            // c has no duration or size; a and b share an album
+            conn.execute("UPDATE tracks SET file_size = 1000, album = 'X', artist = 'P' WHERE id IN (?1, ?2)", params![a, b]).unwrap();
+            conn.execute("UPDATE tracks SET duration_ms = NULL WHERE file_path = '/music/c.mp3'", []).unwrap();
         }
+        DbOperations::link_track_artists(&db, a, &["P".to_string()]).unwrap();
+        DbOperations::link_track_artists(&db, b, &["P".to_string()]).unwrap();
 
-        // Move the track to its new position
-        tx.execute(
-            "UPDATE queue_tracks SET position = ?1 WHERE queue_id = ?2 AND track_id = ?3 AND position = ?4",
-            params![to_position, queue_id, track_id, from_position],
-        )?;
+        let stats = DbOperations::get_library_stats(&db).unwrap();
+        assert_eq!(stats, LibraryStats {
+            track_count: 3,
+            artist_count: 1,
+            album_count: 1,
+            total_duration_ms: 360_000,
+            total_size_bytes: 2000,
+        });
+    }
 
-        // Note: We do NOT update original_order when reordering a shuffled queue.
-        // The original_order preserves the pre-shuffle state for restoration when unshuffle is pressed.
-        // User's drag-and-drop only affects the current (shuffled) order.
+    #[test]
+    fn test_clear_queue_keeps_queue_and_shuffle() {
+        let db = test_db();
+        let ids: Vec<i64> = (0..3).map(|i| insert_test_track(&db, &format!("/music/{}.mp3", i))).collect();
+        let queue_id = DbOperations::create_queue(&db, "Keep me").unwrap();
+        DbOperations::add_tracks_to_queue(&db, queue_id, &ids).unwrap();
+        let (seed, _) = DbOperations::toggle_queue_shuffle(&db, queue_id, Some(ids[0])).unwrap();
+        DbOperations::update_queue_current_index(&db, queue_id, 2).unwrap();
 
-        // Calculate new current_track_index
-        let new_index = if from_position == current_index {
-            // The currently playing track was moved
-            to_position
-        } else if from_position < current_index && to_position >= current_index {
-            // Track moved from before current to after/at current: current shifts up
-            current_index - 1
-        } else if from_position > current_index && to_position <= current_index {
-            // Track moved from after current to before/at current: current shifts down
-            current_index + 1
-        } else {
-            // No effect on current index
-            current_index
+        DbOperations::clear_queue(&db, queue_id).unwrap();
+        assert_eq!(DbOperations::get_queue_length(&db, queue_id).unwrap(), 0);
+        assert!(DbOperations::get_queue_track_at_position(&db, queue_id, 0).unwrap().is_none());
+        assert_eq!(DbOperations::get_queue_current_index(&db, queue_id).unwrap(), 0);
+        let queue = DbOperations::get_active_queue(&db).unwrap().unwrap();
+        assert_eq!((queue.id, queue.name.as_str(), queue.shuffle_seed), (queue_id, "Keep me", seed));
+
+        // Still usable, and tracks added afterwards are part of the original order
+        DbOperations::append_tracks_to_queue(&db, queue_id, &[ids[1]]).unwrap();
+        let queue = DbOperations::get_active_queue(&db).unwrap().unwrap();
+        assert_eq!(queue.original_order.as_deref(), Some(format!("[{}]", ids[1]).as_str()));
+
+        assert!(DbOperations::clear_queue(&db, queue_id + 100).is_err());
+    }
+
+    #[test]
+    fn test_smart_playlist_rules() {
+        let db = test_db();
+        let ids: Vec<i64> = (0..4).map(|i| insert_test_track(&db, &format!("/music/{}.mp3", i))).collect();
+        {
+            let conn = db.get_connection();
+            let conn = conn.lock().unwrap();
+            for (id, play_count, year, genre) in [
+                (ids[0], 10, 1999, "Rock; Pop"),
+                (ids[1], 3, 2005, "Jazz"),
+                (ids[2], 7, 2010, "50%_Rock"),
+                (ids[3], 0, 1985, "Pop"),
+            ] {
+                conn.execute(
+                    "UPDATE tracks SET play_count = ?1, year = ?2, genre = ?3 WHERE id = ?4",
+                    params![play_count, year, genre, id],
+                ).unwrap();
+            }
+        }
+        let evaluate = |name: &str, rules: &str| {
+            let id = DbOperations::create_smart_playlist(&db, name, rules).unwrap();
+            DbOperations::evaluate_smart_playlist(&db, id).unwrap().iter().map(|t| t.id).collect::<Vec<_>>()
         };
 
-        // Update current_track_index
-        tx.execute(
-            "UPDATE queues SET current_track_index = ?1 WHERE id = ?2",
-            params![new_index, queue_id],
-        )?;
+        // Played a lot, or from before 1990, most played first
+        assert_eq!(evaluate("Favourites", r#"{
+            "match": "any",
+            "rules": [
+                {"field": "play_count", "operator": ">=", "value": 7},
+                {"field": "year", "operator": "<", "value": 1990}
+            ],
+            "sort": "play_count_desc"
+        }"#), vec![ids[0], ids[2], ids[3]]);
 
-        tx.commit()?;
-        Ok(new_index)
+        // Nested group and a limit
+        assert_eq!(evaluate("Rock since 1995", r#"{
+            "rules": [
+                {"field": "genre", "operator": "contains", "value": "rock"},
+                {"match": "any", "rules": [{"field": "year", "operator": ">", "value": 1995}]}
+            ],
+            "limit": 1,
+            "sort": "play_count_desc"
+        }"#), vec![ids[0]]);
+
+        // LIKE wildcards in values are literal
+        assert_eq!(evaluate("Literal", r#"{"rules": [{"field": "genre", "operator": "starts_with", "value": "50%_"}]}"#), vec![ids[2]]);
+        assert_eq!(evaluate("Never played", r#"{"rules": [{"field": "last_played", "operator": "is_empty"}], "sort": "title_asc"}"#).len(), 4);
+
+        // Only whitelisted fields, operators and sorts
+        for rules in [
+            r#"{"rules": [{"field": "file_path; DROP TABLE tracks", "operator": "=", "value": 1}]}"#,
+            r#"{"rules": [{"field": "year", "operator": "OR 1=1 --", "value": 1}]}"#,
+            r#"{"rules": [{"field": "genre", "operator": ">", "value": "Rock"}]}"#,
+            r#"{"rules": [{"field": "year", "operator": "=", "value": "1999"}]}"#,
+            r#"{"match": "some", "rules": []}"#,
+            r#"{"rules": [], "sort": "id; DROP TABLE tracks"}"#,
+        ] {
+            assert!(DbOperations::create_smart_playlist(&db, "Bad", rules).is_err(), "{}", rules);
+        }
+        assert_eq!(DbOperations::get_all_smart_playlists(&db).unwrap().len(), 4);
     }
 
-    /// Append tracks to the end of a queue
-    /// If the queue is shuffled, also appends to the end of original_order
-    pub fn append_tracks_to_queue(
-        db: &DatabaseConnection,
-        queue_id: i64,
-        track_ids: &[i64],
-    ) -> Result<(), anyhow::Error> {
-        let conn = db.get_connection();
-        let mut conn = conn.lock().unwrap();
+    #[test]
+    fn test_pending_scrobbles_oldest_first() {
+        let db = test_db();
+        let scrobble = |title: &str, timestamp: i64| Scrobble {
+            id: 0,
+            artist: "Artist".to_string(),
+            title: title.to_string(),
+            album: None,
+            album_artist: None,
+            duration_ms: Some(180_000),
+            timestamp,
+        };
+        DbOperations::add_pending_scrobble(&db, &scrobble("Later", 2_000)).unwrap();
+        DbOperations::add_pending_scrobble(&db, &scrobble("Earlier", 1_000)).unwrap();
 
-        // Get current max position and original_order
-        let (max_position, original_order_json): (i32, Option<String>) = conn
-            .query_row(
-                "SELECT COALESCE((SELECT MAX(position) FROM queue_tracks WHERE queue_id = ?1), -1), 
-                        (SELECT original_order FROM queues WHERE id = ?1)",
-                [queue_id],
-                |row| Ok((row.get(0)?, row.get(1)?)),
-            )?;
+        let pending = DbOperations::get_pending_scrobbles(&db, 50).unwrap();
+        let titles: Vec<&str> = pending.iter().map(|s| s.title.as_str()).collect();
+        assert_eq!(titles, vec!["Earlier", "Later"]);
 
-        let tx = conn.transaction()?;
+        assert_eq!(DbOperations::delete_pending_scrobbles(&db, &[pending[0].id]).unwrap(), 1);
+        let pending = DbOperations::get_pending_scrobbles(&db, 50).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].title, "Later");
+    }
 
-        for (index, track_id) in track_ids.iter().enumerate() {
-            tx.execute(
-                "INSERT INTO queue_tracks (queue_id, track_id, position) VALUES (?1, ?2, ?3)",
-                params![queue_id, track_id, max_position + 1 + index as i32],
-            )?;
+    #[test]
+    fn test_create_playlist_with_tracks_is_all_or_nothing() {
+        let db = test_db();
+        let first = insert_test_track(&db, "/music/1.mp3");
+        let second = insert_test_track(&db, "/music/2.mp3");
+
+        let (playlist_id, added) =
+            DbOperations::create_playlist_with_tracks(&db, "Mix", &[second, first, second]).unwrap();
+        assert_eq!(added, 2);
+        let ids: Vec<i64> = DbOperations::get_playlist_tracks(&db, playlist_id).unwrap().iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![second, first]);
+
+        // An insert that fails rolls back the playlist as well
+        assert!(DbOperations::create_playlist_with_tracks(&db, "Broken", &[first, second + 100]).is_err());
+        let names: Vec<String> = DbOperations::get_all_playlists(&db).unwrap().into_iter().map(|p| p.name).collect();
+        assert_eq!(names, vec!["Mix"]);
+    }
+
+    #[test]
+    fn test_album_artwork_sources() {
+        let db = test_db();
+        let track_id = insert_test_track(&db, "/music/a/1.mp3");
+        set_played(&db, track_id, "Album", 0);
+        let album_id = DbOperations::assign_track_album(&db, track_id).unwrap().unwrap();
+        assert_eq!(DbOperations::get_track_album_artwork(&db, track_id).unwrap(), (None, None));
+
+        // Folder art only fills an empty slot
+        DbOperations::set_album_folder_artwork(&db, album_id, "/music/a/cover.jpg").unwrap();
+        DbOperations::set_album_folder_artwork(&db, album_id, "/music/a/folder.jpg").unwrap();
+        assert!(DbOperations::set_album_custom_artwork(&db, album_id, Some("/pics/mine.png")).unwrap());
+        assert_eq!(
+            DbOperations::get_track_album_artwork(&db, track_id).unwrap(),
+            (Some("/pics/mine.png".to_string()), Some("/music/a/cover.jpg".to_string()))
+        );
+
+        assert!(DbOperations::set_album_custom_artwork(&db, album_id, None).unwrap());
+        assert_eq!(DbOperations::get_track_album_artwork(&db, track_id).unwrap().0, None);
+        assert!(!DbOperations::set_album_custom_artwork(&db, album_id + 100, None).unwrap());
+
+        // A cached cover replaces the folder art once, then repeat lookups write nothing
+        assert!(DbOperations::set_album_artwork_path(&db, track_id, "/cache/abc.png").unwrap());
+        assert!(!DbOperations::set_album_artwork_path(&db, track_id, "/cache/abc.png").unwrap());
+        assert_eq!(
+            DbOperations::get_track_album_artwork(&db, track_id).unwrap().1,
+            Some("/cache/abc.png".to_string())
+        );
+    }
+
+    #[test]
+    fn test_delete_track_keeps_positions_contiguous() {
+        let db = test_db();
+        let track_ids: Vec<i64> = (0..4).map(|i| insert_test_track(&db, &format!("/music/{}.mp3", i))).collect();
+        // The deleted track appears twice in the queue, before and after the current one
+        let queue_id = DbOperations::create_queue(&db, "Test Queue").unwrap();
+        let queued = [track_ids[0], track_ids[1], track_ids[2], track_ids[3], track_ids[1]];
+        DbOperations::add_tracks_to_queue(&db, queue_id, &queued).unwrap();
+        DbOperations::update_queue_current_index(&db, queue_id, 2).unwrap();
+        let playlist_id = DbOperations::create_playlist(&db, "Mix", None).unwrap();
+        for &track_id in &[track_ids[0], track_ids[1], track_ids[2]] {
+            DbOperations::add_track_to_playlist(&db, playlist_id, track_id).unwrap();
         }
 
-        // If shuffled, also append to original_order
-        if let Some(original_order_str) = original_order_json {
-            let mut original_ids: Vec<i64> = serde_json::from_str(&original_order_str)?;
-            original_ids.extend(track_ids.iter().copied());
-            let updated_original_order = serde_json::to_string(&original_ids)?;
-            tx.execute(
-                "UPDATE queues SET original_order = ?1 WHERE id = ?2",
-                params![updated_original_order, queue_id],
-            )?;
+        assert!(DbOperations::delete_track(&db, track_ids[1]).unwrap());
+        assert!(!DbOperations::delete_track(&db, track_ids[1]).unwrap());
+        assert!(DbOperations::get_track_by_id(&db, track_ids[1]).unwrap().is_none());
+
+        let queue: Vec<i64> = DbOperations::get_queue_tracks(&db, queue_id).unwrap().iter().map(|t| t.id).collect();
+        assert_eq!(queue, vec![track_ids[0], track_ids[2], track_ids[3]]);
+        // Still on the track that was current
+        assert_eq!(DbOperations::get_queue_current_index(&db, queue_id).unwrap(), 1);
+
+        let playlist: Vec<i64> = DbOperations::get_playlist_tracks(&db, playlist_id).unwrap().iter().map(|t| t.id).collect();
+        assert_eq!(playlist, vec![track_ids[0], track_ids[2]]);
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        let positions: Vec<i32> = conn
+            .prepare("SELECT position FROM playlist_tracks WHERE playlist_id = ?1 ORDER BY position").unwrap()
+            .query_map(params![playlist_id], |row| row.get(0)).unwrap()
+            .collect::<Result<_, _>>().unwrap();
+        assert_eq!(positions, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_browse_by_year_and_decade() {
+        let db = test_db();
+        for (path, year) in [("a.mp3", Some(1994)), ("b.mp3", Some(1999)), ("c.mp3", Some(2003)), ("d.mp3", None)] {
+            let id = insert_test_track(&db, path);
+            let conn = db.get_connection();
+            conn.lock().unwrap().execute("UPDATE tracks SET year = ?1 WHERE id = ?2", params![year, id]).unwrap();
         }
 
-        tx.commit()?;
-        Ok(())
+        assert_eq!(DbOperations::get_all_years(&db).unwrap(), vec![(2003, 1), (1999, 1), (1994, 1), (0, 1)]);
+
+        let paths = |tracks: Vec<Track>| tracks.into_iter().map(|t| t.file_path).collect::<Vec<_>>();
+        assert_eq!(paths(DbOperations::get_tracks_by_year(&db, 1999).unwrap()), vec!["b.mp3"]);
+        assert_eq!(paths(DbOperations::get_tracks_by_year(&db, 0).unwrap()), vec!["d.mp3"]);
+        assert_eq!(paths(DbOperations::get_tracks_by_decade(&db, 1990).unwrap()), vec!["b.mp3", "a.mp3"]);
+        assert!(DbOperations::get_tracks_by_decade(&db, 1980).unwrap().is_empty());
     }
 
-    /// Insert tracks after a specific position, shifting existing tracks
-    pub fn insert_tracks_after_position(
-        db: &DatabaseConnection,
-        queue_id: i64,
-        track_ids: &[i64],
-        after_position: i32,
-    ) -> Result<(), anyhow::Error> {
-        let conn = db.get_connection();
-        let mut conn = conn.lock().unwrap();
+    #[test]
+    fn test_find_duplicates() {
+        let db = test_db();
+        let set = |id: i64, column: &str, value: &dyn rusqlite::ToSql| {
+            let conn = db.get_connection();
+            conn.lock().unwrap().execute(&format!("UPDATE tracks SET {} = ?1 WHERE id = ?2", column), params![value, id]).unwrap();
+        };
+        let original = insert_test_track(&db, "/music/song.flac");
+        let copy = insert_test_track(&db, "/backup/song.flac");
+        let unhashed = insert_test_track(&db, "/music/other.flac");
+        let unhashed_copy = insert_test_track(&db, "/backup/other.flac");
+        for id in [original, copy] {
+            set(id, "file_hash", &"same-content");
+        }
+        for id in [unhashed, unhashed_copy] {
+            set(id, "file_hash", &rusqlite::types::Null);
+        }
+        // Two tracks of one CUE sheet share a hash but aren't duplicates
+        let cue_a = insert_test_track(&db, "/music/album.flac#0-1000");
+        let cue_b = insert_test_track(&db, "/music/album.flac#1000-");
+        for id in [cue_a, cue_b] {
+            set(id, "file_hash", &"sheet");
+        }
 
-        let tx = conn.transaction()?;
+        let ids = |groups: Vec<Vec<Track>>| -> Vec<Vec<i64>> {
+            groups.into_iter().map(|g| g.into_iter().map(|t| t.id).collect()).collect()
+        };
+        assert_eq!(ids(DbOperations::find_duplicate_tracks(&db).unwrap()), vec![vec![copy, original]]);
 
-        // Shift existing tracks after the insertion point
-        let shift_amount = track_ids.len() as i32;
-        tx.execute(
-            "UPDATE queue_tracks SET position = position + ?1 WHERE queue_id = ?2 AND position > ?3",
-            params![shift_amount, queue_id, after_position],
-        )?;
+        // Near-duplicates: same name in another encoding, a little longer
+        let mp3 = insert_test_track(&db, "/music/song.mp3");
+        let different = insert_test_track(&db, "/music/live.mp3");
+        for (id, title, duration) in [
+            (original, "Don't Stop!", 180_000),
+            (copy, "Don't Stop!", 180_000),
+            (mp3, "dont  stop", 181_500),
+            (different, "Don't Stop", 240_000),
+        ] {
+            set(id, "title", &title);
+            set(id, "duration_ms", &duration);
+        }
+        assert_eq!(ids(DbOperations::find_duplicate_metadata(&db).unwrap()), vec![vec![original, copy, mp3]]);
+    }
 
-        // Insert new tracks
-        for (index, track_id) in track_ids.iter().enumerate() {
-            tx.execute(
-                "INSERT INTO queue_tracks (queue_id, track_id, position) VALUES (?1, ?2, ?3)",
-                params![queue_id, track_id, after_position + 1 + index as i32],
-            )?;
+    #[test]
+    fn test_compilations_group_under_various_artists() {
+        let db = test_db();
+        // A soundtrack tagged as a compilation but without an album artist
+        let soundtrack: Vec<i64> = (0..3).map(|i| insert_test_track(&db, &format!("/music/ost/{}.mp3", i))).collect();
+        // A sampler credited to Various Artists
+        let sampler: Vec<i64> = (0..2).map(|i| insert_test_track(&db, &format!("/music/sampler/{}.mp3", i))).collect();
+        // A regular album that happens to be credited to VA with a single artist
+        let single = insert_test_track(&db, "/music/single/1.mp3");
+        {
+            let conn = db.get_connection();
+            let conn = conn.lock().unwrap();
+            for (i, id) in soundtrack.iter().enumerate() {
+                conn.execute(
+                    "UPDATE tracks SET album = 'OST', artist = ?1, compilation = 1 WHERE id = ?2",
+                    params![format!("Artist {}", i), id],
+                ).unwrap();
+            }
+            for (i, id) in sampler.iter().enumerate() {
+                conn.execute(
+                    "UPDATE tracks SET album = 'Sampler', album_artist = 'Various Artists', artist = ?1 WHERE id = ?2",
+                    params![format!("Artist {}", i), id],
+                ).unwrap();
+            }
+            conn.execute(
+                "UPDATE tracks SET album = 'Single', album_artist = 'VA', artist = 'Solo' WHERE id = ?1",
+                [single],
+            ).unwrap();
         }
+        DbOperations::sync_albums(&db).unwrap();
 
-        tx.commit()?;
-        Ok(())
+        let albums = DbOperations::get_all_albums(&db, AlbumSort::NameAsc).unwrap();
+        let ost: Vec<_> = albums.iter().filter(|a| a.name == "OST").collect();
+        assert_eq!(ost.len(), 1);
+        assert_eq!((ost[0].artist.as_deref(), ost[0].song_count), (Some("Various Artists"), 3));
+        assert_eq!(DbOperations::get_tracks_by_album_and_artist(&db, "OST", Some("Various Artists")).unwrap().len(), 3);
+
+        let compilations: Vec<String> = DbOperations::get_compilation_albums(&db).unwrap()
+            .into_iter()
+            .map(|a| a.name)
+            .collect();
+        assert_eq!(compilations, vec!["OST", "Sampler"]);
+
+        // Indexing a single track flags its album too
+        let extra = insert_test_track(&db, "/music/sampler/2.mp3");
+        {
+            let conn = db.get_connection();
+            conn.lock().unwrap().execute(
+                "UPDATE tracks SET album = 'Single', album_artist = 'VA', artist = 'Guest' WHERE id = ?1",
+                [extra],
+            ).unwrap();
+        }
+        DbOperations::assign_track_album(&db, extra).unwrap();
+        assert_eq!(DbOperations::get_compilation_albums(&db).unwrap().len(), 3);
+
+        // Deleting the guest track leaves "Single" with one artist again
+        assert!(DbOperations::delete_track(&db, extra).unwrap());
+        let compilations: Vec<String> = DbOperations::get_compilation_albums(&db).unwrap()
+            .into_iter()
+            .map(|a| a.name)
+            .collect();
+        assert_eq!(compilations, vec!["OST", "Sampler"]);
+        // and deleting an album's last track drops its row
+        assert!(DbOperations::delete_track(&db, single).unwrap());
+        let albums = DbOperations::get_all_albums(&db, AlbumSort::NameAsc).unwrap();
+        assert!(albums.iter().all(|a| a.name != "Single"));
     }
 
-    /// Remove a track at a specific position from a queue
-    /// If the queue is shuffled, also removes the first occurrence from original_order
-    /// Returns the new current_track_index after adjustment (-1 if queue becomes empty)
-    pub fn remove_track_at_position(
-        db: &DatabaseConnection,
-        queue_id: i64,
-        position: i32,
-    ) -> Result<i32, anyhow::Error> {
-        let conn = db.get_connection();
-        let mut conn = conn.lock().unwrap();
-        
-        let tx = conn.transaction()?;
+    #[test]
+    fn test_album_and_artist_sort_orders() {
+        let db = test_db();
+        let ids: Vec<i64> = (0..6).map(|i| insert_test_track(&db, &format!("/music/{}.mp3", i))).collect();
+        {
+            let conn = db.get_connection();
+            let conn = conn.lock().unwrap();
+            for (id, album, artist, year) in [
+                (ids[0], "Blue", "Miles", Some(1959)),
+                (ids[1], "Abbey Road", "The Beatles", Some(1969)),
+                (ids[2], "Abbey Road", "The Beatles", Some(1969)),
+                (ids[3], "Abbey Road", "The Beatles", Some(1969)),
+                (ids[4], "Currents", "Tame Impala", None),
+                (ids[5], "Currents", "Tame Impala", None),
+            ] {
+                conn.execute(
+                    "UPDATE tracks SET album = ?1, artist = ?2, year = ?3 WHERE id = ?4",
+                    params![album, artist, year, id],
+                ).unwrap();
+            }
+        }
+        DbOperations::sync_albums(&db).unwrap();
+        DbOperations::link_track_artists(&db, ids[0], &["Miles".to_string()]).unwrap();
+        for &id in &ids[1..4] {
+            DbOperations::link_track_artists(&db, id, &["The Beatles".to_string()]).unwrap();
+        }
+        for &id in &ids[4..] {
+            DbOperations::link_track_artists(&db, id, &["Tame Impala".to_string()]).unwrap();
+        }
 
-        // Get current track index and original_order before removal
-        let (current_index, original_order_json): (i32, Option<String>) = tx.query_row(
-            "SELECT current_track_index, original_order FROM queues WHERE id = ?1",
-            params![queue_id],
-            |row| Ok((row.get(0)?, row.get(1)?))
-        )?;
+        let albums = |sort| -> Vec<String> {
+            DbOperations::get_all_albums(&db, sort).unwrap().into_iter().map(|a| a.name).collect()
+        };
+        assert_eq!(albums(AlbumSort::default()), vec!["Abbey Road", "Blue", "Currents"]);
+        assert_eq!(albums(AlbumSort::NameAsc), vec!["Abbey Road", "Blue", "Currents"]);
+        assert_eq!(albums(AlbumSort::YearDesc), vec!["Abbey Road", "Blue", "Currents"]);
+        assert_eq!(albums(AlbumSort::SongCountDesc), vec!["Abbey Road", "Currents", "Blue"]);
+        assert_eq!(albums(AlbumSort::ArtistAsc), vec!["Blue", "Currents", "Abbey Road"]);
 
-        // Get total track count before removal
-        let track_count: i32 = tx.query_row(
-            "SELECT COUNT(*) FROM queue_tracks WHERE queue_id = ?1",
-            params![queue_id],
-            |row| row.get(0)
-        )?;
+        let artists = |sort| -> Vec<String> {
+            DbOperations::get_all_artists(&db, sort).unwrap().into_iter().map(|a| a.name).collect()
+        };
+        assert_eq!(artists(ArtistSort::default()), vec!["Miles", "Tame Impala", "The Beatles"]);
+        assert_eq!(artists(ArtistSort::NameAsc), vec!["Miles", "Tame Impala", "The Beatles"]);
+        assert_eq!(artists(ArtistSort::SongCountDesc), vec!["The Beatles", "Tame Impala", "Miles"]);
 
-        // Get the track_id being removed (needed to update original_order)
-        let removed_track_id: i64 = tx.query_row(
-            "SELECT track_id FROM queue_tracks WHERE queue_id = ?1 AND position = ?2",
-            params![queue_id, position],
-            |row| row.get(0)
-        )?;
+        assert_eq!(AlbumSort::from_name("year_desc"), Some(AlbumSort::YearDesc));
+        assert_eq!(ArtistSort::from_name("song_count_desc"), Some(ArtistSort::SongCountDesc));
+        assert_eq!(AlbumSort::from_name("name; DROP TABLE albums"), None);
+    }
 
-        // Delete the track at the specified position
-        tx.execute(
-            "DELETE FROM queue_tracks WHERE queue_id = ?1 AND position = ?2",
-            params![queue_id, position],
-        )?;
+    #[test]
+    fn test_artist_and_genre_names_collapse_when_normalized() {
+        let db = test_db();
 
-        // Shift all positions after the removed track down by 1
-        tx.execute(
-            "UPDATE queue_tracks SET position = position - 1 WHERE queue_id = ?1 AND position > ?2",
-            params![queue_id, position],
-        )?;
+        let beatles = DbOperations::insert_or_get_artist(&db, "Beatles").unwrap();
+        assert_eq!(DbOperations::insert_or_get_artist(&db, "beatles").unwrap(), beatles);
+        assert_eq!(DbOperations::insert_or_get_artist(&db, "  BEATLES ").unwrap(), beatles);
+        assert_ne!(DbOperations::insert_or_get_artist(&db, "The Beatles").unwrap(), beatles);
 
-        // If shuffled, remove the first occurrence from original_order
-        if let Some(original_order_str) = original_order_json {
-            let mut original_ids: Vec<i64> = serde_json::from_str(&original_order_str)?;
-            if let Some(pos) = original_ids.iter().position(|&id| id == removed_track_id) {
-                original_ids.remove(pos);
-                let updated_original_order = serde_json::to_string(&original_ids)?;
-                tx.execute(
-                    "UPDATE queues SET original_order = ?1 WHERE id = ?2",
-                    params![updated_original_order, queue_id],
-                )?;
+        // Precomposed and decomposed forms of the same name
+        let beyonce = DbOperations::insert_or_get_artist(&db, "Beyonc\u{e9}").unwrap();
+        assert_eq!(DbOperations::insert_or_get_artist(&db, "Beyonce\u{301}").unwrap(), beyonce);
+
+        // The first-seen spelling is kept for display
+        assert_eq!(DbOperations::get_artist_name(&db, beatles).unwrap().as_deref(), Some("Beatles"));
+
+        let rock = DbOperations::insert_or_get_genre(&db, "Rock ").unwrap();
+        assert_eq!(DbOperations::insert_or_get_genre(&db, "ROCK").unwrap(), rock);
+        assert_eq!(DbOperations::get_genre_name(&db, rock).unwrap().as_deref(), Some("Rock"));
+    }
+
+    #[test]
+    fn test_migration_merges_normalized_duplicates() {
+        let db = test_db();
+        let ids: Vec<i64> = (0..3).map(|i| insert_test_track(&db, &format!("/music/{}.mp3", i))).collect();
+        {
+            // A database from before name_normalized, with case and spacing variants
+            let conn = db.get_connection();
+            let conn = conn.lock().unwrap();
+            conn.execute_batch(
+                "DROP INDEX idx_artists_name_normalized;
+                 ALTER TABLE artists DROP COLUMN name_normalized;
+                 DROP INDEX idx_genres_name_normalized;
+                 ALTER TABLE genres DROP COLUMN name_normalized;
+                 INSERT INTO artists (id, name) VALUES (1, 'Beatles'), (2, 'beatles'), (3, 'Beatles '), (4, 'Queen');
+                 INSERT INTO genres (id, name) VALUES (1, 'Rock'), (2, 'rock');",
+            ).unwrap();
+            for (track_id, artist_id) in [(ids[0], 1), (ids[1], 2), (ids[1], 4), (ids[2], 3), (ids[2], 1)] {
+                conn.execute(
+                    "INSERT INTO track_artists (track_id, artist_id) VALUES (?1, ?2)",
+                    params![track_id, artist_id],
+                ).unwrap();
             }
+            conn.execute("INSERT INTO track_album_artists (track_id, artist_id) VALUES (?1, 2)", [ids[0]]).unwrap();
+            conn.execute("INSERT INTO track_genres (track_id, genre_id) VALUES (?1, 2)", [ids[0]]).unwrap();
+
+            crate::db::migrations::run_migrations(&conn).unwrap();
         }
 
-        // Calculate new current index
-        let new_index = if track_count <= 1 {
-            // Queue becomes empty
-            0
-        } else if position < current_index {
-            // Removed before current: shift current index down
-            current_index - 1
-        } else if position == current_index {
-            // Removed current track: stay at same position (next track slides into this position)
-            // But clamp to valid range (in case we removed the last track)
-            std::cmp::min(current_index, track_count - 2)
-        } else {
-            // Removed after current: no change needed
-            current_index
-        };
+        let artists = DbOperations::get_all_artists(&db, ArtistSort::NameAsc).unwrap();
+        let listed: Vec<(i64, &str, i32)> = artists.iter().map(|a| (a.id, a.name.as_str(), a.song_count)).collect();
+        assert_eq!(listed, vec![(1, "Beatles", 3), (4, "Queen", 1)]);
+        assert_eq!(DbOperations::insert_or_get_artist(&db, "BEATLES").unwrap(), 1);
 
-        // Update current_track_index in database
-        tx.execute(
-            "UPDATE queues SET current_track_index = ?1 WHERE id = ?2",
-            params![new_index, queue_id],
-        )?;
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        let album_artist: i64 = conn.query_row("SELECT artist_id FROM track_album_artists", [], |row| row.get(0)).unwrap();
+        assert_eq!(album_artist, 1);
+        let genres: Vec<(i64, String)> = conn.prepare("SELECT id, name FROM genres").unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap()
+            .collect::<Result<_, _>>().unwrap();
+        assert_eq!(genres, vec![(1, "Rock".to_string())]);
+        let genre_link: i64 = conn.query_row("SELECT genre_id FROM track_genres", [], |row| row.get(0)).unwrap();
+        assert_eq!(genre_link, 1);
+    }
 
-        tx.commit()?;
-        Ok(new_index)
+    #[test]
+    fn test_get_track_by_id() {
+        let db = test_db();
+        let track_id = insert_test_track(&db, "/music/a.mp3");
+        {
+            let conn = db.get_connection();
+            let conn = conn.lock().unwrap();
+            conn.execute("UPDATE tracks SET file_hash = 'abc123' WHERE id = ?1", [track_id]).unwrap();
+        }
+
+        let track = DbOperations::get_track_by_id(&db, track_id).unwrap().unwrap();
+        assert_eq!(track.file_path, "/music/a.mp3");
+        assert_eq!(track.file_hash.as_deref(), Some("abc123"));
+        assert_eq!(track.duration_ms, Some(180_000));
+
+        assert!(DbOperations::get_track_by_id(&db, track_id + 1).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_relative_to_directory() {
+        assert_eq!(relative_to_directory(r"C:\Music\Rock\a.mp3", r"C:\Music"), Some(vec!["Rock", "a.mp3"]));
+        assert_eq!(relative_to_directory(r"C:\Music\Rock\a.mp3", r"c:\music\"), Some(vec!["Rock", "a.mp3"]));
+        assert_eq!(relative_to_directory(r"C:\Music\Rock\a.mp3", "C:/Music/Rock"), Some(vec!["a.mp3"]));
+        assert_eq!(relative_to_directory(r"C:\Musical\a.mp3", r"C:\Music"), None);
+        assert_eq!(relative_to_directory("/music/rock/a.mp3", "/music/"), Some(vec!["rock", "a.mp3"]));
+        assert_eq!(relative_to_directory("/music/rock/a.mp3", "/Music"), None);
+        assert_eq!(relative_to_directory("/music/a.mp3", "/"), Some(vec!["music", "a.mp3"]));
+    }
+
+    #[test]
+    fn test_browse_directory() {
+        let db = test_db();
+        for path in [
+            r"C:\Music\Rock\a.mp3",
+            r"C:\Music\rock\Live\b.mp3",
+            r"C:\Music\Jazz\c.mp3",
+            r"C:\Music\d.mp3",
+            r"C:\Musical\e.mp3",
+            "/home/me/music/f.mp3",
+            "/home/me/Music/g.mp3",
+        ] {
+            insert_test_track(&db, path);
+        }
+        let paths = |tracks: Vec<Track>| -> Vec<String> { tracks.into_iter().map(|t| t.file_path).collect() };
+
+        // Same listing whatever the case, separators or trailing separator
+        for dir in [r"C:\Music", r"C:\music\", "C:/Music/"] {
+            let listing = DbOperations::browse_directory(&db, dir).unwrap();
+            assert_eq!(listing.path, dir);
+            assert_eq!(paths(listing.tracks), vec![r"C:\Music\d.mp3"]);
+            let names: Vec<String> = listing.subdirectories.iter()
+                .map(|p| p.rsplit(['/', '\\']).next().unwrap().to_string())
+                .collect();
+            assert_eq!(names, vec!["Jazz", "Rock"]);
+        }
+        assert_eq!(DbOperations::get_subdirectories(&db, r"C:\Music\").unwrap(), vec![r"C:\Music\Jazz", r"C:\Music\Rock"]);
+
+        assert_eq!(
+            paths(DbOperations::get_tracks_in_directory(&db, r"C:\Music\Rock", true).unwrap()),
+            vec![r"C:\Music\Rock\a.mp3", r"C:\Music\rock\Live\b.mp3"]
+        );
+        assert_eq!(
+            DbOperations::get_subdirectories(&db, r"C:\Music\Rock").unwrap(),
+            vec![r"C:\Music\Rock\Live"]
+        );
+        assert_eq!(DbOperations::get_tracks_in_directory(&db, r"C:\Music", true).unwrap().len(), 4);
+
+        // Unix paths are case-sensitive
+        assert_eq!(paths(DbOperations::get_tracks_in_directory(&db, "/home/me/music", true).unwrap()), vec!["/home/me/music/f.mp3"]);
+        assert_eq!(DbOperations::get_subdirectories(&db, "/home/me").unwrap(), vec!["/home/me/Music", "/home/me/music"]);
+    }
+
+    #[test]
+    fn test_tracks_for_audio_file_use_the_path_index() {
+        let db = test_db();
+        let whole = insert_test_track(&db, "/music/live.flac");
+        let first = insert_test_track(&db, "/music/live.flac#0-1000");
+        let second = insert_test_track(&db, "/music/live.flac#1000-");
+        // Same prefix, but not tracks of that file
+        insert_test_track(&db, "/music/live.flac#notes.flac");
+        insert_test_track(&db, "/music/live.flac2");
+        insert_test_track(&db, "/music/live.flaca#0-1000");
+
+        let ids: Vec<i64> = DbOperations::get_tracks_for_audio_file(&db, "/music/live.flac")
+            .unwrap()
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(ids, vec![whole, first, second]);
+
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        let plan: Vec<String> = conn
+            .prepare(&format!("EXPLAIN QUERY PLAN {}", TRACKS_FOR_AUDIO_FILE_SQL))
+            .unwrap()
+            .query_map(["/music/live.flac"], |row| row.get::<_, String>(3))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert!(plan.iter().all(|step| !step.starts_with("SCAN tracks")), "{:?}", plan);
+    }
+
+    #[test]
+    fn test_scan_cleanup_only_looks_inside_scanned_roots() {
+        let db = test_db();
+        insert_test_track(&db, "/music/a.mp3");
+        let gone = insert_test_track(&db, "/music/sub/b.mp3");
+        insert_test_track(&db, "/music2/c.mp3");
+        // Opened from outside the scan paths
+        insert_test_track(&db, "/downloads/d.mp3");
+
+        let found: std::collections::HashSet<String> = ["/music/a.mp3".to_string()].into();
+        let roots = vec!["/music/".to_string()];
+        assert_eq!(DbOperations::find_tracks_not_in_paths(&db, &roots, &found).unwrap(), vec![gone]);
+        assert!(DbOperations::find_tracks_not_in_paths(&db, &[], &found).unwrap().is_empty());
+
+        let path_id = DbOperations::add_scan_path(&db, "/music").unwrap();
+        assert_eq!(DbOperations::get_all_scan_paths(&db).unwrap()[0].file_count, None);
+        DbOperations::set_scan_path_file_count(&db, path_id, 2).unwrap();
+        assert_eq!(DbOperations::get_all_scan_paths(&db).unwrap()[0].file_count, Some(2));
+        DbOperations::clear_library(&db).unwrap();
+        assert_eq!(DbOperations::get_all_scan_paths(&db).unwrap()[0].file_count, None);
+    }
+
+    #[test]
+    fn test_directory_queries_escape_like_wildcards() {
+        let db = test_db();
+        let hits = insert_test_track(&db, "/music/100%_Hits/a.mp3");
+        insert_test_track(&db, "/music/100xxHits/b.mp3");
+        let accented = insert_test_track(&db, r"C:\Música\c.mp3");
+        insert_test_track(&db, r"C:\Musica\d.mp3");
+
+        assert_eq!(directory_like_pattern("/music/100%_Hits/"), r"%music_100\%\_Hits_%");
+        assert_eq!(DbOperations::find_tracks_in_folder(&db, "/music/100%_Hits").unwrap(), vec![hits]);
+        // LIKE only ignores ASCII case, relative_to_directory does the rest
+        assert_eq!(DbOperations::find_tracks_in_folder(&db, r"c:\MÚSICA").unwrap(), vec![accented]);
+        assert_eq!(DbOperations::get_subdirectories(&db, "/music").unwrap(), vec!["/music/100%_Hits", "/music/100xxHits"]);
     }
 }
@@ -1,2 +1,55 @@
 // Database queries
-// TODO: Implement SQL queries for CRUD operations
+// SQL and name keys shared by the migrations and DbOperations
+use unicode_normalization::UnicodeNormalization;
+
+/// Key an artist or genre name is looked up by: trimmed, lowercased and NFC-normalized, so
+/// "Beatles", "beatles " and a decomposed "Beyonce\u{301}" each map to one row
+pub fn normalize_name(name: &str) -> String {
+    name.trim().to_lowercase().nfc().collect()
+}
+
+/// The artist a track's album is credited to: its album artist, "Various Artists" for
+/// compilations without one, otherwise its track artist. Together with the album name this
+/// identifies an albums row. `tracks` is the table name or alias to qualify the columns with.
+pub fn album_artist_key(tracks: &str) -> String {
+    format!(
+        "COALESCE({0}.album_artist, CASE WHEN {0}.compilation THEN 'Various Artists' END, {0}.artist)",
+        tracks
+    )
+}
+
+/// Rebuild album rows and track album_ids from the tracks' album/artist tags, see
+/// DbOperations::sync_albums. Also run once by the migration adding tracks.album_id.
+pub fn sync_albums_sql() -> String {
+    format!(
+        "INSERT INTO albums (name, artist, year)
+         SELECT t.album, {0}, MIN(t.year) FROM tracks t
+         WHERE t.album IS NOT NULL
+           AND NOT EXISTS (SELECT 1 FROM albums a WHERE a.name = t.album AND a.artist IS {0})
+         GROUP BY t.album, {0};
+         UPDATE tracks SET album_id = (
+             SELECT a.id FROM albums a WHERE a.name = tracks.album AND a.artist IS {1}
+         );
+         DELETE FROM albums WHERE id NOT IN (SELECT album_id FROM tracks WHERE album_id IS NOT NULL);",
+        album_artist_key("t"),
+        album_artist_key("tracks")
+    )
+}
+
+/// Set albums.is_compilation: any of the album's tracks is tagged as a compilation, or the
+/// album is credited to various artists and its tracks have more than one distinct artist.
+/// Runs after sync_albums_sql; add a WHERE clause to update only some albums.
+pub(crate) const MARK_COMPILATIONS_SQL: &str = "
+    UPDATE albums SET is_compilation = (
+        EXISTS (SELECT 1 FROM tracks t WHERE t.album_id = albums.id AND t.compilation)
+        OR (LOWER(albums.artist) IN ('various artists', 'various', 'va')
+            AND (SELECT COUNT(DISTINCT LOWER(t.artist)) FROM tracks t WHERE t.album_id = albums.id) > 1)
+    )";
+
+/// Tracks of the audio file ?1: the file itself and its CUE tracks ("<file>#<range>"). A
+/// range on file_path rather than a substr() comparison, so the file_path index is used.
+/// Other files can also start with "<file>#", so callers still check the rest is a range.
+pub const TRACKS_FOR_AUDIO_FILE_SQL: &str = "
+    SELECT id, file_path FROM tracks
+    WHERE file_path = ?1 OR (file_path >= ?1 || '#' AND file_path < ?1 || '$')
+    ORDER BY id";
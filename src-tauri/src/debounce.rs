@@ -0,0 +1,148 @@
+// Track change debouncing
+// Rapid skipping shouldn't trigger SMTC redraws, artwork extraction or play counting
+// for tracks that were only on screen for a moment
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a track must stay current before side effects fire
+pub const TRACK_SETTLE_MS: u64 = 500;
+
+struct TrackChange {
+    generation: u64,
+    /// None until the first track change
+    changed_at: Option<Instant>,
+    /// A play or skip was already counted for this listen
+    listen_counted: bool,
+}
+
+/// Generation counter bumped on every track change. Work scheduled for a generation
+/// only runs if no other track change happened before it settled.
+pub struct TrackChangeDebouncer {
+    settle: Duration,
+    state: Mutex<TrackChange>,
+}
+
+impl TrackChangeDebouncer {
+    pub fn new(settle: Duration) -> Self {
+        Self {
+            settle,
+            state: Mutex::new(TrackChange {
+                generation: 0,
+                changed_at: None,
+                listen_counted: false,
+            }),
+        }
+    }
+
+    /// Record that playback moved to a new track, returns the new generation
+    pub fn track_changed(&self) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        state.generation += 1;
+        state.changed_at = Some(Instant::now());
+        state.listen_counted = false;
+        state.generation
+    }
+
+    /// The generation of the current track
+    pub fn generation(&self) -> u64 {
+        self.state.lock().unwrap().generation
+    }
+
+    /// Whether the current track has been current for at least the settle interval
+    pub fn is_settled(&self) -> bool {
+        self.time_until_settled().is_zero()
+    }
+
+    /// How long until the current track has been current for the settle interval
+    fn time_until_settled(&self) -> Duration {
+        match self.state.lock().unwrap().changed_at {
+            Some(changed_at) => self.settle.saturating_sub(changed_at.elapsed()),
+            None => Duration::ZERO,
+        }
+    }
+
+    /// Claim the current listen for counting a play or a skip. True only the first time
+    /// after each track change, so one listen never counts as both.
+    pub fn claim_listen(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        !std::mem::replace(&mut state.listen_counted, true)
+    }
+
+    /// Wait out what is left of the settle interval, then report whether `generation` is
+    /// still current. Returns at once if it's already settled or already replaced.
+    pub fn wait_until_settled(&self, generation: u64) -> bool {
+        if self.generation() != generation {
+            return false;
+        }
+        let remaining = self.time_until_settled();
+        if !remaining.is_zero() {
+            thread::sleep(remaining);
+        }
+        self.generation() == generation
+    }
+
+    /// Run `f` on a background thread once the current track has settled.
+    /// Dropped if the track changes again in the meantime.
+    pub fn run_when_settled<F>(self: &Arc<Self>, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let generation = self.generation();
+        let debouncer = self.clone();
+        thread::spawn(move || {
+            if debouncer.wait_until_settled(generation) {
+                f();
+            }
+        });
+    }
+}
+
+impl Default for TrackChangeDebouncer {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(TRACK_SETTLE_MS))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rapid_skips_only_settle_final_track() {
+        let debouncer = TrackChangeDebouncer::new(Duration::ZERO);
+        let skipped: Vec<u64> = (0..2).map(|_| debouncer.track_changed()).collect();
+        let last = debouncer.track_changed();
+
+        for generation in skipped {
+            assert!(!debouncer.wait_until_settled(generation));
+        }
+        assert!(debouncer.wait_until_settled(last));
+    }
+
+    #[test]
+    fn test_waits_only_while_a_track_change_is_pending() {
+        let debouncer = TrackChangeDebouncer::new(Duration::from_secs(3600));
+        // Nothing changed yet, so there's nothing to wait for
+        assert!(debouncer.is_settled());
+        assert!(debouncer.wait_until_settled(debouncer.generation()));
+
+        // A replaced generation is dropped without waiting out the interval
+        let skipped = debouncer.track_changed();
+        debouncer.track_changed();
+        assert!(!debouncer.is_settled());
+        assert!(!debouncer.wait_until_settled(skipped));
+    }
+
+    #[test]
+    fn test_listen_counts_as_play_or_skip_once() {
+        let debouncer = TrackChangeDebouncer::new(Duration::ZERO);
+        debouncer.track_changed();
+        assert!(debouncer.claim_listen());
+        assert!(!debouncer.claim_listen());
+
+        // Replaying the track is a new listen
+        debouncer.track_changed();
+        assert!(debouncer.claim_listen());
+    }
+}
@@ -0,0 +1,199 @@
+// Error type returned by commands
+// Serialized as {kind, message}, so the frontend can tell a missing file from a database
+// error or an undecodable track and still show the message as it is.
+use serde::Serialize;
+use std::fmt::Display;
+
+#[derive(Debug, thiserror::Error, Serialize)]
+#[serde(tag = "kind", content = "message", rename_all = "camelCase")]
+pub enum AppError {
+    /// Reading or writing files
+    #[error("{0}")]
+    Io(String),
+    /// The library database
+    #[error("{0}")]
+    Db(String),
+    /// Audio that can't be opened or decoded
+    #[error("{0}")]
+    Decode(String),
+    /// A track, album, queue or file that doesn't exist
+    #[error("{0}")]
+    NotFound(String),
+    /// Reading or writing tags and embedded pictures
+    #[error("{0}")]
+    Metadata(String),
+    /// Arguments or settings a command can't accept
+    #[error("{0}")]
+    InvalidInput(String),
+    /// Anything else: poisoned locks, background tasks, audio devices, Last.fm
+    #[error("{0}")]
+    Other(String),
+}
+
+impl AppError {
+    /// The same kind of error, its message prefixed with what was being done
+    pub fn context(self, action: impl Display) -> Self {
+        let prefix = |message: String| format!("{}: {}", action, message);
+        match self {
+            AppError::Io(message) => AppError::Io(prefix(message)),
+            AppError::Db(message) => AppError::Db(prefix(message)),
+            AppError::Decode(message) => AppError::Decode(prefix(message)),
+            AppError::NotFound(message) => AppError::NotFound(prefix(message)),
+            AppError::Metadata(message) => AppError::Metadata(prefix(message)),
+            AppError::InvalidInput(message) => AppError::InvalidInput(prefix(message)),
+            AppError::Other(message) => AppError::Other(prefix(message)),
+        }
+    }
+
+    fn from_io(error: &std::io::Error, message: String) -> Self {
+        match error.kind() {
+            std::io::ErrorKind::NotFound => AppError::NotFound(message),
+            _ => AppError::Io(message),
+        }
+    }
+
+    fn from_db(error: &rusqlite::Error, message: String) -> Self {
+        match error {
+            rusqlite::Error::QueryReturnedNoRows => AppError::NotFound(message),
+            _ => AppError::Db(message),
+        }
+    }
+}
+
+/// Turn a failed result into an `AppError` that says what was being done
+pub trait ErrorContext<T> {
+    fn context(self, action: impl Display) -> Result<T, AppError>;
+
+    fn with_context<D: Display>(self, action: impl FnOnce() -> D) -> Result<T, AppError>;
+}
+
+impl<T, E: Into<AppError>> ErrorContext<T> for Result<T, E> {
+    fn context(self, action: impl Display) -> Result<T, AppError> {
+        self.map_err(|e| e.into().context(action))
+    }
+
+    fn with_context<D: Display>(self, action: impl FnOnce() -> D) -> Result<T, AppError> {
+        self.map_err(|e| e.into().context(action()))
+    }
+}
+
+/// Classified by the first error in the chain this knows about, keeping the outer message
+impl From<anyhow::Error> for AppError {
+    fn from(error: anyhow::Error) -> Self {
+        let message = error.to_string();
+        for cause in error.chain() {
+            if let Some(e) = cause.downcast_ref::<AppError>() {
+                return match e {
+                    AppError::Io(_) => AppError::Io(message),
+                    AppError::Db(_) => AppError::Db(message),
+                    AppError::Decode(_) => AppError::Decode(message),
+                    AppError::NotFound(_) => AppError::NotFound(message),
+                    AppError::Metadata(_) => AppError::Metadata(message),
+                    AppError::InvalidInput(_) => AppError::InvalidInput(message),
+                    AppError::Other(_) => AppError::Other(message),
+                };
+            }
+            if let Some(e) = cause.downcast_ref::<rusqlite::Error>() {
+                return AppError::from_db(e, message);
+            }
+            if let Some(e) = cause.downcast_ref::<std::io::Error>() {
+                return AppError::from_io(e, message);
+            }
+            if cause.is::<lofty::error::LoftyError>() || cause.is::<id3::Error>() {
+                return AppError::Metadata(message);
+            }
+            if cause.is::<symphonia::core::errors::Error>() {
+                return AppError::Decode(message);
+            }
+        }
+        AppError::Other(message)
+    }
+}
+
+impl From<rusqlite::Error> for AppError {
+    fn from(error: rusqlite::Error) -> Self {
+        let message = error.to_string();
+        AppError::from_db(&error, message)
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(error: std::io::Error) -> Self {
+        let message = error.to_string();
+        AppError::from_io(&error, message)
+    }
+}
+
+impl From<lofty::error::LoftyError> for AppError {
+    fn from(error: lofty::error::LoftyError) -> Self {
+        AppError::Metadata(error.to_string())
+    }
+}
+
+impl From<symphonia::core::errors::Error> for AppError {
+    fn from(error: symphonia::core::errors::Error) -> Self {
+        match error {
+            symphonia::core::errors::Error::IoError(e) => e.into(),
+            e => AppError::Decode(e.to_string()),
+        }
+    }
+}
+
+impl From<tauri::Error> for AppError {
+    fn from(error: tauri::Error) -> Self {
+        AppError::Other(error.to_string())
+    }
+}
+
+impl From<tokio::task::JoinError> for AppError {
+    fn from(error: tokio::task::JoinError) -> Self {
+        AppError::Other(error.to_string())
+    }
+}
+
+impl<T> From<std::sync::PoisonError<T>> for AppError {
+    fn from(error: std::sync::PoisonError<T>) -> Self {
+        AppError::Other(error.to_string())
+    }
+}
+
+impl From<String> for AppError {
+    fn from(message: String) -> Self {
+        AppError::Other(message)
+    }
+}
+
+impl From<&str> for AppError {
+    fn from(message: &str) -> Self {
+        AppError::Other(message.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serializes_kind_and_message() {
+        let error = AppError::NotFound("Track not found: 7".to_string());
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({ "kind": "notFound", "message": "Track not found: 7" })
+        );
+    }
+
+    #[test]
+    fn test_anyhow_errors_keep_their_kind() {
+        let db: anyhow::Error = rusqlite::Error::InvalidQuery.into();
+        let error = AppError::from(db.context("Failed to get tracks"));
+        assert!(matches!(error, AppError::Db(ref message) if message == "Failed to get tracks"));
+
+        let missing: Result<(), anyhow::Error> = Err(AppError::NotFound("Queue not found: 3".to_string()).into());
+        let error = missing.context("Failed to get queue").unwrap_err();
+        assert!(matches!(error, AppError::NotFound(ref message) if message == "Failed to get queue: Queue not found: 3"));
+
+        let io = std::io::Error::new(std::io::ErrorKind::PermissionDenied, "denied");
+        assert!(matches!(AppError::from(anyhow::Error::from(io)), AppError::Io(_)));
+        assert!(matches!(AppError::from("Lock error".to_string()), AppError::Other(_)));
+    }
+}
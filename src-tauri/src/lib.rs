@@ -3,14 +3,22 @@
 mod audio;
 mod commands;
 mod db;
+mod debounce;
+mod error;
 mod library;
 mod metadata;
+mod path_utils;
 mod playlist;
 mod queue;
+mod scrobble;
 mod settings;
 mod smtc;
 mod state;
+#[cfg(test)]
+mod test_support;
 
+use audio::crossfeed::CrossfeedLevel;
+use audio::equalizer::EqualizerParams;
 use audio::player::Player;
 use db::connection::DatabaseConnection;
 use settings::AppSettings;
@@ -93,6 +101,33 @@ pub fn run() {
             // Initialize audio player
             let player = Player::new();
 
+            // Restore the saved gapless, fade, crossfade, normalization, equalizer, crossfeed and output device settings
+            let settings = AppSettings::load(&app_dir).unwrap_or_default();
+            let playback_settings = &settings.playback;
+            player.set_gapless(playback_settings.gapless);
+            player.set_fade(&playback_settings.fade);
+            player.set_crossfade_ms(playback_settings.crossfade_ms as i64);
+            player.set_equalizer(EqualizerParams::from_settings(playback_settings));
+            player.set_normalization_enabled(playback_settings.replay_gain.enabled);
+            player.set_output_device(playback_settings.output_device.clone());
+            if playback_settings.crossfeed_enabled {
+                if let Some(level) = CrossfeedLevel::from_name(&playback_settings.crossfeed_level) {
+                    player.set_crossfeed(level);
+                }
+            }
+
+            // Let the frontend know when playback switched to a new output device
+            let device_app_handle = app.handle().clone();
+            player.set_device_changed_callback(move |device_name| {
+                let _ = device_app_handle.emit("audio-device-changed", device_name);
+            });
+
+            // Tell the frontend when a track finished so it can advance the queue
+            let track_ended_app_handle = app.handle().clone();
+            player.set_track_ended_callback(move |file_path| {
+                let _ = track_ended_app_handle.emit("track-ended", file_path);
+            });
+
             // Initialize SMTC (Windows only)
             let smtc = match SmtcManager::new() {
                 Ok(s) => {
@@ -105,24 +140,27 @@ pub fn run() {
                 }
             };
 
-            // Set up SMTC button callback to emit events
+            // Set up SMTC button callback to emit events: one "media-key" event carrying the
+            // button name, plus the per-button "smtc-*" events the SMTC service listens to
             if let Some(ref smtc) = smtc {
                 let app_handle = app.handle().clone();
-                let _ = smtc.set_button_callback(move |button| {
-                    let event_name = match button {
-                        SmtcButton::Play => "smtc-play",
-                        SmtcButton::Pause => "smtc-pause",
-                        SmtcButton::Stop => "smtc-stop",
-                        SmtcButton::Next => "smtc-next",
-                        SmtcButton::Previous => "smtc-previous",
-                    };
-                    let _ = app_handle.emit(event_name, ());
+                let _ = smtc.set_button_callback(move |button: SmtcButton| {
+                    let _ = app_handle.emit("media-key", button.name());
+                    let _ = app_handle.emit(&format!("smtc-{}", button.name()), ());
                 });
             }
 
-            // Create and manage app state (now includes app_dir for settings)
-            let app_state = AppState::new(player, db, smtc, app_dir);
+            // Create and manage app state (now includes app_dir and the loaded settings)
+            let app_state = AppState::new(player, db, smtc, app_dir, settings);
             app.manage(app_state);
+            commands::restart_library_watcher(app.handle());
+            
+            // Report plays to Last.fm (and retry plays queued while offline)
+            let app_handle = app.handle().clone();
+            let state = app.state::<AppState>();
+            scrobble::spawn_monitor(state.player.clone(), state.db.clone(), move || {
+                app_handle.state::<AppState>().settings().ok().map(|s| s.scrobble)
+            });
 
             // Set window icon
             if let Some(window) = app.get_webview_window("main") {
@@ -184,24 +222,18 @@ pub fn run() {
         .on_window_event(|window, event| {
             match event {
                 WindowEvent::CloseRequested { api, .. } => {
-                    let app_dir = window.app_handle().path().app_data_dir()
-                        .expect("Failed to get app data directory");
-                    let settings = AppSettings::load(&app_dir).unwrap_or_default();
+                    let settings = window.state::<AppState>().settings().unwrap_or_default();
                     if settings.interface.behaviour.on_close == "tray" {
                         api.prevent_close();
                         let _ = window.hide();
                     }
                 }
-                WindowEvent::Focused(false) => {
-                    // Check if window was minimized and should go to tray instead
-                    if window.is_minimized().unwrap_or(false) {
-                        let app_dir = window.app_handle().path().app_data_dir()
-                            .expect("Failed to get app data directory");
-                        let settings = AppSettings::load(&app_dir).unwrap_or_default();
-                        if settings.interface.behaviour.on_minimize == "tray" {
-                            let _ = window.unminimize();
-                            let _ = window.hide();
-                        }
+                // Check if window was minimized and should go to tray instead
+                WindowEvent::Focused(false) if window.is_minimized().unwrap_or(false) => {
+                    let settings = window.state::<AppState>().settings().unwrap_or_default();
+                    if settings.interface.behaviour.on_minimize == "tray" {
+                        let _ = window.unminimize();
+                        let _ = window.hide();
                     }
                 }
                 _ => {}
@@ -211,34 +243,79 @@ pub fn run() {
             commands::set_current_track,
             commands::clear_current_track,
             commands::scan_library,
+            commands::cancel_scan,
             commands::add_scan_path,
+            commands::validate_scan_path,
             commands::get_all_scan_paths,
+            commands::get_track_locations,
+            commands::get_library_status,
+            commands::get_library_stats,
             commands::remove_scan_path,
             commands::pick_folder,
             commands::get_all_tracks,
+            commands::get_tracks_page,
+            commands::get_track,
             commands::get_all_albums,
+            commands::get_compilation_albums,
             commands::get_all_artists,
             commands::get_all_genres,
+            commands::get_all_years,
+            commands::get_folder_tree,
+            commands::browse_directory,
             commands::clear_library,
+            commands::delete_track,
+            commands::find_duplicate_tracks,
+            commands::find_duplicate_metadata,
+            commands::reset_play_statistics,
             commands::get_tracks_by_artist,
+            commands::get_tracks_by_album_artist,
+            commands::get_tracks_by_composer,
             commands::get_tracks_by_genre,
+            commands::get_tracks_by_year,
+            commands::get_tracks_by_decade,
             commands::get_tracks_by_album,
+            commands::get_tracks_by_album_and_artist,
+            commands::get_tracks_by_album_id,
+            commands::get_album_stats,
+            commands::ensure_track,
             commands::get_current_track,
+            commands::refresh_track,
+            commands::update_track_tags,
             commands::get_album_art,
+            commands::get_album_art_path,
+            commands::set_album_art,
+            commands::set_album_artwork,
+            commands::prune_artwork_cache,
             commands::get_lyrics,
             commands::create_queue_from_tracks,
+            commands::play_track_now,
+            commands::create_queue_from_artist,
+            commands::create_queue_from_genre,
             commands::get_all_queues,
             commands::get_queue_tracks,
+            commands::get_queue_tracks_annotated,
             commands::set_active_queue,
             commands::get_active_queue,
             commands::delete_queue,
+            commands::clear_queue,
+            commands::prune_queues,
             commands::update_queue_current_index,
             commands::get_queue_current_index,
             commands::get_next_queue,
             commands::get_queue_track_at_position,
             commands::get_queue_track_at_shuffled_position,
+            commands::find_track_position_in_queue,
+            commands::queue_jump_to,
             commands::get_queue_length,
+            commands::get_queue_duration,
+            commands::get_queue_total_duration_ms,
+            commands::get_queue_remaining_duration_ms,
             commands::toggle_queue_shuffle,
+            commands::get_queue_playback_modes,
+            commands::set_queue_playback_modes,
+            commands::set_queue_repeat_mode,
+            commands::get_queue_repeat_mode,
+            commands::get_next_queue_position,
             commands::find_shuffled_position,
             commands::set_queue_shuffle_seed,
             commands::get_queue_shuffle_seed,
@@ -247,18 +324,36 @@ pub fn run() {
             commands::get_recent_tracks,
             commands::get_most_played_tracks,
             commands::get_unplayed_tracks,
+            commands::get_never_finished_tracks,
+            commands::get_favorite_tracks,
+            commands::get_recently_played_albums,
+            commands::get_tracks_played_on_day,
             commands::record_track_play,
+            commands::record_play,
+            commands::record_skip,
+            commands::set_track_rating,
+            commands::toggle_favorite,
             commands::get_all_playlists,
             commands::create_playlist,
             commands::rename_playlist,
             commands::add_track_to_playlist,
             commands::get_playlist_tracks,
+            commands::export_playlist_m3u8,
             commands::remove_track_from_playlist,
+            commands::remove_playlist_track_at_position,
             commands::delete_playlist,
+            commands::create_smart_playlist,
+            commands::get_all_smart_playlists,
+            commands::get_smart_playlist_tracks,
+            commands::delete_smart_playlist,
             commands::reorder_playlist_track,
+            commands::create_playlist_from_folder,
+            commands::import_playlist_m3u8,
             commands::reorder_queue_track,
+            commands::sort_queue,
             commands::append_tracks_to_queue,
             commands::insert_tracks_after_position,
+            commands::play_next,
             commands::remove_track_at_position,
             commands::save_album_art,
             // Audio player commands
@@ -267,21 +362,37 @@ pub fn run() {
             commands::player_resume,
             commands::player_stop,
             commands::player_seek,
+            commands::seek_to_percent,
+            commands::get_output_info,
+            commands::list_audio_devices,
+            commands::set_audio_device,
+            commands::restore_session,
+            commands::save_session,
             commands::player_set_volume,
             commands::player_set_volume_db,
             commands::player_get_state,
+            commands::get_playback_position_ms,
             commands::player_has_track_ended,
+            commands::set_stop_after_current,
+            commands::get_stop_after_current,
             // Gapless playback commands
             commands::player_preload_next_track,
             commands::player_clear_preloaded_track,
             commands::player_has_gapless_transition,
             // Audio player normalization commands
             commands::player_play_with_normalization,
+            commands::player_crossfade_to,
             commands::player_set_track_gain,
             commands::player_set_normalization_enabled,
             commands::player_get_normalization_enabled,
+            commands::set_crossfeed,
             commands::analyze_library_loudness,
+            commands::backfill_durations,
+            commands::start_loudness_backfill,
+            commands::cancel_loudness_backfill,
             commands::recalculate_track_replaygain,
+            commands::analyze_track_loudness,
+            commands::analyze_album_loudness,
             // SMTC commands
             commands::smtc_update_metadata,
             commands::smtc_set_playback_status,
@@ -290,6 +401,21 @@ pub fn run() {
             // Settings commands
             commands::get_settings,
             commands::save_settings,
+            commands::set_fade_settings,
+            commands::set_crossfade_ms,
+            commands::get_tab_config,
+            commands::set_tab_config,
+            commands::reset_tabs,
+            // Last.fm commands
+            commands::lastfm_get_auth_url,
+            commands::set_lastfm_session,
+            // Equalizer commands
+            commands::set_equalizer_enabled,
+            commands::set_equalizer_bands,
+            commands::list_equalizer_presets,
+            commands::save_equalizer_preset,
+            commands::delete_equalizer_preset,
+            commands::apply_equalizer_preset,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -1,16 +1,27 @@
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::fs::File;
 use std::io::Read;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use crate::db::connection::DatabaseConnection;
+use crate::error::AppError;
+use crate::audio::decoder::AudioDecoder;
+use crate::db::models::{DurationBackfillProgress, Track, TrackLoudness};
 use crate::db::operations::DbOperations;
+use crate::metadata::artwork::{find_folder_art, read_album_art};
+use crate::metadata::cue::{cue_sheets_in_folder, cue_track_path, is_cue_sheet, split_cue_track_path, CueSheet, CueTrack};
 use crate::metadata::extractor::MetadataExtractor;
 use crate::metadata::parser::{parse_artists, parse_genres};
-use crate::metadata::loudness::analyze_loudness_sampled;
+use crate::metadata::loudness::{measure_track_loudness, LoudnessSource};
+use crate::metadata::sidecar::SidecarMetadata;
+use crate::path_utils::to_extended_length;
 use blake3;
 use rayon::prelude::*;
 
+/// Pause between tracks in the background loudness backfill so it doesn't starve playback
+const BACKFILL_THROTTLE_MS: u64 = 50;
+
 /// Result of an indexing operation
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct IndexingResult {
@@ -18,9 +29,16 @@ pub struct IndexingResult {
     pub successful: usize,
     pub failed: usize,
     pub skipped: usize,
+    /// Skipped files that weren't even read because their size and modification time
+    /// matched the stored values (included in `skipped`)
+    #[serde(default)]
+    pub unchanged: usize,
     pub updated: usize,
     pub removed: usize,
     pub errors: Vec<String>,
+    /// The scan was cancelled; the counts cover only the files processed before that
+    #[serde(default)]
+    pub aborted: bool,
 }
 
 /// Progress update for indexing
@@ -41,15 +59,36 @@ pub struct LoudnessAnalysisProgress {
     pub failed: usize,
 }
 
+/// What indexing a single file did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IndexOutcome {
+    /// Inserted, or its content changed and the row was updated
+    Updated,
+    /// Read and hashed, but identical to what's stored (or recorded as a duplicate)
+    Skipped,
+    /// Not read at all: size and modification time match the stored values
+    Unchanged,
+}
+
 /// Library indexer for adding tracks to database
 pub struct LibraryIndexer;
 
 impl LibraryIndexer {
-    /// Index a list of audio files into the database with progress callback
+    /// Index a list of audio files into the database with progress callback.
+    /// `prefer_sidecar` makes sidecar metadata files override embedded tags.
+    /// With `dedup_by_hash`, a file identical to an already indexed track is recorded as an
+    /// alternate location of that track instead of becoming a new one.
+    /// Files whose size and modification time match the stored values are skipped without
+    /// being read; anything else is hashed, and the hash decides whether it really changed.
+    /// Setting `cancel` stops before the next file and returns the partial result as aborted.
+    /// Audio files described by a CUE sheet are indexed through the sheet, one track per
+    /// CUE track, whether the batch lists the sheet, the audio file or both.
     pub fn index_files_with_progress<P: AsRef<Path>, F>(
         paths: &[P],
         db: &DatabaseConnection,
-        last_scanned: Option<i64>,
+        prefer_sidecar: bool,
+        dedup_by_hash: bool,
+        cancel: &AtomicBool,
         mut progress_callback: F,
     ) -> Result<IndexingResult, anyhow::Error>
     where
@@ -59,10 +98,22 @@ impl LibraryIndexer {
         let mut successful = 0;
         let mut failed = 0;
         let mut skipped = 0;
+        let mut unchanged = 0;
         let mut updated = 0;
         let mut errors = Vec::new();
+        let mut aborted = false;
+        
+        // Audio files covered by CUE sheets, per folder, and the sheets indexed so far
+        let mut cue_folders: HashMap<PathBuf, HashMap<PathBuf, PathBuf>> = HashMap::new();
+        let mut indexed_sheets: HashSet<PathBuf> = HashSet::new();
         
         for (index, path) in paths.iter().enumerate() {
+            if cancel.load(Ordering::SeqCst) {
+                eprintln!("[Scan] Cancelled after {} of {} files", index, total_files);
+                aborted = true;
+                break;
+            }
+            
             let path_ref = path.as_ref();
             
             // Send progress update
@@ -75,12 +126,30 @@ impl LibraryIndexer {
                     .to_string(),
             });
             
-            match Self::index_single_file(path_ref, db, last_scanned) {
-                Ok(was_updated) => {
-                    if was_updated {
-                        updated += 1;
-                    } else {
-                        skipped += 1;
+            let sheet = if is_cue_sheet(path_ref) {
+                Some(path_ref.to_path_buf())
+            } else {
+                let folder = path_ref.parent().unwrap_or(Path::new("")).to_path_buf();
+                cue_folders.entry(folder)
+                    .or_insert_with_key(|folder| cue_sheets_in_folder(folder))
+                    .get(path_ref)
+                    .cloned()
+            };
+            let indexed = match sheet {
+                Some(sheet) if !indexed_sheets.insert(sheet.clone()) => Ok(IndexOutcome::Skipped),
+                Some(sheet) => Self::index_cue_sheet(&sheet, db, true),
+                None => Self::index_single_file(path_ref, db, prefer_sidecar, dedup_by_hash, true),
+            };
+            
+            match indexed {
+                Ok(outcome) => {
+                    match outcome {
+                        IndexOutcome::Updated => updated += 1,
+                        IndexOutcome::Skipped => skipped += 1,
+                        IndexOutcome::Unchanged => {
+                            skipped += 1;
+                            unchanged += 1;
+                        }
                     }
                     successful += 1;
                 }
@@ -97,15 +166,72 @@ impl LibraryIndexer {
             successful,
             failed,
             skipped,
+            unchanged,
             updated,
             removed: 0, // Removal is now handled separately in the command
             errors,
+            aborted,
         })
     }
     
+    /// Make sure a single file is in the database and return its Track.
+    /// Used for files opened from outside the library (e.g. drag-and-drop);
+    /// the file is indexed on its own and is NOT added to the scan paths.
+    pub fn ensure_track(
+        path: &Path,
+        db: &DatabaseConnection,
+    ) -> Result<crate::db::models::Track, anyhow::Error> {
+        let file_path_str = path.to_string_lossy().to_string();
+
+        // Already indexed - return as-is
+        if let Some(track) = DbOperations::get_track_by_file_path(db, &file_path_str)? {
+            return Ok(track);
+        }
+
+        if !to_extended_length(path).is_file() {
+            return Err(AppError::NotFound(format!("File not found: {}", path.display())).into());
+        }
+
+        Self::index_single_file(path, db, false, false, true)?;
+
+        DbOperations::get_track_by_file_path(db, &file_path_str)?
+            .ok_or_else(|| anyhow::anyhow!("Track was not indexed: {}", path.display()))
+    }
+
+    /// Re-read a single track's file after its tags were edited outside the app and update
+    /// its row and artist/genre links, without scanning the library. Returns the fresh Track.
+    pub fn refresh_track(
+        track_id: i64,
+        db: &DatabaseConnection,
+        prefer_sidecar: bool,
+    ) -> Result<crate::db::models::Track, anyhow::Error> {
+        let track = DbOperations::get_track_by_id(db, track_id)?
+            .ok_or_else(|| AppError::NotFound(format!("Track not found: {}", track_id)))?;
+        let (file_path, cue_range) = split_cue_track_path(&track.file_path);
+        let path = Path::new(file_path);
+
+        if !to_extended_length(path).is_file() {
+            return Err(AppError::NotFound(format!("File not found: {}", path.display())).into());
+        }
+
+        // Always re-read (an edit can keep size and mtime), the hash check in the upsert
+        // skips files that didn't actually change. CUE tracks are re-read through their sheet.
+        if cue_range.is_some() {
+            let sheet = path.parent()
+                .and_then(|folder| cue_sheets_in_folder(folder).remove(path))
+                .ok_or_else(|| AppError::NotFound(format!("No CUE sheet found for {}", path.display())))?;
+            Self::index_cue_sheet(&sheet, db, false)?;
+        } else {
+            Self::index_single_file(path, db, prefer_sidecar, false, false)?;
+        }
+
+        DbOperations::get_track_by_id(db, track_id)?
+            .ok_or_else(|| AppError::NotFound(format!("Track not found: {}", track_id)).into())
+    }
+
     /// Calculate file hash using BLAKE3 (fast and secure)
-    fn calculate_file_hash(path: &Path) -> Result<String, anyhow::Error> {
-        let mut file = File::open(path)?;
+    pub(crate) fn calculate_file_hash(path: &Path) -> Result<String, anyhow::Error> {
+        let mut file = File::open(to_extended_length(path))?;
         let mut hasher = blake3::Hasher::new();
         let mut buffer = [0; 8192];
         
@@ -120,99 +246,364 @@ impl LibraryIndexer {
         Ok(hasher.finalize().to_hex().to_string())
     }
     
-    /// Index a single audio file, returns true if updated/inserted, false if skipped
+    /// Modification time (unix milliseconds) and size of a file, None if unavailable
+    fn file_stamp(path: &Path) -> Option<(i64, i64)> {
+        let metadata = std::fs::metadata(to_extended_length(path)).ok()?;
+        let modified = metadata.modified().ok()?
+            .duration_since(std::time::UNIX_EPOCH).ok()?;
+        Some((modified.as_millis() as i64, metadata.len() as i64))
+    }
+
+    /// Index a single audio file. With `trust_mtime`, a file whose size and modification
+    /// time match the stored track is skipped without being read.
     fn index_single_file(
         path: &Path,
         db: &DatabaseConnection,
-        last_scanned: Option<i64>,
-    ) -> Result<bool, anyhow::Error> {
-        // If last_scanned is provided, check file modification time
-        if let Some(last_scan_time) = last_scanned {
-            if let Ok(metadata) = std::fs::metadata(path) {
-                if let Ok(modified) = metadata.modified() {
-                    if let Ok(modified_duration) = modified.duration_since(std::time::UNIX_EPOCH) {
-                        let modified_timestamp = modified_duration.as_secs() as i64;
-                        // Skip if file wasn't modified since last scan
-                        if modified_timestamp < last_scan_time {
-                            return Ok(false);
-                        }
-                    }
-                }
-            }
+        prefer_sidecar: bool,
+        dedup_by_hash: bool,
+        trust_mtime: bool,
+    ) -> Result<IndexOutcome, anyhow::Error> {
+        let file_path = path.to_string_lossy();
+        let stamp = Self::file_stamp(path);
+        
+        if trust_mtime && stamp.is_some()
+            && DbOperations::get_track_file_stamp(db, &file_path)? == stamp
+        {
+            return Ok(IndexOutcome::Unchanged);
         }
         
         // Calculate file hash first
         let file_hash = Self::calculate_file_hash(path)?;
         
+        // A copy of a track that's already in the library only adds a location
+        if dedup_by_hash
+            && DbOperations::record_duplicate_location(db, &file_path, &file_hash, stamp)?.is_some()
+        {
+            return Ok(IndexOutcome::Skipped);
+        }
+        
         // Extract metadata - this already creates a Track struct
         // Fallback is now handled inside extract_from_file
-        let track = MetadataExtractor::extract_from_file(path)?;
+        let mut track = MetadataExtractor::extract_from_file(path)?;
+        
+        // Overlay user-maintained sidecar metadata (<file>.json, metadata.json, album.nfo)
+        if let Some(sidecar) = SidecarMetadata::find_for(path) {
+            sidecar.apply_to(&mut track, prefer_sidecar);
+        }
         
         // Upsert track with hash comparison
         let (track_id, was_updated) = DbOperations::upsert_track_with_hash(db, &track, &file_hash)?;
         
+        // Remember the stamp the hash was taken at, so the next scan can skip the file
+        if let Some((mtime, _)) = stamp {
+            DbOperations::set_track_mtime(db, track_id, mtime)?;
+        }
+        
         // Only update relationships if track was actually updated/inserted
         if was_updated {
-            // Parse multi-value artist field
-            let artist_names = if let Some(ref artist_name) = track.artist {
-                parse_artists(artist_name)
-            } else {
-                vec![]
-            };
+            Self::update_track_links(db, track_id, &track, path)?;
             
-            // Parse multi-value genre field
-            let genre_names = if let Some(ref genre_name) = track.genre {
-                parse_genres(genre_name)
-            } else {
-                vec![]
+            // Rows this file had as CUE tracks, from a sheet that's gone now
+            for (id, _) in DbOperations::get_tracks_for_audio_file(db, &file_path)? {
+                if id != track_id {
+                    DbOperations::delete_track(db, id)?;
+                }
+            }
+        }
+        
+        Ok(if was_updated { IndexOutcome::Updated } else { IndexOutcome::Skipped })
+    }
+    
+    /// Index the tracks of a CUE sheet. Each CUE track gets its own row, read from the audio
+    /// file's tags and overridden by the sheet. Rows the audio file had before (the whole
+    /// file, or tracks the sheet no longer lists) are removed.
+    fn index_cue_sheet(
+        cue_path: &Path,
+        db: &DatabaseConnection,
+        trust_mtime: bool,
+    ) -> Result<IndexOutcome, anyhow::Error> {
+        let sheet = CueSheet::read(cue_path)?;
+        let sheet_bytes = std::fs::read(to_extended_length(cue_path))?;
+        let cue_stamp = Self::file_stamp(cue_path);
+        
+        let mut outcomes = Vec::new();
+        for (audio_path, cue_tracks) in sheet.tracks_by_file(cue_path) {
+            let audio_file = audio_path.to_string_lossy().to_string();
+            let file_paths: Vec<String> = cue_tracks.iter()
+                .map(|cue_track| cue_track_path(&audio_file, cue_track.range))
+                .collect();
+            
+            // Changing either the sheet or the audio file re-reads the tracks
+            let stamp = match (cue_stamp, Self::file_stamp(&audio_path)) {
+                (Some((cue_mtime, _)), Some((mtime, size))) => Some((mtime.max(cue_mtime), size)),
+                _ => None,
             };
+            let mut unchanged = trust_mtime && stamp.is_some();
+            for file_path in &file_paths {
+                if !unchanged {
+                    break;
+                }
+                unchanged = DbOperations::get_track_file_stamp(db, file_path)? == stamp;
+            }
+            if unchanged {
+                outcomes.push(IndexOutcome::Unchanged);
+                continue;
+            }
             
-            // Insert individual artists
-            for artist in &artist_names {
-                let _ = DbOperations::insert_or_get_artist(db, artist)?;
+            // The tracks share the audio, so the hash covers the audio and the sheet
+            let mut hasher = blake3::Hasher::new();
+            hasher.update(Self::calculate_file_hash(&audio_path)?.as_bytes());
+            hasher.update(&sheet_bytes);
+            let file_hash = hasher.finalize().to_hex().to_string();
+            
+            let file_track = MetadataExtractor::extract_from_file(&audio_path)?;
+            let mut updated = false;
+            for (cue_track, file_path) in cue_tracks.iter().zip(&file_paths) {
+                let track = Self::cue_track(&sheet, cue_track, file_path, cue_tracks.len(), &file_track);
+                let (track_id, was_updated) = DbOperations::upsert_track_with_hash(db, &track, &file_hash)?;
+                if let Some((mtime, _)) = stamp {
+                    DbOperations::set_track_mtime(db, track_id, mtime)?;
+                }
+                if was_updated {
+                    Self::update_track_links(db, track_id, &track, &audio_path)?;
+                    updated = true;
+                }
             }
             
-            // Insert individual genres
-            for genre in &genre_names {
-                let _ = DbOperations::insert_or_get_genre(db, genre)?;
+            for (id, path) in DbOperations::get_tracks_for_audio_file(db, &audio_file)? {
+                if !file_paths.contains(&path) {
+                    DbOperations::delete_track(db, id)?;
+                }
+            }
+            outcomes.push(if updated { IndexOutcome::Updated } else { IndexOutcome::Skipped });
+        }
+        
+        Ok(if outcomes.contains(&IndexOutcome::Updated) {
+            IndexOutcome::Updated
+        } else if !outcomes.is_empty() && outcomes.iter().all(|o| *o == IndexOutcome::Unchanged) {
+            IndexOutcome::Unchanged
+        } else {
+            IndexOutcome::Skipped
+        })
+    }
+    
+    /// Track row for one CUE track: the sheet's fields where it has them, the audio
+    /// file's tags otherwise
+    fn cue_track(sheet: &CueSheet, cue_track: &CueTrack, file_path: &str, track_total: usize, file_track: &Track) -> Track {
+        let range = cue_track.range;
+        let duration_ms = match range.end_ms {
+            Some(end_ms) => Some(end_ms - range.start_ms),
+            None => file_track.duration_ms.map(|duration_ms| (duration_ms - range.start_ms).max(0)),
+        };
+        let year = sheet.date.as_deref()
+            .and_then(|date| date.get(..4))
+            .and_then(|year| year.parse().ok())
+            .or(file_track.year);
+        
+        Track {
+            file_path: file_path.to_string(),
+            title: cue_track.title.clone().unwrap_or_else(|| format!("Track {:02}", cue_track.number)),
+            artist: cue_track.performer.clone()
+                .or_else(|| sheet.performer.clone())
+                .or_else(|| file_track.artist.clone()),
+            album: sheet.title.clone().or_else(|| file_track.album.clone()),
+            album_artist: sheet.performer.clone().or_else(|| file_track.album_artist.clone()),
+            year,
+            track_number: Some(cue_track.number),
+            track_total: Some(track_total as i32),
+            duration_ms,
+            genre: sheet.genre.clone().or_else(|| file_track.genre.clone()),
+            ..file_track.clone()
+        }
+    }
+    
+    /// Rebuild a track's artist, album artist, genre and album links after its row changed.
+    /// `path` is the audio file the track was read from.
+    fn update_track_links(
+        db: &DatabaseConnection,
+        track_id: i64,
+        track: &Track,
+        path: &Path,
+    ) -> Result<(), anyhow::Error> {
+        // Parse multi-value artist field
+        let artist_names = if let Some(ref artist_name) = track.artist {
+            parse_artists(artist_name)
+        } else {
+            vec![]
+        };
+        
+        // Collaborative album artists ("Artist A & Artist B") link to each artist too
+        let album_artist_names = if let Some(ref album_artist) = track.album_artist {
+            parse_artists(album_artist)
+        } else {
+            vec![]
+        };
+        
+        // Parse multi-value genre field
+        let genre_names = if let Some(ref genre_name) = track.genre {
+            parse_genres(genre_name)
+        } else {
+            vec![]
+        };
+        
+        // Insert individual artists
+        for artist in &artist_names {
+            let _ = DbOperations::insert_or_get_artist(db, artist)?;
+        }
+        
+        // Insert individual genres
+        for genre in &genre_names {
+            let _ = DbOperations::insert_or_get_genre(db, genre)?;
+        }
+        
+        // Link the track to its album row (created on first sight); rows left empty
+        // by retagged or removed tracks are dropped by sync_albums after a scan
+        let album_id = DbOperations::assign_track_album(db, track_id)?;
+        
+        // Without embedded art, fall back to a cover image next to the file
+        if let (Some(album_id), Some(folder_art)) = (album_id, path.parent().and_then(find_folder_art)) {
+            if read_album_art(path).is_none() {
+                DbOperations::set_album_folder_artwork(db, album_id, &folder_art.to_string_lossy())?;
+            }
+        }
+        
+        // Clear existing relationships
+        let conn = db.get_connection();
+        let conn = conn.lock().unwrap();
+        conn.execute("DELETE FROM track_artists WHERE track_id = ?1", rusqlite::params![track_id])?;
+        conn.execute("DELETE FROM track_album_artists WHERE track_id = ?1", rusqlite::params![track_id])?;
+        conn.execute("DELETE FROM track_genres WHERE track_id = ?1", rusqlite::params![track_id])?;
+        drop(conn);
+        
+        // Link track with multiple artists via junction table
+        if !artist_names.is_empty() {
+            DbOperations::link_track_artists(db, track_id, &artist_names)?;
+        }
+        
+        // Link track with its album artists via junction table
+        if !album_artist_names.is_empty() {
+            DbOperations::link_track_album_artists(db, track_id, &album_artist_names)?;
+        }
+        
+        // Link track with multiple genres via junction table
+        if !genre_names.is_empty() {
+            DbOperations::link_track_genres(db, track_id, &genre_names)?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Background loudness analysis for tracks that don't have normalization data yet.
+    /// Unlike analyze_loudness_with_progress this runs one track at a time with a short pause
+    /// between tracks, so it can run alongside playback. Results are saved as they come in,
+    /// so a cancelled run resumes where it left off next time.
+    pub fn backfill_loudness<F>(
+        db: &DatabaseConnection,
+        segments_per_minute: i32,
+        prefer_tags: bool,
+        cancel: &AtomicBool,
+        mut progress_callback: F,
+    ) -> Result<(usize, usize), anyhow::Error>
+    where
+        F: FnMut(LoudnessAnalysisProgress),
+    {
+        let tracks = DbOperations::get_tracks_needing_loudness_analysis(db)?;
+        let total = tracks.len();
+        let mut analyzed = 0;
+        let mut failed = 0;
+        
+        for (index, track) in tracks.iter().enumerate() {
+            if cancel.load(Ordering::SeqCst) {
+                eprintln!("[Loudness] Backfill cancelled after {} of {} tracks", index, total);
+                break;
             }
             
-            // Insert album if present (use first artist from multi-value field)
-            if let Some(ref album_title) = track.album {
-                let album_artist = artist_names.first().map(|s| s.as_str());
-                let _ = DbOperations::insert_or_get_album(
-                    db,
-                    album_title,
-                    album_artist,
-                    track.year,
-                )?;
+            let path = Path::new(&track.file_path);
+            match measure_track_loudness(path, track.id, segments_per_minute, prefer_tags) {
+                Ok((loudness, source)) => {
+                    analyzed += 1;
+                    DbOperations::upsert_loudness_from(db, &loudness, source.as_str())?;
+                }
+                Err(e) => {
+                    eprintln!("Loudness analysis failed for {}: {}", track.file_path, e);
+                    failed += 1;
+                    // Mark as failed so it isn't retried every run
+                    DbOperations::mark_loudness_failed(db, track.id)?;
+                }
             }
             
-            // Clear existing relationships
-            let conn = db.get_connection();
-            let conn = conn.lock().unwrap();
-            conn.execute("DELETE FROM track_artists WHERE track_id = ?1", rusqlite::params![track_id])?;
-            conn.execute("DELETE FROM track_genres WHERE track_id = ?1", rusqlite::params![track_id])?;
-            drop(conn);
+            progress_callback(LoudnessAnalysisProgress {
+                current: index + 1,
+                total,
+                current_file: path.file_name()
+                    .and_then(|n| n.to_str())
+                    .unwrap_or("unknown")
+                    .to_string(),
+                analyzed,
+                failed,
+            });
             
-            // Link track with multiple artists via junction table
-            if !artist_names.is_empty() {
-                DbOperations::link_track_artists(db, track_id, &artist_names)?;
+            std::thread::sleep(std::time::Duration::from_millis(BACKFILL_THROTTLE_MS));
+        }
+        
+        Ok((analyzed, failed))
+    }
+    
+    /// Fill in duration_ms for tracks whose tags didn't provide one, by opening them with
+    /// the audio decoder (which derives the duration from the frame count).
+    /// Files that can't be opened or report no duration are skipped.
+    /// Returns (updated, failed).
+    pub fn backfill_durations<F>(
+        db: &DatabaseConnection,
+        mut progress_callback: F,
+    ) -> Result<(usize, usize), anyhow::Error>
+    where
+        F: FnMut(DurationBackfillProgress),
+    {
+        let tracks = DbOperations::get_tracks_missing_duration(db)?;
+        let total = tracks.len();
+        let mut updated = 0;
+        let mut failed = 0;
+        
+        for (index, (track_id, file_path)) in tracks.iter().enumerate() {
+            let path = Path::new(file_path);
+            match AudioDecoder::open(path).map(|decoder| decoder.duration_ms()) {
+                Ok(Some(duration_ms)) if duration_ms > 0 => {
+                    DbOperations::update_track_duration(db, *track_id, duration_ms)?;
+                    updated += 1;
+                }
+                Ok(_) => {
+                    eprintln!("[Backend] No duration available for {}", file_path);
+                    failed += 1;
+                }
+                Err(e) => {
+                    eprintln!("[Backend] Failed to open {} for duration: {}", file_path, e);
+                    failed += 1;
+                }
             }
             
-            // Link track with multiple genres via junction table
-            if !genre_names.is_empty() {
-                DbOperations::link_track_genres(db, track_id, &genre_names)?;
-            }
+            progress_callback(DurationBackfillProgress {
+                current: index + 1,
+                total,
+                current_file: path.file_name()
+                    .and_then(|n| n.to_str())
+                    .unwrap_or("unknown")
+                    .to_string(),
+                updated,
+                failed,
+            });
         }
         
-        Ok(was_updated)
+        Ok((updated, failed))
     }
     
     /// Analyze loudness for all tracks that don't have normalization data yet
     /// This is CPU-intensive and runs in PARALLEL using all available cores
+    /// With `prefer_tags`, tracks carrying ReplayGain tags use those instead of being analyzed.
     pub fn analyze_loudness_with_progress<F>(
         db: &DatabaseConnection,
+        segments_per_minute: i32,
+        prefer_tags: bool,
         mut progress_callback: F,
     ) -> Result<(usize, usize), anyhow::Error>
     where
@@ -253,8 +644,8 @@ impl LibraryIndexer {
         let tracks_clone = tracks.clone();
         let analysis_handle = std::thread::spawn(move || {
             // Analyze tracks in parallel and collect results
-            // Result: (track_id, Option<normalization_gain_db>)
-            let results: Vec<(i64, Option<f32>)> = tracks_clone
+            // Result: (track_id, Option<(loudness, source)>)
+            let results: Vec<(i64, Option<(TrackLoudness, LoudnessSource)>)> = tracks_clone
                 .par_iter()
                 .map(|track| {
                     // Update current file name for progress display
@@ -270,10 +661,10 @@ impl LibraryIndexer {
                     let path = std::path::Path::new(&track.file_path);
                     
                     // Use sampled analysis for speed during scanning (5-10x faster)
-                    let result = match analyze_loudness_sampled(path) {
-                        Ok(loudness_result) => {
+                    let result = match measure_track_loudness(path, track.id, segments_per_minute, prefer_tags) {
+                        Ok(measured) => {
                             analyzed_clone.fetch_add(1, Ordering::Relaxed);
-                            Some(loudness_result.normalization_gain_db)
+                            Some(measured)
                         }
                         Err(e) => {
                             eprintln!("Loudness analysis failed for {}: {}", track.file_path, e);
@@ -336,8 +727,8 @@ impl LibraryIndexer {
             }
             
             match gain_result {
-                Some(gain_db) => {
-                    if let Err(e) = DbOperations::update_track_normalization_gain(db, *track_id, *gain_db) {
+                Some((loudness, source)) => {
+                    if let Err(e) = DbOperations::upsert_loudness_from(db, loudness, source.as_str()) {
                         eprintln!("Failed to update normalization gain for track {}: {}", track_id, e);
                         final_failed += 1;
                     } else {
@@ -345,8 +736,8 @@ impl LibraryIndexer {
                     }
                 }
                 None => {
-                    // Analysis failed - set to 0.0 dB to prevent re-analyzing every scan
-                    let _ = DbOperations::update_track_normalization_gain(db, *track_id, 0.0);
+                    // Analysis failed - mark it to prevent re-analyzing every scan
+                    let _ = DbOperations::mark_loudness_failed(db, *track_id);
                     final_failed += 1;
                 }
             }
@@ -355,3 +746,335 @@ impl LibraryIndexer {
         Ok((final_analyzed, final_failed))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::models::ArtistSort;
+    use crate::test_support::{insert_test_track, temp_dir, test_db, write_test_wav};
+
+    #[test]
+    fn test_ensure_track_indexes_new_file() {
+        let dir = temp_dir("ensure_new");
+        let file = dir.join("dropped.wav");
+        write_test_wav(&file);
+        let db = test_db();
+
+        let track = LibraryIndexer::ensure_track(&file, &db).unwrap();
+        assert!(track.id > 0);
+        assert_eq!(track.title, "dropped");
+        assert!(DbOperations::get_all_scan_paths(&db).unwrap().is_empty());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_ensure_track_returns_existing_track() {
+        let dir = temp_dir("ensure_existing");
+        let file = dir.join("existing.wav");
+        write_test_wav(&file);
+        let db = test_db();
+
+        let first = LibraryIndexer::ensure_track(&file, &db).unwrap();
+        let second = LibraryIndexer::ensure_track(&file, &db).unwrap();
+        assert_eq!(first.id, second.id);
+        assert_eq!(DbOperations::get_all_tracks(&db).unwrap().len(), 1);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_backfill_loudness_cancel_and_resume() {
+        let dir = temp_dir("backfill");
+        let file = dir.join("quiet.wav");
+        write_test_wav(&file);
+        let db = test_db();
+        LibraryIndexer::ensure_track(&file, &db).unwrap();
+
+        // Cancelled before starting: nothing is processed
+        let cancel = AtomicBool::new(true);
+        assert_eq!(LibraryIndexer::backfill_loudness(&db, 10, true, &cancel, |_| {}).unwrap(), (0, 0));
+        assert_eq!(DbOperations::get_tracks_needing_loudness_analysis(&db).unwrap().len(), 1);
+
+        // Resumed: the track is processed and not picked up again
+        cancel.store(false, Ordering::SeqCst);
+        let (analyzed, failed) = LibraryIndexer::backfill_loudness(&db, 10, true, &cancel, |_| {}).unwrap();
+        assert_eq!(analyzed + failed, 1);
+        assert!(DbOperations::get_tracks_needing_loudness_analysis(&db).unwrap().is_empty());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_failed_loudness_analysis_is_not_stored_as_a_gain() {
+        let dir = temp_dir("backfill_failed");
+        let file = dir.join("broken.wav");
+        std::fs::write(&file, b"not audio").unwrap();
+        let db = test_db();
+        let track_id = insert_test_track(&db, file.to_str().unwrap());
+
+        let (analyzed, failed) = LibraryIndexer::backfill_loudness(&db, 10, false, &AtomicBool::new(false), |_| {}).unwrap();
+        assert_eq!((analyzed, failed), (0, 1));
+        assert_eq!(DbOperations::get_track_by_id(&db, track_id).unwrap().unwrap().normalization_gain_db, None);
+        assert!(DbOperations::get_tracks_needing_loudness_analysis(&db).unwrap().is_empty());
+
+        // Once the file changes it's analyzed again
+        let track = DbOperations::get_track_by_id(&db, track_id).unwrap().unwrap();
+        DbOperations::upsert_track_with_hash(&db, &Track { normalization_gain_db: None, ..track }, "new hash").unwrap();
+        assert_eq!(DbOperations::get_tracks_needing_loudness_analysis(&db).unwrap().len(), 1);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_refresh_track_after_external_tag_edit() {
+        use id3::TagLike;
+
+        let dir = temp_dir("refresh_track");
+        let file = dir.join("edited.mp3");
+        std::fs::write(&file, b"").unwrap();
+        let mut tag = id3::Tag::new();
+        tag.set_title("Before");
+        tag.set_artist("Old Artist");
+        tag.write_to_path(&file, id3::Version::Id3v24).unwrap();
+        let db = test_db();
+        let track = LibraryIndexer::ensure_track(&file, &db).unwrap();
+        assert_eq!(track.title, "Before");
+
+        // Edited in another program
+        tag.set_title("After");
+        tag.set_artist("New Artist");
+        tag.write_to_path(&file, id3::Version::Id3v24).unwrap();
+
+        let refreshed = LibraryIndexer::refresh_track(track.id, &db, false).unwrap();
+        assert_eq!(refreshed.id, track.id);
+        assert_eq!(refreshed.title, "After");
+        assert_eq!(refreshed.artist.as_deref(), Some("New Artist"));
+        assert_ne!(refreshed.file_hash, track.file_hash);
+
+        std::fs::remove_file(&file).unwrap();
+        assert!(LibraryIndexer::refresh_track(track.id, &db, false).is_err());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_dedup_by_hash_records_alternate_location() {
+        let dir = temp_dir("dedup");
+        let original = dir.join("Album").join("song.wav");
+        let backup = dir.join("Backup").join("song.wav");
+        std::fs::create_dir_all(original.parent().unwrap()).unwrap();
+        std::fs::create_dir_all(backup.parent().unwrap()).unwrap();
+        write_test_wav(&original);
+        std::fs::copy(&original, &backup).unwrap();
+        let db = test_db();
+
+        let result = LibraryIndexer::index_files_with_progress(
+            &[&original, &backup], &db, false, true, &AtomicBool::new(false), |_| {},
+        ).unwrap();
+        assert_eq!(result.updated, 1);
+        assert_eq!(result.skipped, 1);
+
+        let tracks = DbOperations::get_all_tracks(&db).unwrap();
+        assert_eq!(tracks.len(), 1);
+        let locations = DbOperations::get_track_locations(&db, tracks[0].id).unwrap();
+        assert_eq!(locations, vec![
+            original.to_string_lossy().to_string(),
+            backup.to_string_lossy().to_string(),
+        ]);
+
+        // Rescanning doesn't duplicate the location, or hash the unchanged copy again
+        let result = LibraryIndexer::index_files_with_progress(&[&backup], &db, false, true, &AtomicBool::new(false), |_| {}).unwrap();
+        assert_eq!(result.unchanged, 1);
+        assert_eq!(DbOperations::get_track_locations(&db, tracks[0].id).unwrap().len(), 2);
+
+        // A copy touched since is hashed again and stays a location
+        let file = std::fs::OpenOptions::new().write(true).open(&backup).unwrap();
+        file.set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000)).unwrap();
+        let result = LibraryIndexer::index_files_with_progress(&[&backup], &db, false, true, &AtomicBool::new(false), |_| {}).unwrap();
+        assert_eq!((result.unchanged, result.skipped), (0, 1));
+        assert_eq!(DbOperations::get_track_locations(&db, tracks[0].id).unwrap().len(), 2);
+        assert_eq!(LibraryIndexer::index_files_with_progress(&[&backup], &db, false, true, &AtomicBool::new(false), |_| {}).unwrap().unchanged, 1);
+
+        // The original disappears: the track moves to the backup copy
+        let found: std::collections::HashSet<String> = [backup.to_string_lossy().to_string()].into();
+        assert_eq!(DbOperations::promote_track_locations(&db, &found).unwrap(), 1);
+        assert_eq!(
+            DbOperations::get_track_locations(&db, tracks[0].id).unwrap(),
+            vec![backup.to_string_lossy().to_string()]
+        );
+
+        // Without deduplication copies are separate tracks
+        let other_db = test_db();
+        LibraryIndexer::index_files_with_progress(&[&original, &backup], &other_db, false, false, &AtomicBool::new(false), |_| {}).unwrap();
+        assert_eq!(DbOperations::get_all_tracks(&other_db).unwrap().len(), 2);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_rescan_skips_files_with_unchanged_mtime_and_size() {
+        let dir = temp_dir("mtime");
+        let path = dir.join("song.wav");
+        write_test_wav(&path);
+        let db = test_db();
+        let set_mtime = |secs: u64| {
+            let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+            file.set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs)).unwrap();
+        };
+        set_mtime(1_000_000);
+
+        let scan = || LibraryIndexer::index_files_with_progress(&[&path], &db, false, false, &AtomicBool::new(false), |_| {}).unwrap();
+        assert_eq!(scan().updated, 1);
+
+        let result = scan();
+        assert_eq!((result.updated, result.skipped, result.unchanged), (0, 1, 1));
+
+        // Touched but identical: read and hashed once, then trusted again
+        set_mtime(2_000_000);
+        let result = scan();
+        assert_eq!((result.updated, result.skipped, result.unchanged), (0, 1, 0));
+        assert_eq!(scan().unchanged, 1);
+
+        // Changed content is picked up even with the same modification time
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes.extend_from_slice(&[0; 4]);
+        std::fs::write(&path, bytes).unwrap();
+        set_mtime(2_000_000);
+        assert_eq!(scan().updated, 1);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_cancelled_scan_returns_partial_result() {
+        let dir = temp_dir("cancel_scan");
+        let paths: Vec<PathBuf> = (0..3).map(|i| dir.join(format!("song{}.wav", i))).collect();
+        for path in &paths {
+            write_test_wav(path);
+        }
+        let db = test_db();
+
+        // Cancel while the second file is being reported
+        let cancel = AtomicBool::new(false);
+        let result = LibraryIndexer::index_files_with_progress(&paths, &db, false, false, &cancel, |progress| {
+            if progress.current == 2 {
+                cancel.store(true, Ordering::SeqCst);
+            }
+        }).unwrap();
+        assert!(result.aborted);
+        assert_eq!((result.total_files, result.successful), (3, 2));
+        assert_eq!(DbOperations::get_all_tracks(&db).unwrap().len(), 2);
+
+        // Resuming indexes only the file that was left
+        cancel.store(false, Ordering::SeqCst);
+        let result = LibraryIndexer::index_files_with_progress(&paths, &db, false, false, &cancel, |_| {}).unwrap();
+        assert!(!result.aborted);
+        assert_eq!((result.updated, result.unchanged), (1, 2));
+
+        // The cleanup passes stop as well
+        std::fs::remove_file(&paths[0]).unwrap();
+        cancel.store(true, Ordering::SeqCst);
+        assert_eq!(DbOperations::remove_missing_files(&db, &cancel, |_, _| {}).unwrap(), 0);
+        cancel.store(false, Ordering::SeqCst);
+        assert_eq!(DbOperations::remove_missing_files(&db, &cancel, |_, _| {}).unwrap(), 1);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_collaborative_album_artist_links_each_artist() {
+        use id3::TagLike;
+
+        let dir = temp_dir("album_artists");
+        let file = dir.join("collab.mp3");
+        std::fs::write(&file, b"").unwrap();
+        let mut tag = id3::Tag::new();
+        tag.set_title("Otis");
+        tag.set_artist("Jay-Z");
+        tag.set_album_artist("Jay-Z & Kanye West");
+        tag.write_to_path(&file, id3::Version::Id3v24).unwrap();
+        let db = test_db();
+        let track = LibraryIndexer::ensure_track(&file, &db).unwrap();
+
+        let artists = DbOperations::get_all_artists(&db, ArtistSort::NameAsc).unwrap();
+        let summary: Vec<(&str, i32)> = artists.iter().map(|a| (a.name.as_str(), a.song_count)).collect();
+        assert_eq!(summary, vec![("Jay-Z", 1), ("Kanye West", 1)]);
+
+        let kanye = artists.iter().find(|a| a.name == "Kanye West").unwrap().id;
+        let ids = |tracks: Vec<crate::db::models::Track>| tracks.iter().map(|t| t.id).collect::<Vec<_>>();
+        assert_eq!(ids(DbOperations::get_tracks_by_album_artist(&db, kanye).unwrap()), vec![track.id]);
+        assert_eq!(ids(DbOperations::get_tracks_by_artist(&db, kanye).unwrap()), vec![track.id]);
+        assert_eq!(DbOperations::get_library_stats(&db).unwrap().artist_count, 2);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_cue_sheet_splits_single_file_album() {
+        let dir = temp_dir("cue_album");
+        let audio = dir.join("Album.wav");
+        write_test_wav(&audio);
+        let db = test_db();
+        let index = |paths: &[&Path]| {
+            LibraryIndexer::index_files_with_progress(paths, &db, false, false, &AtomicBool::new(false), |_| {}).unwrap()
+        };
+
+        // Indexed as one track until the sheet shows up
+        index(&[&audio]);
+        assert_eq!(DbOperations::get_all_tracks(&db).unwrap().len(), 1);
+
+        let cue = dir.join("Album.cue");
+        std::fs::write(&cue, "PERFORMER \"The Band\"\nTITLE \"Live\"\nFILE \"Album.wav\" WAVE\n  TRACK 01 AUDIO\n    TITLE \"Intro\"\n    INDEX 01 00:00:00\n  TRACK 02 AUDIO\n    TITLE \"Song\"\n    INDEX 01 00:00:03\n").unwrap();
+        index(&[&audio, &cue]);
+
+        let tracks = DbOperations::get_tracks_by_album(&db, "Live").unwrap();
+        let audio_file = audio.to_string_lossy();
+        let summary: Vec<(String, String, Option<i32>, Option<i64>)> = tracks.iter()
+            .map(|t| (t.file_path.clone(), t.title.clone(), t.track_number, t.duration_ms))
+            .collect();
+        assert_eq!(summary, vec![
+            (format!("{}#0-40", audio_file), "Intro".to_string(), Some(1), Some(40)),
+            (format!("{}#40-", audio_file), "Song".to_string(), Some(2), Some(60)),
+        ]);
+        assert_eq!(tracks[0].album_artist.as_deref(), Some("The Band"));
+        assert_eq!(DbOperations::get_all_tracks(&db).unwrap().len(), 2);
+
+        // Unchanged on the next scan, and still counted as present on disk
+        assert_eq!(index(&[&audio, &cue]).unchanged, 1);
+        let found: std::collections::HashSet<String> = [audio_file.to_string(), cue.to_string_lossy().to_string()].into();
+        assert!(DbOperations::find_tracks_not_in_paths(&db, &[dir.to_string_lossy().to_string()], &found).unwrap().is_empty());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_backfill_durations_from_decoder() {
+        let dir = temp_dir("durations");
+        // 100ms of silence
+        let wav = dir.join("untagged.wav");
+        write_test_wav(&wav);
+
+        let db = test_db();
+        let wav_id = insert_test_track(&db, wav.to_str().unwrap());
+        let missing_id = insert_test_track(&db, "/music/missing.mp3");
+        let tagged_id = insert_test_track(&db, "/music/tagged.mp3");
+        {
+            let conn = db.get_connection();
+            let conn = conn.lock().unwrap();
+            conn.execute("UPDATE tracks SET duration_ms = NULL WHERE id IN (?1, ?2)", rusqlite::params![wav_id, missing_id]).unwrap();
+        }
+
+        let mut progress_updates = 0;
+        let (updated, failed) = LibraryIndexer::backfill_durations(&db, |_| progress_updates += 1).unwrap();
+        assert_eq!((updated, failed), (1, 1));
+        assert_eq!(progress_updates, 2);
+
+        let duration = |id| DbOperations::get_track_by_id(&db, id).unwrap().unwrap().duration_ms;
+        assert_eq!(duration(wav_id), Some(100));
+        assert_eq!(duration(missing_id), None);
+        assert_eq!(duration(tagged_id), Some(180_000));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -3,3 +3,4 @@
 
 pub mod scanner;
 pub mod indexer;
+pub mod watcher;
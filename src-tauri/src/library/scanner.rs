@@ -1,40 +1,209 @@
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
-/// List of supported audio file extensions
+use crate::error::AppError;
+use crate::metadata::cue::is_cue_sheet;
+use crate::path_utils::{from_extended_length, to_extended_length};
+
+/// List of supported audio file extensions. Each needs a matching Symphonia format/codec
+/// feature in Cargo.toml, otherwise files are indexed but can't be played (which is why
+/// APE and WavPack, which Symphonia 0.5 has no decoders for, aren't listed).
 const SUPPORTED_EXTENSIONS: &[&str] = &[
-    "mp3", "flac", "ogg", "wav", "m4a", "aac", "opus", "wma",
+    "mp3", "flac", "ogg", "wav", "m4a", "aac", "opus", "wma", "aiff", "aif",
 ];
 
 /// Scanner for finding audio files in a directory tree
 pub struct DirectoryScanner;
 
 impl DirectoryScanner {
-    /// Scan a directory recursively and return all audio file paths
-    pub fn scan<P: AsRef<Path>>(directory: P) -> Result<Vec<PathBuf>, anyhow::Error> {
+    /// Whether the file's extension is one the scanner picks up (case-insensitive)
+    pub fn is_supported_audio_file<P: AsRef<Path>>(path: P) -> bool {
+        path.as_ref()
+            .extension()
+            .map(|ext| SUPPORTED_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+            .unwrap_or(false)
+    }
+    
+    /// Scan a directory recursively and return all audio file and CUE sheet paths.
+    /// With `follow_symlinks`, symlinked folders and files are followed. Every followed
+    /// folder is canonicalized to skip ones already visited (self-referential links, several
+    /// links to the same folder), so each file is found once. This costs a canonicalize call
+    /// per folder and can make scans of large link-heavy trees noticeably slower.
+    pub fn scan<P: AsRef<Path>>(directory: P, follow_symlinks: bool) -> Result<Vec<PathBuf>, anyhow::Error> {
         let mut audio_files = Vec::new();
+        let mut visited_dirs: HashSet<PathBuf> = HashSet::new();
+        let mut seen_files: HashSet<PathBuf> = HashSet::new();
         
-        for entry in WalkDir::new(directory)
-            .follow_links(false)
-            .into_iter()
-            .filter_map(|e| e.ok())
-        {
+        // Walk the extended-length form so deep folders stay reachable on Windows,
+        // and report paths in their plain form
+        let mut walker = WalkDir::new(to_extended_length(directory.as_ref()))
+            .follow_links(follow_symlinks)
+            .into_iter();
+        
+        while let Some(entry) = walker.next() {
+            // Unreadable entries and detected link loops are skipped
+            let Ok(entry) = entry else { continue };
             let path = entry.path();
             
+            if follow_symlinks {
+                let canonical = match path.canonicalize() {
+                    Ok(p) => p,
+                    Err(_) => continue, // Dangling link
+                };
+                if entry.file_type().is_dir() {
+                    if !visited_dirs.insert(canonical) {
+                        walker.skip_current_dir();
+                    }
+                    continue;
+                }
+                if !seen_files.insert(canonical) {
+                    continue;
+                }
+            }
+            
             // Skip directories
             if !path.is_file() {
                 continue;
             }
             
             // Check if file has a supported extension
-            if let Some(extension) = path.extension() {
-                let ext_str = extension.to_string_lossy().to_lowercase();
-                if SUPPORTED_EXTENSIONS.contains(&ext_str.as_str()) {
-                    audio_files.push(path.to_path_buf());
-                }
+            if Self::is_supported_audio_file(path) || is_cue_sheet(path) {
+                audio_files.push(from_extended_length(path));
+            }
+        }
+        
+        Ok(audio_files)
+    }
+    
+    /// Check that a folder can be used as a scan path: it must exist, be a directory and be
+    /// readable. Returns the number of audio files directly inside it (not in subfolders),
+    /// which is 0 for libraries organised into artist/album folders.
+    pub fn validate_scan_path<P: AsRef<Path>>(directory: P) -> Result<usize, anyhow::Error> {
+        let directory = directory.as_ref();
+        let extended = to_extended_length(directory);
+        
+        let metadata = match std::fs::metadata(&extended) {
+            Ok(m) => m,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(AppError::NotFound(format!("Path does not exist: {}", directory.display())).into());
+            }
+            Err(e) => {
+                return Err(anyhow::anyhow!("Cannot access {}: {}", directory.display(), e));
             }
+        };
+        if !metadata.is_dir() {
+            return Err(AppError::InvalidInput(format!("Not a directory: {}", directory.display())).into());
+        }
+        
+        let entries = std::fs::read_dir(&extended)
+            .map_err(|e| anyhow::anyhow!("Cannot read directory {}: {}", directory.display(), e))?;
+        
+        let audio_files = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|path| path.is_file())
+            .filter(|path| Self::is_supported_audio_file(path))
+            .count();
+        
+        Ok(audio_files)
+    }
+    
+    /// Scan a single folder, optionally descending into subfolders.
+    /// Results are returned in filesystem (file name) order.
+    pub fn scan_folder<P: AsRef<Path>>(directory: P, recursive: bool) -> Result<Vec<PathBuf>, anyhow::Error> {
+        let directory = directory.as_ref();
+        if !to_extended_length(directory).is_dir() {
+            return Err(AppError::InvalidInput(format!("Not a directory: {}", directory.display())).into());
+        }
+        
+        let mut walker = WalkDir::new(to_extended_length(directory))
+            .follow_links(false)
+            .sort_by_file_name();
+        if !recursive {
+            walker = walker.max_depth(1);
         }
         
+        let audio_files = walker
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .map(|e| from_extended_length(e.path()))
+            .filter(|path| to_extended_length(path).is_file())
+            .filter(|path| Self::is_supported_audio_file(path))
+            .collect();
+        
         Ok(audio_files)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_follows_symlinks_once_without_looping() {
+        use std::os::unix::fs::symlink;
+
+        let root = std::env::temp_dir().join(format!("musicsloth_symlinks_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let outside = root.join("outside");
+        let library = root.join("library");
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::create_dir_all(&library).unwrap();
+        std::fs::write(outside.join("a.mp3"), b"a").unwrap();
+        std::fs::write(outside.join("b.flac"), b"b").unwrap();
+        std::fs::write(library.join("c.mp3"), b"c").unwrap();
+
+        // Two links to the same folder, and a link back to the library itself
+        symlink(&outside, library.join("linked")).unwrap();
+        symlink(&outside, library.join("linked_again")).unwrap();
+        symlink(&library, library.join("self")).unwrap();
+
+        let mut names: Vec<String> = DirectoryScanner::scan(&library, true)
+            .unwrap()
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["a.mp3", "b.flac", "c.mp3"]);
+
+        // Without following links only the real file is found
+        assert_eq!(DirectoryScanner::scan(&library, false).unwrap().len(), 1);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_validate_scan_path() {
+        let root = std::env::temp_dir().join(format!("musicsloth_validate_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("Artist")).unwrap();
+        std::fs::write(root.join("a.mp3"), b"a").unwrap();
+        std::fs::write(root.join("B.FLAC"), b"b").unwrap();
+        std::fs::write(root.join("cover.jpg"), b"c").unwrap();
+        std::fs::write(root.join("Artist").join("d.mp3"), b"d").unwrap();
+
+        // Only audio files at the top level are counted
+        assert_eq!(DirectoryScanner::validate_scan_path(&root).unwrap(), 2);
+        assert_eq!(DirectoryScanner::validate_scan_path(root.join("Artist")).unwrap(), 1);
+
+        let missing = DirectoryScanner::validate_scan_path(root.join("Typo")).unwrap_err();
+        assert!(missing.to_string().starts_with("Path does not exist"));
+
+        let file = DirectoryScanner::validate_scan_path(root.join("a.mp3")).unwrap_err();
+        assert!(file.to_string().starts_with("Not a directory"));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_is_supported_audio_file() {
+        assert!(DirectoryScanner::is_supported_audio_file("song.aiff"));
+        assert!(DirectoryScanner::is_supported_audio_file("Music/Song.AIF"));
+        assert!(DirectoryScanner::is_supported_audio_file("song.flac"));
+        assert!(!DirectoryScanner::is_supported_audio_file("song.ape"));
+        assert!(!DirectoryScanner::is_supported_audio_file("cover.jpg"));
+        assert!(!DirectoryScanner::is_supported_audio_file("README"));
+    }
+}
@@ -0,0 +1,262 @@
+// Filesystem watcher for automatic library updates
+// Watches the scan paths and hands batches of changed paths to a callback once the folder
+// has been quiet for a moment, so copying an album in triggers one update, not hundreds.
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::db::connection::DatabaseConnection;
+use crate::db::operations::DbOperations;
+use crate::library::indexer::LibraryIndexer;
+use crate::library::scanner::DirectoryScanner;
+use crate::metadata::cue::is_cue_sheet;
+use crate::path_utils::to_extended_length;
+use crate::settings::LibrarySettings;
+
+/// Quiet time after the last filesystem event before a batch is processed
+pub const WATCH_DEBOUNCE_MS: u64 = 2000;
+
+/// What applying a batch of changes did to the library
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct LibraryChange {
+    pub updated: usize,
+    pub removed: usize,
+}
+
+impl LibraryChange {
+    pub fn is_empty(&self) -> bool {
+        self.updated == 0 && self.removed == 0
+    }
+}
+
+/// Watches a set of folders until dropped
+pub struct LibraryWatcher {
+    // Dropping the watcher closes the event channel, which ends the debounce thread
+    _watcher: RecommendedWatcher,
+    watched: Vec<PathBuf>,
+}
+
+impl LibraryWatcher {
+    /// Watch `folders` recursively. `on_batch` runs on a background thread with every path
+    /// that changed, once no event arrived for WATCH_DEBOUNCE_MS. Folders that can't be
+    /// watched (unmounted drives, ...) are skipped.
+    pub fn start<F>(folders: &[PathBuf], on_batch: F) -> Result<Self, anyhow::Error>
+    where
+        F: Fn(Vec<PathBuf>) + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel::<Vec<PathBuf>>();
+
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            match event {
+                // Reads don't change anything
+                Ok(event) if !matches!(event.kind, EventKind::Access(_)) => {
+                    let _ = sender.send(event.paths);
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("[Watcher] Event error: {}", e),
+            }
+        })?;
+
+        let mut watched = Vec::new();
+        for folder in folders {
+            match watcher.watch(folder, RecursiveMode::Recursive) {
+                Ok(()) => watched.push(folder.clone()),
+                Err(e) => eprintln!("[Watcher] Can't watch {}: {}", folder.display(), e),
+            }
+        }
+
+        std::thread::spawn(move || {
+            Self::debounce(&receiver, Duration::from_millis(WATCH_DEBOUNCE_MS), on_batch);
+        });
+
+        Ok(Self {
+            _watcher: watcher,
+            watched,
+        })
+    }
+
+    /// Collect the changed paths coming in on `receiver` and hand them to `on_batch`, sorted
+    /// and without duplicates, whenever nothing arrived for `quiet`. Once the sender is gone
+    /// the last paths are handed over and it returns.
+    fn debounce<F>(receiver: &mpsc::Receiver<Vec<PathBuf>>, quiet: Duration, on_batch: F)
+    where
+        F: Fn(Vec<PathBuf>),
+    {
+        let mut pending: BTreeSet<PathBuf> = BTreeSet::new();
+        loop {
+            match receiver.recv_timeout(quiet) {
+                Ok(paths) => pending.extend(paths),
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    if !pending.is_empty() {
+                        on_batch(std::mem::take(&mut pending).into_iter().collect());
+                    }
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    if !pending.is_empty() {
+                        on_batch(std::mem::take(&mut pending).into_iter().collect());
+                    }
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Folders that are actually being watched
+    pub fn watched_folders(&self) -> &[PathBuf] {
+        &self.watched
+    }
+
+    /// Bring the library in line with a batch of changed paths: new or modified audio files
+    /// (and audio files inside new folders) are indexed, tracks whose file or folder is gone
+    /// are removed, unless they have a copy elsewhere (see promote_track_locations).
+    pub fn apply_changes(
+        db: &DatabaseConnection,
+        paths: &[PathBuf],
+        settings: &LibrarySettings,
+    ) -> Result<LibraryChange, anyhow::Error> {
+        let mut to_index = Vec::new();
+        let mut removed_ids = Vec::new();
+
+        for path in paths {
+            let on_disk = to_extended_length(path);
+            if on_disk.is_file() {
+                if DirectoryScanner::is_supported_audio_file(path) || is_cue_sheet(path) {
+                    to_index.push(path.clone());
+                }
+            } else if on_disk.is_dir() {
+                // A folder moved or copied in: its files may not have events of their own
+                to_index.extend(DirectoryScanner::scan(path, settings.follow_symlinks)?);
+            } else {
+                removed_ids.extend(Self::tracks_at(db, path)?);
+            }
+        }
+
+        let mut change = LibraryChange::default();
+        if !to_index.is_empty() {
+            to_index.sort();
+            to_index.dedup();
+            let result = LibraryIndexer::index_files_with_progress(
+                &to_index,
+                db,
+                settings.prefer_sidecar,
+                settings.dedup_by_hash,
+                &AtomicBool::new(false),
+                |_| {},
+            )?;
+            change.updated = result.updated;
+        }
+        if !removed_ids.is_empty() {
+            removed_ids.sort();
+            removed_ids.dedup();
+            // Like a scan, tracks whose file is gone but that have a copy move to the copy
+            let moved = DbOperations::promote_locations_of_tracks(db, &removed_ids, |path| {
+                to_extended_length(Path::new(path)).exists()
+            })?;
+            removed_ids.retain(|id| !moved.contains(id));
+            change.updated += moved.len();
+            change.removed = DbOperations::remove_tracks_by_ids(db, &removed_ids)?;
+        }
+        if !change.is_empty() {
+            DbOperations::sync_albums(db)?;
+        }
+
+        Ok(change)
+    }
+
+    /// Tracks for a path that no longer exists: the track with that file, or every track
+    /// inside it if it was a folder
+    fn tracks_at(db: &DatabaseConnection, path: &Path) -> Result<Vec<i64>, anyhow::Error> {
+        let file_path = path.to_string_lossy();
+        if let Some(track) = DbOperations::get_track_by_file_path(db, &file_path)? {
+            return Ok(vec![track.id]);
+        }
+        let cue_tracks = DbOperations::get_tracks_for_audio_file(db, &file_path)?;
+        if !cue_tracks.is_empty() {
+            return Ok(cue_tracks.into_iter().map(|(id, _)| id).collect());
+        }
+        DbOperations::find_tracks_in_folder(db, &file_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{test_db, write_test_wav};
+
+    #[test]
+    fn test_apply_changes_indexes_new_files_and_removes_deleted_ones() {
+        let dir = std::env::temp_dir().join(format!("musicsloth_watcher_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let album = dir.join("Album");
+        std::fs::create_dir_all(&album).unwrap();
+        let single = dir.join("single.wav");
+        write_test_wav(&single);
+        write_test_wav(&album.join("01.wav"));
+        write_test_wav(&album.join("02.wav"));
+        std::fs::write(dir.join("notes.txt"), b"not audio").unwrap();
+        let db = test_db();
+        let settings = LibrarySettings::default();
+
+        // A new file, a new folder and a non-audio file
+        let change = LibraryWatcher::apply_changes(
+            &db,
+            &[single.clone(), album.clone(), dir.join("notes.txt")],
+            &settings,
+        ).unwrap();
+        assert_eq!(change, LibraryChange { updated: 3, removed: 0 });
+
+        // The file and then the whole folder disappear
+        std::fs::remove_file(&single).unwrap();
+        std::fs::remove_dir_all(&album).unwrap();
+        let change = LibraryWatcher::apply_changes(&db, &[single, album], &settings).unwrap();
+        assert_eq!(change, LibraryChange { updated: 0, removed: 3 });
+        assert!(DbOperations::get_all_tracks(&db).unwrap().is_empty());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_apply_changes_moves_deleted_track_to_its_copy() {
+        let dir = std::env::temp_dir().join(format!("musicsloth_watcher_copy_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let original = dir.join("song.wav");
+        let backup = dir.join("song (backup).wav");
+        write_test_wav(&original);
+        std::fs::copy(&original, &backup).unwrap();
+        let db = test_db();
+        let settings = LibrarySettings { dedup_by_hash: true, ..LibrarySettings::default() };
+        LibraryWatcher::apply_changes(&db, std::slice::from_ref(&original), &settings).unwrap();
+        LibraryWatcher::apply_changes(&db, std::slice::from_ref(&backup), &settings).unwrap();
+        let track_id = DbOperations::get_all_tracks(&db).unwrap()[0].id;
+
+        std::fs::remove_file(&original).unwrap();
+        let change = LibraryWatcher::apply_changes(&db, &[original], &settings).unwrap();
+        assert_eq!(change, LibraryChange { updated: 1, removed: 0 });
+        assert_eq!(
+            DbOperations::get_track_locations(&db, track_id).unwrap(),
+            vec![backup.to_string_lossy().to_string()]
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_debounce_batches_paths_until_quiet() {
+        let (sender, receiver) = mpsc::channel();
+        let (batch_sender, batches) = mpsc::channel();
+        let a = PathBuf::from("a.wav");
+        let b = PathBuf::from("b.wav");
+        sender.send(vec![b.clone(), a.clone()]).unwrap();
+        sender.send(vec![a.clone()]).unwrap();
+        drop(sender);
+
+        // Everything that arrived without a pause is one batch, and closing the channel
+        // still delivers it before the loop ends
+        LibraryWatcher::debounce(&receiver, Duration::from_secs(60), |paths| batch_sender.send(paths).unwrap());
+        assert_eq!(batches.try_iter().collect::<Vec<_>>(), vec![vec![a, b]]);
+    }
+}
@@ -1,2 +1,482 @@
 // Album artwork handling
-// TODO: Implement artwork extraction and caching
+// Reads embedded cover art and embeds new art into audio files
+use lofty::config::WriteOptions;
+use lofty::file::{AudioFile, FileType, TaggedFileExt};
+use lofty::picture::{MimeType, Picture, PictureType};
+use lofty::probe::Probe;
+use lofty::tag::{Tag, TagType};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::db::connection::DatabaseConnection;
+use crate::error::AppError;
+use crate::db::models::Track;
+use crate::db::operations::DbOperations;
+use crate::metadata::cue::audio_file_path;
+use crate::path_utils::to_extended_length;
+
+/// Folder under the app data dir holding cached covers, named `<track file hash>.<ext>`
+pub const ARTWORK_CACHE_DIR: &str = "artwork";
+
+/// Priority order for picture types (matching foobar2000 behavior)
+const PICTURE_PRIORITY: [PictureType; 19] = [
+    PictureType::CoverFront,        // Front Cover (most common)
+    PictureType::Media,             // Media (e.g., label side of CD)
+    PictureType::CoverBack,         // Back Cover
+    PictureType::Leaflet,           // Leaflet page
+    PictureType::Other,             // Other/Undefined
+    PictureType::Icon,              // Icon
+    PictureType::OtherIcon,         // Other Icon
+    PictureType::Artist,            // Artist/Performer
+    PictureType::Band,              // Band/Orchestra
+    PictureType::Composer,          // Composer
+    PictureType::Lyricist,          // Lyricist/Text writer
+    PictureType::RecordingLocation, // Recording Location
+    PictureType::DuringRecording,   // During Recording
+    PictureType::DuringPerformance, // During Performance
+    PictureType::ScreenCapture,     // Screen Capture
+    PictureType::BrightFish,        // Bright Colored Fish
+    PictureType::Illustration,      // Illustration
+    PictureType::BandLogo,          // Band/Artist Logotype
+    PictureType::PublisherLogo,     // Publisher/Studio Logotype
+];
+
+/// Read the embedded album art of a file, picking the best picture by type
+pub fn read_album_art(path: &Path) -> Option<Vec<u8>> {
+    // Try lofty first
+    if let Ok(tagged_file) = Probe::open(to_extended_length(path)).and_then(|p| p.read()) {
+        // Try the primary tag first, then all tags if it didn't have cover art
+        let tags = tagged_file.primary_tag().into_iter().chain(tagged_file.tags());
+        for tag in tags {
+            for pic_type in &PICTURE_PRIORITY {
+                if let Some(picture) = tag.pictures().iter().find(|p| p.pic_type() == *pic_type) {
+                    return Some(picture.data().to_vec());
+                }
+            }
+        }
+        return None;
+    }
+
+    // Fallback: try id3 crate for MP3 files if lofty failed
+    let extension = path.extension()
+        .and_then(|s| s.to_str())
+        .map(|s| s.to_lowercase());
+
+    if extension.as_deref() == Some("mp3") {
+        if let Ok(tag) = id3::Tag::read_from_path(to_extended_length(path)) {
+            // id3 crate picture type priority (same as lofty)
+            use id3::frame::PictureType as Id3PictureType;
+            let id3_priority = [
+                Id3PictureType::CoverFront,
+                Id3PictureType::Media,
+                Id3PictureType::CoverBack,
+                Id3PictureType::Leaflet,
+                Id3PictureType::Other,
+                Id3PictureType::Icon,
+                Id3PictureType::OtherIcon,
+                Id3PictureType::Artist,
+                Id3PictureType::Band,
+                Id3PictureType::Composer,
+                Id3PictureType::Lyricist,
+                Id3PictureType::RecordingLocation,
+                Id3PictureType::DuringRecording,
+                Id3PictureType::DuringPerformance,
+                Id3PictureType::ScreenCapture,
+                Id3PictureType::BrightFish,
+                Id3PictureType::Illustration,
+                Id3PictureType::BandLogo,
+                Id3PictureType::PublisherLogo,
+            ];
+
+            for pic_type in &id3_priority {
+                if let Some(picture) = tag.pictures().find(|p| p.picture_type == *pic_type) {
+                    return Some(picture.data.clone());
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// Sidecar cover file names (without extension), most specific first
+const FOLDER_ART_NAMES: [&str; 5] = ["cover", "folder", "front", "album", "albumart"];
+/// Image extensions tried for each sidecar name, in order
+const FOLDER_ART_EXTENSIONS: [&str; 3] = ["jpg", "jpeg", "png"];
+
+/// Cover image kept next to the audio files in `dir` (cover.jpg, folder.jpg, ...).
+/// Names are matched case-insensitively; FOLDER_ART_NAMES decides between several.
+pub fn find_folder_art(dir: &Path) -> Option<PathBuf> {
+    let files: Vec<PathBuf> = std::fs::read_dir(to_extended_length(dir)).ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect();
+    let lower_name = |path: &Path| path.file_name().map(|n| n.to_string_lossy().to_lowercase());
+
+    FOLDER_ART_NAMES.iter()
+        .flat_map(|name| FOLDER_ART_EXTENSIONS.iter().map(move |ext| format!("{}.{}", name, ext)))
+        .find_map(|candidate| {
+            files.iter()
+                .find(|path| lower_name(path).as_deref() == Some(candidate.as_str()))
+                .map(|path| dir.join(path.file_name().unwrap_or_default()))
+        })
+}
+
+/// Picture type from its frontend name ("front_cover", "back_cover", "artist", ...)
+pub fn picture_type_from_name(name: &str) -> Option<PictureType> {
+    let pic_type = match name.to_lowercase().replace(['-', ' '], "_").as_str() {
+        "front" | "front_cover" | "cover_front" => PictureType::CoverFront,
+        "back" | "back_cover" | "cover_back" => PictureType::CoverBack,
+        "media" => PictureType::Media,
+        "leaflet" => PictureType::Leaflet,
+        "artist" => PictureType::Artist,
+        "band" => PictureType::Band,
+        "composer" => PictureType::Composer,
+        "icon" => PictureType::Icon,
+        "illustration" => PictureType::Illustration,
+        "band_logo" => PictureType::BandLogo,
+        "publisher_logo" => PictureType::PublisherLogo,
+        "other" => PictureType::Other,
+        _ => return None,
+    };
+    Some(pic_type)
+}
+
+/// Check that `data` is a decodable JPEG or PNG image and return its MIME type
+pub fn validate_image(data: &[u8]) -> Result<MimeType, anyhow::Error> {
+    match image::guess_format(data) {
+        Ok(image::ImageFormat::Png) => {
+            image::load_from_memory_with_format(data, image::ImageFormat::Png)
+                .map_err(|e| AppError::InvalidInput(format!("Invalid PNG image: {}", e)))?;
+            Ok(MimeType::Png)
+        }
+        Ok(image::ImageFormat::Jpeg) => {
+            // The image crate is built without a JPEG decoder, so walk the markers instead
+            if !jpeg_has_frame(data) {
+                return Err(AppError::InvalidInput("Invalid JPEG image: no frame header".to_string()).into());
+            }
+            Ok(MimeType::Jpeg)
+        }
+        _ => Err(AppError::InvalidInput("Unsupported image format, expected JPEG or PNG".to_string()).into()),
+    }
+}
+
+/// Whether a JPEG stream has a start-of-frame header with non-zero dimensions
+/// before its image data starts
+fn jpeg_has_frame(data: &[u8]) -> bool {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return false;
+    }
+
+    let mut pos = 2;
+    while pos + 4 <= data.len() {
+        if data[pos] != 0xFF {
+            return false;
+        }
+        let marker = data[pos + 1];
+        let length = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        if length < 2 || pos + 2 + length > data.len() {
+            return false;
+        }
+
+        match marker {
+            // SOF0-SOF15, except DHT (C4), JPG (C8) and DAC (CC)
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                if length < 8 {
+                    return false;
+                }
+                let height = u16::from_be_bytes([data[pos + 5], data[pos + 6]]);
+                let width = u16::from_be_bytes([data[pos + 7], data[pos + 8]]);
+                return height > 0 && width > 0;
+            }
+            // Start of scan before any frame header
+            0xDA => return false,
+            _ => pos += 2 + length,
+        }
+    }
+
+    false
+}
+
+/// Tag type to store pictures in: the file's primary tag if it can hold pictures
+/// (RIFF INFO, ID3v1 and AIFF text chunks can't), otherwise ID3v2 or APE
+fn picture_tag_type(file_type: FileType) -> Option<TagType> {
+    let supports_pictures = |tag_type: TagType| matches!(
+        tag_type,
+        TagType::Id3v2 | TagType::Ape | TagType::VorbisComments | TagType::Mp4Ilst
+    );
+
+    let primary = file_type.primary_tag_type();
+    if supports_pictures(primary) {
+        return Some(primary);
+    }
+    [TagType::Id3v2, TagType::Ape]
+        .into_iter()
+        .find(|t| file_type.supports_tag_type(*t))
+}
+
+/// Embed `image_data` into the file as a picture of `pic_type`, replacing any existing
+/// picture of that type. Returns the new file hash, since the file contents changed.
+pub fn set_album_art(
+    path: &Path,
+    image_data: Vec<u8>,
+    pic_type: PictureType,
+) -> Result<String, anyhow::Error> {
+    let mime_type = validate_image(&image_data)?;
+
+    let mut tagged_file = Probe::open(to_extended_length(path))?.read()?;
+    let tag_type = picture_tag_type(tagged_file.file_type())
+        .ok_or_else(|| anyhow::anyhow!("File format can't hold pictures: {}", path.display()))?;
+
+    if tagged_file.tag(tag_type).is_none() {
+        tagged_file.insert_tag(Tag::new(tag_type));
+    }
+    let tag = tagged_file
+        .tag_mut(tag_type)
+        .ok_or_else(|| anyhow::anyhow!("Failed to create tag for {}", path.display()))?;
+
+    tag.remove_picture_type(pic_type);
+    tag.push_picture(Picture::new_unchecked(pic_type, Some(mime_type), None, image_data));
+    tagged_file.save_to_path(to_extended_length(path), WriteOptions::default())?;
+
+    crate::library::indexer::LibraryIndexer::calculate_file_hash(path)
+}
+
+/// Extensions a cached cover can have, see image_extension
+const CACHED_IMAGE_EXTENSIONS: [&str; 5] = ["jpg", "png", "gif", "webp", "bmp"];
+
+/// File extension for cached image data, by its format
+fn image_extension(data: &[u8]) -> &'static str {
+    match image::guess_format(data) {
+        Ok(image::ImageFormat::Png) => "png",
+        Ok(image::ImageFormat::Gif) => "gif",
+        Ok(image::ImageFormat::WebP) => "webp",
+        Ok(image::ImageFormat::Bmp) => "bmp",
+        _ => "jpg",
+    }
+}
+
+/// Cache key for a track's cover: its file hash, or a hash of its path and modification
+/// time for tracks indexed without one. Either way it changes when the file does.
+fn artwork_cache_key(track: &Track) -> String {
+    if let Some(hash) = track.file_hash.as_ref().filter(|h| !h.is_empty()) {
+        return hash.clone();
+    }
+    let modified = std::fs::metadata(to_extended_length(Path::new(audio_file_path(&track.file_path))))
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    blake3::hash(format!("{}|{}", track.file_path, modified).as_bytes()).to_hex().to_string()
+}
+
+/// Previously cached cover for `key`, whatever its extension
+fn find_cached(cache_dir: &Path, key: &str) -> Option<PathBuf> {
+    CACHED_IMAGE_EXTENSIONS.iter()
+        .map(|ext| cache_dir.join(format!("{}.{}", key, ext)))
+        .find(|path| path.is_file())
+}
+
+/// Path of the track's cover in the artwork cache, extracting it from the file on the
+/// first call. None if the file has no embedded art.
+pub fn cached_album_art(cache_dir: &Path, track: &Track) -> Result<Option<PathBuf>, anyhow::Error> {
+    let key = artwork_cache_key(track);
+    if let Some(path) = find_cached(cache_dir, &key) {
+        return Ok(Some(path));
+    }
+
+    let Some(data) = read_album_art(Path::new(audio_file_path(&track.file_path))) else {
+        return Ok(None);
+    };
+    std::fs::create_dir_all(cache_dir)?;
+    let path = cache_dir.join(format!("{}.{}", key, image_extension(&data)));
+    std::fs::write(&path, &data)?;
+    Ok(Some(path))
+}
+
+/// Delete cached covers nothing refers to any more: files not named after the hash of a
+/// track whose audio file still exists, and not used as an album/playlist artwork path.
+/// Returns the number of bytes reclaimed.
+pub fn prune_cache(db: &DatabaseConnection, cache_dir: &Path) -> Result<u64, anyhow::Error> {
+    let entries = match std::fs::read_dir(cache_dir) {
+        Ok(entries) => entries,
+        // Nothing cached yet
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+
+    let live_hashes: HashSet<String> = DbOperations::get_track_hashes(db)?
+        .into_iter()
+        .filter(|(_, file_path)| Path::new(audio_file_path(file_path)).is_file())
+        .map(|(hash, _)| hash)
+        .collect();
+    let referenced: HashSet<String> = DbOperations::get_referenced_artwork_paths(db)?
+        .into_iter()
+        .collect();
+
+    let mut reclaimed = 0;
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_file() {
+            continue;
+        }
+
+        let hash = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        if live_hashes.contains(&hash) || referenced.contains(path.to_string_lossy().as_ref()) {
+            continue;
+        }
+
+        let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+        match std::fs::remove_file(&path) {
+            Ok(()) => {
+                reclaimed += size;
+                removed += 1;
+            }
+            Err(e) => eprintln!("Failed to remove cached artwork {:?}: {}", path, e),
+        }
+    }
+
+    if removed > 0 {
+        println!("[Backend] Pruned {} cached artwork files ({} bytes)", removed, reclaimed);
+    }
+    Ok(reclaimed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{temp_dir, test_db, test_track, write_test_wav};
+    use std::io::Cursor;
+
+    fn png_bytes(color: [u8; 3]) -> Vec<u8> {
+        let img = image::RgbImage::from_pixel(4, 4, image::Rgb(color));
+        let mut bytes = Cursor::new(Vec::new());
+        img.write_to(&mut bytes, image::ImageFormat::Png).unwrap();
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn test_embed_and_read_back_album_art() {
+        let dir = temp_dir("set_album_art");
+        let path = dir.join("no_art.wav");
+        write_test_wav(&path);
+        assert!(read_album_art(&path).is_none());
+
+        let first = png_bytes([255, 0, 0]);
+        let hash = set_album_art(&path, first.clone(), PictureType::CoverFront).unwrap();
+        assert_eq!(read_album_art(&path), Some(first));
+
+        // Replaces the existing front cover instead of adding a second one
+        let second = png_bytes([0, 0, 255]);
+        let new_hash = set_album_art(&path, second.clone(), PictureType::CoverFront).unwrap();
+        assert_ne!(hash, new_hash);
+        assert_eq!(read_album_art(&path), Some(second));
+
+        assert!(set_album_art(&path, b"not an image".to_vec(), PictureType::CoverFront).is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_find_folder_art_priority() {
+        let dir = temp_dir("folder_art");
+        assert_eq!(find_folder_art(&dir), None);
+
+        std::fs::write(dir.join("cover.txt"), b"not art").unwrap();
+        std::fs::write(dir.join("AlbumArt.JPG"), [0u8]).unwrap();
+        assert_eq!(find_folder_art(&dir), Some(dir.join("AlbumArt.JPG")));
+
+        std::fs::write(dir.join("front.png"), [0u8]).unwrap();
+        assert_eq!(find_folder_art(&dir), Some(dir.join("front.png")));
+
+        std::fs::write(dir.join("Folder.jpg"), [0u8]).unwrap();
+        assert_eq!(find_folder_art(&dir), Some(dir.join("Folder.jpg")));
+
+        // Within one name, .jpg beats .png
+        std::fs::write(dir.join("cover.png"), [0u8]).unwrap();
+        assert_eq!(find_folder_art(&dir), Some(dir.join("cover.png")));
+        std::fs::write(dir.join("cover.jpg"), [0u8]).unwrap();
+        assert_eq!(find_folder_art(&dir), Some(dir.join("cover.jpg")));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_validate_image() {
+        assert!(matches!(validate_image(&png_bytes([0, 0, 0])), Ok(MimeType::Png)));
+
+        // Minimal JPEG marker layout: SOI, SOF0 (1x1, 1 component), SOS
+        let jpeg = [
+            0xFF, 0xD8,
+            0xFF, 0xC0, 0x00, 0x0B, 0x08, 0x00, 0x01, 0x00, 0x01, 0x01, 0x01, 0x11, 0x00,
+            0xFF, 0xDA, 0x00, 0x08, 0x01, 0x01, 0x00, 0x00, 0x3F, 0x00,
+        ];
+        assert!(matches!(validate_image(&jpeg), Ok(MimeType::Jpeg)));
+        assert!(validate_image(&jpeg[..6]).is_err());
+        assert!(validate_image(&[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n']).is_err());
+    }
+
+    #[test]
+    fn test_prune_cache_removes_orphans() {
+        let dir = temp_dir("prune_artwork");
+        let cache_dir = dir.join(ARTWORK_CACHE_DIR);
+        std::fs::create_dir_all(&cache_dir).unwrap();
+        let db = test_db();
+
+        let audio = dir.join("song.wav");
+        write_test_wav(&audio);
+        let track = Track {
+            title: "song".to_string(),
+            ..test_track(&audio.to_string_lossy())
+        };
+        DbOperations::upsert_track_with_hash(&db, &track, "livehash").unwrap();
+
+        std::fs::write(cache_dir.join("livehash.png"), [0u8; 10]).unwrap();
+        std::fs::write(cache_dir.join("deadhash.jpg"), [0u8; 25]).unwrap();
+
+        assert_eq!(prune_cache(&db, &cache_dir).unwrap(), 25);
+        assert!(cache_dir.join("livehash.png").exists());
+        assert!(!cache_dir.join("deadhash.jpg").exists());
+
+        // Once the audio file is gone its cover goes too
+        std::fs::remove_file(&audio).unwrap();
+        assert_eq!(prune_cache(&db, &cache_dir).unwrap(), 10);
+
+        assert_eq!(prune_cache(&db, &dir.join("missing")).unwrap(), 0);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_cached_album_art_extracts_once() {
+        let dir = temp_dir("cached_album_art");
+        let cache_dir = dir.join(ARTWORK_CACHE_DIR);
+        let audio = dir.join("covered.wav");
+        write_test_wav(&audio);
+        let cover = png_bytes([0, 255, 0]);
+        let file_hash = set_album_art(&audio, cover.clone(), PictureType::CoverFront).unwrap();
+
+        let mut track = Track {
+            id: 1,
+            title: "covered".to_string(),
+            file_hash: Some(file_hash.clone()),
+            ..test_track(&audio.to_string_lossy())
+        };
+
+        let path = cached_album_art(&cache_dir, &track).unwrap().unwrap();
+        assert_eq!(path, cache_dir.join(format!("{}.png", file_hash)));
+        assert_eq!(std::fs::read(&path).unwrap(), cover);
+
+        // Served from the cache once extracted, even if the file is gone
+        std::fs::remove_file(&audio).unwrap();
+        assert_eq!(cached_album_art(&cache_dir, &track).unwrap(), Some(path));
+
+        track.file_hash = Some("other".to_string());
+        assert_eq!(cached_album_art(&cache_dir, &track).unwrap(), None);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
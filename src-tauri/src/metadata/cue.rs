@@ -0,0 +1,347 @@
+// CUE sheet support
+// Albums ripped to one big audio file come with a .cue sheet marking where each track starts.
+// Every CUE track becomes its own library track whose file_path is the audio file plus the
+// track's bounds ("Album.flac#252666-498000", end left empty for the last track), which the
+// decoder turns back into a start position and a stop position.
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::library::scanner::DirectoryScanner;
+use crate::path_utils::to_extended_length;
+
+/// CUE positions are minutes:seconds:frames with 75 frames per second (CD sectors)
+const FRAMES_PER_SECOND: i64 = 75;
+
+/// Start and end of a CUE track within its audio file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CueRange {
+    pub start_ms: i64,
+    /// None for the last track, which plays to the end of the file
+    pub end_ms: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CueTrack {
+    /// Audio file as written in the sheet's FILE line
+    pub file: String,
+    pub number: i32,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub range: CueRange,
+}
+
+/// The parts of a CUE sheet the library uses
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CueSheet {
+    /// Album title
+    pub title: Option<String>,
+    /// Album artist
+    pub performer: Option<String>,
+    pub genre: Option<String>,
+    pub date: Option<String>,
+    pub tracks: Vec<CueTrack>,
+}
+
+/// Whether the file is a CUE sheet (case-insensitive extension check)
+pub fn is_cue_sheet<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref()
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("cue"))
+}
+
+/// Library path of a CUE track
+pub fn cue_track_path(audio_path: &str, range: CueRange) -> String {
+    match range.end_ms {
+        Some(end_ms) => format!("{}#{}-{}", audio_path, range.start_ms, end_ms),
+        None => format!("{}#{}-", audio_path, range.start_ms),
+    }
+}
+
+/// Split a library path into the file on disk and, for CUE tracks, the track's bounds.
+/// Only a trailing "#<start>-<end>" counts, so file names containing '#' are left alone.
+pub fn split_cue_track_path(path: &str) -> (&str, Option<CueRange>) {
+    let Some((file, bounds)) = path.rsplit_once('#') else {
+        return (path, None);
+    };
+    let Some((start, end)) = bounds.split_once('-') else {
+        return (path, None);
+    };
+    let is_number = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if !is_number(start) || !(end.is_empty() || is_number(end)) {
+        return (path, None);
+    }
+    match (start.parse(), end.parse().ok()) {
+        (Ok(start_ms), end_ms) if end_ms.is_none_or(|end_ms| end_ms > start_ms) => {
+            (file, Some(CueRange { start_ms, end_ms }))
+        }
+        _ => (path, None),
+    }
+}
+
+/// The file on disk behind a library path
+pub fn audio_file_path(path: &str) -> &str {
+    split_cue_track_path(path).0
+}
+
+/// Parse an "mm:ss:ff" INDEX position to milliseconds
+fn parse_position(value: &str) -> Option<i64> {
+    let mut parts = value.trim().splitn(3, ':').map(|part| part.parse::<i64>().ok());
+    let (minutes, seconds, frames) = (parts.next()??, parts.next()??, parts.next()??);
+    if seconds >= 60 || frames >= FRAMES_PER_SECOND || minutes < 0 || seconds < 0 || frames < 0 {
+        return None;
+    }
+    Some((minutes * 60 + seconds) * 1000 + frames * 1000 / FRAMES_PER_SECOND)
+}
+
+/// A command's argument, without the quotes if it is quoted
+fn argument(rest: &str) -> String {
+    let rest = rest.trim();
+    match rest.strip_prefix('"') {
+        Some(quoted) => quoted.split('"').next().unwrap_or_default().to_string(),
+        None => rest.to_string(),
+    }
+}
+
+/// A TRACK block while it's being read
+#[derive(Default)]
+struct PendingTrack {
+    number: i32,
+    title: Option<String>,
+    performer: Option<String>,
+    start_ms: Option<i64>,
+}
+
+/// None for empty values, so missing and blank fields look the same
+fn non_empty(value: String) -> Option<String> {
+    Some(value).filter(|v| !v.trim().is_empty())
+}
+
+impl CueSheet {
+    /// Parse the text of a CUE sheet. Tracks without an INDEX 01 and non-audio tracks
+    /// are left out; each track ends where the next track of the same file starts.
+    pub fn parse(content: &str) -> Self {
+        let mut sheet = CueSheet::default();
+        let mut file: Option<String> = None;
+        let mut current: Option<PendingTrack> = None;
+        // Past the first TRACK, TITLE and PERFORMER belong to tracks (even skipped ones)
+        let mut in_tracks = false;
+        let mut tracks = Vec::new();
+
+        let mut finish = |current: &mut Option<PendingTrack>, file: &Option<String>| {
+            let Some(track) = current.take() else { return };
+            if let (Some(start_ms), Some(file)) = (track.start_ms, file) {
+                tracks.push(CueTrack {
+                    file: file.clone(),
+                    number: track.number,
+                    title: track.title,
+                    performer: track.performer,
+                    range: CueRange { start_ms, end_ms: None },
+                });
+            }
+        };
+
+        for line in content.trim_start_matches('\u{feff}').lines() {
+            let line = line.trim();
+            let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            match command.to_ascii_uppercase().as_str() {
+                "FILE" => {
+                    finish(&mut current, &file);
+                    // FILE "name with spaces.flac" WAVE: the type after the name is ignored
+                    let rest = rest.trim();
+                    let name = if rest.starts_with('"') {
+                        argument(rest)
+                    } else {
+                        rest.rsplit_once(char::is_whitespace).map_or(rest, |(name, _)| name).to_string()
+                    };
+                    file = non_empty(name);
+                }
+                "TRACK" => {
+                    finish(&mut current, &file);
+                    in_tracks = true;
+                    let mut words = rest.split_whitespace();
+                    let number = words.next().and_then(|n| n.parse().ok());
+                    let is_audio = words.next().is_some_and(|kind| kind.eq_ignore_ascii_case("AUDIO"));
+                    current = number.filter(|_| is_audio).map(|number| PendingTrack { number, ..Default::default() });
+                }
+                "TITLE" => match current.as_mut() {
+                    Some(track) => track.title = non_empty(argument(rest)),
+                    None if !in_tracks => sheet.title = non_empty(argument(rest)),
+                    None => {}
+                },
+                "PERFORMER" => match current.as_mut() {
+                    Some(track) => track.performer = non_empty(argument(rest)),
+                    None if !in_tracks => sheet.performer = non_empty(argument(rest)),
+                    None => {}
+                },
+                "INDEX" => {
+                    let mut words = rest.split_whitespace();
+                    if let (Some(track), Some("01"), Some(position)) = (current.as_mut(), words.next(), words.next()) {
+                        track.start_ms = parse_position(position);
+                    }
+                }
+                "REM" => {
+                    let (key, value) = rest.trim().split_once(char::is_whitespace).unwrap_or((rest.trim(), ""));
+                    match key.to_ascii_uppercase().as_str() {
+                        "GENRE" => sheet.genre = non_empty(argument(value)),
+                        "DATE" => sheet.date = non_empty(argument(value)),
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        finish(&mut current, &file);
+
+        // A track ends where the next one in the same file starts
+        for i in 0..tracks.len() {
+            let next_start = tracks.get(i + 1)
+                .filter(|next| next.file == tracks[i].file)
+                .map(|next| next.range.start_ms);
+            tracks[i].range.end_ms = next_start.filter(|&end_ms| end_ms > tracks[i].range.start_ms);
+        }
+        sheet.tracks = tracks;
+        sheet
+    }
+
+    /// Read and parse a CUE sheet. Sheets that aren't UTF-8 are read as Latin-1,
+    /// which older rippers wrote.
+    pub fn read(path: &Path) -> Result<Self, anyhow::Error> {
+        let bytes = std::fs::read(to_extended_length(path))?;
+        let content = match String::from_utf8(bytes) {
+            Ok(content) => content,
+            Err(e) => e.into_bytes().iter().map(|&b| b as char).collect(),
+        };
+        Ok(Self::parse(&content))
+    }
+
+    /// The sheet's tracks grouped by the audio file they are in, resolved against the
+    /// sheet's folder. Files that can't be found are left out.
+    pub fn tracks_by_file(&self, cue_path: &Path) -> Vec<(PathBuf, Vec<&CueTrack>)> {
+        let folder = cue_path.parent().unwrap_or(Path::new(""));
+        let mut files: Vec<(PathBuf, Vec<&CueTrack>)> = Vec::new();
+        let mut resolved: HashMap<&str, Option<PathBuf>> = HashMap::new();
+        for track in &self.tracks {
+            let Some(path) = resolved.entry(&track.file).or_insert_with(|| resolve_audio_file(folder, &track.file)) else {
+                continue;
+            };
+            match files.iter_mut().find(|(file, _)| file == path) {
+                Some((_, tracks)) => tracks.push(track),
+                None => files.push((path.clone(), vec![track])),
+            }
+        }
+        files
+    }
+}
+
+/// Find a sheet's audio file. Sheets often still name the file the CD was ripped to
+/// ("CDImage.wav") after it was converted, so another supported file with the same
+/// name but a different extension is accepted too.
+fn resolve_audio_file(folder: &Path, name: &str) -> Option<PathBuf> {
+    let path = folder.join(name);
+    if to_extended_length(&path).is_file() && DirectoryScanner::is_supported_audio_file(&path) {
+        return Some(path);
+    }
+    let stem = path.file_stem()?.to_os_string();
+    std::fs::read_dir(to_extended_length(folder)).ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| folder.join(entry.file_name()))
+        .filter(|candidate| candidate.file_stem() == Some(stem.as_os_str()))
+        .find(|candidate| DirectoryScanner::is_supported_audio_file(candidate) && to_extended_length(candidate).is_file())
+}
+
+/// Audio files in a folder that CUE sheets there describe, mapped to their sheet
+pub fn cue_sheets_in_folder(folder: &Path) -> HashMap<PathBuf, PathBuf> {
+    let Ok(entries) = std::fs::read_dir(to_extended_length(folder)) else {
+        return HashMap::new();
+    };
+    let mut sheets: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| folder.join(entry.file_name()))
+        .filter(|path| is_cue_sheet(path))
+        .collect();
+    sheets.sort();
+
+    let mut covered = HashMap::new();
+    for cue_path in sheets {
+        let Ok(sheet) = CueSheet::read(&cue_path) else { continue };
+        for (audio_path, _) in sheet.tracks_by_file(&cue_path) {
+            covered.entry(audio_path).or_insert_with(|| cue_path.clone());
+        }
+    }
+    covered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHEET: &str = "\u{feff}REM GENRE \"Progressive Rock\"
+REM DATE 1973
+PERFORMER \"Pink Floyd\"
+TITLE \"The Dark Side of the Moon\"
+FILE \"Dark Side.flac\" WAVE
+  TRACK 01 AUDIO
+    TITLE \"Speak to Me\"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE \"Breathe\"
+    PERFORMER \"Pink Floyd feat. Someone\"
+    INDEX 00 01:05:00
+    INDEX 01 01:07:37
+  TRACK 03 AUDIO
+    TITLE \"On the Run\"
+    INDEX 01 03:56:15
+";
+
+    #[test]
+    fn test_parse_sheet() {
+        let sheet = CueSheet::parse(SHEET);
+        assert_eq!(sheet.title.as_deref(), Some("The Dark Side of the Moon"));
+        assert_eq!(sheet.performer.as_deref(), Some("Pink Floyd"));
+        assert_eq!(sheet.genre.as_deref(), Some("Progressive Rock"));
+        assert_eq!(sheet.date.as_deref(), Some("1973"));
+
+        let summary: Vec<(i32, Option<&str>, CueRange)> = sheet.tracks.iter()
+            .map(|t| (t.number, t.title.as_deref(), t.range))
+            .collect();
+        assert_eq!(summary, vec![
+            (1, Some("Speak to Me"), CueRange { start_ms: 0, end_ms: Some(67_493) }),
+            (2, Some("Breathe"), CueRange { start_ms: 67_493, end_ms: Some(236_200) }),
+            (3, Some("On the Run"), CueRange { start_ms: 236_200, end_ms: None }),
+        ]);
+        assert_eq!(sheet.tracks[1].performer.as_deref(), Some("Pink Floyd feat. Someone"));
+        assert!(sheet.tracks.iter().all(|t| t.file == "Dark Side.flac"));
+    }
+
+    #[test]
+    fn test_cue_track_path_round_trip() {
+        let range = CueRange { start_ms: 67_493, end_ms: Some(236_200) };
+        let path = cue_track_path("/music/Album #1/Dark Side.flac", range);
+        assert_eq!(path, "/music/Album #1/Dark Side.flac#67493-236200");
+        assert_eq!(split_cue_track_path(&path), ("/music/Album #1/Dark Side.flac", Some(range)));
+
+        let last = CueRange { start_ms: 236_200, end_ms: None };
+        assert_eq!(split_cue_track_path(&cue_track_path("a.flac", last)), ("a.flac", Some(last)));
+
+        // Ordinary file names containing '#'
+        assert_eq!(split_cue_track_path("/music/Track #1.flac"), ("/music/Track #1.flac", None));
+        assert_eq!(split_cue_track_path("/music/a.flac#10-5"), ("/music/a.flac#10-5", None));
+    }
+
+    #[test]
+    fn test_tracks_by_file_finds_converted_image() {
+        let dir = std::env::temp_dir().join(format!("musicsloth_cue_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("CDImage.flac"), b"").unwrap();
+        let cue_path = dir.join("CDImage.cue");
+        std::fs::write(&cue_path, "FILE \"CDImage.wav\" WAVE\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n").unwrap();
+
+        let sheet = CueSheet::read(&cue_path).unwrap();
+        let files = sheet.tracks_by_file(&cue_path);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].0, dir.join("CDImage.flac"));
+        assert_eq!(cue_sheets_in_folder(&dir).get(&dir.join("CDImage.flac")), Some(&cue_path));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
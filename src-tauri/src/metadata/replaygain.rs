@@ -0,0 +1,81 @@
+// ReplayGain tags written by other tools (foobar2000, mp3gain, loudgain, ...)
+// Reading them is much cheaper than analyzing the audio ourselves
+use lofty::prelude::{ItemKey, TaggedFileExt};
+use lofty::probe::Probe;
+use std::path::Path;
+
+use crate::path_utils::to_extended_length;
+
+/// ReplayGain values from a file's tags
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReplayGainTags {
+    /// REPLAYGAIN_TRACK_GAIN in dB
+    pub track_gain_db: f32,
+    /// REPLAYGAIN_TRACK_PEAK as a fraction of full scale (1.0 = 0 dBFS)
+    pub track_peak: Option<f32>,
+    /// REPLAYGAIN_ALBUM_GAIN in dB
+    pub album_gain_db: Option<f32>,
+    /// REPLAYGAIN_ALBUM_PEAK as a fraction of full scale
+    pub album_peak: Option<f32>,
+}
+
+/// Parse a gain value: "-6.48 dB", "+1.20 dB", "-6.48dB", "3.5 db" or a bare "-6.48".
+/// Some taggers write a decimal comma ("-6,48 dB").
+pub fn parse_gain(value: &str) -> Option<f32> {
+    let value = value.trim();
+    let number = match value.len().checked_sub(2) {
+        Some(split) if value.is_char_boundary(split) && value[split..].eq_ignore_ascii_case("db") => &value[..split],
+        _ => value,
+    };
+    let number = number.trim().trim_start_matches('+').replace(',', ".");
+    number.parse::<f32>().ok().filter(|gain| gain.is_finite())
+}
+
+/// Parse a peak value ("0.988312"), which must be a positive fraction of full scale
+pub fn parse_peak(value: &str) -> Option<f32> {
+    value.trim()
+        .replace(',', ".")
+        .parse::<f32>()
+        .ok()
+        .filter(|peak| peak.is_finite() && *peak > 0.0)
+}
+
+/// ReplayGain values in a tag, None without a (parseable) track gain
+pub fn read_replaygain(tag: &lofty::tag::Tag) -> Option<ReplayGainTags> {
+    let track_gain_db = tag.get_string(&ItemKey::ReplayGainTrackGain).and_then(parse_gain)?;
+    Some(ReplayGainTags {
+        track_gain_db,
+        track_peak: tag.get_string(&ItemKey::ReplayGainTrackPeak).and_then(parse_peak),
+        album_gain_db: tag.get_string(&ItemKey::ReplayGainAlbumGain).and_then(parse_gain),
+        album_peak: tag.get_string(&ItemKey::ReplayGainAlbumPeak).and_then(parse_peak),
+    })
+}
+
+/// ReplayGain values from any of a file's tags
+pub fn read_replaygain_from_file(file_path: &Path) -> Option<ReplayGainTags> {
+    let tagged_file = Probe::open(to_extended_length(file_path)).ok()?.guess_file_type().ok()?.read().ok()?;
+    tagged_file.tags().iter().find_map(read_replaygain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_gain_formats() {
+        assert_eq!(parse_gain("-6.48 dB"), Some(-6.48));
+        assert_eq!(parse_gain("+1.20 dB"), Some(1.2));
+        assert_eq!(parse_gain("-6.48dB"), Some(-6.48));
+        assert_eq!(parse_gain("3.5 db"), Some(3.5));
+        assert_eq!(parse_gain("  -0.10 DB "), Some(-0.1));
+        assert_eq!(parse_gain("-6.48"), Some(-6.48));
+        assert_eq!(parse_gain("-6,48 dB"), Some(-6.48));
+        assert_eq!(parse_gain("dB"), None);
+        assert_eq!(parse_gain("loud"), None);
+        assert_eq!(parse_gain(""), None);
+
+        assert_eq!(parse_peak("0.988312"), Some(0.988312));
+        assert_eq!(parse_peak("1.05"), Some(1.05));
+        assert_eq!(parse_peak("0"), None);
+    }
+}
@@ -0,0 +1,198 @@
+// Sidecar metadata importer
+// Lets users correct metadata in external files instead of editing the audio files.
+//
+// Per-file sidecar: `<file name>.json` (e.g. `song.mp3.json`) or `<file stem>.json` (`song.json`)
+// Album-level sidecar (applies to every file in the folder): `metadata.json` or `album.nfo`
+//
+// JSON schema (every field optional):
+//   { "title": "...", "artist": "...", "album": "...", "year": 1999, "genre": "..." }
+//
+// album.nfo is the Kodi-style XML file; its <title> is the album title and
+// <artist>, <year> and <genre> are read as well.
+use serde::Deserialize;
+use std::path::Path;
+
+use crate::db::models::Track;
+
+/// Metadata fields a sidecar can provide
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct SidecarMetadata {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub artist: Option<String>,
+    #[serde(default)]
+    pub album: Option<String>,
+    #[serde(default)]
+    pub year: Option<u32>,
+    #[serde(default)]
+    pub genre: Option<String>,
+}
+
+impl SidecarMetadata {
+    /// Find the sidecar metadata for an audio file. Per-file sidecar fields take
+    /// precedence over album-level ones. Returns None if there is no sidecar.
+    pub fn find_for(audio_path: &Path) -> Option<Self> {
+        let file_level = Self::read_file_sidecar(audio_path);
+        let album_level = audio_path.parent().and_then(Self::read_album_sidecar);
+
+        match (file_level, album_level) {
+            (Some(file), Some(album)) => Some(file.or(album)),
+            (file, album) => file.or(album),
+        }
+    }
+
+    /// Overlay sidecar fields onto an extracted track. By default only fields the embedded
+    /// tags left empty are filled in; with `prefer_sidecar` every sidecar field wins.
+    pub fn apply_to(&self, track: &mut Track, prefer_sidecar: bool) {
+        // The extractor falls back to the file stem when there's no title tag
+        let title_missing = Path::new(&track.file_path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy() == track.title)
+            .unwrap_or(false);
+        if let Some(ref title) = self.title {
+            if prefer_sidecar || title_missing || track.title.is_empty() {
+                track.title = title.clone();
+            }
+        }
+
+        overlay(&mut track.artist, &self.artist, prefer_sidecar);
+        overlay(&mut track.album, &self.album, prefer_sidecar);
+        overlay(&mut track.genre, &self.genre, prefer_sidecar);
+        if self.year.is_some() && (prefer_sidecar || track.year.is_none()) {
+            track.year = self.year;
+        }
+    }
+
+    /// Fill in any field this sidecar doesn't have from `other`
+    fn or(self, other: Self) -> Self {
+        Self {
+            title: self.title.or(other.title),
+            artist: self.artist.or(other.artist),
+            album: self.album.or(other.album),
+            year: self.year.or(other.year),
+            genre: self.genre.or(other.genre),
+        }
+    }
+
+    fn read_file_sidecar(audio_path: &Path) -> Option<Self> {
+        let file_name = audio_path.file_name()?.to_string_lossy().to_string();
+        let candidates = [
+            audio_path.with_file_name(format!("{}.json", file_name)),
+            audio_path.with_extension("json"),
+        ];
+        candidates.iter().find_map(|p| Self::read_json(p))
+    }
+
+    fn read_album_sidecar(folder: &Path) -> Option<Self> {
+        Self::read_json(&folder.join("metadata.json"))
+            .or_else(|| Self::read_nfo(&folder.join("album.nfo")))
+    }
+
+    fn read_json(path: &Path) -> Option<Self> {
+        let content = std::fs::read_to_string(path).ok()?;
+        match serde_json::from_str(&content) {
+            Ok(metadata) => Some(metadata),
+            Err(e) => {
+                eprintln!("Ignoring invalid sidecar {:?}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    fn read_nfo(path: &Path) -> Option<Self> {
+        let content = std::fs::read_to_string(path).ok()?;
+        Some(Self {
+            title: None,
+            artist: xml_element(&content, "artist"),
+            album: xml_element(&content, "title"),
+            year: xml_element(&content, "year").and_then(|y| y.parse().ok()),
+            genre: xml_element(&content, "genre"),
+        })
+    }
+}
+
+/// Set `field` from the sidecar value if it's empty, or always when the sidecar is preferred
+fn overlay(field: &mut Option<String>, value: &Option<String>, prefer_sidecar: bool) {
+    if let Some(value) = value {
+        let is_empty = field.as_deref().map(|s| s.trim().is_empty()).unwrap_or(true);
+        if prefer_sidecar || is_empty {
+            *field = Some(value.clone());
+        }
+    }
+}
+
+/// Text of the first `<name>...</name>` element, trimmed; None if missing or empty
+fn xml_element(content: &str, name: &str) -> Option<String> {
+    let open = format!("<{}>", name);
+    let close = format!("</{}>", name);
+    let start = content.find(&open)? + open.len();
+    let end = start + content[start..].find(&close)?;
+    let text = content[start..end].trim();
+    if text.is_empty() {
+        None
+    } else {
+        Some(text.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{temp_dir, test_track};
+
+    fn track_for(path: &Path, title: &str, album: Option<&str>) -> Track {
+        Track {
+            title: title.to_string(),
+            artist: Some("Tagged Artist".to_string()),
+            album: album.map(|a| a.to_string()),
+            ..test_track(&path.to_string_lossy())
+        }
+    }
+
+    #[test]
+    fn test_sidecar_fills_missing_album() {
+        let dir = temp_dir("sidecar_fill");
+        let song = dir.join("song.mp3");
+        std::fs::write(
+            dir.join("album.nfo"),
+            "<album>\n  <title>Folder Album</title>\n  <year>1999</year>\n</album>",
+        ).unwrap();
+        std::fs::write(dir.join("song.mp3.json"), r#"{"title": "Sidecar Title", "genre": "Jazz"}"#).unwrap();
+
+        let sidecar = SidecarMetadata::find_for(&song).unwrap();
+        assert_eq!(sidecar.album.as_deref(), Some("Folder Album"));
+
+        // No title tag: the extractor used the file stem
+        let mut track = track_for(&song, "song", None);
+        sidecar.apply_to(&mut track, false);
+        assert_eq!(track.title, "Sidecar Title");
+        assert_eq!(track.album.as_deref(), Some("Folder Album"));
+        assert_eq!(track.year, Some(1999));
+        assert_eq!(track.genre.as_deref(), Some("Jazz"));
+        assert_eq!(track.artist.as_deref(), Some("Tagged Artist"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_embedded_tags_win_by_default() {
+        let dir = temp_dir("sidecar_prefer");
+        let song = dir.join("song.flac");
+        std::fs::write(dir.join("song.json"), r#"{"title": "Sidecar Title", "album": "Sidecar Album"}"#).unwrap();
+        let sidecar = SidecarMetadata::find_for(&song).unwrap();
+
+        let mut track = track_for(&song, "Tagged Title", Some("Tagged Album"));
+        sidecar.apply_to(&mut track, false);
+        assert_eq!(track.title, "Tagged Title");
+        assert_eq!(track.album.as_deref(), Some("Tagged Album"));
+
+        sidecar.apply_to(&mut track, true);
+        assert_eq!(track.title, "Sidecar Title");
+        assert_eq!(track.album.as_deref(), Some("Sidecar Album"));
+
+        assert!(SidecarMetadata::find_for(&dir.join("other.mp3")).is_none());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
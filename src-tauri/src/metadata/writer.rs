@@ -0,0 +1,167 @@
+// Tag writer for fixing mistagged files
+// Only the primary tag is rewritten and only the edited fields change in it, so embedded
+// artwork, lyrics, ReplayGain values and any other tags in the file are kept as they were.
+use anyhow::Result;
+use lofty::config::WriteOptions;
+use lofty::prelude::{Accessor, ItemKey, TagExt, TaggedFileExt};
+use lofty::probe::Probe;
+use lofty::tag::Tag;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::error::AppError;
+use crate::path_utils::to_extended_length;
+
+/// Tag changes for one track. None leaves a field as it is; an empty string (or 0 for
+/// numbers) removes it from the file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrackEdits {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub artist: Option<String>,
+    #[serde(default)]
+    pub album: Option<String>,
+    #[serde(default)]
+    pub album_artist: Option<String>,
+    #[serde(default)]
+    pub year: Option<u32>,
+    #[serde(default)]
+    pub genre: Option<String>,
+    #[serde(default)]
+    pub track_number: Option<u32>,
+}
+
+/// Write the edits into the file's tags, creating a tag of the format's usual type if the
+/// file has none
+pub fn write_tags(file_path: &Path, edits: &TrackEdits) -> Result<()> {
+    let path = to_extended_length(file_path);
+    let metadata = std::fs::metadata(&path)
+        .map_err(|e| AppError::from(e).context(format!("Cannot access {}", file_path.display())))?;
+    if metadata.permissions().readonly() {
+        return Err(AppError::Io(format!("File is read-only: {}", file_path.display())).into());
+    }
+    if let Err(e) = std::fs::OpenOptions::new().write(true).open(&path) {
+        return Err(AppError::Io(format!("Cannot write to {}: {}", file_path.display(), e)).into());
+    }
+
+    let mut tagged_file = Probe::open(&path)?.read()?;
+    if tagged_file.primary_tag().is_none() {
+        let tag_type = tagged_file.primary_tag_type();
+        tagged_file.insert_tag(Tag::new(tag_type));
+    }
+    let tag = tagged_file
+        .primary_tag_mut()
+        .ok_or_else(|| AppError::Metadata(format!("No writable tag in {}", file_path.display())))?;
+
+    apply_edits(tag, edits);
+
+    tag.save_to_path(&path, WriteOptions::default())
+        .map_err(|e| AppError::Metadata(format!("Failed to write tags to {}: {}", file_path.display(), e)).into())
+}
+
+fn apply_edits(tag: &mut Tag, edits: &TrackEdits) {
+    apply_text(tag, edits.title.as_deref(), |t, v| t.set_title(v), |t| t.remove_title());
+    apply_text(tag, edits.artist.as_deref(), |t, v| t.set_artist(v), |t| t.remove_artist());
+    apply_text(tag, edits.album.as_deref(), |t, v| t.set_album(v), |t| t.remove_album());
+    apply_text(
+        tag,
+        edits.album_artist.as_deref(),
+        |t, v| {
+            t.insert_text(ItemKey::AlbumArtist, v);
+        },
+        |t| t.remove_key(&ItemKey::AlbumArtist),
+    );
+    apply_text(tag, edits.genre.as_deref(), |t, v| t.set_genre(v), |t| t.remove_genre());
+
+    match edits.year {
+        Some(0) => tag.remove_year(),
+        Some(year) => tag.set_year(year),
+        None => {}
+    }
+    match edits.track_number {
+        Some(0) => tag.remove_track(),
+        Some(track) => tag.set_track(track),
+        None => {}
+    }
+}
+
+fn apply_text(tag: &mut Tag, value: Option<&str>, set: fn(&mut Tag, String), remove: fn(&mut Tag)) {
+    match value.map(str::trim) {
+        Some("") => remove(tag),
+        Some(value) => set(tag, value.to_string()),
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::extractor::MetadataExtractor;
+    use crate::test_support::{temp_dir, write_silent_wav};
+    use lofty::picture::{MimeType, Picture, PictureType};
+
+    const COVER: [u8; 8] = [0x89, b'P', b'N', b'G', 1, 2, 3, 4];
+
+    /// One second of 8 kHz mono WAV, tagged with title/artist/album and a cover
+    fn tagged_file(name: &str) -> std::path::PathBuf {
+        let dir = temp_dir(&format!("writer_{}", name));
+        let path = dir.join("track.wav");
+        write_silent_wav(&path, 1000);
+
+        let tagged = Probe::open(&path).unwrap().read().unwrap();
+        let mut tag = Tag::new(tagged.primary_tag_type());
+        tag.set_title("Original Title".to_string());
+        tag.set_artist("Wrong Artist".to_string());
+        tag.set_album("Album".to_string());
+        tag.push_picture(Picture::new_unchecked(PictureType::CoverFront, Some(MimeType::Png), None, COVER.to_vec()));
+        tag.save_to_path(&path, WriteOptions::default()).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_write_tags_keeps_untouched_fields_and_artwork() {
+        let path = tagged_file("edit");
+        let edits = TrackEdits {
+            artist: Some("Right Artist".to_string()),
+            album_artist: Some("Right Artist".to_string()),
+            year: Some(1999),
+            track_number: Some(7),
+            album: Some(String::new()),
+            ..Default::default()
+        };
+        write_tags(&path, &edits).unwrap();
+
+        let track = MetadataExtractor::extract_from_file(&path).unwrap();
+        assert_eq!(track.title, "Original Title");
+        assert_eq!(track.artist.as_deref(), Some("Right Artist"));
+        assert_eq!(track.album_artist.as_deref(), Some("Right Artist"));
+        assert_eq!(track.album, None);
+        assert_eq!(track.year, Some(1999));
+        assert_eq!(track.track_number, Some(7));
+
+        let tagged = Probe::open(&path).unwrap().read().unwrap();
+        let pictures = tagged.primary_tag().unwrap().pictures();
+        assert_eq!(pictures.len(), 1);
+        assert_eq!(pictures[0].data(), &COVER);
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_write_tags_rejects_read_only_file() {
+        let path = tagged_file("read_only");
+        let mut permissions = std::fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        std::fs::set_permissions(&path, permissions.clone()).unwrap();
+
+        let edits = TrackEdits { title: Some("New".to_string()), ..Default::default() };
+        let err = write_tags(&path, &edits).unwrap_err();
+        assert!(err.to_string().contains("read-only"), "{}", err);
+
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        std::fs::set_permissions(&path, permissions).unwrap();
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,117 @@
+// Path helpers for long and non-ASCII file paths
+// Classic Windows paths are limited to MAX_PATH (260 characters); the extended-length `\\?\`
+// form lifts that limit. Paths are stored in the database exactly as found on disk and only
+// converted to the extended form right before a filesystem call, so non-ASCII names
+// round-trip unchanged and stored paths keep matching what the scanner finds.
+use std::path::{Path, PathBuf};
+
+const EXTENDED_PREFIX: &str = r"\\?\";
+const EXTENDED_UNC_PREFIX: &str = r"\\?\UNC\";
+const DEVICE_PREFIX: &str = r"\\.\";
+
+/// Path to hand to filesystem calls. On Windows, absolute paths get the `\\?\` prefix
+/// (`\\?\UNC\` for network shares); everywhere else the path is returned unchanged.
+pub fn to_extended_length(path: &Path) -> PathBuf {
+    if cfg!(windows) {
+        if let Some(extended) = path.to_str().and_then(extend_windows_path) {
+            return PathBuf::from(extended);
+        }
+    }
+    path.to_path_buf()
+}
+
+/// Undo `to_extended_length`, giving the path in the form it's stored and displayed in
+pub fn from_extended_length(path: &Path) -> PathBuf {
+    if cfg!(windows) {
+        if let Some(plain) = path.to_str().and_then(strip_windows_prefix) {
+            return PathBuf::from(plain);
+        }
+    }
+    path.to_path_buf()
+}
+
+/// `C:\dir\file` -> `\\?\C:\dir\file`, `\\server\share` -> `\\?\UNC\server\share`.
+/// None for paths that are relative, already extended, or contain `.`/`..` components
+/// (the extended form turns off that normalization, so they'd stop resolving).
+fn extend_windows_path(path: &str) -> Option<String> {
+    if path.starts_with(EXTENDED_PREFIX) || path.starts_with(DEVICE_PREFIX) {
+        return None;
+    }
+
+    // The extended form doesn't accept forward slashes
+    let path = path.replace('/', "\\");
+    if path.split('\\').any(|part| part == "." || part == "..") {
+        return None;
+    }
+
+    if let Some(unc) = path.strip_prefix(r"\\") {
+        return Some(format!("{}{}", EXTENDED_UNC_PREFIX, unc));
+    }
+
+    let bytes = path.as_bytes();
+    let is_drive_absolute = bytes.len() >= 3
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && bytes[2] == b'\\';
+    if is_drive_absolute {
+        Some(format!("{}{}", EXTENDED_PREFIX, path))
+    } else {
+        None
+    }
+}
+
+fn strip_windows_prefix(path: &str) -> Option<String> {
+    if let Some(unc) = path.strip_prefix(EXTENDED_UNC_PREFIX) {
+        return Some(format!(r"\\{}", unc));
+    }
+    path.strip_prefix(EXTENDED_PREFIX).map(|p| p.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::decoder::AudioDecoder;
+    use crate::library::indexer::LibraryIndexer;
+    use crate::library::scanner::DirectoryScanner;
+    use crate::test_support::{test_db, write_test_wav};
+
+    #[test]
+    fn test_extend_windows_path() {
+        assert_eq!(extend_windows_path(r"C:\Music\a.mp3").as_deref(), Some(r"\\?\C:\Music\a.mp3"));
+        assert_eq!(extend_windows_path("D:/Music/b.flac").as_deref(), Some(r"\\?\D:\Music\b.flac"));
+        assert_eq!(extend_windows_path(r"\\nas\music\c.mp3").as_deref(), Some(r"\\?\UNC\nas\music\c.mp3"));
+        assert_eq!(extend_windows_path(r"\\?\C:\Music\a.mp3"), None);
+        assert_eq!(extend_windows_path(r"Music\a.mp3"), None);
+        assert_eq!(extend_windows_path(r"C:\Music\..\a.mp3"), None);
+
+        assert_eq!(strip_windows_prefix(r"\\?\C:\Music\a.mp3").as_deref(), Some(r"C:\Music\a.mp3"));
+        assert_eq!(strip_windows_prefix(r"\\?\UNC\nas\music\c.mp3").as_deref(), Some(r"\\nas\music\c.mp3"));
+        assert_eq!(strip_windows_prefix(r"C:\Music\a.mp3"), None);
+    }
+
+    #[test]
+    fn test_deep_cjk_path_opens_and_indexes() {
+        let root = std::env::temp_dir().join(format!("musicsloth_long_paths_{}", std::process::id()));
+        // Well past MAX_PATH
+        let mut dir = root.clone();
+        for i in 0..12 {
+            dir = dir.join(format!("a_rather_long_folder_name_{:02}", i));
+        }
+        std::fs::create_dir_all(to_extended_length(&dir)).unwrap();
+        let file = dir.join("夜に駆ける.wav");
+        assert!(file.to_string_lossy().len() > 260);
+        write_test_wav(&file);
+
+        assert!(AudioDecoder::open(&file).is_ok());
+        assert_eq!(DirectoryScanner::scan(&root, false).unwrap(), vec![file.clone()]);
+
+        let db = test_db();
+        let track = LibraryIndexer::ensure_track(&file, &db).unwrap();
+        assert_eq!(track.title, "夜に駆ける");
+        // Stored as-is and reopened from the stored string
+        assert_eq!(track.file_path, file.to_string_lossy());
+        assert!(AudioDecoder::open(Path::new(&track.file_path)).is_ok());
+
+        let _ = std::fs::remove_dir_all(to_extended_length(&root));
+    }
+}
@@ -1,2 +1,197 @@
 // Playlist import/export
-// TODO: Implement M3U8 format import/export
+// M3U8 playlists: UTF-8 text with an #EXTM3U header and an #EXTINF line before each path
+use anyhow::Result;
+use std::path::{Component, Path, PathBuf};
+
+use crate::db::models::Track;
+
+/// Render tracks as an extended M3U playlist, keeping their order and absolute paths
+pub fn to_m3u8(tracks: &[Track]) -> String {
+    let mut out = String::from("#EXTM3U\n");
+    for track in tracks {
+        // -1 is the conventional "unknown length"
+        let seconds = track.duration_ms.map(|ms| (ms + 500) / 1000).unwrap_or(-1);
+        let display = match track.artist.as_deref().filter(|a| !a.is_empty()) {
+            Some(artist) => format!("{} - {}", artist, track.title),
+            None => track.title.clone(),
+        };
+        // A line break inside a tag would end the #EXTINF line early
+        let display = display.replace(['\r', '\n'], " ");
+        out.push_str(&format!("#EXTINF:{},{}\n", seconds, display));
+        out.push_str(&track.file_path);
+        out.push('\n');
+    }
+    out
+}
+
+/// Write tracks to an M3U8 file (UTF-8 without BOM)
+pub fn export_m3u8(tracks: &[Track], out_path: &Path) -> Result<()> {
+    std::fs::write(out_path, to_m3u8(tracks))
+        .map_err(|e| anyhow::anyhow!("Failed to write {}: {}", out_path.display(), e))
+}
+
+/// Read the file paths of an M3U or M3U8 playlist, in order. Comment lines (#EXTM3U,
+/// #EXTINF, ...) and blank lines are skipped; relative paths are resolved against the
+/// playlist's own folder.
+pub fn import_m3u8(path: &Path) -> Result<Vec<PathBuf>> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+    Ok(parse_m3u(&String::from_utf8_lossy(&bytes), base_dir))
+}
+
+fn parse_m3u(content: &str, base_dir: &Path) -> Vec<PathBuf> {
+    content
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let entry = match line.strip_prefix("file://") {
+                Some(uri) => PathBuf::from(file_uri_path(uri)),
+                None => PathBuf::from(line),
+            };
+            if entry.is_absolute() {
+                normalize_path(&entry)
+            } else {
+                normalize_path(&base_dir.join(entry))
+            }
+        })
+        .collect()
+}
+
+/// Path of a `file://` URI (given without the scheme): percent-escapes decoded, an
+/// optional "localhost" host dropped, and the slash before a Windows drive letter removed
+fn file_uri_path(uri: &str) -> String {
+    let uri = uri.strip_prefix("localhost").unwrap_or(uri);
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes.get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    let path = String::from_utf8_lossy(&decoded).into_owned();
+
+    // file:///C:/Music/a.mp3
+    let drive = path.as_bytes();
+    if drive.len() >= 3 && drive[0] == b'/' && drive[1].is_ascii_alphabetic() && drive[2] == b':' {
+        return path[1..].to_string();
+    }
+    path
+}
+
+/// Drop "." and resolve ".." lexically, so paths compare equal to the library's stored paths
+/// without touching the filesystem
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push(component);
+                }
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_track;
+
+    fn track(file_path: &str, title: &str, artist: Option<&str>, duration_ms: Option<i64>) -> Track {
+        Track {
+            title: title.to_string(),
+            artist: artist.map(str::to_string),
+            duration_ms,
+            ..test_track(file_path)
+        }
+    }
+
+    #[test]
+    fn test_export_m3u8() {
+        let tracks = vec![
+            track("/music/Björk/Jóga.flac", "Jóga", Some("Björk"), Some(305_400)),
+            track("/music/unknown.mp3", "Line\nBreak", None, None),
+        ];
+        let out_path = std::env::temp_dir().join(format!("musicsloth_export_{}.m3u8", std::process::id()));
+        export_m3u8(&tracks, &out_path).unwrap();
+
+        let bytes = std::fs::read(&out_path).unwrap();
+        assert!(!bytes.starts_with(&[0xEF, 0xBB, 0xBF]));
+        assert_eq!(
+            String::from_utf8(bytes).unwrap(),
+            "#EXTM3U\n\
+             #EXTINF:305,Björk - Jóga\n/music/Björk/Jóga.flac\n\
+             #EXTINF:-1,Line Break\n/music/unknown.mp3\n"
+        );
+
+        std::fs::remove_file(&out_path).ok();
+    }
+
+    #[test]
+    fn test_import_m3u_resolves_relative_paths_and_skips_comments() {
+        let base = Path::new("/music/playlists");
+        let content = "\u{feff}#EXTM3U\r\n\r\n#EXTINF:200,Artist - Song\r\n../Album/01 Song.mp3\r\n\
+                       # a comment\n   \n/abs/Ünïcode.flac\n./same folder.ogg\n";
+        assert_eq!(
+            parse_m3u(content, base),
+            vec![
+                PathBuf::from("/music/Album/01 Song.mp3"),
+                PathBuf::from("/abs/Ünïcode.flac"),
+                PathBuf::from("/music/playlists/same folder.ogg"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_import_m3u_decodes_file_uris() {
+        let base = Path::new("/music/playlists");
+        let content = "file:///music/Bj%C3%B6rk/J%C3%B3ga%20(live).flac\n\
+                       file://localhost/music/100%25.mp3\n\
+                       file:///music/plain.ogg\n";
+        assert_eq!(
+            parse_m3u(content, base),
+            vec![
+                PathBuf::from("/music/Björk/Jóga (live).flac"),
+                PathBuf::from("/music/100%.mp3"),
+                PathBuf::from("/music/plain.ogg"),
+            ]
+        );
+        assert_eq!(file_uri_path("/C:/Music/a%20b.mp3"), "C:/Music/a b.mp3");
+    }
+
+    #[test]
+    fn test_m3u8_round_trip_keeps_order() {
+        let tracks = vec![
+            track("/music/b/zeta.mp3", "Zeta", Some("B"), Some(1_000)),
+            track("/music/a/Ålpha.flac", "Ålpha", None, None),
+            track("/music/c/mid.ogg", "Mid", Some("C"), Some(61_499)),
+        ];
+        let out_path = std::env::temp_dir().join(format!("musicsloth_round_trip_{}.m3u8", std::process::id()));
+        export_m3u8(&tracks, &out_path).unwrap();
+
+        let paths = import_m3u8(&out_path).unwrap();
+        let expected: Vec<PathBuf> = tracks.iter().map(|t| PathBuf::from(&t.file_path)).collect();
+        assert_eq!(paths, expected);
+
+        std::fs::remove_file(&out_path).ok();
+    }
+}
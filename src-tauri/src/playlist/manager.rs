@@ -1,2 +1,186 @@
 // Playlist manager
-// TODO: Implement playlist CRUD operations
+// Higher-level playlist operations built on top of DbOperations
+use std::path::Path;
+
+use crate::db::connection::DatabaseConnection;
+use crate::db::operations::DbOperations;
+use crate::library::indexer::LibraryIndexer;
+use crate::library::scanner::DirectoryScanner;
+use crate::playlist::import_export::import_m3u8;
+
+/// Result of creating a playlist from a folder
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct FolderPlaylistResult {
+    pub playlist_id: i64,
+    pub track_count: usize,
+    pub failed_files: Vec<String>,
+}
+
+/// Result of importing an M3U/M3U8 playlist
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ImportPlaylistResult {
+    pub playlist_id: i64,
+    pub track_count: usize,
+    /// Referenced files that aren't in the library, so the UI can offer to scan them
+    pub missing_files: Vec<String>,
+}
+
+pub struct PlaylistManager;
+
+impl PlaylistManager {
+    /// Create a playlist from the audio files in a folder.
+    /// Files not yet in the library are indexed (without adding the folder to scan paths).
+    /// Tracks keep filesystem order unless `sort_by_track_number` is set, in which case
+    /// they are ordered by disc number, then track number.
+    pub fn create_playlist_from_folder(
+        db: &DatabaseConnection,
+        folder: &Path,
+        name: &str,
+        recursive: bool,
+        sort_by_track_number: bool,
+    ) -> Result<FolderPlaylistResult, anyhow::Error> {
+        let files = DirectoryScanner::scan_folder(folder, recursive)?;
+        
+        let mut tracks = Vec::with_capacity(files.len());
+        let mut failed_files = Vec::new();
+        
+        for file in &files {
+            match LibraryIndexer::ensure_track(file, db) {
+                Ok(track) => tracks.push(track),
+                Err(e) => {
+                    eprintln!("[Playlist] Failed to index {}: {}", file.display(), e);
+                    failed_files.push(file.to_string_lossy().to_string());
+                }
+            }
+        }
+        
+        if sort_by_track_number {
+            // Stable sort keeps filesystem order for tracks without numbers
+            tracks.sort_by_key(|t| (
+                t.disc_number.unwrap_or(i32::MAX),
+                t.track_number.unwrap_or(i32::MAX),
+            ));
+        }
+        
+        let playlist_id = DbOperations::create_playlist(db, name, None)?;
+        
+        let mut track_count = 0;
+        for track in &tracks {
+            // The same file can't appear twice, but skip duplicates defensively
+            if DbOperations::add_track_to_playlist(db, playlist_id, track.id).is_ok() {
+                track_count += 1;
+            }
+        }
+        
+        Ok(FolderPlaylistResult {
+            playlist_id,
+            track_count,
+            failed_files,
+        })
+    }
+
+    /// Create a playlist from an M3U/M3U8 file. Only files already in the library are added,
+    /// in playlist order; the rest are returned as missing.
+    pub fn import_m3u8_playlist(
+        db: &DatabaseConnection,
+        playlist_path: &Path,
+        name: &str,
+    ) -> Result<ImportPlaylistResult, anyhow::Error> {
+        let files = import_m3u8(playlist_path)?;
+        
+        let mut track_ids = Vec::with_capacity(files.len());
+        let mut missing_files = Vec::new();
+        for file in &files {
+            let file_path = file.to_string_lossy();
+            match DbOperations::get_track_by_file_path(db, &file_path)? {
+                Some(track) => track_ids.push(track.id),
+                None => missing_files.push(file_path.to_string()),
+            }
+        }
+        
+        // A playlist can't hold the same track twice, later entries are skipped
+        let (playlist_id, track_count) = DbOperations::create_playlist_with_tracks(db, name, &track_ids)?;
+        
+        Ok(ImportPlaylistResult {
+            playlist_id,
+            track_count,
+            missing_files,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_db;
+    use std::path::PathBuf;
+
+    fn setup_folder(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("musicsloth_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("disc2")).unwrap();
+        // Not real audio - the extractor falls back to minimal track info
+        std::fs::write(dir.join("b.mp3"), b"not really audio b").unwrap();
+        std::fs::write(dir.join("a.mp3"), b"not really audio a").unwrap();
+        std::fs::write(dir.join("cover.jpg"), b"not audio").unwrap();
+        std::fs::write(dir.join("disc2").join("c.flac"), b"not really audio c").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_create_playlist_from_folder() {
+        let dir = setup_folder("folder_playlist");
+        let db = test_db();
+
+        let result = PlaylistManager::create_playlist_from_folder(&db, &dir, "Flat", false, false).unwrap();
+        assert_eq!(result.track_count, 2);
+        assert!(result.failed_files.is_empty());
+
+        let titles: Vec<String> = DbOperations::get_playlist_tracks(&db, result.playlist_id)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.title)
+            .collect();
+        assert_eq!(titles, vec!["a", "b"]);
+
+        // Recursive picks up the subfolder and reuses already-indexed tracks
+        let result = PlaylistManager::create_playlist_from_folder(&db, &dir, "Deep", true, false).unwrap();
+        assert_eq!(result.track_count, 3);
+        assert_eq!(DbOperations::get_all_tracks(&db).unwrap().len(), 3);
+        assert!(DbOperations::get_all_scan_paths(&db).unwrap().is_empty());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_import_m3u8_playlist_reports_missing_files() {
+        let dir = setup_folder("m3u_import");
+        let db = test_db();
+        let library = PlaylistManager::create_playlist_from_folder(&db, &dir, "Library", true, false).unwrap();
+        assert_eq!(library.track_count, 3);
+
+        let playlist_path = dir.join("mix.m3u8");
+        std::fs::write(
+            &playlist_path,
+            "#EXTM3U\n#EXTINF:-1,c\ndisc2/c.flac\n\n#EXTINF:-1,gone\ngone.mp3\nb.mp3\n",
+        )
+        .unwrap();
+
+        let result = PlaylistManager::import_m3u8_playlist(&db, &playlist_path, "Mix").unwrap();
+        assert_eq!(result.track_count, 2);
+        assert_eq!(result.missing_files, vec![dir.join("gone.mp3").to_string_lossy().to_string()]);
+
+        let titles: Vec<String> = DbOperations::get_playlist_tracks(&db, result.playlist_id)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.title)
+            .collect();
+        assert_eq!(titles, vec!["c", "b"]);
+
+        // Importing under a taken name fails instead of reporting success
+        assert!(PlaylistManager::import_m3u8_playlist(&db, &playlist_path, "Mix").is_err());
+        assert_eq!(DbOperations::get_all_playlists(&db).unwrap().len(), 2);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,2 +1,357 @@
 // Queue manager
-// TODO: Implement queue operations (add, remove, reorder)
+// Higher-level queue operations built on top of DbOperations
+use crate::db::connection::DatabaseConnection;
+use crate::error::AppError;
+use crate::db::models::Track;
+use crate::db::operations::DbOperations;
+
+/// Result of starting playback of a whole artist/genre
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct QueueStartResult {
+    pub queue_id: i64,
+    pub first_track: Option<Track>,
+}
+
+/// Queue started by "play next" when no queue is active
+pub const PLAY_NEXT_QUEUE_NAME: &str = "Play Next";
+
+pub struct QueueManager;
+
+impl QueueManager {
+    /// Queue every track by an artist, ordered by album, disc, then track number
+    pub fn create_queue_from_artist(
+        db: &DatabaseConnection,
+        artist_id: i64,
+        shuffle: bool,
+    ) -> Result<QueueStartResult, anyhow::Error> {
+        let name = DbOperations::get_artist_name(db, artist_id)?
+            .ok_or_else(|| AppError::NotFound(format!("Artist not found: {}", artist_id)))?;
+
+        let mut tracks = DbOperations::get_tracks_by_artist(db, artist_id)?;
+        Self::sort_artist_tracks(&mut tracks);
+
+        Self::start_queue(db, &format!("Artist: {}", name), &tracks, shuffle)
+    }
+
+    /// Queue every track in a genre, grouped by artist, then album, then disc/track number
+    pub fn create_queue_from_genre(
+        db: &DatabaseConnection,
+        genre_id: i64,
+        shuffle: bool,
+    ) -> Result<QueueStartResult, anyhow::Error> {
+        let name = DbOperations::get_genre_name(db, genre_id)?
+            .ok_or_else(|| AppError::NotFound(format!("Genre not found: {}", genre_id)))?;
+
+        let mut tracks = DbOperations::get_tracks_by_genre(db, genre_id)?;
+        Self::sort_genre_tracks(&mut tracks);
+
+        Self::start_queue(db, &format!("Genre: {}", name), &tracks, shuffle)
+    }
+
+    /// "Play next": insert tracks right after the current track of the active queue and
+    /// return the queue's new length. Positions are the queue's stored order, which is also
+    /// the play order while shuffled. Without an active queue one is started with
+    /// `current_track_id` (playing outside any queue), followed by the tracks.
+    pub fn play_next(
+        db: &DatabaseConnection,
+        current_track_id: Option<i64>,
+        track_ids: &[i64],
+    ) -> Result<i32, anyhow::Error> {
+        if track_ids.is_empty() {
+            return Err(anyhow::anyhow!("No tracks to play next"));
+        }
+
+        let queue_id = match DbOperations::get_active_queue(db)? {
+            Some(queue) => {
+                // An empty queue has no current track to insert after
+                let after_position = if DbOperations::get_queue_length(db, queue.id)? == 0 {
+                    -1
+                } else {
+                    DbOperations::get_queue_current_index(db, queue.id)?
+                };
+                DbOperations::insert_tracks_after_position(db, queue.id, track_ids, after_position)?;
+                queue.id
+            }
+            None => {
+                let seeded: Vec<i64> = current_track_id.into_iter()
+                    .chain(track_ids.iter().copied())
+                    .collect();
+                match DbOperations::find_queue_by_name(db, PLAY_NEXT_QUEUE_NAME)? {
+                    Some(queue_id) => {
+                        DbOperations::replace_queue_tracks(db, queue_id, &seeded)?;
+                        DbOperations::reset_queue_shuffle(db, queue_id)?;
+                        DbOperations::set_active_queue(db, queue_id)?;
+                        queue_id
+                    }
+                    None => {
+                        let queue_id = DbOperations::create_queue(db, PLAY_NEXT_QUEUE_NAME)?;
+                        DbOperations::add_tracks_to_queue(db, queue_id, &seeded)?;
+                        queue_id
+                    }
+                }
+            }
+        };
+
+        DbOperations::get_queue_length(db, queue_id)
+    }
+
+    /// Order tracks about to be added to a queue. With `group_by_album` they're sorted by
+    /// album, disc and track number so albums stay contiguous, otherwise the given order is kept.
+    /// Ids that aren't in the library go last, in their given order.
+    pub fn order_for_insert(
+        db: &DatabaseConnection,
+        track_ids: &[i64],
+        group_by_album: bool,
+    ) -> Result<Vec<i64>, anyhow::Error> {
+        if !group_by_album {
+            return Ok(track_ids.to_vec());
+        }
+
+        let mut tracks = Vec::with_capacity(track_ids.len());
+        let mut unknown = Vec::new();
+        for &track_id in track_ids {
+            match DbOperations::get_track_by_id(db, track_id)? {
+                Some(track) => tracks.push(track),
+                None => unknown.push(track_id),
+            }
+        }
+        Self::sort_artist_tracks(&mut tracks);
+
+        Ok(tracks.into_iter().map(|t| t.id).chain(unknown).collect())
+    }
+
+    /// The artist a track's album is credited to, like db::queries::album_artist_key
+    fn album_artist(track: &Track) -> &str {
+        track.album_artist.as_deref()
+            .or(track.compilation.then_some("Various Artists"))
+            .or(track.artist.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Album (same-named albums by different artists kept apart), then disc, then track
+    /// number. Tracks without a disc number count as disc 1, tracks without a track number
+    /// go after the numbered ones.
+    fn sort_artist_tracks(tracks: &mut [Track]) {
+        tracks.sort_by_cached_key(|t| (
+            t.album.as_deref().unwrap_or("").to_lowercase(),
+            Self::album_artist(t).to_lowercase(),
+            t.disc_number.unwrap_or(1),
+            t.track_number.unwrap_or(i32::MAX),
+            t.title.to_lowercase(),
+        ));
+    }
+
+    /// Album artist (falling back to track artist), then the artist ordering
+    fn sort_genre_tracks(tracks: &mut [Track]) {
+        Self::sort_artist_tracks(tracks);
+        // Stable sort keeps the album/disc/track order within each artist
+        tracks.sort_by_cached_key(|t| {
+            t.album_artist.as_deref()
+                .or(t.artist.as_deref())
+                .unwrap_or("")
+                .to_lowercase()
+        });
+    }
+
+    /// Create (or reuse, by name) a queue holding `tracks` in order and make it active.
+    /// With `shuffle`, the whole queue is shuffled so playback starts on a random track.
+    fn start_queue(
+        db: &DatabaseConnection,
+        name: &str,
+        tracks: &[Track],
+        shuffle: bool,
+    ) -> Result<QueueStartResult, anyhow::Error> {
+        if tracks.is_empty() {
+            return Err(anyhow::anyhow!("No tracks to play for {}", name));
+        }
+
+        let track_ids: Vec<i64> = tracks.iter().map(|t| t.id).collect();
+
+        let queue_id = match DbOperations::find_queue_by_name(db, name)? {
+            Some(queue_id) => {
+                DbOperations::replace_queue_tracks(db, queue_id, &track_ids)?;
+                // Shuffle state from the previous contents no longer applies
+                DbOperations::reset_queue_shuffle(db, queue_id)?;
+                DbOperations::set_active_queue(db, queue_id)?;
+                queue_id
+            }
+            None => {
+                // create_queue makes the new queue active at index 0
+                let queue_id = DbOperations::create_queue(db, name)?;
+                DbOperations::add_tracks_to_queue(db, queue_id, &track_ids)?;
+                queue_id
+            }
+        };
+
+        if shuffle {
+            // No current track: everything is shuffled and the original order is kept
+            // so turning shuffle off restores the album order
+            DbOperations::toggle_queue_shuffle(db, queue_id, None)?;
+            DbOperations::set_queue_shuffle_anchor(db, queue_id, 0)?;
+        }
+
+        let first_track = DbOperations::get_queue_track_at_position(db, queue_id, 0)?;
+        println!("[Queue] Started queue '{}' (ID: {}) with {} tracks, shuffle: {}", name, queue_id, track_ids.len(), shuffle);
+
+        Ok(QueueStartResult {
+            queue_id,
+            first_track,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{test_db, test_track};
+
+    fn insert_track(
+        db: &DatabaseConnection,
+        path: &str,
+        artist: &str,
+        album: &str,
+        disc: Option<i32>,
+        number: Option<i32>,
+        genre: &str,
+    ) -> i64 {
+        let track = Track {
+            artist: Some(artist.to_string()),
+            album: Some(album.to_string()),
+            track_number: number,
+            disc_number: disc,
+            duration_ms: Some(180_000),
+            genre: Some(genre.to_string()),
+            ..test_track(path)
+        };
+        let (track_id, _) = DbOperations::upsert_track_with_hash(db, &track, path).unwrap();
+        DbOperations::link_track_artists(db, track_id, &[artist.to_string()]).unwrap();
+        DbOperations::link_track_genres(db, track_id, &[genre.to_string()]).unwrap();
+        track_id
+    }
+
+    fn setup_library(db: &DatabaseConnection) -> Vec<i64> {
+        vec![
+            insert_track(db, "/m/b2-1.mp3", "Beta", "Second", Some(1), Some(1), "Rock"),
+            insert_track(db, "/m/a1-d2-1.mp3", "Alpha", "First", Some(2), Some(1), "Rock"),
+            insert_track(db, "/m/a1-d1-2.mp3", "Alpha", "First", Some(1), Some(2), "Rock"),
+            insert_track(db, "/m/a1-d1-1.mp3", "Alpha", "First", None, Some(1), "Rock"),
+            insert_track(db, "/m/a2-1.mp3", "Alpha", "Another", Some(1), Some(1), "Rock"),
+        ]
+    }
+
+    fn queue_paths(db: &DatabaseConnection, queue_id: i64) -> Vec<String> {
+        DbOperations::get_queue_tracks(db, queue_id)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.file_path)
+            .collect()
+    }
+
+    #[test]
+    fn test_artist_queue_ordered_by_album_disc_track() {
+        let db = test_db();
+        setup_library(&db);
+        let artist_id = DbOperations::insert_or_get_artist(&db, "Alpha").unwrap();
+
+        let result = QueueManager::create_queue_from_artist(&db, artist_id, false).unwrap();
+        assert_eq!(
+            queue_paths(&db, result.queue_id),
+            vec!["/m/a2-1.mp3", "/m/a1-d1-1.mp3", "/m/a1-d1-2.mp3", "/m/a1-d2-1.mp3"]
+        );
+        assert_eq!(result.first_track.unwrap().file_path, "/m/a2-1.mp3");
+        assert_eq!(DbOperations::get_active_queue(&db).unwrap().unwrap().id, result.queue_id);
+    }
+
+    #[test]
+    fn test_genre_queue_grouped_by_artist_then_album() {
+        let db = test_db();
+        setup_library(&db);
+        let genre_id = DbOperations::insert_or_get_genre(&db, "Rock").unwrap();
+
+        let result = QueueManager::create_queue_from_genre(&db, genre_id, false).unwrap();
+        assert_eq!(
+            queue_paths(&db, result.queue_id),
+            vec!["/m/a2-1.mp3", "/m/a1-d1-1.mp3", "/m/a1-d1-2.mp3", "/m/a1-d2-1.mp3", "/m/b2-1.mp3"]
+        );
+    }
+
+    #[test]
+    fn test_shuffled_queue_keeps_every_track() {
+        let db = test_db();
+        let mut track_ids = setup_library(&db);
+        let genre_id = DbOperations::insert_or_get_genre(&db, "Rock").unwrap();
+
+        // Playing the same genre twice reuses its queue
+        let first = QueueManager::create_queue_from_genre(&db, genre_id, true).unwrap();
+        let result = QueueManager::create_queue_from_genre(&db, genre_id, true).unwrap();
+        assert_eq!(first.queue_id, result.queue_id);
+        assert_ne!(DbOperations::get_queue_shuffle_seed(&db, result.queue_id).unwrap(), 1);
+
+        let mut queued: Vec<i64> = DbOperations::get_queue_tracks(&db, result.queue_id)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.id)
+            .collect();
+        assert_eq!(queued.len(), track_ids.len());
+        assert_eq!(result.first_track.unwrap().id, queued[0]);
+        queued.sort();
+        track_ids.sort();
+        assert_eq!(queued, track_ids);
+    }
+
+    #[test]
+    fn test_group_by_album_insert() {
+        let db = test_db();
+        let ids = setup_library(&db);
+        let queue_id = DbOperations::create_queue(&db, "Mixed").unwrap();
+
+        // Interleaved albums: Second, First (disc 2), Another, First (disc 1, track 1)
+        let picked = vec![ids[0], ids[1], ids[4], ids[3]];
+        let grouped = QueueManager::order_for_insert(&db, &picked, true).unwrap();
+        DbOperations::append_tracks_to_queue(&db, queue_id, &grouped).unwrap();
+        assert_eq!(
+            queue_paths(&db, queue_id),
+            vec!["/m/a2-1.mp3", "/m/a1-d1-1.mp3", "/m/a1-d2-1.mp3", "/m/b2-1.mp3"]
+        );
+
+        let ungrouped = QueueManager::order_for_insert(&db, &picked, false).unwrap();
+        assert_eq!(ungrouped, picked);
+
+        // Two artists' "Hits" albums stay apart instead of interleaving by track number
+        let hits = vec![
+            insert_track(&db, "/m/x-hits-1.mp3", "Xeno", "Hits", None, Some(1), "Pop"),
+            insert_track(&db, "/m/y-hits-1.mp3", "Yarrow", "Hits", None, Some(1), "Pop"),
+            insert_track(&db, "/m/x-hits-2.mp3", "Xeno", "Hits", None, Some(2), "Pop"),
+            insert_track(&db, "/m/y-hits-2.mp3", "Yarrow", "Hits", None, Some(2), "Pop"),
+        ];
+        let grouped = QueueManager::order_for_insert(&db, &hits, true).unwrap();
+        assert_eq!(grouped, vec![hits[0], hits[2], hits[1], hits[3]]);
+    }
+
+    #[test]
+    fn test_play_next_inserts_after_current_track() {
+        let db = test_db();
+        let ids = setup_library(&db);
+
+        // No active queue: the playing track first, then the new ones
+        assert_eq!(QueueManager::play_next(&db, Some(ids[0]), &[ids[1]]).unwrap(), 2);
+        let queue_id = DbOperations::get_active_queue(&db).unwrap().unwrap().id;
+        assert_eq!(queue_paths(&db, queue_id), vec!["/m/b2-1.mp3", "/m/a1-d2-1.mp3"]);
+
+        // Shuffled: inserted after the current stored position, and after the same track
+        // in the original order so turning shuffle off keeps them next to it
+        DbOperations::append_tracks_to_queue(&db, queue_id, &[ids[2], ids[3]]).unwrap();
+        DbOperations::toggle_queue_shuffle(&db, queue_id, Some(ids[0])).unwrap();
+        DbOperations::update_queue_current_index(&db, queue_id, 2).unwrap();
+        let current = DbOperations::get_queue_track_at_position(&db, queue_id, 2).unwrap().unwrap();
+        assert_eq!(QueueManager::play_next(&db, None, &[ids[4]]).unwrap(), 5);
+        assert_eq!(DbOperations::get_queue_track_at_position(&db, queue_id, 3).unwrap().unwrap().id, ids[4]);
+        assert_eq!(DbOperations::get_queue_track_at_position(&db, queue_id, 2).unwrap().unwrap().id, current.id);
+
+        DbOperations::toggle_queue_shuffle(&db, queue_id, Some(current.id)).unwrap();
+        let unshuffled: Vec<i64> = DbOperations::get_queue_tracks(&db, queue_id).unwrap().iter().map(|t| t.id).collect();
+        let at = unshuffled.iter().position(|&id| id == current.id).unwrap();
+        assert_eq!(unshuffled[at + 1], ids[4]);
+        assert_eq!(unshuffled.len(), 5);
+    }
+}
@@ -0,0 +1,187 @@
+// Last.fm API client
+// Signed calls to the 2.0 web service (https://www.last.fm/api/authspec): parameters are
+// sorted by name, concatenated as name+value, followed by the secret and MD5-hashed.
+use md5::{Digest, Md5};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::db::models::Scrobble;
+use crate::settings::ScrobbleSettings;
+
+const API_URL: &str = "https://ws.audioscrobbler.com/2.0/";
+const AUTH_URL: &str = "https://www.last.fm/api/auth/";
+const REQUEST_TIMEOUT_SECS: u64 = 15;
+
+/// Most scrobbles Last.fm accepts in one track.scrobble call
+pub const MAX_SCROBBLE_BATCH: usize = 50;
+
+/// A request token and the page where the user authorizes it
+#[derive(Debug, Clone, Serialize)]
+pub struct LastfmAuthRequest {
+    pub token: String,
+    pub url: String,
+}
+
+pub struct LastfmClient {
+    api_key: String,
+    api_secret: String,
+    session_key: Option<String>,
+    http: reqwest::blocking::Client,
+}
+
+impl LastfmClient {
+    pub fn new(api_key: &str, api_secret: &str, session_key: Option<&str>) -> Result<Self, anyhow::Error> {
+        if api_key.is_empty() || api_secret.is_empty() {
+            return Err(anyhow::anyhow!("Last.fm API key and secret are not set"));
+        }
+
+        let http = reqwest::blocking::Client::builder()
+            .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+            .build()?;
+
+        Ok(Self {
+            api_key: api_key.to_string(),
+            api_secret: api_secret.to_string(),
+            session_key: session_key.map(str::to_string),
+            http,
+        })
+    }
+
+    /// Client for the configured account, None until credentials and a session exist
+    pub fn from_settings(settings: &ScrobbleSettings) -> Option<Self> {
+        let session_key = settings.session_key.as_deref()?;
+        Self::new(&settings.api_key, &settings.api_secret, Some(session_key)).ok()
+    }
+
+    /// api_sig for a set of parameters (format and callback are not signed)
+    pub fn sign(params: &BTreeMap<String, String>, api_secret: &str) -> String {
+        let mut hasher = Md5::new();
+        for (name, value) in params {
+            if name == "format" || name == "callback" {
+                continue;
+            }
+            hasher.update(name.as_bytes());
+            hasher.update(value.as_bytes());
+        }
+        hasher.update(api_secret.as_bytes());
+        hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// POST a signed call and return the JSON response, turning Last.fm errors into Err
+    fn call(&self, method: &str, mut params: BTreeMap<String, String>) -> Result<Value, anyhow::Error> {
+        params.insert("method".to_string(), method.to_string());
+        params.insert("api_key".to_string(), self.api_key.clone());
+        params.insert("api_sig".to_string(), Self::sign(&params, &self.api_secret));
+        params.insert("format".to_string(), "json".to_string());
+
+        let body = self.http.post(API_URL).form(&params).send()?.text()?;
+        let response: Value = serde_json::from_str(&body)?;
+        if let Some(code) = response.get("error") {
+            let message = response.get("message").and_then(Value::as_str).unwrap_or("unknown error");
+            return Err(anyhow::anyhow!("Last.fm error {}: {}", code, message));
+        }
+        Ok(response)
+    }
+
+    /// Parameters of an authenticated call
+    fn session_params(&self) -> Result<BTreeMap<String, String>, anyhow::Error> {
+        let session_key = self.session_key.clone()
+            .ok_or_else(|| anyhow::anyhow!("Not logged in to Last.fm"))?;
+        Ok(BTreeMap::from([("sk".to_string(), session_key)]))
+    }
+
+    /// Request token to have the user authorize, step one of desktop auth
+    pub fn get_token(&self) -> Result<String, anyhow::Error> {
+        let response = self.call("auth.getToken", BTreeMap::new())?;
+        response.get("token")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| anyhow::anyhow!("No token in Last.fm response"))
+    }
+
+    /// Page where the user grants access for `token`
+    pub fn auth_url(&self, token: &str) -> String {
+        format!("{}?api_key={}&token={}", AUTH_URL, self.api_key, token)
+    }
+
+    /// Start desktop authorization: a fresh token plus its authorization page
+    pub fn auth_request(&self) -> Result<LastfmAuthRequest, anyhow::Error> {
+        let token = self.get_token()?;
+        let url = self.auth_url(&token);
+        Ok(LastfmAuthRequest { token, url })
+    }
+
+    /// Exchange an authorized token for a session, returns (session key, user name)
+    pub fn get_session(&self, token: &str) -> Result<(String, String), anyhow::Error> {
+        let params = BTreeMap::from([("token".to_string(), token.to_string())]);
+        let response = self.call("auth.getSession", params)?;
+        let session = response.get("session")
+            .ok_or_else(|| anyhow::anyhow!("No session in Last.fm response"))?;
+        let key = session.get("key").and_then(Value::as_str)
+            .ok_or_else(|| anyhow::anyhow!("No session key in Last.fm response"))?;
+        let name = session.get("name").and_then(Value::as_str).unwrap_or_default();
+        Ok((key.to_string(), name.to_string()))
+    }
+
+    pub fn update_now_playing(&self, scrobble: &Scrobble) -> Result<(), anyhow::Error> {
+        let mut params = self.session_params()?;
+        params.insert("artist".to_string(), scrobble.artist.clone());
+        params.insert("track".to_string(), scrobble.title.clone());
+        if let Some(ref album) = scrobble.album {
+            params.insert("album".to_string(), album.clone());
+        }
+        if let Some(ref album_artist) = scrobble.album_artist {
+            params.insert("albumArtist".to_string(), album_artist.clone());
+        }
+        if let Some(duration_ms) = scrobble.duration_ms {
+            params.insert("duration".to_string(), (duration_ms / 1000).to_string());
+        }
+        self.call("track.updateNowPlaying", params)?;
+        Ok(())
+    }
+
+    /// Submit up to MAX_SCROBBLE_BATCH plays. Plays Last.fm ignores (e.g. too old) still
+    /// count as submitted; only a failed request is an error.
+    pub fn scrobble(&self, scrobbles: &[Scrobble]) -> Result<(), anyhow::Error> {
+        if scrobbles.len() > MAX_SCROBBLE_BATCH {
+            return Err(anyhow::anyhow!("At most {} scrobbles per request", MAX_SCROBBLE_BATCH));
+        }
+
+        let mut params = self.session_params()?;
+        for (i, scrobble) in scrobbles.iter().enumerate() {
+            params.insert(format!("artist[{}]", i), scrobble.artist.clone());
+            params.insert(format!("track[{}]", i), scrobble.title.clone());
+            params.insert(format!("timestamp[{}]", i), scrobble.timestamp.to_string());
+            if let Some(ref album) = scrobble.album {
+                params.insert(format!("album[{}]", i), album.clone());
+            }
+            if let Some(ref album_artist) = scrobble.album_artist {
+                params.insert(format!("albumArtist[{}]", i), album_artist.clone());
+            }
+            if let Some(duration_ms) = scrobble.duration_ms {
+                params.insert(format!("duration[{}]", i), (duration_ms / 1000).to_string());
+            }
+        }
+        self.call("track.scrobble", params)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature_sorts_params_and_skips_format() {
+        let params = BTreeMap::from([
+            ("token".to_string(), "abc".to_string()),
+            ("api_key".to_string(), "key".to_string()),
+            ("method".to_string(), "auth.getSession".to_string()),
+            ("format".to_string(), "json".to_string()),
+        ]);
+        // md5("api_keykeymethodauth.getSessiontokenabcsecret")
+        assert_eq!(LastfmClient::sign(&params, "secret"), "6629efc98b97f7c35ff32314185ffaa1");
+    }
+}
@@ -0,0 +1,261 @@
+// Scrobbling module
+// Reports plays to Last.fm from a background thread that watches the player.
+// Plays are queued in pending_scrobbles first, so anything that fails to send
+// (offline, Last.fm down) is retried later, including on the next launch.
+pub mod lastfm;
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::audio::player::Player;
+use crate::db::connection::DatabaseConnection;
+use crate::db::models::{Scrobble, Track};
+use crate::db::operations::DbOperations;
+use crate::settings::ScrobbleSettings;
+use lastfm::{LastfmClient, MAX_SCROBBLE_BATCH};
+
+/// Tracks shorter than this are never scrobbled (Last.fm rule)
+pub const MIN_SCROBBLE_DURATION_MS: i64 = 30_000;
+/// A track counts as played after half its length or four minutes, whichever comes first
+pub const MAX_SCROBBLE_THRESHOLD_MS: i64 = 240_000;
+/// How often the monitor samples the player
+const POLL_INTERVAL_MS: u64 = 1000;
+/// Position jumps larger than the wall-clock time since the last sample (plus this slack)
+/// are seeks and don't count as listening
+const SEEK_SLACK_MS: i64 = 1000;
+
+/// Listening time after which a track of `duration_ms` is scrobbled, None if too short
+pub fn scrobble_threshold_ms(duration_ms: i64) -> Option<i64> {
+    if duration_ms < MIN_SCROBBLE_DURATION_MS {
+        return None;
+    }
+    Some((duration_ms / 2).min(MAX_SCROBBLE_THRESHOLD_MS))
+}
+
+/// What the monitor should report for the latest player sample
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScrobbleEvent {
+    /// A new play of `file` started at `timestamp` (unix seconds)
+    Started { file: String, timestamp: i64 },
+    /// The play of `file` that started at `timestamp` passed the scrobble threshold
+    Reached { file: String, timestamp: i64 },
+}
+
+struct CurrentPlay {
+    file: String,
+    started_at: i64,
+    last_position_ms: i64,
+    listened_ms: i64,
+    scrobbled: bool,
+}
+
+/// Turns periodic player samples into play events. Only time actually listened counts
+/// towards the threshold, so seeking to the end of a track doesn't scrobble it.
+#[derive(Default)]
+pub struct ScrobbleTracker {
+    current: Option<CurrentPlay>,
+}
+
+impl ScrobbleTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed one sample: the playing file (None when stopped), its position and length,
+    /// the wall-clock time since the previous sample and the current unix time
+    pub fn update(
+        &mut self,
+        file: Option<&str>,
+        position_ms: i64,
+        duration_ms: i64,
+        elapsed_ms: i64,
+        now: i64,
+    ) -> Option<ScrobbleEvent> {
+        let Some(file) = file else {
+            self.current = None;
+            return None;
+        };
+
+        // A different file, or the same one restarted after it was scrobbled (repeat), is a new play
+        let restarted = self.current.as_ref().is_some_and(|play| {
+            play.file != file || (play.scrobbled && position_ms < play.last_position_ms && position_ms <= elapsed_ms + SEEK_SLACK_MS)
+        });
+        if self.current.is_none() || restarted {
+            self.current = Some(CurrentPlay {
+                file: file.to_string(),
+                started_at: now - position_ms / 1000,
+                last_position_ms: position_ms,
+                listened_ms: 0,
+                scrobbled: false,
+            });
+            return Some(ScrobbleEvent::Started { file: file.to_string(), timestamp: now - position_ms / 1000 });
+        }
+
+        let play = self.current.as_mut()?;
+        let delta = position_ms - play.last_position_ms;
+        if delta > 0 && delta <= elapsed_ms + SEEK_SLACK_MS {
+            play.listened_ms += delta;
+        }
+        play.last_position_ms = position_ms;
+
+        let threshold = scrobble_threshold_ms(duration_ms)?;
+        if !play.scrobbled && play.listened_ms >= threshold {
+            play.scrobbled = true;
+            return Some(ScrobbleEvent::Reached { file: play.file.clone(), timestamp: play.started_at });
+        }
+        None
+    }
+}
+
+/// The scrobble for a library track, None if it has no artist (Last.fm requires one)
+fn scrobble_for(track: &Track, timestamp: i64) -> Option<Scrobble> {
+    let artist = track.artist.clone().filter(|a| !a.trim().is_empty())?;
+    Some(Scrobble {
+        id: 0,
+        artist,
+        title: track.title.clone(),
+        album: track.album.clone(),
+        album_artist: track.album_artist.clone(),
+        duration_ms: track.duration_ms,
+        timestamp,
+    })
+}
+
+/// Send queued plays in batches, deleting each batch once Last.fm accepted it.
+/// Stops at the first failure and leaves the rest queued.
+pub fn flush_pending(db: &DatabaseConnection, client: &LastfmClient) -> Result<usize, anyhow::Error> {
+    let mut sent = 0;
+    loop {
+        let batch = DbOperations::get_pending_scrobbles(db, MAX_SCROBBLE_BATCH)?;
+        if batch.is_empty() {
+            return Ok(sent);
+        }
+        client.scrobble(&batch)?;
+        let ids: Vec<i64> = batch.iter().map(|s| s.id).collect();
+        sent += DbOperations::delete_pending_scrobbles(db, &ids)?;
+    }
+}
+
+fn flush_and_log(db: &DatabaseConnection, settings: &ScrobbleSettings) {
+    let Some(client) = LastfmClient::from_settings(settings) else {
+        return;
+    };
+    match flush_pending(db, &client) {
+        Ok(0) => {}
+        Ok(sent) => println!("[Scrobble] Sent {} scrobbles", sent),
+        Err(e) => eprintln!("[Scrobble] Failed to send scrobbles, will retry: {}", e),
+    }
+}
+
+/// Start the scrobble monitor thread. It samples the player once a second (holding its
+/// lock only to read the state), and does all database and network work on its own
+/// thread so neither playback nor commands wait on Last.fm.
+pub fn spawn_monitor<F>(player: Arc<Mutex<Player>>, db: DatabaseConnection, settings: F)
+where
+    F: Fn() -> Option<ScrobbleSettings> + Send + 'static,
+{
+    thread::spawn(move || {
+        // Retry whatever was queued during earlier sessions
+        if let Some(settings) = settings().filter(|s| s.scrobbling_enabled) {
+            flush_and_log(&db, &settings);
+        }
+
+        let mut tracker = ScrobbleTracker::new();
+        let mut current: Option<Scrobble> = None;
+        let mut last_sample = Instant::now();
+
+        loop {
+            thread::sleep(Duration::from_millis(POLL_INTERVAL_MS));
+
+            let Some(settings) = settings().filter(|s| s.scrobbling_enabled) else {
+                tracker = ScrobbleTracker::new();
+                current = None;
+                continue;
+            };
+
+            let state = match player.lock() {
+                Ok(player) => player.get_state(),
+                Err(_) => continue,
+            };
+            let elapsed_ms = last_sample.elapsed().as_millis() as i64;
+            last_sample = Instant::now();
+            let now = match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+                Ok(d) => d.as_secs() as i64,
+                Err(_) => continue,
+            };
+
+            let file = state.current_file.as_deref().filter(|_| state.is_playing);
+            match tracker.update(file, state.position_ms, state.duration_ms, elapsed_ms, now) {
+                Some(ScrobbleEvent::Started { file, timestamp }) => {
+                    current = match DbOperations::get_track_by_file_path(&db, &file) {
+                        Ok(track) => track.and_then(|t| scrobble_for(&t, timestamp)),
+                        Err(e) => {
+                            eprintln!("[Scrobble] Failed to look up {}: {}", file, e);
+                            None
+                        }
+                    };
+                    if let (Some(scrobble), Some(client)) = (&current, LastfmClient::from_settings(&settings)) {
+                        if let Err(e) = client.update_now_playing(scrobble) {
+                            eprintln!("[Scrobble] Failed to update now playing: {}", e);
+                        }
+                    }
+                }
+                Some(ScrobbleEvent::Reached { .. }) => {
+                    if let Some(scrobble) = current.take() {
+                        if let Err(e) = DbOperations::add_pending_scrobble(&db, &scrobble) {
+                            eprintln!("[Scrobble] Failed to queue scrobble: {}", e);
+                        }
+                        flush_and_log(&db, &settings);
+                    }
+                }
+                None => {}
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scrobble_threshold() {
+        assert_eq!(scrobble_threshold_ms(20_000), None);
+        assert_eq!(scrobble_threshold_ms(180_000), Some(90_000));
+        assert_eq!(scrobble_threshold_ms(600_000), Some(MAX_SCROBBLE_THRESHOLD_MS));
+    }
+
+    #[test]
+    fn test_tracker_scrobbles_listened_time_only() {
+        let mut tracker = ScrobbleTracker::new();
+        let started = tracker.update(Some("a.flac"), 0, 180_000, 1000, 1_000);
+        assert_eq!(started, Some(ScrobbleEvent::Started { file: "a.flac".to_string(), timestamp: 1_000 }));
+
+        // Seeking past the threshold doesn't count as listening
+        assert_eq!(tracker.update(Some("a.flac"), 170_000, 180_000, 1000, 1_001), None);
+
+        for i in 1..=10 {
+            assert_eq!(tracker.update(Some("a.flac"), 170_000 + i * 1000, 180_000, 1000, 1_001 + i), None);
+        }
+
+        // 90s of listening reaches the threshold of a 3 minute track
+        let mut tracker = ScrobbleTracker::new();
+        tracker.update(Some("a.flac"), 0, 180_000, 1000, 1_000);
+        for i in 1..=89 {
+            assert_eq!(tracker.update(Some("a.flac"), i * 1000, 180_000, 1000, 1_000 + i), None);
+        }
+        assert_eq!(
+            tracker.update(Some("a.flac"), 90_000, 180_000, 1000, 1_090),
+            Some(ScrobbleEvent::Reached { file: "a.flac".to_string(), timestamp: 1_000 })
+        );
+        // Only once per play
+        assert_eq!(tracker.update(Some("a.flac"), 91_000, 180_000, 1000, 1_091), None);
+
+        // Switching tracks starts a new play
+        assert!(matches!(
+            tracker.update(Some("b.flac"), 0, 180_000, 1000, 1_092),
+            Some(ScrobbleEvent::Started { .. })
+        ));
+    }
+}
@@ -1,4 +1,8 @@
 // Settings module - handles app settings persistence
+#[allow(clippy::module_inception)]
 mod settings;
 
-pub use settings::AppSettings;
+pub use settings::{
+    AppSettings, EqPreset, FadeSettings, InterfaceSettings, LibrarySettings, PlaybackSettings, ScrobbleSettings,
+    TabConfig, EQ_BAND_COUNT, MAX_CROSSFADE_MS, MAX_EQ_GAIN_DB,
+};
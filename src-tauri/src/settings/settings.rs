@@ -1,7 +1,11 @@
 // Settings management and persistence
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+/// Schema version written by this build, older files are migrated on load
+pub const CURRENT_SETTINGS_VERSION: i32 = 1;
 
 /// Tab visibility and order configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -85,26 +89,70 @@ impl Default for InterfaceSettings {
     fn default() -> Self {
         Self {
             theme: ThemeSettings::default(),
-            tabs: vec![
-                TabConfig { id: "queues".to_string(), label: "Queues".to_string(), visible: true, order: 0 },
-                TabConfig { id: "library".to_string(), label: "Library".to_string(), visible: true, order: 1 },
-                TabConfig { id: "playlists".to_string(), label: "Playlists".to_string(), visible: true, order: 2 },
-                TabConfig { id: "artists".to_string(), label: "Artists".to_string(), visible: true, order: 3 },
-                TabConfig { id: "albums".to_string(), label: "Albums".to_string(), visible: true, order: 4 },
-                TabConfig { id: "genres".to_string(), label: "Genres".to_string(), visible: true, order: 5 },
-            ],
+            tabs: Self::default_tabs(),
             quick_actions: vec![],
             behaviour: BehaviourSettings::default(),
         }
     }
 }
 
+impl InterfaceSettings {
+    /// The built-in tabs in their default order
+    pub fn default_tabs() -> Vec<TabConfig> {
+        vec![
+            TabConfig { id: "queues".to_string(), label: "Queues".to_string(), visible: true, order: 0 },
+            TabConfig { id: "library".to_string(), label: "Library".to_string(), visible: true, order: 1 },
+            TabConfig { id: "playlists".to_string(), label: "Playlists".to_string(), visible: true, order: 2 },
+            TabConfig { id: "artists".to_string(), label: "Artists".to_string(), visible: true, order: 3 },
+            TabConfig { id: "albums".to_string(), label: "Albums".to_string(), visible: true, order: 4 },
+            TabConfig { id: "genres".to_string(), label: "Genres".to_string(), visible: true, order: 5 },
+        ]
+    }
+
+    /// Check that tab ids are unique and non-empty and that orders are exactly 0..n-1
+    pub fn validate_tabs(tabs: &[TabConfig]) -> Result<(), String> {
+        let mut seen_ids = std::collections::HashSet::new();
+        let mut seen_orders = vec![false; tabs.len()];
+        
+        for tab in tabs {
+            if tab.id.is_empty() {
+                return Err("Tab id cannot be empty".to_string());
+            }
+            if !seen_ids.insert(tab.id.as_str()) {
+                return Err(format!("Duplicate tab id: {}", tab.id));
+            }
+            if tab.order < 0 || tab.order as usize >= tabs.len() {
+                return Err(format!("Tab order out of range for '{}': {}", tab.id, tab.order));
+            }
+            if std::mem::replace(&mut seen_orders[tab.order as usize], true) {
+                return Err(format!("Duplicate tab order: {}", tab.order));
+            }
+        }
+        
+        Ok(())
+    }
+
+    /// Validate and store a new tab configuration, kept sorted by order
+    pub fn set_tabs(&mut self, mut tabs: Vec<TabConfig>) -> Result<(), String> {
+        Self::validate_tabs(&tabs)?;
+        tabs.sort_by_key(|t| t.order);
+        self.tabs = tabs;
+        Ok(())
+    }
+}
+
 /// Fade settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FadeSettings {
     pub enabled: bool,
     pub fade_in_ms: i32,  // 0-2000ms
     pub fade_out_ms: i32, // 0-2000ms
+    #[serde(default = "default_fade_curve")]
+    pub curve: String, // "linear", "equal_power" or "log"
+}
+
+fn default_fade_curve() -> String {
+    "equal_power".to_string()
 }
 
 impl Default for FadeSettings {
@@ -113,6 +161,7 @@ impl Default for FadeSettings {
             enabled: false,
             fade_in_ms: 0,
             fade_out_ms: 0,
+            curve: default_fade_curve(),
         }
     }
 }
@@ -124,6 +173,16 @@ pub struct ReplayGainSettings {
     pub calculate_unanalyzed: bool,
     pub analyze_on_scan: bool,
     pub segments_per_minute: i32, // 1-60
+    /// Use the album gain instead of the track gain while an album plays in order
+    #[serde(default = "default_true")]
+    pub album_gain: bool,
+    /// Use REPLAYGAIN_* tags other tools wrote instead of analyzing tracks that have them
+    #[serde(default = "default_true")]
+    pub prefer_tags: bool,
+}
+
+fn default_true() -> bool {
+    true
 }
 
 impl Default for ReplayGainSettings {
@@ -133,18 +192,167 @@ impl Default for ReplayGainSettings {
             calculate_unanalyzed: true,
             analyze_on_scan: true,
             segments_per_minute: 10,
+            album_gain: true,
+            prefer_tags: true,
         }
     }
 }
 
+/// Number of equalizer bands (ISO octave bands, 31 Hz - 16 kHz)
+pub const EQ_BAND_COUNT: usize = 10;
+/// Largest boost/cut an equalizer band accepts
+pub const MAX_EQ_GAIN_DB: f32 = 12.0;
+
+/// Equalizer presets shipped with the app, by settings name
+const BUILTIN_EQ_PRESETS: [(&str, [f32; EQ_BAND_COUNT]); 5] = [
+    ("flat", [0.0; EQ_BAND_COUNT]),
+    ("bass_boost", [6.0, 5.0, 4.0, 2.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]),
+    ("treble", [0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 2.0, 4.0, 5.0, 6.0]),
+    ("vocal", [-2.0, -2.0, -1.0, 0.0, 2.0, 4.0, 4.0, 2.0, 0.0, -1.0]),
+    ("rock", [5.0, 4.0, 2.0, 0.0, -1.0, -1.0, 1.0, 3.0, 4.0, 5.0]),
+];
+
+/// Band gains of a built-in equalizer preset
+fn builtin_eq_preset(name: &str) -> Option<[f32; EQ_BAND_COUNT]> {
+    BUILTIN_EQ_PRESETS.iter().find(|(preset, _)| *preset == name).map(|(_, gains)| *gains)
+}
+
+/// Longest crossfade between queue tracks the settings accept
+pub const MAX_CROSSFADE_MS: i32 = 12_000;
+
+/// A user-saved equalizer curve
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EqPreset {
+    pub name: String,
+    /// Gain in dB for each band, lowest frequency first
+    pub gains: Vec<f32>,
+}
+
 /// Playback settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlaybackSettings {
     pub gapless: bool,
     pub fade: FadeSettings,
+    /// Crossfade length between queue tracks in ms, 0 = off. Ignored while gapless is on.
+    #[serde(default)]
+    pub crossfade_ms: i32,
     pub equalizer_enabled: bool,
     pub equalizer_preset: String,
     pub replay_gain: ReplayGainSettings,
+    /// Maximum number of saved (inactive) queues to keep, 0 = unlimited
+    #[serde(default = "default_max_saved_queues")]
+    pub max_saved_queues: i32,
+    /// Last player volume in dB, restored on launch
+    #[serde(default)]
+    pub volume_db: f32,
+    /// Custom equalizer presets saved by the user
+    #[serde(default)]
+    pub equalizer_presets: Vec<EqPreset>,
+    /// Band gains the equalizer currently uses, set by applying a preset
+    #[serde(default = "default_equalizer_gains")]
+    pub equalizer_gains: Vec<f32>,
+    /// Mix a filtered bit of each stereo channel into the other (headphones)
+    #[serde(default)]
+    pub crossfeed_enabled: bool,
+    /// Crossfeed strength used when enabled: "low", "medium" or "high"
+    #[serde(default = "default_crossfeed_level")]
+    pub crossfeed_level: String,
+    /// Output device name picked by the user, None = system default
+    #[serde(default)]
+    pub output_device: Option<String>,
+}
+
+fn default_crossfeed_level() -> String {
+    "medium".to_string()
+}
+
+fn default_equalizer_gains() -> Vec<f32> {
+    vec![0.0; EQ_BAND_COUNT]
+}
+
+/// Preset name for hand-tuned bands that aren't saved as a preset
+pub const CUSTOM_EQ_PRESET: &str = "custom";
+
+/// Check a full set of band gains and clamp them to ±MAX_EQ_GAIN_DB
+fn clamp_equalizer_gains(gains: &[f32]) -> Result<Vec<f32>, String> {
+    if gains.len() != EQ_BAND_COUNT {
+        return Err(format!("Expected {} band gains, got {}", EQ_BAND_COUNT, gains.len()));
+    }
+    if gains.iter().any(|g| !g.is_finite()) {
+        return Err("Band gains must be finite numbers".to_string());
+    }
+    Ok(gains.iter().map(|g| g.clamp(-MAX_EQ_GAIN_DB, MAX_EQ_GAIN_DB)).collect())
+}
+
+impl PlaybackSettings {
+    /// Save a custom equalizer preset, replacing any preset with the same name.
+    /// Gains are clamped to ±MAX_EQ_GAIN_DB.
+    pub fn save_equalizer_preset(&mut self, name: &str, gains: &[f32]) -> Result<(), String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Preset name cannot be empty".to_string());
+        }
+        if builtin_eq_preset(name).is_some() || name == CUSTOM_EQ_PRESET {
+            return Err(format!("\"{}\" is a built-in preset", name));
+        }
+
+        let preset = EqPreset {
+            name: name.to_string(),
+            gains: clamp_equalizer_gains(gains)?,
+        };
+        match self.equalizer_presets.iter_mut().find(|p| p.name == preset.name) {
+            Some(existing) => *existing = preset,
+            None => self.equalizer_presets.push(preset),
+        }
+        Ok(())
+    }
+
+    /// Delete a custom equalizer preset, returns false if there was none with that name.
+    /// If it was the selected preset, the equalizer falls back to flat.
+    pub fn delete_equalizer_preset(&mut self, name: &str) -> bool {
+        let before = self.equalizer_presets.len();
+        self.equalizer_presets.retain(|p| p.name != name);
+        let deleted = self.equalizer_presets.len() != before;
+
+        if deleted && self.equalizer_preset == name {
+            self.equalizer_preset = "flat".to_string();
+            self.equalizer_gains = default_equalizer_gains();
+        }
+        deleted
+    }
+
+    /// Select a preset (built-in, saved, or "custom" to keep the current bands) and load
+    /// its gains into the equalizer. Returns the gains now in use.
+    pub fn apply_equalizer_preset(&mut self, name: &str) -> Result<Vec<f32>, String> {
+        let gains = if let Some(gains) = builtin_eq_preset(name) {
+            gains.to_vec()
+        } else if name == CUSTOM_EQ_PRESET {
+            self.equalizer_gains.clone()
+        } else {
+            self.equalizer_presets
+                .iter()
+                .find(|p| p.name == name)
+                .map(|p| p.gains.clone())
+                .ok_or_else(|| format!("Equalizer preset not found: {}", name))?
+        };
+
+        self.equalizer_preset = name.to_string();
+        self.equalizer_gains = gains.clone();
+        Ok(gains)
+    }
+
+    /// Set the band gains directly, which switches to the "custom" preset.
+    /// Returns the clamped gains now in use.
+    pub fn set_equalizer_bands(&mut self, gains: &[f32]) -> Result<Vec<f32>, String> {
+        let gains = clamp_equalizer_gains(gains)?;
+        self.equalizer_preset = CUSTOM_EQ_PRESET.to_string();
+        self.equalizer_gains = gains.clone();
+        Ok(gains)
+    }
+}
+
+fn default_max_saved_queues() -> i32 {
+    50
 }
 
 impl Default for PlaybackSettings {
@@ -152,13 +360,53 @@ impl Default for PlaybackSettings {
         Self {
             gapless: false,
             fade: FadeSettings::default(),
+            crossfade_ms: 0,
             equalizer_enabled: false,
             equalizer_preset: "flat".to_string(),
             replay_gain: ReplayGainSettings::default(),
+            max_saved_queues: default_max_saved_queues(),
+            volume_db: 0.0,
+            equalizer_presets: Vec::new(),
+            equalizer_gains: default_equalizer_gains(),
+            crossfeed_enabled: false,
+            crossfeed_level: default_crossfeed_level(),
+            output_device: None,
         }
     }
 }
 
+/// Library scanning settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LibrarySettings {
+    /// Follow symlinked folders/files while scanning (slower on large link-heavy trees)
+    #[serde(default)]
+    pub follow_symlinks: bool,
+    /// Let sidecar metadata files override embedded tags instead of only filling gaps
+    #[serde(default)]
+    pub prefer_sidecar: bool,
+    /// Index identical copies of a file (same content hash) as one track with several locations
+    #[serde(default)]
+    pub dedup_by_hash: bool,
+}
+
+/// Last.fm scrobbling settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScrobbleSettings {
+    #[serde(default)]
+    pub scrobbling_enabled: bool,
+    /// Last.fm API account credentials (https://www.last.fm/api/account/create)
+    #[serde(default)]
+    pub api_key: String,
+    #[serde(default)]
+    pub api_secret: String,
+    /// Session key from authorizing the app, None until the user did
+    #[serde(default)]
+    pub session_key: Option<String>,
+    /// Last.fm user the session belongs to
+    #[serde(default)]
+    pub username: Option<String>,
+}
+
 /// Main application settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppSettings {
@@ -166,22 +414,28 @@ pub struct AppSettings {
     pub language: LanguageSettings,
     pub interface: InterfaceSettings,
     pub playback: PlaybackSettings,
+    #[serde(default)]
+    pub library: LibrarySettings,
+    #[serde(default)]
+    pub scrobble: ScrobbleSettings,
 }
 
 impl Default for AppSettings {
     fn default() -> Self {
         Self {
-            version: 1,
+            version: CURRENT_SETTINGS_VERSION,
             language: LanguageSettings::default(),
             interface: InterfaceSettings::default(),
             playback: PlaybackSettings::default(),
+            library: LibrarySettings::default(),
+            scrobble: ScrobbleSettings::default(),
         }
     }
 }
 
 impl AppSettings {
     /// Get the settings file path
-    pub fn get_settings_path(app_dir: &PathBuf) -> PathBuf {
+    pub fn get_settings_path(app_dir: &Path) -> PathBuf {
         app_dir.join("settings.json")
     }
 
@@ -197,13 +451,48 @@ impl AppSettings {
         let content = fs::read_to_string(&path)
             .map_err(|e| format!("Failed to read settings file: {}", e))?;
 
-        let settings: AppSettings = serde_json::from_str(&content)
+        let value: Value = serde_json::from_str(&content)
             .map_err(|e| format!("Failed to parse settings: {}", e))?;
+        let file_version = Self::version_of(&value);
+        let settings = Self::migrate(value)?;
+
+        if file_version < CURRENT_SETTINGS_VERSION {
+            eprintln!("[Settings] Migrated settings from version {} to {}", file_version, CURRENT_SETTINGS_VERSION);
+            if let Err(e) = settings.save(app_dir) {
+                eprintln!("[Settings] Failed to save migrated settings: {}", e);
+            }
+        }
 
         eprintln!("[Settings] Loaded settings from {:?}", path);
         Ok(settings)
     }
 
+    /// Schema version of a settings file, files from before versioning count as 0
+    fn version_of(value: &Value) -> i32 {
+        value.get("version").and_then(Value::as_i64).unwrap_or(0) as i32
+    }
+
+    /// Upgrade settings JSON of any older version to the current schema.
+    /// Fields the file doesn't have yet are filled from the defaults.
+    pub fn migrate(mut value: Value) -> Result<Self, String> {
+        if !value.is_object() {
+            return Err("Failed to parse settings: expected an object".to_string());
+        }
+
+        let file_version = Self::version_of(&value);
+        // Version-keyed transforms go here, each taking the JSON from version N to N+1.
+        // Version 0 (files from before versioning) only lacks fields, which the defaults fill in.
+
+        let defaults = serde_json::to_value(Self::default())
+            .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+        fill_missing(&mut value, &defaults);
+        if file_version < CURRENT_SETTINGS_VERSION {
+            value["version"] = Value::from(CURRENT_SETTINGS_VERSION);
+        }
+
+        serde_json::from_value(value).map_err(|e| format!("Failed to parse settings: {}", e))
+    }
+
     /// Save settings to file
     pub fn save(&self, app_dir: &PathBuf) -> Result<(), String> {
         // Ensure directory exists
@@ -221,3 +510,157 @@ impl AppSettings {
         Ok(())
     }
 }
+
+/// Copy every key of `defaults` that `value` lacks, recursing into nested objects.
+/// Values already present (including arrays) are kept as they are.
+fn fill_missing(value: &mut Value, defaults: &Value) {
+    if let (Value::Object(target), Value::Object(defaults)) = (value, defaults) {
+        for (key, default) in defaults {
+            match target.get_mut(key) {
+                Some(existing) => fill_missing(existing, default),
+                None => {
+                    target.insert(key.clone(), default.clone());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tab(id: &str, order: i32) -> TabConfig {
+        TabConfig { id: id.to_string(), label: id.to_string(), visible: true, order }
+    }
+
+    #[test]
+    fn test_default_tabs_are_valid() {
+        assert!(InterfaceSettings::validate_tabs(&InterfaceSettings::default_tabs()).is_ok());
+    }
+
+    #[test]
+    fn test_set_tabs_sorts_by_order() {
+        let mut interface = InterfaceSettings::default();
+        interface.set_tabs(vec![tab("albums", 1), tab("queues", 2), tab("library", 0)]).unwrap();
+        let ids: Vec<&str> = interface.tabs.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, vec!["library", "albums", "queues"]);
+    }
+
+    #[test]
+    fn test_rejects_duplicate_ids() {
+        let err = InterfaceSettings::validate_tabs(&[tab("queues", 0), tab("queues", 1)]).unwrap_err();
+        assert!(err.contains("Duplicate tab id"));
+    }
+
+    #[test]
+    fn test_rejects_out_of_range_and_gapped_orders() {
+        assert!(InterfaceSettings::validate_tabs(&[tab("queues", 0), tab("library", 2)]).is_err());
+        assert!(InterfaceSettings::validate_tabs(&[tab("queues", -1), tab("library", 0)]).is_err());
+        assert!(InterfaceSettings::validate_tabs(&[tab("queues", 1), tab("library", 1)]).is_err());
+        assert!(InterfaceSettings::validate_tabs(&[tab("", 0)]).is_err());
+
+        // A rejected config leaves the current tabs untouched
+        let mut interface = InterfaceSettings::default();
+        assert!(interface.set_tabs(vec![tab("queues", 5)]).is_err());
+        assert_eq!(interface.tabs.len(), InterfaceSettings::default_tabs().len());
+    }
+
+    #[test]
+    fn test_save_overwrite_and_delete_equalizer_preset() {
+        let mut playback = PlaybackSettings::default();
+        let mut gains = vec![0.0; EQ_BAND_COUNT];
+        gains[0] = 20.0;
+        playback.save_equalizer_preset("Bassy", &gains).unwrap();
+        assert_eq!(playback.equalizer_presets[0].gains[0], MAX_EQ_GAIN_DB);
+
+        // Same name overwrites instead of adding a second preset
+        gains[0] = 6.0;
+        playback.save_equalizer_preset("Bassy", &gains).unwrap();
+        assert_eq!(playback.equalizer_presets.len(), 1);
+        assert_eq!(playback.equalizer_presets[0].gains[0], 6.0);
+
+        assert!(playback.save_equalizer_preset("Short", &[1.0, 2.0]).is_err());
+        assert!(playback.save_equalizer_preset("flat", &gains).is_err());
+
+        assert!(playback.delete_equalizer_preset("Bassy"));
+        assert!(!playback.delete_equalizer_preset("Bassy"));
+        assert!(playback.equalizer_presets.is_empty());
+    }
+
+    #[test]
+    fn test_apply_equalizer_preset() {
+        let mut playback = PlaybackSettings::default();
+        let gains: Vec<f32> = (0..EQ_BAND_COUNT).map(|i| i as f32 - 5.0).collect();
+        playback.save_equalizer_preset("Tilt", &gains).unwrap();
+
+        assert_eq!(playback.apply_equalizer_preset("Tilt").unwrap(), gains);
+        assert_eq!(playback.equalizer_preset, "Tilt");
+        assert_eq!(playback.equalizer_gains, gains);
+        assert!(playback.apply_equalizer_preset("Missing").is_err());
+        assert_eq!(playback.equalizer_preset, "Tilt");
+
+        // Deleting the selected preset falls back to flat
+        playback.delete_equalizer_preset("Tilt");
+        assert_eq!(playback.equalizer_preset, "flat");
+        assert_eq!(playback.equalizer_gains, vec![0.0; EQ_BAND_COUNT]);
+    }
+
+    #[test]
+    fn test_builtin_and_custom_equalizer_bands() {
+        let mut playback = PlaybackSettings::default();
+        let rock = playback.apply_equalizer_preset("rock").unwrap();
+        assert_eq!(rock.len(), EQ_BAND_COUNT);
+        assert!(rock[0] > 0.0);
+        assert!(playback.save_equalizer_preset("rock", &rock).is_err());
+        assert!(playback.save_equalizer_preset(CUSTOM_EQ_PRESET, &rock).is_err());
+
+        let mut gains = vec![0.0; EQ_BAND_COUNT];
+        gains[9] = -30.0;
+        let applied = playback.set_equalizer_bands(&gains).unwrap();
+        assert_eq!(applied[9], -MAX_EQ_GAIN_DB);
+        assert_eq!(playback.equalizer_preset, CUSTOM_EQ_PRESET);
+        assert!(playback.set_equalizer_bands(&[0.0; 3]).is_err());
+
+        // Re-selecting "custom" keeps the hand-tuned bands
+        assert_eq!(playback.apply_equalizer_preset(CUSTOM_EQ_PRESET).unwrap(), applied);
+    }
+
+    #[test]
+    fn test_migrates_version_0_settings() {
+        let app_dir = std::env::temp_dir().join(format!("musicsloth_settings_migration_{}", std::process::id()));
+        fs::create_dir_all(&app_dir).unwrap();
+        let old = serde_json::json!({
+            "version": 0,
+            "language": { "language": "en" },
+            "interface": {
+                "theme": { "mode": "light", "accent_color": "#FF0000", "font_family": "serif" },
+                "tabs": [{ "id": "library", "label": "Library", "visible": true, "order": 0 }],
+                "quick_actions": []
+            },
+            "playback": {
+                "gapless": true,
+                "fade": { "enabled": false, "fade_in_ms": 0, "fade_out_ms": 0 },
+                "equalizer_enabled": false,
+                "equalizer_preset": "flat"
+            }
+        });
+        fs::write(AppSettings::get_settings_path(&app_dir), old.to_string()).unwrap();
+
+        let settings = AppSettings::load(&app_dir).unwrap();
+        assert_eq!(settings.version, CURRENT_SETTINGS_VERSION);
+        // Existing values survive, missing blocks get defaults
+        assert!(settings.playback.gapless);
+        assert_eq!(settings.interface.theme.mode, "light");
+        assert_eq!(settings.interface.tabs.len(), 1);
+        assert_eq!(settings.playback.replay_gain.segments_per_minute, ReplayGainSettings::default().segments_per_minute);
+        assert!(settings.playback.replay_gain.enabled);
+
+        // The upgraded file was written back
+        let saved: Value = serde_json::from_str(&fs::read_to_string(AppSettings::get_settings_path(&app_dir)).unwrap()).unwrap();
+        assert_eq!(saved["version"], CURRENT_SETTINGS_VERSION);
+        assert!(saved["playback"]["replay_gain"].is_object());
+
+        let _ = fs::remove_dir_all(&app_dir);
+    }
+}
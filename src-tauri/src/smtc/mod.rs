@@ -49,6 +49,8 @@ mod stub {
         }
     }
 
+    // Only the Windows implementation receives button presses
+    #[allow(dead_code)]
     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub enum SmtcButton {
         Play,
@@ -61,3 +63,16 @@ mod stub {
 
 #[cfg(not(windows))]
 pub use stub::*;
+
+impl SmtcButton {
+    /// Short name used in the "media-key" event payload and the per-button event names
+    pub fn name(self) -> &'static str {
+        match self {
+            SmtcButton::Play => "play",
+            SmtcButton::Pause => "pause",
+            SmtcButton::Stop => "stop",
+            SmtcButton::Next => "next",
+            SmtcButton::Previous => "previous",
+        }
+    }
+}
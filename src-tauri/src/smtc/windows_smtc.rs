@@ -2,10 +2,11 @@
 
 use std::path::Path;
 use std::sync::{Arc, Mutex};
-use windows::Foundation::TypedEventHandler;
+use windows::Foundation::{TimeSpan, TypedEventHandler};
 use windows::Media::{
     MediaPlaybackStatus, MediaPlaybackType, SystemMediaTransportControls,
     SystemMediaTransportControlsButton, SystemMediaTransportControlsButtonPressedEventArgs,
+    SystemMediaTransportControlsTimelineProperties,
 };
 use windows::Media::Playback::MediaPlayer;
 use windows::Storage::StorageFile;
@@ -196,15 +197,38 @@ impl SmtcManager {
             .map_err(|e| format!("Failed to set playback status: {}", e))
     }
     
-    /// Set timeline position (not directly supported by basic SMTC, but we track it)
+    /// Set timeline position so the media flyout shows a progress bar.
+    /// Unknown lengths (duration_ms <= 0) leave the timeline unset.
     pub fn set_timeline(
         &self,
-        _position_ms: i64,
-        _duration_ms: i64,
+        position_ms: i64,
+        duration_ms: i64,
     ) -> Result<(), String> {
-        // Basic SMTC doesn't support timeline display
-        // This would require using MediaPlaybackCommandManager with timeline
-        Ok(())
+        if duration_ms <= 0 {
+            return Ok(());
+        }
+        
+        // TimeSpan counts 100-nanosecond ticks
+        let to_time_span = |ms: i64| TimeSpan { Duration: ms * 10_000 };
+        let position_ms = position_ms.clamp(0, duration_ms);
+        
+        let timeline = SystemMediaTransportControlsTimelineProperties::new()
+            .map_err(|e| format!("Failed to create timeline properties: {}", e))?;
+        timeline.SetStartTime(to_time_span(0))
+            .map_err(|e| format!("Failed to set timeline start: {}", e))?;
+        timeline.SetEndTime(to_time_span(duration_ms))
+            .map_err(|e| format!("Failed to set timeline end: {}", e))?;
+        timeline.SetPosition(to_time_span(position_ms))
+            .map_err(|e| format!("Failed to set timeline position: {}", e))?;
+        timeline.SetMinSeekTime(to_time_span(0))
+            .map_err(|e| format!("Failed to set min seek time: {}", e))?;
+        timeline.SetMaxSeekTime(to_time_span(duration_ms))
+            .map_err(|e| format!("Failed to set max seek time: {}", e))?;
+        
+        self.smtc.SetPlaybackRate(1.0)
+            .map_err(|e| format!("Failed to set playback rate: {}", e))?;
+        self.smtc.UpdateTimelineProperties(&timeline)
+            .map_err(|e| format!("Failed to update timeline: {}", e))
     }
     
     /// Set callback for button presses
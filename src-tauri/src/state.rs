@@ -1,9 +1,14 @@
 // Application state management
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::path::PathBuf;
 
 use crate::audio::player::Player;
 use crate::db::connection::DatabaseConnection;
+use crate::error::{AppError, ErrorContext};
+use crate::debounce::TrackChangeDebouncer;
+use crate::library::watcher::LibraryWatcher;
+use crate::settings::AppSettings;
 use crate::smtc::SmtcManager;
 
 pub struct AppState {
@@ -11,15 +16,141 @@ pub struct AppState {
     pub db: DatabaseConnection,
     pub smtc: Arc<Mutex<Option<SmtcManager>>>,
     pub app_dir: PathBuf,
+    /// The current settings, kept in sync with settings.json by `update_settings`
+    settings: Arc<Mutex<AppSettings>>,
+    /// Set while the background loudness backfill is running
+    pub loudness_backfill_running: Arc<AtomicBool>,
+    /// Set to ask the background loudness backfill to stop
+    pub loudness_backfill_cancel: Arc<AtomicBool>,
+    /// Set to ask a running library scan to stop after the current file
+    pub scan_cancel: Arc<AtomicBool>,
+    /// Watches the scan paths for added/removed files, replaced when they change
+    pub library_watcher: Mutex<Option<LibraryWatcher>>,
+    /// Stop once the current track ends instead of advancing (cleared when it fires)
+    pub stop_after_current: Arc<AtomicBool>,
+    /// Bumped on every track change so per-track side effects can wait for skipping to settle
+    pub track_change: Arc<TrackChangeDebouncer>,
+}
+
+/// What should happen after checking whether the current track ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackEndOutcome {
+    /// The track is still playing
+    Playing,
+    /// The track ended, advance to the next one
+    Advance,
+    /// The track ended and "stop after current" was set, so playback stopped
+    StoppedAfterTrack,
 }
 
 impl AppState {
-    pub fn new(player: Player, db: DatabaseConnection, smtc: Option<SmtcManager>, app_dir: PathBuf) -> Self {
+    pub fn new(
+        player: Player,
+        db: DatabaseConnection,
+        smtc: Option<SmtcManager>,
+        app_dir: PathBuf,
+        settings: AppSettings,
+    ) -> Self {
         Self {
             player: Arc::new(Mutex::new(player)),
             db,
             smtc: Arc::new(Mutex::new(smtc)),
             app_dir,
+            settings: Arc::new(Mutex::new(settings)),
+            loudness_backfill_running: Arc::new(AtomicBool::new(false)),
+            loudness_backfill_cancel: Arc::new(AtomicBool::new(false)),
+            scan_cancel: Arc::new(AtomicBool::new(false)),
+            library_watcher: Mutex::new(None),
+            stop_after_current: Arc::new(AtomicBool::new(false)),
+            track_change: Arc::new(TrackChangeDebouncer::default()),
         }
     }
+
+    /// Snapshot of the current settings, without touching the settings file
+    pub fn settings(&self) -> Result<AppSettings, AppError> {
+        let settings = self.settings.lock().context("Lock error")?;
+        Ok(settings.clone())
+    }
+
+    /// Change the settings, save them and return whatever `change` returns.
+    /// If `change` or the save fails, the current settings are left as they were.
+    pub fn update_settings<T>(
+        &self,
+        change: impl FnOnce(&mut AppSettings) -> Result<T, AppError>,
+    ) -> Result<T, AppError> {
+        let mut settings = self.settings.lock().context("Lock error")?;
+        let mut updated = settings.clone();
+        let result = change(&mut updated)?;
+        updated.save(&self.app_dir).map_err(AppError::Io)?;
+        *settings = updated;
+        Ok(result)
+    }
+
+    /// Check (and consume) the player's track-ended signal, honouring "stop after current"
+    pub fn check_track_end(&self) -> Result<TrackEndOutcome, AppError> {
+        let player = self.player.lock().context("Lock error")?;
+        if !player.has_track_ended() {
+            return Ok(TrackEndOutcome::Playing);
+        }
+        
+        if self.stop_after_current.swap(false, Ordering::SeqCst) {
+            player.stop();
+            return Ok(TrackEndOutcome::StoppedAfterTrack);
+        }
+        
+        Ok(TrackEndOutcome::Advance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_db;
+
+    fn test_state() -> AppState {
+        let db = test_db();
+        let app_dir = std::env::temp_dir().join(format!("musicsloth_state_{}", std::process::id()));
+        AppState::new(Player::new(), db, None, app_dir, AppSettings::default())
+    }
+
+    #[test]
+    fn test_stop_after_current_prevents_advance() {
+        let state = test_state();
+        state.stop_after_current.store(true, Ordering::SeqCst);
+        assert_eq!(state.check_track_end().unwrap(), TrackEndOutcome::Playing);
+
+        // Drive the track to completion
+        state.player.lock().unwrap().mark_track_ended();
+        assert_eq!(state.check_track_end().unwrap(), TrackEndOutcome::StoppedAfterTrack);
+        assert!(!state.player.lock().unwrap().get_state().is_playing);
+
+        // The flag is one-shot: the next track advances normally
+        assert!(!state.stop_after_current.load(Ordering::SeqCst));
+        state.player.lock().unwrap().mark_track_ended();
+        assert_eq!(state.check_track_end().unwrap(), TrackEndOutcome::Advance);
+    }
+
+    #[test]
+    fn test_update_settings_persists_and_caches() {
+        let state = test_state();
+        let volume_db = state
+            .update_settings(|settings| {
+                settings.playback.volume_db = -6.0;
+                Ok(settings.playback.volume_db)
+            })
+            .unwrap();
+        assert_eq!(volume_db, -6.0);
+        assert_eq!(state.settings().unwrap().playback.volume_db, -6.0);
+        assert_eq!(AppSettings::load(&state.app_dir).unwrap().playback.volume_db, -6.0);
+
+        // A rejected change leaves the settings untouched
+        let result: Result<(), AppError> = state.update_settings(|settings| {
+            settings.playback.volume_db = 3.0;
+            Err(AppError::InvalidInput("rejected".to_string()))
+        });
+        assert!(result.is_err());
+        assert_eq!(state.settings().unwrap().playback.volume_db, -6.0);
+
+        let _ = std::fs::remove_dir_all(&state.app_dir);
+    }
 }
@@ -0,0 +1,95 @@
+// Fixtures shared by the unit tests: WAV files, temp folders, in-memory databases and tracks
+use std::path::{Path, PathBuf};
+
+use crate::db::connection::DatabaseConnection;
+use crate::db::models::Track;
+use crate::db::operations::DbOperations;
+use crate::path_utils::to_extended_length;
+
+/// Write a 16-bit mono WAV file with `frames` samples, each given by `sample(frame)`
+pub fn write_wav(path: &Path, sample_rate: u32, frames: u32, sample: impl Fn(u32) -> i16) {
+    let data_len = frames * 2;
+    let mut bytes = Vec::with_capacity(44 + data_len as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // mono
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len.to_le_bytes());
+    for frame in 0..frames {
+        bytes.extend_from_slice(&sample(frame).to_le_bytes());
+    }
+    std::fs::write(to_extended_length(path), bytes).unwrap();
+}
+
+/// Write `duration_ms` of silent 8 kHz WAV
+pub fn write_silent_wav(path: &Path, duration_ms: u32) {
+    write_wav(path, 8000, duration_ms * 8, |_| 0);
+}
+
+/// Write a short (100 ms) silent WAV file
+pub fn write_test_wav(path: &Path) {
+    write_silent_wav(path, 100);
+}
+
+/// An empty folder under the system temp dir, unique to the test run
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("musicsloth_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+pub fn test_db() -> DatabaseConnection {
+    DatabaseConnection::new(PathBuf::from(":memory:")).unwrap()
+}
+
+/// An untagged track titled after its path, for overriding with `..test_track(path)`
+pub fn test_track(file_path: &str) -> Track {
+    Track {
+        id: 0,
+        file_path: file_path.to_string(),
+        title: file_path.to_string(),
+        artist: None,
+        album: None,
+        album_artist: None,
+        year: None,
+        track_number: None,
+        disc_number: None,
+        duration_ms: None,
+        genre: None,
+        file_size: None,
+        file_format: None,
+        bitrate: None,
+        sample_rate: None,
+        date_added: 0,
+        date_modified: 0,
+        play_count: 0,
+        last_played: None,
+        file_hash: None,
+        normalization_gain_db: None,
+        play_time_seconds: 0,
+        has_audio: true,
+        track_total: None,
+        disc_total: None,
+        rating: 0,
+        is_favorite: false,
+        composer: None,
+        comment: None,
+        bpm: None,
+        compilation: false,
+        skip_count: 0,
+    }
+}
+
+/// Add a three-minute track at `file_path` (also used as its hash), returning its id
+pub fn insert_test_track(db: &DatabaseConnection, file_path: &str) -> i64 {
+    let track = Track { duration_ms: Some(180_000), ..test_track(file_path) };
+    DbOperations::upsert_track_with_hash(db, &track, file_path).unwrap().0
+}
@@ -20,7 +20,7 @@ import {
   Delete,
 } from "@mui/icons-material";
 import { listen } from "@tauri-apps/api/event";
-import { libraryApi, IndexingResult, ScanPath } from "../services/api";
+import { libraryApi, IndexingResult, ScanPath, errorMessage } from "../services/api";
 
 interface ScanProgress {
   current: number;
@@ -101,7 +101,7 @@ export default function LibraryScanner({ onScanStart, onScanComplete }: LibraryS
       await libraryApi.removeScanPath(pathId);
       await loadScanPaths();
     } catch (error) {
-      alert(`Failed to remove scan path: ${error}`);
+      alert(`Failed to remove scan path: ${errorMessage(error)}`);
     } finally {
       setLoading(false);
     }
@@ -116,7 +116,7 @@ export default function LibraryScanner({ onScanStart, onScanComplete }: LibraryS
         await loadScanPaths();
       }
     } catch (error) {
-      alert(`Failed to add scan path: ${error}`);
+      alert(`Failed to add scan path: ${errorMessage(error)}`);
     } finally {
       setLoading(false);
     }
@@ -145,7 +145,7 @@ export default function LibraryScanner({ onScanStart, onScanComplete }: LibraryS
       sessionStorage.removeItem('isScanning');
       onScanComplete?.();
     } catch (error) {
-      alert(`Failed to scan library: ${error}`);
+      alert(`Failed to scan library: ${errorMessage(error)}`);
       sessionStorage.removeItem('isScanning');
       onScanComplete?.();
     } finally {
@@ -166,7 +166,7 @@ export default function LibraryScanner({ onScanStart, onScanComplete }: LibraryS
       // Reload page to refresh all views
       window.location.reload();
     } catch (error) {
-      alert(`Failed to clear library: ${error}`);
+      alert(`Failed to clear library: ${errorMessage(error)}`);
     } finally {
       setClearing(false);
     }
@@ -12,7 +12,7 @@ import {
   Typography,
 } from "@mui/material";
 import { ArrowBack, PlayArrow } from "@mui/icons-material";
-import { Track, playerApi } from "../services/api";
+import { Track, playerApi, errorMessage } from "../services/api";
 
 interface TrackListProps {
   tracks: Track[];
@@ -25,7 +25,7 @@ export default function TrackList({ tracks, onBack, title }: TrackListProps) {
     try {
       await playerApi.playFile(track.file_path, track.normalization_gain_db);
     } catch (error) {
-      alert(`Failed to play track: ${error}`);
+      alert(`Failed to play track: ${errorMessage(error)}`);
     }
   };
 
@@ -1,5 +1,5 @@
 import { useState, useEffect, useRef, useCallback, forwardRef, useImperativeHandle } from "react";
-import { libraryApi, Track, playerApi, queueApi, playlistApi } from "../services/api";
+import { libraryApi, Track, playerApi, queueApi, playlistApi, errorMessage } from "../services/api";
 import { usePlayer } from "../contexts/PlayerContext";
 import { Box, Avatar, Typography, TextField, Paper, List, ListItem, ListItemButton, ListItemText, InputAdornment, ClickAwayListener, Checkbox, Button, IconButton, useTheme } from "@mui/material";
 import MusicNoteIcon from "@mui/icons-material/MusicNote";
@@ -565,7 +565,7 @@ const VirtualTrackList = forwardRef<VirtualTrackListRef, VirtualTrackListProps>(
       }
     } catch (error) {
       console.error("Failed to play track:", error);
-      alert(`Failed to play track: ${error}`);
+      alert(`Failed to play track: ${errorMessage(error)}`);
     }
   };
 
@@ -5,6 +5,10 @@ import { smtcService } from "../services/smtcService";
 import { useSettings } from "../contexts/SettingsContext";
 import { getCurrentWindow } from "@tauri-apps/api/window";
 
+// Share of a track that must have played for it to count as played. Leaving it earlier
+// with next/previous counts as a skip instead.
+const PLAY_THRESHOLD = 0.5;
+
 interface PlayerContextType {
   currentTrack: Track | null;
   albumArt: string | null;
@@ -96,7 +100,25 @@ export function PlayerProvider({ children }: { children: ReactNode }) {
     }
   }, [settings.playback.gapless]);
 
-  const playNext = useCallback(async () => {
+  // Count a skip when the current track is left before the play threshold. A track that
+  // ended was already counted as played, so the backend ignores the skip then.
+  const recordSkipIfEarly = useCallback(async () => {
+    if (!currentTrack || !currentTrack.duration_ms) {
+      return;
+    }
+    if (audioPlayer.getState().position >= currentTrack.duration_ms * PLAY_THRESHOLD) {
+      return;
+    }
+    try {
+      await playlistApi.recordSkip(currentTrack.id);
+    } catch (error) {
+      console.error('Failed to record skip:', error);
+    }
+  }, [currentTrack]);
+
+  // Move to the next queue track. With `crossfade` the playing track fades out under it,
+  // unless "stop after current" is set, in which case nothing changes.
+  const advanceInQueue = useCallback(async (crossfade: boolean) => {
     if (currentQueueId === null || currentTrackIndex === null) {
       console.log('[PlayerContext] playNext - no active queue or track index');
       return;
@@ -116,6 +138,9 @@ export function PlayerProvider({ children }: { children: ReactNode }) {
       // Get track directly at position (shuffle is already applied to queue order)
       const nextTrack = await queueApi.getQueueTrackAtPosition(currentQueueId, nextIndex);
       if (nextTrack) {
+        if (crossfade && !(await playerApi.crossfadeTo(nextTrack.file_path, nextTrack.normalization_gain_db))) {
+          return;
+        }
         console.log(`[PlayerContext] playNext - playing track at index ${nextIndex}: ${nextTrack.title}`);
         await updateQueuePosition(currentQueueId, nextIndex);
         setCurrentTrack(nextTrack);
@@ -144,8 +169,10 @@ export function PlayerProvider({ children }: { children: ReactNode }) {
         // Update Media Session metadata immediately
         updateMediaSessionMetadata(nextTrack, artUrl);
 
-        // Play the track with ReplayGain normalization (if available)
-        await playerApi.playFile(nextTrack.file_path, nextTrack.normalization_gain_db);
+        if (!crossfade) {
+          // Play the track with ReplayGain normalization (if available)
+          await playerApi.playFile(nextTrack.file_path, nextTrack.normalization_gain_db);
+        }
         
         // Preload next track for gapless playback
         await preloadNextTrackForGapless(currentQueueId, nextIndex, isRepeating);
@@ -155,11 +182,21 @@ export function PlayerProvider({ children }: { children: ReactNode }) {
     }
   }, [currentQueueId, currentTrackIndex, updateQueuePosition, updateMediaSessionMetadata, preloadNextTrackForGapless, isRepeating]);
 
+  const playNext = useCallback(async () => {
+    if (currentQueueId === null || currentTrackIndex === null) {
+      console.log('[PlayerContext] playNext - no active queue or track index');
+      return;
+    }
+    await recordSkipIfEarly();
+    await advanceInQueue(false);
+  }, [currentQueueId, currentTrackIndex, recordSkipIfEarly, advanceInQueue]);
+
   const playPrevious = useCallback(async () => {
     if (currentQueueId === null || currentTrackIndex === null) {
       console.log('[PlayerContext] playPrevious - no active queue or track index');
       return;
     }
+    await recordSkipIfEarly();
 
     try {
       // Simply move to previous track in the queue (shuffle is handled by track order in DB)
@@ -212,7 +249,7 @@ export function PlayerProvider({ children }: { children: ReactNode }) {
     } catch (error) {
       console.error('Failed to play previous track:', error);
     }
-  }, [currentQueueId, currentTrackIndex, updateQueuePosition, updateMediaSessionMetadata, preloadNextTrackForGapless, isRepeating]);
+  }, [currentQueueId, currentTrackIndex, updateQueuePosition, updateMediaSessionMetadata, preloadNextTrackForGapless, isRepeating, recordSkipIfEarly]);
 
   const toggleShuffle = useCallback(async () => {
     if (currentQueueId === null || !currentTrack) {
@@ -431,6 +468,77 @@ export function PlayerProvider({ children }: { children: ReactNode }) {
     };
   }, [playNext, isRepeating, currentTrack, currentQueueId, currentTrackIndex, preloadNextTrackForGapless]);
 
+  // Count the play as soon as the current track passes the play threshold, so a track that is
+  // left after that counts as played even if it never ends. Seeking back below the threshold
+  // re-arms this; the backend counts each listen once.
+  const playRecordedForRef = useRef<string | null>(null);
+  useEffect(() => {
+    const unsubscribe = audioPlayer.onStateChange(async (state) => {
+      if (!currentTrack || state.currentFile !== currentTrack.file_path || state.duration <= 0) {
+        return;
+      }
+      if (state.position < state.duration * PLAY_THRESHOLD) {
+        playRecordedForRef.current = null;
+        return;
+      }
+      if (playRecordedForRef.current === state.currentFile) {
+        return;
+      }
+      playRecordedForRef.current = state.currentFile;
+
+      try {
+        await playlistApi.recordPlay(currentTrack.id);
+      } catch (error) {
+        console.error('Failed to record play:', error);
+      }
+    });
+
+    return () => {
+      unsubscribe();
+    };
+  }, [currentTrack]);
+
+  // Crossfade into the next queue track the crossfade length before the playing one ends.
+  // A track that is crossfaded out of never reports track-ended, so its play is counted here.
+  const crossfadeStartedForRef = useRef<string | null>(null);
+  useEffect(() => {
+    const crossfadeMs = settings.playback.crossfade_ms ?? 0;
+    if (crossfadeMs <= 0 || settings.playback.gapless || isRepeating) {
+      return;
+    }
+
+    const unsubscribe = audioPlayer.onStateChange(async (state) => {
+      if (!state.isPlaying || !state.currentFile || state.duration <= crossfadeMs) {
+        return;
+      }
+      if (state.duration - state.position > crossfadeMs) {
+        // Played from the start again, e.g. a one-track queue
+        if (crossfadeStartedForRef.current === state.currentFile) {
+          crossfadeStartedForRef.current = null;
+        }
+        return;
+      }
+      if (crossfadeStartedForRef.current === state.currentFile) {
+        return;
+      }
+      crossfadeStartedForRef.current = state.currentFile;
+
+      if (currentTrack && currentTrack.duration_ms) {
+        try {
+          await playlistApi.recordTrackPlay(currentTrack.id, Math.floor(currentTrack.duration_ms / 1000));
+        } catch (error) {
+          console.error('Failed to record track play time:', error);
+        }
+      }
+      console.log('[PlayerContext] Crossfading into next track');
+      await advanceInQueue(true);
+    });
+
+    return () => {
+      unsubscribe();
+    };
+  }, [settings.playback.crossfade_ms, settings.playback.gapless, isRepeating, currentTrack, advanceInQueue]);
+
   // Set up gapless transition listener
   useEffect(() => {
     const unsubscribe = audioPlayer.onGaplessTransition(async () => {
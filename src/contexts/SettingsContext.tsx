@@ -41,6 +41,8 @@ const defaultSettings: AppSettings = {
       calculate_unanalyzed: true,
       analyze_on_scan: true,
       segments_per_minute: 10,
+      album_gain: true,
+      prefer_tags: true,
     },
   },
 };
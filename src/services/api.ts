@@ -10,6 +10,20 @@ export interface PlayerState {
   duration_ms: number | null;
 }
 
+/** Error a backend command rejects with */
+export interface AppError {
+  kind: "io" | "db" | "decode" | "notFound" | "metadata" | "invalidInput" | "other";
+  message: string;
+}
+
+/** Readable message of a rejected command, or of anything else that was thrown */
+export function errorMessage(error: unknown): string {
+  if (typeof error === "object" && error !== null && "message" in error) {
+    return String((error as { message: unknown }).message);
+  }
+  return String(error);
+}
+
 // Player API now uses frontend audio player
 export const playerApi = {
   /**
@@ -24,6 +38,18 @@ export const playerApi = {
     await invoke("set_current_track", { filePath });
   },
 
+  /**
+   * Crossfade from the playing track into a file, using the crossfade length from the settings
+   * @returns false when "stop after current" is set and nothing was started
+   */
+  crossfadeTo: async (filePath: string, normalizationGainDb?: number | null): Promise<boolean> => {
+    const started = await backendPlayerApi.crossfadeTo(filePath, normalizationGainDb ?? undefined);
+    if (started) {
+      await invoke("set_current_track", { filePath });
+    }
+    return started;
+  },
+
   pause: async (): Promise<void> => {
     audioPlayer.pause();
   },
@@ -99,6 +125,18 @@ export interface Track {
   year: number | null;
   track_number: number | null;
   disc_number: number | null;
+  /** Tracks on the disc and discs in the release ("3 of 12", "disc 2 of 3") */
+  track_total: number | null;
+  disc_total: number | null;
+  rating: number;
+  is_favorite: boolean;
+  composer: string | null;
+  comment: string | null;
+  bpm: number | null;
+  /** Tagged as part of a compilation */
+  compilation?: boolean;
+  /** Times the track was skipped before it counted as played */
+  skip_count?: number;
   duration_ms: number | null;
   genre: string | null;
   file_size: number | null;
@@ -109,6 +147,8 @@ export interface Track {
   last_played: number | null;
   date_added: number;
   date_modified: number;
+  /** BLAKE3 hash of the file, used to spot duplicates and moved files */
+  file_hash?: string | null;
   /** ReplayGain normalization gain in dB (EBU R128). 
    * Positive = track quieter than -14 LUFS target, needs boost.
    * Negative = track louder than target, needs reduction. */
@@ -118,12 +158,51 @@ export interface Track {
   play_time_seconds: number;
 }
 
+export type TrackSort =
+  | "date_added_desc"
+  | "date_added_asc"
+  | "title_asc"
+  | "title_desc"
+  | "artist_asc"
+  | "album_asc"
+  | "play_count_desc"
+  | "last_played_desc";
+
+export type AlbumSort = "name_asc" | "year_desc" | "song_count_desc" | "artist_asc";
+
+export type ArtistSort = "name_asc" | "song_count_desc";
+
+export interface DirectoryListing {
+  path: string;
+  /** Full paths of the folders directly inside, sorted by name */
+  subdirectories: string[];
+  /** Tracks directly in the folder */
+  tracks: Track[];
+}
+
+export interface TrackPage {
+  tracks: Track[];
+  total_count: number;
+}
+
+export interface TrackEdits {
+  title?: string;
+  artist?: string;
+  album?: string;
+  album_artist?: string;
+  year?: number;
+  genre?: string;
+  track_number?: number;
+}
+
 export interface Album {
   id: number;
   name: string;
   artist: string | null;
   year: number | null;
   song_count: number;
+  /** Various-artists album (soundtrack, sampler) */
+  is_compilation: boolean;
 }
 
 export interface Artist {
@@ -145,20 +224,54 @@ export interface Queue {
   shuffle_seed: number; // 1 = sequential, other = shuffled
 }
 
+export interface ImportPlaylistResult {
+  playlist_id: number;
+  track_count: number;
+  missing_files: string[];
+}
+
 export interface Playlist {
   id: number;
   name: string;
   description?: string;
 }
 
+export interface SmartPlaylist {
+  id: number;
+  name: string;
+  rules: string; // SmartPlaylistRules as JSON
+}
+
+export type SmartRule =
+  | { field: string; operator: string; value?: number | string }
+  | SmartRuleGroup;
+
+export interface SmartRuleGroup {
+  match: "all" | "any";
+  rules: SmartRule[];
+}
+
+export interface SmartPlaylistRules extends SmartRuleGroup {
+  limit?: number;
+  sort?: TrackSort;
+}
+
 export interface IndexingResult {
   total_files: number;
   successful: number;
   failed: number;
   skipped: number;
+  unchanged: number;
   updated: number;
   removed: number;
   errors: string[];
+  aborted: boolean;
+}
+
+// Payload of the "library-changed" event, emitted when watched scan paths change on disk
+export interface LibraryChange {
+  updated: number;
+  removed: number;
 }
 
 export interface ScanPath {
@@ -166,6 +279,16 @@ export interface ScanPath {
   path: string;
   date_added: number;
   last_scanned: number | null;
+  /** Audio files found by the last scan that finished its cleanup */
+  file_count: number | null;
+}
+
+export interface LibraryStats {
+  track_count: number;
+  artist_count: number;
+  album_count: number;
+  total_duration_ms: number;
+  total_size_bytes: number;
 }
 
 export const libraryApi = {
@@ -173,6 +296,10 @@ export const libraryApi = {
     return await invoke("scan_library");
   },
 
+  cancelScan: async (): Promise<void> => {
+    return await invoke("cancel_scan");
+  },
+
   addScanPath: async (path: string): Promise<number> => {
     return await invoke("add_scan_path", { path });
   },
@@ -181,6 +308,10 @@ export const libraryApi = {
     return await invoke("get_all_scan_paths");
   },
 
+  getLibraryStats: async (): Promise<LibraryStats> => {
+    return await invoke("get_library_stats");
+  },
+
   removeScanPath: async (pathId: number): Promise<void> => {
     return await invoke("remove_scan_path", { pathId });
   },
@@ -193,34 +324,97 @@ export const libraryApi = {
     return await invoke("get_all_tracks");
   },
 
-  getAllAlbums: async (): Promise<Album[]> => {
-    return await invoke("get_all_albums");
+  getTracksPage: async (limit: number, offset: number, sort: TrackSort = "date_added_desc"): Promise<TrackPage> => {
+    return await invoke("get_tracks_page", { limit, offset, sort });
+  },
+
+  /** A track by id, null if it's no longer in the library */
+  getTrack: async (trackId: number): Promise<Track | null> => {
+    return await invoke("get_track", { trackId });
+  },
+
+  /** Subfolders and tracks of a library folder; either separator works */
+  browseDirectory: async (path: string): Promise<DirectoryListing> => {
+    return await invoke("browse_directory", { path });
   },
 
-  getAllArtists: async (): Promise<Artist[]> => {
-    return await invoke("get_all_artists");
+  getAllAlbums: async (sort?: AlbumSort): Promise<Album[]> => {
+    return await invoke("get_all_albums", { sort });
+  },
+
+  /** Albums marked as compilations */
+  getCompilationAlbums: async (): Promise<Album[]> => {
+    return await invoke("get_compilation_albums");
+  },
+
+  getAllArtists: async (sort?: ArtistSort): Promise<Artist[]> => {
+    return await invoke("get_all_artists", { sort });
   },
 
   getAllGenres: async (): Promise<Genre[]> => {
     return await invoke("get_all_genres");
   },
 
+  // [year, track count] pairs, newest first; year 0 is "Unknown"
+  getAllYears: async (): Promise<[number, number][]> => {
+    return await invoke("get_all_years");
+  },
+
   getTracksByArtist: async (artistId: number): Promise<Track[]> => {
     return await invoke("get_tracks_by_artist", { artistId });
   },
 
+  getTracksByAlbumArtist: async (artistId: number): Promise<Track[]> => {
+    return await invoke("get_tracks_by_album_artist", { artistId });
+  },
+
+  getTracksByComposer: async (composer: string): Promise<Track[]> => {
+    return await invoke("get_tracks_by_composer", { composer });
+  },
+
   getTracksByGenre: async (genreId: number): Promise<Track[]> => {
     return await invoke("get_tracks_by_genre", { genreId });
   },
 
+  getTracksByYear: async (year: number): Promise<Track[]> => {
+    return await invoke("get_tracks_by_year", { year });
+  },
+
+  getTracksByDecade: async (decadeStart: number): Promise<Track[]> => {
+    return await invoke("get_tracks_by_decade", { decadeStart });
+  },
+
   getTracksByAlbum: async (albumName: string): Promise<Track[]> => {
     return await invoke("get_tracks_by_album", { albumName });
   },
 
+  getTracksByAlbumAndArtist: async (albumName: string, artist: string | null): Promise<Track[]> => {
+    return await invoke("get_tracks_by_album_and_artist", { albumName, artist });
+  },
+
+  getTracksByAlbumId: async (albumId: number): Promise<Track[]> => {
+    return await invoke("get_tracks_by_album_id", { albumId });
+  },
+
   clearLibrary: async (): Promise<void> => {
     return await invoke("clear_library");
   },
 
+  // Remove one track from the library, queues and playlists, optionally deleting its file
+  deleteTrack: async (trackId: number, deleteFile: boolean): Promise<void> => {
+    return await invoke("delete_track", { trackId, deleteFile });
+  },
+
+  // Tracks with identical content at different paths
+  findDuplicateTracks: async (): Promise<Track[][]> => {
+    return await invoke("find_duplicate_tracks");
+  },
+
+  // Tracks that look like the same recording (title, artist and duration)
+  findDuplicateMetadata: async (): Promise<Track[][]> => {
+    return await invoke("find_duplicate_metadata");
+  },
+
   getCurrentTrack: async (): Promise<Track | null> => {
     return await invoke("get_current_track");
   },
@@ -229,9 +423,25 @@ export const libraryApi = {
     return await invoke("get_album_art", { filePath });
   },
 
+  // Cached cover file, load it with convertFileSrc
+  getAlbumArtPath: async (trackId: number): Promise<string | null> => {
+    return await invoke("get_album_art_path", { trackId });
+  },
+
+  // Cover for an album's tracks without embedded art, null goes back to folder art
+  setAlbumArtwork: async (albumId: number, imagePath: string | null): Promise<void> => {
+    return await invoke("set_album_artwork", { albumId, imagePath });
+  },
+
   getLyrics: async (filePath: string): Promise<string | null> => {
     return await invoke("get_lyrics", { filePath });
   },
+
+  /** Write tag edits into a track's file and return the re-indexed track.
+   * Omitted fields are left alone, an empty string (or 0) removes the field. */
+  updateTrackTags: async (trackId: number, edits: TrackEdits): Promise<Track> => {
+    return await invoke("update_track_tags", { trackId, edits });
+  },
 };
 
 export const queueApi = {
@@ -267,6 +477,10 @@ export const queueApi = {
     return await invoke("delete_queue", { queueId });
   },
 
+  clearQueue: async (queueId: number): Promise<void> => {
+    return await invoke("clear_queue", { queueId });
+  },
+
   updateQueueCurrentIndex: async (queueId: number, trackIndex: number): Promise<void> => {
     return await invoke("update_queue_current_index", { queueId, trackIndex });
   },
@@ -291,6 +505,16 @@ export const queueApi = {
     return await invoke("get_queue_length", { queueId });
   },
 
+  /** Summed duration of all queue tracks in ms (unknown durations count as 0) */
+  getQueueTotalDurationMs: async (queueId: number): Promise<number> => {
+    return await invoke("get_queue_total_duration_ms", { queueId });
+  },
+
+  /** Duration from a play-order position to the end of the queue in ms */
+  getQueueRemainingDurationMs: async (queueId: number, fromPosition: number): Promise<number> => {
+    return await invoke("get_queue_remaining_duration_ms", { queueId, fromPosition });
+  },
+
   toggleQueueShuffle: async (queueId: number, currentTrackId: number | null): Promise<[number, number]> => {
     return await invoke("toggle_queue_shuffle", { queueId, currentTrackId });
   },
@@ -311,16 +535,33 @@ export const queueApi = {
     return await invoke("get_queue_shuffle_anchor", { queueId });
   },
 
+  setQueueRepeatMode: async (queueId: number, repeatMode: "off" | "all" | "one"): Promise<void> => {
+    return await invoke("set_queue_repeat_mode", { queueId, repeatMode });
+  },
+
+  getQueueRepeatMode: async (queueId: number): Promise<"off" | "all" | "one"> => {
+    return await invoke("get_queue_repeat_mode", { queueId });
+  },
+
+  getNextQueuePosition: async (queueId: number, currentIndex: number): Promise<number | null> => {
+    return await invoke("get_next_queue_position", { queueId, currentIndex });
+  },
+
   findShuffledPosition: async (originalIndex: number, seed: number, queueLength: number, anchorPosition: number): Promise<number> => {
     return await invoke("find_shuffled_position", { originalIndex, seed, queueLength, anchorPosition });
   },
 
-  appendTracksToQueue: async (queueId: number, trackIds: number[]): Promise<void> => {
-    return await invoke("append_tracks_to_queue", { queueId, trackIds });
+  appendTracksToQueue: async (queueId: number, trackIds: number[], groupByAlbum = false): Promise<void> => {
+    return await invoke("append_tracks_to_queue", { queueId, trackIds, groupByAlbum });
   },
 
-  insertTracksAfterPosition: async (queueId: number, trackIds: number[], afterPosition: number): Promise<void> => {
-    return await invoke("insert_tracks_after_position", { queueId, trackIds, afterPosition });
+  insertTracksAfterPosition: async (queueId: number, trackIds: number[], afterPosition: number, groupByAlbum = false): Promise<void> => {
+    return await invoke("insert_tracks_after_position", { queueId, trackIds, afterPosition, groupByAlbum });
+  },
+
+  // Insert after the playing track of the active queue, returns the new queue length
+  playNext: async (trackIds: number[]): Promise<number> => {
+    return await invoke("play_next", { trackIds });
   },
 
   removeTrackAtPosition: async (queueId: number, position: number): Promise<number> => {
@@ -345,10 +586,37 @@ export const playlistApi = {
     return await invoke("get_unplayed_tracks");
   },
 
+  /** Tracks skipped more often than they're played */
+  getNeverFinishedTracks: async (): Promise<Track[]> => {
+    return await invoke("get_never_finished_tracks");
+  },
+
+  getFavoriteTracks: async (): Promise<Track[]> => {
+    return await invoke("get_favorite_tracks");
+  },
+
   recordTrackPlay: async (trackId: number, durationSeconds: number): Promise<void> => {
     return await invoke("record_track_play", { trackId, durationSeconds });
   },
 
+  /** Count a play once the track passes the play threshold. Its recordTrackPlay then only adds the play time. */
+  recordPlay: async (trackId: number): Promise<void> => {
+    return await invoke("record_play", { trackId });
+  },
+
+  /** Count a skip, for a track left before the play threshold. A listen counts as a play or a skip, not both. */
+  recordSkip: async (trackId: number): Promise<void> => {
+    return await invoke("record_skip", { trackId });
+  },
+
+  setTrackRating: async (trackId: number, rating: number): Promise<void> => {
+    return await invoke("set_track_rating", { trackId, rating });
+  },
+
+  toggleFavorite: async (trackId: number): Promise<boolean> => {
+    return await invoke("toggle_favorite", { trackId });
+  },
+
   getAllPlaylists: async (): Promise<Playlist[]> => {
     return await invoke("get_all_playlists");
   },
@@ -369,14 +637,42 @@ export const playlistApi = {
     return await invoke("get_playlist_tracks", { playlistId });
   },
 
+  importPlaylistM3u8: async (path: string, playlistName: string): Promise<ImportPlaylistResult> => {
+    return await invoke("import_playlist_m3u8", { path, playlistName });
+  },
+
+  exportPlaylistM3u8: async (playlistId: number, outPath: string): Promise<void> => {
+    return await invoke("export_playlist_m3u8", { playlistId, outPath });
+  },
+
   removeTrackFromPlaylist: async (playlistId: number, trackId: number): Promise<void> => {
     return await invoke("remove_track_from_playlist", { playlistId, trackId });
   },
 
+  removePlaylistTrackAtPosition: async (playlistId: number, position: number): Promise<void> => {
+    return await invoke("remove_playlist_track_at_position", { playlistId, position });
+  },
+
   deletePlaylist: async (playlistId: number): Promise<void> => {
     return await invoke("delete_playlist", { playlistId });
   },
 
+  createSmartPlaylist: async (name: string, rules: SmartPlaylistRules): Promise<number> => {
+    return await invoke("create_smart_playlist", { name, rulesJson: JSON.stringify(rules) });
+  },
+
+  getAllSmartPlaylists: async (): Promise<SmartPlaylist[]> => {
+    return await invoke("get_all_smart_playlists");
+  },
+
+  getSmartPlaylistTracks: async (smartPlaylistId: number): Promise<Track[]> => {
+    return await invoke("get_smart_playlist_tracks", { smartPlaylistId });
+  },
+
+  deleteSmartPlaylist: async (smartPlaylistId: number): Promise<void> => {
+    return await invoke("delete_smart_playlist", { smartPlaylistId });
+  },
+
   reorderPlaylistTrack: async (playlistId: number, fromPosition: number, toPosition: number): Promise<void> => {
     return await invoke("reorder_playlist_track", { playlistId, fromPosition, toPosition });
   },
@@ -412,6 +708,20 @@ export interface LoudnessAnalysisProgress {
   failed: number;
 }
 
+/** Album loudness analysis progress */
+export interface AlbumLoudnessProgress {
+  current: number;
+  total: number;
+  current_file: string;
+}
+
+/** Loudness of an album measured as a whole */
+export interface AlbumLoudness {
+  integrated_lufs: number;
+  peak_db: number;
+  gain_db: number;
+}
+
 /** Backend audio player API (using native Symphonia decoder) */
 export const backendPlayerApi = {
   /** Play a file with optional normalization gain */
@@ -422,6 +732,15 @@ export const backendPlayerApi = {
     });
   },
 
+  /** Crossfade from the playing track into a file, using the crossfade length from the settings.
+   *  Resolves to false when "stop after current" is set and nothing was started. */
+  crossfadeTo: async (filePath: string, normalizationGainDb?: number): Promise<boolean> => {
+    return await invoke("player_crossfade_to", {
+      filePath,
+      normalizationGainDb
+    });
+  },
+
   /** Pause playback */
   pause: async (): Promise<void> => {
     return await invoke("player_pause");
@@ -457,6 +776,21 @@ export const backendPlayerApi = {
     return await invoke("player_get_state");
   },
 
+  /** Get the position of the audio currently playing, in milliseconds */
+  getPositionMs: async (): Promise<number> => {
+    return await invoke("get_playback_position_ms");
+  },
+
+  /** List the available audio output devices */
+  listDevices: async (): Promise<string[]> => {
+    return await invoke("list_audio_devices");
+  },
+
+  /** Select the output device (null = system default), returns the device actually used */
+  setDevice: async (name: string | null): Promise<string | null> => {
+    return await invoke("set_audio_device", { name });
+  },
+
   /** Check if track has ended */
   hasTrackEnded: async (): Promise<boolean> => {
     return await invoke("player_has_track_ended");
@@ -486,6 +820,12 @@ export const loudnessApi = {
   analyzeLibrary: async (): Promise<[number, number]> => {
     return await invoke("analyze_library_loudness");
   },
+
+  /** Analyze an album as a whole and store its album gain.
+   * Emits 'album-loudness-progress' events during analysis. */
+  analyzeAlbum: async (albumId: number): Promise<AlbumLoudness> => {
+    return await invoke("analyze_album_loudness", { albumId });
+  },
 };
 
 // ============================================================================
@@ -532,14 +872,39 @@ export interface ReplayGainSettings {
   calculate_unanalyzed: boolean;
   analyze_on_scan: boolean;
   segments_per_minute: number;
+  album_gain: boolean;
+  prefer_tags: boolean;
 }
 
+/** A user-saved equalizer curve */
+export interface EqPreset {
+  name: string;
+  gains: number[];
+}
+
+/** Equalizer presets shipped with the app */
+export const BUILTIN_EQ_PRESETS = ["flat", "bass_boost", "treble", "vocal", "rock"];
+
 export interface PlaybackSettings {
   gapless: boolean;
   fade: FadeSettings;
+  /** Crossfade length between queue tracks in ms, 0 = off. Ignored while gapless is on. */
+  crossfade_ms?: number;
   equalizer_enabled: boolean;
+  /** A built-in preset, a saved preset name or "custom" */
   equalizer_preset: string;
   replay_gain: ReplayGainSettings;
+  equalizer_presets?: EqPreset[];
+  /** Gain in dB for each of the 10 bands (31 Hz - 16 kHz) */
+  equalizer_gains?: number[];
+}
+
+export interface ScrobbleSettings {
+  scrobbling_enabled: boolean;
+  api_key: string;
+  api_secret: string;
+  session_key: string | null;
+  username: string | null;
 }
 
 export interface AppSettings {
@@ -547,6 +912,12 @@ export interface AppSettings {
   language: LanguageSettings;
   interface: InterfaceSettings;
   playback: PlaybackSettings;
+  scrobble?: ScrobbleSettings;
+}
+
+export interface LastfmAuthRequest {
+  token: string;
+  url: string;
 }
 
 export const settingsApi = {
@@ -559,4 +930,34 @@ export const settingsApi = {
   saveSettings: async (settings: AppSettings): Promise<void> => {
     return await invoke("save_settings", { settings });
   },
+
+  /** Set the crossfade length between queue tracks in ms (0 = off, up to 12000) */
+  setCrossfadeMs: async (crossfadeMs: number): Promise<void> => {
+    return await invoke("set_crossfade_ms", { crossfadeMs });
+  },
+
+  /** Turn the equalizer on or off */
+  setEqualizerEnabled: async (enabled: boolean): Promise<void> => {
+    return await invoke("set_equalizer_enabled", { enabled });
+  },
+
+  /** Select an equalizer preset, returns the band gains now in use */
+  applyEqualizerPreset: async (name: string): Promise<number[]> => {
+    return await invoke("apply_equalizer_preset", { name });
+  },
+
+  /** Set all 10 band gains in dB (switches to the "custom" preset), returns the clamped gains */
+  setEqualizerBands: async (gains: number[]): Promise<number[]> => {
+    return await invoke("set_equalizer_bands", { gains });
+  },
+
+  /** Get a Last.fm request token and the page where the user authorizes it */
+  lastfmGetAuthUrl: async (): Promise<LastfmAuthRequest> => {
+    return await invoke("lastfm_get_auth_url");
+  },
+
+  /** Finish Last.fm authorization for an authorized token, returns the user name */
+  setLastfmSession: async (token: string): Promise<string> => {
+    return await invoke("set_lastfm_session", { token });
+  },
 };
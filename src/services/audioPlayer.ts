@@ -1,6 +1,7 @@
 // Frontend audio player - uses Rust backend via Tauri IPC
 // Symphonia decoding + cpal output in backend
 import { invoke } from '@tauri-apps/api/core';
+import { listen, UnlistenFn } from '@tauri-apps/api/event';
 
 interface PlayerState {
   isPlaying: boolean;
@@ -32,12 +33,32 @@ class AudioPlayer {
   private trackEndedCallbacks: Set<TrackEndedCallback> = new Set();
   private gaplessTransitionCallbacks: Set<GaplessTransitionCallback> = new Set();
   private pollInterval: number | null = null;
+  private unlistenTrackEnded: UnlistenFn | null = null;
   private lastState: PlayerState | null = null;
   private currentVolume: number = 1.0;
 
   constructor() {
     // Poll backend for state updates
     this.startPolling();
+    this.listenForTrackEnd();
+  }
+
+  /** The backend emits track-ended once when a track plays to its end (never on stop or seek) */
+  private listenForTrackEnd(): void {
+    listen<string>('track-ended', async (event) => {
+      try {
+        // Consumes the backend's end flag, and honours "stop after current"
+        const advance = await invoke<boolean>('player_has_track_ended');
+        if (advance) {
+          console.log('[AudioPlayer] Track ended:', event.payload);
+          this.notifyTrackEnded();
+        }
+      } catch (error) {
+        console.error('Failed to handle track end:', error);
+      }
+    })
+      .then(unlisten => { this.unlistenTrackEnded = unlisten; })
+      .catch(error => console.error('Failed to listen for track end:', error));
   }
 
   private startPolling(): void {
@@ -60,14 +81,6 @@ class AudioPlayer {
         volumeDb: backendState.volume_db,
       };
 
-      // Always check if track ended - don't rely on state transitions
-      // This is more robust against race conditions
-      const trackEnded = await invoke<boolean>('player_has_track_ended');
-      if (trackEnded) {
-        console.log('[AudioPlayer] Track ended signal received');
-        this.notifyTrackEnded();
-      }
-      
       // Check for gapless transition
       const gaplessTransition = await invoke<boolean>('player_has_gapless_transition');
       if (gaplessTransition) {
@@ -263,6 +276,7 @@ class AudioPlayer {
     if (this.pollInterval !== null) {
       clearInterval(this.pollInterval);
     }
+    this.unlistenTrackEnded?.();
     this.stateCallbacks.clear();
     this.trackEndedCallbacks.clear();
     this.gaplessTransitionCallbacks.clear();
@@ -105,6 +105,8 @@ export interface ReplayGainSettings {
   calculate_unanalyzed: boolean;
   analyze_on_scan: boolean;
   segments_per_minute: number; // 1-60
+  album_gain: boolean; // Album gain while an album plays in order
+  prefer_tags: boolean; // Use existing REPLAYGAIN_* tags instead of analyzing
 }
 
 export interface PlaybackSettings {
@@ -157,6 +159,8 @@ export const defaultSettings: AppSettings = {
       calculate_unanalyzed: true,
       analyze_on_scan: true,
       segments_per_minute: 10,
+      album_gain: true,
+      prefer_tags: true,
     },
   },
 };
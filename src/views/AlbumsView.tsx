@@ -77,7 +77,7 @@ function AlbumItem({ album, onClick }: AlbumItemProps) {
         const loadAlbumArt = async () => {
             try {
                 // Get first track of the album
-                const tracks = await libraryApi.getTracksByAlbum(album.name);
+                const tracks = await libraryApi.getTracksByAlbumId(album.id);
 
                 // Check if request was cancelled
                 if (currentController.signal.aborted) return;
@@ -247,7 +247,7 @@ export default function AlbumsView({ searchQuery = "", initialAlbumName, initial
     const handleAlbumClick = async (album: Album) => {
         setSelectedAlbum(album);
         try {
-            const tracks = await libraryApi.getTracksByAlbum(album.name);
+            const tracks = await libraryApi.getTracksByAlbumId(album.id);
             setAlbumTracks(tracks);
         } catch (error) {
             console.error("Failed to load album tracks:", error);
@@ -51,7 +51,7 @@ import {
 } from "@dnd-kit/sortable";
 import { CSS } from "@dnd-kit/utilities";
 import { useSettings } from "../contexts/SettingsContext";
-import { TabConfig, BehaviourSettings } from "../services/api";
+import { TabConfig, BehaviourSettings, BUILTIN_EQ_PRESETS, settingsApi } from "../services/api";
 import { MuiColorInput } from "mui-color-input";
 
 // Preset accent colors
@@ -144,6 +144,7 @@ export default function OptionsView() {
     updateFadeSettings,
     updateReplayGainSettings,
     updateBehaviourSettings,
+    reloadSettings,
   } = useSettings();
 
   const [expandedPanel, setExpandedPanel] = useState<string | false>("language");
@@ -486,9 +487,29 @@ export default function OptionsView() {
             label="Enable Equalizer"
             sx={{ mb: 2, display: "flex" }}
           />
-          <Typography variant="caption" color="text.secondary" sx={{ mb: 3, display: "block", ml: 6 }}>
-            Equalizer presets and customization will be available in a future update.
-          </Typography>
+          <FormControl fullWidth size="small" sx={{ mb: 3 }} disabled={!settings.playback.equalizer_enabled}>
+            <InputLabel>Preset</InputLabel>
+            <Select
+              value={settings.playback.equalizer_preset}
+              label="Preset"
+              onChange={async (e) => {
+                await settingsApi.applyEqualizerPreset(e.target.value);
+                await reloadSettings();
+              }}
+            >
+              {BUILTIN_EQ_PRESETS.map((name) => (
+                <MenuItem key={name} value={name}>
+                  {name.replace("_", " ").replace(/^\w/, (c) => c.toUpperCase())}
+                </MenuItem>
+              ))}
+              {(settings.playback.equalizer_presets ?? []).map((preset) => (
+                <MenuItem key={preset.name} value={preset.name}>
+                  {preset.name}
+                </MenuItem>
+              ))}
+              <MenuItem value="custom">Custom</MenuItem>
+            </Select>
+          </FormControl>
 
           <Divider sx={{ my: 3 }} />
 
@@ -529,6 +550,30 @@ export default function OptionsView() {
                 />
               }
               label="Analyze replay gain when scanning"
+              sx={{ mb: 2, display: "flex" }}
+            />
+
+            <FormControlLabel
+              control={
+                <Switch
+                  checked={settings.playback.replay_gain.prefer_tags}
+                  onChange={(e) => updateReplayGainSettings({ prefer_tags: e.target.checked })}
+                  disabled={!settings.playback.replay_gain.enabled}
+                />
+              }
+              label="Use existing ReplayGain tags instead of analyzing"
+              sx={{ mb: 2, display: "flex" }}
+            />
+
+            <FormControlLabel
+              control={
+                <Switch
+                  checked={settings.playback.replay_gain.album_gain}
+                  onChange={(e) => updateReplayGainSettings({ album_gain: e.target.checked })}
+                  disabled={!settings.playback.replay_gain.enabled}
+                />
+              }
+              label="Use album gain when playing albums in order"
               sx={{ mb: 3, display: "flex" }}
             />
 
@@ -1,5 +1,5 @@
 import { useState, useEffect, useCallback } from "react";
-import { libraryApi, playlistApi, Track, Playlist } from "../services/api";
+import { libraryApi, playlistApi, Track, Playlist, errorMessage } from "../services/api";
 import VirtualTrackList from "../components/VirtualTrackList";
 import PlaylistContextMenu from "../components/PlaylistContextMenu";
 import TextInputDialog from "../components/TextInputDialog";
@@ -190,7 +190,7 @@ export default function PlaylistsView({ searchQuery = "", onClearSearch, onNavig
       await loadUserPlaylists();
     } catch (error) {
       console.error("Failed to delete playlist:", error);
-      alert(`Failed to delete playlist: ${error}`);
+      alert(`Failed to delete playlist: ${errorMessage(error)}`);
     }
     setContextMenu(null);
     setContextMenuPlaylist(null);